@@ -0,0 +1,634 @@
+//! HTTP API for remote control (`flow serve --api`)
+//!
+//! A small HTTP/1.1 server so chat-ops tooling can drive Flow without a
+//! terminal. Every request must carry `Authorization: Bearer <token>`.
+//!
+//! | Endpoint | Action |
+//! |----------|--------|
+//! | `GET /status` | Whether a run is in progress, its PID, and `progress.json` |
+//! | `POST /run` | Start a run: `{"cycle": "coding", "max_iterations": 3}` (both optional) |
+//! | `GET /events` | Stream the lifecycle events of runs started here, as NDJSON |
+//! | `POST /stop` | Interrupt the run in progress, like Ctrl+C |
+//!
+//! Runs are separate `flow --emit-events` processes; their event lines are
+//! fanned out to `/events` subscribers. Status and stop go through the run
+//! lock and `progress.json`, so they also see a run started from a terminal.
+//! One request per connection.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{
+    AsyncBufReadExt as _, AsyncRead, AsyncReadExt as _, AsyncWriteExt as _, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, Mutex};
+use tokio_util::sync::CancellationToken;
+
+use crate::cycle::config::FlowConfig;
+use crate::log::lock::live_holder;
+use crate::log::progress::read_progress;
+
+/// Environment variable holding the bearer token clients must present
+pub const API_TOKEN_ENV: &str = "FLOW_API_TOKEN";
+
+/// Largest request head (request line plus headers) accepted, in bytes
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// Largest request body accepted, in bytes
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// How long a client may take to send its whole request
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Event lines buffered per `/events` subscriber before it starts missing some
+const EVENT_BUFFER: usize = 256;
+
+/// A parsed HTTP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// Method, e.g. `GET`
+    pub method: String,
+    /// Path without the query string, e.g. `/status`
+    pub path: String,
+    /// Headers with lowercased names, in order
+    pub headers: Vec<(String, String)>,
+    /// Body (empty without a `Content-Length`)
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Value of the first header named `name` (lowercase).
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A JSON response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// HTTP status code
+    pub status: u16,
+    /// JSON body
+    pub body: Value,
+}
+
+impl Response {
+    const fn json(status: u16, body: Value) -> Self {
+        Self { status, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, json!({ "error": message }))
+    }
+
+    /// Serialize as a complete HTTP/1.1 response.
+    #[must_use]
+    pub fn to_http(&self) -> String {
+        let body = self.body.to_string();
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.status,
+            reason_phrase(self.status),
+            body.len()
+        )
+    }
+}
+
+/// Body of `POST /run`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunRequest {
+    /// Cycle to run; the AI selector chooses when omitted
+    cycle: Option<String>,
+    /// Iterations to run (default: 1)
+    max_iterations: Option<u32>,
+}
+
+/// The API server's state, shared by all connections
+pub struct ApiServer {
+    token: String,
+    config_path: PathBuf,
+    log_dir: PathBuf,
+    /// Program started for `POST /run`
+    flow_bin: PathBuf,
+    /// Arguments passed to every run before the per-request ones
+    run_args: Vec<String>,
+    /// Event lines from runs started here
+    events: broadcast::Sender<String>,
+    /// The run this server started last
+    child: Mutex<Option<tokio::process::Child>>,
+}
+
+impl ApiServer {
+    /// Create a server accepting `token`, validating cycles against
+    /// `config_path` and reading run state from `log_dir`. Runs start the
+    /// current executable with `--config` and `--log-dir` set to match.
+    pub fn new(token: &str, config_path: &Path, log_dir: &Path) -> Result<Self> {
+        let flow_bin = std::env::current_exe().context("Failed to locate the flow executable")?;
+        let run_args = vec![
+            "--config".to_string(),
+            config_path.display().to_string(),
+            "--log-dir".to_string(),
+            log_dir.display().to_string(),
+        ];
+        Ok(Self {
+            token: token.to_string(),
+            config_path: config_path.to_path_buf(),
+            log_dir: log_dir.to_path_buf(),
+            flow_bin,
+            run_args,
+            events: broadcast::channel(EVENT_BUFFER).0,
+            child: Mutex::new(None),
+        })
+    }
+
+    /// Append arguments (e.g. `--profile`, `--set`) to every run started.
+    #[must_use]
+    pub fn with_run_args(mut self, args: impl IntoIterator<Item = String>) -> Self {
+        self.run_args.extend(args);
+        self
+    }
+
+    /// Start runs with this program instead of the current executable.
+    #[must_use]
+    pub fn with_flow_bin(mut self, flow_bin: &Path) -> Self {
+        self.flow_bin = flow_bin.to_path_buf();
+        self
+    }
+
+    /// Accept connections until `cancel` fires.
+    pub async fn serve(self, listener: TcpListener, cancel: CancellationToken) -> Result<()> {
+        let server = Arc::new(self);
+        loop {
+            let (stream, _) = tokio::select! {
+                () = cancel.cancelled() => return Ok(()),
+                accepted = listener.accept() => accepted.context("Failed to accept connection")?,
+            };
+            let server = Arc::clone(&server);
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(stream).await {
+                    eprintln!("API connection error: {e:#}");
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(&self, stream: TcpStream) -> Result<()> {
+        let mut stream = BufReader::new(stream);
+        let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await;
+        let response = match request {
+            Ok(Ok(request)) if !self.authorized(&request) => {
+                Response::error(401, "missing or wrong bearer token")
+            }
+            Ok(Ok(request)) if request.method == "GET" && request.path == "/events" => {
+                return self.stream_events(stream.into_inner()).await;
+            }
+            Ok(Ok(request)) => self.route(&request).await,
+            Ok(Err(e)) => Response::error(400, &format!("{e:#}")),
+            Err(_) => Response::error(408, "timed out waiting for the request"),
+        };
+        let mut stream = stream.into_inner();
+        stream.write_all(response.to_http().as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+
+    /// Whether the request carries this server's bearer token.
+    #[must_use]
+    pub fn authorized(&self, request: &Request) -> bool {
+        request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()))
+    }
+
+    /// Answer a request other than `GET /events`.
+    pub async fn route(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/status") => self.status(),
+            ("POST", "/run") => self.start_run(&request.body).await,
+            ("POST", "/stop") => self.stop(),
+            (_, "/status" | "/run" | "/stop" | "/events") => {
+                Response::error(405, "method not allowed")
+            }
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    fn status(&self) -> Response {
+        let holder = live_holder(&self.log_dir);
+        match read_progress(&self.log_dir) {
+            Ok(progress) => Response::json(
+                200,
+                json!({
+                    "running": holder.is_some(),
+                    "pid": holder.map(|h| h.pid),
+                    "progress": progress,
+                }),
+            ),
+            Err(e) => Response::error(500, &format!("{e:#}")),
+        }
+    }
+
+    async fn start_run(&self, body: &[u8]) -> Response {
+        let request: RunRequest = if body.iter().all(u8::is_ascii_whitespace) {
+            RunRequest::default()
+        } else {
+            match serde_json::from_slice(body) {
+                Ok(request) => request,
+                Err(e) => return Response::error(400, &format!("Invalid run request: {e}")),
+            }
+        };
+        if let Some(ref cycle) = request.cycle {
+            match FlowConfig::from_path(&self.config_path) {
+                Ok(config) if config.get_cycle(cycle).is_none() => {
+                    return Response::error(400, &format!("Unknown cycle '{cycle}'"));
+                }
+                Ok(_) => {}
+                Err(e) => return Response::error(500, &format!("{e:#}")),
+            }
+        }
+        if request.max_iterations == Some(0) {
+            return Response::error(400, "max_iterations must be greater than 0");
+        }
+
+        let mut child = self.child.lock().await;
+        let starting = child
+            .as_mut()
+            .is_some_and(|c| matches!(c.try_wait(), Ok(None)));
+        if starting || live_holder(&self.log_dir).is_some() {
+            return Response::error(409, "a run is already in progress");
+        }
+        let spawned = match self.spawn_run(&request) {
+            Ok(spawned) => spawned,
+            Err(e) => return Response::error(500, &format!("{e:#}")),
+        };
+        let pid = spawned.id();
+        *child = Some(spawned);
+        drop(child);
+        Response::json(202, json!({ "pid": pid }))
+    }
+
+    /// Start `flow --emit-events` and forward its event lines to subscribers.
+    fn spawn_run(&self, request: &RunRequest) -> Result<tokio::process::Child> {
+        let mut cmd = tokio::process::Command::new(&self.flow_bin);
+        cmd.args(&self.run_args).arg("--emit-events");
+        if let Some(ref cycle) = request.cycle {
+            cmd.args(["--cycle", cycle]);
+        }
+        if let Some(iterations) = request.max_iterations {
+            cmd.args(["--max-iterations", &iterations.to_string()]);
+        }
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Failed to start {}", self.flow_bin.display()))?;
+        if let Some(stdout) = child.stdout.take() {
+            let events = self.events.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    // No subscribers is fine; the events are also in the log
+                    let _ = events.send(line);
+                }
+            });
+        }
+        Ok(child)
+    }
+
+    fn stop(&self) -> Response {
+        let Some(holder) = live_holder(&self.log_dir) else {
+            return Response::error(409, "no run in progress");
+        };
+        match interrupt(holder.pid) {
+            Ok(()) => Response::json(202, json!({ "stopping": holder.pid })),
+            Err(e) => Response::error(500, &format!("{e:#}")),
+        }
+    }
+
+    /// Hold the connection open, writing each event line as it arrives.
+    async fn stream_events(&self, mut stream: TcpStream) -> Result<()> {
+        let mut events = self.events.subscribe();
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+            )
+            .await?;
+        loop {
+            match events.recv().await {
+                Ok(line) => {
+                    stream.write_all(format!("{line}\n").as_bytes()).await?;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Read one request: the request line, headers, and a `Content-Length` body.
+///
+/// No more than [`MAX_HEAD_BYTES`] (plus one, to detect the overflow) are
+/// buffered for the head, even if a line never ends.
+pub async fn read_request<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Result<Request> {
+    let mut head_bytes = 0;
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let remaining = (MAX_HEAD_BYTES + 1 - head_bytes) as u64;
+        let read = (&mut *reader).take(remaining).read_line(&mut line).await?;
+        head_bytes += read;
+        if head_bytes > MAX_HEAD_BYTES {
+            bail!("Request head exceeds {MAX_HEAD_BYTES} bytes");
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if read == 0 || line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    let mut lines = lines.into_iter();
+    let request_line = lines.next().context("Empty request")?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Malformed request line '{request_line}'");
+    };
+    let path = target.split('?').next().unwrap_or(target).to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        })
+        .collect();
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .map(|(_, value)| value.parse::<usize>())
+        .transpose()
+        .context("Invalid Content-Length")?
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        bail!("Request body exceeds {MAX_BODY_BYTES} bytes");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Request {
+        method: method.to_string(),
+        path,
+        headers,
+        body,
+    })
+}
+
+/// Compare secrets without returning early at the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+const fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Send SIGINT to a run, which stops it as Ctrl+C would.
+#[cfg(unix)]
+fn interrupt(pid: u32) -> Result<()> {
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &pid.to_string()])
+        .status()
+        .context("Failed to run kill")?;
+    if !status.success() {
+        bail!("kill -INT {pid} failed");
+    }
+    Ok(())
+}
+
+/// Stopping a run needs a signal; not supported here.
+#[cfg(not(unix))]
+fn interrupt(_pid: u32) -> Result<()> {
+    bail!("Stopping a run over the API is only supported on Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::lock::RunLock;
+    use crate::log::progress::{ProgressWriter, RunProgress};
+    use tempfile::TempDir;
+
+    const CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+
+    fn server(dir: &TempDir) -> ApiServer {
+        let config = dir.path().join("cycles.toml");
+        std::fs::write(&config, CONFIG).unwrap();
+        ApiServer::new("s3cret", &config, &dir.path().join(".flow")).unwrap()
+    }
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: vec![("authorization".to_string(), "Bearer s3cret".to_string())],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    async fn parse(raw: &str) -> Result<Request> {
+        read_request(&mut BufReader::new(raw.as_bytes())).await
+    }
+
+    // --- request parsing tests ---
+
+    #[tokio::test]
+    async fn test_read_request_parses_line_headers_and_body() {
+        let request = parse(
+            "POST /run?x=1 HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer t\r\nContent-Length: 17\r\n\r\n{\"cycle\":\"code\"}\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/run");
+        assert_eq!(request.header("authorization"), Some("Bearer t"));
+        assert_eq!(request.body, b"{\"cycle\":\"code\"}\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_request_rejects_oversized_body() {
+        let raw = format!(
+            "POST /run HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_BYTES + 1
+        );
+        assert!(parse(&raw).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_request_stops_at_head_limit_without_newline() {
+        let endless = tokio::io::repeat(b'G');
+        let err = read_request(&mut BufReader::new(endless))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Request head exceeds"), "{err}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_silent_client_gets_request_timeout() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        server.handle_connection(stream).await.unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 408 Request Timeout\r\n"),
+            "{response}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_rejects_empty_request() {
+        assert!(parse("").await.is_err());
+    }
+
+    #[test]
+    fn test_response_serializes_status_and_length() {
+        let http = Response::error(404, "not found").to_http();
+        assert!(http.starts_with("HTTP/1.1 404 Not Found\r\n"), "{http}");
+        assert!(http.contains("Content-Length: 21\r\n"), "{http}");
+        assert!(http.ends_with("{\"error\":\"not found\"}"), "{http}");
+    }
+
+    // --- auth and routing tests ---
+
+    #[test]
+    fn test_authorized_requires_matching_bearer_token() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        assert!(server.authorized(&request("GET", "/status", "")));
+
+        let mut wrong = request("GET", "/status", "");
+        wrong.headers[0].1 = "Bearer s3cre".to_string();
+        assert!(!server.authorized(&wrong));
+        wrong.headers.clear();
+        assert!(!server.authorized(&wrong));
+    }
+
+    #[tokio::test]
+    async fn test_route_unknown_path_and_method() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        assert_eq!(server.route(&request("GET", "/nope", "")).await.status, 404);
+        assert_eq!(server.route(&request("GET", "/run", "")).await.status, 405);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_idle_and_running() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        let idle = server.route(&request("GET", "/status", "")).await;
+        assert_eq!(idle.status, 200);
+        assert_eq!(idle.body["running"], false);
+        assert!(idle.body["progress"].is_null());
+
+        let log_dir = dir.path().join(".flow");
+        let _lock = RunLock::acquire(&log_dir, false).unwrap();
+        let mut progress = RunProgress::new(2);
+        progress.current_cycle = "coding".to_string();
+        ProgressWriter::new(&log_dir)
+            .unwrap()
+            .write(&progress)
+            .unwrap();
+        let running = server.route(&request("GET", "/status", "")).await;
+        assert_eq!(running.body["running"], true);
+        assert_eq!(running.body["pid"], std::process::id());
+        assert_eq!(running.body["progress"]["current_cycle"], "coding");
+    }
+
+    #[tokio::test]
+    async fn test_run_rejects_unknown_cycle_and_bad_body() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        let unknown = server
+            .route(&request("POST", "/run", r#"{"cycle":"deploy"}"#))
+            .await;
+        assert_eq!(unknown.status, 400);
+        assert_eq!(unknown.body["error"], "Unknown cycle 'deploy'");
+
+        let bad = server
+            .route(&request("POST", "/run", r#"{"cycles":"coding"}"#))
+            .await;
+        assert_eq!(bad.status, 400);
+    }
+
+    #[tokio::test]
+    async fn test_run_conflicts_with_run_in_progress() {
+        let dir = TempDir::new().unwrap();
+        let server = server(&dir);
+        let _lock = RunLock::acquire(&dir.path().join(".flow"), false).unwrap();
+        let response = server
+            .route(&request("POST", "/run", r#"{"cycle":"coding"}"#))
+            .await;
+        assert_eq!(response.status, 409);
+    }
+
+    #[tokio::test]
+    async fn test_stop_without_run_is_conflict() {
+        let dir = TempDir::new().unwrap();
+        let response = server(&dir).route(&request("POST", "/stop", "")).await;
+        assert_eq!(response.status, 409);
+    }
+
+    #[tokio::test]
+    async fn test_serve_answers_over_tcp() {
+        let dir = TempDir::new().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let cancel = CancellationToken::new();
+        let serving = tokio::spawn(server(&dir).serve(listener, cancel.clone()));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /status HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n")
+            .await
+            .unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await.unwrap();
+        assert!(reply.starts_with("HTTP/1.1 401 "), "{reply}");
+
+        cancel.cancel();
+        serving.await.unwrap().unwrap();
+    }
+}
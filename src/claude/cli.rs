@@ -6,10 +6,13 @@
 
 use anyhow::{bail, Context, Result};
 use std::process::Command;
+use std::sync::OnceLock;
 use tokio::io::AsyncBufReadExt;
 use tokio::process::Command as TokioCommand;
 
+use super::mock::{parse_claude_bin, CLAUDE_BIN_ENV};
 use super::stream::{parse_event, StreamAccumulator, StreamEvent};
+use crate::cycle::config::PermissionMode;
 
 /// Options for building a Claude Code command beyond prompt and permissions.
 #[derive(Debug, Clone, Default)]
@@ -20,6 +23,14 @@ pub struct CommandOptions {
     pub max_turns: Option<u32>,
     /// Maximum cost in USD (maps to `--max-budget-usd`).
     pub max_cost_usd: Option<f64>,
+    /// Replacement system prompt (maps to `--system-prompt`).
+    pub system_prompt: Option<String>,
+    /// Text appended to the default system prompt (maps to `--append-system-prompt`).
+    pub append_system_prompt: Option<String>,
+    /// Permission mode (maps to `--permission-mode`).
+    pub permission_mode: Option<PermissionMode>,
+    /// Model alias or name (maps to `--model`).
+    pub model: Option<String>,
 }
 
 /// Build a `Command` to invoke Claude Code with the given prompt and permissions.
@@ -53,17 +64,43 @@ pub fn build_command_with_session(
     )
 }
 
+/// `claude_bin` from the user config, used when `FLOW_CLAUDE_BIN` is unset
+static DEFAULT_CLAUDE_BIN: OnceLock<String> = OnceLock::new();
+
+/// Run `value` (a program plus leading arguments) in place of `claude` when
+/// `FLOW_CLAUDE_BIN` is unset. Only the first call takes effect.
+pub fn set_default_claude_bin(value: &str) {
+    let _ = DEFAULT_CLAUDE_BIN.set(value.to_string());
+}
+
+/// The base `claude` command, or the program named by `FLOW_CLAUDE_BIN`
+/// (e.g. `flow mock-claude --fixtures tests/mock`) or the user config's
+/// `claude_bin` when set.
+fn claude_command() -> Command {
+    let custom = std::env::var(CLAUDE_BIN_ENV)
+        .ok()
+        .or_else(|| DEFAULT_CLAUDE_BIN.get().cloned())
+        .and_then(|value| parse_claude_bin(&value));
+    let Some((program, args)) = custom else {
+        return Command::new("claude");
+    };
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    cmd
+}
+
 /// Build a `Command` to invoke Claude Code with full options.
 ///
 /// This is the core builder that all other `build_command*` functions delegate to.
-/// Supports resume args, max turns, and max budget in addition to prompt and permissions.
+/// Supports resume args, max turns, max budget, and system prompts in addition
+/// to prompt and permissions.
 #[must_use]
 pub fn build_command_with_options(
     prompt: &str,
     permissions: &[String],
     options: &CommandOptions,
 ) -> Command {
-    let mut cmd = Command::new("claude");
+    let mut cmd = claude_command();
 
     for arg in &options.resume_args {
         cmd.arg(arg);
@@ -88,17 +125,48 @@ pub fn build_command_with_options(
         cmd.arg("--max-budget-usd").arg(max_cost.to_string());
     }
 
+    if let Some(ref model) = options.model {
+        cmd.arg("--model").arg(model);
+    }
+
+    if let Some(mode) = options.permission_mode {
+        cmd.arg("--permission-mode").arg(mode.as_arg());
+    }
+
+    if let Some(ref system_prompt) = options.system_prompt {
+        cmd.arg("--system-prompt").arg(system_prompt);
+    }
+
+    if let Some(ref append) = options.append_system_prompt {
+        cmd.arg("--append-system-prompt").arg(append);
+    }
+
     cmd
 }
 
+/// Final result text of a Claude Code response and the cost it reported
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    /// Result text
+    pub text: String,
+    /// `total_cost_usd` of the result event, if there was one
+    pub cost_usd: Option<f64>,
+}
+
 /// Spawn a Claude Code command, stream-parse the output, and return the result text.
 ///
 /// Used by the cycle selector and step router — both invoke Claude with no tool
 /// permissions and only need the final result text from the response.
 pub async fn run_for_result(cmd: Command) -> Result<String> {
+    run_for_reply(cmd).await.map(|reply| reply.text)
+}
+
+/// Like [`run_for_result`], but also return the cost Claude Code reported.
+pub async fn run_for_reply(cmd: Command) -> Result<Reply> {
     let mut child = TokioCommand::from(cmd)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
         .spawn()
         .context("Failed to spawn claude")?;
 
@@ -122,16 +190,20 @@ pub async fn run_for_result(cmd: Command) -> Result<String> {
 
     let _ = child.wait().await;
 
-    let result_text = match &accumulator.result {
-        Some(StreamEvent::Result { result_text, .. }) => result_text.clone(),
-        _ => accumulator.text_fragments.join(""),
+    let (text, cost_usd) = match &accumulator.result {
+        Some(StreamEvent::Result {
+            result_text,
+            total_cost_usd,
+            ..
+        }) => (result_text.clone(), Some(*total_cost_usd)),
+        _ => (accumulator.text_fragments.join(""), None),
     };
 
-    if result_text.is_empty() {
+    if text.is_empty() {
         bail!("Claude returned empty response");
     }
 
-    Ok(result_text)
+    Ok(Reply { text, cost_usd })
 }
 
 #[cfg(test)]
@@ -329,12 +401,66 @@ mod tests {
         assert!(args.contains(&"10"));
     }
 
+    #[test]
+    fn test_build_with_system_prompts_adds_flags() {
+        let opts = super::CommandOptions {
+            system_prompt: Some("You are a careful engineer.".to_string()),
+            append_system_prompt: Some("Never push to main.".to_string()),
+            ..Default::default()
+        };
+        let cmd = super::build_command_with_options("Code", &[], &opts);
+        let args: Vec<&str> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        let pos = args.iter().position(|a| *a == "--system-prompt").unwrap();
+        assert_eq!(args[pos + 1], "You are a careful engineer.");
+        let pos = args
+            .iter()
+            .position(|a| *a == "--append-system-prompt")
+            .unwrap();
+        assert_eq!(args[pos + 1], "Never push to main.");
+    }
+
+    #[test]
+    fn test_build_with_permission_mode_adds_flag() {
+        let opts = super::CommandOptions {
+            permission_mode: Some(super::PermissionMode::Plan),
+            ..Default::default()
+        };
+        let cmd = super::build_command_with_options("Plan", &[], &opts);
+        let args: Vec<&str> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        let pos = args.iter().position(|a| *a == "--permission-mode").unwrap();
+        assert_eq!(args[pos + 1], "plan");
+    }
+
+    #[test]
+    fn test_build_with_model_adds_flag() {
+        let opts = super::CommandOptions {
+            model: Some("sonnet".to_string()),
+            ..Default::default()
+        };
+        let cmd = super::build_command_with_options("Code", &[], &opts);
+        let args: Vec<&str> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        let pos = args.iter().position(|a| *a == "--model").unwrap();
+        assert_eq!(args[pos + 1], "sonnet");
+        assert!(!super::build_command("Code", &[])
+            .get_args()
+            .any(|a| a == "--model"));
+    }
+
+    #[test]
+    fn test_build_without_system_prompts_omits_flags() {
+        let cmd = super::build_command("Code", &[]);
+        let args: Vec<&str> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert!(!args.contains(&"--system-prompt"));
+        assert!(!args.contains(&"--append-system-prompt"));
+    }
+
     #[test]
     fn test_build_with_resume_and_limits() {
         let opts = super::CommandOptions {
             resume_args: vec!["--resume".to_string(), "abc-123".to_string()],
             max_turns: Some(30),
             max_cost_usd: Some(2.5),
+            ..Default::default()
         };
         let cmd = super::build_command_with_options("Code", &[], &opts);
         let args: Vec<&str> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
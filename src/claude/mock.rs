@@ -0,0 +1,267 @@
+//! Mock Claude Code backend for offline pipeline tests
+//!
+//! `flow mock-claude` stands in for the `claude` binary (point
+//! `FLOW_CLAUDE_BIN` at it). It reads `responses.toml` from a fixtures
+//! directory, picks the first response whose `match` regex matches the `-p`
+//! prompt, and prints that response as stream-JSON:
+//!
+//! ```toml
+//! [[response]]
+//! match = "Review the plan"
+//! transcript = "plan-review.jsonl"   # stream-JSON lines, relative to the fixtures dir
+//!
+//! [[response]]
+//! match = "Choose the next cycle"
+//! result = '{"cycle": "coding", "reason": "work remains"}'   # single result event
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+/// Environment variable naming the program (plus leading arguments) Flow runs
+/// in place of `claude`, e.g. `"flow mock-claude --fixtures tests/mock"`.
+pub const CLAUDE_BIN_ENV: &str = "FLOW_CLAUDE_BIN";
+
+/// Fixture file read from the fixtures directory.
+pub const RESPONSES_FILE: &str = "responses.toml";
+
+/// One canned response
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct MockResponse {
+    /// Regex matched against the prompt
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Stream-JSON transcript to print, relative to the fixtures directory
+    #[serde(default)]
+    pub transcript: Option<PathBuf>,
+    /// Shorthand: print a single successful `result` event with this text
+    #[serde(default)]
+    pub result: Option<String>,
+    /// Exit code of the mock process (default: 0)
+    #[serde(default)]
+    pub exit_code: i32,
+}
+
+/// Parsed `responses.toml`
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct MockFixtures {
+    /// Responses in priority order (first match wins)
+    #[serde(default, rename = "response")]
+    pub responses: Vec<MockResponse>,
+    /// Directory transcripts are resolved against
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+impl MockFixtures {
+    /// Load `<dir>/responses.toml`.
+    ///
+    /// # Errors
+    /// Returns an error if the file is missing or invalid, or a response sets
+    /// neither (or both of) `transcript` and `result`.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(RESPONSES_FILE);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read mock fixtures '{}'", path.display()))?;
+        let mut fixtures: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        for response in &fixtures.responses {
+            Regex::new(&response.pattern)
+                .with_context(|| format!("Invalid mock match regex '{}'", response.pattern))?;
+            if response.transcript.is_some() == response.result.is_some() {
+                bail!(
+                    "Mock response '{}' must set exactly one of transcript or result",
+                    response.pattern
+                );
+            }
+        }
+        fixtures.dir = dir.to_path_buf();
+        Ok(fixtures)
+    }
+
+    /// The first response whose `match` regex matches `prompt`.
+    #[must_use]
+    pub fn find(&self, prompt: &str) -> Option<&MockResponse> {
+        self.responses
+            .iter()
+            .find(|r| Regex::new(&r.pattern).is_ok_and(|re| re.is_match(prompt)))
+    }
+
+    /// Stream-JSON output and exit code for `prompt`.
+    ///
+    /// # Errors
+    /// Returns an error if no response matches or the transcript can't be read.
+    pub fn respond(&self, prompt: &str) -> Result<(String, i32)> {
+        let Some(response) = self.find(prompt) else {
+            bail!(
+                "No mock response matches prompt: {}",
+                prompt.chars().take(120).collect::<String>()
+            );
+        };
+        let output = match (&response.transcript, &response.result) {
+            (Some(transcript), _) => {
+                let path = self.dir.join(transcript);
+                std::fs::read_to_string(&path).with_context(|| {
+                    format!("Failed to read mock transcript '{}'", path.display())
+                })?
+            }
+            (None, Some(text)) => result_event(text),
+            (None, None) => String::new(),
+        };
+        Ok((output, response.exit_code))
+    }
+}
+
+/// A single successful stream-JSON `result` event carrying `text`.
+#[must_use]
+pub fn result_event(text: &str) -> String {
+    let event = serde_json::json!({
+        "type": "result",
+        "subtype": "success",
+        "is_error": false,
+        "num_turns": 1,
+        "result": text,
+        "total_cost_usd": 0.0,
+        "duration_ms": 0,
+        "permission_denials": [],
+    });
+    format!("{event}\n")
+}
+
+/// The prompt passed with `-p` in a Claude Code argument list.
+#[must_use]
+pub fn prompt_arg(args: &[String]) -> Option<&str> {
+    args.iter()
+        .position(|a| a == "-p")
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// Split a `FLOW_CLAUDE_BIN` value into program and leading arguments.
+///
+/// Returns `None` for an empty value.
+#[must_use]
+pub fn parse_claude_bin(value: &str) -> Option<(String, Vec<String>)> {
+    let mut parts = value.split_whitespace().map(str::to_string);
+    let program = parts.next()?;
+    Some((program, parts.collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fixtures(responses: &str) -> (TempDir, Result<MockFixtures>) {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(RESPONSES_FILE), responses).unwrap();
+        let loaded = MockFixtures::load(dir.path());
+        (dir, loaded)
+    }
+
+    // --- MockFixtures tests ---
+
+    #[test]
+    fn test_first_matching_response_wins() {
+        let (_dir, loaded) = fixtures(
+            r#"
+[[response]]
+match = "Review"
+result = "LGTM"
+
+[[response]]
+match = ".*"
+result = "fallback"
+"#,
+        );
+        let fixtures = loaded.unwrap();
+        assert_eq!(
+            fixtures.find("Review the plan").unwrap().result.as_deref(),
+            Some("LGTM")
+        );
+        assert_eq!(
+            fixtures.find("Write code").unwrap().result.as_deref(),
+            Some("fallback")
+        );
+    }
+
+    #[test]
+    fn test_respond_reads_transcript_relative_to_dir() {
+        let (dir, loaded) = fixtures(
+            r#"
+[[response]]
+match = "coding"
+transcript = "coding.jsonl"
+exit_code = 1
+"#,
+        );
+        std::fs::write(dir.path().join("coding.jsonl"), "{\"type\":\"x\"}\n").unwrap();
+        let (output, code) = loaded.unwrap().respond("do the coding").unwrap();
+        assert_eq!(output, "{\"type\":\"x\"}\n");
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    fn test_respond_without_match_is_error() {
+        let (_dir, loaded) = fixtures("[[response]]\nmatch = \"^plan$\"\nresult = \"ok\"\n");
+        let err = loaded.unwrap().respond("coding").unwrap_err();
+        assert!(err.to_string().contains("No mock response"), "{err}");
+    }
+
+    #[test]
+    fn test_load_rejects_response_without_output() {
+        let (_dir, loaded) = fixtures("[[response]]\nmatch = \"x\"\n");
+        let err = loaded.unwrap_err();
+        assert!(err.to_string().contains("exactly one"), "{err}");
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_regex() {
+        let (_dir, loaded) = fixtures("[[response]]\nmatch = \"(\"\nresult = \"x\"\n");
+        assert!(loaded
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid mock match"));
+    }
+
+    // --- helper tests ---
+
+    #[test]
+    fn test_result_event_parses_as_result() {
+        let line = result_event("all done");
+        let event = crate::claude::stream::parse_event(line.trim()).unwrap();
+        assert!(matches!(
+            event,
+            crate::claude::stream::StreamEvent::Result { ref result_text, .. } if result_text == "all done"
+        ));
+    }
+
+    #[test]
+    fn test_prompt_arg() {
+        let args: Vec<String> = ["--resume", "abc", "-p", "Fix it", "--verbose"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(prompt_arg(&args), Some("Fix it"));
+        assert_eq!(prompt_arg(&args[..2]), None);
+    }
+
+    #[test]
+    fn test_parse_claude_bin() {
+        assert_eq!(
+            parse_claude_bin("flow mock-claude --fixtures t"),
+            Some((
+                "flow".to_string(),
+                vec![
+                    "mock-claude".to_string(),
+                    "--fixtures".to_string(),
+                    "t".to_string()
+                ]
+            ))
+        );
+        assert_eq!(parse_claude_bin("  "), None);
+    }
+}
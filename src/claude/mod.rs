@@ -1,9 +1,17 @@
 //! Claude Code integration
 //!
 //! This module handles permission resolution, CLI command building,
-//! and stream-JSON output parsing.
+//! stream-JSON output parsing, token pricing, secret redaction, container
+//! sandboxing, test-output parsing, transient API error detection, and a mock
+//! backend for offline tests.
 
 pub mod cli;
+pub mod mock;
 pub mod permissions;
+pub mod pricing;
+pub mod redact;
+pub mod sandbox;
 pub mod session;
 pub mod stream;
+pub mod test_parser;
+pub mod transient;
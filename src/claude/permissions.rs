@@ -47,6 +47,64 @@ fn merge_permissions<const N: usize>(layers: [&[String]; N]) -> Vec<String> {
     result
 }
 
+/// Split `Tool(pattern)` into the tool name and its pattern (`None` for a bare tool).
+fn split_permission(perm: &str) -> (&str, Option<&str>) {
+    match perm.split_once('(') {
+        Some((tool, rest)) => (tool, Some(rest.strip_suffix(')').unwrap_or(rest))),
+        None => (perm, None),
+    }
+}
+
+/// Whether `broad` grants everything `narrow` does.
+///
+/// `Edit(./**)` covers `Edit(./src/**)`, `Bash(cargo *)` covers
+/// `Bash(cargo test *)`, and a bare `Edit` covers any `Edit(...)`. Identical
+/// permissions cover each other.
+///
+/// Only trailing wildcards are understood (`**`, `*`, and Bash's `:*`); for
+/// file tools a trailing `*` doesn't cross directories.
+#[must_use]
+pub fn permission_covers(broad: &str, narrow: &str) -> bool {
+    let (broad_tool, broad_pattern) = split_permission(broad);
+    let (narrow_tool, narrow_pattern) = split_permission(narrow);
+    if broad_tool != narrow_tool {
+        return false;
+    }
+    let (Some(broad_pattern), Some(narrow_pattern)) = (broad_pattern, narrow_pattern) else {
+        return broad_pattern.is_none();
+    };
+    if broad_pattern == narrow_pattern {
+        return true;
+    }
+    if let Some(prefix) = broad_pattern
+        .strip_suffix("**")
+        .or_else(|| broad_pattern.strip_suffix(":*"))
+    {
+        return narrow_pattern.starts_with(prefix);
+    }
+    let Some(prefix) = broad_pattern.strip_suffix('*') else {
+        return false;
+    };
+    narrow_pattern
+        .strip_prefix(prefix)
+        .is_some_and(|rest| broad_tool == "Bash" || !(rest.contains('/') || rest.contains("**")))
+}
+
+/// Whether `perm` grants a tool without meaningful limits: any shell command
+/// (`Bash`, `Bash(*)`) or edits anywhere in the tree (`Edit`, `Write(./**)`).
+#[must_use]
+pub fn is_broad_grant(perm: &str) -> bool {
+    let (tool, pattern) = split_permission(perm);
+    let unrestricted = pattern.is_none_or(|p| matches!(p, "*" | "**" | ":*"));
+    match tool {
+        "Bash" => unrestricted,
+        "Edit" | "Write" | "MultiEdit" | "NotebookEdit" => {
+            unrestricted || pattern.is_some_and(|p| matches!(p, "./**" | "/**" | "./*"))
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -214,4 +272,41 @@ permissions = ["Bash(cargo *)", "Edit(./src/**)"]
         // "Bash(cargo *)" appears in global first, so cycle duplicate is dropped
         assert_eq!(resolved, vec!["Read", "Bash(cargo *)", "Edit(./src/**)"]);
     }
+
+    // --- permission_covers tests ---
+
+    #[test]
+    fn test_permission_covers_wildcards() {
+        assert!(permission_covers("Edit(./**)", "Edit(./src/**)"));
+        assert!(permission_covers("Edit", "Edit(./src/main.rs)"));
+        assert!(permission_covers("Bash(cargo *)", "Bash(cargo test *)"));
+        assert!(permission_covers("Bash(git:*)", "Bash(git status)"));
+        assert!(permission_covers("Read", "Read"));
+        assert!(permission_covers("Edit(./src/*)", "Edit(./src/lib.rs)"));
+    }
+
+    #[test]
+    fn test_permission_covers_rejects_non_subsets() {
+        assert!(!permission_covers("Edit(./src/**)", "Edit(./**)"));
+        assert!(!permission_covers("Edit(./src/**)", "Write(./src/**)"));
+        assert!(!permission_covers("Edit(./src/*)", "Edit(./src/a/b.rs)"));
+        assert!(!permission_covers("Edit(./src/*)", "Edit(./src/**)"));
+        assert!(!permission_covers("Bash(cargo *)", "Bash"));
+        assert!(!permission_covers(
+            "Bash(cargo test)",
+            "Bash(cargo test --all)"
+        ));
+    }
+
+    // --- is_broad_grant tests ---
+
+    #[test]
+    fn test_is_broad_grant() {
+        for perm in ["Bash", "Bash(*)", "Edit", "Edit(./**)", "Write(**)"] {
+            assert!(is_broad_grant(perm), "{perm}");
+        }
+        for perm in ["Bash(cargo *)", "Edit(./src/**)", "Read", "WebFetch"] {
+            assert!(!is_broad_grant(perm), "{perm}");
+        }
+    }
 }
@@ -0,0 +1,112 @@
+//! Token-based cost computation
+//!
+//! Prices token usage with the `[pricing.<model>]` table so a cycle's cost is
+//! known even when Claude Code doesn't report `total_cost_usd`, and can be
+//! cross-checked when it does. Table keys match a model exactly or as a
+//! substring of its ID, so `[pricing.sonnet]` prices
+//! `claude-sonnet-4-5-20250929`; the longest matching key wins.
+
+use std::collections::HashMap;
+
+use crate::claude::stream::TokenUsage;
+use crate::cycle::config::ModelPrice;
+
+/// Relative difference between reported and computed cost that draws a warning
+pub const COST_MISMATCH_RATIO: f64 = 0.25;
+
+/// The price for `model`: an exact key, else the longest key contained in it.
+#[must_use]
+#[allow(clippy::implicit_hasher)]
+pub fn price_for<'a>(
+    pricing: &'a HashMap<String, ModelPrice>,
+    model: &str,
+) -> Option<&'a ModelPrice> {
+    pricing.get(model).or_else(|| {
+        pricing
+            .iter()
+            .filter(|(key, _)| model.contains(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| price)
+    })
+}
+
+/// Cost in USD of `usage` at `price`.
+#[must_use]
+#[allow(clippy::cast_precision_loss)] // token counts are far below 2^52
+pub fn usage_cost(price: &ModelPrice, usage: &TokenUsage) -> f64 {
+    let per_token = |tokens: u64, per_million: f64| tokens as f64 * per_million / 1_000_000.0;
+    per_token(usage.input_tokens, price.input)
+        + per_token(usage.output_tokens, price.output)
+        + per_token(usage.cache_read_input_tokens, price.cache_read)
+        + per_token(usage.cache_creation_input_tokens, price.cache_write)
+}
+
+/// Whether a reported cost is off from the computed one by more than
+/// [`COST_MISMATCH_RATIO`] (relative to the larger of the two).
+#[must_use]
+pub fn costs_diverge(reported: f64, computed: f64) -> bool {
+    let larger = reported.max(computed);
+    larger > 0.0 && (reported - computed).abs() / larger > COST_MISMATCH_RATIO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> HashMap<String, ModelPrice> {
+        HashMap::from([
+            (
+                "sonnet".to_string(),
+                ModelPrice {
+                    input: 3.0,
+                    output: 15.0,
+                    cache_read: 0.3,
+                    cache_write: 3.75,
+                },
+            ),
+            (
+                "claude-sonnet-4".to_string(),
+                ModelPrice {
+                    input: 2.0,
+                    ..ModelPrice::default()
+                },
+            ),
+        ])
+    }
+
+    // --- price_for tests ---
+
+    #[test]
+    fn test_price_for_prefers_exact_then_longest_match() {
+        let pricing = table();
+        assert!((price_for(&pricing, "sonnet").unwrap().input - 3.0).abs() < f64::EPSILON);
+        let dated = price_for(&pricing, "claude-sonnet-4-5-20250929").unwrap();
+        assert!((dated.input - 2.0).abs() < f64::EPSILON);
+        assert!(price_for(&pricing, "claude-opus-4-6").is_none());
+    }
+
+    // --- usage_cost tests ---
+
+    #[test]
+    fn test_usage_cost_prices_each_token_kind() {
+        let pricing = table();
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cache_read_input_tokens: 1_000_000,
+            cache_creation_input_tokens: 0,
+        };
+        let cost = usage_cost(&pricing["sonnet"], &usage);
+        assert!((cost - 4.8).abs() < 1e-9, "{cost}");
+    }
+
+    // --- costs_diverge tests ---
+
+    #[test]
+    fn test_costs_diverge_beyond_ratio() {
+        assert!(!costs_diverge(1.0, 0.9));
+        assert!(costs_diverge(1.0, 0.5));
+        assert!(costs_diverge(0.0, 0.2));
+        assert!(!costs_diverge(0.0, 0.0));
+    }
+}
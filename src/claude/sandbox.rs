@@ -0,0 +1,190 @@
+//! Containerized execution for Claude Code
+//!
+//! Wraps a `claude` command in `docker run` for cycles with
+//! `sandbox = "docker"`. The repository is bind-mounted at `/workspace`;
+//! the host home directory and environment stay outside the container except
+//! for the variables listed in `[sandbox].env` and those Flow sets on the
+//! `claude` command itself. The container runs as the host user, so files it
+//! writes to the repository stay owned by them.
+//!
+//! Each container is thrown away when its step ends, so session tags (which
+//! resume a conversation stored under the container's home) are rejected for
+//! sandboxed cycles at config validation.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::cycle::config::SandboxConfig;
+
+/// Mount point of the repository inside the container
+pub const WORKSPACE: &str = "/workspace";
+
+/// Build a `docker run` command that executes `inner` inside `image`.
+///
+/// The container is removed on exit, runs as the host user with all
+/// capabilities dropped and `no-new-privileges`, and applies the configured
+/// CPU, memory, and process limits. `workdir` (the repository root) is the
+/// only host path mounted.
+#[must_use]
+pub fn docker_command(
+    inner: &Command,
+    image: &str,
+    config: &SandboxConfig,
+    workdir: &Path,
+) -> Command {
+    let mut cmd = Command::new("docker");
+    cmd.args(["run", "--rm", "-i", "--init"]);
+    cmd.args(["--cap-drop", "ALL", "--security-opt", "no-new-privileges"]);
+    if let Some(user) = host_user() {
+        cmd.arg("--user").arg(user);
+    }
+    cmd.arg("-v")
+        .arg(format!("{}:{WORKSPACE}", workdir.display()));
+    cmd.args(["-w", WORKSPACE]);
+
+    if let Some(cpus) = config.cpus {
+        cmd.arg("--cpus").arg(cpus.to_string());
+    }
+    if let Some(ref memory) = config.memory {
+        cmd.arg("--memory").arg(memory);
+    }
+    if let Some(pids) = config.pids_limit {
+        cmd.arg("--pids-limit").arg(pids.to_string());
+    }
+    // `-e NAME` copies the host value only if it is set
+    for name in &config.env {
+        cmd.arg("-e").arg(name);
+    }
+    // Variables set on the inner command would otherwise be lost
+    for (name, value) in inner.get_envs() {
+        if let Some(value) = value {
+            let mut var = name.to_os_string();
+            var.push("=");
+            var.push(value);
+            cmd.arg("-e").arg(var);
+        }
+    }
+
+    cmd.arg(image);
+    cmd.arg(inner.get_program());
+    cmd.args(inner.get_args());
+    cmd
+}
+
+/// `uid:gid` of the current user, as `id -u` and `id -g` report them.
+#[cfg(unix)]
+fn host_user() -> Option<String> {
+    let id = |flag: &str| {
+        let output = Command::new("id").arg(flag).output().ok()?;
+        let id = String::from_utf8(output.stdout).ok()?.trim().to_string();
+        (output.status.success() && !id.is_empty()).then_some(id)
+    };
+    Some(format!("{}:{}", id("-u")?, id("-g")?))
+}
+
+/// `uid:gid` of the current user — Docker's default user is used elsewhere.
+#[cfg(not(unix))]
+const fn host_user() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn inner() -> Command {
+        let mut cmd = Command::new("claude");
+        cmd.args(["-p", "Do the task", "--allowedTools", "Read"]);
+        cmd
+    }
+
+    // --- docker_command tests ---
+
+    #[test]
+    fn test_docker_command_mounts_repo_and_runs_inner_command() {
+        let cmd = docker_command(
+            &inner(),
+            "flow-sandbox:latest",
+            &SandboxConfig::default(),
+            Path::new("/home/me/repo"),
+        );
+        assert_eq!(cmd.get_program(), "docker");
+        let args = args(&cmd);
+        assert_eq!(&args[..4], ["run", "--rm", "-i", "--init"]);
+        assert!(args.contains(&"/home/me/repo:/workspace".to_string()));
+        let image = args
+            .iter()
+            .position(|a| a == "flow-sandbox:latest")
+            .unwrap();
+        assert_eq!(
+            &args[image + 1..],
+            ["claude", "-p", "Do the task", "--allowedTools", "Read"]
+        );
+    }
+
+    #[test]
+    fn test_docker_command_applies_limits_and_env() {
+        let config = SandboxConfig {
+            image: None,
+            cpus: Some(1.5),
+            memory: Some("4g".to_string()),
+            pids_limit: Some(256),
+            env: vec!["ANTHROPIC_API_KEY".to_string()],
+        };
+        let args = args(&docker_command(&inner(), "img", &config, Path::new("/r")));
+        let joined = args.join(" ");
+        assert!(joined.contains("--cpus 1.5"), "{joined}");
+        assert!(joined.contains("--memory 4g"), "{joined}");
+        assert!(joined.contains("--pids-limit 256"), "{joined}");
+        assert!(joined.contains("-e ANTHROPIC_API_KEY"), "{joined}");
+        assert!(joined.contains("--cap-drop ALL"), "{joined}");
+    }
+
+    #[test]
+    fn test_docker_command_forwards_inner_env() {
+        let mut inner = inner();
+        inner.env("FLOW_STEP", "plan").env_remove("HOME");
+        let args = args(&docker_command(
+            &inner,
+            "img",
+            &SandboxConfig::default(),
+            Path::new("/r"),
+        ));
+        let joined = args.join(" ");
+        assert!(joined.contains("-e FLOW_STEP=plan"), "{joined}");
+        assert!(!joined.contains("HOME"), "{joined}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_docker_command_runs_as_host_user() {
+        let args = args(&docker_command(
+            &inner(),
+            "img",
+            &SandboxConfig::default(),
+            Path::new("/r"),
+        ));
+        let user = args.iter().position(|a| a == "--user").unwrap();
+        assert_eq!(args[user + 1], host_user().unwrap());
+        assert!(args[user + 1]
+            .split(':')
+            .all(|id| id.parse::<u32>().is_ok()));
+    }
+
+    #[test]
+    fn test_docker_command_mounts_nothing_else() {
+        let args = args(&docker_command(
+            &inner(),
+            "img",
+            &SandboxConfig::default(),
+            Path::new("/r"),
+        ));
+        assert_eq!(args.iter().filter(|a| *a == "-v").count(), 1);
+    }
+}
@@ -3,17 +3,93 @@
 //! Maps session tags (human-readable names in cycles.toml) to Claude Code
 //! session IDs (obtained from the `SystemInit` stream event). Steps sharing
 //! the same session tag continue the same Claude Code conversation.
+//!
+//! Tags on steps with `persist = true` are also saved to `.flow/sessions.json`,
+//! so later runs resume the same conversation.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use std::collections::HashMap;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A session saved to `.flow/sessions.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistedSession {
+    /// Claude Code session ID
+    pub session_id: String,
+    /// Cycle that last used this session
+    pub cycle: String,
+    /// When the session was last used
+    pub last_used: chrono::DateTime<chrono::Utc>,
+}
+
+/// Read persisted sessions, keyed by tag. A missing file yields an empty map.
+///
+/// # Errors
+/// Returns an error if the file exists but cannot be read or parsed.
+pub fn load_sessions(path: &Path) -> Result<BTreeMap<String, PersistedSession>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Write persisted sessions atomically (via a temp file and rename).
+///
+/// # Errors
+/// Returns an error if the file cannot be written.
+pub fn save_sessions(path: &Path, sessions: &BTreeMap<String, PersistedSession>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let json = serde_json::to_string_pretty(sessions).context("Failed to serialize sessions")?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Remove sessions last used before `cutoff`. Returns the removed tags.
+pub fn prune_sessions(
+    sessions: &mut BTreeMap<String, PersistedSession>,
+    cutoff: chrono::DateTime<chrono::Utc>,
+) -> Vec<String> {
+    let stale: Vec<String> = sessions
+        .iter()
+        .filter(|(_, saved)| saved.last_used < cutoff)
+        .map(|(tag, _)| tag.clone())
+        .collect();
+    for tag in &stale {
+        sessions.remove(tag);
+    }
+    stale
+}
+
+/// Where and for which tags sessions are persisted
+#[derive(Debug)]
+struct SessionStore {
+    /// Path to `.flow/sessions.json`
+    path: PathBuf,
+    /// Cycle recorded alongside saved sessions
+    cycle: String,
+    /// Tags whose sessions outlive the cycle execution
+    tags: HashSet<String>,
+}
 
 /// Manages session tag → session ID mapping for one cycle execution.
 ///
 /// Session tags are scoped to a single cycle execution — a new `SessionManager`
-/// is created for each cycle run, so sessions never persist across iterations.
+/// is created for each cycle run — unless the tag is persistent (see
+/// [`SessionManager::with_store`]).
 #[derive(Debug, Default)]
 pub struct SessionManager {
     /// Maps session tag → Claude Code session ID
     tag_to_id: HashMap<String, String>,
+    /// Persistence settings, if any tags are persistent
+    store: Option<SessionStore>,
 }
 
 impl SessionManager {
@@ -23,11 +99,46 @@ impl SessionManager {
         Self::default()
     }
 
+    /// Create a session manager that persists `persistent_tags` to `path`.
+    ///
+    /// Previously saved sessions for those tags are loaded so the first step
+    /// using them resumes the earlier conversation. Other tags stay in memory.
+    ///
+    /// # Errors
+    /// Returns an error if the sessions file exists but cannot be read.
+    pub fn with_store(
+        path: PathBuf,
+        cycle: &str,
+        persistent_tags: impl IntoIterator<Item = String>,
+    ) -> Result<Self> {
+        let tags: HashSet<String> = persistent_tags.into_iter().collect();
+        let tag_to_id = load_sessions(&path)?
+            .into_iter()
+            .filter(|(tag, _)| tags.contains(tag))
+            .map(|(tag, saved)| (tag, saved.session_id))
+            .collect();
+        Ok(Self {
+            tag_to_id,
+            store: Some(SessionStore {
+                path,
+                cycle: cycle.to_string(),
+                tags,
+            }),
+        })
+    }
+
     /// Record a session ID for a given tag.
     ///
     /// Called after a step's `SystemInit` event is received, providing the
     /// real Claude Code session ID that should be used for resumption.
+    /// Persistent tags are also written to the sessions file; a failed write
+    /// is reported but does not abort the cycle.
     pub fn register(&mut self, tag: &str, session_id: String) {
+        if let Some(store) = self.store.as_ref().filter(|s| s.tags.contains(tag)) {
+            if let Err(e) = persist_session(store, tag, &session_id) {
+                eprintln!("Warning: failed to persist session '{tag}': {e:#}");
+            }
+        }
         self.tag_to_id.insert(tag.to_string(), session_id);
     }
 
@@ -53,6 +164,20 @@ impl SessionManager {
     }
 }
 
+/// Save one tag's session to the store, keeping other entries.
+fn persist_session(store: &SessionStore, tag: &str, session_id: &str) -> Result<()> {
+    let mut sessions = load_sessions(&store.path)?;
+    sessions.insert(
+        tag.to_string(),
+        PersistedSession {
+            session_id: session_id.to_string(),
+            cycle: store.cycle.clone(),
+            last_used: chrono::Utc::now(),
+        },
+    );
+    save_sessions(&store.path, &sessions)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,4 +247,91 @@ mod tests {
         assert_eq!(args[0], "--resume");
         assert_eq!(args[1], "xyz-789");
     }
+
+    // --- persistent session tests ---
+
+    #[test]
+    fn test_persistent_tag_is_saved_and_resumed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sessions.json");
+        let mut mgr =
+            SessionManager::with_store(path.clone(), "coding", ["architect".to_string()]).unwrap();
+        mgr.register("architect", "abc-123".to_string());
+
+        let saved = load_sessions(&path).unwrap();
+        assert_eq!(saved["architect"].session_id, "abc-123");
+        assert_eq!(saved["architect"].cycle, "coding");
+
+        let next_run =
+            SessionManager::with_store(path, "coding", ["architect".to_string()]).unwrap();
+        assert_eq!(
+            next_run.resume_args(Some("architect")),
+            vec!["--resume", "abc-123"]
+        );
+    }
+
+    #[test]
+    fn test_non_persistent_tag_is_not_saved() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sessions.json");
+        let mut mgr =
+            SessionManager::with_store(path.clone(), "coding", ["architect".to_string()]).unwrap();
+        mgr.register("coder", "xyz-789".to_string());
+        assert_eq!(mgr.get_session_id("coder"), Some("xyz-789"));
+        assert!(load_sessions(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_saved_session_ignored_when_tag_not_persistent() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sessions.json");
+        let mut mgr =
+            SessionManager::with_store(path.clone(), "coding", ["architect".to_string()]).unwrap();
+        mgr.register("architect", "abc-123".to_string());
+
+        let other = SessionManager::with_store(path, "coding", Vec::new()).unwrap();
+        assert!(other.get_session_id("architect").is_none());
+    }
+
+    #[test]
+    fn test_persisting_keeps_other_tags() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sessions.json");
+        let mut a =
+            SessionManager::with_store(path.clone(), "planning", ["planner".to_string()]).unwrap();
+        a.register("planner", "p-1".to_string());
+        let mut b =
+            SessionManager::with_store(path.clone(), "coding", ["architect".to_string()]).unwrap();
+        b.register("architect", "a-1".to_string());
+
+        let saved = load_sessions(&path).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved["planner"].cycle, "planning");
+    }
+
+    #[test]
+    fn test_prune_sessions_removes_only_stale() {
+        let now = chrono::Utc::now();
+        let saved = |days| PersistedSession {
+            session_id: "id".to_string(),
+            cycle: "coding".to_string(),
+            last_used: now - chrono::Duration::days(days),
+        };
+        let mut sessions = BTreeMap::from([
+            ("old".to_string(), saved(10)),
+            ("recent".to_string(), saved(1)),
+        ]);
+        let removed = prune_sessions(&mut sessions, now - chrono::Duration::days(7));
+        assert_eq!(removed, vec!["old"]);
+        assert!(sessions.contains_key("recent"));
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn test_load_sessions_missing_file_is_empty() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(load_sessions(&dir.path().join("sessions.json"))
+            .unwrap()
+            .is_empty());
+    }
 }
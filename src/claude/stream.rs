@@ -3,8 +3,62 @@
 //! Parses newline-delimited JSON events from Claude Code into structured
 //! `StreamEvent` variants for display and data extraction.
 
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::claude::test_parser::TestParser;
+
+/// Token counts reported in a result event's `usage` object
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenUsage {
+    /// Uncached input tokens
+    #[serde(default)]
+    pub input_tokens: u64,
+    /// Generated output tokens
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Input tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    /// Input tokens read from the prompt cache
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Add another invocation's token counts to these.
+    pub const fn add(&mut self, other: &Self) {
+        self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
+        self.cache_creation_input_tokens = self
+            .cache_creation_input_tokens
+            .saturating_add(other.cache_creation_input_tokens);
+        self.cache_read_input_tokens = self
+            .cache_read_input_tokens
+            .saturating_add(other.cache_read_input_tokens);
+    }
+
+    /// All tokens processed: input, output, and cache reads and writes
+    #[must_use]
+    pub const fn total(&self) -> u64 {
+        self.input_tokens
+            .saturating_add(self.output_tokens)
+            .saturating_add(self.cache_creation_input_tokens)
+            .saturating_add(self.cache_read_input_tokens)
+    }
+
+    /// Whether no tokens were reported at all
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.input_tokens == 0
+            && self.output_tokens == 0
+            && self.cache_creation_input_tokens == 0
+            && self.cache_read_input_tokens == 0
+    }
+}
+
 /// A parsed event from Claude Code's stream-json output
 #[derive(Debug, Clone, PartialEq)]
 pub enum StreamEvent {
@@ -14,12 +68,19 @@ pub enum StreamEvent {
         model: String,
         /// Session ID
         session_id: String,
+        /// Claude Code CLI version (`claude_code_version`), when reported
+        claude_version: Option<String>,
     },
     /// Assistant text output
     AssistantText {
         /// The text content
         text: String,
     },
+    /// Extended-thinking output from the assistant
+    Thinking {
+        /// The thinking content
+        text: String,
+    },
     /// Tool use request by the assistant
     ToolUse {
         /// Tool name (e.g., "Edit", "Bash")
@@ -34,6 +95,20 @@ pub enum StreamEvent {
         /// Content of the result (may be truncated)
         content: String,
     },
+    /// A subagent launched via the `Task` tool
+    SubagentStart {
+        /// Short description of the delegated task
+        description: String,
+        /// Subagent type (e.g., "general-purpose")
+        subagent_type: String,
+    },
+    /// An event emitted from inside a running subagent
+    Subagent {
+        /// ID of the `Task` tool use that spawned the subagent
+        parent_tool_use_id: String,
+        /// The nested event
+        event: Box<Self>,
+    },
     /// Final result of the entire session
     Result {
         /// Whether this was a success
@@ -48,6 +123,8 @@ pub enum StreamEvent {
         duration_ms: u64,
         /// Permission denial details
         permission_denials: Vec<String>,
+        /// Token usage (zero when not reported)
+        usage: TokenUsage,
     },
     /// Unrecognized event type
     Unknown {
@@ -69,16 +146,41 @@ pub fn parse_event(line: &str) -> Option<StreamEvent> {
     let value: Value = serde_json::from_str(line).ok()?;
     let event_type = value.get("type")?.as_str()?;
 
-    match event_type {
+    let event = match event_type {
         "system" => Some(parse_system_event(&value)),
         "assistant" => parse_assistant_event(&value),
         "result" => Some(parse_result_event(&value)),
         other => Some(StreamEvent::Unknown {
             event_type: other.to_string(),
         }),
+    }?;
+
+    // Events emitted by a subagent carry the ID of the Task tool use that spawned it
+    match value.get("parent_tool_use_id").and_then(Value::as_str) {
+        Some(parent) => Some(StreamEvent::Subagent {
+            parent_tool_use_id: parent.to_string(),
+            event: Box::new(event),
+        }),
+        None => Some(event),
     }
 }
 
+/// The message ID and token usage of an assistant event line, if it has both.
+///
+/// Claude Code repeats a message's usage on every content block it streams,
+/// so callers tracking mid-stream usage should key it by message ID.
+#[must_use]
+pub fn message_usage(line: &str) -> Option<(String, TokenUsage)> {
+    let value: Value = serde_json::from_str(line.trim()).ok()?;
+    if value.get("type")?.as_str()? != "assistant" {
+        return None;
+    }
+    let message = value.get("message")?;
+    let id = message.get("id")?.as_str()?.to_string();
+    let usage = serde_json::from_value(message.get("usage")?.clone()).ok()?;
+    Some((id, usage))
+}
+
 fn parse_system_event(value: &Value) -> StreamEvent {
     let model = value
         .get("model")
@@ -91,14 +193,25 @@ fn parse_system_event(value: &Value) -> StreamEvent {
         .unwrap_or("")
         .to_string();
 
-    StreamEvent::SystemInit { model, session_id }
+    let claude_version = value
+        .get("claude_code_version")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+
+    StreamEvent::SystemInit {
+        model,
+        session_id,
+        claude_version,
+    }
 }
 
 fn parse_assistant_event(value: &Value) -> Option<StreamEvent> {
     let message = value.get("message")?;
     let content = message.get("content")?.as_array()?;
 
-    // Extract first meaningful content block
+    // Extract first meaningful content block, falling back to thinking if
+    // the message contains nothing else
+    let mut thinking = None;
     for block in content {
         let block_type = block.get("type")?.as_str()?;
         match block_type {
@@ -113,6 +226,9 @@ fn parse_assistant_event(value: &Value) -> Option<StreamEvent> {
                     .unwrap_or("unknown")
                     .to_string();
                 let input = block.get("input").cloned().unwrap_or(Value::Null);
+                if tool_name == "Task" {
+                    return Some(parse_subagent_start(&input));
+                }
                 return Some(StreamEvent::ToolUse { tool_name, input });
             }
             "tool_result" => {
@@ -127,11 +243,36 @@ fn parse_assistant_event(value: &Value) -> Option<StreamEvent> {
                     .to_string();
                 return Some(StreamEvent::ToolResult { is_error, content });
             }
+            "thinking" if thinking.is_none() => {
+                thinking = block.get("thinking").and_then(Value::as_str).map(|text| {
+                    StreamEvent::Thinking {
+                        text: text.to_string(),
+                    }
+                });
+            }
             _ => {}
         }
     }
 
-    None
+    thinking
+}
+
+fn parse_subagent_start(input: &Value) -> StreamEvent {
+    let description = input
+        .get("description")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string();
+    let subagent_type = input
+        .get("subagent_type")
+        .and_then(Value::as_str)
+        .unwrap_or("general-purpose")
+        .to_string();
+
+    StreamEvent::SubagentStart {
+        description,
+        subagent_type,
+    }
 }
 
 fn parse_result_event(value: &Value) -> StreamEvent {
@@ -165,6 +306,10 @@ fn parse_result_event(value: &Value) -> StreamEvent {
                 .collect()
         })
         .unwrap_or_default();
+    let usage = value
+        .get("usage")
+        .and_then(|u| serde_json::from_value(u.clone()).ok())
+        .unwrap_or_default();
 
     StreamEvent::Result {
         is_error,
@@ -173,6 +318,7 @@ fn parse_result_event(value: &Value) -> StreamEvent {
         total_cost_usd,
         duration_ms,
         permission_denials,
+        usage,
     }
 }
 
@@ -193,25 +339,23 @@ pub fn suggest_permission_fix(tool_name: &str) -> String {
     }
 }
 
-/// Parse the number of passed tests from a cargo test output line.
+/// Extract the paths touched by a file-modifying tool's input.
 ///
-/// Recognizes the pattern `test result: ... N passed;` produced by `cargo test`.
-/// Returns `None` if the content does not contain a recognized cargo test summary.
-fn parse_tests_passed(content: &str) -> Option<u32> {
-    // Look for "N passed" in cargo test output (e.g. "test result: ok. 42 passed; 0 failed")
-    let passed_idx = content.find(" passed")?;
-    // Walk backwards from "passed" to find the start of the number
-    let before = &content[..passed_idx];
-    let number_start = before.rfind(|c: char| !c.is_ascii_digit())?;
-    let number_str = &before[number_start + 1..];
-    if number_str.is_empty() {
-        return None;
-    }
-    // Only parse if this looks like cargo test output (contains "test result")
-    if !content.contains("test result") {
-        return None;
+/// `Edit`, `Write`, and `MultiEdit` use `file_path` (`MultiEdit` may also carry
+/// per-edit `file_path`s in its `edits` array); `NotebookEdit` uses `notebook_path`.
+fn changed_paths(input: &Value) -> Vec<&str> {
+    let mut paths: Vec<&str> = ["file_path", "notebook_path"]
+        .iter()
+        .filter_map(|key| input.get(key).and_then(Value::as_str))
+        .collect();
+    if let Some(edits) = input.get("edits").and_then(Value::as_array) {
+        paths.extend(
+            edits
+                .iter()
+                .filter_map(|e| e.get("file_path").and_then(Value::as_str)),
+        );
     }
-    number_str.parse().ok()
+    paths
 }
 
 /// Accumulator for stream events — collects data across events for final summary.
@@ -223,10 +367,34 @@ pub struct StreamAccumulator {
     pub result: Option<StreamEvent>,
     /// Session ID from `SystemInit` event (used for session affinity in multi-step cycles)
     pub session_id: Option<String>,
-    /// Files modified during the session (from `Edit`/`Write` `ToolUse` events, deduplicated)
+    /// Model reported by the `SystemInit` event
+    pub model: Option<String>,
+    /// Claude Code CLI version reported by the `SystemInit` event
+    pub claude_version: Option<String>,
+    /// Why Flow stopped the invocation early (e.g. `max_tokens` exceeded)
+    pub stop_reason: Option<String>,
+    /// Files modified during the session (from `Edit`/`Write`/`MultiEdit`/`NotebookEdit`
+    /// `ToolUse` events, deduplicated)
     pub files_changed: Vec<String>,
-    /// Total number of tests passed, parsed from cargo test output in `ToolResult` content
+    /// Total number of tests passed, parsed from test output in `ToolResult` content
     pub tests_passed: u32,
+    /// Total number of tests failed, parsed from test output in `ToolResult` content
+    pub tests_failed: u32,
+    /// Total number of tests ignored, parsed from test output in `ToolResult` content
+    pub tests_ignored: u32,
+    /// Parser used to extract test counts (cargo by default)
+    test_parser: TestParser,
+    /// Shell commands executed via `Bash` `ToolUse` events, in order
+    pub commands_run: Vec<String>,
+    /// Number of uses of each tool, by tool name (subagents launched via
+    /// `Task` and tools used inside them included)
+    pub tools_used: BTreeMap<String, u32>,
+    /// Number of extended-thinking blocks emitted during the session
+    pub thinking_blocks: u32,
+    /// Number of subagents launched via the `Task` tool
+    pub subagent_count: u32,
+    /// Cost in USD reported by subagent results (tracked separately from the session total)
+    pub subagent_cost_usd: f64,
 }
 
 impl StreamAccumulator {
@@ -236,32 +404,60 @@ impl StreamAccumulator {
         Self::default()
     }
 
+    /// Create an accumulator that extracts test counts with the given parser
+    #[must_use]
+    pub fn with_test_parser(test_parser: TestParser) -> Self {
+        Self {
+            test_parser,
+            ..Self::default()
+        }
+    }
+
     /// Process a stream event and accumulate relevant data
     pub fn process(&mut self, event: &StreamEvent) {
         match event {
-            StreamEvent::SystemInit { session_id, .. } => {
+            StreamEvent::SystemInit {
+                session_id,
+                model,
+                claude_version,
+            } => {
                 self.session_id = Some(session_id.clone());
+                self.model = Some(model.clone());
+                self.claude_version.clone_from(claude_version);
             }
             StreamEvent::AssistantText { text } => {
                 self.text_fragments.push(text.clone());
             }
+            StreamEvent::Thinking { .. } => {
+                self.thinking_blocks = self.thinking_blocks.saturating_add(1);
+            }
             StreamEvent::ToolUse { tool_name, input } => {
-                if matches!(tool_name.as_str(), "Edit" | "Write") {
-                    if let Some(path) = input.get("file_path").and_then(Value::as_str) {
-                        if !self.files_changed.contains(&path.to_string()) {
-                            self.files_changed.push(path.to_string());
-                        }
-                    }
-                }
+                self.count_tool(tool_name);
+                self.record_tool_use(tool_name, input);
             }
             StreamEvent::ToolResult {
                 is_error: false,
                 content,
             } => {
-                if let Some(count) = parse_tests_passed(content) {
-                    self.tests_passed = self.tests_passed.saturating_add(count);
+                if let Some(counts) = self.test_parser.parse(content) {
+                    self.tests_passed = self.tests_passed.saturating_add(counts.passed);
+                    self.tests_failed = self.tests_failed.saturating_add(counts.failed);
+                    self.tests_ignored = self.tests_ignored.saturating_add(counts.ignored);
                 }
             }
+            StreamEvent::SubagentStart { .. } => {
+                self.subagent_count = self.subagent_count.saturating_add(1);
+                self.count_tool("Task");
+            }
+            StreamEvent::Subagent { event, .. } => match event.as_ref() {
+                // Nested results and session metadata belong to the subagent,
+                // not to this session
+                StreamEvent::Result { total_cost_usd, .. } => {
+                    self.subagent_cost_usd += total_cost_usd;
+                }
+                StreamEvent::SystemInit { .. } => {}
+                nested => self.process(nested),
+            },
             StreamEvent::Result { .. } => {
                 self.result = Some(event.clone());
             }
@@ -269,6 +465,31 @@ impl StreamAccumulator {
         }
     }
 
+    /// Count one use of `tool_name`.
+    fn count_tool(&mut self, tool_name: &str) {
+        let count = self.tools_used.entry(tool_name.to_string()).or_default();
+        *count = count.saturating_add(1);
+    }
+
+    /// Record the files a tool edited or the command it ran.
+    fn record_tool_use(&mut self, tool_name: &str, input: &Value) {
+        match tool_name {
+            "Edit" | "Write" | "MultiEdit" | "NotebookEdit" => {
+                for path in changed_paths(input) {
+                    if !self.files_changed.iter().any(|f| f == path) {
+                        self.files_changed.push(path.to_string());
+                    }
+                }
+            }
+            "Bash" => {
+                if let Some(command) = input.get("command").and_then(Value::as_str) {
+                    self.commands_run.push(command.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Extract the number of permission denials from the result
     #[must_use]
     pub fn permission_denial_count(&self) -> u32 {
@@ -301,14 +522,20 @@ mod tests {
 
     #[test]
     fn test_parse_system_init_event() {
-        let line = r#"{"type":"system","subtype":"init","model":"claude-opus-4-6","session_id":"abc-123","tools":["Read","Edit"]}"#;
+        let line = r#"{"type":"system","subtype":"init","model":"claude-opus-4-6","session_id":"abc-123","tools":["Read","Edit"],"claude_code_version":"2.0.14"}"#;
         let event = parse_event(line).unwrap();
 
-        let StreamEvent::SystemInit { model, session_id } = event else {
+        let StreamEvent::SystemInit {
+            model,
+            session_id,
+            claude_version,
+        } = event
+        else {
             panic!("Expected SystemInit, got {event:?}");
         };
         assert_eq!(model, "claude-opus-4-6");
         assert_eq!(session_id, "abc-123");
+        assert_eq!(claude_version.as_deref(), Some("2.0.14"));
     }
 
     #[test]
@@ -346,6 +573,7 @@ mod tests {
             total_cost_usd,
             duration_ms,
             permission_denials,
+            usage: _,
         } = event
         else {
             panic!("Expected Result, got {event:?}");
@@ -407,7 +635,10 @@ mod tests {
         let line = r#"{"type":"system","subtype":"init","cwd":"/Users/test/project","session_id":"f9c16ac1","tools":["Read","Edit"],"model":"claude-opus-4-6","permissionMode":"default"}"#;
         let event = parse_event(line).unwrap();
 
-        let StreamEvent::SystemInit { model, session_id } = event else {
+        let StreamEvent::SystemInit {
+            model, session_id, ..
+        } = event
+        else {
             panic!("Expected SystemInit, got {event:?}");
         };
         assert_eq!(model, "claude-opus-4-6");
@@ -416,7 +647,7 @@ mod tests {
 
     #[test]
     fn test_parse_real_world_result() {
-        let line = r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":2166,"duration_api_ms":2142,"num_turns":1,"result":"Hello! How can I help you today?","total_cost_usd":0.12109,"usage":{"input_tokens":3},"permission_denials":[]}"#;
+        let line = r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":2166,"duration_api_ms":2142,"num_turns":1,"result":"Hello! How can I help you today?","total_cost_usd":0.12109,"usage":{"input_tokens":3,"cache_creation_input_tokens":1500,"cache_read_input_tokens":12000,"output_tokens":12,"service_tier":"standard"},"permission_denials":[]}"#;
         let event = parse_event(line).unwrap();
 
         let StreamEvent::Result {
@@ -426,6 +657,7 @@ mod tests {
             total_cost_usd,
             duration_ms,
             permission_denials,
+            usage,
         } = event
         else {
             panic!("Expected Result, got {event:?}");
@@ -436,6 +668,41 @@ mod tests {
         assert!((total_cost_usd - 0.12109).abs() < 0.00001);
         assert_eq!(duration_ms, 2166);
         assert!(permission_denials.is_empty());
+        assert_eq!(
+            usage,
+            TokenUsage {
+                input_tokens: 3,
+                output_tokens: 12,
+                cache_creation_input_tokens: 1500,
+                cache_read_input_tokens: 12000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_message_usage_reads_assistant_message_usage() {
+        let line = r#"{"type":"assistant","message":{"id":"msg_1","content":[],"usage":{"input_tokens":5,"cache_read_input_tokens":100,"output_tokens":7}}}"#;
+        let (id, usage) = message_usage(line).unwrap();
+        assert_eq!(id, "msg_1");
+        assert_eq!(usage.total(), 112);
+        assert!(message_usage(r#"{"type":"result","usage":{"input_tokens":5}}"#).is_none());
+        assert!(message_usage("not json").is_none());
+    }
+
+    #[test]
+    fn test_token_usage_add() {
+        let mut total = TokenUsage::default();
+        assert!(total.is_empty());
+        let step = TokenUsage {
+            input_tokens: 10,
+            output_tokens: 5,
+            ..TokenUsage::default()
+        };
+        total.add(&step);
+        total.add(&step);
+        assert_eq!(total.input_tokens, 20);
+        assert_eq!(total.output_tokens, 10);
+        assert!(!total.is_empty());
     }
 
     #[test]
@@ -467,6 +734,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_assistant_thinking_event() {
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"thinking","thinking":"Let me check the tests first."}]}}"#;
+        let event = parse_event(line).unwrap();
+        assert_eq!(
+            event,
+            StreamEvent::Thinking {
+                text: "Let me check the tests first.".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_assistant_prefers_text_over_thinking() {
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"thinking","thinking":"hmm"},{"type":"text","text":"Done"}]}}"#;
+        let event = parse_event(line).unwrap();
+        assert_eq!(
+            event,
+            StreamEvent::AssistantText {
+                text: "Done".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_assistant_thinking_without_content_returns_none() {
+        let line =
+            r#"{"type":"assistant","message":{"content":[{"type":"thinking","signature":"abc"}]}}"#;
+        assert!(parse_event(line).is_none());
+    }
+
+    #[test]
+    fn test_parse_task_tool_use_as_subagent_start() {
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_1","name":"Task","input":{"description":"Find callers","prompt":"Search for...","subagent_type":"Explore"}}]}}"#;
+        let event = parse_event(line).unwrap();
+        assert_eq!(
+            event,
+            StreamEvent::SubagentStart {
+                description: "Find callers".to_string(),
+                subagent_type: "Explore".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_task_tool_use_defaults_subagent_type() {
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Task","input":{"description":"Do it"}}]}}"#;
+        let Some(StreamEvent::SubagentStart { subagent_type, .. }) = parse_event(line) else {
+            panic!("Expected SubagentStart");
+        };
+        assert_eq!(subagent_type, "general-purpose");
+    }
+
+    #[test]
+    fn test_parse_nested_event_wrapped_as_subagent() {
+        let line = r#"{"type":"assistant","parent_tool_use_id":"toolu_1","message":{"content":[{"type":"tool_use","name":"Grep","input":{"pattern":"fn main"}}]}}"#;
+        let event = parse_event(line).unwrap();
+        let StreamEvent::Subagent {
+            parent_tool_use_id,
+            event,
+        } = event
+        else {
+            panic!("Expected Subagent, got {event:?}");
+        };
+        assert_eq!(parent_tool_use_id, "toolu_1");
+        assert!(
+            matches!(*event, StreamEvent::ToolUse { ref tool_name, .. } if tool_name == "Grep")
+        );
+    }
+
+    #[test]
+    fn test_parse_null_parent_tool_use_id_not_wrapped() {
+        let line = r#"{"type":"assistant","parent_tool_use_id":null,"message":{"content":[{"type":"text","text":"Hi"}]}}"#;
+        let event = parse_event(line).unwrap();
+        assert!(matches!(event, StreamEvent::AssistantText { .. }));
+    }
+
     #[test]
     fn test_suggest_permission_fix_write() {
         assert_eq!(
@@ -516,6 +860,144 @@ mod tests {
         assert_eq!(acc.text_fragments, vec!["Hello", "World"]);
     }
 
+    #[test]
+    fn test_accumulator_with_custom_test_parser() {
+        use crate::cycle::config::TestParserPreset;
+
+        let mut acc =
+            StreamAccumulator::with_test_parser(TestParser::preset(TestParserPreset::Pytest));
+        acc.process(&StreamEvent::ToolResult {
+            is_error: false,
+            content: "==== 1 failed, 8 passed in 0.42s ====".to_string(),
+        });
+        assert_eq!(acc.tests_passed, 8);
+        assert_eq!(acc.tests_failed, 1);
+    }
+
+    #[test]
+    fn test_accumulator_tracks_bash_commands() {
+        let mut acc = StreamAccumulator::new();
+        for command in ["cargo test", "git status", "cargo test"] {
+            acc.process(&StreamEvent::ToolUse {
+                tool_name: "Bash".to_string(),
+                input: serde_json::json!({"command": command}),
+            });
+        }
+        acc.process(&StreamEvent::ToolUse {
+            tool_name: "Read".to_string(),
+            input: serde_json::json!({"file_path": "src/main.rs"}),
+        });
+        // Commands are kept in order, including repeats
+        assert_eq!(
+            acc.commands_run,
+            vec!["cargo test", "git status", "cargo test"]
+        );
+    }
+
+    #[test]
+    fn test_accumulator_counts_tool_uses() {
+        let mut acc = StreamAccumulator::new();
+        for tool_name in ["Read", "Bash", "Read"] {
+            acc.process(&StreamEvent::ToolUse {
+                tool_name: tool_name.to_string(),
+                input: serde_json::json!({}),
+            });
+        }
+        acc.process(&StreamEvent::SubagentStart {
+            description: "Explore".to_string(),
+            subagent_type: "general-purpose".to_string(),
+        });
+        acc.process(&StreamEvent::Subagent {
+            parent_tool_use_id: "toolu_1".to_string(),
+            event: Box::new(StreamEvent::ToolUse {
+                tool_name: "Grep".to_string(),
+                input: serde_json::json!({}),
+            }),
+        });
+        assert_eq!(
+            acc.tools_used,
+            BTreeMap::from([
+                ("Bash".to_string(), 1),
+                ("Grep".to_string(), 1),
+                ("Read".to_string(), 2),
+                ("Task".to_string(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_accumulator_bash_without_command_ignored() {
+        let mut acc = StreamAccumulator::new();
+        acc.process(&StreamEvent::ToolUse {
+            tool_name: "Bash".to_string(),
+            input: serde_json::json!({}),
+        });
+        assert!(acc.commands_run.is_empty());
+    }
+
+    #[test]
+    fn test_accumulator_counts_thinking_blocks() {
+        let mut acc = StreamAccumulator::new();
+        for _ in 0..2 {
+            acc.process(&StreamEvent::Thinking {
+                text: "pondering".to_string(),
+            });
+        }
+        assert_eq!(acc.thinking_blocks, 2);
+        assert!(
+            acc.text_fragments.is_empty(),
+            "Thinking should not count as assistant text"
+        );
+    }
+
+    #[test]
+    fn test_accumulator_tracks_subagents_separately() {
+        let mut acc = StreamAccumulator::new();
+        acc.process(&StreamEvent::SubagentStart {
+            description: "Explore".to_string(),
+            subagent_type: "Explore".to_string(),
+        });
+        acc.process(&StreamEvent::Subagent {
+            parent_tool_use_id: "toolu_1".to_string(),
+            event: Box::new(StreamEvent::SystemInit {
+                model: "claude-haiku".to_string(),
+                session_id: "sub-session".to_string(),
+                claude_version: None,
+            }),
+        });
+        acc.process(&StreamEvent::Subagent {
+            parent_tool_use_id: "toolu_1".to_string(),
+            event: Box::new(StreamEvent::ToolUse {
+                tool_name: "Edit".to_string(),
+                input: serde_json::json!({"file_path": "src/lib.rs"}),
+            }),
+        });
+        acc.process(&StreamEvent::Subagent {
+            parent_tool_use_id: "toolu_1".to_string(),
+            event: Box::new(StreamEvent::Result {
+                is_error: false,
+                result_text: "found".to_string(),
+                num_turns: 3,
+                total_cost_usd: 0.25,
+                duration_ms: 1000,
+                permission_denials: vec![],
+                usage: TokenUsage::default(),
+            }),
+        });
+
+        assert_eq!(acc.subagent_count, 1);
+        assert!((acc.subagent_cost_usd - 0.25).abs() < f64::EPSILON);
+        assert!(
+            acc.result.is_none(),
+            "Subagent result must not replace the session result"
+        );
+        assert!(
+            acc.session_id.is_none(),
+            "Subagent session must not replace ours"
+        );
+        assert_eq!(acc.files_changed, vec!["src/lib.rs"]);
+    }
+
     #[test]
     fn test_accumulator_ignores_error_tool_results() {
         let mut acc = StreamAccumulator::new();
@@ -541,6 +1023,7 @@ mod tests {
             total_cost_usd: 1.0,
             duration_ms: 30000,
             permission_denials: vec!["Edit".to_string()],
+            usage: TokenUsage::default(),
         };
         acc.process(&result);
         assert!(acc.result.is_some());
@@ -559,6 +1042,7 @@ mod tests {
         acc.process(&StreamEvent::SystemInit {
             model: "claude-opus-4-6".to_string(),
             session_id: "test-session-abc".to_string(),
+            claude_version: None,
         });
         assert_eq!(acc.session_id.as_deref(), Some("test-session-abc"));
     }
@@ -591,6 +1075,48 @@ mod tests {
         assert_eq!(acc.files_changed, vec!["src/lib.rs"]);
     }
 
+    #[test]
+    fn test_accumulator_tracks_multi_edit_file_path() {
+        let mut acc = StreamAccumulator::new();
+        acc.process(&StreamEvent::ToolUse {
+            tool_name: "MultiEdit".to_string(),
+            input: serde_json::json!({
+                "file_path": "src/lib.rs",
+                "edits": [
+                    {"old_string": "a", "new_string": "b"},
+                    {"old_string": "c", "new_string": "d"}
+                ]
+            }),
+        });
+        assert_eq!(acc.files_changed, vec!["src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_accumulator_tracks_multi_edit_per_edit_paths() {
+        let mut acc = StreamAccumulator::new();
+        acc.process(&StreamEvent::ToolUse {
+            tool_name: "MultiEdit".to_string(),
+            input: serde_json::json!({
+                "file_path": "src/lib.rs",
+                "edits": [
+                    {"file_path": "src/lib.rs", "old_string": "a", "new_string": "b"},
+                    {"file_path": "src/cli.rs", "old_string": "c", "new_string": "d"}
+                ]
+            }),
+        });
+        assert_eq!(acc.files_changed, vec!["src/lib.rs", "src/cli.rs"]);
+    }
+
+    #[test]
+    fn test_accumulator_tracks_notebook_edit_path() {
+        let mut acc = StreamAccumulator::new();
+        acc.process(&StreamEvent::ToolUse {
+            tool_name: "NotebookEdit".to_string(),
+            input: serde_json::json!({"notebook_path": "analysis.ipynb", "new_source": "x = 1"}),
+        });
+        assert_eq!(acc.files_changed, vec!["analysis.ipynb"]);
+    }
+
     #[test]
     fn test_accumulator_deduplicates_files_changed() {
         let mut acc = StreamAccumulator::new();
@@ -715,6 +1241,34 @@ mod tests {
         assert_eq!(acc.tests_passed, 5);
     }
 
+    // --- tests_failed / tests_ignored tracking tests ---
+
+    #[test]
+    fn test_accumulator_tracks_failed_and_ignored_tests() {
+        let mut acc = StreamAccumulator::new();
+        acc.process(&StreamEvent::ToolResult {
+            is_error: false,
+            content: "test result: FAILED. 40 passed; 3 failed; 2 ignored; 0 measured".to_string(),
+        });
+        assert_eq!(acc.tests_passed, 40);
+        assert_eq!(acc.tests_failed, 3);
+        assert_eq!(acc.tests_ignored, 2);
+    }
+
+    #[test]
+    fn test_accumulator_sums_multiple_test_binaries_in_one_result() {
+        let mut acc = StreamAccumulator::new();
+        acc.process(&StreamEvent::ToolResult {
+            is_error: false,
+            content: "running 10 tests\ntest result: ok. 10 passed; 0 failed; 1 ignored\n\
+                      running 4 tests\ntest result: FAILED. 3 passed; 1 failed; 0 ignored"
+                .to_string(),
+        });
+        assert_eq!(acc.tests_passed, 13);
+        assert_eq!(acc.tests_failed, 1);
+        assert_eq!(acc.tests_ignored, 1);
+    }
+
     #[test]
     fn test_accumulator_ignores_error_tool_results_for_tests_passed() {
         let mut acc = StreamAccumulator::new();
@@ -0,0 +1,252 @@
+//! Test-output parsing for tool results
+//!
+//! Extracts passed/failed/ignored test counts from the output of test runners
+//! invoked by Claude Code. Built-in presets cover cargo, pytest, jest, and
+//! `go test -v`; each pattern can be overridden with a custom regex via
+//! `[test_parser]` in cycles.toml.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::cycle::config::{TestParserConfig, TestParserPreset};
+
+/// Test counts parsed from test runner output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestCounts {
+    /// Number of passed tests
+    pub passed: u32,
+    /// Number of failed tests
+    pub failed: u32,
+    /// Number of ignored/skipped tests
+    pub ignored: u32,
+}
+
+/// Regex-based parser for test runner output.
+///
+/// Each pattern is applied line by line. If a pattern has a capture group, the
+/// captured number is added to the count (summary lines like `42 passed`);
+/// otherwise every match counts as one test (per-test lines like `--- PASS:`).
+#[derive(Debug, Clone)]
+pub struct TestParser {
+    passed: Regex,
+    failed: Regex,
+    ignored: Regex,
+}
+
+impl Default for TestParser {
+    fn default() -> Self {
+        Self::preset(TestParserPreset::Cargo)
+    }
+}
+
+impl TestParser {
+    /// Create a parser for one of the built-in presets.
+    #[must_use]
+    pub fn preset(preset: TestParserPreset) -> Self {
+        let (passed, failed, ignored) = preset_patterns(preset);
+        // Preset patterns are static and covered by tests
+        Self {
+            passed: Regex::new(passed).expect("valid preset regex"),
+            failed: Regex::new(failed).expect("valid preset regex"),
+            ignored: Regex::new(ignored).expect("valid preset regex"),
+        }
+    }
+
+    /// Build a parser from config, overlaying custom patterns on the preset.
+    ///
+    /// Returns the cargo preset when no `[test_parser]` section is configured.
+    pub fn from_config(config: Option<&TestParserConfig>) -> Result<Self> {
+        let Some(config) = config else {
+            return Ok(Self::default());
+        };
+        let mut parser = Self::preset(config.preset);
+        if let Some(ref pattern) = config.passed {
+            parser.passed = compile("passed", pattern)?;
+        }
+        if let Some(ref pattern) = config.failed {
+            parser.failed = compile("failed", pattern)?;
+        }
+        if let Some(ref pattern) = config.ignored {
+            parser.ignored = compile("ignored", pattern)?;
+        }
+        Ok(parser)
+    }
+
+    /// Parse test counts from tool output.
+    ///
+    /// Returns `None` if no pattern matched anywhere in the content.
+    #[must_use]
+    pub fn parse(&self, content: &str) -> Option<TestCounts> {
+        let mut counts = TestCounts::default();
+        let mut matched = false;
+        for line in content.lines() {
+            for (regex, total) in [
+                (&self.passed, &mut counts.passed),
+                (&self.failed, &mut counts.failed),
+                (&self.ignored, &mut counts.ignored),
+            ] {
+                for caps in regex.captures_iter(line) {
+                    matched = true;
+                    let n = caps
+                        .get(1)
+                        .map_or(Some(1), |m| m.as_str().parse::<u32>().ok())
+                        .unwrap_or(0);
+                    *total = total.saturating_add(n);
+                }
+            }
+        }
+        matched.then_some(counts)
+    }
+}
+
+/// Compile a user-supplied pattern, naming the field in the error.
+fn compile(field: &str, pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).with_context(|| format!("Invalid test_parser.{field} regex '{pattern}'"))
+}
+
+/// Return the `(passed, failed, ignored)` patterns for a preset.
+const fn preset_patterns(preset: TestParserPreset) -> (&'static str, &'static str, &'static str) {
+    match preset {
+        // test result: ok. 42 passed; 0 failed; 1 ignored; ...
+        TestParserPreset::Cargo => (
+            r"test result: .*?(\d+) passed",
+            r"test result: .*?(\d+) failed",
+            r"test result: .*?(\d+) ignored",
+        ),
+        // ===== 2 failed, 40 passed, 1 skipped in 1.23s =====
+        TestParserPreset::Pytest => (
+            r"(\d+) passed.* in [\d.]+s",
+            r"(\d+) failed.* in [\d.]+s",
+            r"(\d+) skipped.* in [\d.]+s",
+        ),
+        // Tests:       1 failed, 2 skipped, 40 passed, 43 total
+        TestParserPreset::Jest => (
+            r"^\s*Tests:.*?(\d+) passed",
+            r"^\s*Tests:.*?(\d+) failed",
+            r"^\s*Tests:.*?(\d+) skipped",
+        ),
+        // --- PASS: TestFoo (0.00s)
+        TestParserPreset::Go => (r"^\s*--- PASS:", r"^\s*--- FAIL:", r"^\s*--- SKIP:"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn counts(passed: u32, failed: u32, ignored: u32) -> TestCounts {
+        TestCounts {
+            passed,
+            failed,
+            ignored,
+        }
+    }
+
+    // --- cargo preset ---
+
+    #[test]
+    fn test_cargo_summary_line() {
+        let parser = TestParser::default();
+        assert_eq!(
+            parser.parse("test result: FAILED. 40 passed; 3 failed; 2 ignored; 0 measured"),
+            Some(counts(40, 3, 2))
+        );
+    }
+
+    #[test]
+    fn test_cargo_sums_multiple_binaries() {
+        let parser = TestParser::default();
+        let output = "running 10 tests\ntest result: ok. 10 passed; 0 failed; 1 ignored\n\
+                      running 4 tests\ntest result: FAILED. 3 passed; 1 failed; 0 ignored";
+        assert_eq!(parser.parse(output), Some(counts(13, 1, 1)));
+    }
+
+    #[test]
+    fn test_cargo_missing_fields_default_to_zero() {
+        let parser = TestParser::default();
+        assert_eq!(
+            parser.parse("test result: ok. 7 passed"),
+            Some(counts(7, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_cargo_requires_summary_line() {
+        let parser = TestParser::default();
+        assert!(parser.parse("3 passed, 1 failed").is_none());
+        assert!(parser.parse("File saved successfully").is_none());
+    }
+
+    // --- other presets ---
+
+    #[test]
+    fn test_pytest_summary_line() {
+        let parser = TestParser::preset(TestParserPreset::Pytest);
+        assert_eq!(
+            parser.parse("===== 2 failed, 40 passed, 1 skipped in 1.23s ====="),
+            Some(counts(40, 2, 1))
+        );
+        assert_eq!(
+            parser.parse("============ 12 passed in 0.50s ============"),
+            Some(counts(12, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_jest_summary_line() {
+        let parser = TestParser::preset(TestParserPreset::Jest);
+        let output = "Test Suites: 1 failed, 4 passed, 5 total\n\
+                      Tests:       1 failed, 2 skipped, 40 passed, 43 total";
+        // Only the `Tests:` line counts, not `Test Suites:`
+        assert_eq!(parser.parse(output), Some(counts(40, 1, 2)));
+    }
+
+    #[test]
+    fn test_go_counts_per_test_lines() {
+        let parser = TestParser::preset(TestParserPreset::Go);
+        let output = "=== RUN   TestA\n--- PASS: TestA (0.00s)\n=== RUN   TestB\n\
+                      --- FAIL: TestB (0.01s)\n    --- PASS: TestB/sub (0.00s)\n\
+                      --- SKIP: TestC (0.00s)\nFAIL";
+        assert_eq!(parser.parse(output), Some(counts(2, 1, 1)));
+    }
+
+    // --- from_config ---
+
+    #[test]
+    fn test_from_config_none_is_cargo() {
+        let parser = TestParser::from_config(None).unwrap();
+        assert_eq!(
+            parser.parse("test result: ok. 5 passed; 0 failed"),
+            Some(counts(5, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_from_config_custom_pattern_overrides_preset() {
+        let config = TestParserConfig {
+            preset: TestParserPreset::Cargo,
+            passed: Some(r"(\d+) tests? ok".to_string()),
+            failed: None,
+            ignored: None,
+        };
+        let parser = TestParser::from_config(Some(&config)).unwrap();
+        assert_eq!(parser.parse("ran: 9 tests ok"), Some(counts(9, 0, 0)));
+        // Preset patterns for the other fields still apply
+        assert_eq!(
+            parser.parse("test result: FAILED. 1 passed; 2 failed"),
+            Some(counts(0, 2, 0))
+        );
+    }
+
+    #[test]
+    fn test_from_config_invalid_regex_errors() {
+        let config = TestParserConfig {
+            preset: TestParserPreset::Cargo,
+            passed: None,
+            failed: Some("(unclosed".to_string()),
+            ignored: None,
+        };
+        let err = TestParser::from_config(Some(&config)).unwrap_err();
+        assert!(err.to_string().contains("test_parser.failed"), "got: {err}");
+    }
+}
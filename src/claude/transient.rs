@@ -0,0 +1,111 @@
+//! Transient upstream error detection
+//!
+//! Recognizes the signatures of API hiccups — overloaded servers, rate
+//! limits, 5xx responses, dropped connections — in Claude Code's stderr and
+//! result text, so a failed cycle caused by one can be retried with backoff
+//! instead of being treated like a broken prompt.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// `(label, pattern)` pairs checked in order; the first match names the error.
+const SIGNATURES: [(&str, &str); 5] = [
+    ("overloaded", r"(?i)overloaded"),
+    (
+        "rate limited",
+        r"(?i)rate[ _-]?limit|too many requests|(api error|status|http)\D{0,20}\b429\b",
+    ),
+    (
+        "server error",
+        r"(?i)(api error|status|http)\D{0,20}\b5\d\d\b|internal server error|bad gateway|service unavailable|gateway timeout",
+    ),
+    (
+        "connection error",
+        r"(?i)econnreset|etimedout|econnrefused|socket hang up|connection (reset|refused|closed)",
+    ),
+    ("request timed out", r"(?i)request timed out|timeout error"),
+];
+
+fn signatures() -> &'static [(&'static str, Regex)] {
+    static COMPILED: OnceLock<Vec<(&str, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        SIGNATURES
+            .iter()
+            .filter_map(|(label, pattern)| Regex::new(pattern).ok().map(|re| (*label, re)))
+            .collect()
+    })
+}
+
+/// The kind of transient error in a failed invocation's `stderr` or result
+/// text (e.g. `"overloaded"`, `"rate limited"`), or `None` if it looks like
+/// an ordinary failure.
+#[must_use]
+pub fn detect_transient(stderr: &str, result_text: Option<&str>) -> Option<&'static str> {
+    let texts = [stderr, result_text.unwrap_or("")];
+    signatures()
+        .iter()
+        .find(|(_, re)| texts.iter().any(|text| re.is_match(text)))
+        .map(|(label, _)| *label)
+}
+
+/// Delay before retry number `attempt` (0-based): `base_secs` doubled per
+/// attempt, capped at [`MAX_BACKOFF_SECS`].
+#[must_use]
+pub fn backoff_secs(base_secs: u64, attempt: u32) -> u64 {
+    base_secs
+        .saturating_mul(2u64.saturating_pow(attempt))
+        .min(MAX_BACKOFF_SECS)
+}
+
+/// Longest wait between transient-error retries
+pub const MAX_BACKOFF_SECS: u64 = 600;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // --- detect_transient tests ---
+
+    #[test]
+    fn test_detects_api_error_signatures() {
+        let overloaded = r#"API Error: 529 {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        assert_eq!(detect_transient("", Some(overloaded)), Some("overloaded"));
+        assert_eq!(
+            detect_transient("Error: 429 Too Many Requests", None),
+            Some("rate limited")
+        );
+        assert_eq!(
+            detect_transient("", Some("API Error: 503 Service Unavailable")),
+            Some("server error")
+        );
+        assert_eq!(
+            detect_transient("request failed: read ECONNRESET", None),
+            Some("connection error")
+        );
+    }
+
+    #[test]
+    fn test_ordinary_failures_are_not_transient() {
+        assert_eq!(detect_transient("", None), None);
+        assert_eq!(
+            detect_transient(
+                "error[E0425]: cannot find value `x`",
+                Some("Tests failed: 500 passed, 3 failed")
+            ),
+            None
+        );
+        assert_eq!(detect_transient("", Some("Reached max turns (50)")), None);
+    }
+
+    // --- backoff_secs tests ---
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        assert_eq!(backoff_secs(30, 0), 30);
+        assert_eq!(backoff_secs(30, 1), 60);
+        assert_eq!(backoff_secs(30, 2), 120);
+        assert_eq!(backoff_secs(30, 10), MAX_BACKOFF_SECS);
+        assert_eq!(backoff_secs(u64::MAX, 40), MAX_BACKOFF_SECS);
+    }
+}
@@ -3,9 +3,16 @@
 //! Renders stream-JSON events as human-readable terminal output.
 //! All output goes to stderr so stdout remains clean for piping.
 
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
 use colored::Colorize;
 
-use crate::claude::stream::StreamEvent;
+use crate::claude::pricing::{price_for, usage_cost};
+use crate::claude::stream::{StreamEvent, TokenUsage};
+use crate::cli::theme::{theme, Role};
+use crate::cycle::config::ModelPrice;
 
 /// Truncate a string to at most `max_chars` Unicode characters, appending "..." if truncated.
 fn truncate(s: &str, max_chars: usize) -> String {
@@ -18,9 +25,37 @@ fn truncate(s: &str, max_chars: usize) -> String {
     }
 }
 
+/// Where a step sits in its cycle, rendered as `step 2/4: implement`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepProgress {
+    /// 1-based position among the cycle's steps
+    pub position: usize,
+    /// Number of steps in the cycle
+    pub total: usize,
+    /// Step name
+    pub name: String,
+    /// Visit number within this cycle execution (1 on the first run)
+    pub visit: u32,
+}
+
+impl fmt::Display for StepProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {}/{}: {}", self.position, self.total, self.name)?;
+        if self.visit > 1 {
+            write!(f, " (visit {})", self.visit)?;
+        }
+        Ok(())
+    }
+}
+
 /// Display handler for cycle execution output
 pub struct CycleDisplay {
     cycle_name: String,
+    verbose: bool,
+    /// Suppress all output (embedders observing events instead)
+    quiet: bool,
+    /// Step currently running in a multi-step cycle
+    step: Mutex<Option<StepProgress>>,
 }
 
 impl CycleDisplay {
@@ -29,38 +64,126 @@ impl CycleDisplay {
     pub fn new(cycle_name: &str) -> Self {
         Self {
             cycle_name: cycle_name.to_string(),
+            verbose: false,
+            quiet: false,
+            step: Mutex::new(None),
         }
     }
 
+    /// Enable verbose output (thinking blocks and other detail hidden by default)
+    #[must_use]
+    pub const fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Suppress all output from this display
+    #[must_use]
+    pub const fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
     /// Print the cycle header at the start of execution
     pub fn print_header(&self) {
+        if self.quiet {
+            return;
+        }
+        let theme = theme();
         eprintln!(
-            "\n{} {}",
-            "===".bold().cyan(),
-            format!("Cycle: {}", self.cycle_name).bold().cyan()
+            "\n{}{} {}",
+            theme.timestamp(),
+            theme.paint(Role::Header, "===").bold(),
+            theme
+                .paint(Role::Header, &format!("Cycle: {}", self.cycle_name))
+                .bold()
         );
-        eprintln!("{}", "─".repeat(50).dimmed());
+        eprintln!("{}", theme.paint(Role::Muted, &theme.rule(50)));
+    }
+
+    /// Print a step's header and label the rest of its output with it
+    pub fn print_step_header(&self, step: StepProgress) {
+        if !self.quiet {
+            let theme = theme();
+            let title = format!("{} {} {step}", self.cycle_name, theme.glyphs.separator);
+            eprintln!(
+                "{}{} {}",
+                theme.timestamp(),
+                theme.paint(Role::Header, "---").bold(),
+                theme.paint(Role::Header, &title).bold()
+            );
+        }
+        *self.step.lock().unwrap_or_else(PoisonError::into_inner) = Some(step);
+    }
+
+    /// The cycle name, followed by the running step if there is one
+    fn label(&self) -> String {
+        self.step
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map_or_else(
+                || self.cycle_name.clone(),
+                |step| format!("{} {} {step}", self.cycle_name, theme().glyphs.separator),
+            )
     }
 
     /// Render a stream event to stderr
     pub fn render_event(&self, event: &StreamEvent) {
+        if self.quiet {
+            return;
+        }
+        let theme = theme();
+        let ts = theme.timestamp();
+        let glyphs = theme.glyphs;
         match event {
             StreamEvent::SystemInit { model, .. } => {
-                eprintln!("  {} {}", "Model:".dimmed(), model);
+                eprintln!("{ts}  {} {}", theme.paint(Role::Muted, "Model:"), model);
             }
             StreamEvent::AssistantText { text } => {
-                eprintln!("  {}", truncate(text, 500));
+                eprintln!("{ts}  {}", truncate(text, 500));
+            }
+            StreamEvent::Thinking { text } => {
+                if self.verbose {
+                    eprintln!("{ts}  {}", truncate(text, 500).dimmed().italic());
+                }
             }
             StreamEvent::ToolUse { tool_name, input } => {
                 let summary = summarize_tool_input(tool_name, input);
-                eprintln!("  {} {}{}", "▶".blue(), tool_name.bold(), summary.dimmed());
+                eprintln!(
+                    "{ts}  {} {}{}",
+                    theme.paint(Role::Tool, &glyphs.tool.to_string()),
+                    tool_name.bold(),
+                    summary.dimmed()
+                );
+                if self.verbose {
+                    if let Some(diff) = summarize_edit_diff(tool_name, input) {
+                        eprintln!("{ts}    {}", diff.dimmed());
+                    }
+                }
             }
             StreamEvent::ToolResult {
                 is_error: true,
                 content,
             } => {
-                eprintln!("  {} {}", "✗".red().bold(), truncate(content, 200).red());
+                eprintln!(
+                    "{ts}  {} {}",
+                    theme.paint(Role::Error, &glyphs.error.to_string()).bold(),
+                    theme.paint(Role::Error, &truncate(content, 200))
+                );
+            }
+            StreamEvent::SubagentStart {
+                description,
+                subagent_type,
+            } => {
+                eprintln!(
+                    "{ts}  {} {} {}",
+                    theme.paint(Role::Subagent, &glyphs.tool.to_string()),
+                    format!("Task[{subagent_type}]").bold(),
+                    description.dimmed()
+                );
             }
+            StreamEvent::Subagent { event, .. } => self.render_subagent_event(event),
             StreamEvent::Result {
                 is_error,
                 result_text,
@@ -68,6 +191,7 @@ impl CycleDisplay {
                 total_cost_usd,
                 duration_ms,
                 permission_denials,
+                usage: _,
             } => {
                 self.render_result_summary(
                     *is_error,
@@ -83,6 +207,57 @@ impl CycleDisplay {
         }
     }
 
+    /// Render an event from inside a subagent, indented under its `Task` line
+    fn render_subagent_event(&self, event: &StreamEvent) {
+        let theme = theme();
+        let glyphs = theme.glyphs;
+        let indent = format!(
+            "{}    {}",
+            theme.timestamp(),
+            theme.paint(Role::Subagent, &glyphs.nest.to_string())
+        );
+        match event {
+            StreamEvent::AssistantText { text } => {
+                eprintln!("{indent} {}", truncate(text, 200).dimmed());
+            }
+            StreamEvent::Thinking { text } if self.verbose => {
+                eprintln!("{indent} {}", truncate(text, 200).dimmed().italic());
+            }
+            StreamEvent::ToolUse { tool_name, input } => {
+                let summary = summarize_tool_input(tool_name, input);
+                eprintln!(
+                    "{indent} {} {}{}",
+                    theme.paint(Role::Tool, &glyphs.tool.to_string()),
+                    tool_name,
+                    summary.dimmed()
+                );
+            }
+            StreamEvent::ToolResult {
+                is_error: true,
+                content,
+            } => {
+                eprintln!(
+                    "{indent} {} {}",
+                    theme.paint(Role::Error, &glyphs.error.to_string()),
+                    theme.paint(Role::Error, &truncate(content, 200))
+                );
+            }
+            StreamEvent::Result {
+                is_error,
+                total_cost_usd,
+                ..
+            } => {
+                let status = if *is_error { "failed" } else { "done" };
+                eprintln!(
+                    "{}    {} subagent {status} (${total_cost_usd:.2})",
+                    theme.timestamp(),
+                    theme.paint(Role::Subagent, &glyphs.nest_end.to_string())
+                );
+            }
+            _ => {}
+        }
+    }
+
     /// Render the post-cycle summary
     fn render_result_summary(
         &self,
@@ -93,14 +268,15 @@ impl CycleDisplay {
         duration_ms: u64,
         permission_denials: &[String],
     ) {
-        eprintln!("{}", "─".repeat(50).dimmed());
+        let theme = theme();
+        eprintln!("{}", theme.paint(Role::Muted, &theme.rule(50)));
 
         let status = if is_error {
-            "FAILED".red().bold().to_string()
+            theme.paint(Role::Error, "FAILED").bold()
         } else {
-            "COMPLETED".green().bold().to_string()
+            theme.paint(Role::Success, "COMPLETED").bold()
         };
-        eprintln!("  {} {}", status, self.cycle_name.bold());
+        eprintln!("  {} {}", status, self.label().bold());
 
         if !result_text.is_empty() {
             eprintln!("  {}", truncate(result_text, 500));
@@ -110,14 +286,16 @@ impl CycleDisplay {
         let duration = format_duration(duration_ms / 1000);
         eprintln!(
             "  {} {num_turns} turns | ${total_cost_usd:.2} | {duration}",
-            "Stats:".dimmed()
+            theme.paint(Role::Muted, "Stats:")
         );
 
         // Permission denials
         if !permission_denials.is_empty() {
             eprintln!(
                 "  {} {} permission denial(s)",
-                "⚠".yellow().bold(),
+                theme
+                    .paint(Role::Warning, &theme.glyphs.warning.to_string())
+                    .bold(),
                 permission_denials.len()
             );
         }
@@ -129,10 +307,14 @@ impl CycleDisplay {
 /// Summarize tool input as a short one-line string
 fn summarize_tool_input(tool_name: &str, input: &serde_json::Value) -> String {
     match tool_name {
-        "Edit" | "Read" | "Write" => input
+        "Edit" | "MultiEdit" | "Read" | "Write" => input
             .get("file_path")
             .and_then(serde_json::Value::as_str)
             .map_or_else(String::new, |p| format!(" {p}")),
+        "NotebookEdit" => input
+            .get("notebook_path")
+            .and_then(serde_json::Value::as_str)
+            .map_or_else(String::new, |p| format!(" {p}")),
         "Bash" => input
             .get("command")
             .and_then(serde_json::Value::as_str)
@@ -149,6 +331,120 @@ fn summarize_tool_input(tool_name: &str, input: &serde_json::Value) -> String {
     }
 }
 
+/// Summarize the change an `Edit`/`MultiEdit`/`Write` call makes, e.g.
+/// `-2 +3 lines: fn old() → fn new()` or `+40 lines`.
+///
+/// Returns `None` for other tools or when the input lacks the expected fields.
+fn summarize_edit_diff(tool_name: &str, input: &serde_json::Value) -> Option<String> {
+    let str_field = |v: &serde_json::Value, key: &str| {
+        v.get(key)
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string)
+    };
+    match tool_name {
+        "Edit" => {
+            let old = str_field(input, "old_string")?;
+            let new = str_field(input, "new_string")?;
+            Some(format!(
+                "-{} +{} lines: {} → {}",
+                old.lines().count(),
+                new.lines().count(),
+                first_line_preview(&old),
+                first_line_preview(&new)
+            ))
+        }
+        "MultiEdit" => {
+            let edits = input.get("edits")?.as_array()?;
+            let (removed, added) = edits.iter().fold((0, 0), |(r, a), edit| {
+                let count = |key| str_field(edit, key).map_or(0, |s| s.lines().count());
+                (r + count("old_string"), a + count("new_string"))
+            });
+            Some(format!("{} edits, -{removed} +{added} lines", edits.len()))
+        }
+        "Write" => {
+            let content = str_field(input, "content")?;
+            Some(format!("+{} lines", content.lines().count()))
+        }
+        _ => None,
+    }
+}
+
+/// First non-blank line of `s`, trimmed and truncated for a one-line preview.
+fn first_line_preview(s: &str) -> String {
+    let line = s
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("");
+    truncate(line, 40)
+}
+
+/// Render a one-line summary of what a cycle did (files, lines, commands, tests).
+///
+/// Returns `None` when the cycle produced no observable activity.
+#[must_use]
+pub fn render_activity_summary(result: &crate::cycle::executor::CycleResult) -> Option<String> {
+    let mut parts = Vec::new();
+    if !result.files_changed.is_empty() {
+        parts.push(format!("{} file(s) changed", result.files_changed.len()));
+    }
+    if let (Some(added), Some(removed)) = (result.lines_added, result.lines_removed) {
+        if added + removed > 0 {
+            parts.push(format!("+{added}/-{removed} lines"));
+        }
+    }
+    if !result.commands_run.is_empty() {
+        parts.push(format!("{} command(s) run", result.commands_run.len()));
+    }
+    if let Some(count) = result.subagent_count {
+        let cost = result.subagent_cost_usd.unwrap_or_default();
+        parts.push(format!("{count} subagent(s) (${cost:.2})"));
+    }
+    if result.tests_passed > 0 {
+        parts.push(format!("{} tests passed", result.tests_passed));
+    }
+    if result.tests_failed > 0 {
+        parts.push(
+            theme()
+                .paint(
+                    Role::Error,
+                    &format!("{} tests failed", result.tests_failed),
+                )
+                .to_string(),
+        );
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "  {} {}",
+        theme().paint(Role::Muted, "Activity:"),
+        parts.join(" | ")
+    ))
+}
+
+/// Render a one-line breakdown of the tools a cycle used, most used first:
+/// `Tools: Read×88, Bash×42, Edit×17`.
+///
+/// Returns `None` when no tools were used.
+#[must_use]
+pub fn render_tool_summary(result: &crate::cycle::executor::CycleResult) -> Option<String> {
+    if result.tools_used.is_empty() {
+        return None;
+    }
+    let mut tools: Vec<(&String, &u32)> = result.tools_used.iter().collect();
+    tools.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let counts: Vec<String> = tools
+        .into_iter()
+        .map(|(tool, count)| format!("{tool}×{count}"))
+        .collect();
+    Some(format!(
+        "  {} {}",
+        theme().paint(Role::Muted, "Tools:"),
+        counts.join(", ")
+    ))
+}
+
 /// Render a diagnostic report as a human-readable string.
 ///
 /// Formats findings by severity with codes, messages, and suggestions.
@@ -201,6 +497,155 @@ pub fn render_diagnostic_report(report: &crate::doctor::DiagnosticReport) -> Str
     lines.join("\n")
 }
 
+/// Render persisted sessions (`flow sessions list`) as an aligned table.
+#[must_use]
+pub fn render_sessions(
+    sessions: &std::collections::BTreeMap<String, crate::claude::session::PersistedSession>,
+) -> String {
+    if sessions.is_empty() {
+        return "No persisted sessions.".to_string();
+    }
+    let tag_width = sessions.keys().map(String::len).max().unwrap_or(0).max(3);
+    let cycle_width = sessions
+        .values()
+        .map(|s| s.cycle.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let mut lines = vec![format!(
+        "{:<tag_width$}  {:<cycle_width$}  {:<20}  SESSION ID",
+        "TAG", "CYCLE", "LAST USED"
+    )];
+    for (tag, saved) in sessions {
+        lines.push(format!(
+            "{tag:<tag_width$}  {:<cycle_width$}  {:<20}  {}",
+            saved.cycle,
+            saved.last_used.format("%Y-%m-%d %H:%M UTC").to_string(),
+            saved.session_id
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Render what `flow gc` removed (or, with `dry_run`, would remove).
+#[must_use]
+pub fn render_gc_report(report: &crate::gc::GcReport, dry_run: bool) -> String {
+    if report.is_empty() {
+        return "Nothing to clean up.".to_string();
+    }
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    let mut lines: Vec<String> = report
+        .removals
+        .iter()
+        .map(|r| format!("{verb} {} ({})", r.path.display(), r.reason))
+        .collect();
+    if !report.pruned_sessions.is_empty() {
+        let verb = if dry_run { "Would prune" } else { "Pruned" };
+        lines.push(format!(
+            "{verb} {} stale session(s): {}",
+            report.pruned_sessions.len(),
+            report.pruned_sessions.join(", ")
+        ));
+    }
+    #[allow(clippy::cast_precision_loss)] // display only
+    let kib = report.bytes_freed() as f64 / 1024.0;
+    lines.push(format!(
+        "{} {} file(s), {kib:.1} KiB",
+        if dry_run { "Would free" } else { "Freed" },
+        report.removals.len()
+    ));
+    lines.join("\n")
+}
+
+/// Render a permission drift report (`flow permissions diff`): per cycle, the
+/// grants no logged run exercised and the tools it keeps being denied.
+#[must_use]
+pub fn render_permission_drift(report: &crate::drift::DriftReport) -> String {
+    let mut lines = Vec::new();
+    for cycle in &report.cycles {
+        if cycle.runs == 0 {
+            lines.push(format!("{}: no logged runs", cycle.cycle));
+            continue;
+        }
+        let clean = cycle.unused.is_empty() && cycle.denied.is_empty();
+        let status = if clean && cycle.runs_with_usage > 0 {
+            ": no drift"
+        } else {
+            ""
+        };
+        lines.push(format!("{} ({} run(s)){status}", cycle.cycle, cycle.runs));
+        if cycle.runs_with_usage == 0 {
+            lines.push("  no tool usage logged; unused grants can't be checked".to_string());
+        }
+        for perm in &cycle.unused {
+            lines.push(format!("  unused grant: {perm} (consider removing)"));
+        }
+        for denied in &cycle.denied {
+            lines.push(format!(
+                "  denied {}×: {} (consider granting {})",
+                denied.count, denied.tool, denied.suggestion
+            ));
+        }
+    }
+    for perm in &report.unused_global {
+        lines.push(format!(
+            "[global] unused grant: {perm} (no cycle used it; consider removing)"
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Render per-cycle totals and rubric trends for `flow stats`.
+#[must_use]
+pub fn render_cycle_trends(trends: &[crate::stats::CycleTrend], window: usize) -> String {
+    if trends.is_empty() {
+        return "No logged runs.".to_string();
+    }
+    let mut lines = Vec::new();
+    for trend in trends {
+        let rate = trend.successes * 100 / trend.runs.max(1);
+        lines.push(format!(
+            "{}: {} run(s), {rate}% success, ${:.2}",
+            trend.cycle, trend.runs, trend.total_cost_usd
+        ));
+        for criterion in &trend.criteria {
+            let recent_runs = criterion.scored_runs.min(window.max(1));
+            let change = criterion.delta().map_or_else(String::new, |delta| {
+                let arrow = if delta > 0.05 {
+                    "↑"
+                } else if delta < -0.05 {
+                    "↓"
+                } else {
+                    "→"
+                };
+                format!(" ({arrow} {delta:+.1} vs the {recent_runs} before)")
+            });
+            lines.push(format!(
+                "  {}: {:.1}/5 over the last {recent_runs}{change}",
+                criterion.criterion, criterion.recent
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Render a diagnostic report as pretty-printed JSON for machine consumption.
+///
+/// Emits every finding (severity, code, message, suggestion, cycle) plus a
+/// summary of counts per severity, so CI pipelines can gate without parsing text.
+#[must_use]
+pub fn render_diagnostic_json(report: &crate::doctor::DiagnosticReport) -> String {
+    let value = serde_json::json!({
+        "findings": report.findings,
+        "summary": {
+            "errors": report.error_count(),
+            "warnings": report.warning_count(),
+            "info": report.info_count(),
+        },
+    });
+    serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string())
+}
+
 /// Format a duration in seconds as a human-readable string (e.g. "2m 15s", "30s", "5m").
 pub(crate) fn format_duration(secs: u64) -> String {
     let mins = secs / 60;
@@ -214,11 +659,117 @@ pub(crate) fn format_duration(secs: u64) -> String {
     }
 }
 
+/// Format a token count compactly: `950`, `12.3k`, `1.2M`.
+#[allow(clippy::cast_precision_loss)] // display only
+fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1_000.0),
+        _ => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}
+
+/// Format an ETA as `~1h 05m (finishes ~14:32)`.
+///
+/// The finish time is shown in local time, prefixed with the weekday when it
+/// falls on a different day than `now`.
+#[must_use]
+pub fn format_eta(remaining_secs: u64, now: chrono::DateTime<chrono::Local>) -> String {
+    let finish = now + chrono::Duration::seconds(i64::try_from(remaining_secs).unwrap_or(i64::MAX));
+    let clock = if finish.date_naive() == now.date_naive() {
+        finish.format("%H:%M")
+    } else {
+        finish.format("%a %H:%M")
+    };
+    let hours = remaining_secs / 3600;
+    let remaining = if hours == 0 {
+        format_duration(remaining_secs)
+    } else {
+        format!("{hours}h {:02}m", (remaining_secs % 3600) / 60)
+    };
+    format!("~{remaining} (finishes ~{clock})")
+}
+
+/// Render a run's progress for `flow watch-progress`:
+/// `[3/20] coding · running · 7m 25s · $3.45 · ETA ~5m (finishes ~14:32)`.
+///
+/// A stopped or failed run says why instead of estimating a finish.
+#[must_use]
+pub fn render_watch_progress(
+    progress: &crate::log::RunProgress,
+    now: chrono::DateTime<chrono::Local>,
+) -> String {
+    use crate::log::RunStatus;
+
+    let theme = theme();
+    let (status, role) = match progress.current_status {
+        RunStatus::Running => ("running", Role::Header),
+        RunStatus::Completed => ("completed", Role::Success),
+        RunStatus::Failed => ("failed", Role::Error),
+        RunStatus::Stopped => ("stopped", Role::Warning),
+    };
+    let now_utc = now.with_timezone(&chrono::Utc);
+    let elapsed = u64::try_from((now_utc - progress.started_at).num_seconds()).unwrap_or(0);
+    let mut parts = vec![
+        progress.current_cycle.bold().to_string(),
+        theme.paint(role, status).to_string(),
+        format_duration(elapsed),
+        format!("${:.2}", progress.total_cost_usd),
+    ];
+    if let Some(ref step) = progress.failed_step {
+        parts.push(format!("failed at step '{step}'"));
+    }
+    if let Some(ref reason) = progress.stop_reason {
+        parts.push(reason.clone());
+    } else if progress.current_status == RunStatus::Running {
+        if let Some(remaining) = progress.estimated_remaining_secs(now_utc) {
+            parts.push(format!("ETA {}", format_eta(remaining, now)));
+        }
+    }
+    format!(
+        "{}[{}/{}] {}",
+        theme.timestamp(),
+        progress.current_iteration,
+        progress.max_iterations,
+        parts.join(&format!(" {} ", theme.glyphs.separator))
+    )
+}
+
+/// Render a logged cycle for `flow watch-progress`:
+/// `done #3 coding · 2m 15s · $0.45 · 8 turns: Added the parser`.
+#[must_use]
+pub fn render_watch_outcome(outcome: &crate::log::CycleOutcome) -> String {
+    let theme = theme();
+    let status = if outcome.is_success() {
+        theme.paint(Role::Success, "done")
+    } else {
+        theme.paint(Role::Error, "failed")
+    };
+    let mut parts = vec![
+        format!("#{} {}", outcome.iteration, outcome.cycle.bold()),
+        format_duration(outcome.duration_secs),
+    ];
+    if let Some(cost) = outcome.total_cost_usd {
+        parts.push(format!("${cost:.2}"));
+    }
+    if let Some(turns) = outcome.num_turns {
+        parts.push(format!("{turns} turns"));
+    }
+    format!(
+        "{}  {status} {}: {}",
+        theme.timestamp(),
+        parts.join(&format!(" {} ", theme.glyphs.separator)),
+        truncate(&outcome.outcome, 120)
+    )
+}
+
 /// Render a periodic run summary as a compact multi-line block.
 ///
 /// Displayed every N iterations during multi-iteration runs to give users
 /// an aggregated view of progress, cost, cycle mix, and success rate.
+/// `eta` (from [`format_eta`]) adds an estimated-finish line.
 #[must_use]
+#[allow(clippy::too_many_arguments)]
 pub fn render_run_summary(
     iteration: u32,
     max_iterations: u32,
@@ -227,6 +778,7 @@ pub fn render_run_summary(
     successes: u32,
     failures: u32,
     duration_secs: u64,
+    eta: Option<&str>,
 ) -> String {
     let total = successes + failures;
     let cycle_parts: Vec<String> = cycles
@@ -235,18 +787,109 @@ pub fn render_run_summary(
         .collect();
     let cycles_str = cycle_parts.join(", ");
 
+    let eta_line = eta.map(|eta| format!("ETA: {eta}\n")).unwrap_or_default();
+
     format!(
         "\u{2500}\u{2500}\u{2500} Run Summary ({iteration}/{max_iterations}) \u{2500}\u{2500}\u{2500}\n\
          Cycles: {cycles_str}\n\
          Results: {successes}/{total} succeeded\n\
          Cost: ${total_cost_usd:.2} | Duration: {}\n\
+         {eta_line}\
          \u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}\u{2500}",
         format_duration(duration_secs)
     )
 }
 
-/// Health color for the status bar
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Render the final run summary as Markdown for `.flow/last-run-summary.md`.
+///
+/// `outcomes` are the log entries written during this run; they drive the
+/// per-cycle table (runs, successes, failures, cost, duration).
+#[must_use]
+pub fn render_run_summary_markdown(
+    progress: &crate::log::progress::RunProgress,
+    outcomes: &[crate::log::CycleOutcome],
+) -> String {
+    use crate::log::progress::RunStatus;
+    use std::fmt::Write;
+
+    #[derive(Default)]
+    struct CycleRow {
+        runs: u32,
+        failures: u32,
+        cost_usd: f64,
+        duration_secs: u64,
+        lines_added: u32,
+        lines_removed: u32,
+    }
+
+    let mut rows: std::collections::BTreeMap<&str, CycleRow> = std::collections::BTreeMap::new();
+    for outcome in outcomes {
+        let row = rows.entry(outcome.cycle.as_str()).or_default();
+        row.runs += 1;
+        if !outcome.is_success() {
+            row.failures += 1;
+        }
+        row.cost_usd += outcome.total_cost_usd.unwrap_or(0.0);
+        row.duration_secs += outcome.duration_secs;
+        row.lines_added += outcome.lines_added.unwrap_or(0);
+        row.lines_removed += outcome.lines_removed.unwrap_or(0);
+    }
+
+    let status = match progress.current_status {
+        RunStatus::Running => "running",
+        RunStatus::Completed => "completed",
+        RunStatus::Failed => "failed",
+        RunStatus::Stopped => "stopped",
+    };
+    let successes = outcomes.iter().filter(|o| o.is_success()).count();
+    let total_cost: f64 = rows.values().map(|r| r.cost_usd).sum();
+    let total_duration: u64 = rows.values().map(|r| r.duration_secs).sum();
+    let lines_added: u32 = rows.values().map(|r| r.lines_added).sum();
+    let lines_removed: u32 = rows.values().map(|r| r.lines_removed).sum();
+
+    let mut out = String::from("# Flow Run Summary\n\n");
+    let _ = writeln!(
+        out,
+        "- Started: {}",
+        progress.started_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    let _ = writeln!(out, "- Status: {status}");
+    if let Some(ref reason) = progress.stop_reason {
+        let _ = writeln!(out, "- Stop reason: {reason}");
+    }
+    let _ = writeln!(
+        out,
+        "- Iterations: {}/{}",
+        outcomes.len(),
+        progress.max_iterations
+    );
+    let _ = writeln!(out, "- Results: {successes}/{} succeeded", outcomes.len());
+    let _ = writeln!(out, "- Cost: ${total_cost:.2}");
+    let _ = writeln!(out, "- Duration: {}", format_duration(total_duration));
+    let _ = writeln!(out, "- Lines: +{lines_added}/-{lines_removed}");
+
+    if !rows.is_empty() {
+        out.push_str("\n| Cycle | Runs | Succeeded | Failed | Cost | Duration | Lines |\n");
+        out.push_str("|-------|------|-----------|--------|------|----------|-------|\n");
+        for (name, row) in &rows {
+            let _ = writeln!(
+                out,
+                "| {name} | {} | {} | {} | ${:.2} | {} | +{}/-{} |",
+                row.runs,
+                row.runs - row.failures,
+                row.failures,
+                row.cost_usd,
+                format_duration(row.duration_secs),
+                row.lines_added,
+                row.lines_removed
+            );
+        }
+    }
+    out
+}
+
+/// Health color for the status bar, ordered from healthy to critical
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) enum HealthColor {
     /// Healthy: 0 errors
     Green,
@@ -259,7 +902,8 @@ pub(crate) enum HealthColor {
 /// Live status bar displayed at the bottom of the terminal during cycle execution.
 ///
 /// Tracks turn count, cost, elapsed time, and error count from stream events.
-/// Renders a single ANSI-formatted line using save/restore cursor positioning.
+/// Reserves the bottom row of the terminal with a scroll region so regular
+/// output scrolls above it. Does nothing when stderr is not a terminal.
 pub struct StatusLine {
     cycle_name: String,
     turn_count: u32,
@@ -268,8 +912,31 @@ pub struct StatusLine {
     start: std::time::Instant,
     /// Optional `(current_iteration, max_iterations)` for multi-iteration runs.
     iteration_context: Option<(u32, u32)>,
+    /// Current spinner frame; `None` until the first [`StatusLine::tick`].
+    spinner_frame: Option<usize>,
+    /// Terminal height the scroll region was set up for, if one is active.
+    scroll_region_rows: Option<u16>,
+    /// Cost limit for this invocation (`max_cost_usd`), shown as `$spent / $limit`.
+    budget_usd: Option<f64>,
+    /// Never draw the status line (output disabled by an embedder)
+    hidden: bool,
+    /// Step being run, shown after the cycle name in multi-step cycles
+    step: Option<StepProgress>,
+    /// `(turns, errors)` last printed as a plain line, on non-ANSI terminals
+    plain_printed: Option<(u32, u32)>,
+    /// Token prices (`[pricing]`) for estimating cost before the result arrives
+    pricing: HashMap<String, ModelPrice>,
+    /// Price of the model this invocation runs, once the init event names it
+    price: Option<ModelPrice>,
+    /// Latest usage of each assistant message so far, by message ID
+    message_usage: HashMap<String, TokenUsage>,
+    /// Whether `cost_usd` is the reported final cost rather than a live estimate
+    cost_final: bool,
 }
 
+/// Fraction of the budget at which the status line turns yellow.
+const BUDGET_WARNING_RATIO: f64 = 0.8;
+
 impl StatusLine {
     /// Create a new status line for the given cycle
     #[must_use]
@@ -281,6 +948,16 @@ impl StatusLine {
             error_count: 0,
             start: std::time::Instant::now(),
             iteration_context: None,
+            spinner_frame: None,
+            scroll_region_rows: None,
+            budget_usd: None,
+            hidden: false,
+            plain_printed: None,
+            step: None,
+            pricing: HashMap::new(),
+            price: None,
+            message_usage: HashMap::new(),
+            cost_final: false,
         }
     }
 
@@ -296,6 +973,16 @@ impl StatusLine {
             error_count: 0,
             start: std::time::Instant::now(),
             iteration_context: Some((current, max)),
+            spinner_frame: None,
+            scroll_region_rows: None,
+            budget_usd: None,
+            hidden: false,
+            plain_printed: None,
+            step: None,
+            pricing: HashMap::new(),
+            price: None,
+            message_usage: HashMap::new(),
+            cost_final: false,
         }
     }
 
@@ -309,6 +996,16 @@ impl StatusLine {
             error_count: 0,
             start,
             iteration_context: None,
+            spinner_frame: None,
+            scroll_region_rows: None,
+            budget_usd: None,
+            hidden: false,
+            plain_printed: None,
+            step: None,
+            pricing: HashMap::new(),
+            price: None,
+            message_usage: HashMap::new(),
+            cost_final: false,
         }
     }
 
@@ -327,13 +1024,80 @@ impl StatusLine {
             error_count: 0,
             start,
             iteration_context: Some((current, max)),
+            spinner_frame: None,
+            scroll_region_rows: None,
+            budget_usd: None,
+            hidden: false,
+            plain_printed: None,
+            step: None,
+            pricing: HashMap::new(),
+            price: None,
+            message_usage: HashMap::new(),
+            cost_final: false,
+        }
+    }
+
+    /// Show spend against a cost limit, e.g. `$1.20 / $5.00`.
+    #[must_use]
+    pub const fn with_budget(mut self, budget_usd: Option<f64>) -> Self {
+        self.budget_usd = budget_usd;
+        self
+    }
+
+    /// Never draw this status line.
+    #[must_use]
+    pub const fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Show the running step after the cycle name, e.g. `[coding] step 2/4: implement`.
+    #[must_use]
+    pub fn with_step(mut self, step: Option<StepProgress>) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Estimate cost from streamed token usage with these prices until the
+    /// final result reports the real cost.
+    #[must_use]
+    pub fn with_pricing(mut self, pricing: &HashMap<String, ModelPrice>) -> Self {
+        self.pricing.clone_from(pricing);
+        self
+    }
+
+    /// Track an assistant message's token usage (see
+    /// [`crate::claude::stream::message_usage`]), updating the live cost
+    /// estimate when the model's price is known.
+    pub fn observe_usage(&mut self, message_id: &str, usage: &TokenUsage) {
+        let latest = self
+            .message_usage
+            .entry(message_id.to_string())
+            .or_default();
+        if usage.total() >= latest.total() {
+            *latest = *usage;
+        }
+        if let (Some(price), false) = (&self.price, self.cost_final) {
+            self.cost_usd = self
+                .message_usage
+                .values()
+                .map(|usage| usage_cost(price, usage))
+                .sum();
         }
     }
 
+    /// Tokens streamed so far, summed over assistant messages
+    fn live_tokens(&self) -> u64 {
+        self.message_usage.values().map(TokenUsage::total).sum()
+    }
+
     /// Update the status line from a stream event
-    pub const fn update(&mut self, event: &StreamEvent) {
+    pub fn update(&mut self, event: &StreamEvent) {
         match event {
-            StreamEvent::ToolUse { .. } => {
+            StreamEvent::SystemInit { model, .. } => {
+                self.price = price_for(&self.pricing, model).copied();
+            }
+            StreamEvent::ToolUse { .. } | StreamEvent::SubagentStart { .. } => {
                 self.turn_count += 1;
             }
             StreamEvent::ToolResult { is_error: true, .. } => {
@@ -346,16 +1110,47 @@ impl StatusLine {
             } => {
                 self.turn_count = *num_turns;
                 self.cost_usd = *total_cost_usd;
+                self.cost_final = true;
             }
             _ => {}
         }
     }
 
+    /// Advance the activity spinner.
+    ///
+    /// Called on a timer so the status line keeps moving (and the elapsed
+    /// time keeps counting) during long tool executions with no stream output.
+    pub fn tick(&mut self) {
+        let frames = theme().glyphs.spinner.len();
+        self.spinner_frame = Some(self.spinner_frame.map_or(0, |frame| (frame + 1) % frames));
+    }
+
     /// Render the status line content (without ANSI cursor positioning).
     ///
-    /// Returns the formatted string like: `[coding] ▶ 12 turns | $1.23 | 2m 15s | 0 errors`
+    /// Returns the formatted string like: `[coding] ▶ 12 turns | $1.23 | 2m 15s | 0 errors`,
+    /// with the step after the cycle name in multi-step cycles. Elapsed time
+    /// and cost are for this invocation, so per step when running a step.
+    /// Until the final result arrives the cost is a live estimate from
+    /// streamed usage (`~$0.42`), or the token count when the model has no
+    /// `[pricing]` entry.
+    /// Once [`StatusLine::tick`] has been called, the `▶` becomes a spinner frame.
     #[must_use]
     pub fn render(&self) -> String {
+        let glyphs = theme().glyphs;
+        let glyph = self
+            .spinner_frame
+            .map_or(glyphs.tool, |frame| glyphs.spinner[frame]);
+        self.render_with_glyph(Some(glyph))
+    }
+
+    /// Render the status line for a console without ANSI support: like
+    /// [`StatusLine::render`] but without the spinner glyph.
+    #[must_use]
+    pub fn render_plain(&self) -> String {
+        self.render_with_glyph(None)
+    }
+
+    fn render_with_glyph(&self, glyph: Option<char>) -> String {
         let elapsed = self.start.elapsed().as_secs();
         let mins = elapsed / 60;
         let secs = elapsed % 60;
@@ -363,22 +1158,46 @@ impl StatusLine {
             Some((current, max)) if max > 1 => format!("[{current}/{max}] "),
             _ => String::new(),
         };
+        let glyph = glyph.map(|g| format!("{g} ")).unwrap_or_default();
+        let budget = self
+            .budget_usd
+            .map(|limit| format!(" / ${limit:.2}"))
+            .unwrap_or_default();
+        let cost = if self.cost_final || self.message_usage.is_empty() {
+            format!("${:.2}{budget}", self.cost_usd)
+        } else if self.price.is_some() {
+            format!("~${:.2}{budget}", self.cost_usd)
+        } else {
+            format!("{} tokens", format_tokens(self.live_tokens()))
+        };
+        let step = self
+            .step
+            .as_ref()
+            .map(|step| format!("{step} "))
+            .unwrap_or_default();
         format!(
-            "{prefix}[{}] \u{25b6} {} turns | ${:.2} | {}m {:02}s | {} errors",
-            self.cycle_name, self.turn_count, self.cost_usd, mins, secs, self.error_count
+            "{prefix}[{}] {step}{glyph}{} turns | {cost} | {}m {:02}s | {} errors",
+            self.cycle_name, self.turn_count, mins, secs, self.error_count
         )
     }
 
-    /// Determine the health level based on error count.
+    /// Determine the health level from error count and budget usage.
     ///
-    /// Returns a color indicator: green (0 errors), yellow (1-2), red (3+).
+    /// Errors: green (0), yellow (1-2), red (3+). Budget: yellow from 80% of
+    /// the limit, red once it is reached. The worse of the two wins.
     #[must_use]
-    const fn health_color(&self) -> HealthColor {
-        match self.error_count {
+    fn health_color(&self) -> HealthColor {
+        let by_errors = match self.error_count {
             0 => HealthColor::Green,
             1..=2 => HealthColor::Yellow,
             _ => HealthColor::Red,
-        }
+        };
+        let by_budget = match self.budget_usd {
+            Some(limit) if self.cost_usd >= limit => HealthColor::Red,
+            Some(limit) if self.cost_usd >= limit * BUDGET_WARNING_RATIO => HealthColor::Yellow,
+            _ => HealthColor::Green,
+        };
+        by_errors.max(by_budget)
     }
 
     /// Render the status line with color-coded health.
@@ -387,35 +1206,172 @@ impl StatusLine {
     /// green (healthy), yellow (warning), red (critical).
     #[must_use]
     pub fn render_colored(&self) -> String {
-        let content = self.render();
+        self.paint(&self.render())
+    }
+
+    /// Apply the health color to already-rendered content.
+    fn paint(&self, content: &str) -> String {
         match self.health_color() {
-            HealthColor::Green => content.green().to_string(),
-            HealthColor::Yellow => content.yellow().to_string(),
-            HealthColor::Red => content.red().bold().to_string(),
+            HealthColor::Green => theme().paint(Role::Success, content).to_string(),
+            HealthColor::Yellow => theme().paint(Role::Warning, content).to_string(),
+            HealthColor::Red => theme().paint(Role::Error, content).bold().to_string(),
+        }
+    }
+
+    /// The plain status line to print, if turns or errors changed since the
+    /// last one (so a non-ANSI console gets a line per change, not per tick).
+    fn next_plain_line(&mut self) -> Option<String> {
+        let snapshot = (self.turn_count, self.error_count);
+        if self.plain_printed == Some(snapshot) {
+            return None;
+        }
+        self.plain_printed = Some(snapshot);
+        Some(self.render_plain())
+    }
+
+    /// Print the status line on the bottom row of the terminal.
+    ///
+    /// On first use (and whenever the terminal height changes) a scroll region
+    /// covering all rows but the last is set up. The line is truncated to the
+    /// terminal width and color-coded by health: green (0 errors), yellow (1-2),
+    /// red (3+). Skipped entirely when stderr is not a terminal. On consoles
+    /// without ANSI support (see [`terminal_mode`]) a plain line is printed
+    /// whenever the turn or error count changes instead.
+    pub fn print(&mut self) {
+        if self.hidden {
+            return;
+        }
+        let Some((rows, cols)) = stderr_size() else {
+            return;
+        };
+        if terminal_mode() == TerminalMode::Plain {
+            if let Some(line) = self.next_plain_line() {
+                eprintln!("{}", fit_width(&line, cols));
+            }
+            return;
+        }
+        if rows < 2 {
+            return;
         }
+        let mut out = String::new();
+        if self.scroll_region_rows != Some(rows) {
+            out.push_str(&scroll_region_setup(rows));
+            self.scroll_region_rows = Some(rows);
+        }
+        let content = self.paint(&fit_width(&self.render(), cols));
+        out.push_str(&bottom_row_write(rows, &content));
+        eprint!("{out}");
     }
 
-    /// Print the status line to the terminal using ANSI escape codes.
+    /// Clear the status line and release the scroll region.
     ///
-    /// Uses save cursor → move to bottom → clear line → print → restore cursor.
-    /// Color-coded based on health: green (0 errors), yellow (1-2), red (3+).
-    pub fn print(&self) {
-        let content = self.render_colored();
-        // Save cursor, move to last row, clear line, print, restore cursor
-        eprint!("\x1b[s\x1b[999;1H\x1b[2K{content}\x1b[u");
+    /// A no-op if the status line was never printed.
+    pub fn clear(&mut self) {
+        if let Some(rows) = self.scroll_region_rows.take() {
+            eprint!("{}", scroll_region_reset(rows));
+        }
+    }
+}
+
+impl Drop for StatusLine {
+    /// Restore the terminal if the cycle bailed out before `clear()` ran.
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// How the terminal can render Flow's live output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalMode {
+    /// ANSI escape sequences work: colors, scroll region, in-place status line
+    Ansi,
+    /// No ANSI support (e.g. legacy Windows consoles): plain status lines, no color
+    Plain,
+}
+
+/// Environment variable forcing the terminal mode (`ansi` or `plain`)
+pub const TERMINAL_MODE_ENV: &str = "FLOW_TERMINAL";
+
+/// Terminal mode of this process, detected once.
+///
+/// On Windows this enables virtual terminal processing for stdout and stderr;
+/// consoles that refuse it fall back to [`TerminalMode::Plain`], as do
+/// `TERM=dumb` terminals. `FLOW_TERMINAL=ansi|plain` overrides detection.
+/// Colors are turned off in plain mode.
+pub fn terminal_mode() -> TerminalMode {
+    static MODE: std::sync::OnceLock<TerminalMode> = std::sync::OnceLock::new();
+    *MODE.get_or_init(|| {
+        let mode = detect_terminal_mode(
+            std::env::var(TERMINAL_MODE_ENV).ok().as_deref(),
+            anstyle_query::windows::enable_ansi_colors(),
+            std::env::var("TERM").ok().as_deref(),
+        );
+        if mode == TerminalMode::Plain {
+            colored::control::set_override(false);
+        }
+        mode
+    })
+}
+
+/// Pick the terminal mode from a `FLOW_TERMINAL` override, the result of
+/// enabling Windows virtual terminal processing (`None` elsewhere), and `TERM`.
+fn detect_terminal_mode(
+    forced: Option<&str>,
+    windows_vt: Option<bool>,
+    term: Option<&str>,
+) -> TerminalMode {
+    match forced.map(str::to_ascii_lowercase).as_deref() {
+        Some("ansi") => return TerminalMode::Ansi,
+        Some("plain") => return TerminalMode::Plain,
+        _ => {}
+    }
+    if windows_vt == Some(false) || term == Some("dumb") {
+        TerminalMode::Plain
+    } else {
+        TerminalMode::Ansi
     }
+}
+
+/// Terminal size of stderr as `(rows, cols)`, or `None` if it isn't a TTY.
+fn stderr_size() -> Option<(u16, u16)> {
+    terminal_size::terminal_size_of(std::io::stderr())
+        .map(|(terminal_size::Width(w), terminal_size::Height(h))| (h, w))
+}
 
-    /// Clear the status line from the terminal.
-    pub fn clear(&self) {
-        // Save cursor, move to last row, clear line, restore cursor
-        eprint!("\x1b[s\x1b[999;1H\x1b[2K\x1b[u");
+/// Fit `s` within a terminal `cols` wide, leaving the last column free so the
+/// terminal never auto-wraps.
+fn fit_width(s: &str, cols: u16) -> String {
+    let max = usize::from(cols).saturating_sub(1);
+    if s.chars().count() <= max {
+        s.to_string()
+    } else {
+        truncate(s, max.saturating_sub(3))
     }
 }
 
+/// Escape sequence reserving the bottom row: make room for it, then limit
+/// scrolling to rows `1..rows-1`. Setting the region homes the cursor, so the
+/// cursor is saved and restored around it.
+fn scroll_region_setup(rows: u16) -> String {
+    format!("\n\x1b[1A\x1b[s\x1b[1;{}r\x1b[u", rows - 1)
+}
+
+/// Escape sequence writing `content` to the bottom row without moving the cursor.
+fn bottom_row_write(rows: u16, content: &str) -> String {
+    format!("\x1b[s\x1b[{rows};1H\x1b[2K{content}\x1b[u")
+}
+
+/// Escape sequence clearing the bottom row and restoring full-screen scrolling.
+fn scroll_region_reset(rows: u16) -> String {
+    format!("\x1b[s\x1b[r\x1b[{rows};1H\x1b[2K\x1b[u")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::claude::stream::TokenUsage;
     use serde_json::json;
+    use std::collections::BTreeMap;
 
     // --- truncate helper tests ---
 
@@ -468,6 +1424,13 @@ mod tests {
     fn test_new_display() {
         let display = CycleDisplay::new("coding");
         assert_eq!(display.cycle_name, "coding");
+        assert!(!display.verbose);
+    }
+
+    #[test]
+    fn test_display_with_verbose() {
+        let display = CycleDisplay::new("coding").with_verbose(true);
+        assert!(display.verbose);
     }
 
     #[test]
@@ -489,24 +1452,78 @@ mod tests {
     }
 
     #[test]
-    fn test_summarize_bash_tool() {
-        let input = json!({"command": "cargo test --lib"});
-        assert_eq!(summarize_tool_input("Bash", &input), " `cargo test --lib`");
+    fn test_summarize_multi_edit_and_notebook_edit_tools() {
+        let input = json!({"file_path": "src/lib.rs", "edits": []});
+        assert_eq!(summarize_tool_input("MultiEdit", &input), " src/lib.rs");
+        let input = json!({"notebook_path": "nb.ipynb"});
+        assert_eq!(summarize_tool_input("NotebookEdit", &input), " nb.ipynb");
     }
 
+    // --- summarize_edit_diff tests ---
+
     #[test]
-    fn test_summarize_bash_long_command_truncated() {
-        let long_cmd = "a".repeat(200);
-        let input = json!({"command": long_cmd});
-        let result = summarize_tool_input("Bash", &input);
-        // " `" + 80 chars + "...`" = 87 chars
-        assert!(result.len() <= 87);
-        assert!(result.ends_with("...`"));
+    fn test_edit_diff_counts_lines_and_previews() {
+        let input = json!({
+            "file_path": "src/lib.rs",
+            "old_string": "fn old() {\n}",
+            "new_string": "\nfn new() {\n    todo!()\n}"
+        });
+        assert_eq!(
+            summarize_edit_diff("Edit", &input).unwrap(),
+            "-2 +4 lines: fn old() { → fn new() {"
+        );
     }
 
     #[test]
-    fn test_summarize_glob_tool() {
-        let input = json!({"pattern": "**/*.rs"});
+    fn test_edit_diff_truncates_long_preview() {
+        let input = json!({"old_string": "a".repeat(100), "new_string": "b"});
+        let diff = summarize_edit_diff("Edit", &input).unwrap();
+        assert!(diff.contains(&format!("{}...", "a".repeat(40))));
+    }
+
+    #[test]
+    fn test_multi_edit_diff_totals() {
+        let input = json!({"edits": [
+            {"old_string": "a", "new_string": "b\nc"},
+            {"old_string": "d\ne", "new_string": "f"}
+        ]});
+        assert_eq!(
+            summarize_edit_diff("MultiEdit", &input).unwrap(),
+            "2 edits, -3 +3 lines"
+        );
+    }
+
+    #[test]
+    fn test_write_diff_counts_lines() {
+        let input = json!({"file_path": "a.rs", "content": "one\ntwo\nthree"});
+        assert_eq!(summarize_edit_diff("Write", &input).unwrap(), "+3 lines");
+    }
+
+    #[test]
+    fn test_edit_diff_none_for_other_tools_or_missing_fields() {
+        assert!(summarize_edit_diff("Read", &json!({"file_path": "a"})).is_none());
+        assert!(summarize_edit_diff("Edit", &json!({"file_path": "a"})).is_none());
+    }
+
+    #[test]
+    fn test_summarize_bash_tool() {
+        let input = json!({"command": "cargo test --lib"});
+        assert_eq!(summarize_tool_input("Bash", &input), " `cargo test --lib`");
+    }
+
+    #[test]
+    fn test_summarize_bash_long_command_truncated() {
+        let long_cmd = "a".repeat(200);
+        let input = json!({"command": long_cmd});
+        let result = summarize_tool_input("Bash", &input);
+        // " `" + 80 chars + "...`" = 87 chars
+        assert!(result.len() <= 87);
+        assert!(result.ends_with("...`"));
+    }
+
+    #[test]
+    fn test_summarize_glob_tool() {
+        let input = json!({"pattern": "**/*.rs"});
         assert_eq!(summarize_tool_input("Glob", &input), " **/*.rs");
     }
 
@@ -537,6 +1554,7 @@ mod tests {
         display.render_event(&StreamEvent::SystemInit {
             model: "claude-opus-4-6".to_string(),
             session_id: "abc".to_string(),
+            claude_version: None,
         });
         display.render_event(&StreamEvent::AssistantText {
             text: "Hello".to_string(),
@@ -560,12 +1578,60 @@ mod tests {
             total_cost_usd: 1.23,
             duration_ms: 30000,
             permission_denials: vec![],
+            usage: TokenUsage::default(),
         });
         display.render_event(&StreamEvent::Unknown {
             event_type: "other".to_string(),
         });
     }
 
+    #[test]
+    fn test_render_subagent_events_no_panic() {
+        let display = CycleDisplay::new("test");
+        display.render_event(&StreamEvent::SubagentStart {
+            description: "Find callers".to_string(),
+            subagent_type: "Explore".to_string(),
+        });
+        for nested in [
+            StreamEvent::AssistantText {
+                text: "Looking".to_string(),
+            },
+            StreamEvent::ToolUse {
+                tool_name: "Grep".to_string(),
+                input: json!({"pattern": "fn main"}),
+            },
+            StreamEvent::ToolResult {
+                is_error: true,
+                content: "denied".to_string(),
+            },
+            StreamEvent::Result {
+                is_error: false,
+                result_text: "found".to_string(),
+                num_turns: 2,
+                total_cost_usd: 0.1,
+                duration_ms: 500,
+                permission_denials: vec![],
+                usage: TokenUsage::default(),
+            },
+        ] {
+            display.render_event(&StreamEvent::Subagent {
+                parent_tool_use_id: "toolu_1".to_string(),
+                event: Box::new(nested),
+            });
+        }
+    }
+
+    #[test]
+    fn test_render_thinking_no_panic() {
+        let thinking = StreamEvent::Thinking {
+            text: "x".repeat(600),
+        };
+        CycleDisplay::new("test").render_event(&thinking);
+        CycleDisplay::new("test")
+            .with_verbose(true)
+            .render_event(&thinking);
+    }
+
     #[test]
     fn test_render_long_assistant_text_truncated_no_panic() {
         let display = CycleDisplay::new("test");
@@ -583,6 +1649,7 @@ mod tests {
             total_cost_usd: 2.50,
             duration_ms: 120_000,
             permission_denials: vec!["Edit".to_string(), "Bash".to_string()],
+            usage: TokenUsage::default(),
         });
     }
 
@@ -661,11 +1728,88 @@ mod tests {
             total_cost_usd: 2.50,
             duration_ms: 60000,
             permission_denials: vec![],
+            usage: TokenUsage::default(),
         });
         assert_eq!(status.turn_count, 15);
         assert!((status.cost_usd - 2.50).abs() < f64::EPSILON);
     }
 
+    fn streamed(output_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens: 1_000,
+            output_tokens,
+            ..TokenUsage::default()
+        }
+    }
+
+    fn init(model: &str) -> StreamEvent {
+        StreamEvent::SystemInit {
+            model: model.to_string(),
+            session_id: "s".to_string(),
+            claude_version: None,
+        }
+    }
+
+    #[test]
+    fn test_status_line_estimates_cost_from_streamed_usage() {
+        let pricing = HashMap::from([(
+            "sonnet".to_string(),
+            ModelPrice {
+                input: 3.0,
+                output: 15.0,
+                ..ModelPrice::default()
+            },
+        )]);
+        let mut status = StatusLine::new("coding").with_pricing(&pricing);
+        status.update(&init("claude-sonnet-4"));
+        status.observe_usage("msg_1", &streamed(100));
+        // Usage repeated on a later content block of the same message isn't double-counted
+        status.observe_usage("msg_1", &streamed(1_000));
+        status.observe_usage("msg_2", &streamed(0));
+        // 2,000 input at $3/M + 1,000 output at $15/M
+        assert!(
+            (status.cost_usd - 0.021).abs() < 1e-9,
+            "{}",
+            status.cost_usd
+        );
+        assert!(
+            status.render_plain().contains("| ~$0.02 |"),
+            "{}",
+            status.render_plain()
+        );
+
+        status.update(&StreamEvent::Result {
+            is_error: false,
+            result_text: "Done".to_string(),
+            num_turns: 2,
+            total_cost_usd: 0.05,
+            duration_ms: 1_000,
+            permission_denials: vec![],
+            usage: TokenUsage::default(),
+        });
+        status.observe_usage("msg_3", &streamed(1_000));
+        assert!(
+            (status.cost_usd - 0.05).abs() < f64::EPSILON,
+            "reported cost wins"
+        );
+        assert!(status.render_plain().contains("| $0.05 |"));
+    }
+
+    #[test]
+    fn test_status_line_shows_tokens_without_a_price() {
+        let mut status = StatusLine::new("coding");
+        assert!(status.render_plain().contains("| $0.00 |"));
+        status.update(&init("claude-opus-4"));
+        status.observe_usage("msg_1", &streamed(11_345));
+        assert!(
+            status.render_plain().contains("| 12.3k tokens |"),
+            "{}",
+            status.render_plain()
+        );
+        assert_eq!(format_tokens(950), "950");
+        assert_eq!(format_tokens(1_250_000), "1.2M");
+    }
+
     #[test]
     fn test_status_line_render_format() {
         let mut status = StatusLine::with_start("gardening", std::time::Instant::now());
@@ -688,6 +1832,41 @@ mod tests {
         assert!(rendered.contains("\u{25b6}")); // ▶ character
     }
 
+    #[test]
+    fn test_status_line_render_shows_step() {
+        let step = StepProgress {
+            position: 2,
+            total: 4,
+            name: "implement".to_string(),
+            visit: 1,
+        };
+        let status = StatusLine::with_start("coding", std::time::Instant::now())
+            .with_step(Some(step.clone()));
+        assert!(
+            status
+                .render_plain()
+                .starts_with("[coding] step 2/4: implement 0 turns"),
+            "{}",
+            status.render_plain()
+        );
+
+        let revisit = StepProgress { visit: 3, ..step };
+        assert_eq!(revisit.to_string(), "step 2/4: implement (visit 3)");
+    }
+
+    #[test]
+    fn test_step_header_labels_result_summary() {
+        let display = CycleDisplay::new("coding").with_quiet(true);
+        assert_eq!(display.label(), "coding");
+        display.print_step_header(StepProgress {
+            position: 1,
+            total: 2,
+            name: "plan".to_string(),
+            visit: 1,
+        });
+        assert_eq!(display.label(), "coding · step 1/2: plan");
+    }
+
     #[test]
     fn test_status_line_health_green_no_errors() {
         let status = StatusLine::new("coding");
@@ -745,6 +1924,240 @@ mod tests {
 
     // --- Doctor display tests ---
 
+    // --- render_activity_summary tests ---
+
+    fn empty_result() -> crate::cycle::executor::CycleResult {
+        crate::cycle::executor::CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stderr: String::new(),
+            duration_secs: 60,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_activity_summary_none_when_idle() {
+        assert!(render_activity_summary(&empty_result()).is_none());
+    }
+
+    #[test]
+    fn test_render_activity_summary_counts() {
+        let mut result = empty_result();
+        result.files_changed = vec!["src/main.rs".to_string()];
+        result.commands_run = vec!["cargo test".to_string(), "git diff".to_string()];
+        result.tests_passed = 12;
+        result.tests_failed = 2;
+        result.lines_added = Some(40);
+        result.lines_removed = Some(7);
+        result.subagent_count = Some(2);
+        result.subagent_cost_usd = Some(0.25);
+
+        let summary = render_activity_summary(&result).unwrap();
+        assert!(summary.contains("1 file(s) changed"));
+        assert!(summary.contains("+40/-7 lines"));
+        assert!(summary.contains("2 command(s) run"));
+        assert!(summary.contains("2 subagent(s) ($0.25)"), "{summary}");
+        assert!(summary.contains("12 tests passed"));
+        assert!(summary.contains("2 tests failed"));
+    }
+
+    #[test]
+    fn test_render_tool_summary_most_used_first() {
+        assert!(render_tool_summary(&empty_result()).is_none());
+
+        let mut result = empty_result();
+        result.tools_used = BTreeMap::from([
+            ("Bash".to_string(), 42),
+            ("Edit".to_string(), 17),
+            ("Read".to_string(), 88),
+        ]);
+        let summary = render_tool_summary(&result).unwrap();
+        assert!(summary.ends_with("Read×88, Bash×42, Edit×17"), "{summary}");
+    }
+
+    #[test]
+    fn test_render_gc_report() {
+        use crate::gc::{GcReport, Removal};
+
+        assert_eq!(
+            render_gc_report(&GcReport::default(), false),
+            "Nothing to clean up."
+        );
+        let report = GcReport {
+            removals: vec![Removal {
+                path: ".flow/debug/coding.log".into(),
+                bytes: 2048,
+                reason: "debug transcript older than 14 day(s)".to_string(),
+            }],
+            pruned_sessions: vec!["architect".to_string()],
+        };
+        assert_eq!(
+            render_gc_report(&report, true),
+            "Would remove .flow/debug/coding.log (debug transcript older than 14 day(s))\n\
+             Would prune 1 stale session(s): architect\n\
+             Would free 1 file(s), 2.0 KiB"
+        );
+    }
+
+    #[test]
+    fn test_render_permission_drift() {
+        use crate::drift::{CycleDrift, DeniedTool, DriftReport};
+
+        let report = DriftReport {
+            cycles: vec![
+                CycleDrift {
+                    cycle: "coding".to_string(),
+                    runs: 4,
+                    runs_with_usage: 4,
+                    unused: vec!["Bash(npm *)".to_string()],
+                    denied: vec![DeniedTool {
+                        tool: "Write".to_string(),
+                        count: 3,
+                        suggestion: "Write(./**) or Write(./src/**)".to_string(),
+                    }],
+                },
+                CycleDrift {
+                    cycle: "review".to_string(),
+                    runs: 2,
+                    runs_with_usage: 0,
+                    unused: vec![],
+                    denied: vec![],
+                },
+                CycleDrift {
+                    cycle: "gardening".to_string(),
+                    runs: 0,
+                    runs_with_usage: 0,
+                    unused: vec![],
+                    denied: vec![],
+                },
+            ],
+            unused_global: vec!["WebFetch".to_string()],
+        };
+        assert_eq!(
+            render_permission_drift(&report),
+            "coding (4 run(s))\n\
+             \x20 unused grant: Bash(npm *) (consider removing)\n\
+             \x20 denied 3×: Write (consider granting Write(./**) or Write(./src/**))\n\
+             review (2 run(s))\n\
+             \x20 no tool usage logged; unused grants can't be checked\n\
+             gardening: no logged runs\n\
+             [global] unused grant: WebFetch (no cycle used it; consider removing)"
+        );
+    }
+
+    #[test]
+    fn test_render_cycle_trends() {
+        use crate::stats::{CriterionTrend, CycleTrend};
+
+        let trends = vec![
+            CycleTrend {
+                cycle: "coding".to_string(),
+                runs: 4,
+                successes: 3,
+                total_cost_usd: 2.5,
+                criteria: vec![
+                    CriterionTrend {
+                        criterion: "Small diff".to_string(),
+                        recent: 4.0,
+                        previous: Some(4.0),
+                        scored_runs: 4,
+                    },
+                    CriterionTrend {
+                        criterion: "Tests cover the change".to_string(),
+                        recent: 2.5,
+                        previous: Some(4.5),
+                        scored_runs: 4,
+                    },
+                ],
+            },
+            CycleTrend {
+                cycle: "review".to_string(),
+                runs: 1,
+                successes: 1,
+                total_cost_usd: 0.0,
+                criteria: vec![CriterionTrend {
+                    criterion: "Actionable".to_string(),
+                    recent: 5.0,
+                    previous: None,
+                    scored_runs: 1,
+                }],
+            },
+        ];
+        assert_eq!(
+            render_cycle_trends(&trends, 2),
+            "coding: 4 run(s), 75% success, $2.50\n\
+             \x20 Small diff: 4.0/5 over the last 2 (→ +0.0 vs the 2 before)\n\
+             \x20 Tests cover the change: 2.5/5 over the last 2 (↓ -2.0 vs the 2 before)\n\
+             review: 1 run(s), 100% success, $0.00\n\
+             \x20 Actionable: 5.0/5 over the last 1"
+        );
+        assert_eq!(render_cycle_trends(&[], 2), "No logged runs.");
+    }
+
+    #[test]
+    fn test_render_sessions_empty() {
+        let sessions = std::collections::BTreeMap::new();
+        assert_eq!(render_sessions(&sessions), "No persisted sessions.");
+    }
+
+    #[test]
+    fn test_render_sessions_lists_each_tag() {
+        use crate::claude::session::PersistedSession;
+        use chrono::TimeZone;
+
+        let sessions = std::collections::BTreeMap::from([(
+            "architect".to_string(),
+            PersistedSession {
+                session_id: "abc-123".to_string(),
+                cycle: "coding".to_string(),
+                last_used: chrono::Utc.with_ymd_and_hms(2026, 3, 1, 12, 30, 0).unwrap(),
+            },
+        )]);
+        let output = render_sessions(&sessions);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("TAG"));
+        assert!(lines[1].starts_with("architect  coding"));
+        assert!(lines[1].contains("2026-03-01 12:30 UTC"));
+        assert!(lines[1].ends_with("abc-123"));
+    }
+
     #[test]
     fn test_render_diagnostic_report_clean() {
         use crate::doctor::DiagnosticReport;
@@ -791,6 +2204,59 @@ mod tests {
         assert!(output.contains("Add Edit(./src/**) to permissions"));
     }
 
+    #[test]
+    fn test_render_diagnostic_json_structure() {
+        use crate::doctor::{DiagnosticReport, Finding, Severity};
+
+        let report = DiagnosticReport {
+            findings: vec![
+                Finding {
+                    severity: Severity::Error,
+                    code: "D001".to_string(),
+                    message: "Permission denied for Edit".to_string(),
+                    suggestion: Some("Add Edit(./src/**) to permissions".to_string()),
+                    cycle_name: Some("coding".to_string()),
+                },
+                Finding {
+                    severity: Severity::Info,
+                    code: "D004".to_string(),
+                    message: "Consider setting min_interval".to_string(),
+                    suggestion: None,
+                    cycle_name: None,
+                },
+            ],
+        };
+        let output = render_diagnostic_json(&report);
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        let findings = value["findings"].as_array().unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0]["severity"], "error");
+        assert_eq!(findings[0]["code"], "D001");
+        assert_eq!(
+            findings[0]["suggestion"],
+            "Add Edit(./src/**) to permissions"
+        );
+        assert_eq!(findings[0]["cycle_name"], "coding");
+        assert_eq!(findings[1]["severity"], "info");
+        assert!(findings[1]["suggestion"].is_null());
+
+        assert_eq!(value["summary"]["errors"], 1);
+        assert_eq!(value["summary"]["warnings"], 0);
+        assert_eq!(value["summary"]["info"], 1);
+    }
+
+    #[test]
+    fn test_render_diagnostic_json_clean_report() {
+        use crate::doctor::DiagnosticReport;
+
+        let report = DiagnosticReport { findings: vec![] };
+        let value: serde_json::Value =
+            serde_json::from_str(&render_diagnostic_json(&report)).unwrap();
+        assert_eq!(value["findings"].as_array().unwrap().len(), 0);
+        assert_eq!(value["summary"]["errors"], 0);
+    }
+
     #[test]
     fn test_render_diagnostic_report_summary_counts() {
         use crate::doctor::{DiagnosticReport, Finding, Severity};
@@ -826,7 +2292,7 @@ mod tests {
         cycles.insert("coding".to_string(), 3u32);
         cycles.insert("gardening".to_string(), 2u32);
 
-        let output = render_run_summary(5, 20, 3.45, &cycles, 4, 1, 510);
+        let output = render_run_summary(5, 20, 3.45, &cycles, 4, 1, 510, None);
         assert!(output.contains("5/20"), "Should show iteration progress");
         assert!(output.contains("$3.45"), "Should show cost");
         assert!(
@@ -846,7 +2312,7 @@ mod tests {
         let mut cycles = std::collections::BTreeMap::new();
         cycles.insert("coding".to_string(), 5u32);
 
-        let output = render_run_summary(5, 10, 1.00, &cycles, 5, 0, 300);
+        let output = render_run_summary(5, 10, 1.00, &cycles, 5, 0, 300, None);
         assert!(output.contains("5/5 succeeded"));
     }
 
@@ -855,7 +2321,7 @@ mod tests {
         let mut cycles = std::collections::BTreeMap::new();
         cycles.insert("coding".to_string(), 5u32);
 
-        let output = render_run_summary(5, 10, 2.00, &cycles, 4, 1, 600);
+        let output = render_run_summary(5, 10, 2.00, &cycles, 4, 1, 600, None);
         assert!(output.contains("coding\u{00d7}5"));
         // Should not contain a comma since there's only one cycle type
         let cycles_line = output
@@ -871,10 +2337,178 @@ mod tests {
     #[test]
     fn test_render_run_summary_zero_cost() {
         let cycles = std::collections::BTreeMap::new();
-        let output = render_run_summary(1, 5, 0.0, &cycles, 1, 0, 30);
+        let output = render_run_summary(1, 5, 0.0, &cycles, 1, 0, 30, None);
         assert!(output.contains("$0.00"));
     }
 
+    #[test]
+    fn test_render_run_summary_with_eta() {
+        let cycles = std::collections::BTreeMap::new();
+        let output = render_run_summary(2, 5, 0.0, &cycles, 2, 0, 60, Some("~1m 30s"));
+        assert!(output.contains("ETA: ~1m 30s\n"), "{output}");
+        let without = render_run_summary(2, 5, 0.0, &cycles, 2, 0, 60, None);
+        assert!(!without.contains("ETA"));
+    }
+
+    // --- render_run_summary_markdown tests ---
+
+    fn summary_outcome(
+        cycle: &str,
+        outcome: &str,
+        cost: f64,
+        secs: u64,
+    ) -> crate::log::CycleOutcome {
+        let mut o = crate::testutil::make_test_outcome(1, cycle, outcome);
+        o.total_cost_usd = Some(cost);
+        o.duration_secs = secs;
+        o
+    }
+
+    #[test]
+    fn test_run_summary_markdown_per_cycle_table() {
+        let mut progress = crate::log::progress::RunProgress::new(5);
+        progress.current_status = crate::log::progress::RunStatus::Completed;
+        let outcomes = vec![
+            summary_outcome("coding", "Done", 1.0, 60),
+            summary_outcome("coding", "Failed with exit code 1", 0.5, 30),
+            summary_outcome("gardening", "Done", 0.25, 45),
+        ];
+        let md = render_run_summary_markdown(&progress, &outcomes);
+        assert!(md.starts_with("# Flow Run Summary"));
+        assert!(md.contains("- Status: completed"));
+        assert!(md.contains("- Iterations: 3/5"));
+        assert!(md.contains("- Results: 2/3 succeeded"));
+        assert!(md.contains("- Cost: $1.75"));
+        assert!(md.contains("- Duration: 2m 15s"));
+        assert!(
+            md.contains("| coding | 2 | 1 | 1 | $1.50 | 1m 30s |"),
+            "{md}"
+        );
+        assert!(
+            md.contains("| gardening | 1 | 1 | 0 | $0.25 | 45s |"),
+            "{md}"
+        );
+    }
+
+    #[test]
+    fn test_run_summary_markdown_line_counts() {
+        let progress = crate::log::progress::RunProgress::new(2);
+        let mut first = summary_outcome("coding", "Done", 1.0, 60);
+        first.lines_added = Some(120);
+        first.lines_removed = Some(30);
+        let mut second = summary_outcome("coding", "Done", 1.0, 60);
+        second.lines_added = Some(5);
+        second.lines_removed = Some(5);
+        let md = render_run_summary_markdown(&progress, &[first, second]);
+        assert!(md.contains("- Lines: +125/-35"), "{md}");
+        assert!(md.contains("| 2m | +125/-35 |"), "{md}");
+    }
+
+    #[test]
+    fn test_run_summary_markdown_no_outcomes_omits_table() {
+        let progress = crate::log::progress::RunProgress::new(3);
+        let md = render_run_summary_markdown(&progress, &[]);
+        assert!(md.contains("- Iterations: 0/3"));
+        assert!(!md.contains("| Cycle |"));
+        assert!(!md.contains("Stop reason"));
+    }
+
+    #[test]
+    fn test_run_summary_markdown_includes_stop_reason() {
+        let mut progress = crate::log::progress::RunProgress::new(3);
+        progress.current_status = crate::log::progress::RunStatus::Stopped;
+        progress.stop_reason = Some("max run duration of 60s reached after 61s".to_string());
+        let md = render_run_summary_markdown(&progress, &[]);
+        assert!(md.contains("- Status: stopped"));
+        assert!(md.contains("- Stop reason: max run duration of 60s reached after 61s"));
+    }
+
+    // --- format_eta tests ---
+
+    fn local_time(h: u32, m: u32) -> chrono::DateTime<chrono::Local> {
+        use chrono::TimeZone;
+        chrono::Local
+            .with_ymd_and_hms(2025, 3, 12, h, m, 0)
+            .single()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_format_eta_same_day() {
+        assert_eq!(
+            format_eta(90, local_time(10, 0)),
+            "~1m 30s (finishes ~10:01)"
+        );
+    }
+
+    #[test]
+    fn test_format_eta_hours() {
+        assert_eq!(
+            format_eta(3900, local_time(10, 0)),
+            "~1h 05m (finishes ~11:05)"
+        );
+    }
+
+    #[test]
+    fn test_format_eta_next_day_shows_weekday() {
+        let eta = format_eta(3 * 3600, local_time(23, 0));
+        assert!(eta.ends_with("(finishes ~Thu 02:00)"), "{eta}");
+    }
+
+    // --- watch-progress rendering tests ---
+
+    fn watched_progress(now: chrono::DateTime<chrono::Local>) -> crate::log::RunProgress {
+        let mut progress = crate::log::RunProgress::new(4);
+        progress.started_at = (now - chrono::Duration::seconds(600)).with_timezone(&chrono::Utc);
+        progress.current_iteration = 3;
+        progress.current_cycle = "coding".to_string();
+        progress.cycles_executed.insert("coding".to_string(), 2);
+        progress.total_cost_usd = 1.5;
+        progress
+    }
+
+    #[test]
+    fn test_render_watch_progress_running_with_eta() {
+        let now = local_time(10, 0);
+        let line = render_watch_progress(&watched_progress(now), now);
+        assert!(line.contains("[3/4]"), "{line}");
+        assert!(line.contains("coding"), "{line}");
+        assert!(line.contains("running"), "{line}");
+        assert!(line.contains("10m"), "{line}");
+        assert!(line.contains("$1.50"), "{line}");
+        // 300s per completed iteration × 2 remaining
+        assert!(line.contains("ETA ~10m (finishes ~10:10)"), "{line}");
+    }
+
+    #[test]
+    fn test_render_watch_progress_stopped_shows_reason() {
+        let now = local_time(10, 0);
+        let mut progress = watched_progress(now);
+        progress.current_status = crate::log::RunStatus::Stopped;
+        progress.stop_reason = Some("max duration reached".to_string());
+        let line = render_watch_progress(&progress, now);
+        assert!(line.contains("stopped"), "{line}");
+        assert!(line.contains("max duration reached"), "{line}");
+        assert!(!line.contains("ETA"), "{line}");
+    }
+
+    #[test]
+    fn test_render_watch_outcome() {
+        let mut outcome = crate::testutil::make_test_outcome(3, "coding", "Added the parser");
+        outcome.duration_secs = 135;
+        outcome.total_cost_usd = Some(0.45);
+        outcome.num_turns = Some(8);
+        let line = render_watch_outcome(&outcome);
+        assert!(line.contains("done"), "{line}");
+        assert!(line.contains("#3"), "{line}");
+        assert!(line.contains("2m 15s"), "{line}");
+        assert!(line.contains("$0.45"), "{line}");
+        assert!(line.contains("8 turns: Added the parser"), "{line}");
+
+        outcome.outcome = "Failed: tests broke".to_string();
+        assert!(render_watch_outcome(&outcome).contains("failed"));
+    }
+
     #[test]
     fn test_status_line_render_with_iteration_context() {
         let status = StatusLine::with_iteration("coding", 3, 10);
@@ -932,12 +2566,178 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_status_line_tick_animates_spinner() {
+        let mut status = StatusLine::new("coding");
+        assert!(status.render().contains('\u{25b6}'));
+
+        status.tick();
+        let first = status.render();
+        assert!(first.contains(theme().glyphs.spinner[0]), "{first}");
+        assert!(!first.contains('\u{25b6}'));
+
+        status.tick();
+        assert!(status.render().contains(theme().glyphs.spinner[1]));
+    }
+
+    #[test]
+    fn test_status_line_tick_wraps_around() {
+        let mut status = StatusLine::new("coding");
+        for _ in 0..=theme().glyphs.spinner.len() {
+            status.tick();
+        }
+        assert_eq!(status.spinner_frame, Some(0));
+    }
+
+    #[test]
+    fn test_status_line_renders_budget() {
+        let mut status = StatusLine::new("coding").with_budget(Some(5.0));
+        status.update(&StreamEvent::Result {
+            is_error: false,
+            result_text: String::new(),
+            num_turns: 3,
+            total_cost_usd: 1.2,
+            duration_ms: 0,
+            permission_denials: vec![],
+            usage: TokenUsage::default(),
+        });
+        let rendered = status.render();
+        assert!(rendered.contains("$1.20 / $5.00"), "{rendered}");
+    }
+
+    #[test]
+    fn test_status_line_without_budget_shows_plain_cost() {
+        let rendered = StatusLine::new("coding").with_budget(None).render();
+        assert!(rendered.contains("| $0.00 |"), "{rendered}");
+    }
+
+    #[test]
+    fn test_status_line_health_tracks_budget() {
+        let mut status = StatusLine::new("coding").with_budget(Some(10.0));
+        assert_eq!(status.health_color(), HealthColor::Green);
+        status.cost_usd = 8.0;
+        assert_eq!(status.health_color(), HealthColor::Yellow);
+        status.cost_usd = 10.0;
+        assert_eq!(status.health_color(), HealthColor::Red);
+    }
+
+    #[test]
+    fn test_status_line_health_takes_worse_of_errors_and_budget() {
+        let mut status = StatusLine::new("coding").with_budget(Some(10.0));
+        status.cost_usd = 9.0;
+        for _ in 0..3 {
+            status.update(&StreamEvent::ToolResult {
+                is_error: true,
+                content: "denied".to_string(),
+            });
+        }
+        assert_eq!(status.health_color(), HealthColor::Red);
+    }
+
+    #[test]
+    fn test_status_line_clear_without_print_is_noop() {
+        let mut status = StatusLine::new("coding");
+        status.clear();
+        assert!(status.scroll_region_rows.is_none());
+    }
+
+    #[test]
+    fn test_fit_width_leaves_short_lines_alone() {
+        assert_eq!(fit_width("[coding] 3 turns", 80), "[coding] 3 turns");
+    }
+
+    #[test]
+    fn test_fit_width_truncates_to_terminal_width() {
+        let line = "x".repeat(100);
+        let fitted = fit_width(&line, 40);
+        assert_eq!(fitted.chars().count(), 39);
+        assert!(fitted.ends_with("..."));
+    }
+
+    #[test]
+    fn test_fit_width_tiny_terminal_no_panic() {
+        assert_eq!(fit_width("abcdef", 1), "...");
+        assert_eq!(fit_width("abcdef", 0), "...");
+    }
+
+    #[test]
+    fn test_scroll_region_excludes_bottom_row() {
+        let seq = scroll_region_setup(24);
+        assert!(seq.contains("\x1b[1;23r"), "{seq:?}");
+        assert!(seq.starts_with('\n'), "should make room first: {seq:?}");
+    }
+
+    #[test]
+    fn test_bottom_row_write_targets_last_row() {
+        let seq = bottom_row_write(50, "status");
+        assert_eq!(seq, "\x1b[s\x1b[50;1H\x1b[2Kstatus\x1b[u");
+        assert!(!seq.contains("999"));
+    }
+
+    #[test]
+    fn test_scroll_region_reset_restores_full_screen() {
+        let seq = scroll_region_reset(30);
+        assert!(seq.contains("\x1b[r"));
+        assert!(seq.contains("\x1b[30;1H\x1b[2K"));
+    }
+
+    // --- terminal mode tests ---
+
+    #[test]
+    fn test_detect_terminal_mode() {
+        assert_eq!(detect_terminal_mode(None, None, None), TerminalMode::Ansi);
+        assert_eq!(
+            detect_terminal_mode(None, Some(true), Some("xterm")),
+            TerminalMode::Ansi
+        );
+        assert_eq!(
+            detect_terminal_mode(None, Some(false), None),
+            TerminalMode::Plain
+        );
+        assert_eq!(
+            detect_terminal_mode(None, None, Some("dumb")),
+            TerminalMode::Plain
+        );
+        assert_eq!(
+            detect_terminal_mode(Some("ANSI"), Some(false), None),
+            TerminalMode::Ansi
+        );
+        assert_eq!(
+            detect_terminal_mode(Some("plain"), Some(true), None),
+            TerminalMode::Plain
+        );
+    }
+
+    #[test]
+    fn test_render_plain_has_no_glyph_or_escapes() {
+        let mut status = StatusLine::new("coding");
+        status.tick();
+        let plain = status.render_plain();
+        assert!(plain.starts_with("[coding] 0 turns | $0.00 | "), "{plain}");
+        assert!(plain.is_ascii(), "{plain}");
+    }
+
+    #[test]
+    fn test_plain_line_printed_only_on_change() {
+        let mut status = StatusLine::new("coding");
+        assert!(status.next_plain_line().is_some());
+        status.tick();
+        assert!(status.next_plain_line().is_none());
+        status.update(&StreamEvent::ToolUse {
+            tool_name: "Read".to_string(),
+            input: json!({}),
+        });
+        assert!(status.next_plain_line().unwrap().contains("1 turns"));
+        assert!(status.next_plain_line().is_none());
+    }
+
     #[test]
     fn test_status_line_ignores_irrelevant_events() {
         let mut status = StatusLine::new("coding");
         status.update(&StreamEvent::SystemInit {
             model: "claude-opus-4-6".to_string(),
             session_id: "abc".to_string(),
+            claude_version: None,
         });
         status.update(&StreamEvent::AssistantText {
             text: "Hello".to_string(),
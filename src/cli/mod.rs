@@ -4,8 +4,25 @@
 //! replacing raw JSON output with formatted, colored output.
 
 pub mod display;
+pub mod picker;
+pub mod theme;
 
+pub use display::format_eta;
+pub use display::render_activity_summary;
+pub use display::render_cycle_trends;
+pub use display::render_diagnostic_json;
 pub use display::render_diagnostic_report;
+pub use display::render_gc_report;
+pub use display::render_permission_drift;
 pub use display::render_run_summary;
+pub use display::render_run_summary_markdown;
+pub use display::render_sessions;
+pub use display::render_tool_summary;
+pub use display::render_watch_outcome;
+pub use display::render_watch_progress;
 pub use display::CycleDisplay;
 pub use display::StatusLine;
+pub use display::StepProgress;
+pub use display::{terminal_mode, TerminalMode};
+pub use picker::pick_cycle;
+pub use theme::{set_theme, theme, Role};
@@ -0,0 +1,146 @@
+//! Interactive cycle picker
+//!
+//! Running `flow` in a terminal without `--cycle` or `--max-iterations`
+//! lists the configured cycles and asks which one to run, so nobody has to
+//! remember cycle names. The menu goes to stderr like all other human output;
+//! the answer is read a line at a time.
+
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+
+use crate::cli::theme::{theme, Role};
+use crate::cycle::config::FlowConfig;
+
+/// The numbered menu of cycles, one `  1) name  description` line each.
+#[must_use]
+pub fn render_cycle_menu(config: &FlowConfig) -> String {
+    let width = config
+        .cycles
+        .iter()
+        .map(|c| c.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let number_width = config.cycles.len().to_string().len();
+    config
+        .cycles
+        .iter()
+        .enumerate()
+        .map(|(i, cycle)| {
+            format!(
+                "  {:>number_width$}) {}  {}",
+                i + 1,
+                theme().paint(Role::Header, &format!("{:<width$}", cycle.name)),
+                theme().paint(Role::Muted, &cycle.description),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The cycle an answer names: its menu number or its name.
+#[must_use]
+pub fn parse_cycle_choice<'a>(answer: &str, config: &'a FlowConfig) -> Option<&'a str> {
+    let answer = answer.trim();
+    let cycle = answer.parse::<usize>().map_or_else(
+        |_| config.get_cycle(answer),
+        |n| n.checked_sub(1).and_then(|i| config.cycles.get(i)),
+    );
+    cycle.map(|c| c.name.as_str())
+}
+
+/// Show the menu on `output` and read answers from `input` until one names a
+/// cycle. Returns `None` if the user enters nothing or input ends.
+///
+/// # Errors
+/// Returns an error if reading `input` or writing `output` fails.
+pub fn pick_cycle(
+    config: &FlowConfig,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<Option<String>> {
+    writeln!(
+        output,
+        "Which cycle should run?\n{}",
+        render_cycle_menu(config)
+    )?;
+    loop {
+        write!(output, "Cycle [1-{}, or name]: ", config.cycles.len())?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 || answer.trim().is_empty() {
+            return Ok(None);
+        }
+        match parse_cycle_choice(&answer, config) {
+            Some(name) => return Ok(Some(name.to_string())),
+            None => writeln!(
+                output,
+                "{} No cycle '{}'",
+                theme().paint(Role::Error, &theme().glyphs.error.to_string()),
+                answer.trim()
+            )?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Pick a task and implement with TDD"
+prompt = "Code"
+
+[[cycle]]
+name = "gardening"
+description = "Deps, refactoring, docs"
+prompt = "Garden"
+"#;
+
+    fn config() -> FlowConfig {
+        FlowConfig::parse(CONFIG).unwrap()
+    }
+
+    #[test]
+    fn test_menu_lists_every_cycle_with_description() {
+        let menu = render_cycle_menu(&config());
+        let lines: Vec<&str> = menu.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "  1) coding     Pick a task and implement with TDD"
+        );
+        assert_eq!(lines[1], "  2) gardening  Deps, refactoring, docs");
+    }
+
+    #[test]
+    fn test_choice_by_number_or_name() {
+        let config = config();
+        assert_eq!(parse_cycle_choice("2\n", &config), Some("gardening"));
+        assert_eq!(parse_cycle_choice(" coding ", &config), Some("coding"));
+        assert_eq!(parse_cycle_choice("0", &config), None);
+        assert_eq!(parse_cycle_choice("3", &config), None);
+        assert_eq!(parse_cycle_choice("deploy", &config), None);
+    }
+
+    #[test]
+    fn test_pick_reprompts_until_valid() {
+        let mut output = Vec::new();
+        let picked = pick_cycle(&config(), &b"deploy\n1\n"[..], &mut output).unwrap();
+        assert_eq!(picked.as_deref(), Some("coding"));
+        let shown = String::from_utf8(output).unwrap();
+        assert!(shown.contains("No cycle 'deploy'"), "{shown}");
+        assert_eq!(shown.matches("Cycle [1-2, or name]").count(), 2);
+    }
+
+    #[test]
+    fn test_pick_cancelled_by_empty_answer_or_eof() {
+        assert_eq!(pick_cycle(&config(), &b"\n"[..], Vec::new()).unwrap(), None);
+        assert_eq!(pick_cycle(&config(), &b""[..], Vec::new()).unwrap(), None);
+    }
+}
@@ -0,0 +1,267 @@
+//! Display theme
+//!
+//! Colors, glyphs, and timestamp prefixes for terminal output, configured by
+//! the `[display]` section. The theme is set once after the config loads;
+//! until then (and for embedders that never set one) the dark theme with
+//! unicode glyphs applies, matching Flow's original output.
+
+use std::sync::OnceLock;
+
+use colored::{Color, ColoredString, Colorize};
+
+use crate::cycle::config::{DisplayConfig, GlyphSet, ThemeName};
+
+/// Kind of output, each painted in its own theme color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Cycle and step headers, `>>>` run messages
+    Header,
+    /// Tool-use markers
+    Tool,
+    /// Subagent activity
+    Subagent,
+    /// Errors and failed results
+    Error,
+    /// Completed results and healthy status
+    Success,
+    /// Warnings and degraded status
+    Warning,
+    /// Secondary detail: labels, summaries, rules
+    Muted,
+}
+
+/// Characters drawn for markers, rules, and the spinner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyphs {
+    /// Marks a tool use, and the idle status line
+    pub tool: char,
+    /// Marks a failed tool result
+    pub error: char,
+    /// Marks a warning
+    pub warning: char,
+    /// Repeated to draw horizontal rules
+    pub rule: char,
+    /// Left margin of a subagent's nested output
+    pub nest: char,
+    /// Closes a subagent's nested output
+    pub nest_end: char,
+    /// Separates the cycle name from the step
+    pub separator: char,
+    /// Status line spinner frames
+    pub spinner: &'static [char],
+}
+
+/// Box-drawing characters, arrows, and a braille spinner
+pub const UNICODE_GLYPHS: Glyphs = Glyphs {
+    tool: '▶',
+    error: '✗',
+    warning: '⚠',
+    rule: '─',
+    nest: '│',
+    nest_end: '└',
+    separator: '·',
+    spinner: &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'],
+};
+
+/// Plain ASCII, for fonts and terminals without the unicode glyphs
+pub const ASCII_GLYPHS: Glyphs = Glyphs {
+    tool: '>',
+    error: 'x',
+    warning: '!',
+    rule: '-',
+    nest: '|',
+    nest_end: '`',
+    separator: '-',
+    spinner: &['|', '/', '-', '\\'],
+};
+
+/// Colors, glyphs, and timestamp setting for terminal output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Colors for header, tool, subagent, error, success, and warning output
+    colors: [Color; 6],
+    /// Glyph set in use
+    pub glyphs: Glyphs,
+    /// Prefix event lines with the local time
+    pub timestamps: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_config(&DisplayConfig::default())
+    }
+}
+
+impl Theme {
+    /// Build the theme a `[display]` section describes.
+    ///
+    /// Color overrides that don't parse are ignored; config validation
+    /// rejects them before this point.
+    #[must_use]
+    pub fn from_config(config: &DisplayConfig) -> Self {
+        let mut colors = match config.theme {
+            ThemeName::Dark | ThemeName::Plain => [
+                Color::Cyan,
+                Color::Blue,
+                Color::Magenta,
+                Color::Red,
+                Color::Green,
+                Color::Yellow,
+            ],
+            ThemeName::Light => [
+                Color::Blue,
+                Color::Blue,
+                Color::Magenta,
+                Color::Red,
+                Color::Green,
+                Color::Magenta,
+            ],
+        };
+        for (slot, (_, color)) in colors.iter_mut().zip(config.colors.entries()) {
+            if let Some(color) = color.and_then(|c| c.parse().ok()) {
+                *slot = color;
+            }
+        }
+        Self {
+            colors,
+            glyphs: match config.glyphs {
+                GlyphSet::Unicode => UNICODE_GLYPHS,
+                GlyphSet::Ascii => ASCII_GLYPHS,
+            },
+            timestamps: config.timestamps,
+        }
+    }
+
+    /// Paint `text` in the color for `role`.
+    #[must_use]
+    pub fn paint(&self, role: Role, text: &str) -> ColoredString {
+        let color = match role {
+            Role::Header => self.colors[0],
+            Role::Tool => self.colors[1],
+            Role::Subagent => self.colors[2],
+            Role::Error => self.colors[3],
+            Role::Success => self.colors[4],
+            Role::Warning => self.colors[5],
+            Role::Muted => return text.dimmed(),
+        };
+        text.color(color)
+    }
+
+    /// A horizontal rule of `width` rule glyphs.
+    #[must_use]
+    pub fn rule(&self, width: usize) -> String {
+        self.glyphs.rule.to_string().repeat(width)
+    }
+
+    /// `12:34:56 ` when timestamps are on, otherwise empty.
+    #[must_use]
+    pub fn timestamp(&self) -> String {
+        if self.timestamps {
+            format!(
+                "{} ",
+                chrono::Local::now().format("%H:%M:%S").to_string().dimmed()
+            )
+        } else {
+            String::new()
+        }
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Apply a `[display]` section to this process's output.
+///
+/// The first call wins. The `plain` theme turns colors off entirely.
+pub fn set_theme(config: &DisplayConfig) {
+    if THEME.set(Theme::from_config(config)).is_ok() && config.theme == ThemeName::Plain {
+        colored::control::set_override(false);
+    }
+}
+
+/// The theme in effect: the one set by [`set_theme`], or the default.
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycle::config::ThemeColors;
+
+    #[test]
+    fn test_default_theme_matches_original_colors() {
+        let theme = Theme::default();
+        assert_eq!(theme.colors[0], Color::Cyan);
+        assert_eq!(theme.colors[5], Color::Yellow);
+        assert_eq!(theme.glyphs, UNICODE_GLYPHS);
+        assert!(!theme.timestamps);
+    }
+
+    #[test]
+    fn test_light_theme_avoids_yellow_and_cyan() {
+        let theme = Theme::from_config(&DisplayConfig {
+            theme: ThemeName::Light,
+            ..DisplayConfig::default()
+        });
+        assert!(!theme.colors.contains(&Color::Yellow));
+        assert!(!theme.colors.contains(&Color::Cyan));
+    }
+
+    #[test]
+    fn test_color_overrides_apply_per_role() {
+        let theme = Theme::from_config(&DisplayConfig {
+            colors: ThemeColors {
+                tool: Some("bright green".to_string()),
+                warning: Some("#aa5500".to_string()),
+                ..ThemeColors::default()
+            },
+            ..DisplayConfig::default()
+        });
+        assert_eq!(theme.colors[1], Color::BrightGreen);
+        assert_eq!(
+            theme.colors[5],
+            Color::TrueColor {
+                r: 0xaa,
+                g: 0x55,
+                b: 0x00
+            }
+        );
+        assert_eq!(theme.colors[0], Color::Cyan);
+    }
+
+    #[test]
+    fn test_ascii_glyphs_are_ascii() {
+        let theme = Theme::from_config(&DisplayConfig {
+            glyphs: GlyphSet::Ascii,
+            ..DisplayConfig::default()
+        });
+        let g = theme.glyphs;
+        for c in [
+            g.tool,
+            g.error,
+            g.warning,
+            g.rule,
+            g.nest,
+            g.nest_end,
+            g.separator,
+        ]
+        .iter()
+        .chain(g.spinner)
+        {
+            assert!(c.is_ascii(), "{c}");
+        }
+        assert_eq!(theme.rule(3), "---");
+    }
+
+    #[test]
+    fn test_timestamp_prefix_only_when_enabled() {
+        assert_eq!(Theme::default().timestamp(), "");
+        let theme = Theme::from_config(&DisplayConfig {
+            timestamps: true,
+            ..DisplayConfig::default()
+        });
+        let stamp = theme.timestamp();
+        assert_eq!(stamp.matches(':').count(), 2, "{stamp}");
+        assert!(stamp.ends_with(' '));
+    }
+}
@@ -0,0 +1,129 @@
+//! Reviewer arbitration (`arbitrates = "<cycle>"`)
+//!
+//! A review cycle that arbitrates another cycle ends its result with a verdict
+//! such as `{"verdict": "reject", "reasons": ["No tests for the new parser"]}`.
+//! When it runs right after the cycle it arbitrates, the runner acts on the
+//! verdict: approved changes are committed, rejected ones are rolled back to
+//! the snapshot taken before the arbitrated cycle ran.
+
+use serde::{Deserialize, Serialize};
+
+/// What the reviewer decided
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    /// Keep and commit the changes
+    Approve,
+    /// Roll the changes back
+    Reject,
+}
+
+impl Decision {
+    /// Lowercase name, as written in a verdict.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Approve => "approve",
+            Self::Reject => "reject",
+        }
+    }
+}
+
+/// A reviewer's verdict on the cycle it arbitrates
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Verdict {
+    /// Approve or reject
+    pub decision: Decision,
+    /// Reasons the reviewer gave (may be empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
+}
+
+/// Find the verdict in a review cycle's result text.
+///
+/// Looks for a JSON object with a `"verdict"` field of `approve`/`approved` or
+/// `reject`/`rejected` (any case), on its own line or in a fenced block. The
+/// last such object wins. `"reasons"` may be a list of strings or one string.
+#[must_use]
+pub fn parse_verdict(text: &str) -> Option<Verdict> {
+    json_candidates(text)
+        .into_iter()
+        .rev()
+        .find_map(|candidate| {
+            let value: serde_json::Value = serde_json::from_str(&candidate).ok()?;
+            let decision = match value.get("verdict")?.as_str()?.to_lowercase().as_str() {
+                "approve" | "approved" => Decision::Approve,
+                "reject" | "rejected" => Decision::Reject,
+                _ => return None,
+            };
+            let reasons = match value.get("reasons") {
+                Some(serde_json::Value::Array(items)) => items
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(str::to_string)
+                    .collect(),
+                Some(serde_json::Value::String(reason)) => vec![reason.clone()],
+                _ => Vec::new(),
+            };
+            Some(Verdict { decision, reasons })
+        })
+}
+
+/// Single-line JSON objects and the contents of fenced code blocks, in order.
+fn json_candidates(text: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let mut fenced: Option<String> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            match fenced.take() {
+                Some(block) => candidates.push(block),
+                None => fenced = Some(String::new()),
+            }
+        } else if let Some(ref mut block) = fenced {
+            block.push_str(line);
+            block.push('\n');
+        } else if trimmed.starts_with('{') && trimmed.ends_with('}') {
+            candidates.push(trimmed.to_string());
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_line_verdict() {
+        let verdict = parse_verdict(
+            "Looks solid overall.\n{\"verdict\": \"approve\", \"reasons\": [\"Tests cover it\"]}",
+        )
+        .unwrap();
+        assert_eq!(verdict.decision, Decision::Approve);
+        assert_eq!(verdict.reasons, vec!["Tests cover it"]);
+    }
+
+    #[test]
+    fn test_parse_fenced_verdict_with_string_reason() {
+        let text = "Review:\n```json\n{\n  \"verdict\": \"Rejected\",\n  \"reasons\": \"Breaks the build\"\n}\n```\n";
+        let verdict = parse_verdict(text).unwrap();
+        assert_eq!(verdict.decision, Decision::Reject);
+        assert_eq!(verdict.reasons, vec!["Breaks the build"]);
+    }
+
+    #[test]
+    fn test_last_verdict_wins() {
+        let text = "{\"verdict\": \"approve\"}\nOn second thought:\n{\"verdict\": \"reject\"}";
+        let verdict = parse_verdict(text).unwrap();
+        assert_eq!(verdict.decision, Decision::Reject);
+        assert!(verdict.reasons.is_empty());
+    }
+
+    #[test]
+    fn test_no_verdict() {
+        assert!(parse_verdict("The change looks fine to me.").is_none());
+        assert!(parse_verdict("{\"verdict\": \"maybe\"}").is_none());
+        assert!(parse_verdict("{\"next\": \"DONE\"}").is_none());
+    }
+}
@@ -0,0 +1,183 @@
+//! Task claiming in TODO.md
+//!
+//! When the selector names a task, Flow marks its checkbox in progress
+//! (`- [~] ... <!-- flow:claimed -->`) before the cycle starts, so the next
+//! iteration's selector no longer sees it as pending and can't hand it to
+//! another cycle. When the cycle ends the claim is resolved: checked off if
+//! the cycle succeeded, released back to `- [ ]` if it failed or the run was
+//! cancelled. A cycle that checks the task off itself is left as it is.
+//! Claims an interrupted run left behind are released when the next run starts.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::cycle::selector::{parse_checkbox, split_inline_priority};
+
+/// Marker appended to a claimed task's line
+pub const CLAIM_TAG: &str = "<!-- flow:claimed -->";
+
+/// `content` with the pending task `task` claimed (`None` if none matches).
+///
+/// An exact description match wins over a case-insensitive one; inline
+/// `priority:` tags are ignored when comparing.
+#[must_use]
+pub fn claim(content: &str, task: &str) -> Option<String> {
+    let task = task.trim();
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+    let description = |line: &str| match parse_checkbox(line.trim()) {
+        Some((false, desc)) => Some(split_inline_priority(desc).0),
+        _ => None,
+    };
+    let index = lines
+        .iter()
+        .position(|line| description(line).is_some_and(|d| d == task))
+        .or_else(|| {
+            lines
+                .iter()
+                .position(|line| description(line).is_some_and(|d| d.eq_ignore_ascii_case(task)))
+        })?;
+    let line = &lines[index];
+    let body = line.trim_end_matches(['\n', '\r']);
+    let ending = &line[body.len()..];
+    lines[index] = format!("{} {CLAIM_TAG}{ending}", body.replacen("[ ]", "[~]", 1));
+    Some(lines.concat())
+}
+
+/// `content` with every claim resolved — claimed checkboxes checked off when
+/// `completed`, released otherwise — and the number of claims found.
+#[must_use]
+pub fn resolve_claims(content: &str, completed: bool) -> (String, usize) {
+    let mark = if completed { "[x]" } else { "[ ]" };
+    let mut resolved = 0;
+    let lines: Vec<String> = content
+        .split_inclusive('\n')
+        .map(|line| {
+            if !line.contains(CLAIM_TAG) {
+                return line.to_string();
+            }
+            resolved += 1;
+            let line = line
+                .replacen(&format!(" {CLAIM_TAG}"), "", 1)
+                .replacen(CLAIM_TAG, "", 1);
+            line.replacen("[~]", mark, 1)
+        })
+        .collect();
+    (lines.concat(), resolved)
+}
+
+/// Claim `task` in the TODO file. Returns whether a matching pending task was found.
+///
+/// # Errors
+/// Returns an error if the file exists but can't be read or written.
+pub fn claim_task(todo_path: &Path, task: &str) -> Result<bool> {
+    let Some(content) = read_todo(todo_path)? else {
+        return Ok(false);
+    };
+    let Some(claimed) = claim(&content, task) else {
+        return Ok(false);
+    };
+    write_todo(todo_path, &claimed)?;
+    Ok(true)
+}
+
+/// Resolve the claims in the TODO file (see [`resolve_claims`]). Returns how
+/// many there were.
+///
+/// # Errors
+/// Returns an error if the file exists but can't be read or written.
+pub fn finish_claims(todo_path: &Path, completed: bool) -> Result<usize> {
+    let Some(content) = read_todo(todo_path)? else {
+        return Ok(0);
+    };
+    let (resolved, count) = resolve_claims(&content, completed);
+    if count > 0 {
+        write_todo(todo_path, &resolved)?;
+    }
+    Ok(count)
+}
+
+/// The TODO file's content, or `None` if it doesn't exist.
+fn read_todo(todo_path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(todo_path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", todo_path.display())),
+    }
+}
+
+/// Replace the TODO file's content.
+fn write_todo(todo_path: &Path, content: &str) -> Result<()> {
+    std::fs::write(todo_path, content)
+        .with_context(|| format!("Failed to write {}", todo_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TODO: &str = "# TODO\n\n- [x] Done already\n- [ ] Fix the login bug priority:p0\n  - [ ] Add a test\n- [ ] Write docs\n";
+
+    #[test]
+    fn test_claim_marks_matching_task() {
+        let claimed = claim(TODO, "Fix the login bug").unwrap();
+        assert!(
+            claimed.contains("- [~] Fix the login bug priority:p0 <!-- flow:claimed -->\n"),
+            "{claimed}"
+        );
+        assert!(claimed.contains("  - [ ] Add a test\n"), "{claimed}");
+        assert!(claimed.contains("- [ ] Write docs\n"), "{claimed}");
+    }
+
+    #[test]
+    fn test_claim_matches_case_insensitively_and_skips_checked() {
+        let claimed = claim(TODO, " write DOCS ").unwrap();
+        assert!(claimed.ends_with("- [~] Write docs <!-- flow:claimed -->\n"));
+        assert_eq!(claim(TODO, "Done already"), None);
+        assert_eq!(claim(TODO, "Something else"), None);
+    }
+
+    #[test]
+    fn test_claim_keeps_crlf_line_endings() {
+        let claimed = claim("- [ ] Task\r\n- [ ] Other\r\n", "Task").unwrap();
+        assert_eq!(
+            claimed,
+            "- [~] Task <!-- flow:claimed -->\r\n- [ ] Other\r\n"
+        );
+    }
+
+    #[test]
+    fn test_resolve_claims_completes_or_releases() {
+        let claimed = claim(TODO, "Write docs").unwrap();
+        let (completed, count) = resolve_claims(&claimed, true);
+        assert_eq!(count, 1);
+        assert!(completed.ends_with("- [x] Write docs\n"), "{completed}");
+        let (released, _) = resolve_claims(&claimed, false);
+        assert_eq!(released, TODO);
+    }
+
+    #[test]
+    fn test_resolve_claims_respects_cycle_edits() {
+        let checked_by_cycle = "- [x] Write docs <!-- flow:claimed -->\n";
+        let (resolved, count) = resolve_claims(checked_by_cycle, false);
+        assert_eq!(count, 1);
+        assert_eq!(resolved, "- [x] Write docs\n");
+        assert_eq!(resolve_claims(TODO, true), (TODO.to_string(), 0));
+    }
+
+    #[test]
+    fn test_claim_task_and_finish_claims_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("TODO.md");
+        assert!(!claim_task(&path, "Write docs").unwrap());
+        assert_eq!(finish_claims(&path, true).unwrap(), 0);
+
+        std::fs::write(&path, TODO).unwrap();
+        assert!(claim_task(&path, "Write docs").unwrap());
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("[~] Write docs"));
+        assert_eq!(finish_claims(&path, false).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), TODO);
+    }
+}
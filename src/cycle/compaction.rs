@@ -0,0 +1,261 @@
+//! LLM-powered history compaction for `context = "compact"`
+//!
+//! Old log entries are condensed, a block of `[compaction].block_size` at a
+//! time, into digests appended to `.flow/digests.jsonl`. Each digest covers a
+//! contiguous range of log positions, so a compact context is the digests
+//! followed by one-line summaries of the entries after the last one. The
+//! newest `keep_recent` entries are never digested. A run compacts before
+//! each cycle that uses compact context; `flow logs compact` does it on demand.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::claude::cli::{build_command_with_options, run_for_result, CommandOptions};
+use crate::cycle::config::CompactionConfig;
+use crate::log::jsonl::{CycleOutcome, JsonlLogger};
+
+/// Name of the digest file inside the log directory
+pub const DIGESTS_FILE: &str = "digests.jsonl";
+
+/// A condensed summary of a block of log entries
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Digest {
+    /// Position in the log of the first entry covered (0-based)
+    pub start: usize,
+    /// Position just past the last entry covered
+    pub end: usize,
+    /// Iteration number of the first entry covered
+    pub first_iteration: u32,
+    /// Iteration number of the last entry covered
+    pub last_iteration: u32,
+    /// The condensed history
+    pub summary: String,
+    /// When the digest was written
+    pub created_at: DateTime<Utc>,
+}
+
+/// Load the digests at `path` (empty if the file doesn't exist). Lines that
+/// don't parse are skipped; digests are derived data and can be regenerated.
+///
+/// # Errors
+/// Returns an error if the file exists but can't be read.
+pub fn load_digests(path: &Path) -> Result<Vec<Digest>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Append a digest to `path`.
+///
+/// # Errors
+/// Returns an error if the digest can't be serialized or written.
+pub fn append_digest(path: &Path, digest: &Digest) -> Result<()> {
+    let json = serde_json::to_string(digest).context("Failed to serialize digest")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{json}").with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The digests that chain contiguously from the start of the log; anything
+/// after a gap or overlap is ignored.
+#[must_use]
+pub fn contiguous(digests: &[Digest]) -> &[Digest] {
+    let mut expected = 0;
+    let len = digests
+        .iter()
+        .take_while(|d| {
+            let chained = d.start == expected && d.end > d.start;
+            expected = d.end;
+            chained
+        })
+        .count();
+    &digests[..len]
+}
+
+/// Number of leading log entries the digests cover.
+#[must_use]
+pub fn covered(digests: &[Digest]) -> usize {
+    contiguous(digests).last().map_or(0, |d| d.end)
+}
+
+/// The next block of log positions to digest, if enough entries beyond the
+/// `keep_recent` window have accumulated since the last digest.
+#[must_use]
+pub fn next_block(
+    covered: usize,
+    total_entries: usize,
+    settings: &CompactionConfig,
+) -> Option<Range<usize>> {
+    let end = covered + settings.block_size;
+    (settings.block_size > 0 && end + settings.keep_recent <= total_entries).then_some(covered..end)
+}
+
+/// Build the prompt asking Claude to condense `entries` into a digest.
+#[must_use]
+pub fn build_compaction_prompt(entries: &[CycleOutcome]) -> String {
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|o| {
+            let status = if o.is_success() { "ok" } else { "FAIL" };
+            let files = if o.files_changed.is_empty() {
+                String::new()
+            } else {
+                format!(" (files: {})", o.files_changed.join(", "))
+            };
+            format!(
+                "- Iteration {} [{}] {status}: {}{files}",
+                o.iteration, o.cycle, o.outcome
+            )
+        })
+        .collect();
+    format!(
+        r"You are condensing the history of an automated coding pipeline so later iterations can see what happened without reading every entry.
+
+## Log Entries
+
+{entries}
+
+Summarize these entries in at most 10 bullet points: the work completed, decisions made, recurring failures and their causes, and anything left unfinished. Prefer concrete names (features, files, tests) over narration. Respond with ONLY the bullet points.",
+        entries = lines.join("\n"),
+    )
+}
+
+/// Digest every complete block of old entries in `logger`'s log, appending
+/// to the digest file next to it. Returns the digests written.
+///
+/// # Errors
+/// Returns an error if the log or digests can't be read or written, or
+/// Claude fails to produce a summary. Digests written before the failure are
+/// kept.
+pub async fn compact(logger: &JsonlLogger, settings: &CompactionConfig) -> Result<Vec<Digest>> {
+    let path = logger.log_path().with_file_name(DIGESTS_FILE);
+    let mut covered = covered(&load_digests(&path)?);
+    let total_entries = usize::try_from(logger.index()?.entries)?;
+    let mut written = Vec::new();
+    while let Some(block) = next_block(covered, total_entries, settings) {
+        let entries: Vec<CycleOutcome> = logger
+            .stream()?
+            .skip(block.start)
+            .take(block.len())
+            .collect();
+        let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+            break;
+        };
+        let cmd = build_command_with_options(
+            &build_compaction_prompt(&entries),
+            &[],
+            &CommandOptions {
+                model: settings.model.clone(),
+                ..CommandOptions::default()
+            },
+        );
+        let summary = run_for_result(cmd)
+            .await
+            .context("Failed to summarize log entries")?;
+        let digest = Digest {
+            start: block.start,
+            end: block.end,
+            first_iteration: first.iteration,
+            last_iteration: last.iteration,
+            summary: summary.trim().to_string(),
+            created_at: Utc::now(),
+        };
+        append_digest(&path, &digest)?;
+        covered = digest.end;
+        written.push(digest);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::make_test_outcome;
+    use tempfile::TempDir;
+
+    fn digest(start: usize, end: usize) -> Digest {
+        Digest {
+            start,
+            end,
+            first_iteration: u32::try_from(start + 1).unwrap(),
+            last_iteration: u32::try_from(end).unwrap(),
+            summary: format!("- entries {start}..{end}"),
+            created_at: Utc::now(),
+        }
+    }
+
+    // --- covered ---
+
+    #[test]
+    fn test_covered_stops_at_first_gap() {
+        assert_eq!(covered(&[]), 0);
+        assert_eq!(covered(&[digest(0, 50), digest(50, 100)]), 100);
+        assert_eq!(covered(&[digest(0, 50), digest(60, 100)]), 50);
+        assert_eq!(covered(&[digest(10, 50)]), 0);
+    }
+
+    // --- next_block ---
+
+    #[test]
+    fn test_next_block_leaves_recent_entries_verbatim() {
+        let settings = CompactionConfig {
+            keep_recent: 5,
+            block_size: 10,
+            model: None,
+        };
+        assert_eq!(next_block(0, 14, &settings), None);
+        assert_eq!(next_block(0, 15, &settings), Some(0..10));
+        assert_eq!(next_block(10, 24, &settings), None);
+        assert_eq!(next_block(10, 40, &settings), Some(10..20));
+    }
+
+    // --- storage ---
+
+    #[test]
+    fn test_digests_round_trip_and_skip_garbage() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(DIGESTS_FILE);
+        assert!(load_digests(&path).unwrap().is_empty());
+
+        append_digest(&path, &digest(0, 50)).unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "not json\n").unwrap();
+        append_digest(&path, &digest(50, 100)).unwrap();
+
+        let loaded = load_digests(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(covered(&loaded), 100);
+    }
+
+    // --- build_compaction_prompt ---
+
+    #[test]
+    fn test_compaction_prompt_lists_entries() {
+        let mut failed = make_test_outcome(2, "review", "Failed: tests broke");
+        failed.files_changed = vec!["src/lib.rs".to_string()];
+        let prompt =
+            build_compaction_prompt(&[make_test_outcome(1, "coding", "Added parser"), failed]);
+        assert!(
+            prompt.contains("- Iteration 1 [coding] ok: Added parser"),
+            "{prompt}"
+        );
+        assert!(
+            prompt.contains("- Iteration 2 [review] FAIL: Failed: tests broke (files: src/lib.rs)"),
+            "{prompt}"
+        );
+    }
+}
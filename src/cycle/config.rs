@@ -3,11 +3,17 @@
 //! Parses `cycles.toml` into structured cycle definitions.
 
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::cycle::overrides::{
+    apply_override, apply_profile, expand_step_templates, merge_defaults, resolve_cycle_inheritance,
+};
+use crate::cycle::router::compile_router_script;
+use crate::cycle::template::{expand_snippets, snippet_names};
+
 /// Context mode for a cycle - controls how much history is provided
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -16,10 +22,83 @@ pub enum ContextMode {
     Full,
     /// Summarized history
     Summaries,
+    /// Condensed digests of older entries (see `[compaction]`) plus one-line
+    /// summaries of the recent ones
+    Compact,
     /// No history context
     None,
 }
 
+/// Claude Code permission mode (maps to `--permission-mode`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionMode {
+    /// Prompt-based defaults (unlisted tools are denied in non-interactive runs)
+    Default,
+    /// Auto-approve file edits
+    AcceptEdits,
+    /// Read-only planning; no edits or commands are executed
+    Plan,
+    /// Skip all permission checks
+    BypassPermissions,
+}
+
+impl PermissionMode {
+    /// The value passed to `--permission-mode`.
+    #[must_use]
+    pub const fn as_arg(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::AcceptEdits => "acceptEdits",
+            Self::Plan => "plan",
+            Self::BypassPermissions => "bypassPermissions",
+        }
+    }
+}
+
+/// Minimum spacing between auto-triggered runs of a cycle.
+///
+/// Written as an integer (`min_interval = 3`, log entries since last run) or a
+/// duration string (`min_interval = "6h"`, wall-clock time since last run).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "MinIntervalRepr", into = "MinIntervalRepr")]
+pub enum MinInterval {
+    /// Number of log entries since the cycle last ran
+    Iterations(u32),
+    /// Seconds of wall-clock time since the cycle last ran
+    Duration(u64),
+}
+
+/// On-disk form of [`MinInterval`]: a bare integer or a duration string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum MinIntervalRepr {
+    Iterations(u32),
+    Duration(String),
+}
+
+impl TryFrom<MinIntervalRepr> for MinInterval {
+    type Error = String;
+
+    fn try_from(repr: MinIntervalRepr) -> std::result::Result<Self, Self::Error> {
+        match repr {
+            MinIntervalRepr::Iterations(n) => Ok(Self::Iterations(n)),
+            MinIntervalRepr::Duration(s) => parse_duration_secs(&s)
+                .map(Self::Duration)
+                .map_err(|e| format!("invalid min_interval: {e}")),
+        }
+    }
+}
+
+impl From<MinInterval> for MinIntervalRepr {
+    fn from(interval: MinInterval) -> Self {
+        match interval {
+            MinInterval::Iterations(n) => Self::Iterations(n),
+            MinInterval::Duration(secs) => Self::Duration(format!("{secs}s")),
+        }
+    }
+}
+
 /// Global configuration shared across all cycles
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct GlobalConfig {
@@ -35,12 +114,105 @@ pub struct GlobalConfig {
     /// Stop the entire run if this many consecutive cycles fail (default: 3)
     #[serde(default = "default_max_consecutive_failures")]
     pub max_consecutive_failures: u32,
+    /// Retries for a cycle that failed on a transient API error (overloaded,
+    /// rate limited, 5xx) before it counts as a failure (default: 3, 0 = disabled)
+    #[serde(default = "default_transient_retries")]
+    pub transient_retries: u32,
+    /// Wait before the first transient-error retry, doubled for each further
+    /// retry (default: 30)
+    #[serde(default = "default_transient_backoff_secs")]
+    pub transient_backoff_secs: u64,
     /// Print a periodic run summary every N iterations (default: 5, 0 = disabled)
     #[serde(default = "default_summary_interval")]
     pub summary_interval: u32,
     /// User-defined template variables accessible as `{{key}}` in prompts
     #[serde(default)]
     pub vars: HashMap<String, String>,
+    /// Tee raw stream-JSON output into `.flow/debug/` for troubleshooting (default: false)
+    #[serde(default)]
+    pub debug_stream: bool,
+    /// Stop starting new iterations once the run has lasted this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_run_duration_secs: Option<u64>,
+    /// Goal-based stop condition checked between iterations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_when: Option<StopWhenConfig>,
+    /// Replace Claude Code's system prompt (maps to `--system-prompt`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Project-wide ground rules appended to the system prompt
+    /// (maps to `--append-system-prompt`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append_system_prompt: Option<String>,
+    /// Create a branch per run from this template (e.g. `"flow/{run_id}"`) and
+    /// commit each cycle's changes onto it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_branch_template: Option<String>,
+    /// What to do with the run branch once the run finishes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_on_finish: Option<GitFinish>,
+    /// Claude model for every cycle, e.g. `"sonnet"` (maps to `--model`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// How a run branch is handed back at the end of a run (`global.git_on_finish`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GitFinish {
+    /// Fast-forward the starting branch to the run branch
+    FastForward,
+    /// Push the run branch and open a pull request with `gh`
+    PullRequest,
+}
+
+/// Goal-based stop condition for multi-iteration runs (`[global.stop_when]`).
+///
+/// The run stops once every configured condition holds.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct StopWhenConfig {
+    /// Stop when TODO.md has no pending tasks at these priorities (e.g. `["P0", "P1"]`).
+    /// An empty list means no pending tasks at any priority.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_pending_tasks: Option<Vec<String>>,
+    /// Stop when this shell command exits successfully (e.g. `"cargo test"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+impl GlobalConfig {
+    /// Validate global permissions, run limits, stop conditions, and git settings.
+    fn validate(&self) -> Result<()> {
+        for perm in &self.permissions {
+            validate_permission(perm)?;
+        }
+        if self.max_run_duration_secs == Some(0) {
+            bail!("global.max_run_duration_secs must be greater than 0");
+        }
+        if let Some(ref stop_when) = self.stop_when {
+            if stop_when.no_pending_tasks.is_none() && stop_when.command.is_none() {
+                bail!("global.stop_when must set 'no_pending_tasks' or 'command'");
+            }
+            if stop_when
+                .command
+                .as_deref()
+                .is_some_and(|c| c.trim().is_empty())
+            {
+                bail!("global.stop_when.command cannot be empty");
+            }
+        }
+        if self
+            .git_branch_template
+            .as_deref()
+            .is_some_and(|t| t.trim().is_empty())
+        {
+            bail!("global.git_branch_template cannot be empty");
+        }
+        if self.git_on_finish.is_some() && self.git_branch_template.is_none() {
+            bail!("global.git_on_finish requires global.git_branch_template");
+        }
+        Ok(())
+    }
 }
 
 const fn default_max_permission_denials() -> u32 {
@@ -55,6 +227,14 @@ const fn default_max_consecutive_failures() -> u32 {
     3
 }
 
+const fn default_transient_retries() -> u32 {
+    3
+}
+
+const fn default_transient_backoff_secs() -> u64 {
+    30
+}
+
 const fn default_summary_interval() -> u32 {
     5
 }
@@ -67,6 +247,8 @@ pub enum StepRouter {
     Sequential,
     /// Use an LLM call to determine the next step based on the completed step's output
     Llm,
+    /// Run the step's `router_script` (Rhai) to pick the next step
+    Script,
 }
 
 const fn default_step_router() -> StepRouter {
@@ -77,6 +259,50 @@ const fn default_max_visits() -> u32 {
     3
 }
 
+const fn default_selectable() -> bool {
+    true
+}
+
+/// Directory that `prompt_file` paths in the config at `path` are relative to.
+fn config_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+/// Expand the snippet includes in `prompt`, rejecting unknown snippets.
+fn include_snippets(
+    prompt: &str,
+    snippets: &HashMap<String, String>,
+    label: &str,
+) -> Result<String> {
+    if let Some(unknown) = snippet_names(prompt)
+        .into_iter()
+        .find(|name| !snippets.contains_key(*name))
+    {
+        bail!("{label} includes unknown snippet '{unknown}'");
+    }
+    Ok(expand_snippets(prompt, snippets))
+}
+
+/// Inline and file field names of cycle and step prompts.
+const PROMPT_FIELDS: (&str, &str) = ("prompt", "prompt_file");
+
+/// Read the file `label` (e.g. `Cycle 'coding'`) points at, which must not
+/// also set inline text. `fields` names the inline and file settings.
+fn read_prompt_file(
+    base_dir: &Path,
+    file: &Path,
+    inline: &str,
+    label: &str,
+    (inline_field, file_field): (&str, &str),
+) -> Result<String> {
+    if !inline.is_empty() {
+        bail!("{label} sets both '{inline_field}' and '{file_field}'; use one");
+    }
+    let path = base_dir.join(file);
+    std::fs::read_to_string(&path)
+        .with_context(|| format!("{label}: cannot read {file_field} '{}'", path.display()))
+}
+
 /// A single step within a multi-step cycle
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct StepConfig {
@@ -85,16 +311,42 @@ pub struct StepConfig {
     /// Optional session tag — steps sharing the same tag continue the same Claude session
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub session: Option<String>,
+    /// Save this step's session to `.flow/sessions.json` so later runs resume
+    /// the same conversation (requires `session`)
+    #[serde(default)]
+    pub persist: bool,
+    /// Start a new conversation for `session` even if the tag was used before;
+    /// later steps with the tag continue the new one (requires `session`)
+    #[serde(default)]
+    pub fresh_session: bool,
     /// The prompt to send to Claude Code for this step
+    #[serde(default)]
     pub prompt: String,
+    /// Markdown/text file holding the prompt, relative to the config file
+    /// (replaces `prompt`; loaded when the config is read)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_file: Option<PathBuf>,
     /// Additional permissions for this step (additive to global + cycle)
     #[serde(default)]
     pub permissions: Vec<String>,
+    /// Permission mode for this step. Overrides the cycle-level value when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permission_mode: Option<PermissionMode>,
+    /// History context injected into this step's prompt.
+    /// Overrides the cycle-level `context` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<ContextMode>,
     /// How to determine the next step after this one completes.
     /// `sequential` (default): proceed to the next step in TOML order.
     /// `llm`: invoke a model to choose the next step based on this step's output.
+    /// `script`: evaluate `router_script`.
     #[serde(default = "default_step_router")]
     pub router: StepRouter,
+    /// Rhai script for `router = "script"`. Sees `step`, `steps`, `result_text`,
+    /// `exit_code`, and `visits`; returns a step name, `"DONE"`, or `()` to
+    /// continue sequentially.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub router_script: Option<String>,
     /// Maximum number of times this step can be visited in one cycle execution.
     /// Prevents infinite loops when using LLM routing. Default: 3.
     #[serde(default = "default_max_visits")]
@@ -107,6 +359,126 @@ pub struct StepConfig {
     /// Overrides the cycle-level value when set.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_cost_usd: Option<f64>,
+    /// Maximum tokens for this step; Flow kills the invocation once its usage
+    /// passes this. Overrides the cycle-level value when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    /// Skip this step when these conditions hold, moving on to the next step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_if: Option<SkipIfConfig>,
+    /// Re-run this step until these conditions hold (bounded by `max_visits`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_until: Option<RepeatUntilConfig>,
+}
+
+/// Conditions for skipping a step (`[cycle.step.skip_if]`); the step is
+/// skipped when every condition that is set holds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SkipIfConfig {
+    /// Shell command that exits successfully (e.g. `"git diff --quiet -- PLAN.md"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Path (relative to the project) that exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exists: Option<PathBuf>,
+}
+
+/// When a looping step is done (`[cycle.step.repeat_until]`); the step is
+/// re-run until every condition that is set holds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RepeatUntilConfig {
+    /// Shell command that exits successfully (e.g. `"cargo test"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Regex that the step's result text matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_matches: Option<String>,
+}
+
+impl StepConfig {
+    /// Validate the step prompt, permissions, and session flags.
+    fn validate(&self, cycle_name: &str) -> Result<()> {
+        if self.prompt.is_empty() {
+            bail!(
+                "Step '{}' in cycle '{cycle_name}' must have a 'prompt' or 'prompt_file'",
+                self.name
+            );
+        }
+        for (flag, set) in [
+            ("persist", self.persist),
+            ("fresh_session", self.fresh_session),
+        ] {
+            if set && self.session.is_none() {
+                bail!(
+                    "Step '{}' in cycle '{cycle_name}' sets {flag} = true but has no session tag",
+                    self.name
+                );
+            }
+        }
+        for perm in &self.permissions {
+            validate_permission(perm)
+                .with_context(|| format!("in step '{}' of cycle '{cycle_name}'", self.name))?;
+        }
+        match (&self.router, &self.router_script) {
+            (StepRouter::Script, None) => bail!(
+                "Step '{}' in cycle '{cycle_name}' uses router = \"script\" but has no router_script",
+                self.name
+            ),
+            (StepRouter::Script, Some(script)) => compile_router_script(script)
+                .with_context(|| format!("in step '{}' of cycle '{cycle_name}'", self.name))?,
+            (_, Some(_)) => bail!(
+                "Step '{}' in cycle '{cycle_name}' sets router_script but router is not \"script\"",
+                self.name
+            ),
+            (_, None) => {}
+        }
+        if let Some(ref skip_if) = self.skip_if {
+            if skip_if.command.is_none() && skip_if.exists.is_none() {
+                bail!(
+                    "Step '{}' in cycle '{cycle_name}': skip_if must set 'command' or 'exists'",
+                    self.name
+                );
+            }
+            if skip_if
+                .command
+                .as_deref()
+                .is_some_and(|c| c.trim().is_empty())
+            {
+                bail!(
+                    "Step '{}' in cycle '{cycle_name}': skip_if.command cannot be empty",
+                    self.name
+                );
+            }
+        }
+        if let Some(ref repeat_until) = self.repeat_until {
+            repeat_until.validate(&self.name, cycle_name)?;
+        }
+        Ok(())
+    }
+}
+
+impl RepeatUntilConfig {
+    /// Check that at least one condition is set and that they are well-formed.
+    fn validate(&self, step_name: &str, cycle_name: &str) -> Result<()> {
+        if self.command.is_none() && self.result_matches.is_none() {
+            bail!(
+                "Step '{step_name}' in cycle '{cycle_name}': repeat_until must set 'command' or 'result_matches'"
+            );
+        }
+        if let Some(ref pattern) = self.result_matches {
+            if let Err(e) = regex::Regex::new(pattern) {
+                bail!(
+                    "Step '{step_name}' in cycle '{cycle_name}': invalid repeat_until.result_matches regex '{pattern}': {e}"
+                );
+            }
+        }
+        if self.command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+            bail!(
+                "Step '{step_name}' in cycle '{cycle_name}': repeat_until.command cannot be empty"
+            );
+        }
+        Ok(())
+    }
 }
 
 /// A single cycle definition
@@ -116,22 +488,51 @@ pub struct CycleConfig {
     pub name: String,
     /// Human-readable description
     pub description: String,
+    /// Cycle this one inherits unset keys from (resolved when the config loads)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
     /// The prompt to send to Claude Code (used for single-step cycles; empty for multi-step)
     #[serde(default)]
     pub prompt: String,
+    /// File holding the prompt, relative to the config file (replaces `prompt`;
+    /// loaded when the config is read)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_file: Option<PathBuf>,
     /// Additional permissions for this cycle (additive to global)
     #[serde(default)]
     pub permissions: Vec<String>,
-    /// Cycles that must complete before this one triggers
-    #[serde(default)]
+    /// Cycles that trigger this one when any of them completes (`after_any` is an alias)
+    #[serde(default, alias = "after_any")]
     pub after: Vec<String>,
+    /// Cycles that must all have completed since this one last ran before it triggers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after_all: Vec<String>,
+    /// Cycles whose failure triggers this one (e.g. a review after a failed coding cycle)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after_failures_of: Vec<String>,
     /// How much context to provide
     #[serde(default = "default_context")]
     pub context: ContextMode,
-    /// Minimum iterations since last run before this cycle can be auto-triggered.
-    /// None means no constraint (always eligible).
+    /// Most recent log entries included by `full` and `summaries` context
+    /// (default: the whole log)
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub min_interval: Option<u32>,
+    pub context_window: Option<usize>,
+    /// Minimum iterations (or wall-clock time, e.g. `"6h"`) since last run before
+    /// this cycle can be auto-triggered. None means no constraint (always eligible).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_interval: Option<MinInterval>,
+    /// Auto-trigger this cycle whenever this many log entries have accumulated since
+    /// it last ran, independent of `after` relationships. None means no cadence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every_n_iterations: Option<u32>,
+    /// Relative preference for the AI selector (higher = chosen more often).
+    /// None means the default weight of 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    /// Whether the AI selector may pick this cycle. Non-selectable cycles still
+    /// run via `--cycle` or triggers.
+    #[serde(default = "default_selectable")]
+    pub selectable: bool,
     /// Maximum number of agentic turns per invocation (maps to `--max-turns`).
     /// Used as fallback for steps that don't set their own `max_turns`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -140,9 +541,126 @@ pub struct CycleConfig {
     /// Used as fallback for steps that don't set their own `max_cost_usd`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_cost_usd: Option<f64>,
+    /// Maximum tokens (input, output, and cache reads/writes) per invocation,
+    /// enforced by Flow mid-stream. Fallback for steps without their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
     /// Steps for multi-step cycles. Empty means single-step (uses top-level `prompt`).
     #[serde(default, rename = "step")]
     pub steps: Vec<StepConfig>,
+    /// Recovery cycle to run when this cycle fails, before the failure counts
+    /// toward `max_consecutive_failures`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+    /// Cycle whose changes this (review) cycle's verdict commits or rolls back,
+    /// when it runs right after that cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arbitrates: Option<String>,
+    /// Extra criteria a successful exit must also meet to count as success
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_when: Option<SuccessWhenConfig>,
+    /// Rubric the outcome is scored against after each run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval: Option<EvalConfig>,
+    /// Claude Code permission mode (`default`, `acceptEdits`, `plan`,
+    /// `bypassPermissions`). Used as fallback for steps that don't set their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permission_mode: Option<PermissionMode>,
+    /// Snapshot the working tree before this cycle and restore it if the cycle
+    /// fails (including unmet `success_when` criteria). Requires a git repository.
+    #[serde(default)]
+    pub rollback_on_failure: bool,
+    /// Run the cycle this many times from the same starting tree and keep the
+    /// sample a judge picks as best. None (or 1) means a single run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<u32>,
+    /// Run this cycle's Claude Code subprocess in a sandbox (`"docker"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxKind>,
+    /// Container image for the sandbox (overrides `[sandbox].image`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox_image: Option<String>,
+    /// Replace the system prompt for this cycle (overrides `global.system_prompt`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Text appended to the system prompt after `global.append_system_prompt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append_system_prompt: Option<String>,
+    /// Claude model for this cycle (overrides `global.model`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// Per-cycle rubric evaluation (`[cycle.eval]`).
+///
+/// After each run, a no-tool Claude Code call scores the outcome 1–5 against
+/// every criterion; the scores are logged and trended by `flow stats`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct EvalConfig {
+    /// Rubric criteria, each scored separately (e.g. `"Tests cover the change"`)
+    pub criteria: Vec<String>,
+    /// Model that scores the outcome (default: Claude Code's default model)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl EvalConfig {
+    /// Check that the rubric has criteria and none is blank or repeated.
+    fn validate(&self, cycle_name: &str) -> Result<()> {
+        if self.criteria.is_empty() {
+            bail!("Cycle '{cycle_name}': eval.criteria cannot be empty");
+        }
+        let mut seen = HashSet::new();
+        for criterion in &self.criteria {
+            if criterion.trim().is_empty() {
+                bail!("Cycle '{cycle_name}': eval.criteria cannot contain an empty criterion");
+            }
+            if !seen.insert(criterion) {
+                bail!("Cycle '{cycle_name}': duplicate eval criterion '{criterion}'");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Per-cycle success criteria (`[cycle.success_when]`).
+///
+/// Evaluated after a cycle exits successfully; every configured criterion must
+/// hold or the cycle is recorded as failed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct SuccessWhenConfig {
+    /// Regex that the final result text must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_matches: Option<String>,
+    /// Shell command that must exit successfully (e.g. `"cargo test"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Minimum number of passing tests observed during the cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_tests_passed: Option<u32>,
+}
+
+impl SuccessWhenConfig {
+    /// Check that at least one criterion is set and that they are well-formed.
+    fn validate(&self, cycle_name: &str) -> Result<()> {
+        if self.result_matches.is_none()
+            && self.command.is_none()
+            && self.min_tests_passed.is_none()
+        {
+            bail!(
+                "Cycle '{cycle_name}': success_when must set 'result_matches', 'command', or 'min_tests_passed'"
+            );
+        }
+        if let Some(ref pattern) = self.result_matches {
+            if let Err(e) = regex::Regex::new(pattern) {
+                bail!("Cycle '{cycle_name}': invalid success_when.result_matches regex '{pattern}': {e}");
+            }
+        }
+        if self.command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+            bail!("Cycle '{cycle_name}': success_when.command cannot be empty");
+        }
+        Ok(())
+    }
 }
 
 const fn default_context() -> ContextMode {
@@ -150,6 +668,18 @@ const fn default_context() -> ContextMode {
 }
 
 impl CycleConfig {
+    /// Check `samples` (within `1..=MAX_SAMPLES`) and the `eval` rubric.
+    fn validate_evaluation(&self) -> Result<()> {
+        let max = crate::cycle::samples::MAX_SAMPLES;
+        if self.samples.is_some_and(|n| n == 0 || n > max) {
+            bail!("Cycle '{}': samples must be between 1 and {max}", self.name);
+        }
+        if let Some(ref eval) = self.eval {
+            eval.validate(&self.name)?;
+        }
+        Ok(())
+    }
+
     /// Returns `true` if this cycle has explicit steps (multi-step cycle).
     ///
     /// Single-step cycles use the top-level `prompt` field. Multi-step cycles
@@ -158,6 +688,79 @@ impl CycleConfig {
     pub const fn is_multi_step(&self) -> bool {
         !self.steps.is_empty()
     }
+
+    /// Effective `(system_prompt, append_system_prompt)` for this cycle.
+    ///
+    /// The cycle's `system_prompt` replaces the global one; append texts are
+    /// combined, global first.
+    #[must_use]
+    pub fn system_prompts(&self, global: &GlobalConfig) -> (Option<String>, Option<String>) {
+        let system_prompt = self
+            .system_prompt
+            .clone()
+            .or_else(|| global.system_prompt.clone());
+        let append = match (&global.append_system_prompt, &self.append_system_prompt) {
+            (Some(g), Some(c)) => Some(format!("{g}\n\n{c}")),
+            (g, c) => c.clone().or_else(|| g.clone()),
+        };
+        (system_prompt, append)
+    }
+
+    /// Effective model: the cycle's `model`, else `global.model`.
+    #[must_use]
+    pub fn model<'a>(&'a self, global: &'a GlobalConfig) -> Option<&'a str> {
+        self.model.as_deref().or(global.model.as_deref())
+    }
+
+    /// Context mode for a step: the step's own `context`, else the cycle's.
+    #[must_use]
+    pub const fn step_context<'a>(&'a self, step: &'a StepConfig) -> &'a ContextMode {
+        match &step.context {
+            Some(mode) => mode,
+            None => &self.context,
+        }
+    }
+
+    /// Whether the cycle's prompt (or any step's) injects log history, so
+    /// running it requires reading the log.
+    #[must_use]
+    pub fn uses_history(&self) -> bool {
+        self.context_modes().any(|mode| *mode != ContextMode::None)
+    }
+
+    /// How many of the newest log entries running the cycle needs: its
+    /// `context_window`, unless a compact prompt needs the whole log (`None`).
+    #[must_use]
+    pub fn history_window(&self) -> Option<usize> {
+        self.context_window.filter(|_| !self.uses_compaction())
+    }
+
+    /// Whether the cycle's prompt (or any step's) uses `context = "compact"`.
+    #[must_use]
+    pub fn uses_compaction(&self) -> bool {
+        self.context_modes()
+            .any(|mode| *mode == ContextMode::Compact)
+    }
+
+    /// Effective context mode of each prompt the cycle sends: the cycle's
+    /// own for a single-step cycle, else each step's.
+    fn context_modes(&self) -> Box<dyn Iterator<Item = &ContextMode> + '_> {
+        if self.steps.is_empty() {
+            return Box::new(std::iter::once(&self.context));
+        }
+        Box::new(self.steps.iter().map(|step| self.step_context(step)))
+    }
+
+    /// All cycles that can auto-trigger this one (`after`, `after_all`, `after_failures_of`).
+    #[must_use]
+    pub fn trigger_sources(&self) -> Vec<&str> {
+        self.after
+            .iter()
+            .chain(&self.after_all)
+            .chain(&self.after_failures_of)
+            .map(String::as_str)
+            .collect()
+    }
 }
 
 /// Configuration for the AI cycle selector
@@ -166,811 +769,3411 @@ pub struct SelectorConfig {
     /// Custom prompt/guidance for the selector (replaces the default selection criteria)
     #[serde(default)]
     pub prompt: String,
+    /// Give up on a selector call after this many seconds (default: 300)
+    #[serde(default = "default_selector_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Cycle to run when the selector fails, times out, or returns an
+    /// unparseable answer (default: abort the run)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_cycle: Option<String>,
 }
 
-/// Top-level Flow configuration parsed from cycles.toml
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct FlowConfig {
-    /// Global configuration
-    pub global: GlobalConfig,
-    /// Optional selector configuration
+/// Selector call timeout used when `selector.timeout_secs` isn't set
+pub const DEFAULT_SELECTOR_TIMEOUT_SECS: u64 = 300;
+
+const fn default_selector_timeout_secs() -> u64 {
+    DEFAULT_SELECTOR_TIMEOUT_SECS
+}
+
+/// Configuration for `flow doctor`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct DoctorConfig {
+    /// Diagnostic codes to suppress from reports (e.g., `["D004", "D006"]`)
     #[serde(default)]
-    pub selector: Option<SelectorConfig>,
-    /// Cycle definitions
-    #[serde(rename = "cycle")]
-    pub cycles: Vec<CycleConfig>,
+    pub suppress: Vec<String>,
+    /// Unsandboxed cycles allowed broad grants like `Bash(*)` without a D010 warning
+    #[serde(default)]
+    pub trusted_cycles: Vec<String>,
 }
 
-impl FlowConfig {
-    /// Parse a cycles.toml file from a path
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path.as_ref();
-        let content = std::fs::read_to_string(path)
-            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
-        Self::parse(&content)
-    }
+/// Retention settings for `flow gc` (`[gc]`), in days
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GcConfig {
+    /// Keep `--debug-stream` transcripts in `<log_dir>/debug/` this long (default: 14)
+    #[serde(default = "default_gc_debug_days")]
+    pub debug_days: u32,
+    /// Keep `--record` recordings in `<log_dir>/recordings/` this long (default: 30)
+    #[serde(default = "default_gc_recording_days")]
+    pub recording_days: u32,
+    /// Keep rotated or backed-up logs (`log.jsonl.*`) this long (default: 30)
+    #[serde(default = "default_gc_log_backup_days")]
+    pub log_backup_days: u32,
+    /// Prune persisted sessions unused for this long (default: 30)
+    #[serde(default = "default_gc_session_days")]
+    pub session_days: u32,
+}
 
-    /// Parse cycles.toml content from a string
-    pub fn parse(content: &str) -> Result<Self> {
-        let config: Self = toml::from_str(content).context("Failed to parse cycles.toml")?;
-        config.validate()?;
-        Ok(config)
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            debug_days: default_gc_debug_days(),
+            recording_days: default_gc_recording_days(),
+            log_backup_days: default_gc_log_backup_days(),
+            session_days: default_gc_session_days(),
+        }
     }
+}
 
-    /// Find a cycle by name
-    #[must_use]
-    pub fn get_cycle(&self, name: &str) -> Option<&CycleConfig> {
-        self.cycles.iter().find(|c| c.name == name)
-    }
+const fn default_gc_debug_days() -> u32 {
+    14
+}
 
-    /// Validate the configuration
-    fn validate(&self) -> Result<()> {
-        // Check for duplicate cycle names
-        let mut seen = HashSet::new();
-        for cycle in &self.cycles {
-            if !seen.insert(&cycle.name) {
-                bail!("Duplicate cycle name: '{}'", cycle.name);
-            }
-        }
+const fn default_gc_recording_days() -> u32 {
+    30
+}
 
-        // Check that `after` references exist
-        let names: HashSet<&str> = self.cycles.iter().map(|c| c.name.as_str()).collect();
-        for cycle in &self.cycles {
-            for dep in &cycle.after {
-                if !names.contains(dep.as_str()) {
-                    bail!(
-                        "Cycle '{}' references unknown cycle '{}' in 'after'",
-                        cycle.name,
-                        dep
-                    );
-                }
-            }
-        }
+const fn default_gc_log_backup_days() -> u32 {
+    30
+}
 
-        // Check that cycle names are non-empty
-        for cycle in &self.cycles {
-            if cycle.name.trim().is_empty() {
-                bail!("Cycle name cannot be empty");
-            }
-        }
+const fn default_gc_session_days() -> u32 {
+    30
+}
 
-        // Validate permission strings in global config
-        for perm in &self.global.permissions {
-            validate_permission(perm)?;
+/// History compaction settings (`[compaction]`) for `context = "compact"`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompactionConfig {
+    /// Newest log entries always kept verbatim, never digested (default: 20)
+    #[serde(default = "default_compaction_keep_recent")]
+    pub keep_recent: usize,
+    /// Log entries condensed into each digest (default: 50)
+    #[serde(default = "default_compaction_block_size")]
+    pub block_size: usize,
+    /// Model that writes the digests (default: Claude Code's default model)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            keep_recent: default_compaction_keep_recent(),
+            block_size: default_compaction_block_size(),
+            model: None,
         }
+    }
+}
 
-        // Validate permission strings in each cycle
-        for cycle in &self.cycles {
-            for perm in &cycle.permissions {
-                validate_permission(perm).with_context(|| format!("in cycle '{}'", cycle.name))?;
+const fn default_compaction_keep_recent() -> usize {
+    20
+}
+
+const fn default_compaction_block_size() -> usize {
+    50
+}
+
+/// Built-in test runner output formats for `[test_parser]`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TestParserPreset {
+    /// `cargo test` summary lines (default)
+    #[default]
+    Cargo,
+    /// pytest summary lines
+    Pytest,
+    /// Jest `Tests:` summary lines
+    Jest,
+    /// `go test -v` per-test lines
+    Go,
+}
+
+/// Configuration for extracting test counts from tool output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct TestParserConfig {
+    /// Built-in preset to start from (default: cargo)
+    #[serde(default)]
+    pub preset: TestParserPreset,
+    /// Custom regex for passed tests (overrides the preset). A capture group
+    /// supplies the count; without one, each match counts as a single test.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passed: Option<String>,
+    /// Custom regex for failed tests (overrides the preset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed: Option<String>,
+    /// Custom regex for ignored/skipped tests (overrides the preset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignored: Option<String>,
+}
+
+impl TestParserConfig {
+    /// Check that custom test parser patterns compile.
+    fn validate(&self) -> Result<()> {
+        for (field, pattern) in [
+            ("passed", &self.passed),
+            ("failed", &self.failed),
+            ("ignored", &self.ignored),
+        ] {
+            if let Some(pattern) = pattern {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    bail!("Invalid test_parser.{field} regex '{pattern}': {e}");
+                }
             }
         }
+        Ok(())
+    }
+}
 
-        // Validate max_turns and max_cost_usd on cycles and steps
-        for cycle in &self.cycles {
-            validate_limits(cycle.max_turns, cycle.max_cost_usd, &cycle.name, None)?;
-            for step in &cycle.steps {
-                validate_limits(
-                    step.max_turns,
-                    step.max_cost_usd,
-                    &cycle.name,
-                    Some(&step.name),
-                )?;
-            }
+/// Test-suite regression guard (`[test_guard]`).
+///
+/// Runs `command` before and after each guarded cycle and parses the output
+/// with `[test_parser]`; the pass/fail counts before and after are logged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct TestGuardConfig {
+    /// Shell command that runs the test suite (e.g. `"cargo test"`)
+    pub command: String,
+    /// Cycles to guard (default: every cycle)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycles: Vec<String>,
+    /// Fail a successful cycle that leaves fewer passing or more failing tests
+    #[serde(default)]
+    pub fail_on_regression: bool,
+}
+
+impl TestGuardConfig {
+    /// Whether `cycle_name` is guarded.
+    #[must_use]
+    pub fn guards(&self, cycle_name: &str) -> bool {
+        self.cycles.is_empty() || self.cycles.iter().any(|c| c == cycle_name)
+    }
+}
+
+/// Lint feedback loop (`[lint]`).
+///
+/// Runs `command` after each linted cycle, logs the warning count, and lists
+/// the warnings the cycle introduced in the next linted cycle's context.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct LintConfig {
+    /// Shell command that runs the linter (e.g. `"cargo clippy --all-targets"`)
+    pub command: String,
+    /// Cycles to lint (default: every cycle)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycles: Vec<String>,
+    /// Regex matching one warning per line (default: cargo's output format)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+impl LintConfig {
+    /// Whether `cycle_name` is linted.
+    #[must_use]
+    pub fn lints(&self, cycle_name: &str) -> bool {
+        self.cycles.is_empty() || self.cycles.iter().any(|c| c == cycle_name)
+    }
+}
+
+/// Secret redaction for Claude Code output (`[redaction]`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RedactionConfig {
+    /// Mask built-in secret patterns: API keys, tokens, and emails (default: true)
+    #[serde(default = "default_redaction_builtin")]
+    pub builtin: bool,
+    /// Additional regexes to mask
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            builtin: default_redaction_builtin(),
+            patterns: Vec::new(),
         }
+    }
+}
 
-        // Validate that every cycle has either a prompt (single-step) or steps (multi-step)
-        for cycle in &self.cycles {
-            if cycle.steps.is_empty() && cycle.prompt.is_empty() {
-                bail!(
-                    "Cycle '{}' must have a 'prompt' (single-step) or '[[cycle.step]]' entries (multi-step)",
-                    cycle.name
-                );
+const fn default_redaction_builtin() -> bool {
+    true
+}
+
+impl RedactionConfig {
+    /// Check that custom redaction patterns compile.
+    fn validate(&self) -> Result<()> {
+        for pattern in &self.patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                bail!("Invalid redaction pattern '{pattern}': {e}");
             }
         }
+        Ok(())
+    }
+}
 
-        // Validate multi-step cycle constraints
-        for cycle in &self.cycles {
-            if !cycle.steps.is_empty() {
-                // Multi-step cycle: prompt must not also be set
-                if !cycle.prompt.is_empty() {
-                    bail!(
-                        "Cycle '{}' cannot have both a top-level 'prompt' and '[[cycle.step]]' entries",
-                        cycle.name
-                    );
-                }
+/// Where a cycle's Claude Code subprocess runs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxKind {
+    /// Inside a Docker container with the repository bind-mounted
+    Docker,
+}
 
-                // Step names must be unique and non-empty
-                let mut step_names = HashSet::new();
-                for step in &cycle.steps {
-                    if step.name.trim().is_empty() {
-                        bail!("Step name cannot be empty in cycle '{}'", cycle.name);
-                    }
-                    if !step_names.insert(step.name.as_str()) {
-                        bail!(
-                            "Duplicate step name '{}' in cycle '{}'",
-                            step.name,
-                            cycle.name
-                        );
-                    }
-                }
+/// Container settings shared by sandboxed cycles (`[sandbox]`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SandboxConfig {
+    /// Default image for cycles that don't set `sandbox_image`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// CPU limit (maps to `docker run --cpus`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<f64>,
+    /// Memory limit, e.g. `"4g"` (maps to `docker run --memory`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    /// Maximum number of processes (maps to `docker run --pids-limit`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pids_limit: Option<u32>,
+    /// Host environment variables passed into the container; nothing else from
+    /// the host environment or home directory is visible (default: `ANTHROPIC_API_KEY`)
+    #[serde(default = "default_sandbox_env")]
+    pub env: Vec<String>,
+}
 
-                // Validate step permissions
-                for step in &cycle.steps {
-                    for perm in &step.permissions {
-                        validate_permission(perm).with_context(|| {
-                            format!("in step '{}' of cycle '{}'", step.name, cycle.name)
-                        })?;
-                    }
-                }
-            }
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            image: None,
+            cpus: None,
+            memory: None,
+            pids_limit: None,
+            env: default_sandbox_env(),
         }
+    }
+}
+
+fn default_sandbox_env() -> Vec<String> {
+    vec!["ANTHROPIC_API_KEY".to_string()]
+}
 
+impl SandboxConfig {
+    /// Check that resource limits are positive.
+    fn validate(&self) -> Result<()> {
+        if self.cpus.is_some_and(|c| !c.is_finite() || c <= 0.0) {
+            bail!("sandbox.cpus must be a positive number");
+        }
+        if self.pids_limit == Some(0) {
+            bail!("sandbox.pids_limit must be greater than 0");
+        }
         Ok(())
     }
 }
 
-/// Validate that a permission string matches `--allowedTools` syntax:
-/// either `ToolName` (bare) or `ToolName(specifier)`.
+/// Run event that can raise a notification
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// The run finished all of its iterations
+    RunCompleted,
+    /// A stop gate ended the run early (failures, denials, stop conditions, time limit)
+    RunStopped,
+    /// The run's total cost reached `budget_warning_usd`
+    BudgetWarning,
+    /// A cycle finished successfully
+    CycleCompleted,
+    /// A cycle failed
+    CycleFailed,
+}
+
+impl NotificationEvent {
+    /// How urgent the event is, for `min_severity` rule matching.
+    #[must_use]
+    pub const fn severity(self) -> NotificationSeverity {
+        match self {
+            Self::RunCompleted | Self::CycleCompleted => NotificationSeverity::Info,
+            Self::BudgetWarning | Self::CycleFailed => NotificationSeverity::Warning,
+            Self::RunStopped => NotificationSeverity::Error,
+        }
+    }
+}
+
+/// Urgency of a notification event, ordered from least to most severe
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationSeverity {
+    /// Routine progress
+    Info,
+    /// Worth a look
+    Warning,
+    /// The run stopped
+    Error,
+}
+
+/// Kind of destination a notification channel delivers to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelKind {
+    /// Native desktop notification
+    Desktop,
+    /// JSON `POST` to an arbitrary URL
+    Webhook,
+    /// Slack incoming webhook
+    Slack,
+}
+
+/// A named notification destination (`[notifications.channels.<name>]`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NotificationChannel {
+    /// Where the channel delivers
+    #[serde(rename = "type")]
+    pub kind: ChannelKind,
+    /// Endpoint for `webhook` and `slack` channels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Routes matching events to channels (`[[notifications.rule]]`).
 ///
-/// Tool names must start with an uppercase ASCII letter and contain only
-/// ASCII alphanumeric characters.
-fn validate_permission(perm: &str) -> Result<()> {
-    if perm.is_empty() {
-        bail!("Invalid permission '': permission string cannot be empty");
+/// Every configured condition must hold; empty lists match anything.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotificationRule {
+    /// Events this rule matches (empty = any)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<NotificationEvent>,
+    /// Cycle names this rule matches (empty = any); run-level events never
+    /// match a rule that lists cycles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycles: Vec<String>,
+    /// Least severe event this rule matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<NotificationSeverity>,
+    /// Only match when the cycle (or run) cost is at least this many USD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_cost_usd: Option<f64>,
+    /// Channel names to deliver to (`"desktop"` is always available)
+    pub channels: Vec<String>,
+}
+
+/// Notifications for run events (`[notifications]`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotificationsConfig {
+    /// Pop native desktop notifications for `events` (default: true).
+    /// Ignored once any `rule` is configured.
+    #[serde(default = "default_notifications_desktop")]
+    pub desktop: bool,
+    /// Events that raise a desktop notification when no rules are configured
+    /// (default: run completed, run stopped, budget warning)
+    #[serde(default = "default_notification_events")]
+    pub events: Vec<NotificationEvent>,
+    /// Warn once when the run's total cost reaches this many USD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_warning_usd: Option<f64>,
+    /// Named delivery channels referenced by rules
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub channels: HashMap<String, NotificationChannel>,
+    /// Routing rules; when present they replace the `desktop`/`events` default
+    #[serde(default, rename = "rule", skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<NotificationRule>,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            desktop: default_notifications_desktop(),
+            events: default_notification_events(),
+            budget_warning_usd: None,
+            channels: HashMap::new(),
+            rules: Vec::new(),
+        }
     }
+}
 
-    // Find where the tool name ends
-    let tool_end = perm
-        .find(|c: char| !c.is_ascii_alphanumeric())
-        .unwrap_or(perm.len());
+const fn default_notifications_desktop() -> bool {
+    true
+}
 
-    let tool_name = &perm[..tool_end];
+fn default_notification_events() -> Vec<NotificationEvent> {
+    vec![
+        NotificationEvent::RunCompleted,
+        NotificationEvent::RunStopped,
+        NotificationEvent::BudgetWarning,
+    ]
+}
 
-    // Tool name must be non-empty and start with uppercase
-    if tool_name.is_empty() || !tool_name.starts_with(|c: char| c.is_ascii_uppercase()) {
-        bail!("Invalid permission '{perm}': tool name must start with an uppercase letter");
+impl NotificationsConfig {
+    /// Check thresholds, channel endpoints, and that rules name known channels.
+    fn validate(&self) -> Result<()> {
+        if self
+            .budget_warning_usd
+            .is_some_and(|b| !b.is_finite() || b <= 0.0)
+        {
+            bail!("notifications.budget_warning_usd must be a positive number");
+        }
+        for (name, channel) in &self.channels {
+            let needs_url = channel.kind != ChannelKind::Desktop;
+            if needs_url && channel.url.as_deref().is_none_or(|u| u.trim().is_empty()) {
+                bail!("Notification channel '{name}' needs a url");
+            }
+        }
+        for rule in &self.rules {
+            if rule.channels.is_empty() {
+                bail!("Every [[notifications.rule]] needs at least one channel");
+            }
+            if let Some(unknown) = rule
+                .channels
+                .iter()
+                .find(|c| *c != "desktop" && !self.channels.contains_key(*c))
+            {
+                bail!("Notification rule references unknown channel '{unknown}'");
+            }
+            if rule.min_cost_usd.is_some_and(|c| !c.is_finite() || c < 0.0) {
+                bail!("notifications.rule.min_cost_usd must be a non-negative number");
+            }
+        }
+        Ok(())
     }
+}
 
-    let rest = &perm[tool_end..];
+/// Shell commands run on lifecycle events (`[hooks]`).
+///
+/// Each command runs through `sh -c` with the event as a JSON line on stdin.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HooksConfig {
+    /// Commands run when the run loop starts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub run_start: Vec<String>,
+    /// Commands run after every cycle
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycle_end: Vec<String>,
+    /// Commands run when the run loop ends
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub run_end: Vec<String>,
+    /// Commands run when a cycle fails or a stop gate trips
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failure: Vec<String>,
+    /// Kill a hook command after this many seconds (default: 30)
+    #[serde(default = "default_hook_timeout_secs")]
+    pub timeout_secs: u64,
+}
 
-    if rest.is_empty() {
-        // Bare tool name like "Read" — valid
-        return Ok(());
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            run_start: Vec::new(),
+            cycle_end: Vec::new(),
+            run_end: Vec::new(),
+            failure: Vec::new(),
+            timeout_secs: default_hook_timeout_secs(),
+        }
     }
+}
 
-    // Must be ToolName(specifier)
-    if !rest.starts_with('(') || !rest.ends_with(')') {
-        bail!("Invalid permission '{perm}': expected format 'ToolName' or 'ToolName(specifier)'");
+const fn default_hook_timeout_secs() -> u64 {
+    30
+}
+
+impl HooksConfig {
+    /// Check that commands are non-empty and the timeout is positive.
+    fn validate(&self) -> Result<()> {
+        if self.timeout_secs == 0 {
+            bail!("hooks.timeout_secs must be greater than 0");
+        }
+        let commands = [
+            &self.run_start,
+            &self.cycle_end,
+            &self.run_end,
+            &self.failure,
+        ];
+        if commands
+            .iter()
+            .flat_map(|c| c.iter())
+            .any(|c| c.trim().is_empty())
+        {
+            bail!("Hook commands cannot be empty");
+        }
+        Ok(())
     }
+}
 
-    // Extract specifier (strip outer parens)
-    let specifier = &rest[1..rest.len() - 1];
-    if specifier.is_empty() {
-        bail!("Invalid permission '{perm}': specifier inside parentheses cannot be empty");
+/// A reusable prompt fragment (`[snippet.<name>]`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SnippetConfig {
+    /// Snippet text
+    #[serde(default)]
+    pub text: String,
+    /// File holding the text, relative to the config file (replaces `text`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+/// Token prices for one model (`[pricing.<model>]`), in USD per million tokens
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelPrice {
+    /// Uncached input tokens
+    #[serde(default)]
+    pub input: f64,
+    /// Output tokens
+    #[serde(default)]
+    pub output: f64,
+    /// Input tokens read from the prompt cache
+    #[serde(default)]
+    pub cache_read: f64,
+    /// Input tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_write: f64,
+}
+
+impl ModelPrice {
+    /// Reject negative or non-finite prices.
+    fn validate(&self, model: &str) -> Result<()> {
+        let prices = [
+            ("input", self.input),
+            ("output", self.output),
+            ("cache_read", self.cache_read),
+            ("cache_write", self.cache_write),
+        ];
+        for (name, price) in prices {
+            if !price.is_finite() || price < 0.0 {
+                bail!("pricing.{model}.{name} must be a non-negative number");
+            }
+        }
+        Ok(())
     }
+}
 
-    Ok(())
+/// Color palette for terminal output (`[display].theme`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    /// Bright colors for dark backgrounds
+    #[default]
+    Dark,
+    /// Darker colors that stay readable on light backgrounds
+    Light,
+    /// No colors or text styles at all
+    Plain,
 }
 
-/// Validate `max_turns` and `max_cost_usd` for a cycle or step.
-fn validate_limits(
-    max_turns: Option<u32>,
-    max_cost_usd: Option<f64>,
-    cycle_name: &str,
-    step_name: Option<&str>,
-) -> Result<()> {
-    let prefix = step_name.map_or_else(
-        || format!("Cycle '{cycle_name}'"),
-        |s| format!("Step '{s}' in cycle '{cycle_name}'"),
-    );
-    if max_turns == Some(0) {
-        bail!("{prefix}: max_turns must be greater than 0");
+/// Glyphs used for markers, rules, and the spinner (`[display].glyphs`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GlyphSet {
+    /// Box-drawing characters, arrows, and a braille spinner
+    #[default]
+    Unicode,
+    /// Plain ASCII, for fonts or terminals without those characters
+    Ascii,
+}
+
+/// Per-kind color overrides on top of the theme (`[display.colors]`)
+///
+/// Values are color names (`red`, `bright blue`, ...) or `#rrggbb`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ThemeColors {
+    /// Cycle headers and `>>>` run messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// Tool-use markers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Subagent activity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subagent: Option<String>,
+    /// Errors and failed results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Completed results and healthy status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<String>,
+    /// Warnings and degraded status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl ThemeColors {
+    /// Each configured override with its key name.
+    #[must_use]
+    pub fn entries(&self) -> [(&'static str, Option<&str>); 6] {
+        [
+            ("header", self.header.as_deref()),
+            ("tool", self.tool.as_deref()),
+            ("subagent", self.subagent.as_deref()),
+            ("error", self.error.as_deref()),
+            ("success", self.success.as_deref()),
+            ("warning", self.warning.as_deref()),
+        ]
     }
-    if let Some(cost) = max_cost_usd {
-        if cost <= 0.0 {
-            bail!("{prefix}: max_cost_usd must be greater than 0");
+}
+
+/// Terminal output styling (`[display]`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DisplayConfig {
+    /// Color palette: `dark` (default), `light`, or `plain`
+    #[serde(default)]
+    pub theme: ThemeName,
+    /// Per-kind color overrides
+    #[serde(default)]
+    pub colors: ThemeColors,
+    /// Prefix event lines with the local time (default: false)
+    #[serde(default)]
+    pub timestamps: bool,
+    /// `unicode` (default) or `ascii` glyphs
+    #[serde(default)]
+    pub glyphs: GlyphSet,
+}
+
+impl DisplayConfig {
+    /// Reject color overrides that don't name a color.
+    fn validate(&self) -> Result<()> {
+        for (kind, color) in self.colors.entries() {
+            if let Some(color) = color {
+                if color.parse::<colored::Color>().is_err() {
+                    bail!("display.colors.{kind}: unknown color '{color}'");
+                }
+            }
         }
+        Ok(())
     }
-    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Top-level Flow configuration parsed from cycles.toml
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlowConfig {
+    /// Global configuration
+    pub global: GlobalConfig,
+    /// Optional selector configuration
+    #[serde(default)]
+    pub selector: Option<SelectorConfig>,
+    /// Optional doctor configuration
+    #[serde(default)]
+    pub doctor: Option<DoctorConfig>,
+    /// Optional retention settings for `flow gc`
+    #[serde(default)]
+    pub gc: Option<GcConfig>,
+    /// Optional history compaction settings for `context = "compact"`
+    #[serde(default)]
+    pub compaction: Option<CompactionConfig>,
+    /// Optional test-output parser configuration
+    #[serde(default)]
+    pub test_parser: Option<TestParserConfig>,
+    /// Optional test-suite regression guard run around cycles
+    #[serde(default)]
+    pub test_guard: Option<TestGuardConfig>,
+    /// Optional linter run after cycles, with new warnings fed back
+    #[serde(default)]
+    pub lint: Option<LintConfig>,
+    /// Optional secret redaction for output, debug logs, and the JSONL log
+    #[serde(default)]
+    pub redaction: Option<RedactionConfig>,
+    /// Optional container settings for cycles with `sandbox = "docker"`
+    #[serde(default)]
+    pub sandbox: Option<SandboxConfig>,
+    /// Optional desktop notifications for run events
+    #[serde(default)]
+    pub notifications: Option<NotificationsConfig>,
+    /// Optional shell-command hooks for lifecycle events
+    #[serde(default)]
+    pub hooks: Option<HooksConfig>,
+    /// Optional terminal output styling
+    #[serde(default)]
+    pub display: Option<DisplayConfig>,
+    /// Reusable prompt text, included in prompts as `{{snippet:<name>}}`
+    #[serde(default, rename = "snippet", skip_serializing_if = "HashMap::is_empty")]
+    pub snippets: HashMap<String, SnippetConfig>,
+    /// Token prices by model name, for computing and cross-checking cycle costs
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pricing: HashMap<String, ModelPrice>,
+    /// Cycle definitions
+    #[serde(rename = "cycle")]
+    pub cycles: Vec<CycleConfig>,
+}
+
+impl FlowConfig {
+    /// Container image for a sandboxed cycle: its `sandbox_image`, else `[sandbox].image`.
+    #[must_use]
+    pub fn sandbox_image<'a>(&'a self, cycle: &'a CycleConfig) -> Option<&'a str> {
+        cycle
+            .sandbox_image
+            .as_deref()
+            .or_else(|| self.sandbox.as_ref().and_then(|s| s.image.as_deref()))
+    }
+
+    /// Parse a cycles.toml file from a path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Self::parse_in(&content, config_dir(path))
+    }
+
+    /// Parse cycles.toml content from a string (`prompt_file` paths are
+    /// relative to the current directory)
+    pub fn parse(content: &str) -> Result<Self> {
+        Self::parse_in(content, Path::new(""))
+    }
+
+    /// Parse cycles.toml content whose `prompt_file` paths are relative to `base_dir`
+    fn parse_in(content: &str, base_dir: &Path) -> Result<Self> {
+        let mut table: toml::Table = content.parse().context("Failed to parse cycles.toml")?;
+        resolve_cycle_inheritance(&mut table)?;
+        expand_step_templates(&mut table)?;
+        let mut config: Self = toml::Value::Table(table)
+            .try_into()
+            .context("Failed to parse cycles.toml")?;
+        config.resolve_prompts(base_dir)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a config file, apply the selected `[profile.<name>]` and then
+    /// `--set key.path=value` overrides before validation
+    pub fn from_path_with_overrides<P: AsRef<Path>>(
+        path: P,
+        profile: Option<&str>,
+        overrides: &[String],
+    ) -> Result<Self> {
+        Self::from_path_with_defaults(path, &toml::Table::new(), profile, overrides)
+    }
+
+    /// Like [`Self::from_path_with_overrides`], with `defaults` (the user
+    /// config's tables) merged under the file first
+    pub fn from_path_with_defaults<P: AsRef<Path>>(
+        path: P,
+        defaults: &toml::Table,
+        profile: Option<&str>,
+        overrides: &[String],
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Self::parse_with_overrides_in(&content, defaults, profile, overrides, config_dir(path))
+    }
+
+    /// Parse cycles.toml content, merge the selected profile, apply overrides in
+    /// order, then validate the merged result
+    pub fn parse_with_overrides(
+        content: &str,
+        profile: Option<&str>,
+        overrides: &[String],
+    ) -> Result<Self> {
+        Self::parse_with_overrides_in(
+            content,
+            &toml::Table::new(),
+            profile,
+            overrides,
+            Path::new(""),
+        )
+    }
+
+    fn parse_with_overrides_in(
+        content: &str,
+        defaults: &toml::Table,
+        profile: Option<&str>,
+        overrides: &[String],
+        base_dir: &Path,
+    ) -> Result<Self> {
+        let mut table: toml::Table = content.parse().context("Failed to parse cycles.toml")?;
+        merge_defaults(&mut table, defaults);
+        resolve_cycle_inheritance(&mut table)?;
+        expand_step_templates(&mut table)?;
+        apply_profile(&mut table, profile)?;
+        for assignment in overrides {
+            apply_override(&mut table, assignment)?;
+        }
+        let mut config: Self = toml::Value::Table(table)
+            .try_into()
+            .context("Invalid config after applying profile and --set overrides")?;
+        config.resolve_prompts(base_dir)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load every `prompt_file` and snippet `file` (resolved against
+    /// `base_dir`), then expand `{{snippet:<name>}}` includes in all prompts.
+    fn resolve_prompts(&mut self, base_dir: &Path) -> Result<()> {
+        let snippets = self.snippet_texts(base_dir)?;
+        for cycle in &mut self.cycles {
+            let label = format!("Cycle '{}'", cycle.name);
+            if let Some(ref file) = cycle.prompt_file {
+                cycle.prompt =
+                    read_prompt_file(base_dir, file, &cycle.prompt, &label, PROMPT_FIELDS)?;
+            }
+            cycle.prompt = include_snippets(&cycle.prompt, &snippets, &label)?;
+            for step in &mut cycle.steps {
+                let label = format!("Step '{}' in cycle '{}'", step.name, cycle.name);
+                if let Some(ref file) = step.prompt_file {
+                    step.prompt =
+                        read_prompt_file(base_dir, file, &step.prompt, &label, PROMPT_FIELDS)?;
+                }
+                step.prompt = include_snippets(&step.prompt, &snippets, &label)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Text of each `[snippet.<name>]`, loading `file` snippets.
+    fn snippet_texts(&self, base_dir: &Path) -> Result<HashMap<String, String>> {
+        let mut texts = HashMap::new();
+        for (name, snippet) in &self.snippets {
+            let label = format!("Snippet '{name}'");
+            let text = match snippet.file {
+                Some(ref file) => {
+                    read_prompt_file(base_dir, file, &snippet.text, &label, ("text", "file"))?
+                }
+                None => snippet.text.clone(),
+            };
+            if let Some(nested) = snippet_names(&text).first() {
+                bail!(
+                    "{label} includes snippet '{nested}'; snippets cannot include other snippets"
+                );
+            }
+            texts.insert(name.clone(), text);
+        }
+        Ok(texts)
+    }
+
+    /// Find a cycle by name
+    #[must_use]
+    pub fn get_cycle(&self, name: &str) -> Option<&CycleConfig> {
+        self.cycles.iter().find(|c| c.name == name)
+    }
+
+    /// Validate the optional top-level sections (`[test_parser]`, `[redaction]`,
+    /// `[sandbox]`, `[notifications]`, `[hooks]`).
+    fn validate_sections(&self) -> Result<()> {
+        if let Some(ref parser) = self.test_parser {
+            parser.validate()?;
+        }
+        if let Some(ref redaction) = self.redaction {
+            redaction.validate()?;
+        }
+        if let Some(ref sandbox) = self.sandbox {
+            sandbox.validate()?;
+        }
+        if let Some(ref notifications) = self.notifications {
+            notifications.validate()?;
+        }
+        if let Some(ref hooks) = self.hooks {
+            hooks.validate()?;
+        }
+        if let Some(ref display) = self.display {
+            display.validate()?;
+        }
+        for (model, price) in &self.pricing {
+            price.validate(model)?;
+        }
+        if self.selector.as_ref().is_some_and(|s| s.timeout_secs == 0) {
+            bail!("selector.timeout_secs must be greater than 0");
+        }
+        if self.compaction.as_ref().is_some_and(|c| c.block_size == 0) {
+            bail!("compaction.block_size must be greater than 0");
+        }
+        if let Some(ref guard) = self.test_guard {
+            if guard.command.trim().is_empty() {
+                bail!("test_guard.command cannot be empty");
+            }
+            if let Some(unknown) = guard.cycles.iter().find(|c| self.get_cycle(c).is_none()) {
+                bail!("test_guard.cycles references unknown cycle '{unknown}'");
+            }
+        }
+        if let Some(ref lint) = self.lint {
+            if lint.command.trim().is_empty() {
+                bail!("lint.command cannot be empty");
+            }
+            if let Some(unknown) = lint.cycles.iter().find(|c| self.get_cycle(c).is_none()) {
+                bail!("lint.cycles references unknown cycle '{unknown}'");
+            }
+            if let Some(ref pattern) = lint.pattern {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    bail!("Invalid lint.pattern regex '{pattern}': {e}");
+                }
+            }
+        }
+        for cycle in &self.cycles {
+            if cycle.sandbox.is_some() && self.sandbox_image(cycle).is_none() {
+                bail!(
+                    "Cycle '{}' sets sandbox but no image (set sandbox_image or [sandbox].image)",
+                    cycle.name
+                );
+            }
+            // The sandbox container, and the session stored in it, is gone
+            // when the step ends, so a later step couldn't resume it
+            if let Some(step) = cycle
+                .steps
+                .iter()
+                .find(|step| step.session.is_some())
+                .filter(|_| cycle.sandbox.is_some())
+            {
+                bail!(
+                    "Cycle '{}' sets sandbox, so step '{}' can't use a session tag \
+                     (sessions don't outlive the sandbox container)",
+                    cycle.name,
+                    step.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the configuration
+    fn validate(&self) -> Result<()> {
+        // Check for duplicate cycle names
+        let mut seen = HashSet::new();
+        for cycle in &self.cycles {
+            if !seen.insert(&cycle.name) {
+                bail!("Duplicate cycle name: '{}'", cycle.name);
+            }
+        }
+
+        self.validate_cycle_references()?;
+
+        // Check that cycle names are non-empty
+        for cycle in &self.cycles {
+            if cycle.name.trim().is_empty() {
+                bail!("Cycle name cannot be empty");
+            }
+        }
+
+        self.validate_sections()?;
+        self.global.validate()?;
+
+        // Validate permission strings in each cycle
+        for cycle in &self.cycles {
+            for perm in &cycle.permissions {
+                validate_permission(perm).with_context(|| format!("in cycle '{}'", cycle.name))?;
+            }
+        }
+
+        // Validate max_turns and max_cost_usd on cycles and steps
+        for cycle in &self.cycles {
+            if let Some(ref success_when) = cycle.success_when {
+                success_when.validate(&cycle.name)?;
+            }
+            if cycle.every_n_iterations == Some(0) {
+                bail!(
+                    "Cycle '{}': every_n_iterations must be greater than 0",
+                    cycle.name
+                );
+            }
+            if cycle.context_window == Some(0) {
+                bail!(
+                    "Cycle '{}': context_window must be greater than 0 (use context = \"none\" for no history)",
+                    cycle.name
+                );
+            }
+            cycle.validate_evaluation()?;
+            if cycle.weight == Some(0) {
+                bail!(
+                    "Cycle '{}': weight must be greater than 0 (use selectable = false to exclude it)",
+                    cycle.name
+                );
+            }
+            validate_limits(
+                cycle.max_turns,
+                cycle.max_cost_usd,
+                cycle.max_tokens,
+                &cycle.name,
+                None,
+            )?;
+            for step in &cycle.steps {
+                validate_limits(
+                    step.max_turns,
+                    step.max_cost_usd,
+                    step.max_tokens,
+                    &cycle.name,
+                    Some(&step.name),
+                )?;
+            }
+        }
+
+        // Validate that every cycle has either a prompt (single-step) or steps (multi-step)
+        for cycle in &self.cycles {
+            if cycle.steps.is_empty() && cycle.prompt.is_empty() {
+                bail!(
+                    "Cycle '{}' must have a 'prompt' or 'prompt_file' (single-step) or '[[cycle.step]]' entries (multi-step)",
+                    cycle.name
+                );
+            }
+        }
+
+        // Validate multi-step cycle constraints
+        for cycle in &self.cycles {
+            if !cycle.steps.is_empty() {
+                // Multi-step cycle: prompt must not also be set
+                if !cycle.prompt.is_empty() {
+                    bail!(
+                        "Cycle '{}' cannot have both a top-level 'prompt' and '[[cycle.step]]' entries",
+                        cycle.name
+                    );
+                }
+
+                // Step names must be unique and non-empty
+                let mut step_names = HashSet::new();
+                for step in &cycle.steps {
+                    if step.name.trim().is_empty() {
+                        bail!("Step name cannot be empty in cycle '{}'", cycle.name);
+                    }
+                    if !step_names.insert(step.name.as_str()) {
+                        bail!(
+                            "Duplicate step name '{}' in cycle '{}'",
+                            step.name,
+                            cycle.name
+                        );
+                    }
+                }
+
+                for step in &cycle.steps {
+                    step.validate(&cycle.name)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that cycle names referenced by other cycles (`after`, `on_failure`) exist.
+    fn validate_cycle_references(&self) -> Result<()> {
+        // Check that trigger references exist
+        let names: HashSet<&str> = self.cycles.iter().map(|c| c.name.as_str()).collect();
+        for cycle in &self.cycles {
+            for (field, deps) in [
+                ("after", &cycle.after),
+                ("after_all", &cycle.after_all),
+                ("after_failures_of", &cycle.after_failures_of),
+            ] {
+                for dep in deps {
+                    if !names.contains(dep.as_str()) {
+                        bail!(
+                            "Cycle '{}' references unknown cycle '{}' in '{field}'",
+                            cycle.name,
+                            dep
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(fallback) = self
+            .selector
+            .as_ref()
+            .and_then(|s| s.fallback_cycle.as_ref())
+        {
+            if !names.contains(fallback.as_str()) {
+                bail!("selector.fallback_cycle references unknown cycle '{fallback}'");
+            }
+        }
+
+        // Check that `on_failure` references exist and aren't self-referential
+        for cycle in &self.cycles {
+            if let Some(ref recovery) = cycle.on_failure {
+                if !names.contains(recovery.as_str()) {
+                    bail!(
+                        "Cycle '{}' references unknown cycle '{}' in 'on_failure'",
+                        cycle.name,
+                        recovery
+                    );
+                }
+                if *recovery == cycle.name {
+                    bail!(
+                        "Cycle '{}' cannot be its own 'on_failure' cycle",
+                        cycle.name
+                    );
+                }
+            }
+            if let Some(ref target) = cycle.arbitrates {
+                if !names.contains(target.as_str()) {
+                    bail!(
+                        "Cycle '{}' references unknown cycle '{target}' in 'arbitrates'",
+                        cycle.name
+                    );
+                }
+                if *target == cycle.name {
+                    bail!("Cycle '{}' cannot arbitrate itself", cycle.name);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Validate that a permission string matches `--allowedTools` syntax:
+/// either `ToolName` (bare) or `ToolName(specifier)`.
+///
+/// Tool names must start with an uppercase ASCII letter and contain only
+/// ASCII alphanumeric characters.
+fn validate_permission(perm: &str) -> Result<()> {
+    if perm.is_empty() {
+        bail!("Invalid permission '': permission string cannot be empty");
+    }
+
+    // Find where the tool name ends
+    let tool_end = perm
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(perm.len());
+
+    let tool_name = &perm[..tool_end];
+
+    // Tool name must be non-empty and start with uppercase
+    if tool_name.is_empty() || !tool_name.starts_with(|c: char| c.is_ascii_uppercase()) {
+        bail!("Invalid permission '{perm}': tool name must start with an uppercase letter");
+    }
+
+    let rest = &perm[tool_end..];
+
+    if rest.is_empty() {
+        // Bare tool name like "Read" — valid
+        return Ok(());
+    }
+
+    // Must be ToolName(specifier)
+    if !rest.starts_with('(') || !rest.ends_with(')') {
+        bail!("Invalid permission '{perm}': expected format 'ToolName' or 'ToolName(specifier)'");
+    }
+
+    // Extract specifier (strip outer parens)
+    let specifier = &rest[1..rest.len() - 1];
+    if specifier.is_empty() {
+        bail!("Invalid permission '{perm}': specifier inside parentheses cannot be empty");
+    }
+
+    Ok(())
+}
+
+/// Parse a human-friendly duration such as `90`, `45s`, `30m`, `4h`, `2d`, or `1h30m`.
+///
+/// A bare number is seconds. Returns the total in seconds.
+pub fn parse_duration_secs(input: &str) -> Result<u64> {
+    let input = input.trim();
+    if input.is_empty() {
+        bail!("empty duration");
+    }
+    if let Ok(secs) = input.parse::<u64>() {
+        return Ok(secs);
+    }
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => bail!("invalid duration '{input}': unknown unit '{c}' (use s, m, h, or d)"),
+        };
+        let value: u64 = digits
+            .parse()
+            .with_context(|| format!("invalid duration '{input}': missing number before '{c}'"))?;
+        total = total.saturating_add(value.saturating_mul(unit));
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        bail!("invalid duration '{input}': trailing number without unit");
+    }
+    Ok(total)
+}
+
+/// Validate `max_turns` and `max_cost_usd` for a cycle or step.
+fn validate_limits(
+    max_turns: Option<u32>,
+    max_cost_usd: Option<f64>,
+    max_tokens: Option<u64>,
+    cycle_name: &str,
+    step_name: Option<&str>,
+) -> Result<()> {
+    let prefix = step_name.map_or_else(
+        || format!("Cycle '{cycle_name}'"),
+        |s| format!("Step '{s}' in cycle '{cycle_name}'"),
+    );
+    if max_turns == Some(0) {
+        bail!("{prefix}: max_turns must be greater than 0");
+    }
+    if max_tokens == Some(0) {
+        bail!("{prefix}: max_tokens must be greater than 0");
+    }
+    if let Some(cost) = max_cost_usd {
+        if cost <= 0.0 {
+            bail!("{prefix}: max_cost_usd must be greater than 0");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID_CONFIG: &str = r#"
+[global]
+permissions = ["Read", "Edit(./src/**)", "Bash(cargo *)"]
+
+[[cycle]]
+name = "coding"
+description = "Pick a task and implement with TDD"
+prompt = "You are Flow's coding cycle."
+permissions = ["Edit(./tests/**)", "Bash(cargo test *)"]
+after = []
+context = "summaries"
+
+[[cycle]]
+name = "gardening"
+description = "Deps, refactoring, docs"
+prompt = "You are Flow's gardening cycle."
+permissions = ["Edit(./Cargo.toml)", "Bash(cargo update *)"]
+after = ["coding"]
+context = "none"
+"#;
+
+    #[test]
+    fn test_parse_valid_config() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+
+        assert_eq!(config.global.permissions.len(), 3);
+        assert_eq!(config.global.permissions[0], "Read");
+        assert_eq!(config.cycles.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_cycle_fields() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+
+        assert_eq!(coding.name, "coding");
+        assert_eq!(coding.description, "Pick a task and implement with TDD");
+        assert_eq!(coding.prompt, "You are Flow's coding cycle.");
+        assert_eq!(
+            coding.permissions,
+            vec!["Edit(./tests/**)", "Bash(cargo test *)"]
+        );
+        assert!(coding.after.is_empty());
+        assert_eq!(coding.context, ContextMode::Summaries);
+    }
+
+    #[test]
+    fn test_parse_after_dependencies() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        let gardening = config.get_cycle("gardening").unwrap();
+
+        assert_eq!(gardening.after, vec!["coding"]);
+    }
+
+    #[test]
+    fn test_parse_context_modes() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+
+        assert_eq!(
+            config.get_cycle("coding").unwrap().context,
+            ContextMode::Summaries
+        );
+        assert_eq!(
+            config.get_cycle("gardening").unwrap().context,
+            ContextMode::None
+        );
+    }
+
+    #[test]
+    fn test_context_mode_full() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "review"
+description = "Code review"
+prompt = "Review code"
+context = "full"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert_eq!(
+            config.get_cycle("review").unwrap().context,
+            ContextMode::Full
+        );
+    }
+
+    #[test]
+    fn test_default_context_is_none() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "review"
+description = "Code review"
+prompt = "Review code"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert_eq!(
+            config.get_cycle("review").unwrap().context,
+            ContextMode::None
+        );
+    }
+
+    #[test]
+    fn test_default_empty_permissions() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "review"
+description = "Code review"
+prompt = "Review code"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let review = config.get_cycle("review").unwrap();
+
+        assert!(review.permissions.is_empty());
+        assert!(review.after.is_empty());
+    }
+
+    #[test]
+    fn test_get_cycle_not_found() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        assert!(config.get_cycle("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_reject_duplicate_cycle_names() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "First"
+prompt = "First"
+
+[[cycle]]
+name = "coding"
+description = "Duplicate"
+prompt = "Duplicate"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Duplicate cycle name"),
+            "Expected 'Duplicate cycle name' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_unknown_after_reference() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+after = ["nonexistent"]
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown cycle"),
+            "Expected 'unknown cycle' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_empty_cycle_name() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = ""
+description = "Empty"
+prompt = "Empty"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("empty"),
+            "Expected 'empty' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_missing_required_fields() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        // Either TOML parse error (missing description) or our validation error (no prompt/steps)
+        let msg = err.to_string();
+        assert!(
+            msg.contains("missing field")
+                || msg.contains("Failed to parse")
+                || msg.contains("must have"),
+            "Expected parse error for missing fields, got: {msg}"
+        );
+    }
+
+    #[test]
+    fn test_reject_invalid_toml() {
+        let err = FlowConfig::parse("not valid toml {{{").unwrap_err();
+        assert!(err.to_string().contains("Failed to parse"));
+    }
+
+    #[test]
+    fn test_from_path_missing_file() {
+        let err = FlowConfig::from_path("/nonexistent/cycles.toml").unwrap_err();
+        assert!(err.to_string().contains("Failed to read"));
+    }
+
+    #[test]
+    fn test_from_path_valid_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("cycles.toml");
+        std::fs::write(&config_path, VALID_CONFIG).unwrap();
+
+        let config = FlowConfig::from_path(&config_path).unwrap();
+        assert_eq!(config.cycles.len(), 2);
+    }
+
+    // --- prompt_file tests ---
+
+    #[test]
+    fn test_prompt_file_loaded_relative_to_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("prompts")).unwrap();
+        std::fs::write(
+            dir.path().join("prompts/coding.md"),
+            "# Coding\n\nDo TDD.\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("prompts/plan.md"), "Plan it.").unwrap();
+        let config_path = dir.path().join("cycles.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt_file = "prompts/coding.md"
+
+[[cycle]]
+name = "planned"
+description = "Planned"
+
+[[cycle.step]]
+name = "plan"
+prompt_file = "prompts/plan.md"
+"#,
+        )
+        .unwrap();
+
+        let config = FlowConfig::from_path(&config_path).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.prompt, "# Coding\n\nDo TDD.\n");
+        assert_eq!(
+            coding.prompt_file.as_deref(),
+            Some(Path::new("prompts/coding.md"))
+        );
+        assert_eq!(
+            config.get_cycle("planned").unwrap().steps[0].prompt,
+            "Plan it."
+        );
+    }
+
+    #[test]
+    fn test_prompt_file_missing_is_error() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config_path = dir.path().join("cycles.toml");
+        std::fs::write(
+            &config_path,
+            "[global]\npermissions = []\n\n[[cycle]]\nname = \"coding\"\ndescription = \"C\"\nprompt_file = \"missing.md\"\n",
+        )
+        .unwrap();
+        let err = FlowConfig::from_path(&config_path).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Cycle 'coding': cannot read prompt_file"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_prompt_and_prompt_file_together_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("p.md"), "x").unwrap();
+        let config_path = dir.path().join("cycles.toml");
+        std::fs::write(
+            &config_path,
+            "[global]\npermissions = []\n\n[[cycle]]\nname = \"coding\"\ndescription = \"C\"\nprompt = \"inline\"\nprompt_file = \"p.md\"\n",
+        )
+        .unwrap();
+        let err = FlowConfig::from_path(&config_path).unwrap_err();
+        assert!(
+            err.to_string().contains("both 'prompt' and 'prompt_file'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_cycle_extends_inherits_from_base() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "base-coding"
+description = "Coding"
+selectable = false
+permissions = ["Edit(./src/**)"]
+max_cost_usd = 2.0
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement the next task."
+
+[[cycle]]
+name = "parser-coding"
+extends = "base-coding"
+permissions = ["Edit(./parser/**)"]
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let parser = config.get_cycle("parser-coding").unwrap();
+        assert_eq!(parser.extends.as_deref(), Some("base-coding"));
+        assert_eq!(parser.description, "Coding");
+        assert_eq!(parser.permissions, vec!["Edit(./parser/**)"]);
+        assert_eq!(parser.max_cost_usd, Some(2.0));
+        assert_eq!(parser.steps.len(), 1);
+        assert!(parser.selectable);
+        assert!(!config.get_cycle("base-coding").unwrap().selectable);
+    }
+
+    #[test]
+    fn test_step_templates_instantiated_and_validated() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[step_template]]
+name = "reflect"
+prompt = "Reflect on {{cycle_name}}."
+max_visits = 1
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement."
+
+[[cycle.step]]
+template = "reflect"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let reflect = &config.get_cycle("coding").unwrap().steps[1];
+        assert_eq!(reflect.name, "reflect");
+        assert_eq!(reflect.max_visits, 1);
+
+        let broken = toml.replace("max_visits = 1", "router = \"script\"");
+        let err = FlowConfig::parse(&broken).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Step 'reflect' in cycle 'coding' uses router = \"script\""),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_step_without_prompt_rejected() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+
+[[cycle.step]]
+name = "plan"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Step 'plan' in cycle 'coding' must have a 'prompt' or 'prompt_file'"),
+            "{err}"
+        );
+    }
+
+    // --- snippet tests ---
+
+    #[test]
+    fn test_snippets_expanded_into_cycle_and_step_prompts() {
+        let toml = r#"
+[global]
+permissions = []
+
+[snippet.tdd-rules]
+text = "Write the failing test first."
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Implement. {{snippet:tdd-rules}} Cycle {{cycle_name}}."
+
+[[cycle]]
+name = "planned"
+description = "Planned"
+
+[[cycle.step]]
+name = "implement"
+prompt = "{{snippet:tdd-rules}}"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert_eq!(
+            config.get_cycle("coding").unwrap().prompt,
+            "Implement. Write the failing test first. Cycle {{cycle_name}}."
+        );
+        assert_eq!(
+            config.get_cycle("planned").unwrap().steps[0].prompt,
+            "Write the failing test first."
+        );
+    }
+
+    #[test]
+    fn test_snippet_file_loaded_relative_to_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("rules.md"), "Ground rules.").unwrap();
+        let config_path = dir.path().join("cycles.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+[global]
+permissions = []
+
+[snippet.rules]
+file = "rules.md"
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "{{snippet:rules}}"
+"#,
+        )
+        .unwrap();
+        let config = FlowConfig::from_path(&config_path).unwrap();
+        assert_eq!(config.get_cycle("coding").unwrap().prompt, "Ground rules.");
+    }
+
+    #[test]
+    fn test_unknown_snippet_rejected() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "{{snippet:tdd-rule}}"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Cycle 'coding' includes unknown snippet 'tdd-rule'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_nested_snippet_rejected() {
+        let toml = r#"
+[global]
+permissions = []
+
+[snippet.a]
+text = "{{snippet:b}}"
+
+[snippet.b]
+text = "b"
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "{{snippet:a}}"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("cannot include other snippets"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_multiline_prompt() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding cycle"
+prompt = """
+Line one.
+Line two.
+Line three.
+"""
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert!(coding.prompt.contains("Line one."));
+        assert!(coding.prompt.contains("Line three."));
+    }
+
+    #[test]
+    fn test_global_safeguard_defaults() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert_eq!(config.global.max_permission_denials, 10);
+        assert_eq!(config.global.circuit_breaker_repeated, 5);
+        assert_eq!(config.global.transient_retries, 3);
+        assert_eq!(config.global.transient_backoff_secs, 30);
+    }
+
+    #[test]
+    fn test_global_safeguard_custom_values() {
+        let toml = r#"
+[global]
+permissions = []
+max_permission_denials = 20
+circuit_breaker_repeated = 3
+transient_retries = 0
+transient_backoff_secs = 5
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert_eq!(config.global.max_permission_denials, 20);
+        assert_eq!(config.global.circuit_breaker_repeated, 3);
+        assert_eq!(config.global.transient_retries, 0);
+        assert_eq!(config.global.transient_backoff_secs, 5);
+    }
+
+    #[test]
+    fn test_global_permissions_preserved() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        assert_eq!(
+            config.global.permissions,
+            vec!["Read", "Edit(./src/**)", "Bash(cargo *)"]
+        );
+    }
+
+    // --- min_interval config field tests ---
+
+    #[test]
+    fn test_min_interval_default_is_none() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "Garden"
+after = ["coding"]
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let gardening = config.get_cycle("gardening").unwrap();
+        assert_eq!(gardening.min_interval, None);
+    }
+
+    #[test]
+    fn test_min_interval_parsed_from_config() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "Garden"
+after = ["coding"]
+min_interval = 3
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let gardening = config.get_cycle("gardening").unwrap();
+        assert_eq!(gardening.min_interval, Some(MinInterval::Iterations(3)));
+    }
+
+    #[test]
+    fn test_min_interval_zero_is_valid() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+min_interval = 0
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.min_interval, Some(MinInterval::Iterations(0)));
+    }
+
+    // --- Permission string validation tests ---
+
+    #[test]
+    fn test_valid_bare_tool_name() {
+        let toml = r#"
+[global]
+permissions = ["Read"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        assert!(FlowConfig::parse(toml).is_ok());
+    }
+
+    #[test]
+    fn test_valid_tool_with_specifier() {
+        let toml = r#"
+[global]
+permissions = ["Edit(./src/**)", "Bash(cargo test *)"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        assert!(FlowConfig::parse(toml).is_ok());
+    }
+
+    #[test]
+    fn test_reject_lowercase_tool_name() {
+        let toml = r#"
+[global]
+permissions = ["read"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid permission"),
+            "Expected 'Invalid permission' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_empty_permission_string() {
+        let toml = r#"
+[global]
+permissions = [""]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid permission"),
+            "Expected 'Invalid permission' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_permission_with_spaces_in_tool_name() {
+        let toml = r#"
+[global]
+permissions = ["Read Write"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid permission"),
+            "Expected 'Invalid permission' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_unbalanced_parens() {
+        let toml = r#"
+[global]
+permissions = ["Edit(./src/**"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid permission"),
+            "Expected 'Invalid permission' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_empty_specifier() {
+        let toml = r#"
+[global]
+permissions = ["Edit()"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid permission"),
+            "Expected 'Invalid permission' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_permission_with_trailing_text() {
+        let toml = r#"
+[global]
+permissions = ["Edit(./src/**)extra"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid permission"),
+            "Expected 'Invalid permission' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_invalid_cycle_permission() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+permissions = ["not-valid!"]
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        let msg = format!("{err:?}");
+        assert!(
+            msg.contains("Invalid permission"),
+            "Expected 'Invalid permission' error, got: {msg}"
+        );
+        assert!(
+            msg.contains("in cycle 'test'"),
+            "Expected cycle context, got: {msg}"
+        );
+    }
+
+    #[test]
+    fn test_valid_known_tool_names() {
+        let toml = r#"
+[global]
+permissions = ["Read", "Glob", "Grep", "Edit(./src/**)", "Write(./out.txt)", "Bash(cargo *)", "WebFetch", "WebSearch", "NotebookEdit(./nb.ipynb)", "Task", "TodoWrite"]
+
+[[cycle]]
+name = "test"
+description = "Test"
+prompt = "Test"
+"#;
+        assert!(FlowConfig::parse(toml).is_ok());
+    }
+
+    // --- Multi-step cycle config tests ---
+
+    #[test]
+    fn test_parse_multi_step_cycle() {
+        let toml = r#"
+[global]
+permissions = ["Read"]
+
+[[cycle]]
+name = "coding"
+description = "Multi-step coding cycle"
+after = []
+
+[[cycle.step]]
+name = "plan"
+session = "architect"
+prompt = "Read TODO.md and write a plan."
+permissions = ["Edit(./.flow/current-plan.md)"]
+
+[[cycle.step]]
+name = "implement"
+session = "coder"
+prompt = "Read the plan and implement it."
+permissions = ["Edit(./src/**)", "Bash(cargo *)"]
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.steps.len(), 2);
+        assert_eq!(coding.steps[0].name, "plan");
+        assert_eq!(coding.steps[0].session, Some("architect".to_string()));
+        assert_eq!(coding.steps[0].prompt, "Read TODO.md and write a plan.");
+        assert_eq!(
+            coding.steps[0].permissions,
+            vec!["Edit(./.flow/current-plan.md)"]
+        );
+        assert_eq!(coding.steps[1].name, "implement");
+    }
+
+    #[test]
+    fn test_single_step_cycle_has_empty_steps() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "You are gardening."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let gardening = config.get_cycle("gardening").unwrap();
+        assert!(gardening.steps.is_empty());
+        assert_eq!(gardening.prompt, "You are gardening.");
+    }
+
+    #[test]
+    fn test_step_without_session_tag_is_valid() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement the task."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.steps[0].session, None);
+        assert!(!coding.steps[0].persist);
+    }
+
+    #[test]
+    fn test_step_persist_parses() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+
+[[cycle.step]]
+name = "plan"
+session = "architect"
+persist = true
+prompt = "Plan."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert!(config.get_cycle("coding").unwrap().steps[0].persist);
+    }
+
+    #[test]
+    fn test_cycle_model_overrides_global() {
+        let toml = r#"
+[global]
+model = "sonnet"
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code."
+model = "opus"
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let model = |name| config.get_cycle(name).unwrap().model(&config.global);
+        assert_eq!(model("coding"), Some("opus"));
+        assert_eq!(model("review"), Some("sonnet"));
+    }
+
+    #[test]
+    fn test_system_prompts_cycle_overrides_and_appends() {
+        let toml = r#"
+[global]
+permissions = []
+system_prompt = "Global persona."
+append_system_prompt = "Never push to main."
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code."
+system_prompt = "Coding persona."
+append_system_prompt = "Run cargo test before committing."
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let (system, append) = config
+            .get_cycle("coding")
+            .unwrap()
+            .system_prompts(&config.global);
+        assert_eq!(system.as_deref(), Some("Coding persona."));
+        assert_eq!(
+            append.as_deref(),
+            Some("Never push to main.\n\nRun cargo test before committing.")
+        );
+
+        let (system, append) = config
+            .get_cycle("review")
+            .unwrap()
+            .system_prompts(&config.global);
+        assert_eq!(system.as_deref(), Some("Global persona."));
+        assert_eq!(append.as_deref(), Some("Never push to main."));
+    }
+
+    #[test]
+    fn test_permission_mode_parses_and_step_overrides() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+permission_mode = "acceptEdits"
+
+[[cycle.step]]
+name = "plan"
+permission_mode = "plan"
+prompt = "Plan."
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.permission_mode, Some(PermissionMode::AcceptEdits));
+        assert_eq!(coding.steps[0].permission_mode, Some(PermissionMode::Plan));
+        assert_eq!(coding.steps[1].permission_mode, None);
+        assert_eq!(PermissionMode::AcceptEdits.as_arg(), "acceptEdits");
+    }
+
+    #[test]
+    fn test_reject_unknown_permission_mode() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code."
+permission_mode = "yolo"
+"#;
+        assert!(FlowConfig::parse(toml).is_err());
+    }
+
+    #[test]
+    fn test_docker_sandbox_parses_with_defaults() {
+        let toml = r#"
+[global]
+permissions = []
+
+[sandbox]
+image = "flow-sandbox:latest"
+memory = "4g"
+cpus = 2
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code."
+sandbox = "docker"
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review."
+sandbox = "docker"
+sandbox_image = "reviewer:1"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let sandbox = config.sandbox.as_ref().unwrap();
+        assert_eq!(sandbox.memory.as_deref(), Some("4g"));
+        assert_eq!(sandbox.env, vec!["ANTHROPIC_API_KEY"]);
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.sandbox, Some(SandboxKind::Docker));
+        assert_eq!(config.sandbox_image(coding), Some("flow-sandbox:latest"));
+        let review = config.get_cycle("review").unwrap();
+        assert_eq!(config.sandbox_image(review), Some("reviewer:1"));
+    }
+
+    #[test]
+    fn test_reject_sandbox_with_session_tag() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+sandbox = "docker"
+sandbox_image = "flow-sandbox:latest"
+
+[[cycle.step]]
+name = "plan"
+session = "architect"
+prompt = "Plan."
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement."
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("step 'plan'"), "{err}");
+        let toml = toml.replace("session = \"architect\"\n", "");
+        assert!(FlowConfig::parse(&toml).is_ok());
+    }
+
+    #[test]
+    fn test_reject_sandbox_without_image() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code."
+sandbox = "docker"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("no image"), "{err}");
+    }
+
+    #[test]
+    fn test_reject_non_positive_sandbox_cpus() {
+        let toml = r#"
+[global]
+permissions = []
+
+[sandbox]
+image = "x"
+cpus = 0
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code."
+"#;
+        assert!(FlowConfig::parse(toml).is_err());
+    }
+
+    #[test]
+    fn test_step_context_overrides_cycle_context() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+context = "full"
+
+[[cycle.step]]
+name = "plan"
+context = "summaries"
+prompt = "Plan."
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.steps[0].context, Some(ContextMode::Summaries));
+        assert_eq!(coding.steps[1].context, None);
+        assert_eq!(
+            coding.step_context(&coding.steps[0]),
+            &ContextMode::Summaries
+        );
+        assert_eq!(coding.step_context(&coding.steps[1]), &ContextMode::Full);
+    }
+
+    #[test]
+    fn test_context_window_parsed_and_validated() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Go."
+context = "summaries"
+context_window = 10
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.context_window, Some(10));
+        assert_eq!(coding.history_window(), Some(10));
+
+        let compact = toml.replace("\"summaries\"", "\"compact\"");
+        let config = FlowConfig::parse(&compact).unwrap();
+        assert_eq!(
+            config.cycles[0].history_window(),
+            None,
+            "compact reads the whole log"
+        );
+
+        let zero = toml.replace("context_window = 10", "context_window = 0");
+        let err = FlowConfig::parse(&zero).unwrap_err();
+        assert!(err.to_string().contains("context_window"), "{err}");
+    }
+
+    #[test]
+    fn test_uses_history_considers_effective_step_context() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "single"
+description = "Single"
+prompt = "Go."
+
+[[cycle]]
+name = "stepped"
+description = "Stepped"
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+
+[[cycle.step]]
+name = "review"
+context = "summaries"
+prompt = "Review."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert!(!config.get_cycle("single").unwrap().uses_history());
+        assert!(config.get_cycle("stepped").unwrap().uses_history());
+    }
+
+    #[test]
+    fn test_reject_persist_without_session() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+
+[[cycle.step]]
+name = "plan"
+persist = true
+prompt = "Plan."
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("no session tag"), "{err}");
+    }
+
+    #[test]
+    fn test_step_fresh_session_parses_and_requires_session() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+
+[[cycle.step]]
+name = "plan"
+session = "architect"
+fresh_session = true
+prompt = "Plan."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        assert!(config.get_cycle("coding").unwrap().steps[0].fresh_session);
+
+        let err = FlowConfig::parse(&toml.replace("session = \"architect\"\n", "")).unwrap_err();
+        assert!(err.to_string().contains("fresh_session"), "{err}");
+    }
+
+    #[test]
+    fn test_reject_multi_step_cycle_with_top_level_prompt() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "This should not be here alongside steps."
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("cannot have both"),
+            "Expected 'cannot have both' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_cycle_without_prompt_and_without_steps() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        let msg = err.to_string();
+        assert!(
+            msg.contains("must have") || msg.contains("missing field") || msg.contains("prompt"),
+            "Expected error about missing prompt or steps, got: {msg}"
+        );
+    }
+
+    #[test]
+    fn test_reject_duplicate_step_names_within_cycle() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+
+[[cycle.step]]
+name = "plan"
+prompt = "Also plan."
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Duplicate step name"),
+            "Expected 'Duplicate step name' error, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_reject_empty_step_name() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = ""
+prompt = "Plan."
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("empty"),
+            "Expected 'empty' error for step name, got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_multi_step_cycle_has_no_top_level_prompt() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert!(coding.prompt.is_empty());
+    }
+
+    /// Verify the actual cycles.toml in the project root parses and that the
+    /// coding cycle is a multi-step cycle with plan / plan-review / implement steps.
+    #[test]
+    fn test_actual_cycles_toml_coding_is_multi_step() {
+        let config = FlowConfig::from_path("cycles.toml").expect("cycles.toml must be parseable");
+        let coding = config.get_cycle("coding").expect("coding cycle must exist");
+
+        assert!(
+            coding.is_multi_step(),
+            "coding cycle should be multi-step (using [[cycle.step]] entries)"
+        );
+        assert!(
+            coding.prompt.is_empty(),
+            "multi-step cycle must not have a top-level prompt"
+        );
+
+        let step_names: Vec<&str> = coding.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            step_names,
+            vec!["plan", "plan-review", "implement", "reflect"],
+            "coding cycle should have plan, plan-review, implement, reflect steps"
+        );
+
+        // plan step: architect session, read-only + plan file write
+        let plan = &coding.steps[0];
+        assert_eq!(plan.session.as_deref(), Some("architect"));
+        assert!(
+            plan.permissions
+                .iter()
+                .any(|p| p.starts_with("Edit(./.flow/")),
+            "plan step should have edit permission for .flow/ artifacts"
+        );
+
+        // plan-review step: architect continues (same session), reads plan, can exit 1
+        let review = &coding.steps[1];
+        assert_eq!(review.name, "plan-review");
+        assert_eq!(
+            review.session.as_deref(),
+            Some("architect"),
+            "plan-review should continue the architect session"
+        );
+
+        // implement step: coder session with full write permissions
+        let implement = &coding.steps[2];
+        assert_eq!(implement.session.as_deref(), Some("coder"));
+        assert!(
+            implement.permissions.iter().any(|p| p == "Bash(git *)"),
+            "implement step should have git permissions for committing"
+        );
+    }
+
+    // --- StepConfig router and max_visits tests ---
+
+    #[test]
+    fn test_step_router_default_is_sequential() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        assert_eq!(step.router, StepRouter::Sequential);
+    }
 
-    const VALID_CONFIG: &str = r#"
+    #[test]
+    fn test_step_router_llm_parsed() {
+        let toml = r#"
 [global]
-permissions = ["Read", "Edit(./src/**)", "Bash(cargo *)"]
+permissions = []
 
 [[cycle]]
 name = "coding"
-description = "Pick a task and implement with TDD"
-prompt = "You are Flow's coding cycle."
-permissions = ["Edit(./tests/**)", "Bash(cargo test *)"]
+description = "Coding"
 after = []
-context = "summaries"
 
-[[cycle]]
-name = "gardening"
-description = "Deps, refactoring, docs"
-prompt = "You are Flow's gardening cycle."
-permissions = ["Edit(./Cargo.toml)", "Bash(cargo update *)"]
-after = ["coding"]
-context = "none"
+[[cycle.step]]
+name = "plan-review"
+prompt = "Review the plan."
+router = "llm"
 "#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        assert_eq!(step.router, StepRouter::Llm);
+    }
 
     #[test]
-    fn test_parse_valid_config() {
-        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+    fn test_step_router_sequential_explicit() {
+        let toml = r#"
+[global]
+permissions = []
 
-        assert_eq!(config.global.permissions.len(), 3);
-        assert_eq!(config.global.permissions[0], "Read");
-        assert_eq!(config.cycles.len(), 2);
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+router = "sequential"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        assert_eq!(step.router, StepRouter::Sequential);
     }
 
     #[test]
-    fn test_parse_cycle_fields() {
-        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
-        let coding = config.get_cycle("coding").unwrap();
+    fn test_step_router_script_parsed() {
+        let toml = r#"
+[global]
+permissions = []
 
-        assert_eq!(coding.name, "coding");
-        assert_eq!(coding.description, "Pick a task and implement with TDD");
-        assert_eq!(coding.prompt, "You are Flow's coding cycle.");
-        assert_eq!(
-            coding.permissions,
-            vec!["Edit(./tests/**)", "Bash(cargo test *)"]
-        );
-        assert!(coding.after.is_empty());
-        assert_eq!(coding.context, ContextMode::Summaries);
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "review"
+prompt = "Review."
+router = "script"
+router_script = 'if result_text.contains("LGTM") { "DONE" } else { "review" }'
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        assert_eq!(step.router, StepRouter::Script);
+        assert!(step.router_script.as_deref().unwrap().contains("LGTM"));
     }
 
     #[test]
-    fn test_parse_after_dependencies() {
-        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
-        let gardening = config.get_cycle("gardening").unwrap();
+    fn test_step_router_script_requires_script() {
+        let toml = r#"
+[global]
+permissions = []
 
-        assert_eq!(gardening.after, vec!["coding"]);
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "review"
+prompt = "Review."
+router = "script"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("no router_script"), "{err}");
     }
 
     #[test]
-    fn test_parse_context_modes() {
-        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+    fn test_step_router_script_rejects_syntax_error() {
+        let toml = r#"
+[global]
+permissions = []
 
-        assert_eq!(
-            config.get_cycle("coding").unwrap().context,
-            ContextMode::Summaries
-        );
-        assert_eq!(
-            config.get_cycle("gardening").unwrap().context,
-            ContextMode::None
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "review"
+prompt = "Review."
+router = "script"
+router_script = "if {"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("Invalid router_script"),
+            "{err:#}"
         );
     }
 
     #[test]
-    fn test_context_mode_full() {
+    fn test_router_script_without_script_router_rejected() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
 name = "review"
-description = "Code review"
-prompt = "Review code"
-context = "full"
+prompt = "Review."
+router_script = "\"DONE\""
 "#;
-        let config = FlowConfig::parse(toml).unwrap();
-        assert_eq!(
-            config.get_cycle("review").unwrap().context,
-            ContextMode::Full
-        );
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("router is not"), "{err}");
     }
 
     #[test]
-    fn test_default_context_is_none() {
+    fn test_step_repeat_until_parsed() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
-name = "review"
-description = "Code review"
-prompt = "Review code"
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "fix"
+prompt = "Fix the failing tests."
+max_visits = 5
+
+[cycle.step.repeat_until]
+command = "cargo test"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        assert_eq!(
-            config.get_cycle("review").unwrap().context,
-            ContextMode::None
-        );
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        let repeat_until = step.repeat_until.as_ref().unwrap();
+        assert_eq!(repeat_until.command.as_deref(), Some("cargo test"));
+        assert_eq!(repeat_until.result_matches, None);
     }
 
     #[test]
-    fn test_default_empty_permissions() {
+    fn test_step_repeat_until_rejects_empty_and_bad_regex() {
+        let toml = |repeat_until: &str| {
+            format!(
+                r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "fix"
+prompt = "Fix."
+
+[cycle.step.repeat_until]
+{repeat_until}
+"#
+            )
+        };
+        let err = FlowConfig::parse(&toml("")).unwrap_err();
+        assert!(err.to_string().contains("must set"), "{err}");
+        let err = FlowConfig::parse(&toml("result_matches = \"(\"")).unwrap_err();
+        assert!(err.to_string().contains("invalid repeat_until"), "{err}");
+    }
+
+    #[test]
+    fn test_step_max_visits_default_is_3() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
-name = "review"
-description = "Code review"
-prompt = "Review code"
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let review = config.get_cycle("review").unwrap();
-
-        assert!(review.permissions.is_empty());
-        assert!(review.after.is_empty());
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        assert_eq!(step.max_visits, 3);
     }
 
     #[test]
-    fn test_get_cycle_not_found() {
-        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
-        assert!(config.get_cycle("nonexistent").is_none());
+    fn test_step_max_visits_custom_value() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+max_visits = 5
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        assert_eq!(step.max_visits, 5);
     }
 
     #[test]
-    fn test_reject_duplicate_cycle_names() {
+    fn test_reject_invalid_router_value() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
 name = "coding"
-description = "First"
-prompt = "First"
+description = "Coding"
+after = []
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+router = "invalid"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("Failed to parse"),
+            "Expected parse error for invalid router, got: {err}"
+        );
+    }
+
+    // --- SelectorConfig tests ---
+
+    #[test]
+    fn test_selector_config_parsed() {
+        let toml = r#"
+[global]
+permissions = []
+
+[selector]
+prompt = "Read TODO.md for priorities. Focus on P0 tasks first."
 
 [[cycle]]
 name = "coding"
-description = "Duplicate"
-prompt = "Duplicate"
+description = "Coding"
+prompt = "Code"
 "#;
-        let err = FlowConfig::parse(toml).unwrap_err();
+        let config = FlowConfig::parse(toml).unwrap();
+        let selector = config.selector.as_ref().expect("selector should be Some");
+        assert_eq!(
+            selector.prompt,
+            "Read TODO.md for priorities. Focus on P0 tasks first."
+        );
+    }
+
+    #[test]
+    fn test_selector_config_absent_is_none() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
         assert!(
-            err.to_string().contains("Duplicate cycle name"),
-            "Expected 'Duplicate cycle name' error, got: {err}"
+            config.selector.is_none(),
+            "config without [selector] should have selector = None"
         );
     }
 
+    /// Verify the actual cycles.toml parses correctly with the optional [selector] field.
+    /// When [selector] is added to cycles.toml, this test validates it has a non-empty prompt.
+    #[test]
+    fn test_actual_cycles_toml_parses_with_optional_selector() {
+        let config = FlowConfig::from_path("cycles.toml").expect("cycles.toml must be parseable");
+        // selector is optional — just verify the config parses without error
+        if let Some(selector) = &config.selector {
+            assert!(
+                !selector.prompt.is_empty(),
+                "if [selector] is present, its prompt should be non-empty"
+            );
+        }
+    }
+
     #[test]
-    fn test_reject_unknown_after_reference() {
+    fn test_selector_config_empty_prompt() {
         let toml = r#"
 [global]
 permissions = []
 
+[selector]
+prompt = ""
+
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
-after = ["nonexistent"]
 "#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("unknown cycle"),
-            "Expected 'unknown cycle' error, got: {err}"
-        );
+        let config = FlowConfig::parse(toml).unwrap();
+        let selector = config.selector.as_ref().expect("selector should be Some");
+        assert!(selector.prompt.is_empty());
     }
 
+    // --- DoctorConfig tests ---
+
     #[test]
-    fn test_reject_empty_cycle_name() {
+    fn test_doctor_config_parsed() {
         let toml = r#"
 [global]
 permissions = []
 
+[doctor]
+suppress = ["D004", "D006"]
+
 [[cycle]]
-name = ""
-description = "Empty"
-prompt = "Empty"
+name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("empty"),
-            "Expected 'empty' error, got: {err}"
-        );
+        let config = FlowConfig::parse(toml).unwrap();
+        let doctor = config.doctor.as_ref().expect("doctor should be Some");
+        assert_eq!(doctor.suppress, vec!["D004", "D006"]);
     }
 
     #[test]
-    fn test_reject_missing_required_fields() {
+    fn test_doctor_config_absent_is_none() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        assert!(config.doctor.is_none());
+    }
+
+    // --- GcConfig tests ---
+
+    #[test]
+    fn test_gc_config_defaults_unset_fields() {
         let toml = r#"
 [global]
 permissions = []
 
+[gc]
+debug_days = 3
+
 [[cycle]]
 name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        // Either TOML parse error (missing description) or our validation error (no prompt/steps)
-        let msg = err.to_string();
-        assert!(
-            msg.contains("missing field")
-                || msg.contains("Failed to parse")
-                || msg.contains("must have"),
-            "Expected parse error for missing fields, got: {msg}"
-        );
+        let config = FlowConfig::parse(toml).unwrap();
+        let gc = config.gc.expect("gc should be Some");
+        assert_eq!(gc.debug_days, 3);
+        assert_eq!(gc.recording_days, 30);
+        assert_eq!(gc.session_days, GcConfig::default().session_days);
     }
 
-    #[test]
-    fn test_reject_invalid_toml() {
-        let err = FlowConfig::parse("not valid toml {{{").unwrap_err();
-        assert!(err.to_string().contains("Failed to parse"));
-    }
+    // --- CompactionConfig tests ---
 
     #[test]
-    fn test_from_path_missing_file() {
-        let err = FlowConfig::from_path("/nonexistent/cycles.toml").unwrap_err();
-        assert!(err.to_string().contains("Failed to read"));
-    }
+    fn test_compaction_config_and_compact_context() {
+        let toml = r#"
+[global]
+permissions = []
 
-    #[test]
-    fn test_from_path_valid_file() {
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        let config_path = temp_dir.path().join("cycles.toml");
-        std::fs::write(&config_path, VALID_CONFIG).unwrap();
+[compaction]
+keep_recent = 5
 
-        let config = FlowConfig::from_path(&config_path).unwrap();
-        assert_eq!(config.cycles.len(), 2);
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+context = "compact"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let compaction = config.compaction.expect("compaction should be Some");
+        assert_eq!(compaction.keep_recent, 5);
+        assert_eq!(compaction.block_size, 50);
+        let coding = config.cycles[0].clone();
+        assert_eq!(coding.context, ContextMode::Compact);
+        assert!(coding.uses_compaction());
+
+        let invalid = toml.replace("keep_recent = 5", "block_size = 0");
+        let err = FlowConfig::parse(&invalid).unwrap_err();
+        assert!(err.to_string().contains("block_size"), "{err}");
     }
 
+    // --- TestParserConfig tests ---
+
     #[test]
-    fn test_multiline_prompt() {
+    fn test_test_parser_config_parsed() {
         let toml = r#"
 [global]
 permissions = []
 
+[test_parser]
+preset = "pytest"
+failed = '(\d+) errors?'
+
 [[cycle]]
 name = "coding"
-description = "Coding cycle"
-prompt = """
-Line one.
-Line two.
-Line three.
-"""
+description = "Coding"
+prompt = "Code"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let coding = config.get_cycle("coding").unwrap();
-        assert!(coding.prompt.contains("Line one."));
-        assert!(coding.prompt.contains("Line three."));
+        let parser = config
+            .test_parser
+            .as_ref()
+            .expect("test_parser should be Some");
+        assert_eq!(parser.preset, TestParserPreset::Pytest);
+        assert_eq!(parser.failed.as_deref(), Some(r"(\d+) errors?"));
+        assert!(parser.passed.is_none());
     }
 
     #[test]
-    fn test_global_safeguard_defaults() {
+    fn test_test_parser_preset_defaults_to_cargo() {
         let toml = r#"
 [global]
 permissions = []
 
+[test_parser]
+passed = 'ok: (\d+)'
+
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        assert_eq!(config.global.max_permission_denials, 10);
-        assert_eq!(config.global.circuit_breaker_repeated, 5);
+        assert_eq!(config.test_parser.unwrap().preset, TestParserPreset::Cargo);
     }
 
     #[test]
-    fn test_global_safeguard_custom_values() {
+    fn test_test_parser_rejects_invalid_regex() {
         let toml = r#"
 [global]
 permissions = []
-max_permission_denials = 20
-circuit_breaker_repeated = 3
+
+[test_parser]
+passed = "(unclosed"
 
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
 "#;
-        let config = FlowConfig::parse(toml).unwrap();
-        assert_eq!(config.global.max_permission_denials, 20);
-        assert_eq!(config.global.circuit_breaker_repeated, 3);
-    }
-
-    #[test]
-    fn test_global_permissions_preserved() {
-        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
-        assert_eq!(
-            config.global.permissions,
-            vec!["Read", "Edit(./src/**)", "Bash(cargo *)"]
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string().contains("test_parser.passed"),
+            "Expected regex error, got: {err}"
         );
     }
 
-    // --- min_interval config field tests ---
-
     #[test]
-    fn test_min_interval_default_is_none() {
+    fn test_test_parser_rejects_unknown_preset() {
         let toml = r#"
 [global]
 permissions = []
 
+[test_parser]
+preset = "mocha"
+
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
-
-[[cycle]]
-name = "gardening"
-description = "Gardening"
-prompt = "Garden"
-after = ["coding"]
 "#;
-        let config = FlowConfig::parse(toml).unwrap();
-        let gardening = config.get_cycle("gardening").unwrap();
-        assert_eq!(gardening.min_interval, None);
+        assert!(FlowConfig::parse(toml).is_err());
     }
 
+    // --- TestGuardConfig and LintConfig tests ---
+
     #[test]
-    fn test_min_interval_parsed_from_config() {
+    fn test_test_guard_and_lint_parsed_and_validated() {
         let toml = r#"
 [global]
 permissions = []
 
+[test_guard]
+command = "cargo test"
+cycles = ["coding"]
+fail_on_regression = true
+
+[lint]
+command = "ruff check ."
+cycles = ["review"]
+pattern = '^\S+:\d+:\d+: '
+
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
 
 [[cycle]]
-name = "gardening"
-description = "Gardening"
-prompt = "Garden"
-after = ["coding"]
-min_interval = 3
+name = "review"
+description = "Review"
+prompt = "Review"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let gardening = config.get_cycle("gardening").unwrap();
-        assert_eq!(gardening.min_interval, Some(3));
+        let guard = config.test_guard.expect("test_guard should be Some");
+        assert_eq!(guard.command, "cargo test");
+        assert!(guard.fail_on_regression);
+        assert!(guard.guards("coding"));
+        assert!(!guard.guards("review"));
+        let lint = config.lint.expect("lint should be Some");
+        assert_eq!(lint.command, "ruff check .");
+        assert!(lint.lints("review"));
+        assert!(!lint.lints("coding"));
+
+        for (from, to, error) in [
+            (
+                r#"cycles = ["coding"]"#,
+                r#"cycles = ["deploy"]"#,
+                "test_guard.cycles references unknown cycle 'deploy'",
+            ),
+            (
+                r#"cycles = ["review"]"#,
+                r#"cycles = ["deploy"]"#,
+                "lint.cycles references unknown cycle 'deploy'",
+            ),
+            (
+                r#"command = "cargo test""#,
+                r#"command = " ""#,
+                "test_guard.command",
+            ),
+            (
+                r#"command = "ruff check .""#,
+                r#"command = """#,
+                "lint.command",
+            ),
+            (r"'^\S+:\d+:\d+: '", "'(unclosed'", "lint.pattern"),
+        ] {
+            let err = FlowConfig::parse(&toml.replace(from, to)).unwrap_err();
+            assert!(err.to_string().contains(error), "{err}");
+        }
+    }
+
+    // --- debug_stream config field tests ---
+
+    #[test]
+    fn test_debug_stream_defaults_to_false() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        assert!(!config.global.debug_stream);
     }
 
     #[test]
-    fn test_min_interval_zero_is_valid() {
+    fn test_debug_stream_parsed() {
         let toml = r#"
 [global]
 permissions = []
+debug_stream = true
 
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
-min_interval = 0
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let coding = config.get_cycle("coding").unwrap();
-        assert_eq!(coding.min_interval, Some(0));
+        assert!(config.global.debug_stream);
     }
 
-    // --- Permission string validation tests ---
+    // --- max_run_duration_secs / parse_duration_secs tests ---
 
     #[test]
-    fn test_valid_bare_tool_name() {
-        let toml = r#"
-[global]
-permissions = ["Read"]
-
-[[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
-"#;
-        assert!(FlowConfig::parse(toml).is_ok());
+    fn test_max_run_duration_defaults_to_none() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        assert!(config.global.max_run_duration_secs.is_none());
     }
 
     #[test]
-    fn test_valid_tool_with_specifier() {
+    fn test_max_run_duration_parsed() {
         let toml = r#"
 [global]
-permissions = ["Edit(./src/**)", "Bash(cargo test *)"]
+permissions = []
+max_run_duration_secs = 14400
 
 [[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
+name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
-        assert!(FlowConfig::parse(toml).is_ok());
+        let config = FlowConfig::parse(toml).unwrap();
+        assert_eq!(config.global.max_run_duration_secs, Some(14400));
     }
 
     #[test]
-    fn test_reject_lowercase_tool_name() {
+    fn test_max_run_duration_zero_rejected() {
         let toml = r#"
 [global]
-permissions = ["read"]
+permissions = []
+max_run_duration_secs = 0
 
 [[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
+name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
         let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Invalid permission"),
-            "Expected 'Invalid permission' error, got: {err}"
-        );
+        assert!(err.to_string().contains("max_run_duration_secs"));
     }
 
     #[test]
-    fn test_reject_empty_permission_string() {
-        let toml = r#"
-[global]
-permissions = [""]
-
-[[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
-"#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Invalid permission"),
-            "Expected 'Invalid permission' error, got: {err}"
-        );
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration_secs("90").unwrap(), 90);
+        assert_eq!(parse_duration_secs("45s").unwrap(), 45);
+        assert_eq!(parse_duration_secs("30m").unwrap(), 1800);
+        assert_eq!(parse_duration_secs("4h").unwrap(), 14400);
+        assert_eq!(parse_duration_secs("2d").unwrap(), 172_800);
     }
 
     #[test]
-    fn test_reject_permission_with_spaces_in_tool_name() {
-        let toml = r#"
-[global]
-permissions = ["Read Write"]
-
-[[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
-"#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Invalid permission"),
-            "Expected 'Invalid permission' error, got: {err}"
-        );
+    fn test_parse_duration_compound() {
+        assert_eq!(parse_duration_secs("1h30m").unwrap(), 5400);
+        assert_eq!(parse_duration_secs(" 2m10s ").unwrap(), 130);
     }
 
     #[test]
-    fn test_reject_unbalanced_parens() {
-        let toml = r#"
-[global]
-permissions = ["Edit(./src/**"]
-
-[[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
-"#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Invalid permission"),
-            "Expected 'Invalid permission' error, got: {err}"
-        );
+    fn test_parse_duration_invalid() {
+        assert!(parse_duration_secs("").is_err());
+        assert!(parse_duration_secs("h").is_err());
+        assert!(parse_duration_secs("4x").is_err());
+        assert!(parse_duration_secs("1h30").is_err());
+        assert!(parse_duration_secs("-5m").is_err());
     }
 
-    #[test]
-    fn test_reject_empty_specifier() {
-        let toml = r#"
-[global]
-permissions = ["Edit()"]
+    // --- stop_when config tests ---
 
-[[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
-"#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Invalid permission"),
-            "Expected 'Invalid permission' error, got: {err}"
-        );
+    #[test]
+    fn test_stop_when_defaults_to_none() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        assert!(config.global.stop_when.is_none());
     }
 
     #[test]
-    fn test_reject_permission_with_trailing_text() {
+    fn test_stop_when_parsed() {
         let toml = r#"
 [global]
-permissions = ["Edit(./src/**)extra"]
+permissions = []
+
+[global.stop_when]
+no_pending_tasks = ["P0", "P1"]
+command = "cargo test"
 
 [[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
+name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Invalid permission"),
-            "Expected 'Invalid permission' error, got: {err}"
+        let config = FlowConfig::parse(toml).unwrap();
+        let stop_when = config.global.stop_when.unwrap();
+        assert_eq!(
+            stop_when.no_pending_tasks,
+            Some(vec!["P0".to_string(), "P1".to_string()])
         );
+        assert_eq!(stop_when.command.as_deref(), Some("cargo test"));
     }
 
     #[test]
-    fn test_reject_invalid_cycle_permission() {
+    fn test_stop_when_without_conditions_rejected() {
         let toml = r#"
 [global]
 permissions = []
 
+[global.stop_when]
+
 [[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
-permissions = ["not-valid!"]
+name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
         let err = FlowConfig::parse(toml).unwrap_err();
-        let msg = format!("{err:?}");
-        assert!(
-            msg.contains("Invalid permission"),
-            "Expected 'Invalid permission' error, got: {msg}"
-        );
-        assert!(
-            msg.contains("in cycle 'test'"),
-            "Expected cycle context, got: {msg}"
-        );
+        assert!(err.to_string().contains("stop_when"), "{err}");
     }
 
     #[test]
-    fn test_valid_known_tool_names() {
+    fn test_stop_when_empty_command_rejected() {
         let toml = r#"
 [global]
-permissions = ["Read", "Glob", "Grep", "Edit(./src/**)", "Write(./out.txt)", "Bash(cargo *)", "WebFetch", "WebSearch", "NotebookEdit(./nb.ipynb)", "Task", "TodoWrite"]
+permissions = []
+stop_when = { command = "  " }
 
 [[cycle]]
-name = "test"
-description = "Test"
-prompt = "Test"
+name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
-        assert!(FlowConfig::parse(toml).is_ok());
+        assert!(FlowConfig::parse(toml).is_err());
     }
 
-    // --- Multi-step cycle config tests ---
+    // --- git branch config tests ---
 
     #[test]
-    fn test_parse_multi_step_cycle() {
+    fn test_git_branch_settings_parsed() {
         let toml = r#"
 [global]
-permissions = ["Read"]
+permissions = []
+git_branch_template = "flow/{run_id}"
+git_on_finish = "pull_request"
 
 [[cycle]]
 name = "coding"
-description = "Multi-step coding cycle"
-after = []
-
-[[cycle.step]]
-name = "plan"
-session = "architect"
-prompt = "Read TODO.md and write a plan."
-permissions = ["Edit(./.flow/current-plan.md)"]
-
-[[cycle.step]]
-name = "implement"
-session = "coder"
-prompt = "Read the plan and implement it."
-permissions = ["Edit(./src/**)", "Bash(cargo *)"]
+description = "Coding"
+prompt = "Code"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let coding = config.get_cycle("coding").unwrap();
-        assert_eq!(coding.steps.len(), 2);
-        assert_eq!(coding.steps[0].name, "plan");
-        assert_eq!(coding.steps[0].session, Some("architect".to_string()));
-        assert_eq!(coding.steps[0].prompt, "Read TODO.md and write a plan.");
         assert_eq!(
-            coding.steps[0].permissions,
-            vec!["Edit(./.flow/current-plan.md)"]
+            config.global.git_branch_template.as_deref(),
+            Some("flow/{run_id}")
         );
-        assert_eq!(coding.steps[1].name, "implement");
+        assert_eq!(config.global.git_on_finish, Some(GitFinish::PullRequest));
     }
 
     #[test]
-    fn test_single_step_cycle_has_empty_steps() {
+    fn test_git_on_finish_requires_branch_template() {
         let toml = r#"
 [global]
 permissions = []
+git_on_finish = "fast_forward"
 
 [[cycle]]
-name = "gardening"
-description = "Gardening"
-prompt = "You are gardening."
+name = "coding"
+description = "Coding"
+prompt = "Code"
 "#;
-        let config = FlowConfig::parse(toml).unwrap();
-        let gardening = config.get_cycle("gardening").unwrap();
-        assert!(gardening.steps.is_empty());
-        assert_eq!(gardening.prompt, "You are gardening.");
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("git_branch_template"), "{err}");
     }
 
+    // --- success_when config tests ---
+
     #[test]
-    fn test_step_without_session_tag_is_valid() {
+    fn test_success_when_defaults_to_none() {
+        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
+        assert!(config.cycles.iter().all(|c| c.success_when.is_none()));
+    }
+
+    #[test]
+    fn test_success_when_parsed() {
         let toml = r#"
 [global]
 permissions = []
@@ -978,19 +4181,22 @@ permissions = []
 [[cycle]]
 name = "coding"
 description = "Coding"
-after = []
+prompt = "Code"
 
-[[cycle.step]]
-name = "implement"
-prompt = "Implement the task."
+[cycle.success_when]
+result_matches = "(?i)committed"
+command = "cargo test"
+min_tests_passed = 1
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let coding = config.get_cycle("coding").unwrap();
-        assert_eq!(coding.steps[0].session, None);
+        let criteria = config.cycles[0].success_when.as_ref().unwrap();
+        assert_eq!(criteria.result_matches.as_deref(), Some("(?i)committed"));
+        assert_eq!(criteria.command.as_deref(), Some("cargo test"));
+        assert_eq!(criteria.min_tests_passed, Some(1));
     }
 
     #[test]
-    fn test_reject_multi_step_cycle_with_top_level_prompt() {
+    fn test_success_when_empty_rejected() {
         let toml = r#"
 [global]
 permissions = []
@@ -998,21 +4204,15 @@ permissions = []
 [[cycle]]
 name = "coding"
 description = "Coding"
-prompt = "This should not be here alongside steps."
-
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
+prompt = "Code"
+success_when = {}
 "#;
         let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("cannot have both"),
-            "Expected 'cannot have both' error, got: {err}"
-        );
+        assert!(err.to_string().contains("success_when"), "{err}");
     }
 
     #[test]
-    fn test_reject_cycle_without_prompt_and_without_steps() {
+    fn test_success_when_invalid_regex_rejected() {
         let toml = r#"
 [global]
 permissions = []
@@ -1020,17 +4220,17 @@ permissions = []
 [[cycle]]
 name = "coding"
 description = "Coding"
+prompt = "Code"
+success_when = { result_matches = "(unclosed" }
 "#;
         let err = FlowConfig::parse(toml).unwrap_err();
-        let msg = err.to_string();
-        assert!(
-            msg.contains("must have") || msg.contains("missing field") || msg.contains("prompt"),
-            "Expected error about missing prompt or steps, got: {msg}"
-        );
+        assert!(err.to_string().contains("result_matches"), "{err}");
     }
 
+    // --- after_any / after_all / after_failures_of config tests ---
+
     #[test]
-    fn test_reject_duplicate_step_names_within_cycle() {
+    fn test_after_any_alias_and_new_trigger_fields_parsed() {
         let toml = r#"
 [global]
 permissions = []
@@ -1038,182 +4238,208 @@ permissions = []
 [[cycle]]
 name = "coding"
 description = "Coding"
-after = []
+prompt = "Code"
 
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
+[[cycle]]
+name = "testing"
+description = "Testing"
+prompt = "Test"
 
-[[cycle.step]]
-name = "plan"
-prompt = "Also plan."
+[[cycle]]
+name = "docs"
+description = "Docs"
+prompt = "Docs"
+after_any = ["coding"]
+after_all = ["coding", "testing"]
+after_failures_of = ["testing"]
 "#;
-        let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Duplicate step name"),
-            "Expected 'Duplicate step name' error, got: {err}"
+        let config = FlowConfig::parse(toml).unwrap();
+        let docs = config.get_cycle("docs").unwrap();
+        assert_eq!(docs.after, vec!["coding"]);
+        assert_eq!(docs.after_all, vec!["coding", "testing"]);
+        assert_eq!(docs.after_failures_of, vec!["testing"]);
+        assert_eq!(
+            docs.trigger_sources(),
+            vec!["coding", "coding", "testing", "testing"]
         );
     }
 
     #[test]
-    fn test_reject_empty_step_name() {
+    fn test_after_all_unknown_cycle_rejected() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
-name = "coding"
-description = "Coding"
-after = []
-
-[[cycle.step]]
-name = ""
-prompt = "Plan."
+name = "docs"
+description = "Docs"
+prompt = "Docs"
+after_all = ["ghost"]
 "#;
         let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("empty"),
-            "Expected 'empty' error for step name, got: {err}"
-        );
+        assert!(err.to_string().contains("'after_all'"), "{err}");
     }
 
     #[test]
-    fn test_multi_step_cycle_has_no_top_level_prompt() {
+    fn test_after_failures_of_unknown_cycle_rejected() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
-name = "coding"
-description = "Coding"
-after = []
-
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
+name = "review"
+description = "Review"
+prompt = "Review"
+after_failures_of = ["ghost"]
 "#;
-        let config = FlowConfig::parse(toml).unwrap();
-        let coding = config.get_cycle("coding").unwrap();
-        assert!(coding.prompt.is_empty());
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("'after_failures_of'"), "{err}");
     }
 
-    /// Verify the actual cycles.toml in the project root parses and that the
-    /// coding cycle is a multi-step cycle with plan / plan-review / implement steps.
     #[test]
-    fn test_actual_cycles_toml_coding_is_multi_step() {
-        let config = FlowConfig::from_path("cycles.toml").expect("cycles.toml must be parseable");
-        let coding = config.get_cycle("coding").expect("coding cycle must exist");
-
-        assert!(
-            coding.is_multi_step(),
-            "coding cycle should be multi-step (using [[cycle.step]] entries)"
-        );
-        assert!(
-            coding.prompt.is_empty(),
-            "multi-step cycle must not have a top-level prompt"
-        );
+    fn test_min_interval_duration_parsed() {
+        let toml = r#"
+[global]
+permissions = []
 
-        let step_names: Vec<&str> = coding.steps.iter().map(|s| s.name.as_str()).collect();
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "Garden"
+min_interval = "6h"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
         assert_eq!(
-            step_names,
-            vec!["plan", "plan-review", "implement", "reflect"],
-            "coding cycle should have plan, plan-review, implement, reflect steps"
+            config.cycles[0].min_interval,
+            Some(MinInterval::Duration(6 * 3600))
         );
+    }
 
-        // plan step: architect session, read-only + plan file write
-        let plan = &coding.steps[0];
-        assert_eq!(plan.session.as_deref(), Some("architect"));
-        assert!(
-            plan.permissions
-                .iter()
-                .any(|p| p.starts_with("Edit(./.flow/")),
-            "plan step should have edit permission for .flow/ artifacts"
-        );
+    #[test]
+    fn test_min_interval_invalid_duration_rejected() {
+        let toml = r#"
+[global]
+permissions = []
 
-        // plan-review step: architect continues (same session), reads plan, can exit 1
-        let review = &coding.steps[1];
-        assert_eq!(review.name, "plan-review");
-        assert_eq!(
-            review.session.as_deref(),
-            Some("architect"),
-            "plan-review should continue the architect session"
-        );
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "Garden"
+min_interval = "6 hours"
+"#;
+        assert!(FlowConfig::parse(toml).is_err());
+    }
 
-        // implement step: coder session with full write permissions
-        let implement = &coding.steps[2];
-        assert_eq!(implement.session.as_deref(), Some("coder"));
-        assert!(
-            implement.permissions.iter().any(|p| p == "Bash(git *)"),
-            "implement step should have git permissions for committing"
-        );
+    #[test]
+    fn test_min_interval_duration_round_trips() {
+        let cycle: CycleConfig = toml::from_str(
+            r#"
+name = "gardening"
+description = "Gardening"
+prompt = "Garden"
+min_interval = "1h30m"
+"#,
+        )
+        .unwrap();
+        let serialized = toml::to_string(&cycle).unwrap();
+        let reparsed: CycleConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.min_interval, Some(MinInterval::Duration(5400)));
     }
 
-    // --- StepConfig router and max_visits tests ---
+    // --- selector weight / selectable config tests ---
 
     #[test]
-    fn test_step_router_default_is_sequential() {
-        let toml = r#"
+    fn test_weight_and_selectable_defaults() {
+        let config = FlowConfig::parse(
+            r#"
 [global]
 permissions = []
 
 [[cycle]]
 name = "coding"
 description = "Coding"
-after = []
-
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
-"#;
-        let config = FlowConfig::parse(toml).unwrap();
-        let step = &config.get_cycle("coding").unwrap().steps[0];
-        assert_eq!(step.router, StepRouter::Sequential);
+prompt = "Code"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.cycles[0].weight, None);
+        assert!(config.cycles[0].selectable);
     }
 
     #[test]
-    fn test_step_router_llm_parsed() {
-        let toml = r#"
+    fn test_weight_and_selectable_parsed() {
+        let config = FlowConfig::parse(
+            r#"
 [global]
 permissions = []
 
 [[cycle]]
 name = "coding"
 description = "Coding"
-after = []
+prompt = "Code"
+weight = 3
 
-[[cycle.step]]
-name = "plan-review"
-prompt = "Review the plan."
-router = "llm"
-"#;
-        let config = FlowConfig::parse(toml).unwrap();
-        let step = &config.get_cycle("coding").unwrap().steps[0];
-        assert_eq!(step.router, StepRouter::Llm);
+[[cycle]]
+name = "recovery"
+description = "Recovery"
+prompt = "Fix"
+selectable = false
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.cycles[0].weight, Some(3));
+        assert!(!config.cycles[1].selectable);
     }
 
     #[test]
-    fn test_step_router_sequential_explicit() {
-        let toml = r#"
+    fn test_weight_zero_rejected() {
+        let err = FlowConfig::parse(
+            r#"
 [global]
 permissions = []
 
 [[cycle]]
 name = "coding"
 description = "Coding"
-after = []
+prompt = "Code"
+weight = 0
+"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("weight"), "{err}");
+    }
 
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
-router = "sequential"
+    // --- samples config tests ---
+
+    #[test]
+    fn test_samples_parsed_and_bounded() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+samples = 3
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let step = &config.get_cycle("coding").unwrap().steps[0];
-        assert_eq!(step.router, StepRouter::Sequential);
+        assert_eq!(config.cycles[0].samples, Some(3));
+
+        for bad in ["samples = 0", "samples = 11"] {
+            let err = FlowConfig::parse(&toml.replace("samples = 3", bad)).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("Cycle 'coding': samples must be between 1 and 10"),
+                "{err}"
+            );
+        }
     }
 
+    // --- eval config tests ---
+
     #[test]
-    fn test_step_max_visits_default_is_3() {
+    fn test_eval_rubric_parsed_and_validated() {
         let toml = r#"
 [global]
 permissions = []
@@ -1221,125 +4447,146 @@ permissions = []
 [[cycle]]
 name = "coding"
 description = "Coding"
-after = []
+prompt = "Code"
 
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
+[cycle.eval]
+criteria = ["Tests cover the change", "Small diff"]
+model = "haiku"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let step = &config.get_cycle("coding").unwrap().steps[0];
-        assert_eq!(step.max_visits, 3);
+        let eval = config.cycles[0].eval.as_ref().unwrap();
+        assert_eq!(eval.criteria, vec!["Tests cover the change", "Small diff"]);
+        assert_eq!(eval.model.as_deref(), Some("haiku"));
+
+        let empty = toml.replace(r#"["Tests cover the change", "Small diff"]"#, "[]");
+        let err = FlowConfig::parse(&empty).unwrap_err();
+        assert!(
+            err.to_string().contains("eval.criteria cannot be empty"),
+            "{err}"
+        );
+        let duplicate = toml.replace("\"Small diff\"", "\"Tests cover the change\"");
+        let err = FlowConfig::parse(&duplicate).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("duplicate eval criterion 'Tests cover the change'"),
+            "{err}"
+        );
     }
 
+    // --- every_n_iterations config tests ---
+
     #[test]
-    fn test_step_max_visits_custom_value() {
+    fn test_every_n_iterations_parsed() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
-name = "coding"
-description = "Coding"
-after = []
-
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
-max_visits = 5
+name = "docs"
+description = "Docs"
+prompt = "Docs"
+every_n_iterations = 5
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let step = &config.get_cycle("coding").unwrap().steps[0];
-        assert_eq!(step.max_visits, 5);
+        assert_eq!(config.cycles[0].every_n_iterations, Some(5));
     }
 
     #[test]
-    fn test_reject_invalid_router_value() {
+    fn test_every_n_iterations_zero_rejected() {
         let toml = r#"
 [global]
 permissions = []
 
 [[cycle]]
-name = "coding"
-description = "Coding"
-after = []
-
-[[cycle.step]]
-name = "plan"
-prompt = "Plan."
-router = "invalid"
+name = "docs"
+description = "Docs"
+prompt = "Docs"
+every_n_iterations = 0
 "#;
         let err = FlowConfig::parse(toml).unwrap_err();
-        assert!(
-            err.to_string().contains("Failed to parse"),
-            "Expected parse error for invalid router, got: {err}"
-        );
+        assert!(err.to_string().contains("every_n_iterations"), "{err}");
     }
 
-    // --- SelectorConfig tests ---
+    // --- on_failure config tests ---
 
-    #[test]
-    fn test_selector_config_parsed() {
-        let toml = r#"
+    const ON_FAILURE_CONFIG: &str = r#"
 [global]
 permissions = []
 
-[selector]
-prompt = "Read TODO.md for priorities. Focus on P0 tasks first."
-
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
+on_failure = "fixup"
+
+[[cycle]]
+name = "fixup"
+description = "Repair a broken build"
+prompt = "Fix"
 "#;
-        let config = FlowConfig::parse(toml).unwrap();
-        let selector = config.selector.as_ref().expect("selector should be Some");
+
+    #[test]
+    fn test_on_failure_parsed() {
+        let config = FlowConfig::parse(ON_FAILURE_CONFIG).unwrap();
         assert_eq!(
-            selector.prompt,
-            "Read TODO.md for priorities. Focus on P0 tasks first."
+            config.get_cycle("coding").unwrap().on_failure.as_deref(),
+            Some("fixup")
         );
+        assert!(config.get_cycle("fixup").unwrap().on_failure.is_none());
     }
 
     #[test]
-    fn test_selector_config_absent_is_none() {
-        let config = FlowConfig::parse(VALID_CONFIG).unwrap();
-        assert!(
-            config.selector.is_none(),
-            "config without [selector] should have selector = None"
-        );
+    fn test_on_failure_unknown_cycle_rejected() {
+        let toml = ON_FAILURE_CONFIG.replace("on_failure = \"fixup\"", "on_failure = \"nope\"");
+        let err = FlowConfig::parse(&toml).unwrap_err();
+        assert!(err.to_string().contains("on_failure"), "{err}");
     }
 
-    /// Verify the actual cycles.toml parses correctly with the optional [selector] field.
-    /// When [selector] is added to cycles.toml, this test validates it has a non-empty prompt.
     #[test]
-    fn test_actual_cycles_toml_parses_with_optional_selector() {
-        let config = FlowConfig::from_path("cycles.toml").expect("cycles.toml must be parseable");
-        // selector is optional — just verify the config parses without error
-        if let Some(selector) = &config.selector {
-            assert!(
-                !selector.prompt.is_empty(),
-                "if [selector] is present, its prompt should be non-empty"
-            );
-        }
+    fn test_on_failure_self_reference_rejected() {
+        let toml = ON_FAILURE_CONFIG.replace("on_failure = \"fixup\"", "on_failure = \"coding\"");
+        let err = FlowConfig::parse(&toml).unwrap_err();
+        assert!(err.to_string().contains("its own"), "{err}");
     }
 
+    // --- arbitrates config tests ---
+
     #[test]
-    fn test_selector_config_empty_prompt() {
+    fn test_arbitrates_parsed_and_checked() {
         let toml = r#"
 [global]
 permissions = []
 
-[selector]
-prompt = ""
-
 [[cycle]]
 name = "coding"
 description = "Coding"
 prompt = "Code"
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review"
+after = ["coding"]
+arbitrates = "coding"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let selector = config.selector.as_ref().expect("selector should be Some");
-        assert!(selector.prompt.is_empty());
+        assert_eq!(
+            config.get_cycle("review").unwrap().arbitrates.as_deref(),
+            Some("coding")
+        );
+
+        let err =
+            FlowConfig::parse(&toml.replace("arbitrates = \"coding\"", "arbitrates = \"nope\""))
+                .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Cycle 'review' references unknown cycle 'nope' in 'arbitrates'"),
+            "{err}"
+        );
+        let err =
+            FlowConfig::parse(&toml.replace("arbitrates = \"coding\"", "arbitrates = \"review\""))
+                .unwrap_err();
+        assert!(err.to_string().contains("cannot arbitrate itself"), "{err}");
     }
 
     // --- summary_interval config field tests ---
@@ -1520,6 +4767,35 @@ max_turns = 0
         );
     }
 
+    #[test]
+    fn test_max_tokens_parses_and_rejects_zero() {
+        let toml = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+max_tokens = 500000
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan"
+max_tokens = 0
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Step 'plan' in cycle 'coding': max_tokens must be greater than 0"),
+            "{err}"
+        );
+        let config =
+            FlowConfig::parse(&toml.replace("max_tokens = 0", "max_tokens = 1000")).unwrap();
+        let coding = config.get_cycle("coding").unwrap();
+        assert_eq!(coding.max_tokens, Some(500_000));
+        assert_eq!(coding.steps[0].max_tokens, Some(1000));
+    }
+
     #[test]
     fn test_reject_max_cost_usd_zero() {
         let toml = r#"
@@ -1586,6 +4862,250 @@ permissions = ["not-valid"]
         );
     }
 
+    // --- redaction config tests ---
+
+    #[test]
+    fn test_redaction_absent_by_default() {
+        let config = FlowConfig::parse(OVERRIDE_CONFIG).unwrap();
+        assert!(config.redaction.is_none());
+    }
+
+    #[test]
+    fn test_redaction_section_defaults_builtin_on() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[redaction]\npatterns = [\"internal-\\\\d+\"]\n");
+        let config = FlowConfig::parse(&toml).unwrap();
+        let redaction = config.redaction.unwrap();
+        assert!(redaction.builtin);
+        assert_eq!(redaction.patterns, vec![r"internal-\d+"]);
+    }
+
+    #[test]
+    fn test_redaction_invalid_pattern_rejected() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[redaction]\npatterns = [\"(unclosed\"]\n");
+        let err = FlowConfig::parse(&toml).unwrap_err();
+        assert!(err.to_string().contains("redaction pattern"), "{err}");
+    }
+
+    // --- notifications config tests ---
+
+    #[test]
+    fn test_notifications_section_defaults() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[notifications]\nbudget_warning_usd = 10.0\n");
+        let config = FlowConfig::parse(&toml).unwrap();
+        let notifications = config.notifications.unwrap();
+        assert!(notifications.desktop);
+        assert_eq!(notifications.events.len(), 3);
+        assert_eq!(notifications.budget_warning_usd, Some(10.0));
+    }
+
+    #[test]
+    fn test_notifications_events_parsed() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[notifications]\nevents = [\"run_stopped\"]\n");
+        let config = FlowConfig::parse(&toml).unwrap();
+        assert_eq!(
+            config.notifications.unwrap().events,
+            vec![NotificationEvent::RunStopped]
+        );
+    }
+
+    #[test]
+    fn test_notifications_non_positive_budget_rejected() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[notifications]\nbudget_warning_usd = 0.0\n");
+        let err = FlowConfig::parse(&toml).unwrap_err();
+        assert!(err.to_string().contains("budget_warning_usd"), "{err}");
+    }
+
+    #[test]
+    fn test_notification_rules_and_channels_parsed() {
+        let toml = format!(
+            r#"{OVERRIDE_CONFIG}
+[notifications.channels.team]
+type = "slack"
+url = "https://hooks.slack.com/services/T/B/X"
+
+[[notifications.rule]]
+events = ["cycle_failed"]
+cycles = ["coding"]
+min_cost_usd = 2.0
+channels = ["team", "desktop"]
+
+[[notifications.rule]]
+min_severity = "error"
+channels = ["desktop"]
+"#
+        );
+        let config = FlowConfig::parse(&toml).unwrap();
+        let notifications = config.notifications.unwrap();
+        assert_eq!(notifications.channels["team"].kind, ChannelKind::Slack);
+        assert_eq!(notifications.rules.len(), 2);
+        assert_eq!(notifications.rules[0].cycles, vec!["coding"]);
+        assert_eq!(
+            notifications.rules[1].min_severity,
+            Some(NotificationSeverity::Error)
+        );
+    }
+
+    #[test]
+    fn test_notification_rule_unknown_channel_rejected() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[[notifications.rule]]\nchannels = [\"pager\"]\n");
+        let err = FlowConfig::parse(&toml).unwrap_err();
+        assert!(err.to_string().contains("unknown channel 'pager'"), "{err}");
+    }
+
+    #[test]
+    fn test_notification_webhook_channel_requires_url() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[notifications.channels.ops]\ntype = \"webhook\"\n");
+        let err = FlowConfig::parse(&toml).unwrap_err();
+        assert!(err.to_string().contains("needs a url"), "{err}");
+    }
+
+    // --- hooks config tests ---
+
+    #[test]
+    fn test_hooks_section_parsed() {
+        let toml = format!(
+            "{OVERRIDE_CONFIG}\n[hooks]\nrun_start = [\"./announce.sh\"]\nfailure = [\"./page.sh\", \"./log.sh\"]\n"
+        );
+        let hooks = FlowConfig::parse(&toml).unwrap().hooks.unwrap();
+        assert_eq!(hooks.run_start, vec!["./announce.sh"]);
+        assert_eq!(hooks.failure.len(), 2);
+        assert!(hooks.cycle_end.is_empty());
+        assert_eq!(hooks.timeout_secs, 30);
+    }
+
+    #[test]
+    fn test_hooks_empty_command_rejected() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[hooks]\nrun_end = [\" \"]\n");
+        let err = FlowConfig::parse(&toml).unwrap_err();
+        assert!(err.to_string().contains("Hook commands"), "{err}");
+    }
+
+    // --- parse_with_overrides tests ---
+
+    const OVERRIDE_CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+
+    #[test]
+    fn test_user_defaults_merge_under_project_and_overrides() {
+        let defaults: toml::Table = "[global]\nmodel = \"sonnet\"\nsummary_interval = 9\n"
+            .parse()
+            .unwrap();
+        let content = OVERRIDE_CONFIG.replace("[global]\n", "[global]\nsummary_interval = 2\n");
+        let config = FlowConfig::parse_with_overrides_in(
+            &content,
+            &defaults,
+            None,
+            &["global.model=\"opus\"".to_string()],
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(config.global.summary_interval, 2);
+        assert_eq!(config.global.model.as_deref(), Some("opus"));
+
+        let config = FlowConfig::parse_with_overrides_in(
+            OVERRIDE_CONFIG,
+            &defaults,
+            None,
+            &[],
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(config.global.summary_interval, 9);
+        assert_eq!(config.global.model.as_deref(), Some("sonnet"));
+    }
+
+    #[test]
+    fn test_parse_with_overrides_applies_values() {
+        let config = FlowConfig::parse_with_overrides(
+            OVERRIDE_CONFIG,
+            None,
+            &[
+                "global.summary_interval=10".to_string(),
+                "cycle.coding.max_turns=30".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.global.summary_interval, 10);
+        assert_eq!(config.cycles[0].max_turns, Some(30));
+    }
+
+    #[test]
+    fn test_parse_with_overrides_later_wins() {
+        let config = FlowConfig::parse_with_overrides(
+            OVERRIDE_CONFIG,
+            None,
+            &[
+                "cycle.coding.max_turns=30".to_string(),
+                "cycle.coding.max_turns=40".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.cycles[0].max_turns, Some(40));
+    }
+
+    #[test]
+    fn test_parse_with_overrides_revalidates() {
+        let result = FlowConfig::parse_with_overrides(
+            OVERRIDE_CONFIG,
+            None,
+            &["cycle.coding.max_turns=0".to_string()],
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_with_overrides_rejects_wrong_type() {
+        let result = FlowConfig::parse_with_overrides(
+            OVERRIDE_CONFIG,
+            None,
+            &["global.summary_interval=often".to_string()],
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_with_profile_then_overrides() {
+        let toml = format!(
+            "{OVERRIDE_CONFIG}\n[profile.ci.global]\nsummary_interval = 1\n\n[profile.ci.cycle.coding]\nmax_turns = 20\n"
+        );
+        let config = FlowConfig::parse_with_overrides(
+            &toml,
+            Some("ci"),
+            &["cycle.coding.max_turns=25".to_string()],
+        )
+        .unwrap();
+        assert_eq!(config.global.summary_interval, 1);
+        assert_eq!(
+            config.cycles[0].max_turns,
+            Some(25),
+            "--set wins over profile"
+        );
+
+        let default = FlowConfig::parse_with_overrides(&toml, None, &[]).unwrap();
+        assert_eq!(default.global.summary_interval, 5);
+        assert_eq!(default.cycles[0].max_turns, None);
+    }
+
+    #[test]
+    fn test_parse_accepts_config_with_profiles() {
+        let toml = format!("{OVERRIDE_CONFIG}\n[profile.ci.global]\nsummary_interval = 1\n");
+        assert!(FlowConfig::parse(&toml).is_ok());
+    }
+
+    #[test]
+    fn test_parse_with_no_overrides_matches_parse() {
+        let plain = FlowConfig::parse(OVERRIDE_CONFIG).unwrap();
+        let merged = FlowConfig::parse_with_overrides(OVERRIDE_CONFIG, None, &[]).unwrap();
+        assert_eq!(plain, merged);
+    }
+
     // --- global.vars config field tests ---
 
     #[test]
@@ -1650,4 +5170,107 @@ prompt = "Code"
         // vars is optional — just verify the config parses
         let _ = &config.global.vars;
     }
+
+    // --- pricing config tests ---
+
+    #[test]
+    fn test_pricing_table_parses() {
+        let toml = r#"
+[global]
+permissions = []
+
+[pricing.sonnet]
+input = 3.0
+output = 15.0
+cache_read = 0.3
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let sonnet = config.pricing["sonnet"];
+        assert!((sonnet.output - 15.0).abs() < f64::EPSILON);
+        assert!(sonnet.cache_write.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_pricing_rejects_negative_price() {
+        let toml = r#"
+[global]
+permissions = []
+
+[pricing.opus]
+input = -1.0
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("pricing.opus.input"), "{err}");
+    }
+
+    // --- display config tests ---
+
+    #[test]
+    fn test_display_section_parses() {
+        let toml = r##"
+[global]
+permissions = []
+
+[display]
+theme = "light"
+timestamps = true
+glyphs = "ascii"
+
+[display.colors]
+tool = "bright blue"
+warning = "#aa5500"
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"##;
+        let config = FlowConfig::parse(toml).unwrap();
+        let display = config.display.unwrap();
+        assert_eq!(display.theme, ThemeName::Light);
+        assert_eq!(display.glyphs, GlyphSet::Ascii);
+        assert!(display.timestamps);
+        assert_eq!(display.colors.tool.as_deref(), Some("bright blue"));
+        assert_eq!(display.colors.header, None);
+    }
+
+    #[test]
+    fn test_display_defaults() {
+        let display: DisplayConfig = toml::from_str("").unwrap();
+        assert_eq!(display.theme, ThemeName::Dark);
+        assert_eq!(display.glyphs, GlyphSet::Unicode);
+        assert!(!display.timestamps);
+    }
+
+    #[test]
+    fn test_display_rejects_unknown_color() {
+        let toml = r#"
+[global]
+permissions = []
+
+[display.colors]
+error = "crimson"
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+        let err = FlowConfig::parse(toml).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("display.colors.error: unknown color 'crimson'"),
+            "{err}"
+        );
+    }
 }
@@ -4,6 +4,7 @@
 //! This context block is prepended to the cycle's prompt so Claude knows what
 //! happened in previous iterations.
 
+use crate::cycle::compaction::{contiguous, covered, Digest};
 use crate::cycle::config::ContextMode;
 use crate::log::jsonl::CycleOutcome;
 
@@ -12,14 +13,66 @@ use crate::log::jsonl::CycleOutcome;
 /// Returns `None` when `mode` is `ContextMode::None`, meaning no context
 /// should be added to the prompt. Returns `Some(text)` for `Full` and
 /// `Summaries` modes, even if the log is empty (in which case the block
-/// indicates no history exists yet).
+/// indicates no history exists yet). `Compact` without digests reads like
+/// `Summaries`.
 #[must_use]
 pub fn build_context(mode: &ContextMode, outcomes: &[CycleOutcome]) -> Option<String> {
+    build_context_with_digests(mode, &[], outcomes)
+}
+
+/// The entries a `mode` prompt sees under a `context_window`: the newest
+/// `window` for `full` and `summaries`, all of them otherwise (`compact`
+/// bounds itself with digests).
+#[must_use]
+pub fn windowed<'a>(
+    mode: &ContextMode,
+    window: Option<usize>,
+    outcomes: &'a [CycleOutcome],
+) -> &'a [CycleOutcome] {
+    match (mode, window) {
+        (ContextMode::Full | ContextMode::Summaries, Some(n)) => {
+            &outcomes[outcomes.len().saturating_sub(n)..]
+        }
+        _ => outcomes,
+    }
+}
+
+/// Like [`build_context`], with the digests that stand in for the oldest
+/// entries in `Compact` mode.
+#[must_use]
+pub fn build_context_with_digests(
+    mode: &ContextMode,
+    digests: &[Digest],
+    outcomes: &[CycleOutcome],
+) -> Option<String> {
     match mode {
         ContextMode::None => None,
         ContextMode::Summaries => Some(build_summaries_context(outcomes)),
         ContextMode::Full => Some(build_full_context(outcomes)),
+        ContextMode::Compact => Some(build_compact_context(digests, outcomes)),
+    }
+}
+
+/// Format context as digests of the oldest entries followed by one-line
+/// summaries of the entries they don't cover.
+fn build_compact_context(digests: &[Digest], outcomes: &[CycleOutcome]) -> String {
+    let digests = contiguous(digests);
+    let recent = &outcomes[covered(digests).min(outcomes.len())..];
+    if digests.is_empty() {
+        return build_summaries_context(recent);
+    }
+
+    let mut lines = vec!["## Compacted History".to_string(), String::new()];
+    for digest in digests {
+        lines.push(format!(
+            "### Iterations {}–{}",
+            digest.first_iteration, digest.last_iteration
+        ));
+        lines.push(digest.summary.clone());
+        lines.push(String::new());
     }
+    lines.push(build_summaries_context(recent));
+    lines.join("\n")
 }
 
 /// Format context as a brief summary list — one line per iteration.
@@ -79,6 +132,15 @@ fn build_full_context(outcomes: &[CycleOutcome]) -> String {
     lines.join("\n")
 }
 
+/// Rough token count for `text`, at about four characters per token.
+///
+/// Good enough for sizing prompts against a context window; the real
+/// tokenizer isn't available offline.
+#[must_use]
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
 /// Inject a context block into a prompt string.
 ///
 /// If context is `None`, returns the original prompt unchanged.
@@ -111,6 +173,58 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    // --- build_context: ContextMode::Compact ---
+
+    #[test]
+    fn test_compact_without_digests_matches_summaries() {
+        let outcomes = vec![make_outcome(1, "coding", "Built feature A")];
+        assert_eq!(
+            build_context(&ContextMode::Compact, &outcomes),
+            build_context(&ContextMode::Summaries, &outcomes)
+        );
+    }
+
+    #[test]
+    fn test_compact_replaces_digested_entries() {
+        let outcomes: Vec<_> = (1..=4)
+            .map(|i| make_outcome(i, "coding", &format!("Work {i}")))
+            .collect();
+        let digest = Digest {
+            start: 0,
+            end: 3,
+            first_iteration: 1,
+            last_iteration: 3,
+            summary: "- Built the parser".to_string(),
+            created_at: chrono::Utc::now(),
+        };
+        let result =
+            build_context_with_digests(&ContextMode::Compact, &[digest], &outcomes).unwrap();
+        assert!(
+            result.contains("### Iterations 1–3\n- Built the parser"),
+            "{result}"
+        );
+        assert!(result.contains("Iteration 4 [coding]: Work 4"), "{result}");
+        assert!(
+            !result.contains("Work 2"),
+            "digested entries are not repeated: {result}"
+        );
+    }
+
+    // --- windowed ---
+
+    #[test]
+    fn test_windowed_keeps_newest_entries_for_full_and_summaries() {
+        let outcomes: Vec<_> = (1..=5).map(|i| make_outcome(i, "coding", "Work")).collect();
+        let last_two = windowed(&ContextMode::Summaries, Some(2), &outcomes);
+        assert_eq!(
+            last_two.iter().map(|o| o.iteration).collect::<Vec<_>>(),
+            [4, 5]
+        );
+        assert_eq!(windowed(&ContextMode::Full, Some(10), &outcomes).len(), 5);
+        assert_eq!(windowed(&ContextMode::Full, None, &outcomes).len(), 5);
+        assert_eq!(windowed(&ContextMode::Compact, Some(2), &outcomes).len(), 5);
+    }
+
     // --- build_context: ContextMode::Summaries ---
 
     #[test]
@@ -365,4 +479,14 @@ mod tests {
         let prompt_pos = result.find(prompt).unwrap();
         assert!(prompt_pos > sep_pos, "Prompt should come after separator");
     }
+
+    // --- estimate_tokens ---
+
+    #[test]
+    fn test_estimate_tokens_rounds_up_per_four_chars() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abc"), 1);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("ääää"), 1);
+    }
 }
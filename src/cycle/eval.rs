@@ -0,0 +1,194 @@
+//! Rubric-based output evaluation (`[cycle.eval]`)
+//!
+//! After a cycle with an `eval` block runs, a cheap no-tool Claude Code call
+//! scores the outcome 1–5 against each rubric criterion, from the cycle's
+//! result text, test counts, and diff. Scores are stored in the log entry
+//! (`eval_scores`) so `flow stats` can show quality drifting over time, which
+//! success/failure alone can't.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use anyhow::{Context, Result};
+
+use crate::claude::cli::{build_command_with_options, run_for_result, CommandOptions};
+use crate::cycle::config::EvalConfig;
+
+/// Lowest rubric score
+pub const MIN_SCORE: u8 = 1;
+
+/// Highest rubric score
+pub const MAX_SCORE: u8 = 5;
+
+/// What the evaluator sees of a cycle's outcome
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EvalSubject<'a> {
+    /// Cycle name
+    pub cycle: &'a str,
+    /// Whether the cycle succeeded
+    pub success: bool,
+    /// Final result text
+    pub result_text: &'a str,
+    /// Passing tests observed
+    pub tests_passed: u32,
+    /// Failing tests observed
+    pub tests_failed: u32,
+    /// Working-tree diff the cycle produced (possibly truncated)
+    pub diff: &'a str,
+}
+
+/// Build the prompt asking for one score per criterion.
+#[must_use]
+pub fn build_eval_prompt(criteria: &[String], subject: &EvalSubject<'_>) -> String {
+    let mut rubric = String::new();
+    for criterion in criteria {
+        let _ = writeln!(rubric, "- {criterion}");
+    }
+    let diff = if subject.diff.trim().is_empty() {
+        "(no changes)"
+    } else {
+        subject.diff.trim_end()
+    };
+    format!(
+        r#"You are grading the output of one cycle of an automated coding pipeline.
+
+Cycle: {cycle} ({status}, {passed} tests passed, {failed} failed)
+
+Result:
+---
+{result}
+---
+
+Diff:
+```diff
+{diff}
+```
+
+## Rubric
+{rubric}
+## Instructions
+Score the output against each rubric criterion from {MIN_SCORE} (poor) to {MAX_SCORE} (excellent).
+
+Respond with ONLY a JSON object on a single line, no other text, keyed by the criteria exactly as written:
+{{"scores": {{"<criterion>": <score>, ...}}}}"#,
+        cycle = subject.cycle,
+        status = if subject.success {
+            "succeeded"
+        } else {
+            "failed"
+        },
+        passed = subject.tests_passed,
+        failed = subject.tests_failed,
+        result = subject.result_text.trim(),
+    )
+}
+
+/// Parse the evaluator's scores, keeping only known criteria with in-range
+/// scores. Returns `None` if no criterion was scored.
+#[must_use]
+pub fn parse_eval_response(response: &str, criteria: &[String]) -> Option<BTreeMap<String, u8>> {
+    response.lines().find_map(|line| {
+        let trimmed = line.trim();
+        if !(trimmed.starts_with('{') && trimmed.ends_with('}')) {
+            return None;
+        }
+        let value: serde_json::Value = serde_json::from_str(trimmed).ok()?;
+        let scores: BTreeMap<String, u8> = value
+            .get("scores")?
+            .as_object()?
+            .iter()
+            .filter(|(criterion, _)| criteria.contains(criterion))
+            .filter_map(|(criterion, score)| {
+                let score = u8::try_from(score.as_u64()?).ok()?;
+                (MIN_SCORE..=MAX_SCORE)
+                    .contains(&score)
+                    .then(|| (criterion.clone(), score))
+            })
+            .collect();
+        (!scores.is_empty()).then_some(scores)
+    })
+}
+
+/// Score a cycle's outcome against its rubric with Claude Code.
+///
+/// # Errors
+/// Returns an error if Claude Code fails or returns no usable scores.
+pub async fn evaluate(
+    eval: &EvalConfig,
+    subject: &EvalSubject<'_>,
+) -> Result<BTreeMap<String, u8>> {
+    let cmd = build_command_with_options(
+        &build_eval_prompt(&eval.criteria, subject),
+        &[],
+        &CommandOptions {
+            model: eval.model.clone(),
+            ..CommandOptions::default()
+        },
+    );
+    let response = run_for_result(cmd).await?;
+    parse_eval_response(&response, &eval.criteria)
+        .context("Failed to parse rubric scores from Claude response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn criteria() -> Vec<String> {
+        vec![
+            "Tests cover the change".to_string(),
+            "Small diff".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_eval_prompt_lists_rubric_and_outcome() {
+        let subject = EvalSubject {
+            cycle: "coding",
+            success: true,
+            result_text: "Added the parser",
+            tests_passed: 12,
+            tests_failed: 0,
+            diff: "+fn parse() {}\n",
+        };
+        let prompt = build_eval_prompt(&criteria(), &subject);
+        assert!(
+            prompt.contains("Cycle: coding (succeeded, 12 tests passed, 0 failed)"),
+            "{prompt}"
+        );
+        assert!(prompt.contains("Added the parser"), "{prompt}");
+        assert!(prompt.contains("+fn parse() {}"), "{prompt}");
+        assert!(
+            prompt.contains("- Tests cover the change\n- Small diff\n"),
+            "{prompt}"
+        );
+        assert!(
+            prompt.contains("from 1 (poor) to 5 (excellent)"),
+            "{prompt}"
+        );
+    }
+
+    #[test]
+    fn test_parse_eval_response() {
+        let scores = parse_eval_response(
+            "Scores:\n{\"scores\": {\"Tests cover the change\": 4, \"Small diff\": 2}}",
+            &criteria(),
+        )
+        .unwrap();
+        assert_eq!(scores["Tests cover the change"], 4);
+        assert_eq!(scores["Small diff"], 2);
+    }
+
+    #[test]
+    fn test_parse_eval_response_drops_unknown_and_out_of_range() {
+        let scores = parse_eval_response(
+            r#"{"scores": {"Small diff": 9, "Tests cover the change": 3, "Style": 5}}"#,
+            &criteria(),
+        )
+        .unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores["Tests cover the change"], 3);
+        assert!(parse_eval_response(r#"{"scores": {"Small diff": 0}}"#, &criteria()).is_none());
+        assert!(parse_eval_response("Looks good", &criteria()).is_none());
+    }
+}
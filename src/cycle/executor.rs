@@ -4,25 +4,53 @@
 //! building the Claude Code CLI command, and running it as a subprocess.
 
 use anyhow::{Context, Result};
+use colored::Colorize;
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Instant;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command as TokioCommand;
+use tokio_util::sync::CancellationToken;
 
-use crate::claude::stream::{parse_event, StreamAccumulator, StreamEvent};
+use crate::claude::pricing::{costs_diverge, price_for, usage_cost};
+use crate::claude::redact::Redactor;
+use crate::claude::stream::{
+    message_usage, parse_event, StreamAccumulator, StreamEvent, TokenUsage,
+};
+use crate::claude::test_parser::{TestCounts, TestParser};
+use crate::claude::transient::{backoff_secs, detect_transient};
 use crate::claude::{
     cli::{build_command_with_options, CommandOptions},
     permissions::{resolve_permissions, resolve_step_permissions},
+    sandbox::docker_command,
     session::SessionManager,
 };
-use crate::cli::{CycleDisplay, StatusLine};
-use crate::cycle::config::FlowConfig;
-use crate::cycle::context::{build_context, inject_context};
+use crate::cli::{theme, CycleDisplay, Role, StatusLine, StepProgress};
+use crate::cycle::arbiter::{parse_verdict, Verdict};
+use crate::cycle::compaction::{load_digests, Digest};
+use crate::cycle::config::StepRouter;
+use crate::cycle::config::{FlowConfig, SandboxKind};
+use crate::cycle::context::{build_context_with_digests, inject_context, windowed};
+use crate::cycle::eval::{evaluate, EvalSubject};
+use crate::cycle::explain::{command_line, PlannedInvocation, SessionPlan};
+use crate::cycle::lint::{lint_feedback, run_lint, LintReport};
+use crate::cycle::observer::{EventSink, ExecutionObserver};
+use crate::cycle::repeat::check_repeat_until;
 use crate::cycle::router::{determine_next_step, RouteDecision, VisitTracker};
+use crate::cycle::samples::{judge_samples, sample_diff, SampleSummary};
+use crate::cycle::skip::check_skip_if;
+use crate::cycle::snapshot::{working_tree, Snapshot};
+use crate::cycle::success::check_success_when;
 use crate::cycle::template::expand_template;
-use crate::log::jsonl::CycleOutcome;
+use crate::cycle::test_guard::{run_test_command, TestDelta};
+use crate::git::{diff_line_counts, head_sha, truncated_diff};
+use crate::log::jsonl::{CycleOutcome, StepOutcome};
+use crate::log::recording::{RecordedEntry, RecordedInvocation, Recording};
+use crate::queue::with_queued_task;
 
 /// Prepared cycle ready for execution
 #[derive(Debug)]
@@ -33,10 +61,22 @@ pub struct PreparedCycle {
     pub prompt: String,
     /// Resolved permissions (global + cycle-specific, deduplicated)
     pub permissions: Vec<String>,
+    /// Effective turn limit (CLI override, else cycle config)
+    pub max_turns: Option<u32>,
+    /// Effective cost limit in USD (CLI override, else cycle config)
+    pub max_cost_usd: Option<f64>,
+    /// Effective replacement system prompt (cycle, else global)
+    pub system_prompt: Option<String>,
+    /// Effective appended system prompt (global and cycle text combined)
+    pub append_system_prompt: Option<String>,
+    /// Permission mode passed to Claude Code, if configured
+    pub permission_mode: Option<crate::cycle::config::PermissionMode>,
+    /// Effective model (cycle, else global)
+    pub model: Option<String>,
 }
 
 /// Result of executing a cycle
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct CycleResult {
     /// Name of the cycle that was executed
     pub cycle_name: String,
@@ -52,8 +92,16 @@ pub struct CycleResult {
     pub result_text: Option<String>,
     /// Number of conversation turns
     pub num_turns: Option<u32>,
-    /// Total cost in USD
+    /// Total cost in USD (reported by Claude Code, else computed from `[pricing]`)
     pub total_cost_usd: Option<f64>,
+    /// Cost computed from token usage with the `[pricing]` table
+    pub computed_cost_usd: Option<f64>,
+    /// Token usage (summed across steps)
+    pub usage: Option<TokenUsage>,
+    /// Model reported by Claude Code (of the last step)
+    pub model: Option<String>,
+    /// Claude Code CLI version reported by the session (of the last step)
+    pub claude_version: Option<String>,
     /// Number of permission denials during the cycle
     pub permission_denial_count: Option<u32>,
     /// List of denied tool names (e.g., `["Edit", "Bash"]`)
@@ -62,22 +110,450 @@ pub struct CycleResult {
     pub files_changed: Vec<String>,
     /// Total number of tests that passed, parsed from cargo test output in tool results
     pub tests_passed: u32,
+    /// Total number of tests that failed, parsed from cargo test output in tool results
+    pub tests_failed: u32,
+    /// Shell commands executed via the Bash tool, in order
+    pub commands_run: Vec<String>,
+    /// Number of uses of each tool, by tool name
+    pub tools_used: BTreeMap<String, u32>,
+    /// Why a cycle that exited cleanly was still recorded as failed (e.g. unmet `success_when`)
+    pub failure_reason: Option<String>,
+    /// Transient API error (e.g. `"overloaded"`, `"rate limited"`) that made
+    /// the cycle fail; such failures are retried with backoff
+    pub transient_error: Option<String>,
+    /// Steps of a multi-step cycle that finished successfully, in execution order
+    pub completed_steps: Vec<String>,
+    /// Steps of a multi-step cycle passed over because their `skip_if` held
+    pub skipped_steps: Vec<String>,
+    /// Per-step metrics of a multi-step cycle, one per step run, in execution order
+    pub steps: Vec<StepOutcome>,
+    /// Step of a multi-step cycle that failed (`None` on success or for single-step cycles)
+    pub failed_step: Option<String>,
+    /// Whether the working tree was restored after a failure (`rollback_on_failure`)
+    pub rolled_back: bool,
+    /// Sample kept by a multi-sample cycle (1-based; `None` for a single run
+    /// or when no sample succeeded)
+    pub chosen_sample: Option<u32>,
+    /// Attempts made when transient API errors were retried (`None` for a
+    /// single attempt)
+    pub attempts: Option<u32>,
+    /// Verdict found in the result of a cycle that `arbitrates` another
+    pub verdict: Option<Verdict>,
+    /// Rubric scores from the cycle's `eval` block, by criterion
+    pub eval_scores: Option<BTreeMap<String, u8>>,
+    /// Test counts before and after the cycle, from `[test_guard]`
+    pub test_delta: Option<TestDelta>,
+    /// Lint warnings after the cycle, from `[lint]`
+    pub lint: Option<LintReport>,
+    /// Subagents launched via the `Task` tool (`None` if there were none)
+    pub subagent_count: Option<u32>,
+    /// Cost reported by those subagents, separate from `total_cost_usd`
+    pub subagent_cost_usd: Option<f64>,
+    /// `HEAD` commit when the cycle started (`None` outside a git repository)
+    pub git_sha_before: Option<String>,
+    /// `HEAD` commit when the cycle finished, after any rollback
+    pub git_sha_after: Option<String>,
+    /// Lines added by the cycle, diffed against the pre-cycle working tree
+    /// (`None` outside a git repository)
+    pub lines_added: Option<u32>,
+    /// Lines removed by the cycle, diffed against the pre-cycle working tree
+    pub lines_removed: Option<u32>,
+    /// Hash of the fully-resolved prompt (combined across steps)
+    pub prompt_hash: Option<String>,
+    /// Hash of the resolved permission set (combined across steps)
+    pub permissions_hash: Option<String>,
+}
+
+/// Characters of the cycle's diff shown to the rubric evaluator
+const MAX_EVAL_DIFF_CHARS: usize = 8000;
+
+/// Arguments shared by every run of a cycle within one `execute_with_display`
+struct Attempt<'a> {
+    cycle_name: &'a str,
+    circuit_breaker_threshold: u32,
+    log_entries: &'a [CycleOutcome],
+    display: &'a CycleDisplay,
+    iteration_context: Option<(u32, u32)>,
+    template_vars: &'a std::collections::HashMap<String, String>,
+}
+
+/// A value computed for a working tree, reused until the tree changes
+/// (e.g. a cycle's "before" check is the previous cycle's "after").
+struct TreeCache<T>(Mutex<Option<(String, T)>>);
+
+impl<T: Clone> TreeCache<T> {
+    const fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    /// The value cached for `tree`, or else `compute`'s, cached for `tree`
+    /// when it is known.
+    async fn get_or_compute(
+        &self,
+        tree: Option<String>,
+        compute: impl std::future::Future<Output = Option<T>>,
+    ) -> Option<T> {
+        if let Some(ref tree) = tree {
+            let cached = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some((cached_tree, value)) = cached.as_ref() {
+                if cached_tree == tree {
+                    return Some(value.clone());
+                }
+            }
+        }
+        let value = compute.await?;
+        if let Some(tree) = tree {
+            *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some((tree, value.clone()));
+        }
+        Some(value)
+    }
 }
 
 /// Executes cycles by invoking Claude Code CLI
 pub struct CycleExecutor {
     config: FlowConfig,
-    shutdown: Arc<AtomicBool>,
+    /// Cancelled on shutdown (e.g. Ctrl+C); kills the running step
+    cancel: CancellationToken,
+    verbose: bool,
+    debug_stream_dir: Option<PathBuf>,
+    max_turns_override: Option<u32>,
+    max_cost_usd_override: Option<f64>,
+    sessions_path: Option<PathBuf>,
+    /// Digests of compacted history (`.flow/digests.jsonl`) for `context = "compact"`
+    digests_path: Option<PathBuf>,
+    /// `(cycle, step)` to start the next execution of `cycle` from; consumed on use
+    start_step: Mutex<Option<(String, String)>>,
+    /// Paths that snapshots neither capture nor restore (the log directory)
+    snapshot_exclude: Vec<String>,
+    /// Render stream output and the status line to stderr (default: true)
+    display: bool,
+    /// Receives stream events, step transitions, and gate decisions
+    observer: Option<Arc<dyn ExecutionObserver>>,
+    /// Captures Claude Code output (`--record`) or stands in for it (`--replay`)
+    recording: Option<Arc<Recording>>,
+    /// Last `[test_guard]` counts and the working tree they were taken on
+    test_counts: TreeCache<TestCounts>,
+    /// Last `[lint]` warnings and the working tree they were found on
+    lint_warnings: TreeCache<Vec<String>>,
+    /// Repository root every command runs in (default: the current directory)
+    project_dir: PathBuf,
 }
 
 impl CycleExecutor {
-    /// Create a new executor with the given configuration and shutdown flag.
+    /// Create a new executor with the given configuration and cancellation token.
+    ///
+    /// The token is watched while a step runs; once cancelled, the child
+    /// process is killed, no further steps start, and execution returns promptly.
+    #[must_use]
+    pub fn new(config: FlowConfig, cancel: CancellationToken) -> Self {
+        Self {
+            config,
+            cancel,
+            verbose: false,
+            debug_stream_dir: None,
+            max_turns_override: None,
+            max_cost_usd_override: None,
+            sessions_path: None,
+            digests_path: None,
+            start_step: Mutex::new(None),
+            snapshot_exclude: Vec::new(),
+            display: true,
+            observer: None,
+            recording: None,
+            test_counts: TreeCache::new(),
+            lint_warnings: TreeCache::new(),
+            project_dir: std::env::current_dir().unwrap_or_default(),
+        }
+    }
+
+    /// Run Claude Code and every check command in `dir`, and snapshot and
+    /// diff the working tree there.
+    #[must_use]
+    pub fn with_project_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.project_dir = dir.into();
+        self
+    }
+
+    /// Enable verbose display output (e.g., extended-thinking blocks).
+    #[must_use]
+    pub const fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Render stream output and the status line to stderr. Embedders that
+    /// consume events through an observer can turn this off.
+    #[must_use]
+    pub const fn with_display(mut self, display: bool) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// Report stream events, step transitions, and gate decisions to `observer`.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Option<Arc<dyn ExecutionObserver>>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Record every invocation's output and LLM routing decision to
+    /// `recording`, or, for a recording opened for replay, read them from it
+    /// instead of running Claude Code.
+    #[must_use]
+    pub fn with_recording(mut self, recording: Option<Arc<Recording>>) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    /// Whether a recording stands in for Claude Code (`--replay`).
+    fn replaying(&self) -> bool {
+        self.recording.as_deref().is_some_and(Recording::is_replay)
+    }
+
+    /// Evaluate a command-backed condition, recording its outcome — or, when
+    /// replaying, taking the outcome from the recording without running it.
+    async fn condition(
+        &self,
+        cycle: &str,
+        step: Option<&str>,
+        check: &str,
+        evaluate: impl std::future::Future<Output = Option<String>>,
+    ) -> Result<Option<String>> {
+        let recording = self.recording.as_deref();
+        if let Some(replay) = recording.filter(|r| r.is_replay()) {
+            return replay.take_condition(cycle, step, check);
+        }
+        let outcome = evaluate.await;
+        if let Some(recording) = recording {
+            recording.record(&RecordedEntry::Condition {
+                cycle: cycle.to_string(),
+                step: step.map(str::to_string),
+                check: check.to_string(),
+                outcome: outcome.clone(),
+            });
+        }
+        Ok(outcome)
+    }
+
+    /// The observer events are reported to, if any.
+    fn observer(&self) -> Option<&dyn ExecutionObserver> {
+        self.observer.as_deref()
+    }
+
+    /// Status line for one invocation, hidden when display is off.
+    fn status_line(
+        &self,
+        label: &str,
+        iteration_context: Option<(u32, u32)>,
+        budget_usd: Option<f64>,
+    ) -> StatusLine {
+        match iteration_context {
+            Some((c, m)) => StatusLine::with_iteration(label, c, m),
+            None => StatusLine::new(label),
+        }
+        .with_budget(budget_usd)
+        .with_pricing(&self.config.pricing)
+        .with_hidden(!self.display)
+    }
+
+    /// Tee every raw stdout line from Claude Code into a log file under `dir`
+    /// (one file per cycle or step invocation), before it is parsed.
+    #[must_use]
+    pub fn with_debug_stream(mut self, dir: Option<PathBuf>) -> Self {
+        self.debug_stream_dir = dir;
+        self
+    }
+
+    /// Persist sessions of `persist = true` steps to this file (`.flow/sessions.json`).
+    ///
+    /// Without it, every session lasts only for one cycle execution.
+    #[must_use]
+    pub fn with_sessions_path(mut self, path: Option<PathBuf>) -> Self {
+        self.sessions_path = path;
+        self
+    }
+
+    /// Read compacted-history digests from this file (`.flow/digests.jsonl`)
+    /// for prompts with `context = "compact"`.
+    ///
+    /// Without it, compact context lists every entry like `summaries`.
+    #[must_use]
+    pub fn with_digests_path(mut self, path: Option<PathBuf>) -> Self {
+        self.digests_path = path;
+        self
+    }
+
+    /// Leave these paths (e.g. `.flow`) alone when rolling back a failed cycle.
+    #[must_use]
+    pub fn with_snapshot_exclude(mut self, paths: Vec<String>) -> Self {
+        self.snapshot_exclude = paths;
+        self
+    }
+
+    /// Start the next execution of multi-step `cycle` at `step`, skipping the
+    /// steps before it (e.g. to resume after a failed step).
+    ///
+    /// Applies once; later executions of the cycle start from the first step.
+    /// Earlier steps' sessions are only available if they were persisted.
+    #[must_use]
+    pub fn with_start_step(self, cycle: &str, step: &str) -> Self {
+        *self
+            .start_step
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some((cycle.to_string(), step.to_string()));
+        self
+    }
+
+    /// Take the pending start step if it belongs to `cycle_name`.
+    fn take_start_step(&self, cycle_name: &str) -> Option<String> {
+        let mut pending = self
+            .start_step
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if pending
+            .as_ref()
+            .is_some_and(|(cycle, _)| cycle == cycle_name)
+        {
+            pending.take().map(|(_, step)| step)
+        } else {
+            None
+        }
+    }
+
+    /// Snapshot the working tree if the cycle rolls back on failure (never
+    /// when replaying, which leaves the tree alone).
     ///
-    /// The shutdown flag is checked during stream reading; when set, the child
-    /// process is killed and execution stops promptly.
+    /// A failed snapshot is reported and the cycle runs without rollback.
+    fn snapshot_before(&self, cycle: &crate::cycle::config::CycleConfig) -> Option<Snapshot> {
+        if !cycle.rollback_on_failure || self.replaying() {
+            return None;
+        }
+        Snapshot::capture(&self.project_dir, &self.snapshot_exclude)
+            .map_err(|e| {
+                eprintln!(
+                    "Warning: cannot snapshot for '{}' rollback: {e:#}",
+                    cycle.name
+                );
+            })
+            .ok()
+    }
+
+    /// Lines added and removed in the working tree since `tree_before` was captured.
+    fn lines_changed_since(&self, root: &std::path::Path, tree_before: &str) -> Option<(u32, u32)> {
+        let tree_after = working_tree(root, &self.snapshot_exclude).ok()?;
+        diff_line_counts(root, tree_before, &tree_after).ok()
+    }
+
+    /// Set a Claude Code command to run in the project directory, wrapped in
+    /// the cycle's sandbox if it has one.
+    fn sandboxed(
+        &self,
+        cycle_name: &str,
+        mut cmd: std::process::Command,
+    ) -> Result<std::process::Command> {
+        cmd.current_dir(&self.project_dir);
+        let cycle = self
+            .config
+            .get_cycle(cycle_name)
+            .with_context(|| format!("Unknown cycle: '{cycle_name}'"))?;
+        let Some(SandboxKind::Docker) = cycle.sandbox else {
+            return Ok(cmd);
+        };
+        let image = self
+            .config
+            .sandbox_image(cycle)
+            .with_context(|| format!("Cycle '{cycle_name}' sets sandbox but no image"))?;
+        let settings = self.config.sandbox.clone().unwrap_or_default();
+        // Docker needs an absolute path to mount
+        let workdir = std::fs::canonicalize(&self.project_dir).with_context(|| {
+            format!(
+                "Failed to resolve project directory {}",
+                self.project_dir.display()
+            )
+        })?;
+        let mut docker = docker_command(&cmd, image, &settings, &workdir);
+        docker.current_dir(&self.project_dir);
+        Ok(docker)
+    }
+
+    /// Index of the step a multi-step cycle execution starts at (0 unless
+    /// `with_start_step` targeted this cycle).
+    fn start_step_index(&self, cycle: &crate::cycle::config::CycleConfig) -> Result<usize> {
+        let Some(step_name) = self.take_start_step(&cycle.name) else {
+            return Ok(0);
+        };
+        cycle
+            .steps
+            .iter()
+            .position(|s| s.name == step_name)
+            .with_context(|| format!("Unknown step '{step_name}' in cycle '{}'", cycle.name))
+    }
+
+    /// Session manager for one cycle execution, persisting `persist = true`
+    /// step sessions when a sessions file is configured.
+    fn session_manager(&self, cycle: &crate::cycle::config::CycleConfig) -> Result<SessionManager> {
+        let Some(path) = &self.sessions_path else {
+            return Ok(SessionManager::new());
+        };
+        SessionManager::with_store(
+            path.clone(),
+            &cycle.name,
+            cycle
+                .steps
+                .iter()
+                .filter(|s| s.persist)
+                .filter_map(|s| s.session.clone()),
+        )
+    }
+
+    /// Override `max_turns` / `max_cost_usd` for every cycle and step in this run.
+    ///
+    /// `None` leaves the configured value in place; `Some` takes precedence over
+    /// both cycle- and step-level settings.
     #[must_use]
-    pub const fn new(config: FlowConfig, shutdown: Arc<AtomicBool>) -> Self {
-        Self { config, shutdown }
+    pub const fn with_limit_overrides(
+        mut self,
+        max_turns: Option<u32>,
+        max_cost_usd: Option<f64>,
+    ) -> Self {
+        self.max_turns_override = max_turns;
+        self.max_cost_usd_override = max_cost_usd;
+        self
+    }
+
+    /// Effective limits for a cycle or step, with CLI overrides applied on top.
+    fn effective_limits(
+        &self,
+        cycle: &crate::cycle::config::CycleConfig,
+        step: Option<&crate::cycle::config::StepConfig>,
+    ) -> (Option<u32>, Option<f64>) {
+        let (max_turns, max_cost_usd) = resolve_limits(cycle, step);
+        (
+            self.max_turns_override.or(max_turns),
+            self.max_cost_usd_override.or(max_cost_usd),
+        )
+    }
+
+    /// Path of the raw stream log for one invocation, if debug streaming is enabled.
+    fn debug_log_path(&self, label: &str) -> Option<PathBuf> {
+        self.debug_stream_dir
+            .as_ref()
+            .map(|dir| debug_log_path(dir, label, chrono::Utc::now()))
+    }
+
+    /// Configuration the executor runs cycles from
+    #[must_use]
+    pub const fn config(&self) -> &FlowConfig {
+        &self.config
+    }
+
+    /// Compacted-history digests, loaded only when `cycle` uses compact context.
+    /// An unreadable digest file just means no digests.
+    fn digests_for(&self, cycle: &crate::cycle::config::CycleConfig) -> Vec<Digest> {
+        match &self.digests_path {
+            Some(path) if cycle.uses_compaction() => load_digests(path).unwrap_or_default(),
+            _ => Vec::new(),
+        }
     }
 
     /// Prepare a cycle for execution with an empty log context.
@@ -104,16 +580,182 @@ impl CycleExecutor {
             .with_context(|| format!("Unknown cycle: '{cycle_name}'"))?;
 
         let permissions = resolve_permissions(&self.config.global, cycle);
-        let context = build_context(&cycle.context, log_entries);
-        let prompt = inject_context(&cycle.prompt, context);
+        let digests = self.digests_for(cycle);
+        let history = windowed(&cycle.context, cycle.context_window, log_entries);
+        let context = build_context_with_digests(&cycle.context, &digests, history);
+        let feedback = self.lint_feedback_for(cycle_name, log_entries);
+        let prompt = inject_context(&inject_context(&cycle.prompt, feedback), context);
+        let (max_turns, max_cost_usd) = self.effective_limits(cycle, None);
+        let (system_prompt, append_system_prompt) = cycle.system_prompts(&self.config.global);
 
         Ok(PreparedCycle {
             cycle_name: cycle_name.to_string(),
             prompt,
             permissions,
+            max_turns,
+            max_cost_usd,
+            system_prompt,
+            append_system_prompt,
+            permission_mode: cycle.permission_mode,
+            model: cycle.model(&self.config.global).map(str::to_string),
         })
     }
 
+    /// The Claude Code command for a prepared single-step cycle.
+    fn single_step_command(&self, prepared: &PreparedCycle) -> Result<std::process::Command> {
+        self.sandboxed(
+            &prepared.cycle_name,
+            build_command_with_options(
+                &prepared.prompt,
+                &prepared.permissions,
+                &CommandOptions {
+                    max_turns: prepared.max_turns,
+                    max_cost_usd: prepared.max_cost_usd,
+                    system_prompt: prepared.system_prompt.clone(),
+                    append_system_prompt: prepared.append_system_prompt.clone(),
+                    permission_mode: prepared.permission_mode,
+                    model: prepared.model.clone(),
+                    ..Default::default()
+                },
+            ),
+        )
+    }
+
+    /// The Claude Code command for one step of a multi-step cycle.
+    fn step_command(
+        &self,
+        cycle: &crate::cycle::config::CycleConfig,
+        step: &crate::cycle::config::StepConfig,
+        prompt: &str,
+        permissions: &[String],
+        resume_args: Vec<String>,
+    ) -> Result<std::process::Command> {
+        let (max_turns, max_cost_usd) = self.effective_limits(cycle, Some(step));
+        let (system_prompt, append_system_prompt) = cycle.system_prompts(&self.config.global);
+        self.sandboxed(
+            &cycle.name,
+            build_command_with_options(
+                prompt,
+                permissions,
+                &CommandOptions {
+                    resume_args,
+                    max_turns,
+                    max_cost_usd,
+                    system_prompt,
+                    append_system_prompt,
+                    permission_mode: step.permission_mode.or(cycle.permission_mode),
+                    model: cycle.model(&self.config.global).map(str::to_string),
+                },
+            ),
+        )
+    }
+
+    /// Plan a cycle's Claude Code invocations without running them
+    /// (`flow explain`): one for a single-step cycle, one per step, in
+    /// declaration order, for a multi-step cycle.
+    ///
+    /// Prompts are resolved as a run would resolve them from `log_entries`
+    /// and `template_vars`. A step continuing a session an earlier step
+    /// starts gets a `<session:TAG>` placeholder in place of the ID it would
+    /// resume.
+    ///
+    /// # Errors
+    /// Returns an error if the cycle doesn't exist, its sandbox has no image,
+    /// or the sessions file can't be read.
+    pub fn explain(
+        &self,
+        cycle_name: &str,
+        log_entries: &[CycleOutcome],
+        template_vars: &std::collections::HashMap<String, String>,
+    ) -> Result<Vec<PlannedInvocation>> {
+        let cycle = self
+            .config
+            .get_cycle(cycle_name)
+            .with_context(|| format!("Unknown cycle: '{cycle_name}'"))?;
+        if !cycle.is_multi_step() {
+            let mut prepared = self.prepare_with_context(cycle_name, log_entries)?;
+            prepared.prompt = expand_template(
+                &with_queued_task(&prepared.prompt, template_vars),
+                template_vars,
+            );
+            let command = command_line(&self.single_step_command(&prepared)?);
+            return Ok(vec![PlannedInvocation {
+                step: None,
+                prompt: prepared.prompt,
+                permissions: prepared.permissions,
+                session: SessionPlan::New,
+                max_turns: prepared.max_turns,
+                max_cost_usd: prepared.max_cost_usd,
+                max_tokens: cycle.max_tokens,
+                model: prepared.model,
+                command,
+            }]);
+        }
+
+        let sessions = self.session_manager(cycle)?;
+        let digests = self.digests_for(cycle);
+        let lint_feedback = self.lint_feedback_for(cycle_name, log_entries);
+        let mut started = std::collections::HashSet::new();
+        cycle
+            .steps
+            .iter()
+            .map(|step| {
+                let prompt = step_prompt(
+                    cycle,
+                    step,
+                    template_vars,
+                    &digests,
+                    log_entries,
+                    lint_feedback.clone(),
+                );
+                let permissions = resolve_step_permissions(&self.config.global, cycle, step);
+                let session = step.session.clone().map_or(SessionPlan::New, |tag| {
+                    if step.fresh_session {
+                        SessionPlan::Fresh(tag)
+                    } else if started.contains(&tag) {
+                        SessionPlan::Continue(tag)
+                    } else if let Some(id) = sessions.get_session_id(&tag) {
+                        SessionPlan::Resume {
+                            session_id: id.to_string(),
+                            tag,
+                        }
+                    } else {
+                        SessionPlan::Start(tag)
+                    }
+                });
+                let resume_args = match &session {
+                    SessionPlan::Continue(tag) => {
+                        vec!["--resume".to_string(), format!("<session:{tag}>")]
+                    }
+                    SessionPlan::Resume { session_id, .. } => {
+                        vec!["--resume".to_string(), session_id.clone()]
+                    }
+                    _ => Vec::new(),
+                };
+                started.extend(step.session.clone());
+                let (max_turns, max_cost_usd) = self.effective_limits(cycle, Some(step));
+                let command = command_line(&self.step_command(
+                    cycle,
+                    step,
+                    &prompt,
+                    &permissions,
+                    resume_args,
+                )?);
+                Ok(PlannedInvocation {
+                    step: Some(step.name.clone()),
+                    prompt,
+                    permissions,
+                    session,
+                    max_turns,
+                    max_cost_usd,
+                    max_tokens: step.max_tokens.or(cycle.max_tokens),
+                    model: cycle.model(&self.config.global).map(str::to_string),
+                    command,
+                })
+            })
+            .collect()
+    }
+
     /// Execute a cycle with rich display and stream-JSON parsing.
     ///
     /// For single-step cycles, executes the cycle's top-level prompt directly.
@@ -138,29 +780,574 @@ impl CycleExecutor {
             .get_cycle(cycle_name)
             .with_context(|| format!("Unknown cycle: '{cycle_name}'"))?;
 
-        let display = CycleDisplay::new(cycle_name);
+        let display = CycleDisplay::new(cycle_name)
+            .with_verbose(self.verbose)
+            .with_quiet(!self.display);
         display.print_header();
+        let root = self.project_dir.clone();
+        let git_sha_before = head_sha(&root);
+        let tree_before = working_tree(&root, &self.snapshot_exclude).ok();
+        let snapshot = self.snapshot_before(cycle);
+        let guard = self.test_guard_for(cycle_name);
+        let tests_before = match guard {
+            Some(guard) => self.guarded_test_counts(&guard.command, &root).await,
+            None => None,
+        };
+        let lint = self.lint_for(cycle_name);
+        let lint_before = match lint {
+            Some(lint) => self.current_lint_warnings(lint, &root).await,
+            None => None,
+        };
 
-        if cycle.is_multi_step() {
+        let attempt = Attempt {
+            cycle_name,
+            circuit_breaker_threshold,
+            log_entries,
+            display: &display,
+            iteration_context,
+            template_vars,
+        };
+        let mut result = self
+            .run_with_retries(&attempt, cycle, snapshot.as_ref(), &root)
+            .await?;
+
+        if let (Some(guard), Some(before)) = (guard, tests_before) {
+            self.check_test_guard(guard, before, &root, &mut result)
+                .await;
+        }
+        if let (false, Some(snapshot)) = (result.success, snapshot) {
+            result.rolled_back = rollback(&snapshot, cycle_name);
+        }
+        if cycle.arbitrates.is_some() {
+            result.verdict = result.result_text.as_deref().and_then(parse_verdict);
+        }
+        result.git_sha_before = git_sha_before;
+        result.git_sha_after = head_sha(&root);
+        if let Some((added, removed)) = tree_before
+            .as_deref()
+            .and_then(|before| self.lines_changed_since(&root, before))
+        {
+            result.lines_added = Some(added);
+            result.lines_removed = Some(removed);
+        }
+        if let (Some(lint), Some(before), false) = (lint, lint_before, self.cancel.is_cancelled()) {
+            if let Some(after) = self.current_lint_warnings(lint, &root).await {
+                let report = LintReport::new(&before, &after);
+                eprintln!(
+                    "  {} {} warning(s), {} new",
+                    theme().paint(Role::Header, "Lint:").bold(),
+                    report.warnings,
+                    report.new_count
+                );
+                result.lint = Some(report);
+            }
+        }
+        if let Some(ref eval) = cycle.eval {
+            result.eval_scores = self
+                .evaluate_outcome(eval, &result, &root, tree_before.as_deref())
+                .await;
+        }
+
+        Ok(result)
+    }
+
+    /// Run the cycle (or its samples), retrying failures caused by transient
+    /// API errors.
+    ///
+    /// Retries wait `global.transient_backoff_secs`, doubling per attempt, for
+    /// at most `global.transient_retries` retries; the wait is skipped when
+    /// replaying and cut short by cancellation. A `rollback_on_failure`
+    /// snapshot is restored before each retry. The result is the last
+    /// attempt's, carrying the spend of all of them and the attempt count.
+    async fn run_with_retries(
+        &self,
+        attempt: &Attempt<'_>,
+        cycle: &crate::cycle::config::CycleConfig,
+        snapshot: Option<&Snapshot>,
+        root: &Path,
+    ) -> Result<CycleResult> {
+        let global = &self.config.global;
+        let mut failed: Vec<CycleResult> = Vec::new();
+        let mut retries = 0;
+        let mut result = loop {
+            let result = match cycle.samples {
+                Some(samples) if samples > 1 => {
+                    self.execute_samples(attempt, samples, root).await?
+                }
+                _ => self.run_attempt(attempt).await?,
+            };
+            let Some(ref error) = result.transient_error else {
+                break result;
+            };
+            if retries >= global.transient_retries || self.cancel.is_cancelled() {
+                break result;
+            }
+            let delay = if self.replaying() {
+                0
+            } else {
+                backoff_secs(global.transient_backoff_secs, retries)
+            };
+            retries += 1;
+            eprintln!(
+                "Cycle '{}' hit a transient API error ({error}); retrying in {delay}s (retry {retries}/{})",
+                attempt.cycle_name, global.transient_retries
+            );
+            tokio::select! {
+                () = tokio::time::sleep(std::time::Duration::from_secs(delay)) => {}
+                () = self.cancel.cancelled() => break result,
+            }
+            failed.push(result);
+            if let Some(snapshot) = snapshot {
+                rollback(snapshot, attempt.cycle_name);
+            }
+        };
+        for earlier in &failed {
+            add_spend(&mut result, earlier);
+        }
+        result.attempts = (retries > 0).then_some(retries + 1);
+        Ok(result)
+    }
+
+    /// Run a cycle once and judge the result: pricing, transient-error
+    /// detection, and `success_when`.
+    async fn run_attempt(&self, attempt: &Attempt<'_>) -> Result<CycleResult> {
+        let cycle = self
+            .config
+            .get_cycle(attempt.cycle_name)
+            .with_context(|| format!("Unknown cycle: '{}'", attempt.cycle_name))?;
+        let mut result = if cycle.is_multi_step() {
             self.execute_steps(
-                cycle_name,
-                circuit_breaker_threshold,
-                log_entries,
-                &display,
-                iteration_context,
-                template_vars,
+                attempt.cycle_name,
+                attempt.circuit_breaker_threshold,
+                attempt.log_entries,
+                attempt.display,
+                attempt.iteration_context,
+                attempt.template_vars,
             )
+            .await?
+        } else {
+            self.execute_single_step(
+                attempt.cycle_name,
+                attempt.circuit_breaker_threshold,
+                attempt.log_entries,
+                attempt.display,
+                attempt.iteration_context,
+                attempt.template_vars,
+            )
+            .await?
+        };
+
+        self.apply_pricing(cycle, &mut result);
+        if !result.success {
+            result.transient_error =
+                detect_transient(&result.stderr, result.result_text.as_deref()).map(str::to_string);
+        }
+        if let (true, Some(criteria)) = (result.success, &cycle.success_when) {
+            let check = check_success_when(criteria, &result, &self.project_dir);
+            let unmet = self
+                .condition(attempt.cycle_name, None, "success_when", check)
+                .await?;
+            if let Some(reason) = unmet {
+                eprintln!(
+                    "  {} {reason}",
+                    theme().paint(Role::Error, "success_when not met:").bold()
+                );
+                result.success = false;
+                result.failure_reason = Some(reason);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Run a cycle `samples` times from the same starting tree and keep the
+    /// best successful sample, as picked by the judge.
+    ///
+    /// Each sample's working tree and sessions file are saved and the
+    /// starting ones put back before the next sample, so no sample sees
+    /// another's edits or resumes its sessions. Cost, tokens, and duration add
+    /// up across samples and the judge. If no sample succeeds, the starting
+    /// tree is restored and the last sample's failure is returned; so it is if
+    /// a tree can't be captured or restored along the way. A replay takes no
+    /// snapshots and leaves the tree alone.
+    async fn execute_samples(
+        &self,
+        attempt: &Attempt<'_>,
+        samples: u32,
+        root: &std::path::Path,
+    ) -> Result<CycleResult> {
+        let base = if self.replaying() {
+            None
+        } else {
+            match Snapshot::capture(root, &self.snapshot_exclude) {
+                Ok(base) => Some(base),
+                Err(e) => {
+                    eprintln!(
+                        "Warning: cannot snapshot for '{}' samples, running once: {e:#}",
+                        attempt.cycle_name
+                    );
+                    return self.run_attempt(attempt).await;
+                }
+            }
+        };
+        let start = SampleState {
+            tree: base,
+            sessions: self.read_sessions(),
+        };
+
+        let mut runs: Vec<(CycleResult, SampleState)> = Vec::new();
+        for number in 1..=samples {
+            if number > 1 {
+                if let Err(e) = self.restore_sample(&start) {
+                    return Ok(self.abandon_samples(attempt.cycle_name, &start, runs, &e));
+                }
+            }
+            eprintln!(
+                "{}",
+                theme()
+                    .paint(Role::Header, &format!(">>> Sample {number}/{samples}"))
+                    .bold()
+            );
+            let result = self.run_attempt(attempt).await?;
+            let captured = match start.tree {
+                Some(_) => Snapshot::capture(root, &self.snapshot_exclude).map(Some),
+                None => Ok(None),
+            };
+            let (tree, failed) = match captured {
+                Ok(tree) => (tree, None),
+                Err(e) => (None, Some(e)),
+            };
+            let sessions = self.read_sessions();
+            runs.push((result, SampleState { tree, sessions }));
+            if let Some(e) = failed {
+                return Ok(self.abandon_samples(attempt.cycle_name, &start, runs, &e));
+            }
+            if self.cancel.is_cancelled() {
+                break;
+            }
+        }
+
+        let successful: Vec<u32> = (1..)
+            .zip(&runs)
+            .filter(|(_, (result, _))| result.success)
+            .map(|(number, _)| number)
+            .collect();
+        let mut judge_cost = None;
+        let chosen = match successful.as_slice() {
+            [] => None,
+            [only] => Some(*only),
+            _ => {
+                let (sample, cost) = self
+                    .judge(
+                        attempt.cycle_name,
+                        root,
+                        start.tree.as_ref(),
+                        &runs,
+                        &successful,
+                    )
+                    .await?;
+                judge_cost = cost;
+                Some(sample)
+            }
+        };
+
+        let index = chosen.map_or(runs.len() - 1, |number| number as usize - 1);
+        let kept = chosen.map_or(&start, |_| &runs[index].1);
+        if let Err(e) = self.restore_sample(kept) {
+            return Ok(self.abandon_samples(attempt.cycle_name, &start, runs, &e));
+        }
+        let (mut result, _) = runs.swap_remove(index);
+        for (other, _) in &runs {
+            add_spend(&mut result, other);
+        }
+        if let Some(cost) = judge_cost {
+            result.total_cost_usd = Some(result.total_cost_usd.unwrap_or(0.0) + cost);
+        }
+        result.chosen_sample = chosen;
+        Ok(result)
+    }
+
+    /// Give up on samples after their working tree couldn't be captured or
+    /// restored: put the starting tree and sessions back and fail the cycle
+    /// with the last sample's result, carrying the spend of all of them.
+    fn abandon_samples(
+        &self,
+        cycle_name: &str,
+        start: &SampleState,
+        runs: Vec<(CycleResult, SampleState)>,
+        error: &anyhow::Error,
+    ) -> CycleResult {
+        eprintln!("Warning: abandoning '{cycle_name}' samples: {error:#}");
+        if let Some(ref tree) = start.tree {
+            rollback(tree, cycle_name);
+        }
+        if let Err(e) = self.write_sessions(start.sessions.as_deref()) {
+            eprintln!("Warning: failed to restore sessions before '{cycle_name}': {e:#}");
+        }
+        let mut runs = runs.into_iter().map(|(result, _)| result);
+        let mut result = runs.next_back().unwrap_or_else(|| CycleResult {
+            cycle_name: cycle_name.to_string(),
+            ..CycleResult::default()
+        });
+        for earlier in runs {
+            add_spend(&mut result, &earlier);
+        }
+        result.success = false;
+        result.transient_error = None;
+        result.failure_reason = Some(format!("samples abandoned: {error:#}"));
+        result
+    }
+
+    /// Put a sample's working tree and sessions file back in place.
+    fn restore_sample(&self, state: &SampleState) -> Result<()> {
+        if let Some(ref tree) = state.tree {
+            tree.restore()?;
+        }
+        self.write_sessions(state.sessions.as_deref())
+    }
+
+    /// Contents of the sessions file, if one is configured and exists.
+    fn read_sessions(&self) -> Option<String> {
+        let path = self.sessions_path.as_ref()?;
+        std::fs::read_to_string(path).ok()
+    }
+
+    /// Replace the sessions file with `contents`, or remove it for `None`.
+    fn write_sessions(&self, contents: Option<&str>) -> Result<()> {
+        let Some(path) = &self.sessions_path else {
+            return Ok(());
+        };
+        let written = contents.map_or_else(
+            || match std::fs::remove_file(path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                removed => removed,
+            },
+            |contents| std::fs::write(path, contents),
+        );
+        written.with_context(|| format!("Failed to restore sessions file '{}'", path.display()))
+    }
+
+    /// Ask the judge which of the `successful` samples to keep, falling back
+    /// to the first one if the judge fails. Also returns what the judge cost.
+    /// The pick is recorded, and taken from the recording when replaying.
+    async fn judge(
+        &self,
+        cycle_name: &str,
+        root: &std::path::Path,
+        base: Option<&Snapshot>,
+        runs: &[(CycleResult, SampleState)],
+        successful: &[u32],
+    ) -> Result<(u32, Option<f64>)> {
+        let recording = self.recording.as_deref();
+        if let Some(replay) = recording.filter(|r| r.is_replay()) {
+            let (sample, reason) = replay.take_judgment(cycle_name)?;
+            print_judgment(sample, &reason);
+            return Ok((sample, None));
+        }
+        let summaries: Vec<SampleSummary> = successful
+            .iter()
+            .map(|&number| {
+                let (result, sample) = &runs[number as usize - 1];
+                let diff = base.zip(sample.tree.as_ref()).and_then(|(base, snapshot)| {
+                    sample_diff(root, base.tree(), snapshot.tree()).ok()
+                });
+                SampleSummary {
+                    number,
+                    result_text: result.result_text.clone().unwrap_or_default(),
+                    diff: diff.unwrap_or_default(),
+                }
+            })
+            .collect();
+        let task = self.config.get_cycle(cycle_name).map_or("", |cycle| {
+            if cycle.prompt.is_empty() {
+                cycle.description.as_str()
+            } else {
+                cycle.prompt.as_str()
+            }
+        });
+        let (sample, reason, cost) = match judge_samples(cycle_name, task, &summaries).await {
+            Ok((verdict, cost)) => {
+                print_judgment(verdict.sample, &verdict.reason);
+                (verdict.sample, verdict.reason, cost)
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: judge failed ({e:#}), keeping sample {}",
+                    successful[0]
+                );
+                (successful[0], format!("judge failed: {e:#}"), None)
+            }
+        };
+        if let Some(recording) = recording {
+            recording.record(&RecordedEntry::Judgment {
+                cycle: cycle_name.to_string(),
+                sample,
+                reason,
+            });
+        }
+        Ok((sample, cost))
+    }
+
+    /// Score a finished cycle against its rubric. Skipped when replaying or
+    /// cancelled; a failed evaluation is reported and leaves no scores.
+    async fn evaluate_outcome(
+        &self,
+        eval: &crate::cycle::config::EvalConfig,
+        result: &CycleResult,
+        root: &Path,
+        tree_before: Option<&str>,
+    ) -> Option<BTreeMap<String, u8>> {
+        if self.replaying() || self.cancel.is_cancelled() {
+            return None;
+        }
+        let diff = tree_before
+            .and_then(|before| {
+                let after = working_tree(root, &self.snapshot_exclude).ok()?;
+                truncated_diff(root, before, &after, MAX_EVAL_DIFF_CHARS).ok()
+            })
+            .unwrap_or_default();
+        let subject = EvalSubject {
+            cycle: &result.cycle_name,
+            success: result.success,
+            result_text: result.result_text.as_deref().unwrap_or_default(),
+            tests_passed: result.tests_passed,
+            tests_failed: result.tests_failed,
+            diff: &diff,
+        };
+        match evaluate(eval, &subject).await {
+            Ok(scores) => {
+                let summary: Vec<String> = scores
+                    .iter()
+                    .map(|(criterion, score)| format!("{criterion} {score}/5"))
+                    .collect();
+                eprintln!(
+                    "  {} {}",
+                    theme().paint(Role::Header, "Eval:").bold(),
+                    summary.join(" · ")
+                );
+                Some(scores)
+            }
+            Err(e) => {
+                eprintln!("Warning: rubric evaluation failed: {e:#}");
+                None
+            }
+        }
+    }
+
+    /// The `[test_guard]` section if it guards `cycle_name` (never when replaying).
+    fn test_guard_for(&self, cycle_name: &str) -> Option<&crate::cycle::config::TestGuardConfig> {
+        self.config
+            .test_guard
+            .as_ref()
+            .filter(|guard| !self.replaying() && guard.guards(cycle_name))
+    }
+
+    /// Test counts for the current working tree, reusing the last run's counts
+    /// when the tree hasn't changed since (e.g. the previous cycle's "after").
+    async fn guarded_test_counts(&self, command: &str, root: &Path) -> Option<TestCounts> {
+        let tree = working_tree(root, &self.snapshot_exclude).ok();
+        self.test_counts
+            .get_or_compute(tree, async {
+                let parser = TestParser::from_config(self.config.test_parser.as_ref()).ok()?;
+                run_test_command(command, &parser, &self.project_dir).await
+            })
             .await
+    }
+
+    /// Run the tests again after a guarded cycle, record the delta, and fail
+    /// a successful cycle on regression if `fail_on_regression` is set.
+    async fn check_test_guard(
+        &self,
+        guard: &crate::cycle::config::TestGuardConfig,
+        before: TestCounts,
+        root: &Path,
+        result: &mut CycleResult,
+    ) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        let Some(after) = self.guarded_test_counts(&guard.command, root).await else {
+            return;
+        };
+        let delta = TestDelta::new(before, after);
+        let regressed = delta.regressed();
+        let (role, label) = if regressed {
+            (Role::Error, "Tests regressed:")
         } else {
-            self.execute_single_step(
-                cycle_name,
-                circuit_breaker_threshold,
-                log_entries,
-                &display,
-                iteration_context,
-                template_vars,
-            )
+            (Role::Success, "Tests:")
+        };
+        eprintln!(
+            "  {} {}",
+            theme().paint(role, label).bold(),
+            delta.summary()
+        );
+        if regressed && guard.fail_on_regression && result.success {
+            result.success = false;
+            result.failure_reason = Some(format!("Test regression: {}", delta.summary()));
+        }
+        result.test_delta = Some(delta);
+    }
+
+    /// The `[lint]` section if it lints `cycle_name` (never when replaying).
+    fn lint_for(&self, cycle_name: &str) -> Option<&crate::cycle::config::LintConfig> {
+        self.config
+            .lint
+            .as_ref()
+            .filter(|lint| !self.replaying() && lint.lints(cycle_name))
+    }
+
+    /// Lint warnings for the current working tree, reusing the last run's
+    /// warnings when the tree hasn't changed since.
+    async fn current_lint_warnings(
+        &self,
+        lint: &crate::cycle::config::LintConfig,
+        root: &Path,
+    ) -> Option<Vec<String>> {
+        let tree = working_tree(root, &self.snapshot_exclude).ok();
+        self.lint_warnings
+            .get_or_compute(tree, async {
+                let pattern = lint
+                    .pattern
+                    .as_deref()
+                    .and_then(|pattern| regex::Regex::new(pattern).ok());
+                run_lint(&lint.command, pattern.as_ref(), &self.project_dir).await
+            })
             .await
+    }
+
+    /// New warnings from the last lint run, for a cycle that `[lint]` covers.
+    fn lint_feedback_for(&self, cycle_name: &str, log_entries: &[CycleOutcome]) -> Option<String> {
+        self.config
+            .lint
+            .as_ref()
+            .filter(|lint| lint.lints(cycle_name))
+            .and_then(|_| lint_feedback(log_entries))
+    }
+
+    /// Price the cycle's token usage with `[pricing]`, filling in a missing
+    /// reported cost and warning when the reported cost disagrees.
+    fn apply_pricing(&self, cycle: &crate::cycle::config::CycleConfig, result: &mut CycleResult) {
+        let Some(usage) = result.usage else {
+            return;
+        };
+        let Some(price) = [result.model.as_deref(), cycle.model(&self.config.global)]
+            .into_iter()
+            .flatten()
+            .find_map(|model| price_for(&self.config.pricing, model))
+        else {
+            return;
+        };
+        let computed = usage_cost(price, &usage);
+        result.computed_cost_usd = Some(computed);
+        match result.total_cost_usd {
+            Some(reported) if reported > 0.0 => {
+                if costs_diverge(reported, computed) {
+                    eprintln!(
+                        "  {} Claude Code reported ${reported:.4}, token usage prices at ${computed:.4}",
+                        theme().paint(Role::Warning, "Cost mismatch:").bold()
+                    );
+                }
+            }
+            _ => result.total_cost_usd = Some(computed),
         }
     }
 
@@ -174,45 +1361,217 @@ impl CycleExecutor {
         iteration_context: Option<(u32, u32)>,
         template_vars: &std::collections::HashMap<String, String>,
     ) -> Result<CycleResult> {
-        let cycle = self
-            .config
-            .get_cycle(cycle_name)
-            .with_context(|| format!("Unknown cycle: '{cycle_name}'"))?;
-        let (max_turns, max_cost_usd) = resolve_limits(cycle, None);
+        let test_parser = TestParser::from_config(self.config.test_parser.as_ref())?;
+        let redactor = Redactor::from_config(self.config.redaction.as_ref())?;
         let mut prepared = self.prepare_with_context(cycle_name, log_entries)?;
-        prepared.prompt = expand_template(&prepared.prompt, template_vars);
-        let cmd = build_command_with_options(
-            &prepared.prompt,
-            &prepared.permissions,
-            &CommandOptions {
-                max_turns,
-                max_cost_usd,
-                ..Default::default()
-            },
+        prepared.prompt = expand_template(
+            &with_queued_task(&prepared.prompt, template_vars),
+            template_vars,
         );
-        let mut status_line = match iteration_context {
-            Some((c, m)) => StatusLine::with_iteration(cycle_name, c, m),
-            None => StatusLine::new(cycle_name),
-        };
+        let max_cost_usd = prepared.max_cost_usd;
+        let max_tokens = self
+            .config
+            .get_cycle(cycle_name)
+            .and_then(|cycle| cycle.max_tokens);
+        let cmd = self.single_step_command(&prepared)?;
+        let mut status_line = self.status_line(cycle_name, iteration_context, max_cost_usd);
 
-        let (accumulator, stderr, exit_code, duration_secs) = run_command_with_display(
-            cmd,
-            display,
-            &mut status_line,
-            circuit_breaker_threshold,
-            &self.shutdown,
-        )
-        .await?;
+        let (accumulator, stderr, exit_code, duration_secs) = self
+            .invoke(
+                cmd,
+                cycle_name,
+                display,
+                &mut status_line,
+                &test_parser,
+                &redactor,
+                StepGuards::new(circuit_breaker_threshold, max_tokens),
+                EventSink::new(self.observer(), cycle_name, None),
+            )
+            .await?;
 
         status_line.clear();
 
-        Ok(build_cycle_result(
+        let mut result = build_cycle_result(
             prepared.cycle_name,
             exit_code,
             stderr,
             duration_secs,
             &accumulator,
-        ))
+        );
+        result.prompt_hash = Some(prompt_hash(&prepared.prompt));
+        result.permissions_hash = Some(permissions_hash(&prepared.permissions));
+        Ok(result)
+    }
+
+    /// Run one cycle or step invocation, or replay its recorded output.
+    ///
+    /// `label` names the debug stream log. When recording, the redacted
+    /// stdout is captured and appended to the recording afterwards.
+    #[allow(clippy::too_many_arguments)]
+    async fn invoke(
+        &self,
+        cmd: std::process::Command,
+        label: &str,
+        display: &CycleDisplay,
+        status_line: &mut StatusLine,
+        test_parser: &TestParser,
+        redactor: &Redactor,
+        guards: StepGuards,
+        sink: EventSink<'_>,
+    ) -> Result<(StreamAccumulator, String, Option<i32>, u64)> {
+        let recording = self.recording.as_deref();
+        if let Some(replay) = recording.filter(|r| r.is_replay()) {
+            let invocation = replay.take_invocation(sink.cycle, sink.step)?;
+            return Ok(replay_with_display(
+                invocation,
+                display,
+                status_line,
+                test_parser,
+                guards,
+                sink,
+            ));
+        }
+        let mut tee = StreamTee::new(self.debug_log_path(label).as_deref(), recording.is_some());
+        let output = run_command_with_display(
+            cmd,
+            display,
+            status_line,
+            test_parser,
+            redactor,
+            &mut tee,
+            guards,
+            &self.cancel,
+            sink,
+        )
+        .await?;
+        if let (Some(recording), Some(stdout)) = (recording, tee.captured) {
+            recording.record(&RecordedEntry::Invocation(RecordedInvocation {
+                cycle: sink.cycle.to_string(),
+                step: sink.step.map(str::to_string),
+                stdout,
+                stderr: output.1.clone(),
+                exit_code: output.2,
+                duration_secs: output.3,
+            }));
+        }
+        Ok(output)
+    }
+
+    /// Index of the first step from `start` on whose `skip_if` doesn't hold,
+    /// recording the skipped ones in `skipped`; `None` if they all hold.
+    ///
+    /// # Errors
+    /// Returns an error if a replayed `skip_if` outcome diverges.
+    async fn first_unskipped(
+        &self,
+        cycle: &crate::cycle::config::CycleConfig,
+        start: usize,
+        skipped: &mut Vec<String>,
+    ) -> Result<Option<usize>> {
+        for (index, step) in cycle.steps.iter().enumerate().skip(start) {
+            let Some(skip_if) = &step.skip_if else {
+                return Ok(Some(index));
+            };
+            let check = check_skip_if(skip_if, &self.project_dir);
+            let skip = self
+                .condition(&cycle.name, Some(&step.name), "skip_if", check)
+                .await?;
+            let Some(reason) = skip else {
+                return Ok(Some(index));
+            };
+            eprintln!("Skipping step '{}': {reason}", step.name);
+            skipped.push(step.name.clone());
+        }
+        Ok(None)
+    }
+
+    /// Decide what follows `cycle.steps[index]`. A step whose `repeat_until`
+    /// is unmet routes back to itself; otherwise its router decides. LLM
+    /// routing decisions are recorded, and taken from the recording when
+    /// replaying.
+    async fn route(
+        &self,
+        cycle: &crate::cycle::config::CycleConfig,
+        index: usize,
+        result_text: &str,
+        exit_code: Option<i32>,
+        visit_tracker: &VisitTracker,
+    ) -> Result<Option<RouteDecision>> {
+        let step = &cycle.steps[index];
+        if let Some(ref repeat_until) = step.repeat_until {
+            let check = check_repeat_until(repeat_until, result_text, &self.project_dir);
+            let unmet = self
+                .condition(&cycle.name, Some(&step.name), "repeat_until", check)
+                .await?;
+            if let Some(unmet) = unmet {
+                eprintln!("Repeating step '{}': {unmet}", step.name);
+                return Ok(Some(RouteDecision::GoTo {
+                    step_name: step.name.clone(),
+                    reason: format!("repeat_until not met: {unmet}"),
+                }));
+            }
+        }
+        let recording = self
+            .recording
+            .as_deref()
+            .filter(|_| matches!(step.router, StepRouter::Llm));
+        if let Some(replay) = recording.filter(|r| r.is_replay()) {
+            let (to, reason) = replay.take_route(&cycle.name, &step.name)?;
+            return Ok(Some(match to {
+                Some(step_name) => RouteDecision::GoTo { step_name, reason },
+                None => RouteDecision::Done { reason },
+            }));
+        }
+        let decision = determine_next_step(
+            step,
+            index,
+            result_text,
+            exit_code,
+            &cycle.steps,
+            visit_tracker,
+        )
+        .await?;
+        if let (Some(recording), Some(decision)) = (recording, &decision) {
+            let (to, reason) = match decision {
+                RouteDecision::GoTo { step_name, reason } => (Some(step_name.clone()), reason),
+                RouteDecision::Done { reason } => (None, reason),
+            };
+            recording.record(&RecordedEntry::Route {
+                cycle: cycle.name.clone(),
+                from: step.name.clone(),
+                to,
+                reason: reason.clone(),
+            });
+        }
+        Ok(decision)
+    }
+
+    /// Record a visit to `step` before running it, returning the visit
+    /// number. Returns `None` if the cycle must stop instead: the run was
+    /// cancelled or the step hit `max_visits`.
+    fn enter_step(
+        &self,
+        visit_tracker: &mut VisitTracker,
+        sink: EventSink<'_>,
+        step: &crate::cycle::config::StepConfig,
+    ) -> Option<u32> {
+        if self.cancel.is_cancelled() {
+            return None;
+        }
+        if visit_tracker.would_exceed(&step.name, step.max_visits) {
+            let reason = format!(
+                "Step '{}' reached max_visits limit ({}), stopping cycle",
+                step.name, step.max_visits
+            );
+            eprintln!("{reason}");
+            sink.gate("max_visits", &reason);
+            return None;
+        }
+        let visit = visit_tracker.record(&step.name);
+        if let Some(observer) = self.observer() {
+            observer.on_step_started(sink.cycle, &step.name, visit);
+        }
+        Some(visit)
     }
 
     /// Execute a multi-step cycle with router-based step navigation.
@@ -223,6 +1582,9 @@ impl CycleExecutor {
     /// - `sequential`: proceed to the next step in TOML order.
     /// - `llm`: invoke Claude to choose the next step based on output.
     ///
+    /// A step with an unmet `repeat_until` runs again before its router is
+    /// consulted; one whose `skip_if` holds is passed over.
+    ///
     /// Visit counts are tracked per step; a step cannot be visited more than
     /// its `max_visits` limit (default 3) to prevent infinite loops.
     ///
@@ -241,55 +1603,63 @@ impl CycleExecutor {
             .get_cycle(cycle_name)
             .with_context(|| format!("Unknown cycle: '{cycle_name}'"))?;
 
-        let context = build_context(&cycle.context, log_entries);
-        let mut session_mgr = SessionManager::new();
+        let test_parser = TestParser::from_config(self.config.test_parser.as_ref())?;
+        let redactor = Redactor::from_config(self.config.redaction.as_ref())?;
+        let mut session_mgr = self.session_manager(cycle)?;
         let mut visit_tracker = VisitTracker::new();
         let mut agg = StepAggregator::new();
-        let mut current_step_index: usize = 0;
+        let mut current_step_index = self.start_step_index(cycle)?;
+        let digests = self.digests_for(cycle);
+        let lint_feedback = self.lint_feedback_for(cycle_name, log_entries);
 
-        loop {
+        while let Some(index) = self
+            .first_unskipped(cycle, current_step_index, &mut agg.skipped_steps)
+            .await?
+        {
+            current_step_index = index;
             let step = &cycle.steps[current_step_index];
-
-            if visit_tracker.would_exceed(&step.name, step.max_visits) {
-                eprintln!(
-                    "Step '{}' reached max_visits limit ({}), stopping cycle",
-                    step.name, step.max_visits
-                );
+            let sink = EventSink::new(self.observer(), cycle_name, Some(&step.name));
+            let Some(visit) = self.enter_step(&mut visit_tracker, sink, step) else {
                 break;
-            }
-            visit_tracker.record(&step.name);
-
-            let step_label = format!("{cycle_name}/{}", step.name);
-            let mut status_line = match iteration_context {
-                Some((c, m)) => StatusLine::with_iteration(&step_label, c, m),
-                None => StatusLine::new(&step_label),
             };
-            // Update step_name for this step's template expansion
-            let mut step_vars = template_vars.clone();
-            step_vars.insert("step_name".to_string(), step.name.clone());
-            let expanded_prompt = expand_template(&step.prompt, &step_vars);
-            let step_prompt = inject_context(&expanded_prompt, context.clone());
-            let permissions = resolve_step_permissions(&self.config.global, cycle, step);
-            let resume_args = session_mgr.resume_args(step.session.as_deref());
-            let (max_turns, max_cost_usd) = resolve_limits(cycle, Some(step));
-            let cmd = build_command_with_options(
-                &step_prompt,
-                &permissions,
-                &CommandOptions {
-                    resume_args,
-                    max_turns,
-                    max_cost_usd,
-                },
-            );
+            let progress = step_progress(cycle, current_step_index, visit);
+            display.print_step_header(progress.clone());
 
-            let (accumulator, stderr, exit_code, duration_secs) = run_command_with_display(
-                cmd,
-                display,
-                &mut status_line,
-                circuit_breaker_threshold,
-                &self.shutdown,
-            )
-            .await?;
+            let step_prompt = step_prompt(
+                cycle,
+                step,
+                template_vars,
+                &digests,
+                log_entries,
+                lint_feedback.clone(),
+            );
+            let permissions = resolve_step_permissions(&self.config.global, cycle, step);
+            agg.prompt_hashes.push(prompt_hash(&step_prompt));
+            agg.permissions_hashes.push(permissions_hash(&permissions));
+            // A fresh session starts a new conversation even if the tag has one
+            let resume_args =
+                session_mgr.resume_args(step.session.as_deref().filter(|_| !step.fresh_session));
+            let (_, max_cost_usd) = self.effective_limits(cycle, Some(step));
+            let mut status_line = self
+                .status_line(cycle_name, iteration_context, max_cost_usd)
+                .with_step(Some(progress));
+            let cmd = self.step_command(cycle, step, &step_prompt, &permissions, resume_args)?;
+
+            let (accumulator, stderr, exit_code, duration_secs) = self
+                .invoke(
+                    cmd,
+                    &format!("{cycle_name}-{}", step.name),
+                    display,
+                    &mut status_line,
+                    &test_parser,
+                    &redactor,
+                    StepGuards::new(
+                        circuit_breaker_threshold,
+                        step.max_tokens.or(cycle.max_tokens),
+                    ),
+                    sink,
+                )
+                .await?;
 
             status_line.clear();
 
@@ -298,32 +1668,36 @@ impl CycleExecutor {
             }
 
             let step_result_text = agg.accumulate(&accumulator, &stderr, exit_code, duration_secs);
+            agg.record_step(step, &accumulator, exit_code, duration_secs);
+            if let Some(observer) = self.observer() {
+                observer.on_step_finished(cycle_name, &step.name, exit_code);
+            }
 
             // Fail-fast: stop if this step failed
             if agg.last_exit_code != Some(0) {
+                agg.failed_step = Some(step.name.clone());
                 break;
             }
+            agg.completed_steps.push(step.name.clone());
 
             // Determine the next step using the router
-            let decision = determine_next_step(
-                step,
-                current_step_index,
-                &step_result_text,
-                &cycle.steps,
-                &visit_tracker,
-            )
-            .await?;
+            let decision = self
+                .route(
+                    cycle,
+                    current_step_index,
+                    &step_result_text,
+                    agg.last_exit_code,
+                    &visit_tracker,
+                )
+                .await?;
+            if let Some(observer) = self.observer() {
+                report_route(observer, cycle_name, &step.name, decision.as_ref());
+            }
 
             match decision {
                 None | Some(RouteDecision::Done { .. }) => break,
                 Some(RouteDecision::GoTo { step_name, reason }) => {
-                    current_step_index = cycle
-                        .steps
-                        .iter()
-                        .position(|s| s.name == step_name)
-                        .with_context(|| {
-                            format!("Router selected unknown step '{step_name}' (reason: {reason})")
-                        })?;
+                    current_step_index = step_index(&cycle.steps, &step_name, &reason)?;
                 }
             }
         }
@@ -332,6 +1706,124 @@ impl CycleExecutor {
     }
 }
 
+/// Position and visit of `cycle.steps[index]`, for the display.
+fn step_progress(
+    cycle: &crate::cycle::config::CycleConfig,
+    index: usize,
+    visit: u32,
+) -> StepProgress {
+    StepProgress {
+        position: index + 1,
+        total: cycle.steps.len(),
+        name: cycle.steps[index].name.clone(),
+        visit,
+    }
+}
+
+/// A step's prompt with templates expanded (`step_name` set) and log context
+/// and lint feedback injected.
+fn step_prompt(
+    cycle: &crate::cycle::config::CycleConfig,
+    step: &crate::cycle::config::StepConfig,
+    template_vars: &std::collections::HashMap<String, String>,
+    digests: &[Digest],
+    log_entries: &[CycleOutcome],
+    lint_feedback: Option<String>,
+) -> String {
+    let mut step_vars = template_vars.clone();
+    step_vars.insert("step_name".to_string(), step.name.clone());
+    let expanded_prompt = expand_template(&with_queued_task(&step.prompt, &step_vars), &step_vars);
+    let mode = cycle.step_context(step);
+    let history = windowed(mode, cycle.context_window, log_entries);
+    let context = build_context_with_digests(mode, digests, history);
+    inject_context(&inject_context(&expanded_prompt, lint_feedback), context)
+}
+
+/// Tell `observer` where the router sent a cycle after step `from`.
+fn report_route(
+    observer: &dyn ExecutionObserver,
+    cycle_name: &str,
+    from: &str,
+    decision: Option<&RouteDecision>,
+) {
+    let (to, reason) = match decision {
+        Some(RouteDecision::GoTo { step_name, reason }) => {
+            (Some(step_name.as_str()), reason.as_str())
+        }
+        Some(RouteDecision::Done { reason }) => (None, reason.as_str()),
+        None => (None, "Last step"),
+    };
+    observer.on_step_routed(cycle_name, from, to, reason);
+}
+
+/// Index of the step a router chose.
+fn step_index(
+    steps: &[crate::cycle::config::StepConfig],
+    step_name: &str,
+    reason: &str,
+) -> Result<usize> {
+    steps
+        .iter()
+        .position(|s| s.name == step_name)
+        .with_context(|| format!("Router selected unknown step '{step_name}' (reason: {reason})"))
+}
+
+/// Report which sample the judge kept and why.
+fn print_judgment(sample: u32, reason: &str) {
+    eprintln!(
+        "  {} sample {sample}: {reason}",
+        theme().paint(Role::Success, "Judge picked").bold()
+    );
+}
+
+/// Working tree and sessions file at one point of a multi-sample cycle
+struct SampleState {
+    /// `None` when replaying, which leaves the tree alone
+    tree: Option<Snapshot>,
+    /// Sessions file contents, `None` if there is no such file
+    sessions: Option<String>,
+}
+
+/// Add the cost, tokens, duration, and subagents of an `earlier` run of the
+/// same cycle to `result`.
+fn add_spend(result: &mut CycleResult, earlier: &CycleResult) {
+    result.duration_secs += earlier.duration_secs;
+    if let Some(usage) = earlier.usage {
+        result
+            .usage
+            .get_or_insert_with(Default::default)
+            .add(&usage);
+    }
+    let add = |total: &mut Option<f64>, more: Option<f64>| {
+        if let Some(more) = more {
+            *total = Some(total.unwrap_or(0.0) + more);
+        }
+    };
+    add(&mut result.total_cost_usd, earlier.total_cost_usd);
+    add(&mut result.computed_cost_usd, earlier.computed_cost_usd);
+    add(&mut result.subagent_cost_usd, earlier.subagent_cost_usd);
+    if let Some(count) = earlier.subagent_count {
+        *result.subagent_count.get_or_insert(0) += count;
+    }
+}
+
+/// Restore a failed cycle's snapshot, reporting the outcome. Returns whether it succeeded.
+fn rollback(snapshot: &Snapshot, cycle_name: &str) -> bool {
+    match snapshot.restore() {
+        Ok(()) => {
+            eprintln!(
+                "  {} restored working tree to its state before '{cycle_name}'",
+                theme().paint(Role::Warning, "Rolled back:").bold()
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("Warning: rollback after '{cycle_name}' failed: {e:#}");
+            false
+        }
+    }
+}
+
 /// Resolve effective limits for a step, falling back to cycle-level values.
 ///
 /// Step values override cycle values (not additive). If neither is set, returns `None`.
@@ -353,9 +1845,28 @@ struct StepAggregator {
     all_denials: Vec<String>,
     all_files_changed: Vec<String>,
     total_tests_passed: u32,
+    total_tests_failed: u32,
+    all_commands_run: Vec<String>,
+    tools_used: BTreeMap<String, u32>,
+    subagent_count: u32,
+    subagent_cost_usd: f64,
     last_result_text: Option<String>,
+    total_usage: TokenUsage,
+    last_model: Option<String>,
+    /// Claude Code version reported by the most recent step
+    last_claude_version: Option<String>,
+    /// Why Flow stopped the failed step early, if it did
+    stop_reason: Option<String>,
     last_exit_code: Option<i32>,
     combined_stderr: String,
+    completed_steps: Vec<String>,
+    skipped_steps: Vec<String>,
+    steps: Vec<StepOutcome>,
+    failed_step: Option<String>,
+    /// Prompt hash of each executed step, in order
+    prompt_hashes: Vec<String>,
+    /// Permissions hash of each executed step, in order
+    permissions_hashes: Vec<String>,
 }
 
 impl StepAggregator {
@@ -368,12 +1879,58 @@ impl StepAggregator {
             all_denials: Vec::new(),
             all_files_changed: Vec::new(),
             total_tests_passed: 0,
+            total_tests_failed: 0,
+            all_commands_run: Vec::new(),
+            tools_used: BTreeMap::new(),
+            subagent_count: 0,
+            subagent_cost_usd: 0.0,
             last_result_text: None,
+            total_usage: TokenUsage {
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 0,
+            },
+            last_model: None,
+            last_claude_version: None,
+            stop_reason: None,
             last_exit_code: None,
             combined_stderr: String::new(),
+            completed_steps: Vec::new(),
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            failed_step: None,
+            prompt_hashes: Vec::new(),
+            permissions_hashes: Vec::new(),
         }
     }
 
+    /// Record one step run's own metrics for the logged outcome.
+    fn record_step(
+        &mut self,
+        step: &crate::cycle::config::StepConfig,
+        accumulator: &StreamAccumulator,
+        exit_code: Option<i32>,
+        duration_secs: u64,
+    ) {
+        let (num_turns, cost_usd) = match &accumulator.result {
+            Some(StreamEvent::Result {
+                num_turns,
+                total_cost_usd,
+                ..
+            }) => (Some(*num_turns), Some(*total_cost_usd)),
+            _ => (None, None),
+        };
+        self.steps.push(StepOutcome {
+            name: step.name.clone(),
+            session: step.session.clone(),
+            duration_secs,
+            num_turns,
+            cost_usd,
+            exit_code,
+        });
+    }
+
     /// Merge one step's results into the aggregate. Returns the step's result text.
     fn accumulate(
         &mut self,
@@ -396,10 +1953,12 @@ impl StepAggregator {
             num_turns,
             total_cost_usd,
             permission_denials,
+            usage,
             ..
         }) = &accumulator.result
         {
             self.last_result_text = Some(result_text.clone());
+            self.total_usage.add(usage);
             self.total_turns = self.total_turns.saturating_add(*num_turns);
             self.total_cost += total_cost_usd;
             self.total_denials = self
@@ -420,7 +1979,28 @@ impl StepAggregator {
         self.total_tests_passed = self
             .total_tests_passed
             .saturating_add(accumulator.tests_passed);
+        self.total_tests_failed = self
+            .total_tests_failed
+            .saturating_add(accumulator.tests_failed);
+        self.all_commands_run
+            .extend(accumulator.commands_run.iter().cloned());
+        for (tool, count) in &accumulator.tools_used {
+            let total = self.tools_used.entry(tool.clone()).or_default();
+            *total = total.saturating_add(*count);
+        }
+        self.subagent_count = self
+            .subagent_count
+            .saturating_add(accumulator.subagent_count);
+        self.subagent_cost_usd += accumulator.subagent_cost_usd;
 
+        if accumulator.model.is_some() {
+            self.last_model.clone_from(&accumulator.model);
+        }
+        if accumulator.claude_version.is_some() {
+            self.last_claude_version
+                .clone_from(&accumulator.claude_version);
+        }
+        self.stop_reason.clone_from(&accumulator.stop_reason);
         self.last_exit_code = exit_code;
 
         step_result_text
@@ -437,14 +2017,72 @@ impl StepAggregator {
             result_text: self.last_result_text,
             num_turns: (self.total_turns > 0).then_some(self.total_turns),
             total_cost_usd: (self.total_cost > 0.0).then_some(self.total_cost),
+            computed_cost_usd: None,
+            usage: (!self.total_usage.is_empty()).then_some(self.total_usage),
+            model: self.last_model,
+            claude_version: self.last_claude_version,
             permission_denial_count: (self.total_denials > 0).then_some(self.total_denials),
             permission_denials: (!self.all_denials.is_empty()).then_some(self.all_denials),
             files_changed: self.all_files_changed,
             tests_passed: self.total_tests_passed,
+            tests_failed: self.total_tests_failed,
+            commands_run: self.all_commands_run,
+            tools_used: self.tools_used,
+            failure_reason: self.stop_reason,
+            completed_steps: self.completed_steps,
+            skipped_steps: self.skipped_steps,
+            failed_step: self.failed_step,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: (self.subagent_count > 0).then_some(self.subagent_count),
+            subagent_cost_usd: (self.subagent_count > 0).then_some(self.subagent_cost_usd),
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: combine_hashes(&self.prompt_hashes),
+            permissions_hash: combine_hashes(&self.permissions_hashes),
+            transient_error: None,
+            steps: self.steps,
         }
     }
 }
 
+/// Short SHA-256 content hash (16 hex digits) for log provenance.
+fn short_hash(content: &str) -> String {
+    let digest = Sha256::digest(content.as_bytes());
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    format!("{:016x}", u64::from_be_bytes(prefix))
+}
+
+/// Hash of a fully-resolved prompt, as sent to Claude Code.
+fn prompt_hash(prompt: &str) -> String {
+    short_hash(prompt)
+}
+
+/// Hash of a permission set; reordering the same permissions doesn't change it.
+fn permissions_hash(permissions: &[String]) -> String {
+    let mut sorted: Vec<&str> = permissions.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+    sorted.dedup();
+    short_hash(&sorted.join("\n"))
+}
+
+/// Combine per-step hashes in execution order; a single hash is kept as is.
+fn combine_hashes(hashes: &[String]) -> Option<String> {
+    match hashes {
+        [] => None,
+        [single] => Some(single.clone()),
+        _ => Some(short_hash(&hashes.join("\n"))),
+    }
+}
+
 /// Build a `CycleResult` from raw subprocess output and accumulated stream data.
 fn build_cycle_result(
     cycle_name: String,
@@ -474,6 +2112,10 @@ fn build_cycle_result(
         ),
         _ => (None, None, None, None, None),
     };
+    let usage = match &accumulator.result {
+        Some(StreamEvent::Result { usage, .. }) if !usage.is_empty() => Some(*usage),
+        _ => None,
+    };
 
     CycleResult {
         cycle_name,
@@ -484,27 +2126,65 @@ fn build_cycle_result(
         result_text,
         num_turns,
         total_cost_usd,
+        computed_cost_usd: None,
+        usage,
+        model: accumulator.model.clone(),
+        claude_version: accumulator.claude_version.clone(),
         permission_denial_count: denial_count,
         permission_denials: denials,
         files_changed: accumulator.files_changed.clone(),
         tests_passed: accumulator.tests_passed,
+        tests_failed: accumulator.tests_failed,
+        commands_run: accumulator.commands_run.clone(),
+        tools_used: accumulator.tools_used.clone(),
+        failure_reason: accumulator.stop_reason.clone(),
+        completed_steps: Vec::new(),
+        failed_step: None,
+        rolled_back: false,
+        chosen_sample: None,
+        attempts: None,
+        verdict: None,
+        eval_scores: None,
+        test_delta: None,
+        lint: None,
+        subagent_count: (accumulator.subagent_count > 0).then_some(accumulator.subagent_count),
+        subagent_cost_usd: (accumulator.subagent_count > 0)
+            .then_some(accumulator.subagent_cost_usd),
+        git_sha_before: None,
+        git_sha_after: None,
+        lines_added: None,
+        lines_removed: None,
+        prompt_hash: None,
+        permissions_hash: None,
+        transient_error: None,
+        skipped_steps: Vec::new(),
+        steps: Vec::new(),
     }
 }
 
+/// How often the status line is redrawn while waiting on the subprocess.
+const STATUS_TICK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
 /// Run a command with stream-JSON parsing and display.
 ///
 /// Parses each stdout line as a stream-JSON event, renders it via the display,
-/// and accumulates data. Updates the status line after each event. Implements a
-/// circuit breaker that kills the subprocess if a tool is denied `threshold`
-/// consecutive times.
+/// forwards it to the observer sink, and accumulates data. Updates the status line after each event and on a
+/// timer so the spinner and elapsed time keep moving. Kills the subprocess
+/// when one of its `guards` trips: the circuit breaker (a tool denied
+/// `threshold` consecutive times) or the `max_tokens` budget.
 ///
 /// Returns `(accumulator, stderr, exit_code, duration_secs)`.
+#[allow(clippy::too_many_arguments)]
 async fn run_command_with_display(
     cmd: std::process::Command,
     display: &CycleDisplay,
     status_line: &mut StatusLine,
-    circuit_breaker_threshold: u32,
-    shutdown: &AtomicBool,
+    test_parser: &TestParser,
+    redactor: &Redactor,
+    tee: &mut StreamTee,
+    mut guards: StepGuards,
+    cancel: &CancellationToken,
+    sink: EventSink<'_>,
 ) -> Result<(StreamAccumulator, String, Option<i32>, u64)> {
     let mut tokio_cmd = TokioCommand::from(cmd);
     tokio_cmd.stdout(Stdio::piped());
@@ -534,29 +2214,29 @@ async fn run_command_with_display(
     });
 
     // Process stdout line-by-line with stream-JSON parsing
-    let mut accumulator = StreamAccumulator::new();
-    let mut consecutive_tool_errors: u32 = 0;
+    let mut accumulator = StreamAccumulator::with_test_parser(test_parser.clone());
     let mut reader = BufReader::new(child_stdout);
     let mut line_buf = String::new();
-    let mut was_shutdown = false;
+    let mut was_cancelled = false;
+    let mut ticker = tokio::time::interval(STATUS_TICK_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
     loop {
-        // Use tokio::select! to race the line read against a shutdown poll.
-        // This ensures responsiveness even when the child is silent.
-        line_buf.clear();
+        // Use tokio::select! to race the line read against cancellation and
+        // the status ticker. This keeps the status line animated and the run
+        // responsive even when the child is silent. `read_line` is cancel-safe:
+        // a partially read line stays in `line_buf` until the next iteration.
         let bytes_read = tokio::select! {
             result = reader.read_line(&mut line_buf) => result.unwrap_or(0),
-            () = async {
-                loop {
-                    if shutdown.load(Ordering::Relaxed) {
-                        return;
-                    }
-                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                }
-            } => {
-                // Shutdown flag was set — kill the child process
+            _ = ticker.tick() => {
+                status_line.tick();
+                status_line.print();
+                continue;
+            }
+            () = cancel.cancelled() => {
+                // Run was cancelled — kill the child process
                 let _ = child.kill().await;
-                was_shutdown = true;
+                was_cancelled = true;
                 break;
             }
         };
@@ -565,48 +2245,264 @@ async fn run_command_with_display(
             break; // EOF or error
         }
 
-        if let Some(event) = parse_event(&line_buf) {
-            display.render_event(&event);
-            accumulator.process(&event);
-            status_line.update(&event);
-            status_line.print();
-
-            // Circuit breaker: track consecutive tool errors
-            match &event {
-                StreamEvent::ToolResult { is_error: true, .. } => {
-                    consecutive_tool_errors += 1;
-                    if circuit_breaker_threshold > 0
-                        && consecutive_tool_errors >= circuit_breaker_threshold
-                    {
-                        eprintln!(
-                            "Circuit breaker: {consecutive_tool_errors} consecutive tool errors, killing subprocess"
-                        );
-                        let _ = child.kill().await;
-                        break;
-                    }
-                }
-                StreamEvent::ToolResult {
-                    is_error: false, ..
-                }
-                | StreamEvent::ToolUse { .. } => {
-                    consecutive_tool_errors = 0;
-                }
-                _ => {}
-            }
+        // Mask secrets before the line reaches the debug log, display, or accumulator
+        let line = redactor.redact_json_line(&line_buf);
+        tee.write(&line);
+        let tripped = handle_line(
+            &line,
+            display,
+            status_line,
+            sink,
+            &mut accumulator,
+            &mut guards,
+        );
+        line_buf.clear();
+        if tripped {
+            let _ = child.kill().await;
+            break;
         }
     }
 
     let status = child.wait().await.context("Failed waiting for process")?;
     let stderr_result = stderr_handle.await.context("stderr reader panicked")?;
+    let stderr_result = redactor.redact(&stderr_result).into_owned();
     let duration_secs = start.elapsed().as_secs();
 
-    // When killed by shutdown, the exit code from `status.code()` is None on Unix
+    // When killed by cancellation, the exit code from `status.code()` is None on Unix
     // (signal death), which correctly matches our expected behavior.
-    let exit_code = if was_shutdown { None } else { status.code() };
+    let exit_code = if was_cancelled { None } else { status.code() };
 
     Ok((accumulator, stderr_result, exit_code, duration_secs))
 }
 
+/// Parse one redacted stdout line and feed the event to the display, the
+/// observer sink, the accumulator, and the status line.
+///
+/// Returns `true` when a guard trips and the step must be stopped. A blown
+/// token budget is also recorded as the accumulator's `stop_reason`.
+fn handle_line(
+    line: &str,
+    display: &CycleDisplay,
+    status_line: &mut StatusLine,
+    sink: EventSink<'_>,
+    accumulator: &mut StreamAccumulator,
+    guards: &mut StepGuards,
+) -> bool {
+    if let Some(event) = parse_event(line) {
+        display.render_event(&event);
+        sink.event(&event);
+        accumulator.process(&event);
+        status_line.update(&event);
+        status_line.print();
+        if let Some(reason) = guards.breaker.observe(&event) {
+            eprintln!("{reason}");
+            sink.gate("circuit_breaker", &reason);
+            return true;
+        }
+    }
+    let Some((message_id, usage)) = message_usage(line) else {
+        return false;
+    };
+    status_line.observe_usage(&message_id, &usage);
+    let Some(reason) = guards.tokens.observe(message_id, &usage) else {
+        return false;
+    };
+    eprintln!("{reason}, killing subprocess");
+    sink.gate("max_tokens", &reason);
+    accumulator.stop_reason = Some(reason);
+    true
+}
+
+/// Mid-stream checks that stop an invocation early
+struct StepGuards {
+    breaker: CircuitBreaker,
+    tokens: TokenBudget,
+}
+
+impl StepGuards {
+    fn new(circuit_breaker_threshold: u32, max_tokens: Option<u64>) -> Self {
+        Self {
+            breaker: CircuitBreaker::new(circuit_breaker_threshold),
+            tokens: TokenBudget::new(max_tokens),
+        }
+    }
+}
+
+/// Stops an invocation once its token usage passes `max_tokens` (`None` disables it)
+struct TokenBudget {
+    max_tokens: Option<u64>,
+    /// Latest usage total of each assistant message, by message ID
+    messages: std::collections::HashMap<String, u64>,
+}
+
+impl TokenBudget {
+    fn new(max_tokens: Option<u64>) -> Self {
+        Self {
+            max_tokens,
+            messages: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Track an assistant message's usage; returns the reason once the budget is exceeded.
+    fn observe(&mut self, id: String, usage: &TokenUsage) -> Option<String> {
+        let max_tokens = self.max_tokens?;
+        let total = self.messages.entry(id).or_default();
+        *total = (*total).max(usage.total());
+        let used: u64 = self.messages.values().sum();
+        (used > max_tokens)
+            .then(|| format!("max_tokens exceeded: {used} tokens used (limit {max_tokens})"))
+    }
+}
+
+/// Stops a step after `threshold` consecutive tool errors (0 disables it)
+struct CircuitBreaker {
+    threshold: u32,
+    consecutive_errors: u32,
+}
+
+impl CircuitBreaker {
+    const fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            consecutive_errors: 0,
+        }
+    }
+
+    /// Track `event`; returns the reason once the threshold is reached.
+    fn observe(&mut self, event: &StreamEvent) -> Option<String> {
+        match event {
+            StreamEvent::ToolResult { is_error: true, .. } => {
+                self.consecutive_errors += 1;
+                (self.threshold > 0 && self.consecutive_errors >= self.threshold).then(|| {
+                    format!(
+                        "Circuit breaker: {} consecutive tool errors, killing subprocess",
+                        self.consecutive_errors
+                    )
+                })
+            }
+            StreamEvent::ToolResult {
+                is_error: false, ..
+            }
+            | StreamEvent::ToolUse { .. } => {
+                self.consecutive_errors = 0;
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Copies of a step's redacted stdout lines: the `--debug-stream` log and
+/// the `--record` capture
+#[derive(Default)]
+struct StreamTee {
+    debug_file: Option<std::fs::File>,
+    /// Lines without trailing newlines, when capturing
+    captured: Option<Vec<String>>,
+}
+
+impl StreamTee {
+    fn new(debug_log: Option<&Path>, capture: bool) -> Self {
+        Self {
+            debug_file: debug_log.and_then(open_debug_log),
+            captured: capture.then(Vec::new),
+        }
+    }
+
+    fn write(&mut self, line: &str) {
+        if let Some(ref mut file) = self.debug_file {
+            let _ = file.write_all(line.as_bytes());
+        }
+        if let Some(ref mut captured) = self.captured {
+            captured.push(line.trim_end_matches(['\n', '\r']).to_string());
+        }
+    }
+}
+
+/// Feed a recorded invocation through the same display, observer, and
+/// guard path as a live run, without spawning anything.
+///
+/// Returns `(accumulator, stderr, exit_code, duration_secs)` as recorded.
+fn replay_with_display(
+    invocation: RecordedInvocation,
+    display: &CycleDisplay,
+    status_line: &mut StatusLine,
+    test_parser: &TestParser,
+    mut guards: StepGuards,
+    sink: EventSink<'_>,
+) -> (StreamAccumulator, String, Option<i32>, u64) {
+    let mut accumulator = StreamAccumulator::with_test_parser(test_parser.clone());
+    for line in &invocation.stdout {
+        if handle_line(
+            line,
+            display,
+            status_line,
+            sink,
+            &mut accumulator,
+            &mut guards,
+        ) {
+            break;
+        }
+    }
+    (
+        accumulator,
+        invocation.stderr,
+        invocation.exit_code,
+        invocation.duration_secs,
+    )
+}
+
+/// Build the raw stream log path: `<dir>/<label>-<YYYYmmddTHHMMSS.mmm>.log`.
+fn debug_log_path(dir: &Path, label: &str, now: chrono::DateTime<chrono::Utc>) -> PathBuf {
+    dir.join(format!("{label}-{}.log", now.format("%Y%m%dT%H%M%S%.3f")))
+}
+
+/// Open a new raw stream log, creating its directory. If `path` is already
+/// taken (e.g. a retry in the same millisecond), a `-2`, `-3`, ... suffix is
+/// added rather than truncating the earlier log.
+///
+/// Failures are reported but never abort the cycle — debug output is best-effort.
+fn open_debug_log(path: &Path) -> Option<std::fs::File> {
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| create_unique(path));
+    match result {
+        Ok((file, path)) => {
+            eprintln!("  Raw stream log: {}", path.display());
+            Some(file)
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: could not open debug stream log {}: {e}",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Create `path`, or the first free `<stem>-N.<ext>` next to it.
+fn create_unique(path: &Path) -> std::io::Result<(std::fs::File, PathBuf)> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let mut candidate = path.to_path_buf();
+    for n in 2.. {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(file) => return Ok((file, candidate)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                candidate = path.with_file_name(format!("{stem}-{n}.{ext}"));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("unbounded suffix search")
+}
+
 /// Run a command, streaming output to terminal and capturing it.
 ///
 /// Spawns the process with piped stdout/stderr, reads them concurrently,
@@ -695,8 +2591,8 @@ permissions = []
         FlowConfig::parse(TEST_CONFIG).unwrap()
     }
 
-    fn no_shutdown() -> Arc<AtomicBool> {
-        Arc::new(AtomicBool::new(false))
+    fn no_shutdown() -> CancellationToken {
+        CancellationToken::new()
     }
 
     #[test]
@@ -794,6 +2690,39 @@ permissions = []
         );
     }
 
+    #[test]
+    fn test_prepare_with_context_injects_lint_feedback_for_linted_cycles() {
+        let mut config = test_config();
+        config.lint = Some(crate::cycle::config::LintConfig {
+            command: "cargo clippy".to_string(),
+            cycles: vec!["review".to_string()],
+            pattern: None,
+        });
+        let executor = CycleExecutor::new(config, no_shutdown());
+        let mut linted = make_outcome(1, "coding", "Implemented something");
+        linted.lint = Some(LintReport::new(
+            &[],
+            &["src/a.rs:3:5: unused variable: `x`".to_string()],
+        ));
+        let log = vec![linted];
+
+        let prepared = executor.prepare_with_context("review", &log).unwrap();
+        assert!(
+            prepared
+                .prompt
+                .starts_with("## New Lint Warnings\n\nIteration 1 [coding] introduced 1 new"),
+            "{}",
+            prepared.prompt
+        );
+        assert!(prepared.prompt.ends_with("You are Flow's review cycle."));
+        let coding = executor.prepare_with_context("coding", &log).unwrap();
+        assert!(
+            !coding.prompt.contains("New Lint Warnings"),
+            "{}",
+            coding.prompt
+        );
+    }
+
     #[test]
     fn test_prepare_with_context_rejects_unknown_cycle() {
         let executor = CycleExecutor::new(test_config(), no_shutdown());
@@ -808,6 +2737,124 @@ permissions = []
         assert_eq!(prepared.permissions, vec!["Read", "Edit(./src/**)"]);
     }
 
+    // --- explain tests ---
+
+    #[test]
+    fn test_explain_single_step_matches_execution() {
+        let executor =
+            CycleExecutor::new(test_config(), no_shutdown()).with_limit_overrides(Some(7), None);
+        let log = vec![make_outcome(1, "review", "Code looked good")];
+        let vars =
+            std::collections::HashMap::from([("queued_task".to_string(), "Fix login".to_string())]);
+        let plan = executor.explain("coding", &log, &vars).unwrap();
+
+        assert_eq!(plan.len(), 1);
+        let invocation = &plan[0];
+        assert_eq!(invocation.step, None);
+        assert_eq!(invocation.session, SessionPlan::New);
+        assert!(invocation.prompt.contains("Code looked good"));
+        assert!(invocation.prompt.ends_with("## Queued task\n\nFix login"));
+        assert_eq!(invocation.max_turns, Some(7));
+        let args = &invocation.command;
+        let prompt_at = args.iter().position(|a| a == "-p").unwrap();
+        assert_eq!(args[prompt_at + 1], invocation.prompt);
+        assert!(args
+            .windows(2)
+            .any(|w| w[0] == "--max-turns" && w[1] == "7"));
+    }
+
+    #[test]
+    fn test_explain_plans_steps_and_sessions() {
+        let executor = CycleExecutor::new(multi_step_config(), no_shutdown());
+        let plan = executor
+            .explain("coding", &[], &std::collections::HashMap::new())
+            .unwrap();
+
+        let steps: Vec<_> = plan.iter().filter_map(|i| i.step.as_deref()).collect();
+        assert_eq!(steps, vec!["plan", "implement", "review"]);
+        assert_eq!(plan[0].session, SessionPlan::Start("architect".to_string()));
+        assert_eq!(plan[1].session, SessionPlan::Start("coder".to_string()));
+        assert_eq!(
+            plan[2].session,
+            SessionPlan::Continue("architect".to_string())
+        );
+        assert!(plan[2].command.contains(&"<session:architect>".to_string()));
+        assert!(!plan[0].command.contains(&"--resume".to_string()));
+        assert_eq!(
+            plan[1].permissions,
+            vec!["Read", "Edit(./src/**)", "Bash(cargo *)"]
+        );
+    }
+
+    #[test]
+    fn test_explain_resumes_persisted_session() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sessions = dir.path().join("sessions.json");
+        let config = FlowConfig::parse(
+            &MULTI_STEP_CONFIG
+                .replace("session = \"coder\"", "session = \"coder\"\npersist = true"),
+        )
+        .unwrap();
+        let mut saved = std::collections::BTreeMap::new();
+        saved.insert(
+            "coder".to_string(),
+            crate::claude::session::PersistedSession {
+                session_id: "sess-42".to_string(),
+                cycle: "coding".to_string(),
+                last_used: chrono::Utc::now(),
+            },
+        );
+        crate::claude::session::save_sessions(&sessions, &saved).unwrap();
+
+        let executor = CycleExecutor::new(config, no_shutdown()).with_sessions_path(Some(sessions));
+        let plan = executor
+            .explain("coding", &[], &std::collections::HashMap::new())
+            .unwrap();
+        assert_eq!(
+            plan[1].session,
+            SessionPlan::Resume {
+                tag: "coder".to_string(),
+                session_id: "sess-42".to_string()
+            }
+        );
+        assert!(plan[1].command.contains(&"sess-42".to_string()));
+    }
+
+    #[test]
+    fn test_each_sample_starts_from_the_same_sessions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let sessions = dir.path().join("sessions.json");
+        let executor = CycleExecutor::new(test_config(), no_shutdown())
+            .with_sessions_path(Some(sessions.clone()));
+        let none_yet = SampleState {
+            tree: None,
+            sessions: executor.read_sessions(),
+        };
+
+        std::fs::write(&sessions, "{\"coder\": \"sample 1\"}").unwrap();
+        let first = SampleState {
+            tree: None,
+            sessions: executor.read_sessions(),
+        };
+        executor.restore_sample(&none_yet).unwrap();
+        assert!(!sessions.exists());
+
+        std::fs::write(&sessions, "{\"coder\": \"sample 2\"}").unwrap();
+        executor.restore_sample(&first).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&sessions).unwrap(),
+            "{\"coder\": \"sample 1\"}"
+        );
+    }
+
+    #[test]
+    fn test_explain_rejects_unknown_cycle() {
+        let executor = CycleExecutor::new(test_config(), no_shutdown());
+        assert!(executor
+            .explain("nonexistent", &[], &std::collections::HashMap::new())
+            .is_err());
+    }
+
     // --- run_command tests (test the subprocess execution directly) ---
 
     #[tokio::test]
@@ -881,6 +2928,34 @@ permissions = []
             permission_denials: None,
             files_changed: vec![],
             tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
         };
         assert!(result.result_text.is_none());
         assert!(result.num_turns.is_none());
@@ -909,6 +2984,34 @@ permissions = []
             ]),
             files_changed: vec!["src/main.rs".to_string(), "src/lib.rs".to_string()],
             tests_passed: 42,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
         };
         assert_eq!(result.result_text.as_deref(), Some("Implemented feature X"));
         assert_eq!(result.num_turns, Some(53));
@@ -1029,6 +3132,35 @@ permissions = ["Edit(./src/**)"]
         assert!(!cycle.is_multi_step());
     }
 
+    // --- provenance hash tests ---
+
+    #[test]
+    fn test_prompt_hash_is_short_stable_hex() {
+        let hash = prompt_hash("Implement the next task");
+        assert_eq!(hash.len(), 16);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(hash, prompt_hash("Implement the next task"));
+        assert_ne!(hash, prompt_hash("Implement the next task."));
+    }
+
+    #[test]
+    fn test_permissions_hash_ignores_order() {
+        let a = vec!["Read".to_string(), "Bash(cargo *)".to_string()];
+        let b = vec!["Bash(cargo *)".to_string(), "Read".to_string()];
+        assert_eq!(permissions_hash(&a), permissions_hash(&b));
+        assert_ne!(permissions_hash(&a), permissions_hash(&a[..1]));
+    }
+
+    #[test]
+    fn test_combine_hashes() {
+        assert_eq!(combine_hashes(&[]), None);
+        let one = vec!["abc".to_string()];
+        assert_eq!(combine_hashes(&one), Some("abc".to_string()));
+        let two = vec!["abc".to_string(), "def".to_string()];
+        let swapped = vec!["def".to_string(), "abc".to_string()];
+        assert_ne!(combine_hashes(&two), combine_hashes(&swapped));
+    }
+
     // --- run_command_with_display tests ---
 
     #[tokio::test]
@@ -1046,15 +3178,252 @@ permissions = ["Edit(./src/**)"]
         let mut cmd2 = std::process::Command::new("echo");
         cmd2.arg(stream_json);
 
-        let (acc, _stderr, exit_code, _duration) =
-            run_command_with_display(cmd2, &display, &mut status_line, 5, &AtomicBool::new(false))
-                .await
-                .unwrap();
+        let (acc, _stderr, exit_code, _duration) = run_command_with_display(
+            cmd2,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &Redactor::default(),
+            &mut StreamTee::default(),
+            StepGuards::new(5, None),
+            &CancellationToken::new(),
+            EventSink::new(None, "test", None),
+        )
+        .await
+        .unwrap();
 
         assert_eq!(exit_code, Some(0));
         assert!(acc.result.is_some());
     }
 
+    #[tokio::test]
+    async fn test_run_command_with_display_reports_to_observer() {
+        use crate::cycle::observer::tests::RecordingObserver;
+
+        let observer = RecordingObserver::default();
+        let display = CycleDisplay::new("test").with_quiet(true);
+        let mut status_line = StatusLine::new("test").with_hidden(true);
+        let error = r#"{"type":"assistant","message":{"content":[{"type":"tool_result","is_error":true,"content":"denied"}]}}"#;
+        let stream = format!(
+            "{}\n{error}\n{error}",
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Hello"}]}}"#
+        );
+        let mut cmd = std::process::Command::new("echo");
+        cmd.arg(stream);
+
+        run_command_with_display(
+            cmd,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &Redactor::default(),
+            &mut StreamTee::default(),
+            StepGuards::new(2, None),
+            &CancellationToken::new(),
+            EventSink::new(Some(&observer), "coding", Some("plan")),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *observer.calls.lock().unwrap(),
+            [
+                "event coding/plan text",
+                "event coding/plan tool_result",
+                "event coding/plan tool_result",
+                "gate circuit_breaker coding",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_with_display_matches_live_processing() {
+        use crate::cycle::observer::tests::RecordingObserver;
+
+        let observer = RecordingObserver::default();
+        let display = CycleDisplay::new("test").with_quiet(true);
+        let mut status_line = StatusLine::new("test").with_hidden(true);
+        let error = r#"{"type":"assistant","message":{"content":[{"type":"tool_result","is_error":true,"content":"denied"}]}}"#;
+        let invocation = RecordedInvocation {
+            cycle: "coding".to_string(),
+            step: Some("plan".to_string()),
+            stdout: vec![error.to_string(), error.to_string(), error.to_string()],
+            stderr: "boom".to_string(),
+            exit_code: None,
+            duration_secs: 42,
+        };
+
+        let (_acc, stderr, exit_code, duration) = replay_with_display(
+            invocation,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            StepGuards::new(2, None),
+            EventSink::new(Some(&observer), "coding", Some("plan")),
+        );
+
+        assert_eq!((stderr.as_str(), exit_code, duration), ("boom", None, 42));
+        // The circuit breaker stops at the same line it did live
+        assert_eq!(
+            *observer.calls.lock().unwrap(),
+            [
+                "event coding/plan tool_result",
+                "event coding/plan tool_result",
+                "gate circuit_breaker coding",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_tokens_stops_invocation_with_reason() {
+        use crate::cycle::observer::tests::RecordingObserver;
+
+        let observer = RecordingObserver::default();
+        let display = CycleDisplay::new("test").with_quiet(true);
+        let mut status_line = StatusLine::new("test").with_hidden(true);
+        let message = |id: &str, input: u64| {
+            format!(
+                r#"{{"type":"assistant","message":{{"id":"{id}","content":[{{"type":"text","text":"Hi"}}],"usage":{{"input_tokens":{input},"output_tokens":100}}}}}}"#
+            )
+        };
+        let invocation = RecordedInvocation {
+            cycle: "coding".to_string(),
+            step: None,
+            // The repeated msg_1 line is one message, counted once
+            stdout: vec![
+                message("msg_1", 400),
+                message("msg_1", 400),
+                message("msg_2", 450),
+                message("msg_3", 10),
+            ],
+            stderr: String::new(),
+            exit_code: None,
+            duration_secs: 1,
+        };
+
+        let (acc, ..) = replay_with_display(
+            invocation,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            StepGuards::new(0, Some(1000)),
+            EventSink::new(Some(&observer), "coding", None),
+        );
+
+        assert_eq!(
+            acc.stop_reason.as_deref(),
+            Some("max_tokens exceeded: 1050 tokens used (limit 1000)")
+        );
+        assert_eq!(
+            observer.calls.lock().unwrap().last().map(String::as_str),
+            Some("gate max_tokens coding")
+        );
+        let result = build_cycle_result("coding".to_string(), None, String::new(), 1, &acc);
+        assert!(!result.success);
+        assert_eq!(result.failure_reason, acc.stop_reason);
+    }
+
+    #[tokio::test]
+    async fn test_run_command_with_display_tees_raw_lines_to_debug_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("debug").join("coding-20260101T000000.log");
+        let display = CycleDisplay::new("test");
+        let mut status_line = StatusLine::new("test");
+        let stream = "{\"type\":\"mystery\"}\nnot json at all";
+        let mut cmd = std::process::Command::new("echo");
+        cmd.arg(stream);
+
+        run_command_with_display(
+            cmd,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &Redactor::default(),
+            &mut StreamTee::new(Some(&log_path), false),
+            StepGuards::new(5, None),
+            &CancellationToken::new(),
+            EventSink::new(None, "test", None),
+        )
+        .await
+        .unwrap();
+
+        // Lines are captured verbatim, including ones the parser drops
+        let raw = std::fs::read_to_string(&log_path).unwrap();
+        assert_eq!(raw, format!("{stream}\n"));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_with_display_redacts_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("coding.log");
+        let display = CycleDisplay::new("test");
+        let mut status_line = StatusLine::new("test");
+        let stream = r#"{"type":"result","subtype":"success","is_error":false,"result":"Key sk-ant-REDACTED","num_turns":1,"total_cost_usd":0.1,"duration_ms":10,"permission_denials":[]}"#;
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c").arg(format!(
+            "echo '{stream}'; echo 'token sk-ant-REDACTED' >&2"
+        ));
+        let redactor =
+            Redactor::from_config(Some(&crate::cycle::config::RedactionConfig::default())).unwrap();
+
+        let (acc, stderr, _exit_code, _duration) = run_command_with_display(
+            cmd,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &redactor,
+            &mut StreamTee::new(Some(&log_path), false),
+            StepGuards::new(5, None),
+            &CancellationToken::new(),
+            EventSink::new(None, "test", None),
+        )
+        .await
+        .unwrap();
+
+        let Some(StreamEvent::Result { result_text, .. }) = &acc.result else {
+            panic!("Expected Result event, got {:?}", acc.result);
+        };
+        assert_eq!(result_text, "Key [REDACTED]");
+        assert_eq!(stderr, "token [REDACTED]");
+        let raw = std::fs::read_to_string(&log_path).unwrap();
+        assert!(
+            !raw.contains("sk-ant-"),
+            "debug log should be redacted: {raw}"
+        );
+    }
+
+    #[test]
+    fn test_debug_log_path_format() {
+        let now = chrono::DateTime::parse_from_rfc3339("2026-03-04T05:06:07Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let path = debug_log_path(Path::new(".flow/debug"), "coding-plan", now);
+        assert_eq!(
+            path,
+            PathBuf::from(".flow/debug/coding-plan-20260304T050607.000.log")
+        );
+    }
+
+    #[test]
+    fn test_open_debug_log_never_truncates_an_existing_log() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("debug/coding-20260304T050607.000.log");
+        let mut first = open_debug_log(&path).unwrap();
+        first.write_all(b"first").unwrap();
+        let mut second = open_debug_log(&path).unwrap();
+        second.write_all(b"second").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+        let next = dir.path().join("debug/coding-20260304T050607.000-2.log");
+        assert_eq!(std::fs::read_to_string(next).unwrap(), "second");
+    }
+
+    #[test]
+    fn test_debug_log_path_disabled_by_default() {
+        let executor = CycleExecutor::new(test_config(), no_shutdown());
+        assert!(executor.debug_log_path("coding").is_none());
+    }
+
     #[tokio::test]
     async fn test_run_command_with_display_captures_result_fields() {
         let display = CycleDisplay::new("test");
@@ -1064,10 +3433,19 @@ permissions = ["Edit(./src/**)"]
         let mut cmd = std::process::Command::new("echo");
         cmd.arg(line);
 
-        let (acc, _stderr, _exit_code, _duration) =
-            run_command_with_display(cmd, &display, &mut status_line, 5, &AtomicBool::new(false))
-                .await
-                .unwrap();
+        let (acc, _stderr, _exit_code, _duration) = run_command_with_display(
+            cmd,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &Redactor::default(),
+            &mut StreamTee::default(),
+            StepGuards::new(5, None),
+            &CancellationToken::new(),
+            EventSink::new(None, "test", None),
+        )
+        .await
+        .unwrap();
 
         assert_eq!(acc.permission_denial_count(), 1);
         let Some(StreamEvent::Result {
@@ -1097,10 +3475,19 @@ permissions = ["Edit(./src/**)"]
         let mut cmd = std::process::Command::new("echo");
         cmd.arg(lines);
 
-        let (acc, _stderr, _exit_code, _duration) =
-            run_command_with_display(cmd, &display, &mut status_line, 5, &AtomicBool::new(false))
-                .await
-                .unwrap();
+        let (acc, _stderr, _exit_code, _duration) = run_command_with_display(
+            cmd,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &Redactor::default(),
+            &mut StreamTee::default(),
+            StepGuards::new(5, None),
+            &CancellationToken::new(),
+            EventSink::new(None, "test", None),
+        )
+        .await
+        .unwrap();
 
         // src/main.rs appears twice but should be deduplicated
         assert_eq!(acc.files_changed, vec!["src/main.rs", "src/lib.rs"]);
@@ -1108,6 +3495,53 @@ permissions = ["Edit(./src/**)"]
 
     // --- build_cycle_result tests ---
 
+    #[test]
+    fn test_apply_pricing_fills_missing_cost() {
+        let mut config = test_config();
+        config.pricing.insert(
+            "sonnet".to_string(),
+            crate::cycle::config::ModelPrice {
+                input: 3.0,
+                output: 15.0,
+                ..Default::default()
+            },
+        );
+        let executor = CycleExecutor::new(config, no_shutdown());
+        let cycle = executor.config.get_cycle("coding").unwrap().clone();
+        let usage = TokenUsage {
+            input_tokens: 100_000,
+            output_tokens: 10_000,
+            ..TokenUsage::default()
+        };
+
+        let mut result = CycleResult {
+            usage: Some(usage),
+            model: Some("claude-sonnet-4-5".to_string()),
+            total_cost_usd: Some(0.0),
+            ..Default::default()
+        };
+        executor.apply_pricing(&cycle, &mut result);
+        assert!((result.computed_cost_usd.unwrap() - 0.45).abs() < 1e-9);
+        assert_eq!(result.total_cost_usd, result.computed_cost_usd);
+
+        // A reported cost is kept; unpriced models are left alone
+        let mut reported = CycleResult {
+            usage: Some(usage),
+            model: Some("claude-sonnet-4-5".to_string()),
+            total_cost_usd: Some(0.5),
+            ..Default::default()
+        };
+        executor.apply_pricing(&cycle, &mut reported);
+        assert_eq!(reported.total_cost_usd, Some(0.5));
+        let mut unpriced = CycleResult {
+            usage: Some(usage),
+            model: Some("claude-opus-4-6".to_string()),
+            ..Default::default()
+        };
+        executor.apply_pricing(&cycle, &mut unpriced);
+        assert!(unpriced.computed_cost_usd.is_none());
+    }
+
     #[test]
     fn test_build_cycle_result_with_result_event() {
         let mut acc = StreamAccumulator::new();
@@ -1126,6 +3560,7 @@ permissions = ["Edit(./src/**)"]
             total_cost_usd: 1.23,
             duration_ms: 30000,
             permission_denials: vec!["Bash".to_string()],
+            usage: TokenUsage::default(),
         });
 
         let result = build_cycle_result("coding".to_string(), Some(0), String::new(), 120, &acc);
@@ -1176,6 +3611,7 @@ permissions = ["Edit(./src/**)"]
             total_cost_usd: 0.50,
             duration_ms: 10000,
             permission_denials: vec![],
+            usage: TokenUsage::default(),
         });
 
         let result = build_cycle_result("review".to_string(), Some(0), String::new(), 10, &acc);
@@ -1200,20 +3636,21 @@ permissions = ["Edit(./src/**)"]
         assert!(result.permission_denials.is_none());
         assert!(result.files_changed.is_empty());
         assert_eq!(result.tests_passed, 0);
+        assert!(result.subagent_count.is_none());
+        assert!(result.subagent_cost_usd.is_none());
     }
 
-    // --- shutdown flag tests ---
+    // --- cancellation tests ---
 
     #[test]
-    fn test_executor_new_accepts_shutdown_flag() {
+    fn test_executor_new_accepts_cancellation_token() {
         let config = test_config();
-        let shutdown = Arc::new(AtomicBool::new(false));
-        let _executor = CycleExecutor::new(config, shutdown);
+        let _executor = CycleExecutor::new(config, CancellationToken::new());
     }
 
     #[tokio::test]
-    async fn test_run_command_stops_on_shutdown_flag() {
-        let shutdown = Arc::new(AtomicBool::new(false));
+    async fn test_run_command_stops_on_cancellation() {
+        let cancel = CancellationToken::new();
         let display = CycleDisplay::new("test");
         let mut status_line = StatusLine::new("test");
 
@@ -1221,21 +3658,30 @@ permissions = ["Edit(./src/**)"]
         let mut cmd = std::process::Command::new("sleep");
         cmd.arg("60");
 
-        let shutdown_clone = shutdown.clone();
-        // Set the shutdown flag after a short delay
+        let cancel_clone = cancel.clone();
+        // Cancel after a short delay
         tokio::spawn(async move {
             tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            shutdown_clone.store(true, Ordering::Relaxed);
+            cancel_clone.cancel();
         });
 
         let start = std::time::Instant::now();
-        let (_, _, exit_code, _) =
-            run_command_with_display(cmd, &display, &mut status_line, 5, &shutdown)
-                .await
-                .unwrap();
+        let (_, _, exit_code, _) = run_command_with_display(
+            cmd,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &Redactor::default(),
+            &mut StreamTee::default(),
+            StepGuards::new(5, None),
+            &cancel,
+            EventSink::new(None, "test", None),
+        )
+        .await
+        .unwrap();
 
         let elapsed = start.elapsed();
-        // Should complete well under 60 seconds (killed by shutdown flag)
+        // Should complete well under 60 seconds (killed on cancellation)
         assert!(
             elapsed.as_secs() < 5,
             "Expected fast shutdown, took {elapsed:?}"
@@ -1247,19 +3693,49 @@ permissions = ["Edit(./src/**)"]
         );
     }
 
+    #[test]
+    fn test_enter_step_stops_when_cancelled_or_over_max_visits() {
+        let config = multi_step_config();
+        let step = &config.get_cycle("coding").unwrap().steps[0];
+        let sink = EventSink::new(None, "coding", Some(&step.name));
+
+        let cancel = CancellationToken::new();
+        let executor = CycleExecutor::new(config.clone(), cancel.clone());
+        let mut tracker = VisitTracker::new();
+        for visit in 1..=step.max_visits {
+            assert_eq!(executor.enter_step(&mut tracker, sink, step), Some(visit));
+        }
+        assert_eq!(executor.enter_step(&mut tracker, sink, step), None);
+
+        cancel.cancel();
+        assert_eq!(
+            executor.enter_step(&mut VisitTracker::new(), sink, step),
+            None
+        );
+    }
+
     #[tokio::test]
-    async fn test_shutdown_flag_not_set_allows_normal_completion() {
-        let shutdown = Arc::new(AtomicBool::new(false));
+    async fn test_uncancelled_token_allows_normal_completion() {
+        let cancel = CancellationToken::new();
         let display = CycleDisplay::new("test");
         let mut status_line = StatusLine::new("test");
 
         let mut cmd = std::process::Command::new("echo");
         cmd.arg("hello");
 
-        let (_, _, exit_code, _) =
-            run_command_with_display(cmd, &display, &mut status_line, 5, &shutdown)
-                .await
-                .unwrap();
+        let (_, _, exit_code, _) = run_command_with_display(
+            cmd,
+            &display,
+            &mut status_line,
+            &TestParser::default(),
+            &Redactor::default(),
+            &mut StreamTee::default(),
+            StepGuards::new(5, None),
+            &cancel,
+            EventSink::new(None, "test", None),
+        )
+        .await
+        .unwrap();
 
         assert_eq!(exit_code, Some(0));
     }
@@ -1281,7 +3757,10 @@ permissions = ["Edit(./src/**)"]
             total_cost_usd: 1.0,
             duration_ms: 10000,
             permission_denials: vec!["Bash".to_string()],
+            usage: TokenUsage::default(),
         });
+        acc1.subagent_count = 2;
+        acc1.subagent_cost_usd = 0.25;
         agg.accumulate(&acc1, "", Some(0), 30);
 
         let mut acc2 = StreamAccumulator::new();
@@ -1296,6 +3775,7 @@ permissions = ["Edit(./src/**)"]
             total_cost_usd: 0.5,
             duration_ms: 5000,
             permission_denials: vec![],
+            usage: TokenUsage::default(),
         });
         agg.accumulate(&acc2, "some error", Some(0), 20);
 
@@ -1309,6 +3789,8 @@ permissions = ["Edit(./src/**)"]
         assert_eq!(result.files_changed, vec!["src/a.rs", "src/b.rs"]);
         assert_eq!(result.result_text.as_deref(), Some("Step 2 done"));
         assert_eq!(result.stderr, "some error");
+        assert_eq!(result.subagent_count, Some(2));
+        assert_eq!(result.subagent_cost_usd, Some(0.25));
     }
 
     // --- resolve_limits tests ---
@@ -1416,6 +3898,171 @@ prompt = "Plan."
         assert_eq!(max_cost_usd, None);
     }
 
+    const LIMITS_CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+max_turns = 200
+max_cost_usd = 10.0
+
+[[cycle]]
+name = "multi"
+description = "Multi"
+max_turns = 200
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+max_turns = 30
+max_cost_usd = 2.0
+"#;
+
+    #[test]
+    fn test_prepare_uses_config_limits_without_overrides() {
+        let config = FlowConfig::parse(LIMITS_CONFIG).unwrap();
+        let executor = CycleExecutor::new(config, no_shutdown());
+        let prepared = executor.prepare("coding").unwrap();
+        assert_eq!(prepared.max_turns, Some(200));
+        assert_eq!(prepared.max_cost_usd, Some(10.0));
+    }
+
+    #[test]
+    fn test_prepare_applies_limit_overrides() {
+        let config = FlowConfig::parse(LIMITS_CONFIG).unwrap();
+        let executor =
+            CycleExecutor::new(config, no_shutdown()).with_limit_overrides(Some(5), Some(0.5));
+        let prepared = executor.prepare("coding").unwrap();
+        assert_eq!(prepared.max_turns, Some(5));
+        assert_eq!(prepared.max_cost_usd, Some(0.5));
+    }
+
+    #[test]
+    fn test_limit_overrides_take_precedence_over_step_values() {
+        let config = FlowConfig::parse(LIMITS_CONFIG).unwrap();
+        let executor =
+            CycleExecutor::new(config.clone(), no_shutdown()).with_limit_overrides(Some(5), None);
+        let cycle = config.get_cycle("multi").unwrap();
+        let (max_turns, max_cost_usd) = executor.effective_limits(cycle, Some(&cycle.steps[0]));
+        assert_eq!(max_turns, Some(5));
+        assert_eq!(max_cost_usd, Some(2.0), "unset override keeps step value");
+    }
+
+    // --- add_spend tests ---
+
+    #[test]
+    fn test_add_spend_sums_cost_tokens_and_duration() {
+        let earlier = CycleResult {
+            duration_secs: 5,
+            total_cost_usd: Some(0.5),
+            usage: Some(crate::claude::stream::TokenUsage {
+                input_tokens: 10,
+                ..Default::default()
+            }),
+            subagent_count: Some(1),
+            ..CycleResult::default()
+        };
+        let mut result = CycleResult {
+            duration_secs: 7,
+            total_cost_usd: Some(0.25),
+            ..CycleResult::default()
+        };
+        add_spend(&mut result, &earlier);
+        add_spend(&mut result, &earlier);
+        assert_eq!(result.duration_secs, 17);
+        assert_eq!(result.total_cost_usd, Some(1.25));
+        assert_eq!(result.usage.map(|u| u.input_tokens), Some(20));
+        assert_eq!(result.subagent_count, Some(2));
+        assert_eq!(result.computed_cost_usd, None);
+    }
+
+    // --- TreeCache tests ---
+
+    #[tokio::test]
+    async fn test_tree_cache_reuses_value_for_same_tree() {
+        let cache = TreeCache::new();
+        let tree = || Some("abc".to_string());
+        assert_eq!(
+            cache.get_or_compute(tree(), async { Some(1) }).await,
+            Some(1)
+        );
+        assert_eq!(
+            cache.get_or_compute(tree(), async { Some(2) }).await,
+            Some(1)
+        );
+        let other = Some("def".to_string());
+        assert_eq!(
+            cache.get_or_compute(other, async { Some(3) }).await,
+            Some(3)
+        );
+        // Without a tree id nothing is reused or cached
+        assert_eq!(cache.get_or_compute(None, async { Some(4) }).await, Some(4));
+        assert_eq!(cache.get_or_compute(None, async { None }).await, None);
+    }
+
+    // --- sandbox tests ---
+
+    #[test]
+    fn test_sandboxed_wraps_only_docker_cycles() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code."
+sandbox = "docker"
+sandbox_image = "flow-sandbox:latest"
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review."
+"#,
+        )
+        .unwrap();
+        let executor = CycleExecutor::new(config, no_shutdown());
+        let cmd = executor
+            .sandboxed("coding", std::process::Command::new("claude"))
+            .unwrap();
+        assert_eq!(cmd.get_program(), "docker");
+        let cmd = executor
+            .sandboxed("review", std::process::Command::new("claude"))
+            .unwrap();
+        assert_eq!(cmd.get_program(), "claude");
+    }
+
+    #[test]
+    fn test_claude_runs_in_project_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = FlowConfig::parse(LIMITS_CONFIG).unwrap();
+        let executor = CycleExecutor::new(config, no_shutdown()).with_project_dir(dir.path());
+        let cmd = executor
+            .sandboxed("coding", std::process::Command::new("claude"))
+            .unwrap();
+        assert_eq!(cmd.get_current_dir(), Some(dir.path()));
+    }
+
+    // --- start step tests ---
+
+    #[test]
+    fn test_start_step_is_consumed_once() {
+        let config = FlowConfig::parse(LIMITS_CONFIG).unwrap();
+        let executor =
+            CycleExecutor::new(config, no_shutdown()).with_start_step("multi", "implement");
+        assert_eq!(executor.take_start_step("coding"), None);
+        assert_eq!(
+            executor.take_start_step("multi").as_deref(),
+            Some("implement")
+        );
+        assert_eq!(executor.take_start_step("multi"), None);
+    }
+
     #[test]
     fn test_step_aggregator_joins_multiple_stderr_with_newlines() {
         let mut agg = StepAggregator::new();
@@ -1428,6 +4075,7 @@ prompt = "Plan."
             total_cost_usd: 0.1,
             duration_ms: 1000,
             permission_denials: vec![],
+            usage: TokenUsage::default(),
         });
         agg.accumulate(&acc1, "error from step 1", Some(0), 10);
 
@@ -1439,6 +4087,7 @@ prompt = "Plan."
             total_cost_usd: 0.1,
             duration_ms: 1000,
             permission_denials: vec![],
+            usage: TokenUsage::default(),
         });
         agg.accumulate(&acc2, "error from step 2", Some(0), 10);
 
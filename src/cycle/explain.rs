@@ -0,0 +1,248 @@
+//! Resolved-invocation preview (`flow explain <cycle>`)
+//!
+//! Shows what a cycle would hand to Claude Code without running anything: the
+//! prompt with templates expanded and log context injected, the resolved
+//! permissions, the session each step starts or resumes, the budgets, and the
+//! exact command line. Steps are listed in declaration order; `skip_if`,
+//! `repeat_until`, and routers are decided at run time and aren't evaluated.
+
+use std::fmt::Write as _;
+use std::process::Command;
+
+/// How an invocation's Claude Code conversation is chosen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionPlan {
+    /// No session tag: a new conversation
+    New,
+    /// The first step using this tag starts its conversation
+    Start(String),
+    /// Continues the conversation an earlier step of this cycle started
+    Continue(String),
+    /// Resumes a session persisted by an earlier run
+    Resume {
+        /// Session tag
+        tag: String,
+        /// Claude Code session ID from `.flow/sessions.json`
+        session_id: String,
+    },
+    /// `fresh_session = true`: a new conversation taking over the tag
+    Fresh(String),
+}
+
+impl SessionPlan {
+    /// One-line description, e.g. `continues 'impl' from an earlier step`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            Self::New => "new conversation".to_string(),
+            Self::Start(tag) => format!("starts session '{tag}'"),
+            Self::Continue(tag) => format!("continues session '{tag}' from an earlier step"),
+            Self::Resume { tag, session_id } => {
+                format!("resumes persisted session '{tag}' ({session_id})")
+            }
+            Self::Fresh(tag) => format!("fresh conversation for session '{tag}'"),
+        }
+    }
+}
+
+/// One Claude Code invocation a cycle would make
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedInvocation {
+    /// Step name (`None` for a single-step cycle)
+    pub step: Option<String>,
+    /// Prompt as sent: templates expanded, context and queued task injected
+    pub prompt: String,
+    /// Resolved `--allowedTools` permissions
+    pub permissions: Vec<String>,
+    /// Conversation the invocation starts or resumes
+    pub session: SessionPlan,
+    /// Effective turn limit
+    pub max_turns: Option<u32>,
+    /// Effective cost limit in USD
+    pub max_cost_usd: Option<f64>,
+    /// Effective token limit
+    pub max_tokens: Option<u64>,
+    /// Effective model
+    pub model: Option<String>,
+    /// Program and arguments, as run
+    pub command: Vec<String>,
+}
+
+/// Program and arguments of a command.
+#[must_use]
+pub fn command_line(cmd: &Command) -> Vec<String> {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Quote `arg` for a POSIX shell, leaving plain words as they are.
+#[must_use]
+pub fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@+%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Render a cycle's planned invocations for `flow explain`.
+#[must_use]
+pub fn render_explanation(cycle_name: &str, invocations: &[PlannedInvocation]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "Cycle '{cycle_name}'");
+    if invocations.len() > 1 {
+        let _ = writeln!(
+            out,
+            "Steps in declaration order; skip_if, repeat_until, and routers are decided at run time."
+        );
+    }
+    for (i, invocation) in invocations.iter().enumerate() {
+        let _ = writeln!(out);
+        if let Some(ref step) = invocation.step {
+            let _ = writeln!(out, "=== Step {}/{}: {step}", i + 1, invocations.len());
+        }
+        let _ = writeln!(out, "Session:     {}", invocation.session.describe());
+        let _ = writeln!(
+            out,
+            "Model:       {}",
+            invocation
+                .model
+                .as_deref()
+                .unwrap_or("(Claude Code default)")
+        );
+        let _ = writeln!(out, "Budgets:     {}", render_budgets(invocation));
+        let _ = writeln!(
+            out,
+            "Permissions: {}",
+            if invocation.permissions.is_empty() {
+                "(none)".to_string()
+            } else {
+                invocation.permissions.join(", ")
+            }
+        );
+        let _ = writeln!(
+            out,
+            "\n--- Prompt ---\n{}\n--- End prompt ---",
+            invocation.prompt
+        );
+        let command: Vec<String> = invocation.command.iter().map(|a| shell_quote(a)).collect();
+        let _ = writeln!(out, "\nCommand:\n{}", command.join(" "));
+    }
+    out
+}
+
+/// `max_turns 30 · max_cost_usd $2.00 · max_tokens 500000`, or `unlimited`.
+fn render_budgets(invocation: &PlannedInvocation) -> String {
+    let budgets: Vec<String> = [
+        invocation.max_turns.map(|t| format!("max_turns {t}")),
+        invocation
+            .max_cost_usd
+            .map(|c| format!("max_cost_usd ${c:.2}")),
+        invocation.max_tokens.map(|t| format!("max_tokens {t}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if budgets.is_empty() {
+        "unlimited".to_string()
+    } else {
+        budgets.join(" · ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(step: Option<&str>) -> PlannedInvocation {
+        PlannedInvocation {
+            step: step.map(str::to_string),
+            prompt: "Implement the next task".to_string(),
+            permissions: vec!["Read".to_string(), "Edit(./src/**)".to_string()],
+            session: SessionPlan::New,
+            max_turns: Some(30),
+            max_cost_usd: Some(2.0),
+            max_tokens: None,
+            model: None,
+            command: vec![
+                "claude".to_string(),
+                "-p".to_string(),
+                "Implement the next task".to_string(),
+                "--allowedTools".to_string(),
+                "Edit(./src/**)".to_string(),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("--max-turns"), "--max-turns");
+        assert_eq!(shell_quote("Edit(./src/**)"), "'Edit(./src/**)'");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_command_line_lists_program_and_args() {
+        let mut cmd = Command::new("claude");
+        cmd.arg("-p").arg("hi there");
+        assert_eq!(command_line(&cmd), vec!["claude", "-p", "hi there"]);
+    }
+
+    #[test]
+    fn test_render_single_step_cycle() {
+        let text = render_explanation("coding", &[invocation(None)]);
+        assert!(text.starts_with("Cycle 'coding'\n"), "{text}");
+        assert!(!text.contains("=== Step"), "{text}");
+        assert!(text.contains("Session:     new conversation"), "{text}");
+        assert!(
+            text.contains("Model:       (Claude Code default)"),
+            "{text}"
+        );
+        assert!(
+            text.contains("Budgets:     max_turns 30 · max_cost_usd $2.00"),
+            "{text}"
+        );
+        assert!(text.contains("Permissions: Read, Edit(./src/**)"), "{text}");
+        assert!(
+            text.contains("--- Prompt ---\nImplement the next task\n--- End prompt ---"),
+            "{text}"
+        );
+        assert!(
+            text.contains("claude -p 'Implement the next task' --allowedTools 'Edit(./src/**)'"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn test_render_steps_with_sessions() {
+        let mut plan = invocation(Some("plan"));
+        plan.session = SessionPlan::Start("work".to_string());
+        let mut build = invocation(Some("build"));
+        build.session = SessionPlan::Continue("work".to_string());
+        build.max_turns = None;
+        build.max_cost_usd = None;
+        let text = render_explanation("planned", &[plan, build]);
+        assert!(text.contains("=== Step 1/2: plan"), "{text}");
+        assert!(text.contains("=== Step 2/2: build"), "{text}");
+        assert!(text.contains("starts session 'work'"), "{text}");
+        assert!(text.contains("continues session 'work'"), "{text}");
+        assert!(text.contains("Budgets:     unlimited"), "{text}");
+    }
+
+    #[test]
+    fn test_session_plan_describes_persisted_resume() {
+        let plan = SessionPlan::Resume {
+            tag: "arch".to_string(),
+            session_id: "sess-1".to_string(),
+        };
+        assert_eq!(plan.describe(), "resumes persisted session 'arch' (sess-1)");
+    }
+}
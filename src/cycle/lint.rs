@@ -0,0 +1,265 @@
+//! Lint feedback loop (`[lint]`)
+//!
+//! Runs a linter (e.g. `cargo clippy`) after each linted cycle and logs the
+//! warning count (`lint`), so lint debt left behind by autonomous iterations
+//! stays visible. Warnings that weren't there before the cycle are listed in
+//! the context of the next linted cycle, which can fix them.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::log::CycleOutcome;
+
+/// New warnings kept in the log and shown to the next cycle
+pub const MAX_NEW_WARNINGS: usize = 20;
+
+/// Lint results for one cycle
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LintReport {
+    /// Warnings after the cycle
+    pub warnings: u32,
+    /// Warnings the cycle introduced
+    pub new_count: u32,
+    /// The first of those new warnings (up to `MAX_NEW_WARNINGS`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub new: Vec<String>,
+}
+
+impl LintReport {
+    /// Compare the warnings from before and after a cycle.
+    ///
+    /// Warnings are matched by text with line and column numbers removed, so
+    /// a warning that only moved because lines were added above it isn't new.
+    #[must_use]
+    pub fn new(before: &[String], after: &[String]) -> Self {
+        let mut remaining: HashMap<String, usize> = HashMap::new();
+        for warning in before {
+            *remaining.entry(identity(warning)).or_default() += 1;
+        }
+        let new: Vec<&String> = after
+            .iter()
+            .filter(|warning| match remaining.get_mut(&identity(warning)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            })
+            .collect();
+        Self {
+            warnings: u32::try_from(after.len()).unwrap_or(u32::MAX),
+            new_count: u32::try_from(new.len()).unwrap_or(u32::MAX),
+            new: new.into_iter().take(MAX_NEW_WARNINGS).cloned().collect(),
+        }
+    }
+}
+
+/// Extract warnings from linter output.
+///
+/// With a `pattern`, every matching line is one warning. Otherwise cargo's
+/// format is parsed: a `warning: ...` or `error[E...]: ...` line followed by
+/// ` --> file:line:col`, giving `file:line:col: message`. Lines without a
+/// location, such as `warning: 3 warnings emitted`, are not warnings.
+/// Duplicates (e.g. the same warning in lib and test builds) count once.
+#[must_use]
+pub fn parse_warnings(output: &str, pattern: Option<&Regex>) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    let lines: Vec<&str> = output.lines().collect();
+    for (index, line) in lines.iter().enumerate() {
+        let warning = pattern.map_or_else(
+            || {
+                let message = cargo_message(line)?;
+                let location = lines.get(index + 1)?.trim().strip_prefix("--> ")?;
+                Some(format!("{location}: {message}"))
+            },
+            |pattern| pattern.is_match(line).then(|| line.trim().to_string()),
+        );
+        if let Some(warning) = warning {
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+    warnings
+}
+
+/// The message of a cargo `warning:`/`error:` line (with optional `[code]`).
+fn cargo_message(line: &str) -> Option<&str> {
+    let (level, message) = line.split_once(": ")?;
+    let level = match level.split_once('[') {
+        Some((level, code)) if code.ends_with(']') => level,
+        Some(_) => return None,
+        None => level,
+    };
+    matches!(level, "warning" | "error").then_some(message)
+}
+
+/// A warning with `:line` and `:col` numbers removed.
+fn identity(warning: &str) -> String {
+    let mut out = String::with_capacity(warning.len());
+    let mut chars = warning.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek().is_some_and(char::is_ascii_digit) {
+            while chars.peek().is_some_and(char::is_ascii_digit) {
+                chars.next();
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Run the lint `command` through `sh -c` in `dir` and extract the warnings
+/// from its combined stdout and stderr.
+///
+/// Returns `None` (with a warning) if the command cannot be run. A non-zero
+/// exit is expected when the linter finds problems, so only the output matters.
+pub async fn run_lint(command: &str, pattern: Option<&Regex>, dir: &Path) -> Option<Vec<String>> {
+    let output = match tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Warning: lint command '{command}' could not be run: {e}");
+            return None;
+        }
+    };
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(parse_warnings(&text, pattern))
+}
+
+/// Context block listing the new warnings from the most recent lint run, if
+/// it found any.
+#[must_use]
+pub fn lint_feedback(outcomes: &[CycleOutcome]) -> Option<String> {
+    let (outcome, report) = outcomes
+        .iter()
+        .rev()
+        .find_map(|outcome| outcome.lint.as_ref().map(|report| (outcome, report)))?;
+    if report.new.is_empty() {
+        return None;
+    }
+    let mut lines = vec![
+        "## New Lint Warnings".to_string(),
+        String::new(),
+        format!(
+            "Iteration {} [{}] introduced {} new lint warning(s) ({} in total). Fix them as part of this cycle:",
+            outcome.iteration, outcome.cycle, report.new_count, report.warnings
+        ),
+        String::new(),
+    ];
+    lines.extend(report.new.iter().map(|warning| format!("- {warning}")));
+    if report.new_count as usize > report.new.len() {
+        lines.push(format!(
+            "- ... and {} more",
+            report.new_count as usize - report.new.len()
+        ));
+    }
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::make_test_outcome;
+
+    const CLIPPY_OUTPUT: &str = r"    Checking flow v0.1.0
+warning: unused variable: `x`
+ --> src/main.rs:2:9
+  |
+2 |     let x = 1;
+  |         ^ help: if this is intentional, prefix it with an underscore: `_x`
+
+error[E0425]: cannot find value `y` in this scope
+  --> src/lib.rs:10:5
+   |
+warning: unused variable: `x`
+ --> src/main.rs:2:9
+warning: `flow` (bin) generated 1 warning
+error: could not compile `flow` (lib) due to 1 previous error
+";
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_cargo_warnings() {
+        let warnings = parse_warnings(CLIPPY_OUTPUT, None);
+        assert_eq!(
+            warnings,
+            vec![
+                "src/main.rs:2:9: unused variable: `x`",
+                "src/lib.rs:10:5: cannot find value `y` in this scope",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_warnings_with_pattern() {
+        let pattern = Regex::new(r"^\S+:\d+:\d+: ").unwrap();
+        let output = "src/a.py:3:1: F401 unused import\nFound 1 error.\n";
+        assert_eq!(
+            parse_warnings(output, Some(&pattern)),
+            vec!["src/a.py:3:1: F401 unused import"]
+        );
+    }
+
+    #[test]
+    fn test_report_ignores_moved_warnings() {
+        let before = strings(&["src/a.rs:2:9: unused variable: `x`"]);
+        let after = strings(&[
+            "src/a.rs:7:9: unused variable: `x`",
+            "src/a.rs:12:1: function `f` is never used",
+        ]);
+        let report = LintReport::new(&before, &after);
+        assert_eq!(report.warnings, 2);
+        assert_eq!(report.new_count, 1);
+        assert_eq!(
+            report.new,
+            vec!["src/a.rs:12:1: function `f` is never used"]
+        );
+    }
+
+    #[test]
+    fn test_report_caps_listed_warnings() {
+        let after: Vec<String> = (0..30).map(|i| format!("src/a.rs:{i}:1: w{i}")).collect();
+        let report = LintReport::new(&[], &after);
+        assert_eq!(report.new_count, 30);
+        assert_eq!(report.new.len(), MAX_NEW_WARNINGS);
+    }
+
+    #[test]
+    fn test_feedback_from_latest_lint_run() {
+        let mut linted = make_test_outcome(1, "coding", "Done");
+        linted.lint = Some(LintReport::new(&[], &strings(&["src/a.rs:1:1: bad"])));
+        let unlinted = make_test_outcome(2, "review", "Done");
+        let feedback = lint_feedback(&[linted.clone(), unlinted]).unwrap();
+        assert!(
+            feedback.contains("Iteration 1 [coding] introduced 1 new"),
+            "{feedback}"
+        );
+        assert!(feedback.contains("- src/a.rs:1:1: bad"), "{feedback}");
+
+        let mut clean = make_test_outcome(3, "coding", "Done");
+        clean.lint = Some(LintReport::new(
+            &strings(&["src/a.rs:1:1: bad"]),
+            &strings(&["src/a.rs:1:1: bad"]),
+        ));
+        assert!(lint_feedback(&[linted, clean]).is_none());
+        assert!(lint_feedback(&[]).is_none());
+    }
+}
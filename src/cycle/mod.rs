@@ -2,10 +2,27 @@
 //!
 //! This module handles cycle configuration, execution, and rules.
 
+pub mod arbiter;
+pub mod claim;
+pub mod compaction;
 pub mod config;
 pub mod context;
+pub mod eval;
 pub mod executor;
+pub mod explain;
+pub mod lint;
+pub mod observer;
+pub mod overrides;
+pub mod repeat;
 pub mod router;
 pub mod rules;
+pub mod samples;
 pub mod selector;
+pub mod show;
+pub mod skip;
+pub mod snapshot;
+pub mod stop;
+pub mod success;
 pub mod template;
+pub mod test_guard;
+pub mod user_config;
@@ -0,0 +1,142 @@
+//! Execution observer API for embedders
+//!
+//! Library consumers implement [`ExecutionObserver`] to receive every parsed
+//! stream event, step transition, and gate decision as it happens, rather than
+//! scraping the terminal display. All methods default to no-ops, so an
+//! observer only overrides what it cares about. Pair with
+//! `CycleExecutor::with_display(false)` to silence the built-in stderr output.
+
+use crate::claude::stream::StreamEvent;
+
+/// Receives execution events from a `CycleExecutor` or `FlowRunner`
+///
+/// Callbacks run inline on the executing task, so they should return quickly.
+pub trait ExecutionObserver: Send + Sync {
+    /// A stream-JSON event from Claude Code (already redacted).
+    /// `step` is `None` for single-step cycles.
+    fn on_stream_event(&self, _cycle: &str, _step: Option<&str>, _event: &StreamEvent) {}
+
+    /// A step of a multi-step cycle is starting (`visit` is 1-based).
+    fn on_step_started(&self, _cycle: &str, _step: &str, _visit: u32) {}
+
+    /// A step finished (`exit_code` is `None` if the process was killed).
+    fn on_step_finished(&self, _cycle: &str, _step: &str, _exit_code: Option<i32>) {}
+
+    /// The router chose what follows `from`: the next step, or `None` when
+    /// the cycle is done.
+    fn on_step_routed(&self, _cycle: &str, _from: &str, _to: Option<&str>, _reason: &str) {}
+
+    /// A gate stopped a step or the run: `circuit_breaker`, `max_visits`,
+    /// `permission_denials`, `consecutive_failures`, or `stop_condition`.
+    fn on_gate(&self, _gate: &str, _reason: &str, _cycle: Option<&str>) {}
+}
+
+/// Where a running command reports its stream events and gate decisions
+#[derive(Clone, Copy)]
+pub(crate) struct EventSink<'a> {
+    pub observer: Option<&'a dyn ExecutionObserver>,
+    pub cycle: &'a str,
+    pub step: Option<&'a str>,
+}
+
+impl<'a> EventSink<'a> {
+    /// A sink reporting to `observer` (if any) for `cycle` / `step`.
+    pub const fn new(
+        observer: Option<&'a dyn ExecutionObserver>,
+        cycle: &'a str,
+        step: Option<&'a str>,
+    ) -> Self {
+        Self {
+            observer,
+            cycle,
+            step,
+        }
+    }
+
+    /// Forward a stream event.
+    pub fn event(&self, event: &StreamEvent) {
+        if let Some(observer) = self.observer {
+            observer.on_stream_event(self.cycle, self.step, event);
+        }
+    }
+
+    /// Forward a gate decision.
+    pub fn gate(&self, gate: &str, reason: &str) {
+        if let Some(observer) = self.observer {
+            observer.on_gate(gate, reason, Some(self.cycle));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records every callback as a short string, for assertions.
+    #[derive(Default)]
+    pub struct RecordingObserver {
+        pub calls: Mutex<Vec<String>>,
+    }
+
+    impl RecordingObserver {
+        fn push(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    impl ExecutionObserver for RecordingObserver {
+        fn on_stream_event(&self, cycle: &str, step: Option<&str>, event: &StreamEvent) {
+            let kind = match event {
+                StreamEvent::SystemInit { .. } => "init",
+                StreamEvent::AssistantText { .. } => "text",
+                StreamEvent::ToolUse { .. } => "tool_use",
+                StreamEvent::ToolResult { .. } => "tool_result",
+                StreamEvent::Result { .. } => "result",
+                _ => "other",
+            };
+            self.push(format!("event {cycle}/{} {kind}", step.unwrap_or("-")));
+        }
+
+        fn on_step_started(&self, cycle: &str, step: &str, visit: u32) {
+            self.push(format!("start {cycle}/{step} #{visit}"));
+        }
+
+        fn on_step_finished(&self, cycle: &str, step: &str, exit_code: Option<i32>) {
+            self.push(format!("finish {cycle}/{step} {exit_code:?}"));
+        }
+
+        fn on_step_routed(&self, cycle: &str, from: &str, to: Option<&str>, _reason: &str) {
+            self.push(format!("route {cycle}/{from} -> {}", to.unwrap_or("done")));
+        }
+
+        fn on_gate(&self, gate: &str, _reason: &str, cycle: Option<&str>) {
+            self.push(format!("gate {gate} {}", cycle.unwrap_or("-")));
+        }
+    }
+
+    // --- EventSink tests ---
+
+    #[test]
+    fn test_event_sink_forwards_to_observer() {
+        let observer = RecordingObserver::default();
+        let sink = EventSink::new(Some(&observer), "coding", Some("plan"));
+        sink.event(&StreamEvent::AssistantText {
+            text: "hi".to_string(),
+        });
+        sink.gate("circuit_breaker", "5 errors");
+        assert_eq!(
+            *observer.calls.lock().unwrap(),
+            ["event coding/plan text", "gate circuit_breaker coding"]
+        );
+    }
+
+    #[test]
+    fn test_event_sink_without_observer_is_noop() {
+        let sink = EventSink::new(None, "coding", None);
+        sink.event(&StreamEvent::AssistantText {
+            text: "hi".to_string(),
+        });
+        sink.gate("circuit_breaker", "5 errors");
+    }
+}
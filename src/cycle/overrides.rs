@@ -0,0 +1,605 @@
+//! Cycle inheritance, step templates, config profiles, and command-line overrides
+//!
+//! Resolves `extends = "<cycle>"` and instantiates `[[step_template]]`
+//! definitions, then applies a
+//! `[profile.<name>]` section and `--set key.path=value` assignments to the
+//! raw `cycles.toml` table before it is deserialized, so the merged result
+//! goes through the normal validation. Arrays of tables (`[[cycle]]`,
+//! `[[cycle.step]]`) are addressed by their `name` field:
+//! `cycle.coding.max_turns=30` or `[profile.ci.cycle.coding]`. User-level
+//! defaults are merged underneath first, so the project file always wins.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use toml::{Table, Value};
+
+/// Keys a cycle never inherits through `extends`
+const NOT_INHERITED: [&str; 3] = ["name", "extends", "selectable"];
+
+/// Keys defining a cycle's work; a cycle that sets any of them inherits none
+const WORK_KEYS: [&str; 3] = ["prompt", "prompt_file", "step"];
+
+/// Apply a single `key.path=value` assignment to a parsed config table.
+///
+/// The value is parsed as a TOML value (`30`, `true`, `["a", "b"]`, `"text"`);
+/// anything that isn't valid TOML is taken as a plain string. Missing
+/// intermediate tables are created; unknown array entries are an error.
+pub fn apply_override(root: &mut Table, assignment: &str) -> Result<()> {
+    let (path, raw_value) = assignment
+        .split_once('=')
+        .with_context(|| format!("invalid override '{assignment}': expected key.path=value"))?;
+    let segments: Vec<&str> = path.trim().split('.').map(str::trim).collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        bail!("invalid override '{assignment}': empty key segment");
+    }
+    let (key, parents) = segments
+        .split_last()
+        .with_context(|| format!("invalid override '{assignment}': missing key"))?;
+
+    let mut table = root;
+    let mut i = 0;
+    while i < parents.len() {
+        let segment = parents[i];
+        let entry = table
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(t) => t,
+            Value::Array(items) => {
+                i += 1;
+                let Some(name) = parents.get(i) else {
+                    bail!("invalid override '{assignment}': '{segment}' needs an entry name");
+                };
+                find_named(items, name).with_context(|| {
+                    format!("invalid override '{assignment}': no '{segment}' named '{name}'")
+                })?
+            }
+            _ => bail!("invalid override '{assignment}': '{segment}' is not a table"),
+        };
+        i += 1;
+    }
+
+    table.insert((*key).to_string(), parse_value(raw_value.trim()));
+    Ok(())
+}
+
+/// Resolve `extends = "<cycle>"` on every `[[cycle]]`.
+///
+/// A cycle inherits each key it doesn't set from the cycle it extends, and
+/// from that cycle's ancestors in turn (nested tables merge key by key;
+/// arrays such as `permissions` and `step` are replaced, not appended). The
+/// cycle's work — `prompt`, `prompt_file`, and `step` — is inherited as a
+/// unit, and `name`, `extends`, and `selectable` are never inherited, so a
+/// base cycle can be hidden from the selector without hiding its variants.
+/// Unknown parents and inheritance loops are errors.
+pub fn resolve_cycle_inheritance(root: &mut Table) -> Result<()> {
+    let Some(Value::Array(cycles)) = root.get_mut("cycle") else {
+        return Ok(());
+    };
+    let originals: HashMap<String, Table> = cycles
+        .iter()
+        .filter_map(Value::as_table)
+        .filter_map(|t| Some((t.get("name")?.as_str()?.to_string(), t.clone())))
+        .collect();
+    for cycle in cycles.iter_mut().filter_map(Value::as_table_mut) {
+        let name = cycle
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let mut chain = vec![name.clone()];
+        let mut parent = extends(cycle, &name)?;
+        while let Some(parent_name) = parent {
+            if chain.contains(&parent_name) {
+                bail!(
+                    "Cycle inheritance loop: {} -> {parent_name}",
+                    chain.join(" -> ")
+                );
+            }
+            let base = originals
+                .get(&parent_name)
+                .with_context(|| format!("Cycle '{name}' extends unknown cycle '{parent_name}'"))?;
+            let mut inherited = base.clone();
+            inherited.retain(|key, _| !NOT_INHERITED.contains(&key));
+            if WORK_KEYS.iter().any(|key| cycle.contains_key(*key)) {
+                inherited.retain(|key, _| !WORK_KEYS.contains(&key));
+            }
+            merge_defaults(cycle, &inherited);
+            parent = extends(base, &parent_name)?;
+            chain.push(parent_name);
+        }
+    }
+    Ok(())
+}
+
+/// The parent a cycle table names in `extends`, if any.
+fn extends(cycle: &Table, name: &str) -> Result<Option<String>> {
+    match cycle.get("extends") {
+        None => Ok(None),
+        Some(Value::String(parent)) => Ok(Some(parent.clone())),
+        Some(_) => bail!("Cycle '{name}': 'extends' must be a cycle name"),
+    }
+}
+
+/// Expand steps that set `template = "<name>"` from the matching
+/// `[[step_template]]`.
+///
+/// The `step_template` array is always removed from `root`. The step's own
+/// keys override the template's (nested tables merge key by key), and a step
+/// without a `name` takes the template's. Templates are only checked once
+/// instantiated, when the step goes through normal validation.
+pub fn expand_step_templates(root: &mut Table) -> Result<()> {
+    let mut templates = match root.remove("step_template") {
+        Some(Value::Array(templates)) => templates,
+        Some(_) => bail!("'step_template' must be an array of [[step_template]] sections"),
+        None => return Ok(()),
+    };
+    let Some(Value::Array(cycles)) = root.get_mut("cycle") else {
+        return Ok(());
+    };
+    for cycle in cycles.iter_mut().filter_map(Value::as_table_mut) {
+        let cycle_name = cycle
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let Some(Value::Array(steps)) = cycle.get_mut("step") else {
+            continue;
+        };
+        for step in steps.iter_mut().filter_map(Value::as_table_mut) {
+            let Some(template) = step.remove("template") else {
+                continue;
+            };
+            let Value::String(template) = template else {
+                bail!("Cycle '{cycle_name}': step 'template' must be a template name");
+            };
+            let defaults = find_named(&mut templates, &template).with_context(|| {
+                format!("Cycle '{cycle_name}' uses unknown step template '{template}'")
+            })?;
+            merge_defaults(step, defaults);
+        }
+    }
+    Ok(())
+}
+
+/// Merge the `[profile.<name>]` section into the rest of the config.
+///
+/// The `profile` table is always removed from `root`. A profile mirrors the
+/// config layout: `[profile.ci.global]` merges into `[global]` and
+/// `[profile.ci.cycle.coding]` into the cycle named `coding`. Nested tables
+/// merge key by key; any other value (including arrays) replaces the original.
+pub fn apply_profile(root: &mut Table, name: Option<&str>) -> Result<()> {
+    let profiles = match root.remove("profile") {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => bail!("'profile' must be a table of [profile.<name>] sections"),
+        None => Table::new(),
+    };
+    let Some(name) = name else {
+        return Ok(());
+    };
+    let Some(profile) = profiles.get(name) else {
+        let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        bail!(
+            "Unknown profile '{name}'. Available profiles: {}",
+            if available.is_empty() {
+                "(none)".to_string()
+            } else {
+                available.join(", ")
+            }
+        );
+    };
+    let Value::Table(profile) = profile.clone() else {
+        bail!("profile '{name}' must be a table");
+    };
+    merge_table(root, profile, &format!("profile.{name}"))
+}
+
+/// Merge `defaults` (e.g. the user config) under `root`: nested tables merge
+/// key by key, and any value `root` already sets wins.
+pub fn merge_defaults(root: &mut Table, defaults: &Table) {
+    for (key, value) in defaults {
+        match (root.get_mut(key), value) {
+            (Some(Value::Table(existing)), Value::Table(nested)) => {
+                merge_defaults(existing, nested);
+            }
+            (Some(_), _) => {}
+            (None, value) => {
+                root.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Recursively merge `overlay` into `base`; `path` is used in error messages.
+fn merge_table(base: &mut Table, overlay: Table, path: &str) -> Result<()> {
+    for (key, value) in overlay {
+        let key_path = format!("{path}.{key}");
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(nested)) => {
+                merge_table(existing, nested, &key_path)?;
+            }
+            (Some(Value::Array(items)), Value::Table(by_name)) => {
+                for (entry, nested) in by_name {
+                    let Value::Table(nested) = nested else {
+                        bail!("{key_path}.{entry} must be a table");
+                    };
+                    let target = find_named(items, &entry)
+                        .with_context(|| format!("{key_path}: no '{key}' named '{entry}'"))?;
+                    merge_table(target, nested, &format!("{key_path}.{entry}"))?;
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find the table in an array of tables whose `name` field equals `name`.
+fn find_named<'a>(items: &'a mut [Value], name: &str) -> Option<&'a mut Table> {
+    items.iter_mut().find_map(|item| match item {
+        Value::Table(t) if t.get("name").and_then(Value::as_str) == Some(name) => Some(t),
+        _ => None,
+    })
+}
+
+/// Parse an override value as TOML, falling back to a plain string.
+fn parse_value(raw: &str) -> Value {
+    format!("v = {raw}")
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[global]
+permissions = ["Read"]
+summary_interval = 5
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+max_turns = 100
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+
+[[cycle]]
+name = "review"
+description = "Review"
+"#;
+
+    fn table() -> Table {
+        CONFIG.parse().unwrap()
+    }
+
+    const TEMPLATED: &str = r#"
+[[step_template]]
+name = "review"
+prompt = "Review the change."
+permissions = ["Read"]
+skip_if = { exists = "SKIP", command = "true" }
+
+[[cycle]]
+name = "coding"
+
+[[cycle.step]]
+template = "review"
+
+[[cycle.step]]
+name = "final-review"
+template = "review"
+prompt = "Review everything."
+skip_if = { command = "false" }
+"#;
+
+    fn cycle<'a>(root: &'a Table, name: &str) -> &'a Table {
+        root["cycle"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_table)
+            .find(|t| t["name"].as_str() == Some(name))
+            .unwrap()
+    }
+
+    // --- resolve_cycle_inheritance tests ---
+
+    const INHERITED: &str = r#"
+[[cycle]]
+name = "base-coding"
+description = "Coding"
+selectable = false
+permissions = ["Read"]
+max_turns = 40
+success_when = { command = "cargo test", min_tests_passed = 1 }
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement."
+
+[[cycle]]
+name = "parser-coding"
+extends = "base-coding"
+description = "Coding in the parser"
+success_when = { command = "cargo test -p parser" }
+
+[[cycle]]
+name = "quick-parser-coding"
+extends = "parser-coding"
+max_turns = 10
+prompt = "Make one small parser fix."
+"#;
+
+    #[test]
+    fn test_cycle_inherits_unset_keys_from_its_ancestors() {
+        let mut root: Table = INHERITED.parse().unwrap();
+        resolve_cycle_inheritance(&mut root).unwrap();
+
+        let parser = cycle(&root, "parser-coding");
+        assert_eq!(parser["description"].as_str(), Some("Coding in the parser"));
+        assert_eq!(parser["max_turns"].as_integer(), Some(40));
+        assert_eq!(parser["step"], cycle(&root, "base-coding")["step"]);
+        assert!(
+            !parser.contains_key("selectable"),
+            "selectable isn't inherited"
+        );
+        let success_when = parser["success_when"].as_table().unwrap();
+        assert_eq!(
+            success_when["command"].as_str(),
+            Some("cargo test -p parser")
+        );
+        assert_eq!(success_when["min_tests_passed"].as_integer(), Some(1));
+
+        let quick = cycle(&root, "quick-parser-coding");
+        assert_eq!(quick["description"].as_str(), Some("Coding in the parser"));
+        assert_eq!(quick["max_turns"].as_integer(), Some(10));
+        assert_eq!(quick["permissions"], parser["permissions"]);
+        assert!(!quick.contains_key("step"), "work is inherited as a unit");
+        assert_eq!(quick["extends"].as_str(), Some("parser-coding"));
+    }
+
+    #[test]
+    fn test_cycle_inheritance_rejects_unknown_parents_and_loops() {
+        let mut root: Table = INHERITED
+            .replace("extends = \"base-coding\"", "extends = \"nope\"")
+            .parse()
+            .unwrap();
+        let err = resolve_cycle_inheritance(&mut root).unwrap_err();
+        assert!(
+            err.to_string().contains("extends unknown cycle 'nope'"),
+            "{err}"
+        );
+
+        let mut root: Table = INHERITED
+            .replace(
+                "extends = \"base-coding\"",
+                "extends = \"quick-parser-coding\"",
+            )
+            .parse()
+            .unwrap();
+        let err = resolve_cycle_inheritance(&mut root).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("loop: parser-coding -> quick-parser-coding -> parser-coding"),
+            "{err}"
+        );
+    }
+
+    // --- expand_step_templates tests ---
+
+    #[test]
+    fn test_step_template_fills_in_unset_keys() {
+        let mut root: Table = TEMPLATED.parse().unwrap();
+        expand_step_templates(&mut root).unwrap();
+        assert!(!root.contains_key("step_template"));
+
+        let steps = cycle(&root, "coding")["step"].as_array().unwrap();
+        let plain = steps[0].as_table().unwrap();
+        assert_eq!(plain["name"].as_str(), Some("review"));
+        assert_eq!(plain["prompt"].as_str(), Some("Review the change."));
+        assert!(!plain.contains_key("template"));
+
+        let custom = steps[1].as_table().unwrap();
+        assert_eq!(custom["name"].as_str(), Some("final-review"));
+        assert_eq!(custom["prompt"].as_str(), Some("Review everything."));
+        assert_eq!(custom["permissions"], plain["permissions"]);
+        let skip_if = custom["skip_if"].as_table().unwrap();
+        assert_eq!(skip_if["command"].as_str(), Some("false"));
+        assert_eq!(
+            skip_if["exists"].as_str(),
+            Some("SKIP"),
+            "nested tables merge"
+        );
+    }
+
+    #[test]
+    fn test_unknown_step_template_rejected() {
+        let mut root: Table = TEMPLATED
+            .replace(
+                "template = \"review\"\nprompt",
+                "template = \"reveiw\"\nprompt",
+            )
+            .parse()
+            .unwrap();
+        let err = expand_step_templates(&mut root).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown step template 'reveiw'"),
+            "{err}"
+        );
+    }
+
+    // --- merge_defaults tests ---
+
+    #[test]
+    fn test_defaults_fill_gaps_without_overriding() {
+        let mut root = table();
+        let defaults: Table = r#"
+[global]
+summary_interval = 99
+model = "sonnet"
+
+[notifications]
+channels = []
+"#
+        .parse()
+        .unwrap();
+        merge_defaults(&mut root, &defaults);
+        assert_eq!(root["global"]["summary_interval"].as_integer(), Some(5));
+        assert_eq!(root["global"]["model"].as_str(), Some("sonnet"));
+        assert!(root["notifications"].is_table());
+    }
+
+    // --- apply_profile tests ---
+
+    const PROFILE_CONFIG: &str = r#"
+[global]
+permissions = ["Read"]
+summary_interval = 5
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+max_turns = 100
+permissions = ["Edit(./src/**)"]
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+
+[profile.ci.global]
+summary_interval = 1
+
+[profile.ci.cycle.coding]
+max_turns = 20
+permissions = []
+
+[profile.ci.cycle.coding.step.plan]
+max_turns = 3
+
+[profile.cheap.global]
+permissions = ["Read", "Glob"]
+"#;
+
+    #[test]
+    fn test_profile_merges_global_and_cycles() {
+        let mut root: Table = PROFILE_CONFIG.parse().unwrap();
+        apply_profile(&mut root, Some("ci")).unwrap();
+        assert_eq!(root["global"]["summary_interval"].as_integer(), Some(1));
+        assert_eq!(root["global"]["permissions"].as_array().unwrap().len(), 1);
+        let coding = cycle(&root, "coding");
+        assert_eq!(coding["max_turns"].as_integer(), Some(20));
+        assert!(coding["permissions"].as_array().unwrap().is_empty());
+        let step = &coding["step"].as_array().unwrap()[0];
+        assert_eq!(step["max_turns"].as_integer(), Some(3));
+        assert_eq!(step["prompt"].as_str(), Some("Plan."));
+    }
+
+    #[test]
+    fn test_profile_section_removed_even_when_unselected() {
+        let mut root: Table = PROFILE_CONFIG.parse().unwrap();
+        apply_profile(&mut root, None).unwrap();
+        assert!(root.get("profile").is_none());
+        assert_eq!(root["global"]["summary_interval"].as_integer(), Some(5));
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_available() {
+        let mut root: Table = PROFILE_CONFIG.parse().unwrap();
+        let err = apply_profile(&mut root, Some("prod")).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("Unknown profile 'prod'"), "{msg}");
+        assert!(msg.contains("cheap") && msg.contains("ci"), "{msg}");
+    }
+
+    #[test]
+    fn test_profile_unknown_cycle_is_error() {
+        let mut root = table();
+        root.insert(
+            "profile".to_string(),
+            "[ci.cycle.nope]\nmax_turns = 1\n"
+                .parse::<Table>()
+                .map(Value::Table)
+                .unwrap(),
+        );
+        let err = apply_profile(&mut root, Some("ci")).unwrap_err();
+        assert!(err.to_string().contains("no 'cycle' named 'nope'"), "{err}");
+    }
+
+    // --- apply_override tests ---
+
+    #[test]
+    fn test_override_global_integer() {
+        let mut root = table();
+        apply_override(&mut root, "global.summary_interval=10").unwrap();
+        assert_eq!(root["global"]["summary_interval"].as_integer(), Some(10));
+    }
+
+    #[test]
+    fn test_override_cycle_by_name() {
+        let mut root = table();
+        apply_override(&mut root, "cycle.coding.max_turns=30").unwrap();
+        assert_eq!(cycle(&root, "coding")["max_turns"].as_integer(), Some(30));
+        assert!(cycle(&root, "review").get("max_turns").is_none());
+    }
+
+    #[test]
+    fn test_override_step_by_name() {
+        let mut root = table();
+        apply_override(&mut root, "cycle.coding.step.plan.max_turns=5").unwrap();
+        let step = &cycle(&root, "coding")["step"].as_array().unwrap()[0];
+        assert_eq!(step["max_turns"].as_integer(), Some(5));
+    }
+
+    #[test]
+    fn test_override_creates_missing_tables() {
+        let mut root = table();
+        apply_override(&mut root, "global.stop_when.command=test -f DONE").unwrap();
+        assert_eq!(
+            root["global"]["stop_when"]["command"].as_str(),
+            Some("test -f DONE")
+        );
+    }
+
+    #[test]
+    fn test_override_parses_toml_values() {
+        let mut root = table();
+        apply_override(&mut root, r#"global.permissions=["Read", "Edit"]"#).unwrap();
+        apply_override(&mut root, "global.debug_stream=true").unwrap();
+        apply_override(&mut root, r#"cycle.review.prompt="Quoted""#).unwrap();
+        assert_eq!(root["global"]["permissions"].as_array().unwrap().len(), 2);
+        assert_eq!(root["global"]["debug_stream"].as_bool(), Some(true));
+        assert_eq!(cycle(&root, "review")["prompt"].as_str(), Some("Quoted"));
+    }
+
+    #[test]
+    fn test_override_value_may_contain_equals() {
+        let mut root = table();
+        apply_override(&mut root, "cycle.review.prompt=a=b").unwrap();
+        assert_eq!(cycle(&root, "review")["prompt"].as_str(), Some("a=b"));
+    }
+
+    #[test]
+    fn test_override_unknown_cycle_is_error() {
+        let mut root = table();
+        let err = apply_override(&mut root, "cycle.nope.max_turns=3").unwrap_err();
+        assert!(err.to_string().contains("no 'cycle' named 'nope'"), "{err}");
+    }
+
+    #[test]
+    fn test_override_rejects_malformed_assignments() {
+        let mut root = table();
+        assert!(apply_override(&mut root, "global.summary_interval").is_err());
+        assert!(apply_override(&mut root, "global..summary_interval=3").is_err());
+        assert!(apply_override(&mut root, "global.summary_interval.x=3").is_err());
+        assert!(apply_override(&mut root, "cycle.coding=3").is_err());
+    }
+}
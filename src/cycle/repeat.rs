@@ -0,0 +1,86 @@
+//! Looping steps — `[cycle.step.repeat_until]`
+//!
+//! Checked after a step succeeds. While a condition is unmet the step routes
+//! back to itself, so "fix the failing tests" can run until `cargo test`
+//! passes without an LLM router deciding. `max_visits` bounds the loop.
+
+use std::path::Path;
+
+use crate::cycle::config::RepeatUntilConfig;
+use crate::cycle::stop::command_succeeds;
+
+/// Evaluate a step's repeat conditions against its result text, running the
+/// `command` in the project directory `dir`.
+///
+/// Returns `None` when every configured condition holds (the step is done),
+/// or a description of the first unmet one.
+pub async fn check_repeat_until(
+    repeat_until: &RepeatUntilConfig,
+    result_text: &str,
+    dir: &Path,
+) -> Option<String> {
+    if let Some(ref pattern) = repeat_until.result_matches {
+        // Patterns are validated at config load; an invalid one never matches.
+        if !regex::Regex::new(pattern).is_ok_and(|re| re.is_match(result_text)) {
+            return Some(format!("result text did not match /{pattern}/"));
+        }
+    }
+
+    if let Some(ref command) = repeat_until.command {
+        if !command_succeeds(command, dir).await {
+            return Some(format!("`{command}` failed"));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat_until(command: Option<&str>, result_matches: Option<&str>) -> RepeatUntilConfig {
+        RepeatUntilConfig {
+            command: command.map(str::to_string),
+            result_matches: result_matches.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_done_when_command_succeeds() {
+        assert!(
+            check_repeat_until(&repeat_until(Some("true"), None), "", Path::new("."))
+                .await
+                .is_none()
+        );
+        let unmet =
+            check_repeat_until(&repeat_until(Some("false"), None), "", Path::new(".")).await;
+        assert_eq!(unmet.as_deref(), Some("`false` failed"));
+    }
+
+    #[tokio::test]
+    async fn test_done_when_result_matches() {
+        let config = repeat_until(None, Some(r"(?i)all tests pass"));
+        assert!(
+            check_repeat_until(&config, "All tests pass now", Path::new("."))
+                .await
+                .is_none()
+        );
+        let unmet = check_repeat_until(&config, "2 tests still failing", Path::new("."))
+            .await
+            .unwrap();
+        assert!(unmet.contains("did not match"), "{unmet}");
+    }
+
+    #[tokio::test]
+    async fn test_requires_every_condition() {
+        let config = repeat_until(Some("false"), Some("DONE"));
+        assert!(check_repeat_until(&config, "DONE", Path::new("."))
+            .await
+            .is_some());
+        let config = repeat_until(Some("true"), Some("DONE"));
+        assert!(check_repeat_until(&config, "DONE", Path::new("."))
+            .await
+            .is_none());
+    }
+}
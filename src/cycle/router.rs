@@ -1,13 +1,15 @@
 //! Step router — determines the next step to execute in a multi-step cycle.
 //!
-//! Supports two routing modes:
+//! Supports three routing modes:
 //! - **Sequential** (default): proceed to the next step in TOML order.
 //! - **LLM**: invoke Claude Code to choose the next step based on the
 //!   completed step's output text and the available step names.
+//! - **Script**: evaluate the step's Rhai `router_script`, which sees the
+//!   step's result text, exit code, and visit counts.
 
 use std::collections::HashMap;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
 use crate::claude::cli::{build_command, run_for_result};
 use crate::cycle::config::{StepConfig, StepRouter};
@@ -55,6 +57,12 @@ impl VisitTracker {
         self.visits.get(step_name).copied().unwrap_or(0)
     }
 
+    /// Visit counts by step name.
+    #[must_use]
+    pub const fn counts(&self) -> &HashMap<String, u32> {
+        &self.visits
+    }
+
     /// Check whether visiting a step would exceed its `max_visits` limit.
     #[must_use]
     pub fn would_exceed(&self, step_name: &str, max_visits: u32) -> bool {
@@ -181,26 +189,127 @@ async fn route_with_llm(
         .context("Failed to parse step routing from Claude response")
 }
 
+/// Maximum Rhai operations per router script evaluation (guards against endless loops).
+const MAX_SCRIPT_OPERATIONS: u64 = 100_000;
+
+/// A sandboxed Rhai engine for router scripts.
+fn script_engine() -> rhai::Engine {
+    let mut engine = rhai::Engine::new();
+    engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+    engine
+}
+
+/// Check that a router script compiles.
+///
+/// # Errors
+/// Returns the Rhai syntax error.
+pub fn compile_router_script(script: &str) -> Result<()> {
+    script_engine()
+        .compile(script)
+        .map(|_| ())
+        .map_err(|e| anyhow!("Invalid router_script: {e}"))
+}
+
+/// Evaluate a step's router script.
+///
+/// The script sees `step` (completed step name), `steps` (all step names),
+/// `result_text`, `exit_code` (`-1` if killed by a signal), and `visits`
+/// (step name → visit count). It returns a step name, `"DONE"`, or `()` to
+/// fall back to sequential routing (`Ok(None)`).
+fn route_with_script(
+    script: &str,
+    completed_step_name: &str,
+    result_text: &str,
+    exit_code: Option<i32>,
+    all_steps: &[StepConfig],
+    visit_tracker: &VisitTracker,
+) -> Result<Option<RouteDecision>> {
+    let mut scope = rhai::Scope::new();
+    scope.push("step", completed_step_name.to_string());
+    scope.push(
+        "steps",
+        all_steps
+            .iter()
+            .map(|s| rhai::Dynamic::from(s.name.clone()))
+            .collect::<rhai::Array>(),
+    );
+    scope.push("result_text", result_text.to_string());
+    scope.push("exit_code", i64::from(exit_code.unwrap_or(-1)));
+    scope.push(
+        "visits",
+        visit_tracker
+            .counts()
+            .iter()
+            .map(|(name, count)| (name.as_str().into(), rhai::Dynamic::from(i64::from(*count))))
+            .collect::<rhai::Map>(),
+    );
+
+    let value = script_engine()
+        .eval_with_scope::<rhai::Dynamic>(&mut scope, script)
+        .map_err(|e| anyhow!("router_script for step '{completed_step_name}' failed: {e}"))?;
+    if value.is_unit() {
+        return Ok(None);
+    }
+    let Ok(next) = value.into_string() else {
+        bail!("router_script for step '{completed_step_name}' must return a step name, \"DONE\", or ()");
+    };
+    if next.eq_ignore_ascii_case("done") {
+        return Ok(Some(RouteDecision::Done {
+            reason: "Router script finished the cycle".to_string(),
+        }));
+    }
+    if !all_steps.iter().any(|s| s.name == next) {
+        bail!("router_script for step '{completed_step_name}' returned unknown step '{next}'");
+    }
+    Ok(Some(RouteDecision::GoTo {
+        step_name: next,
+        reason: "Router script".to_string(),
+    }))
+}
+
+/// The sequential successor of `completed_step_index`, as a decision.
+fn sequential_decision(
+    completed_step_index: usize,
+    all_steps: &[StepConfig],
+) -> Option<RouteDecision> {
+    route_sequential(completed_step_index, all_steps.len()).map(|next_idx| RouteDecision::GoTo {
+        step_name: all_steps[next_idx].name.clone(),
+        reason: "Sequential progression".to_string(),
+    })
+}
+
 /// Determine the next step to execute after the current step completes.
 ///
 /// For `Sequential` routing, this is a simple index increment.
 /// For `Llm` routing, this invokes Claude Code to make the decision.
+/// For `Script` routing, this evaluates the step's `router_script`.
 ///
 /// Returns `Ok(None)` when the cycle is complete (no more steps).
 pub(crate) async fn determine_next_step(
     completed_step: &StepConfig,
     completed_step_index: usize,
     result_text: &str,
+    exit_code: Option<i32>,
     all_steps: &[StepConfig],
     visit_tracker: &VisitTracker,
 ) -> Result<Option<RouteDecision>> {
     match completed_step.router {
-        StepRouter::Sequential => Ok(route_sequential(completed_step_index, all_steps.len()).map(
-            |next_idx| RouteDecision::GoTo {
-                step_name: all_steps[next_idx].name.clone(),
-                reason: "Sequential progression".to_string(),
-            },
-        )),
+        StepRouter::Sequential => Ok(sequential_decision(completed_step_index, all_steps)),
+        StepRouter::Script => {
+            let script = completed_step
+                .router_script
+                .as_deref()
+                .context("router = \"script\" requires router_script")?;
+            let decision = route_with_script(
+                script,
+                &completed_step.name,
+                result_text,
+                exit_code,
+                all_steps,
+                visit_tracker,
+            )?;
+            Ok(decision.or_else(|| sequential_decision(completed_step_index, all_steps)))
+        }
         StepRouter::Llm => {
             let available: Vec<&str> = all_steps
                 .iter()
@@ -424,12 +533,21 @@ mod tests {
         StepConfig {
             name: name.to_string(),
             session: None,
+            persist: false,
+            fresh_session: false,
             prompt: format!("Do {name}"),
             permissions: vec![],
+            permission_mode: None,
+            context: None,
             router,
             max_visits,
             max_turns: None,
             max_cost_usd: None,
+            router_script: None,
+            prompt_file: None,
+            max_tokens: None,
+            skip_if: None,
+            repeat_until: None,
         }
     }
 
@@ -441,7 +559,7 @@ mod tests {
             make_step("test", StepRouter::Sequential, 3),
         ];
         let tracker = VisitTracker::new();
-        let result = determine_next_step(&steps[0], 0, "Done planning", &steps, &tracker)
+        let result = determine_next_step(&steps[0], 0, "Done planning", Some(0), &steps, &tracker)
             .await
             .unwrap();
         assert_eq!(
@@ -460,9 +578,104 @@ mod tests {
             make_step("implement", StepRouter::Sequential, 3),
         ];
         let tracker = VisitTracker::new();
-        let result = determine_next_step(&steps[1], 1, "Done implementing", &steps, &tracker)
+        let result =
+            determine_next_step(&steps[1], 1, "Done implementing", Some(0), &steps, &tracker)
+                .await
+                .unwrap();
+        assert!(result.is_none());
+    }
+
+    // --- script router tests ---
+
+    fn script_step(name: &str, script: &str) -> StepConfig {
+        StepConfig {
+            router_script: Some(script.to_string()),
+            ..make_step(name, StepRouter::Script, 3)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_script_router_routes_on_result_text() {
+        let steps = vec![
+            script_step(
+                "review",
+                r#"if result_text.contains("LGTM") { "DONE" } else { "fix" }"#,
+            ),
+            make_step("fix", StepRouter::Sequential, 3),
+        ];
+        let tracker = VisitTracker::new();
+        let result = determine_next_step(&steps[0], 0, "Needs work", Some(0), &steps, &tracker)
             .await
             .unwrap();
-        assert!(result.is_none());
+        assert!(
+            matches!(result, Some(RouteDecision::GoTo { ref step_name, .. }) if step_name == "fix")
+        );
+        let result = determine_next_step(&steps[0], 0, "LGTM!", Some(0), &steps, &tracker)
+            .await
+            .unwrap();
+        assert!(matches!(result, Some(RouteDecision::Done { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_script_router_sees_visits_and_exit_code() {
+        let steps = vec![script_step(
+            "review",
+            r#"if visits["review"] >= 2 || exit_code != 0 { "DONE" } else { "review" }"#,
+        )];
+        let mut tracker = VisitTracker::new();
+        tracker.record("review");
+        let result = determine_next_step(&steps[0], 0, "", Some(0), &steps, &tracker)
+            .await
+            .unwrap();
+        assert!(matches!(result, Some(RouteDecision::GoTo { .. })));
+        let result = determine_next_step(&steps[0], 0, "", Some(1), &steps, &tracker)
+            .await
+            .unwrap();
+        assert!(matches!(result, Some(RouteDecision::Done { .. })));
+        tracker.record("review");
+        let result = determine_next_step(&steps[0], 0, "", Some(0), &steps, &tracker)
+            .await
+            .unwrap();
+        assert!(matches!(result, Some(RouteDecision::Done { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_script_router_unit_falls_back_to_sequential() {
+        let steps = vec![
+            script_step("plan", "()"),
+            make_step("implement", StepRouter::Sequential, 3),
+        ];
+        let tracker = VisitTracker::new();
+        let result = determine_next_step(&steps[0], 0, "", Some(0), &steps, &tracker)
+            .await
+            .unwrap();
+        assert!(
+            matches!(result, Some(RouteDecision::GoTo { ref step_name, .. }) if step_name == "implement")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_script_router_rejects_unknown_step_and_bad_type() {
+        let tracker = VisitTracker::new();
+        let steps = vec![script_step("plan", r#""nowhere""#)];
+        let err = determine_next_step(&steps[0], 0, "", Some(0), &steps, &tracker)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown step 'nowhere'"), "{err}");
+        let steps = vec![script_step("plan", "42")];
+        let err = determine_next_step(&steps[0], 0, "", Some(0), &steps, &tracker)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("must return"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_script_router_stops_runaway_loop() {
+        let steps = vec![script_step("plan", "loop {}")];
+        let tracker = VisitTracker::new();
+        let err = determine_next_step(&steps[0], 0, "", Some(0), &steps, &tracker)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("router_script"), "{err}");
     }
 }
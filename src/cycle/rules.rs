@@ -1,21 +1,30 @@
 //! Cycle rules engine
 //!
 //! Determines which cycles should trigger after a given cycle completes,
-//! based on the `after` dependencies and frequency constraints in cycle configuration.
+//! based on the `after`, `after_all`, and `after_failures_of` dependencies and
+//! frequency constraints in cycle configuration.
 
-use crate::cycle::config::FlowConfig;
+use chrono::{DateTime, Utc};
+
+use crate::cycle::config::{CycleConfig, FlowConfig, MinInterval};
 use crate::log::CycleOutcome;
 
 /// Find cycles that should trigger after the given cycle completes.
 ///
 /// A cycle triggers if:
-/// 1. Its `after` list contains the completed cycle name
-/// 2. Its `min_interval` constraint is satisfied (enough iterations have passed since last run)
+/// 1. Its `after` (alias `after_any`) list contains the completed cycle name, or
+///    its `after_all` list contains it and every other listed cycle has also
+///    run since this cycle last ran, or its `after_failures_of` list contains
+///    it and the completed cycle's latest log entry is a failure
+/// 2. Its `min_interval` constraint is satisfied (enough iterations, or enough
+///    wall-clock time for duration values, have passed since last run)
 ///
-/// The `log` parameter provides execution history for frequency checking.
+/// The `log` parameter provides execution history for frequency checking and
+/// should already include the completed cycle's entry.
 /// If `min_interval` is `None`, the cycle always triggers (backward compatible).
-/// If `min_interval` is `Some(n)`, at least `n` iterations must have elapsed since
-/// this cycle last ran.
+/// If `min_interval` is an iteration count `n`, at least `n` log entries must
+/// have been written since this cycle last ran. If it is a duration, that much
+/// wall-clock time must have passed since its latest log entry's timestamp.
 ///
 /// Returns cycle names in config definition order.
 #[must_use]
@@ -27,23 +36,97 @@ pub fn find_triggered_cycles<'a>(
     config
         .cycles
         .iter()
-        .filter(|c| c.after.iter().any(|dep| dep == completed_cycle))
-        .filter(|c| {
-            let Some(min_interval) = c.min_interval else {
-                return true; // No constraint — always trigger
-            };
+        .filter(|c| is_triggered_by(c, completed_cycle, log))
+        .filter(|c| min_interval_satisfied(c, log, Utc::now()))
+        .map(|c| c.name.as_str())
+        .collect()
+}
+
+/// Whether `cycle`'s `min_interval` allows it to run again at `now`.
+fn min_interval_satisfied(cycle: &CycleConfig, log: &[CycleOutcome], now: DateTime<Utc>) -> bool {
+    match cycle.min_interval {
+        None => true, // No constraint — always trigger
+        Some(MinInterval::Iterations(min_interval)) => {
             // Count how many log entries ago this cycle last ran.
             // This is immune to iteration-number resets across runs
             // because it only looks at position in the append-only log.
             log.iter()
                 .rev()
-                .position(|entry| entry.cycle == c.name)
+                .position(|entry| entry.cycle == cycle.name)
                 .is_none_or(|d| u32::try_from(d).unwrap_or(u32::MAX) >= min_interval)
+        }
+        Some(MinInterval::Duration(secs)) => log
+            .iter()
+            .rev()
+            .find(|entry| entry.cycle == cycle.name)
+            .is_none_or(|entry| {
+                let elapsed = now.signed_duration_since(entry.timestamp).num_seconds();
+                u64::try_from(elapsed).unwrap_or(0) >= secs
+            }),
+    }
+}
+
+/// Find cycles whose `every_n_iterations` cadence is due.
+///
+/// A cycle is due once at least `n` log entries have been written since it
+/// last ran (or since the start of the log, if it never ran). Like
+/// `min_interval`, this counts log positions rather than iteration numbers so
+/// it is immune to resets across runs.
+///
+/// Returns cycle names in config definition order.
+#[must_use]
+pub fn find_periodic_cycles<'a>(config: &'a FlowConfig, log: &[CycleOutcome]) -> Vec<&'a str> {
+    config
+        .cycles
+        .iter()
+        .filter(|c| {
+            let Some(every) = c.every_n_iterations else {
+                return false;
+            };
+            let since_last_run = log
+                .iter()
+                .rev()
+                .position(|entry| entry.cycle == c.name)
+                .unwrap_or(log.len());
+            u32::try_from(since_last_run).unwrap_or(u32::MAX) >= every
         })
         .map(|c| c.name.as_str())
         .collect()
 }
 
+/// Whether `completed_cycle` finishing satisfies one of `cycle`'s trigger rules.
+fn is_triggered_by(cycle: &CycleConfig, completed_cycle: &str, log: &[CycleOutcome]) -> bool {
+    let listed = |deps: &[String]| deps.iter().any(|dep| dep == completed_cycle);
+
+    if listed(&cycle.after) {
+        return true;
+    }
+
+    if listed(&cycle.after_failures_of)
+        && log
+            .iter()
+            .rev()
+            .find(|entry| entry.cycle == completed_cycle)
+            .is_some_and(|entry| !entry.is_success())
+    {
+        return true;
+    }
+
+    if listed(&cycle.after_all) {
+        // Only entries since this cycle last ran count toward the requirement
+        let since_last_run = log
+            .iter()
+            .rposition(|entry| entry.cycle == cycle.name)
+            .map_or(log, |pos| &log[pos + 1..]);
+        return cycle
+            .after_all
+            .iter()
+            .all(|dep| since_last_run.iter().any(|entry| entry.cycle == *dep));
+    }
+
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -191,11 +274,34 @@ after = ["coding", "testing"]
                 max_permission_denials: 10,
                 circuit_breaker_repeated: 5,
                 max_consecutive_failures: 3,
+                transient_retries: 3,
+                transient_backoff_secs: 30,
                 summary_interval: 5,
                 vars: std::collections::HashMap::new(),
+                debug_stream: false,
+                max_run_duration_secs: None,
+                stop_when: None,
+                system_prompt: None,
+                append_system_prompt: None,
+                git_branch_template: None,
+                git_on_finish: None,
+                model: None,
             },
             selector: None,
+            doctor: None,
+            gc: None,
+            compaction: None,
+            test_parser: None,
+            test_guard: None,
+            lint: None,
             cycles: vec![],
+            redaction: None,
+            sandbox: None,
+            notifications: None,
+            hooks: None,
+            display: None,
+            snippets: std::collections::HashMap::new(),
+            pricing: std::collections::HashMap::new(),
         };
         let triggered = find_triggered_cycles(&config, "anything", &[]);
         assert!(triggered.is_empty());
@@ -405,6 +511,207 @@ after = ["coding"]
         assert_eq!(triggered, vec!["gardening"]);
     }
 
+    // --- after_all / after_failures_of tests ---
+
+    const RICH_TRIGGERS_CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "testing"
+description = "Testing"
+prompt = "Test"
+
+[[cycle]]
+name = "release"
+description = "Release"
+prompt = "Release"
+after_all = ["coding", "testing"]
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review"
+after_failures_of = ["coding"]
+"#;
+
+    #[test]
+    fn test_after_all_waits_for_every_dependency() {
+        let config = FlowConfig::parse(RICH_TRIGGERS_CONFIG).unwrap();
+        let log = vec![make_log_entry(1, "coding")];
+        assert!(find_triggered_cycles(&config, "coding", &log).is_empty());
+
+        let log = vec![make_log_entry(1, "coding"), make_log_entry(2, "testing")];
+        assert_eq!(
+            find_triggered_cycles(&config, "testing", &log),
+            vec!["release"]
+        );
+    }
+
+    #[test]
+    fn test_after_all_only_counts_runs_since_last_trigger() {
+        let config = FlowConfig::parse(RICH_TRIGGERS_CONFIG).unwrap();
+        // coding + testing ran, release ran, then only testing again
+        let log = vec![
+            make_log_entry(1, "coding"),
+            make_log_entry(2, "testing"),
+            make_log_entry(3, "release"),
+            make_log_entry(4, "testing"),
+        ];
+        assert!(find_triggered_cycles(&config, "testing", &log).is_empty());
+    }
+
+    #[test]
+    fn test_after_failures_of_triggers_only_on_failure() {
+        let config = FlowConfig::parse(RICH_TRIGGERS_CONFIG).unwrap();
+        let success = vec![make_log_entry(1, "coding")];
+        assert!(find_triggered_cycles(&config, "coding", &success).is_empty());
+
+        let failure = vec![make_test_outcome(1, "coding", "Failed with exit code 1")];
+        assert_eq!(
+            find_triggered_cycles(&config, "coding", &failure),
+            vec!["review"]
+        );
+    }
+
+    #[test]
+    fn test_after_failures_of_uses_latest_outcome() {
+        let config = FlowConfig::parse(RICH_TRIGGERS_CONFIG).unwrap();
+        let log = vec![
+            make_test_outcome(1, "coding", "Failed with exit code 1"),
+            make_log_entry(2, "coding"),
+        ];
+        assert!(find_triggered_cycles(&config, "coding", &log).is_empty());
+    }
+
+    // --- time-based min_interval tests ---
+
+    fn gardening_after_coding_duration_config() -> FlowConfig {
+        FlowConfig::parse(
+            r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "Garden"
+after = ["coding"]
+min_interval = "6h"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn make_log_entry_at(iteration: u32, cycle: &str, hours_ago: i64) -> CycleOutcome {
+        let mut entry = make_log_entry(iteration, cycle);
+        entry.timestamp = Utc::now() - chrono::Duration::hours(hours_ago);
+        entry
+    }
+
+    #[test]
+    fn test_min_interval_duration_blocks_when_too_recent() {
+        let config = gardening_after_coding_duration_config();
+        let log = vec![
+            make_log_entry_at(1, "gardening", 2),
+            make_log_entry_at(2, "coding", 0),
+        ];
+        assert!(find_triggered_cycles(&config, "coding", &log).is_empty());
+    }
+
+    #[test]
+    fn test_min_interval_duration_allows_when_enough_time_elapsed() {
+        let config = gardening_after_coding_duration_config();
+        let log = vec![
+            make_log_entry_at(1, "gardening", 7),
+            make_log_entry_at(2, "coding", 0),
+        ];
+        assert_eq!(
+            find_triggered_cycles(&config, "coding", &log),
+            vec!["gardening"]
+        );
+    }
+
+    #[test]
+    fn test_min_interval_duration_ignores_entry_count() {
+        let config = gardening_after_coding_duration_config();
+        let mut log = vec![make_log_entry_at(1, "gardening", 1)];
+        log.extend((2..=30).map(|i| make_log_entry_at(i, "coding", 0)));
+        assert!(find_triggered_cycles(&config, "coding", &log).is_empty());
+    }
+
+    #[test]
+    fn test_min_interval_duration_allows_when_never_ran() {
+        let config = gardening_after_coding_duration_config();
+        let log = vec![make_log_entry_at(1, "coding", 0)];
+        assert_eq!(
+            find_triggered_cycles(&config, "coding", &log),
+            vec!["gardening"]
+        );
+    }
+
+    // --- find_periodic_cycles tests ---
+
+    const PERIODIC_CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "docs"
+description = "Docs"
+prompt = "Docs"
+every_n_iterations = 3
+"#;
+
+    #[test]
+    fn test_periodic_not_due_before_cadence() {
+        let config = FlowConfig::parse(PERIODIC_CONFIG).unwrap();
+        let log = vec![make_log_entry(1, "coding"), make_log_entry(2, "coding")];
+        assert!(find_periodic_cycles(&config, &log).is_empty());
+    }
+
+    #[test]
+    fn test_periodic_due_when_never_ran() {
+        let config = FlowConfig::parse(PERIODIC_CONFIG).unwrap();
+        let log: Vec<_> = (1..=3).map(|i| make_log_entry(i, "coding")).collect();
+        assert_eq!(find_periodic_cycles(&config, &log), vec!["docs"]);
+    }
+
+    #[test]
+    fn test_periodic_counts_from_last_run() {
+        let config = FlowConfig::parse(PERIODIC_CONFIG).unwrap();
+        let mut log: Vec<_> = (1..=3).map(|i| make_log_entry(i, "coding")).collect();
+        log.push(make_log_entry(4, "docs"));
+        log.push(make_log_entry(5, "coding"));
+        log.push(make_log_entry(6, "coding"));
+        assert!(find_periodic_cycles(&config, &log).is_empty());
+
+        log.push(make_log_entry(7, "coding"));
+        assert_eq!(find_periodic_cycles(&config, &log), vec!["docs"]);
+    }
+
+    #[test]
+    fn test_periodic_ignores_cycles_without_cadence() {
+        let config = test_config();
+        let log: Vec<_> = (1..=20).map(|i| make_log_entry(i, "coding")).collect();
+        assert!(find_periodic_cycles(&config, &log).is_empty());
+    }
+
     #[test]
     fn test_empty_log_triggers_with_min_interval() {
         let config = gardening_after_coding_config(Some(10));
@@ -0,0 +1,198 @@
+//! Competitive multi-sample cycles (`samples = N`)
+//!
+//! A cycle with `samples` set runs that many times from the same starting
+//! working tree. Each sample's result is captured as a snapshot and the tree
+//! (and sessions file) is reset before the next one, so samples never see each
+//! other's edits or sessions. A judge
+//! — Claude Code with no tool permissions — then compares the outcomes and
+//! diffs and picks one; its snapshot is restored and the others are discarded.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::claude::cli::{build_command, run_for_reply};
+use crate::git::truncated_diff;
+
+/// Largest `samples` value a cycle may set
+pub const MAX_SAMPLES: u32 = 10;
+
+/// Characters of each sample's diff shown to the judge
+const MAX_DIFF_CHARS: usize = 8000;
+
+/// One sample as the judge sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleSummary {
+    /// 1-based sample number
+    pub number: u32,
+    /// Final result text of the sample
+    pub result_text: String,
+    /// `git diff` of the sample against the starting tree (possibly truncated)
+    pub diff: String,
+}
+
+/// The judge's pick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JudgeVerdict {
+    /// 1-based number of the chosen sample
+    pub sample: u32,
+    /// One-sentence justification
+    pub reason: String,
+}
+
+/// The diff between two captured trees, cut to a size the judge can read.
+///
+/// # Errors
+/// Returns an error if `git diff` fails.
+pub fn sample_diff(root: &Path, base_tree: &str, sample_tree: &str) -> Result<String> {
+    truncated_diff(root, base_tree, sample_tree, MAX_DIFF_CHARS)
+}
+
+/// Build the prompt asking the judge to pick the best of `samples`.
+#[must_use]
+pub fn build_judge_prompt(cycle_name: &str, task: &str, samples: &[SampleSummary]) -> String {
+    let mut listing = String::new();
+    for sample in samples {
+        let diff = if sample.diff.trim().is_empty() {
+            "(no changes)"
+        } else {
+            sample.diff.trim_end()
+        };
+        let _ = writeln!(
+            listing,
+            "### Sample {}\n\nResult:\n{}\n\nDiff:\n```diff\n{diff}\n```\n",
+            sample.number,
+            sample.result_text.trim()
+        );
+    }
+    format!(
+        r#"You are judging competing attempts at the same task in an automated coding pipeline.
+
+The cycle "{cycle_name}" was run {count} times independently with this prompt:
+
+---
+{task}
+---
+
+## Samples
+
+{listing}## Instructions
+Pick the sample that best accomplishes the task: correct, complete, and no larger than it needs to be.
+
+Respond with ONLY a JSON object on a single line, no other text:
+{{"sample": <sample number>, "reason": "<one sentence explanation>"}}"#,
+        count = samples.len(),
+    )
+}
+
+/// Parse the judge's response, accepting only one of the `candidates`.
+#[must_use]
+pub fn parse_judge_response(response: &str, candidates: &[u32]) -> Option<JudgeVerdict> {
+    response.lines().find_map(|line| {
+        let trimmed = line.trim();
+        if !(trimmed.starts_with('{') && trimmed.ends_with('}')) {
+            return None;
+        }
+        let value: serde_json::Value = serde_json::from_str(trimmed).ok()?;
+        let sample = u32::try_from(value.get("sample")?.as_u64()?).ok()?;
+        if !candidates.contains(&sample) {
+            return None;
+        }
+        let reason = value
+            .get("reason")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        Some(JudgeVerdict { sample, reason })
+    })
+}
+
+/// Ask Claude Code to pick the best of `samples`. Also returns what the
+/// judge cost, if Claude Code reported it.
+///
+/// # Errors
+/// Returns an error if Claude Code fails or names no valid sample.
+pub async fn judge_samples(
+    cycle_name: &str,
+    task: &str,
+    samples: &[SampleSummary],
+) -> Result<(JudgeVerdict, Option<f64>)> {
+    let prompt = build_judge_prompt(cycle_name, task, samples);
+    let reply = run_for_reply(build_command(&prompt, &[])).await?;
+    let candidates: Vec<u32> = samples.iter().map(|s| s.number).collect();
+    let verdict = parse_judge_response(&reply.text, &candidates)
+        .context("Failed to parse the judge's pick from Claude response")?;
+    Ok((verdict, reply.cost_usd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::run;
+
+    fn sample(number: u32, diff: &str) -> SampleSummary {
+        SampleSummary {
+            number,
+            result_text: format!("Attempt {number} done"),
+            diff: diff.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_judge_prompt_lists_every_sample() {
+        let prompt = build_judge_prompt(
+            "coding",
+            "Implement the next task",
+            &[sample(1, "+fn a() {}\n"), sample(3, "")],
+        );
+        assert!(prompt.contains("run 2 times"), "{prompt}");
+        assert!(prompt.contains("Implement the next task"), "{prompt}");
+        assert!(prompt.contains("### Sample 1"), "{prompt}");
+        assert!(prompt.contains("+fn a() {}"), "{prompt}");
+        assert!(prompt.contains("### Sample 3"), "{prompt}");
+        assert!(prompt.contains("(no changes)"), "{prompt}");
+        assert!(prompt.contains(r#"{"sample": <sample number>"#), "{prompt}");
+    }
+
+    #[test]
+    fn test_parse_judge_response() {
+        let verdict = parse_judge_response(
+            "Here you go:\n{\"sample\": 2, \"reason\": \"Smallest correct diff\"}",
+            &[1, 2],
+        )
+        .unwrap();
+        assert_eq!(verdict.sample, 2);
+        assert_eq!(verdict.reason, "Smallest correct diff");
+    }
+
+    #[test]
+    fn test_parse_judge_response_rejects_unknown_sample() {
+        assert!(parse_judge_response(r#"{"sample": 3, "reason": "x"}"#, &[1, 2]).is_none());
+        assert!(parse_judge_response("Sample 1 is best", &[1, 2]).is_none());
+    }
+
+    #[test]
+    fn test_sample_diff_truncates_long_diffs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        for args in [
+            vec!["init", "-q"],
+            vec!["config", "user.email", "flow@example.com"],
+            vec!["config", "user.name", "Flow"],
+        ] {
+            run(root, &args).unwrap();
+        }
+        std::fs::write(root.join("a.txt"), "one\n").unwrap();
+        run(root, &["add", "a.txt"]).unwrap();
+        let base = run(root, &["write-tree"]).unwrap();
+        std::fs::write(root.join("a.txt"), "two\n".repeat(MAX_DIFF_CHARS)).unwrap();
+        run(root, &["add", "a.txt"]).unwrap();
+        let changed = run(root, &["write-tree"]).unwrap();
+
+        let diff = sample_diff(root, &base, &changed).unwrap();
+        assert!(diff.contains("-one"), "{diff}");
+        assert!(diff.ends_with("... (diff truncated)"));
+        assert!(sample_diff(root, &base, &base).unwrap().is_empty());
+    }
+}
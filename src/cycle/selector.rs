@@ -4,13 +4,17 @@
 //! for Claude Code, which returns the next cycle to execute.
 
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 
 use crate::claude::cli::{build_command, run_for_result};
 use crate::cli::display::format_duration;
-use crate::cycle::config::FlowConfig;
+use crate::cycle::config::{FlowConfig, DEFAULT_SELECTOR_TIMEOUT_SECS};
+use crate::log::index::LogIndex;
 use crate::log::CycleOutcome;
+use crate::tasks::TaskFile;
 
 /// A pending task extracted from TODO.md.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,51 +23,82 @@ pub struct TodoTask {
     pub priority: String,
     /// Task description
     pub description: String,
+    /// Unchecked checkboxes nested under the task, at any depth
+    pub subtasks: Vec<String>,
 }
 
+/// Priority of a task that names none, inline or through its section
+pub const DEFAULT_TODO_PRIORITY: &str = "P2";
+
 /// Parse TODO.md content and extract pending (unchecked) tasks with priorities.
 ///
-/// Looks for lines matching `- [ ] <description>` followed by a line containing
-/// `Priority: P<n>`. Only returns unchecked tasks.
+/// A task is a top-level `- [ ] <description>` checkbox (`*` and `+` bullets
+/// work too). Its priority comes from, in order of precedence:
+/// - a following `Priority: P<n>` line (bare or as a `- Priority:` bullet),
+/// - an inline `priority:p1` tag in the description (removed from it),
+/// - the nearest enclosing heading that names one (`## P1`, `## P0: Bugs`,
+///   or `Critical` / `High` / `Medium` / `Low` for P0-P3),
+/// - otherwise [`DEFAULT_TODO_PRIORITY`].
+///
+/// Checkboxes indented under a task are its subtasks: unchecked ones are
+/// listed on the task rather than counted separately, and those under a
+/// checked task are done with it. Tasks claimed by a running cycle
+/// (`- [~]`, see [`crate::cycle::claim`]) aren't pending.
 #[must_use]
 pub fn parse_todo_tasks(content: &str) -> Vec<TodoTask> {
-    let lines: Vec<&str> = content.lines().collect();
-    let mut tasks = Vec::new();
-
-    for (i, line) in lines.iter().enumerate() {
+    let mut tasks: Vec<TodoTask> = Vec::new();
+    // Enclosing headings: (level, priority named by the heading)
+    let mut headings: Vec<(usize, Option<String>)> = Vec::new();
+    // Indent of the current top-level checkbox and, if pending, its task
+    // index and whether a `Priority:` line already set its priority
+    let mut open: Option<(usize, Option<(usize, bool)>)> = None;
+
+    for line in content.lines() {
         let trimmed = line.trim();
 
-        // Match unchecked task lines: "- [ ] <description>"
-        if let Some(desc) = trimmed.strip_prefix("- [ ] ") {
-            let description = desc.trim().to_string();
-            if description.is_empty() {
-                continue;
-            }
+        if let Some((level, text)) = parse_heading(trimmed) {
+            headings.retain(|(l, _)| *l < level);
+            headings.push((level, heading_priority(text)));
+            open = None;
+            continue;
+        }
 
-            // Look at subsequent lines for "Priority: P<n>"
-            let mut priority = None;
-            for lookahead in lines.iter().skip(i + 1).take(5) {
-                let la = lookahead.trim();
-                if la.starts_with("- [") {
-                    // Hit the next task, stop looking
-                    break;
-                }
-                if let Some(rest) = la.strip_prefix("- Priority:") {
-                    priority = Some(rest.trim().to_string());
-                    break;
-                }
-                // Also match standalone "Priority: P0" lines (indented metadata)
-                if let Some(rest) = la.strip_prefix("Priority:") {
-                    priority = Some(rest.trim().to_string());
-                    break;
+        if let Some((checked, desc)) = parse_checkbox(trimmed) {
+            let indent = indent_width(line);
+            if let Some((top_indent, task)) = open {
+                if indent > top_indent {
+                    if let (false, Some((index, _))) = (checked, task) {
+                        let (desc, _) = split_inline_priority(desc);
+                        if !desc.is_empty() {
+                            tasks[index].subtasks.push(desc);
+                        }
+                    }
+                    continue;
                 }
             }
+            let (description, inline) = split_inline_priority(desc);
+            if checked || description.is_empty() {
+                open = Some((indent, None));
+                continue;
+            }
+            let priority = inline
+                .or_else(|| headings.iter().rev().find_map(|(_, p)| p.clone()))
+                .unwrap_or_else(|| DEFAULT_TODO_PRIORITY.to_string());
+            tasks.push(TodoTask {
+                priority,
+                description,
+                subtasks: Vec::new(),
+            });
+            open = Some((indent, Some((tasks.len() - 1, false))));
+            continue;
+        }
 
-            if let Some(priority) = priority {
-                tasks.push(TodoTask {
-                    priority,
-                    description,
-                });
+        // "- Priority: P0" bullets and standalone "Priority: P0" metadata
+        let metadata = trimmed.strip_prefix("- ").unwrap_or(trimmed);
+        if let Some(rest) = metadata.strip_prefix("Priority:") {
+            if let Some((indent, Some((index, false)))) = open {
+                tasks[index].priority = rest.trim().to_string();
+                open = Some((indent, Some((index, true))));
             }
         }
     }
@@ -71,6 +106,95 @@ pub fn parse_todo_tasks(content: &str) -> Vec<TodoTask> {
     tasks
 }
 
+/// `(level, text)` of a Markdown `#` heading line.
+fn parse_heading(trimmed: &str) -> Option<(usize, &str)> {
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let text = &trimmed[level..];
+    (text.is_empty() || text.starts_with(' ')).then(|| (level, text.trim()))
+}
+
+/// Priority a heading names by its first word: `P1`, or `Critical` / `High`
+/// / `Medium` / `Low` for P0-P3.
+fn heading_priority(text: &str) -> Option<String> {
+    let word = text
+        .split_whitespace()
+        .next()?
+        .trim_end_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_ascii_lowercase();
+    let level = match word.as_str() {
+        "critical" => "P0",
+        "high" => "P1",
+        "medium" => "P2",
+        "low" => "P3",
+        _ => return priority_level(&word),
+    };
+    Some(level.to_string())
+}
+
+/// `P<n>` for a `p<n>` token (any case).
+fn priority_level(token: &str) -> Option<String> {
+    let digits = token.strip_prefix(['p', 'P'])?;
+    (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then(|| format!("P{digits}"))
+}
+
+/// `(checked, description)` of a `- [ ] ...` / `- [x] ...` list item. A task
+/// claimed by a running cycle (`- [~] ...`) counts as checked: it isn't pending.
+pub(crate) fn parse_checkbox(trimmed: &str) -> Option<(bool, &str)> {
+    let item = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+        .or_else(|| trimmed.strip_prefix("+ "))?;
+    let (checked, rest) = if let Some(rest) = item.strip_prefix("[ ]") {
+        (false, rest)
+    } else if let Some(rest) = item
+        .strip_prefix("[x]")
+        .or_else(|| item.strip_prefix("[X]"))
+        .or_else(|| item.strip_prefix("[~]"))
+    {
+        (true, rest)
+    } else {
+        return None;
+    };
+    (rest.is_empty() || rest.starts_with(' ')).then_some((checked, rest.trim()))
+}
+
+/// Leading whitespace width, counting a tab as four spaces.
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Split an inline `priority:p1` tag (any case, optional space after the
+/// colon) out of a task description.
+pub(crate) fn split_inline_priority(desc: &str) -> (String, Option<String>) {
+    let mut priority = None;
+    let mut words: Vec<&str> = Vec::new();
+    let mut tokens = desc.split_whitespace().peekable();
+    while let Some(word) = tokens.next() {
+        if priority.is_none() && word.len() >= 9 && word[..9].eq_ignore_ascii_case("priority:") {
+            let value = if word.len() > 9 {
+                Some(&word[9..])
+            } else {
+                tokens.peek().copied()
+            };
+            if let Some(level) = value.and_then(priority_level) {
+                if word.len() == 9 {
+                    tokens.next();
+                }
+                priority = Some(level);
+                continue;
+            }
+        }
+        words.push(word);
+    }
+    (words.join(" "), priority)
+}
+
 /// Format parsed TODO tasks as a compact string for the selector prompt.
 #[must_use]
 pub fn format_todo_summary(tasks: &[TodoTask]) -> String {
@@ -78,20 +202,20 @@ pub fn format_todo_summary(tasks: &[TodoTask]) -> String {
         return "No pending tasks found in TODO.md".to_string();
     }
 
-    let mut by_priority: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut by_priority: HashMap<&str, Vec<&TodoTask>> = HashMap::new();
     for task in tasks {
-        by_priority
-            .entry(&task.priority)
-            .or_default()
-            .push(&task.description);
+        by_priority.entry(&task.priority).or_default().push(task);
     }
 
     let mut lines = Vec::new();
     for p in &["P0", "P1", "P2", "P3"] {
-        if let Some(descs) = by_priority.get(p) {
-            lines.push(format!("{p}: {} task(s)", descs.len()));
-            for desc in descs {
-                lines.push(format!("  - {desc}"));
+        if let Some(tasks) = by_priority.get(p) {
+            lines.push(format!("{p}: {} task(s)", tasks.len()));
+            for task in tasks {
+                lines.push(format!("  - {}", task.description));
+                for subtask in &task.subtasks {
+                    lines.push(format!("    - {subtask}"));
+                }
             }
         }
     }
@@ -99,8 +223,11 @@ pub fn format_todo_summary(tasks: &[TodoTask]) -> String {
     lines.join("\n")
 }
 
+/// Number of recent outcomes shown to the cycle selector
+pub const SELECTOR_RECENT_OUTCOMES: usize = 5;
+
 /// Compact summary of recent log history for the cycle selector prompt.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct LogSummary {
     /// Total number of iterations in the log
     pub total_iterations: u32,
@@ -142,42 +269,36 @@ pub struct RecentOutcome {
 /// Takes the last `max_recent` outcomes for the `recent_outcomes` field.
 #[must_use]
 pub fn summarize_log(log: &[CycleOutcome], max_recent: usize) -> LogSummary {
-    let total_iterations = log.iter().map(|o| o.iteration).max().unwrap_or(0);
-
-    let mut cycle_counts: HashMap<String, u32> = HashMap::new();
-    let mut cycle_successes: HashMap<String, u32> = HashMap::new();
-    let mut total_cost = 0.0;
-
-    for outcome in log {
-        *cycle_counts.entry(outcome.cycle.clone()).or_insert(0) += 1;
-
-        if outcome.is_success() {
-            *cycle_successes.entry(outcome.cycle.clone()).or_insert(0) += 1;
-        }
+    let recent = &log[log.len().saturating_sub(max_recent)..];
+    LogSummary::from_index(&LogIndex::from_outcomes(log), recent)
+}
 
-        if let Some(cost) = outcome.total_cost_usd {
-            total_cost += cost;
+impl LogSummary {
+    /// Build a summary from the log's running totals and its last few
+    /// outcomes (oldest first), without scanning the whole log.
+    #[must_use]
+    pub fn from_index(index: &LogIndex, recent: &[CycleOutcome]) -> Self {
+        Self {
+            total_iterations: index.total_iterations,
+            cycle_counts: index
+                .cycles
+                .iter()
+                .map(|(name, stats)| (name.clone(), stats.runs))
+                .collect(),
+            cycle_success_rates: index
+                .cycles
+                .iter()
+                .map(|(name, stats)| (name.clone(), stats.success_rate()))
+                .collect(),
+            total_cost_usd: index.total_cost_usd,
+            recent_outcomes: recent.iter().rev().map(RecentOutcome::from).collect(),
         }
     }
+}
 
-    let cycle_success_rates: HashMap<String, f64> = cycle_counts
-        .iter()
-        .map(|(name, &count)| {
-            let successes = cycle_successes.get(name).copied().unwrap_or(0);
-            let rate = if count > 0 {
-                f64::from(successes) / f64::from(count)
-            } else {
-                0.0
-            };
-            (name.clone(), rate)
-        })
-        .collect();
-
-    let recent_outcomes: Vec<RecentOutcome> = log
-        .iter()
-        .rev()
-        .take(max_recent)
-        .map(|o| RecentOutcome {
+impl From<&CycleOutcome> for RecentOutcome {
+    fn from(o: &CycleOutcome) -> Self {
+        Self {
             iteration: o.iteration,
             cycle: o.cycle.clone(),
             success: o.is_success(),
@@ -187,15 +308,7 @@ pub fn summarize_log(log: &[CycleOutcome], max_recent: usize) -> LogSummary {
             cost_usd: o.total_cost_usd,
             duration_secs: o.duration_secs,
             denial_count: o.permission_denial_count.unwrap_or(0),
-        })
-        .collect();
-
-    LogSummary {
-        total_iterations,
-        cycle_counts,
-        cycle_success_rates,
-        total_cost_usd: total_cost,
-        recent_outcomes,
+        }
     }
 }
 
@@ -257,35 +370,76 @@ pub fn format_log_summary(summary: &LogSummary, config: &FlowConfig) -> String {
     lines.join("\n")
 }
 
+/// Template variable holding the task the selector chose (empty when the
+/// cycle wasn't chosen by the selector or it named no task)
+pub const SELECTED_TASK_VAR: &str = "selected_task";
+
+/// Template variable holding the `tasks.toml` id of the selected task
+/// (empty for TODO.md tasks)
+pub const SELECTED_TASK_ID_VAR: &str = "selected_task_id";
+
 /// The result of cycle selection.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CycleSelection {
     /// The name of the selected cycle
     pub cycle: String,
+    /// The task the cycle should work on, if the selector named one: a TODO.md
+    /// description, or a `tasks.toml` id
+    pub task: Option<String>,
     /// The reason for selection
     pub reason: String,
+    /// Whether this is `selector.fallback_cycle` standing in for a failed selection
+    pub fallback: bool,
 }
 
 /// Build the prompt for the cycle selector.
 ///
-/// Composes log summary, TODO summary, and available cycles into a prompt
-/// that asks Claude to return a JSON selection.
+/// Composes log summary, task summary, and available cycles into a prompt
+/// that asks Claude to return a JSON selection. Tasks come from `structured`
+/// (`tasks.toml`) when given, otherwise from the TODO.md content.
 #[must_use]
 pub fn build_selector_prompt(
     config: &FlowConfig,
-    log: &[CycleOutcome],
+    summary: &LogSummary,
     todo_content: &str,
+    structured: Option<&TaskFile>,
 ) -> String {
-    let summary = summarize_log(log, 5);
-    let log_text = format_log_summary(&summary, config);
-    let tasks = parse_todo_tasks(todo_content);
-    let todo_text = format_todo_summary(&tasks);
+    let log_text = format_log_summary(summary, config);
+    let (task_heading, todo_text, task_field) = structured.map_or_else(
+        || {
+            let tasks = parse_todo_tasks(todo_content);
+            let field = (!tasks.is_empty())
+                .then_some("the TODO.md task the cycle should work on, copied verbatim, or empty");
+            ("TODO.md State", format_todo_summary(&tasks), field)
+        },
+        |file| {
+            let field = (!file.ready().is_empty())
+                .then_some("id of the ready task the cycle should work on, or empty");
+            ("Tasks (tasks.toml)", file.selector_summary(), field)
+        },
+    );
 
     let cycle_list: Vec<String> = config
         .cycles
         .iter()
-        .map(|c| format!("- {}: {}", c.name, c.description))
+        .filter(|c| c.selectable)
+        .map(|c| {
+            let weight = c
+                .weight
+                .map(|w| format!(" (weight {w})"))
+                .unwrap_or_default();
+            format!("- {}{weight}: {}", c.name, c.description)
+        })
         .collect();
+    let weight_note = if config
+        .cycles
+        .iter()
+        .any(|c| c.selectable && c.weight.is_some())
+    {
+        "\n\nWeights express relative preference (default 1): over time, choose each cycle roughly in proportion to its weight."
+    } else {
+        ""
+    };
 
     let custom_prompt = config
         .selector
@@ -304,31 +458,39 @@ pub fn build_selector_prompt(
         },
         |prompt| format!("## Selector Guidance\n{prompt}"),
     );
+    let response_format = task_field.map_or_else(
+        || r#"{"cycle": "<name>", "reason": "<one sentence explanation>"}"#.to_string(),
+        |field| {
+            format!(r#"{{"cycle": "<name>", "task": "<{field}>", "reason": "<one sentence explanation>"}}"#)
+        },
+    );
 
     format!(
-        r#"You are Flow's cycle selector. Analyze the current state and choose the next cycle to execute.
+        r"You are Flow's cycle selector. Analyze the current state and choose the next cycle to execute.
 
 ## Run History
 {log_text}
 
-## TODO.md State
+## {task_heading}
 {todo_text}
 
 ## Available Cycles
-{cycle_names}
+{cycle_names}{weight_note}
 
 {criteria}
 
-Choose the next cycle. Respond with ONLY a JSON object on a single line, no other text:
-{{"cycle": "<name>", "reason": "<one sentence explanation>"}}"#,
+Choose the next cycle from the Available Cycles only. Respond with ONLY a JSON object on a single line, no other text:
+{response_format}",
         cycle_names = cycle_list.join("\n"),
     )
 }
 
 /// Parse a cycle selection from the selector's response text.
 ///
-/// Looks for a JSON object containing `"cycle"` and `"reason"` fields.
+/// Looks for a JSON object containing `"cycle"` and `"reason"` fields, and
+/// an optional non-empty `"task"`.
 /// Falls back to matching cycle names in the text if JSON parsing fails.
+/// Only cycles with `selectable = true` are accepted.
 #[must_use]
 pub fn parse_selection(response: &str, config: &FlowConfig) -> Option<CycleSelection> {
     // Try to find and parse a JSON object in the response
@@ -340,11 +502,18 @@ pub fn parse_selection(response: &str, config: &FlowConfig) -> Option<CycleSelec
                     value.get("cycle").and_then(|v| v.as_str()),
                     value.get("reason").and_then(|v| v.as_str()),
                 ) {
-                    // Validate the cycle exists
-                    if config.get_cycle(cycle).is_some() {
+                    // Validate the cycle exists and is eligible for selection
+                    if config.get_cycle(cycle).is_some_and(|c| c.selectable) {
+                        let task = value
+                            .get("task")
+                            .and_then(|v| v.as_str())
+                            .map(str::trim)
+                            .filter(|t| !t.is_empty());
                         return Some(CycleSelection {
                             cycle: cycle.to_string(),
+                            task: task.map(str::to_string),
                             reason: reason.to_string(),
+                            fallback: false,
                         });
                     }
                 }
@@ -353,11 +522,13 @@ pub fn parse_selection(response: &str, config: &FlowConfig) -> Option<CycleSelec
     }
 
     // Fallback: look for a known cycle name mentioned in the response
-    for cycle in &config.cycles {
+    for cycle in config.cycles.iter().filter(|c| c.selectable) {
         if response.contains(&cycle.name) {
             return Some(CycleSelection {
                 cycle: cycle.name.clone(),
+                task: None,
                 reason: "Extracted from response text (JSON parse failed)".to_string(),
+                fallback: false,
             });
         }
     }
@@ -374,15 +545,18 @@ pub fn parse_selection(response: &str, config: &FlowConfig) -> Option<CycleSelec
 /// * `config` - Flow configuration with available cycles
 /// * `log` - Recent log history
 /// * `todo_content` - Raw TODO.md content
+/// * `structured` - Parsed `tasks.toml`, used instead of TODO.md when present
 ///
 /// # Returns
 /// The selected cycle, or an error if Claude Code fails or no cycle can be parsed.
+/// See [`select_with_fallback`] for the timeout and fallback cycle.
 pub async fn select_cycle(
     config: &FlowConfig,
-    log: &[CycleOutcome],
+    summary: &LogSummary,
     todo_content: &str,
+    structured: Option<&TaskFile>,
 ) -> Result<CycleSelection> {
-    let prompt = build_selector_prompt(config, log, todo_content);
+    let prompt = build_selector_prompt(config, summary, todo_content, structured);
     let cmd = build_command(&prompt, &[]);
     let result_text = run_for_result(cmd).await?;
 
@@ -390,6 +564,33 @@ pub async fn select_cycle(
         .context("Failed to parse cycle selection from Claude response")
 }
 
+/// Await `selection` for at most `selector.timeout_secs`. If it fails or
+/// times out and `selector.fallback_cycle` is set, select that cycle instead
+/// (with `fallback = true` and the error as the reason).
+///
+/// # Errors
+/// Returns the selection error (or timeout) when no fallback cycle is configured.
+pub async fn select_with_fallback(
+    config: &FlowConfig,
+    selection: impl Future<Output = Result<CycleSelection>>,
+) -> Result<CycleSelection> {
+    let selector = config.selector.as_ref();
+    let timeout_secs = selector.map_or(DEFAULT_SELECTOR_TIMEOUT_SECS, |s| s.timeout_secs);
+    let result = tokio::time::timeout(Duration::from_secs(timeout_secs), selection)
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("selector timed out after {timeout_secs}s")));
+    match (result, selector.and_then(|s| s.fallback_cycle.as_ref())) {
+        (Ok(selection), _) => Ok(selection),
+        (Err(e), Some(fallback)) => Ok(CycleSelection {
+            cycle: fallback.clone(),
+            task: None,
+            reason: format!("Fallback cycle (selection failed: {e:#})"),
+            fallback: true,
+        }),
+        (Err(e), None) => Err(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -440,6 +641,16 @@ mod tests {
         assert!(summary.recent_outcomes[0].success);
     }
 
+    #[test]
+    fn test_summary_from_index_matches_full_scan() {
+        let log: Vec<CycleOutcome> = (1..=8)
+            .map(|i| make_outcome(i, "coding", "Step", Some(0.5)))
+            .collect();
+        let summary = LogSummary::from_index(&LogIndex::from_outcomes(&log), &log[5..]);
+        assert_eq!(summary, summarize_log(&log, 3));
+        assert!(summarize_log(&log, 0).recent_outcomes.is_empty());
+    }
+
     #[test]
     fn test_summarize_multiple_cycles() {
         let log = vec![
@@ -577,7 +788,7 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_todo_no_priority_skipped() {
+    fn test_parse_todo_no_priority_gets_default() {
         let content = r"
 - [ ] Task without priority info
 
@@ -585,8 +796,98 @@ mod tests {
   - Priority: P0
 ";
         let tasks = parse_todo_tasks(content);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].description, "Task without priority info");
+        assert_eq!(tasks[0].priority, DEFAULT_TODO_PRIORITY);
+        assert_eq!(tasks[1].priority, "P0");
+    }
+
+    #[test]
+    fn test_parse_todo_inline_priority_tag() {
+        let content = "- [ ] Fix login redirect priority:p1\n* [ ] Priority: P0 Patch the leak\n";
+        let tasks = parse_todo_tasks(content);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].description, "Fix login redirect");
+        assert_eq!(tasks[0].priority, "P1");
+        assert_eq!(tasks[1].description, "Patch the leak");
+        assert_eq!(tasks[1].priority, "P0");
+    }
+
+    #[test]
+    fn test_parse_todo_heading_sections_set_priority() {
+        let content = r"
+# TODO
+
+## P0: Bugs
+- [ ] Crash on empty config
+
+### Regressions
+- [ ] Slow startup
+
+## High priority
+- [ ] Add retries
+
+## Ideas
+- [ ] Dark mode
+
+## Low
+- [ ] Polish docs priority:p2
+";
+        let tasks = parse_todo_tasks(content);
+        let priorities: Vec<(&str, &str)> = tasks
+            .iter()
+            .map(|t| (t.description.as_str(), t.priority.as_str()))
+            .collect();
+        assert_eq!(
+            priorities,
+            vec![
+                ("Crash on empty config", "P0"),
+                ("Slow startup", "P0"),
+                ("Add retries", "P1"),
+                ("Dark mode", DEFAULT_TODO_PRIORITY),
+                ("Polish docs", "P2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_todo_priority_line_overrides_heading() {
+        let content = "## P3\n- [ ] Urgent after all\n  Priority: P0\n";
+        let tasks = parse_todo_tasks(content);
+        assert_eq!(tasks[0].priority, "P0");
+    }
+
+    #[test]
+    fn test_parse_todo_nested_subtasks() {
+        let content = r"
+- [ ] Build the exporter
+  - [x] Design the format
+  - [ ] Write the CSV writer
+    - [ ] Quote special characters
+  - Priority: P1
+- [x] Done parent
+  - [ ] Leftover subtask
+- [ ] Next task
+";
+        let tasks = parse_todo_tasks(content);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].description, "Build the exporter");
+        assert_eq!(tasks[0].priority, "P1");
+        assert_eq!(
+            tasks[0].subtasks,
+            vec!["Write the CSV writer", "Quote special characters"]
+        );
+        assert_eq!(tasks[1].description, "Next task");
+        assert!(tasks[1].subtasks.is_empty());
+    }
+
+    #[test]
+    fn test_parse_todo_skips_claimed_tasks() {
+        let content =
+            "- [~] Being worked on <!-- flow:claimed -->\n  - [ ] Its subtask\n- [ ] Free task\n";
+        let tasks = parse_todo_tasks(content);
         assert_eq!(tasks.len(), 1);
-        assert_eq!(tasks[0].description, "Task with priority");
+        assert_eq!(tasks[0].description, "Free task");
     }
 
     #[test]
@@ -601,20 +902,24 @@ mod tests {
             TodoTask {
                 priority: "P0".to_string(),
                 description: "Critical thing".to_string(),
+                subtasks: vec!["Write the failing test".to_string()],
             },
             TodoTask {
                 priority: "P1".to_string(),
                 description: "Less critical".to_string(),
+                subtasks: Vec::new(),
             },
             TodoTask {
                 priority: "P0".to_string(),
                 description: "Another critical".to_string(),
+                subtasks: Vec::new(),
             },
         ];
         let formatted = format_todo_summary(&tasks);
         assert!(formatted.contains("P0: 2 task(s)"));
         assert!(formatted.contains("P1: 1 task(s)"));
         assert!(formatted.contains("Critical thing"));
+        assert!(formatted.contains("\n    - Write the failing test\n"));
     }
 
     // --- build_selector_prompt tests ---
@@ -622,7 +927,7 @@ mod tests {
     #[test]
     fn test_build_selector_prompt_includes_cycles() {
         let config = make_config(&["coding", "gardening"]);
-        let prompt = build_selector_prompt(&config, &[], "");
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), "", None);
         assert!(prompt.contains("coding"));
         assert!(prompt.contains("gardening"));
         assert!(prompt.contains("cycle selector"));
@@ -632,7 +937,12 @@ mod tests {
     fn test_build_selector_prompt_includes_log_context() {
         let config = make_config(&["coding"]);
         let log = vec![make_outcome(1, "coding", "Implemented feature", Some(2.0))];
-        let prompt = build_selector_prompt(&config, &log, "");
+        let prompt = build_selector_prompt(
+            &config,
+            &summarize_log(&log, SELECTOR_RECENT_OUTCOMES),
+            "",
+            None,
+        );
         assert!(prompt.contains("Total iterations: 1"));
         assert!(prompt.contains("coding=1"));
     }
@@ -641,9 +951,33 @@ mod tests {
     fn test_build_selector_prompt_includes_todo_context() {
         let config = make_config(&["coding"]);
         let todo = "- [ ] Fix the bug\n  - Priority: P0\n";
-        let prompt = build_selector_prompt(&config, &[], todo);
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), todo, None);
         assert!(prompt.contains("P0: 1 task(s)"));
         assert!(prompt.contains("Fix the bug"));
+        assert!(prompt.contains(r#""task": "<the TODO.md task"#));
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), "", None);
+        assert!(!prompt.contains(r#""task""#));
+    }
+
+    #[test]
+    fn test_build_selector_prompt_uses_structured_tasks() {
+        let config = make_config(&["coding"]);
+        let tasks =
+            TaskFile::parse("[[task]]\nid = \"api\"\ntitle = \"Invoice API\"\npriority = \"P0\"\n")
+                .unwrap();
+        let prompt = build_selector_prompt(
+            &config,
+            &LogSummary::default(),
+            "- [ ] Ignored TODO task\n",
+            Some(&tasks),
+        );
+        assert!(prompt.contains("## Tasks (tasks.toml)"), "{prompt}");
+        assert!(prompt.contains("[P0] api: Invoice API"), "{prompt}");
+        assert!(!prompt.contains("Ignored TODO task"), "{prompt}");
+        assert!(
+            prompt.contains(r#""task": "<id of the ready task"#),
+            "{prompt}"
+        );
     }
 
     // --- parse_selection tests ---
@@ -655,6 +989,17 @@ mod tests {
         let selection = parse_selection(response, &config).unwrap();
         assert_eq!(selection.cycle, "coding");
         assert_eq!(selection.reason, "P0 tasks pending");
+        assert_eq!(selection.task, None);
+    }
+
+    #[test]
+    fn test_parse_selection_with_task() {
+        let config = make_config(&["coding", "gardening"]);
+        let response = r#"{"cycle": "coding", "task": " Fix the login bug ", "reason": "P0"}"#;
+        let selection = parse_selection(response, &config).unwrap();
+        assert_eq!(selection.task.as_deref(), Some("Fix the login bug"));
+        let response = r#"{"cycle": "gardening", "task": "", "reason": "Tidy"}"#;
+        assert_eq!(parse_selection(response, &config).unwrap().task, None);
     }
 
     #[test]
@@ -699,6 +1044,68 @@ mod tests {
         assert_eq!(selection.cycle, "gardening");
     }
 
+    // --- selector weights and exclusions ---
+
+    const WEIGHTED_CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Write code"
+prompt = "Code"
+weight = 3
+
+[[cycle]]
+name = "gardening"
+description = "Tidy up"
+prompt = "Garden"
+
+[[cycle]]
+name = "recovery"
+description = "Fix failures"
+prompt = "Fix"
+selectable = false
+"#;
+
+    #[test]
+    fn test_build_selector_prompt_shows_weights() {
+        let config = FlowConfig::parse(WEIGHTED_CONFIG).unwrap();
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), "", None);
+        assert!(prompt.contains("- coding (weight 3): Write code"));
+        assert!(prompt.contains("- gardening: Tidy up"));
+        assert!(prompt.contains("relative preference"));
+    }
+
+    #[test]
+    fn test_build_selector_prompt_omits_weight_note_without_weights() {
+        let config = make_config(&["coding", "gardening"]);
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), "", None);
+        assert!(!prompt.contains("relative preference"));
+    }
+
+    #[test]
+    fn test_build_selector_prompt_excludes_non_selectable() {
+        let config = FlowConfig::parse(WEIGHTED_CONFIG).unwrap();
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), "", None);
+        assert!(!prompt.contains("- recovery"));
+    }
+
+    #[test]
+    fn test_parse_selection_rejects_non_selectable_json() {
+        let config = FlowConfig::parse(WEIGHTED_CONFIG).unwrap();
+        let response = r#"{"cycle": "recovery", "reason": "Things are broken"}"#;
+        assert!(parse_selection(response, &config).is_none());
+    }
+
+    #[test]
+    fn test_parse_selection_fallback_skips_non_selectable() {
+        let config = FlowConfig::parse(WEIGHTED_CONFIG).unwrap();
+        let response = "I think recovery is needed, then gardening.";
+        let selection = parse_selection(response, &config).unwrap();
+        assert_eq!(selection.cycle, "gardening");
+    }
+
     // --- build_selector_prompt with custom selector criteria ---
 
     #[test]
@@ -721,7 +1128,7 @@ description = "Gardening"
 prompt = "Garden"
 "#;
         let config = FlowConfig::parse(toml).unwrap();
-        let prompt = build_selector_prompt(&config, &[], "");
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), "", None);
         assert!(
             prompt.contains("Custom guidance: always pick gardening first."),
             "Prompt should include custom selector criteria"
@@ -735,7 +1142,7 @@ prompt = "Garden"
     #[test]
     fn test_build_selector_prompt_falls_back_without_selector() {
         let config = make_config(&["coding", "gardening"]);
-        let prompt = build_selector_prompt(&config, &[], "");
+        let prompt = build_selector_prompt(&config, &LogSummary::default(), "", None);
         assert!(
             prompt.contains("## Selection Criteria"),
             "Prompt should include hardcoded Selection Criteria heading when no selector configured"
@@ -860,4 +1267,78 @@ prompt = "Garden"
             "Zero denials should not appear: {formatted}"
         );
     }
+
+    // --- select_with_fallback tests ---
+
+    fn fallback_config(selector: &str) -> FlowConfig {
+        FlowConfig::parse(&format!(
+            "[global]\npermissions = []\n\n[selector]\n{selector}\n\n\
+             [[cycle]]\nname = \"coding\"\ndescription = \"c\"\nprompt = \"c\"\n\n\
+             [[cycle]]\nname = \"review\"\ndescription = \"r\"\nprompt = \"r\"\n"
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_select_with_fallback_passes_through_success() {
+        let config = fallback_config("fallback_cycle = \"review\"");
+        let chosen = CycleSelection {
+            cycle: "coding".to_string(),
+            task: None,
+            reason: "work left".to_string(),
+            fallback: false,
+        };
+        let selection = select_with_fallback(&config, async { Ok(chosen.clone()) })
+            .await
+            .unwrap();
+        assert_eq!(selection, chosen);
+    }
+
+    #[tokio::test]
+    async fn test_select_with_fallback_uses_fallback_on_error() {
+        let config = fallback_config("fallback_cycle = \"review\"");
+        let selection = select_with_fallback(&config, async { Err(anyhow!("garbage")) })
+            .await
+            .unwrap();
+        assert_eq!(selection.cycle, "review");
+        assert!(selection.fallback);
+        assert!(selection.reason.contains("garbage"), "{}", selection.reason);
+    }
+
+    #[tokio::test]
+    async fn test_select_with_fallback_times_out() {
+        let config = fallback_config("timeout_secs = 1\nfallback_cycle = \"review\"");
+        let selection = select_with_fallback(&config, std::future::pending())
+            .await
+            .unwrap();
+        assert_eq!(selection.cycle, "review");
+        assert!(
+            selection.reason.contains("timed out after 1s"),
+            "{}",
+            selection.reason
+        );
+    }
+
+    #[tokio::test]
+    async fn test_select_without_fallback_returns_error() {
+        let config = fallback_config("prompt = \"\"");
+        assert_eq!(
+            config.selector.as_ref().unwrap().timeout_secs,
+            DEFAULT_SELECTOR_TIMEOUT_SECS
+        );
+        let err = select_with_fallback(&config, async { Err(anyhow!("garbage")) })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("garbage"), "{err}");
+    }
+
+    #[test]
+    fn test_fallback_cycle_must_exist() {
+        let err = FlowConfig::parse(
+            "[global]\npermissions = []\n\n[selector]\nfallback_cycle = \"nope\"\n\n\
+             [[cycle]]\nname = \"coding\"\ndescription = \"c\"\nprompt = \"c\"\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("fallback_cycle"), "{err}");
+    }
 }
@@ -0,0 +1,378 @@
+//! Effective configuration with value origins (`flow config show`)
+//!
+//! Replays the config layers a run applies — the user config, `cycles.toml`,
+//! cycle inheritance (`extends`), `[[step_template]]` instantiations, the selected
+//! `[profile.<name>]`, each `--set` override in order, `--skip-cycle`, and
+//! prompt files and snippet includes — and prints every effective value in
+//! `--set` path syntax (`cycle.coding.max_turns = 30`) with the layer that
+//! last set it. Values no layer sets come from the built-in defaults.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+use anyhow::{Context, Result};
+use toml::{Table, Value};
+
+use super::config::FlowConfig;
+use super::overrides::{
+    apply_override, apply_profile, expand_step_templates, merge_defaults, resolve_cycle_inheritance,
+};
+use super::template::snippet_names;
+use super::user_config::UserConfig;
+
+/// Origin of values that no config layer sets
+pub const DEFAULT_ORIGIN: &str = "default";
+
+/// Column past which origin comments are no longer aligned (long prompts)
+const MAX_ALIGN: usize = 60;
+
+/// Config path (e.g. `cycle.coding.max_turns`) → the layer that last set it
+pub type Origins = HashMap<String, String>;
+
+/// Flatten `table` into `--set`-style paths, addressing arrays of named
+/// tables (`[[cycle]]`, `[[cycle.step]]`) by their `name`.
+#[must_use]
+pub fn flatten(table: &Table) -> BTreeMap<String, Value> {
+    let mut out = BTreeMap::new();
+    flatten_into(table, "", &mut out);
+    out
+}
+
+fn flatten_into(table: &Table, prefix: &str, out: &mut BTreeMap<String, Value>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            Value::Table(inner) => flatten_into(inner, &path, out),
+            Value::Array(items) if !items.is_empty() && items.iter().all(is_named_table) => {
+                for item in items {
+                    if let Value::Table(inner) = item {
+                        let name = inner.get("name").and_then(Value::as_str).unwrap_or("");
+                        flatten_into(inner, &format!("{path}.{name}"), out);
+                    }
+                }
+            }
+            _ => {
+                out.insert(path, value.clone());
+            }
+        }
+    }
+}
+
+fn is_named_table(value: &Value) -> bool {
+    value
+        .as_table()
+        .is_some_and(|t| t.get("name").is_some_and(Value::is_str))
+}
+
+/// Record `origin` for every path whose value differs between `before` and `after`.
+fn mark_changes(
+    before: &BTreeMap<String, Value>,
+    after: &BTreeMap<String, Value>,
+    origin: &str,
+    origins: &mut Origins,
+) {
+    for (path, value) in after {
+        if before.get(path) != Some(value) {
+            origins.insert(path.clone(), origin.to_string());
+        }
+    }
+}
+
+/// Which layer set each value of `content` after merging it over the `user`
+/// config and applying `profile` and then `overrides`, as a run would.
+///
+/// # Errors
+/// Returns an error if the TOML is invalid or the profile or an override
+/// can't be applied.
+pub fn value_origins(
+    content: &str,
+    user: &UserConfig,
+    profile: Option<&str>,
+    overrides: &[String],
+) -> Result<Origins> {
+    let mut table: Table = content.parse().context("Failed to parse cycles.toml")?;
+    let mut origins = Origins::new();
+    let label = user.label();
+    for path in flatten(&user.defaults).into_keys() {
+        origins.insert(path, label.clone());
+    }
+    let mut project = table.clone();
+    project.remove("profile");
+    for path in flatten(&project).into_keys() {
+        origins.insert(path, "cycles.toml".to_string());
+    }
+    merge_defaults(&mut table, &user.defaults);
+    let mut base = table.clone();
+    base.remove("profile");
+    let mut current = flatten(&base);
+
+    for (origin, expand) in [
+        (
+            "extends",
+            resolve_cycle_inheritance as fn(&mut Table) -> Result<()>,
+        ),
+        ("step_template", expand_step_templates),
+    ] {
+        expand(&mut table)?;
+        let mut expanded = table.clone();
+        expanded.remove("profile");
+        let next = flatten(&expanded);
+        mark_changes(&current, &next, origin, &mut origins);
+        current = next;
+    }
+
+    apply_profile(&mut table, profile)?;
+    if let Some(name) = profile {
+        let next = flatten(&table);
+        mark_changes(&current, &next, &format!("profile {name}"), &mut origins);
+        current = next;
+    }
+    for assignment in overrides {
+        apply_override(&mut table, assignment)?;
+        let next = flatten(&table);
+        mark_changes(
+            &current,
+            &next,
+            &format!("--set {assignment}"),
+            &mut origins,
+        );
+        current = next;
+    }
+    for (path, value) in &current {
+        let includes = value.as_str().is_some_and(|v| !snippet_names(v).is_empty());
+        if path.ends_with(".prompt") && includes {
+            if let Some(origin) = origins.get_mut(path) {
+                origin.push_str(" + snippets");
+            }
+        }
+    }
+    Ok(origins)
+}
+
+/// Render the effective `config` as `path = value  # origin` lines.
+///
+/// `origins` comes from [`value_origins`]; `skip_cycles` are the
+/// `--skip-cycle` names already applied to `config`. Prompts loaded from a
+/// `prompt_file` are attributed to that file.
+///
+/// # Errors
+/// Returns an error if the config can't be serialized.
+pub fn render_effective(
+    config: &FlowConfig,
+    origins: &Origins,
+    skip_cycles: &[String],
+) -> Result<String> {
+    let Value::Table(table) =
+        Value::try_from(config).context("Failed to serialize effective config")?
+    else {
+        anyhow::bail!("Effective config did not serialize to a table");
+    };
+
+    let mut layered = origins.clone();
+    for cycle in &config.cycles {
+        let prefix = format!("cycle.{}", cycle.name);
+        if skip_cycles.contains(&cycle.name) {
+            layered.insert(format!("{prefix}.selectable"), "--skip-cycle".to_string());
+        }
+        if let Some(ref file) = cycle.prompt_file {
+            layered.insert(
+                format!("{prefix}.prompt"),
+                format!("prompt_file {}", file.display()),
+            );
+        }
+        for step in &cycle.steps {
+            if let Some(ref file) = step.prompt_file {
+                layered.insert(
+                    format!("{prefix}.step.{}.prompt", step.name),
+                    format!("prompt_file {}", file.display()),
+                );
+            }
+        }
+    }
+
+    let raw = flatten(&table);
+    let width = raw
+        .iter()
+        .map(|(path, value)| path.len() + value.to_string().len() + 3)
+        .max()
+        .unwrap_or(0)
+        .min(MAX_ALIGN);
+    let mut out = String::new();
+    for (path, value) in &raw {
+        let line = format!("{path} = {value}");
+        let origin = layered.get(path).map_or(DEFAULT_ORIGIN, String::as_str);
+        let _ = writeln!(out, "{line:<width$}  # {origin}");
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const CONFIG: &str = r#"
+[global]
+permissions = ["Read"]
+
+[snippet.rules]
+text = "Run the tests."
+
+[[cycle]]
+name = "coding"
+description = "Write code"
+max_turns = 20
+
+[[step_template]]
+name = "reflect"
+prompt = "Reflect."
+max_visits = 1
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan it"
+
+[[cycle.step]]
+name = "look-back"
+template = "reflect"
+
+[[cycle]]
+name = "coding-lite"
+extends = "coding"
+max_turns = 5
+
+[[cycle]]
+name = "gardening"
+description = "Tidy up"
+prompt = "Garden. {{snippet:rules}}"
+
+[profile.ci.cycle.coding]
+max_turns = 5
+"#;
+
+    fn line<'a>(rendered: &'a str, path: &str) -> &'a str {
+        rendered
+            .lines()
+            .find(|l| l.starts_with(&format!("{path} = ")))
+            .unwrap_or_else(|| panic!("no line for {path} in:\n{rendered}"))
+    }
+
+    fn show(profile: Option<&str>, overrides: &[&str], skip: &[&str]) -> String {
+        let overrides: Vec<String> = overrides.iter().map(ToString::to_string).collect();
+        let skip: Vec<String> = skip.iter().map(ToString::to_string).collect();
+        let mut config = FlowConfig::parse_with_overrides(CONFIG, profile, &overrides).unwrap();
+        for cycle in &mut config.cycles {
+            if skip.contains(&cycle.name) {
+                cycle.selectable = false;
+            }
+        }
+        let origins = value_origins(CONFIG, &UserConfig::default(), profile, &overrides).unwrap();
+        render_effective(&config, &origins, &skip).unwrap()
+    }
+
+    // --- flatten tests ---
+
+    #[test]
+    fn test_flatten_addresses_named_tables_by_name() {
+        let table: Table = CONFIG.parse().unwrap();
+        let flat = flatten(&table);
+        assert_eq!(
+            flat.get("cycle.coding.max_turns"),
+            Some(&Value::Integer(20))
+        );
+        assert_eq!(
+            flat.get("cycle.coding.step.plan.prompt"),
+            Some(&Value::String("Plan it".to_string()))
+        );
+        assert!(flat.contains_key("global.permissions"));
+    }
+
+    // --- origin tests ---
+
+    #[test]
+    fn test_file_values_come_from_cycles_toml() {
+        let rendered = show(None, &[], &[]);
+        assert!(line(&rendered, "cycle.coding.max_turns").ends_with("# cycles.toml"));
+        assert!(line(&rendered, "cycle.gardening.selectable").ends_with("# default"));
+    }
+
+    #[test]
+    fn test_profile_and_set_layers_are_attributed() {
+        let rendered = show(Some("ci"), &["global.summary_interval=7"], &[]);
+        let max_turns = line(&rendered, "cycle.coding.max_turns");
+        assert!(max_turns.starts_with("cycle.coding.max_turns = 5 "));
+        assert!(max_turns.ends_with("# profile ci"));
+        assert!(line(&rendered, "global.summary_interval")
+            .ends_with("# --set global.summary_interval=7"));
+    }
+
+    #[test]
+    fn test_step_template_values_are_attributed() {
+        let rendered = show(None, &[], &[]);
+        assert!(
+            line(&rendered, "cycle.coding.step.look-back.max_visits").ends_with("# step_template")
+        );
+        assert!(line(&rendered, "cycle.coding.step.look-back.name").ends_with("# cycles.toml"));
+    }
+
+    #[test]
+    fn test_inherited_values_are_attributed() {
+        let rendered = show(None, &[], &[]);
+        assert!(line(&rendered, "cycle.coding-lite.description").ends_with("# extends"));
+        assert!(line(&rendered, "cycle.coding-lite.max_turns").ends_with("# cycles.toml"));
+    }
+
+    #[test]
+    fn test_later_override_wins() {
+        let rendered = show(
+            Some("ci"),
+            &["cycle.coding.max_turns=9", "cycle.coding.max_turns=11"],
+            &[],
+        );
+        let max_turns = line(&rendered, "cycle.coding.max_turns");
+        assert!(max_turns.contains("= 11 "));
+        assert!(max_turns.ends_with("# --set cycle.coding.max_turns=11"));
+    }
+
+    #[test]
+    fn test_skip_cycle_and_snippets_are_attributed() {
+        let rendered = show(None, &[], &["gardening"]);
+        assert!(line(&rendered, "cycle.gardening.selectable").ends_with("# --skip-cycle"));
+        let prompt = line(&rendered, "cycle.gardening.prompt");
+        assert!(prompt.contains("Run the tests."));
+        assert!(prompt.ends_with("# cycles.toml + snippets"));
+    }
+
+    #[test]
+    fn test_prompt_file_is_attributed() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("plan.md"), "Plan from file").unwrap();
+        let content = CONFIG.replace("prompt = \"Plan it\"", "prompt_file = \"plan.md\"");
+        let path = dir.path().join("cycles.toml");
+        std::fs::write(&path, &content).unwrap();
+        let config = FlowConfig::from_path_with_overrides(&path, None, &[]).unwrap();
+        let origins = value_origins(&content, &UserConfig::default(), None, &[]).unwrap();
+        let rendered = render_effective(&config, &origins, &[]).unwrap();
+        let prompt = line(&rendered, "cycle.coding.step.plan.prompt");
+        assert!(prompt.contains("Plan from file"));
+        assert!(prompt.ends_with("# prompt_file plan.md"));
+    }
+
+    #[test]
+    fn test_user_config_values_are_attributed() {
+        let user =
+            UserConfig::parse("[global]\nmodel = \"sonnet\"\npermissions = [\"Glob\"]\n").unwrap();
+        let origins = value_origins(CONFIG, &user, None, &[]).unwrap();
+        assert_eq!(origins["global.model"], "user config");
+        assert_eq!(origins["global.permissions"], "cycles.toml");
+    }
+
+    #[test]
+    fn test_value_origins_rejects_unknown_profile() {
+        assert!(value_origins(CONFIG, &UserConfig::default(), Some("nope"), &[]).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Conditional step skipping — `[cycle.step.skip_if]`
+//!
+//! Checked before a step runs. A skipped step costs nothing, isn't routed
+//! from, and is recorded in `skipped_steps`; execution moves on to the next
+//! step in order.
+
+use std::path::Path;
+
+use crate::cycle::config::SkipIfConfig;
+use crate::cycle::stop::command_succeeds;
+
+/// Evaluate skip conditions, relative to the project directory `dir`.
+///
+/// Returns why the step should be skipped when every configured condition
+/// holds, or `None` to run it.
+pub async fn check_skip_if(skip_if: &SkipIfConfig, dir: &Path) -> Option<String> {
+    let mut reasons = Vec::new();
+
+    if let Some(ref path) = skip_if.exists {
+        if !dir.join(path).exists() {
+            return None;
+        }
+        reasons.push(format!("{} exists", path.display()));
+    }
+
+    if let Some(ref command) = skip_if.command {
+        if !command_succeeds(command, dir).await {
+            return None;
+        }
+        reasons.push(format!("`{command}` succeeded"));
+    }
+
+    (!reasons.is_empty()).then(|| format!("skip_if met: {}", reasons.join(" and ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn skip_if(command: Option<&str>, exists: Option<std::path::PathBuf>) -> SkipIfConfig {
+        SkipIfConfig {
+            command: command.map(str::to_string),
+            exists,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skips_when_command_succeeds() {
+        let reason = check_skip_if(&skip_if(Some("true"), None), Path::new(".")).await;
+        assert_eq!(reason.as_deref(), Some("skip_if met: `true` succeeded"));
+        assert!(check_skip_if(&skip_if(Some("false"), None), Path::new("."))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_skips_when_path_exists() {
+        let dir = TempDir::new().unwrap();
+        let plan = std::path::PathBuf::from("PLAN.md");
+        assert!(
+            check_skip_if(&skip_if(None, Some(plan.clone())), dir.path())
+                .await
+                .is_none()
+        );
+
+        std::fs::write(dir.path().join(&plan), "plan").unwrap();
+        let reason = check_skip_if(&skip_if(None, Some(plan)), dir.path())
+            .await
+            .unwrap();
+        assert!(reason.contains("PLAN.md exists"), "{reason}");
+    }
+
+    #[tokio::test]
+    async fn test_requires_every_condition() {
+        let dir = TempDir::new().unwrap();
+        let exists = Some(dir.path().to_path_buf());
+        assert!(
+            check_skip_if(&skip_if(Some("false"), exists.clone()), Path::new("."))
+                .await
+                .is_none()
+        );
+        let reason = check_skip_if(&skip_if(Some("true"), exists), Path::new("."))
+            .await
+            .unwrap();
+        assert!(reason.contains(" and "), "{reason}");
+    }
+}
@@ -0,0 +1,232 @@
+//! Working-tree snapshots for rolling back failed cycles
+//!
+//! Before a cycle with `rollback_on_failure = true` runs, the working tree
+//! (tracked and untracked, non-ignored files) is captured as a git tree object
+//! through a temporary index, without touching the real index, stash list, or
+//! branch. If the cycle fails, the tree is checked out again, files created by
+//! the cycle are removed, and `HEAD` is moved back to where it was.
+//!
+//! The same tree capture ([`working_tree`]) measures how many lines each cycle
+//! added and removed.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::git::{exclude_pathspecs, run, run_with_index};
+
+/// A captured working-tree state that can be restored
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Repository root the git commands run in
+    root: PathBuf,
+    /// Commit `HEAD` pointed at when the snapshot was taken
+    head: String,
+    /// Tree object holding every non-ignored file in the working tree
+    tree: String,
+    /// Paths (relative to `root`) left untouched, e.g. the `.flow` log directory
+    exclude: Vec<String>,
+}
+
+impl Snapshot {
+    /// Capture the working tree of the git repository at `root`.
+    ///
+    /// Paths in `exclude` are neither captured nor restored.
+    ///
+    /// # Errors
+    /// Returns an error if `root` is not a git repository with at least one
+    /// commit, or a git command fails.
+    pub fn capture(root: &Path, exclude: &[String]) -> Result<Self> {
+        let head = run(root, &["rev-parse", "--verify", "HEAD"])
+            .context("Snapshots need a git repository with at least one commit")?;
+
+        let tree = working_tree(root, exclude)?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            head,
+            tree,
+            exclude: exclude.to_vec(),
+        })
+    }
+
+    /// Restore the working tree and `HEAD` to the captured state.
+    ///
+    /// Files the cycle created are deleted, modified files are reverted, and
+    /// commits the cycle made are dropped from the current branch. The index is
+    /// reset to `HEAD`, so previously staged-but-uncommitted changes come back
+    /// as unstaged.
+    ///
+    /// # Errors
+    /// Returns an error if a git command fails.
+    pub fn restore(&self) -> Result<()> {
+        let index = temp_index(&self.root)?;
+        let result = (|| {
+            run_with_index(&self.root, &["read-tree", &self.tree], &index)?;
+            run_with_index(&self.root, &["checkout-index", "-a", "-f"], &index)?;
+            let mut clean = vec!["clean", "-f", "-d", "-q"];
+            for path in &self.exclude {
+                clean.extend(["-e", path.as_str()]);
+            }
+            run_with_index(&self.root, &clean, &index)
+        })();
+        let _ = std::fs::remove_file(&index);
+        result.context("Failed to restore working tree")?;
+
+        run(&self.root, &["reset", "-q", &self.head]).context("Failed to reset HEAD")?;
+        Ok(())
+    }
+
+    /// Commit `HEAD` pointed at when the snapshot was taken.
+    #[must_use]
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// Tree object holding the captured working tree.
+    #[must_use]
+    pub fn tree(&self) -> &str {
+        &self.tree
+    }
+}
+
+/// Write the working tree (tracked and untracked, non-ignored files, minus
+/// `exclude`) as a git tree object and return its ID, without touching the
+/// real index.
+///
+/// # Errors
+/// Returns an error if `root` is not a git repository or a git command fails.
+pub fn working_tree(root: &Path, exclude: &[String]) -> Result<String> {
+    let index = temp_index(root)?;
+    let real_index = root.join(run(root, &["rev-parse", "--git-path", "index"])?);
+    // Start from the real index so unchanged files aren't rehashed
+    if real_index.exists() {
+        std::fs::copy(&real_index, &index).context("Failed to copy git index")?;
+    }
+    let result = (|| {
+        let mut add = vec!["add", "-A", "--", "."];
+        let excludes = exclude_pathspecs(root, exclude);
+        add.extend(excludes.iter().map(String::as_str));
+        run_with_index(root, &add, &index)?;
+        run_with_index(root, &["write-tree"], &index)
+    })();
+    let _ = std::fs::remove_file(&index);
+    result.context("Failed to snapshot working tree")
+}
+
+/// Path of the temporary index used while capturing or restoring.
+fn temp_index(root: &Path) -> Result<PathBuf> {
+    let path = run(root, &["rev-parse", "--git-path", "flow-snapshot.index"])?;
+    Ok(root.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn repo() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for args in [
+            vec!["init", "-q"],
+            vec!["config", "user.email", "flow@example.com"],
+            vec!["config", "user.name", "Flow"],
+        ] {
+            run(root, &args).unwrap();
+        }
+        std::fs::write(root.join("tracked.txt"), "original\n").unwrap();
+        run(root, &["add", "tracked.txt"]).unwrap();
+        run(root, &["commit", "-q", "-m", "init"]).unwrap();
+        dir
+    }
+
+    fn read(root: &Path, file: &str) -> String {
+        std::fs::read_to_string(root.join(file)).unwrap()
+    }
+
+    // --- working_tree tests ---
+
+    #[test]
+    fn test_working_tree_changes_with_untracked_files() {
+        let dir = repo();
+        let root = dir.path();
+        let before = working_tree(root, &[]).unwrap();
+        assert_eq!(working_tree(root, &[]).unwrap(), before);
+
+        std::fs::write(root.join("new.txt"), "new\n").unwrap();
+        assert_ne!(working_tree(root, &[]).unwrap(), before);
+        assert_eq!(
+            working_tree(root, &["new.txt".to_string()]).unwrap(),
+            before
+        );
+    }
+
+    // --- Snapshot tests ---
+
+    #[test]
+    fn test_restore_reverts_edits_and_removes_new_files() {
+        let dir = repo();
+        let root = dir.path();
+        let snapshot = Snapshot::capture(root, &[]).unwrap();
+
+        std::fs::write(root.join("tracked.txt"), "half-finished\n").unwrap();
+        std::fs::write(root.join("new.txt"), "stray\n").unwrap();
+        snapshot.restore().unwrap();
+
+        assert_eq!(read(root, "tracked.txt"), "original\n");
+        assert!(!root.join("new.txt").exists());
+    }
+
+    #[test]
+    fn test_restore_keeps_preexisting_uncommitted_changes() {
+        let dir = repo();
+        let root = dir.path();
+        std::fs::write(root.join("tracked.txt"), "wip\n").unwrap();
+        std::fs::write(root.join("notes.txt"), "untracked\n").unwrap();
+        let snapshot = Snapshot::capture(root, &[]).unwrap();
+
+        std::fs::write(root.join("tracked.txt"), "broken\n").unwrap();
+        std::fs::remove_file(root.join("notes.txt")).unwrap();
+        snapshot.restore().unwrap();
+
+        assert_eq!(read(root, "tracked.txt"), "wip\n");
+        assert_eq!(read(root, "notes.txt"), "untracked\n");
+    }
+
+    #[test]
+    fn test_restore_drops_commits_made_after_snapshot() {
+        let dir = repo();
+        let root = dir.path();
+        let snapshot = Snapshot::capture(root, &[]).unwrap();
+
+        std::fs::write(root.join("tracked.txt"), "committed\n").unwrap();
+        run(root, &["commit", "-q", "-am", "cycle"]).unwrap();
+        snapshot.restore().unwrap();
+
+        assert_eq!(run(root, &["rev-parse", "HEAD"]).unwrap(), snapshot.head());
+        assert_eq!(read(root, "tracked.txt"), "original\n");
+    }
+
+    #[test]
+    fn test_restore_leaves_excluded_paths_alone() {
+        let dir = repo();
+        let root = dir.path();
+        std::fs::create_dir(root.join(".flow")).unwrap();
+        std::fs::write(root.join(".flow/log.jsonl"), "one\n").unwrap();
+        let snapshot = Snapshot::capture(root, &[".flow".to_string()]).unwrap();
+
+        std::fs::write(root.join(".flow/log.jsonl"), "one\ntwo\n").unwrap();
+        std::fs::write(root.join(".flow/progress.json"), "{}").unwrap();
+        snapshot.restore().unwrap();
+
+        assert_eq!(read(root, ".flow/log.jsonl"), "one\ntwo\n");
+        assert!(root.join(".flow/progress.json").exists());
+    }
+
+    #[test]
+    fn test_capture_outside_git_repo_is_error() {
+        let dir = TempDir::new().unwrap();
+        assert!(Snapshot::capture(dir.path(), &[]).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Goal-based stop conditions — `[global.stop_when]`
+//!
+//! Evaluated between iterations of a multi-iteration run so a run can end
+//! when its goal is reached (backlog empty, tests green) rather than after a
+//! fixed iteration count.
+
+use std::path::Path;
+
+use crate::cycle::config::StopWhenConfig;
+use crate::cycle::selector::{parse_todo_tasks, TodoTask};
+use crate::tasks::TaskFile;
+
+/// Check whether every configured stop condition holds.
+///
+/// `todo_path` is TODO.md or, when it ends in `.toml`, a structured
+/// `tasks.toml` whose tasks not yet done count as pending. The `command`
+/// runs in `dir`, the project directory.
+///
+/// Returns a human-readable reason when the run should stop. Conditions that
+/// cannot be evaluated (unreadable TODO.md, command failing to spawn) count as
+/// not met, so errors never end a run early.
+pub async fn check_stop_when(
+    stop_when: &StopWhenConfig,
+    todo_path: &Path,
+    dir: &Path,
+) -> Option<String> {
+    let mut reasons = Vec::new();
+
+    if let Some(ref priorities) = stop_when.no_pending_tasks {
+        let tasks = if todo_path.extension().is_some_and(|ext| ext == "toml") {
+            TaskFile::load(todo_path).ok()??.todo_tasks()
+        } else {
+            parse_todo_tasks(&std::fs::read_to_string(todo_path).ok()?)
+        };
+        if tasks.iter().any(|t| priority_matches(t, priorities)) {
+            return None;
+        }
+        if priorities.is_empty() {
+            reasons.push(format!("no pending tasks in {}", todo_path.display()));
+        } else {
+            reasons.push(format!(
+                "no pending {} tasks in {}",
+                priorities.join("/"),
+                todo_path.display()
+            ));
+        }
+    }
+
+    if let Some(ref command) = stop_when.command {
+        if !command_succeeds(command, dir).await {
+            return None;
+        }
+        reasons.push(format!("`{command}` succeeded"));
+    }
+
+    (!reasons.is_empty()).then(|| format!("stop_when met: {}", reasons.join(" and ")))
+}
+
+/// Whether a task's priority is one of `priorities` (all match when empty).
+///
+/// Compares the leading token case-insensitively, so `P1 (urgent)` matches `P1`.
+fn priority_matches(task: &TodoTask, priorities: &[String]) -> bool {
+    if priorities.is_empty() {
+        return true;
+    }
+    let level = task.priority.split_whitespace().next().unwrap_or("");
+    priorities.iter().any(|p| p.eq_ignore_ascii_case(level))
+}
+
+/// Run `command` through `sh -c` in `dir` and report whether it exited successfully.
+pub(crate) async fn command_succeeds(command: &str, dir: &Path) -> bool {
+    match tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await
+    {
+        Ok(status) => status.success(),
+        Err(e) => {
+            eprintln!("Warning: command '{command}' could not be run: {e}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_todo(dir: &TempDir, content: &str) -> std::path::PathBuf {
+        let path = dir.path().join("TODO.md");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn tasks_only(priorities: &[&str]) -> StopWhenConfig {
+        StopWhenConfig {
+            no_pending_tasks: Some(priorities.iter().map(ToString::to_string).collect()),
+            command: None,
+        }
+    }
+
+    const TODO_WITH_P2: &str =
+        "- [ ] Polish docs\n  - Priority: P2\n- [x] Done thing\n  - Priority: P0\n";
+
+    // --- no_pending_tasks tests ---
+
+    #[tokio::test]
+    async fn test_stops_when_no_matching_priority_pending() {
+        let dir = TempDir::new().unwrap();
+        let todo = write_todo(&dir, TODO_WITH_P2);
+        let reason = check_stop_when(&tasks_only(&["P0", "P1"]), &todo, Path::new(".")).await;
+        let reason = reason.expect("should stop");
+        assert!(reason.contains("no pending P0/P1 tasks"), "{reason}");
+    }
+
+    #[tokio::test]
+    async fn test_continues_when_matching_priority_pending() {
+        let dir = TempDir::new().unwrap();
+        let todo = write_todo(&dir, "- [ ] Fix crash\n  - Priority: p1 (urgent)\n");
+        assert!(
+            check_stop_when(&tasks_only(&["P0", "P1"]), &todo, Path::new("."))
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_priority_list_means_any_task() {
+        let dir = TempDir::new().unwrap();
+        let todo = write_todo(&dir, TODO_WITH_P2);
+        assert!(check_stop_when(&tasks_only(&[]), &todo, Path::new("."))
+            .await
+            .is_none());
+
+        let todo = write_todo(&dir, "- [x] All done\n  - Priority: P0\n");
+        assert!(check_stop_when(&tasks_only(&[]), &todo, Path::new("."))
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_missing_todo_does_not_stop() {
+        let dir = TempDir::new().unwrap();
+        let todo = dir.path().join("missing.md");
+        assert!(check_stop_when(&tasks_only(&["P0"]), &todo, Path::new("."))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_structured_task_file_counts_unfinished_tasks() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tasks.toml");
+        std::fs::write(
+            &path,
+            "[[task]]\nid = \"a\"\ntitle = \"A\"\npriority = \"P0\"\nstatus = \"done\"\n\
+             [[task]]\nid = \"b\"\ntitle = \"B\"\npriority = \"P1\"\n",
+        )
+        .unwrap();
+        assert!(check_stop_when(&tasks_only(&["P1"]), &path, Path::new("."))
+            .await
+            .is_none());
+        let reason = check_stop_when(&tasks_only(&["P0"]), &path, Path::new("."))
+            .await
+            .unwrap();
+        assert!(reason.contains("no pending P0 tasks in"), "{reason}");
+    }
+
+    // --- command tests ---
+
+    #[tokio::test]
+    async fn test_stops_when_command_succeeds() {
+        let stop_when = StopWhenConfig {
+            no_pending_tasks: None,
+            command: Some("true".to_string()),
+        };
+        let reason = check_stop_when(&stop_when, Path::new("TODO.md"), Path::new(".")).await;
+        assert_eq!(reason.as_deref(), Some("stop_when met: `true` succeeded"));
+    }
+
+    #[tokio::test]
+    async fn test_continues_when_command_fails() {
+        let stop_when = StopWhenConfig {
+            no_pending_tasks: None,
+            command: Some("exit 1".to_string()),
+        };
+        assert!(
+            check_stop_when(&stop_when, Path::new("TODO.md"), Path::new("."))
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_all_conditions_must_hold() {
+        let dir = TempDir::new().unwrap();
+        let todo = write_todo(&dir, TODO_WITH_P2);
+        let mut stop_when = tasks_only(&["P0"]);
+        stop_when.command = Some("false".to_string());
+        assert!(check_stop_when(&stop_when, &todo, Path::new("."))
+            .await
+            .is_none());
+
+        stop_when.command = Some("true".to_string());
+        let reason = check_stop_when(&stop_when, &todo, Path::new("."))
+            .await
+            .unwrap();
+        assert!(reason.contains(" and "), "{reason}");
+    }
+}
@@ -0,0 +1,163 @@
+//! Per-cycle success criteria — `[cycle.success_when]`
+//!
+//! Claude Code exits 0 even when it accomplished nothing. These checks run
+//! after a cycle exits successfully and can downgrade it to a failure.
+
+use std::path::Path;
+
+use crate::cycle::config::SuccessWhenConfig;
+use crate::cycle::executor::CycleResult;
+use crate::cycle::stop::command_succeeds;
+
+/// Evaluate success criteria against a finished cycle, running the `command`
+/// in the project directory `dir`.
+///
+/// Returns `None` when every configured criterion holds, or a description of
+/// the first unmet criterion.
+pub async fn check_success_when(
+    criteria: &SuccessWhenConfig,
+    result: &CycleResult,
+    dir: &Path,
+) -> Option<String> {
+    if let Some(ref pattern) = criteria.result_matches {
+        // Patterns are validated at config load; an invalid one never matches.
+        let matched = regex::Regex::new(pattern).is_ok_and(|re| {
+            result
+                .result_text
+                .as_deref()
+                .is_some_and(|text| re.is_match(text))
+        });
+        if !matched {
+            return Some(format!("result text did not match /{pattern}/"));
+        }
+    }
+
+    if let Some(min) = criteria.min_tests_passed {
+        if result.tests_passed < min {
+            return Some(format!(
+                "{} tests passed, expected at least {min}",
+                result.tests_passed
+            ));
+        }
+    }
+
+    if let Some(ref command) = criteria.command {
+        if !command_succeeds(command, dir).await {
+            return Some(format!("`{command}` failed"));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result_with(text: &str, tests_passed: u32) -> CycleResult {
+        CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            result_text: Some(text.to_string()),
+            tests_passed,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_criteria_met_trivially() {
+        let criteria = SuccessWhenConfig::default();
+        assert!(
+            check_success_when(&criteria, &result_with("anything", 0), Path::new("."))
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_result_matches() {
+        let criteria = SuccessWhenConfig {
+            result_matches: Some("(?i)committed".to_string()),
+            ..Default::default()
+        };
+        assert!(check_success_when(
+            &criteria,
+            &result_with("Committed abc123", 0),
+            Path::new(".")
+        )
+        .await
+        .is_none());
+        let reason =
+            check_success_when(&criteria, &result_with("Nothing to do", 0), Path::new("."))
+                .await
+                .unwrap();
+        assert!(reason.contains("did not match"), "{reason}");
+    }
+
+    #[tokio::test]
+    async fn test_result_matches_missing_text_fails() {
+        let criteria = SuccessWhenConfig {
+            result_matches: Some(".*".to_string()),
+            ..Default::default()
+        };
+        let mut result = result_with("", 0);
+        result.result_text = None;
+        assert!(check_success_when(&criteria, &result, Path::new("."))
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_min_tests_passed() {
+        let criteria = SuccessWhenConfig {
+            min_tests_passed: Some(5),
+            ..Default::default()
+        };
+        assert!(
+            check_success_when(&criteria, &result_with("", 5), Path::new("."))
+                .await
+                .is_none()
+        );
+        let reason = check_success_when(&criteria, &result_with("", 2), Path::new("."))
+            .await
+            .unwrap();
+        assert_eq!(reason, "2 tests passed, expected at least 5");
+    }
+
+    #[tokio::test]
+    async fn test_command_criterion() {
+        let ok = SuccessWhenConfig {
+            command: Some("true".to_string()),
+            ..Default::default()
+        };
+        assert!(check_success_when(&ok, &result_with("", 0), Path::new("."))
+            .await
+            .is_none());
+
+        let failing = SuccessWhenConfig {
+            command: Some("false".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            check_success_when(&failing, &result_with("", 0), Path::new("."))
+                .await
+                .as_deref(),
+            Some("`false` failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_runs_in_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("marker"), "").unwrap();
+        let criteria = SuccessWhenConfig {
+            command: Some("test -f marker".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            check_success_when(&criteria, &result_with("", 0), dir.path())
+                .await
+                .is_none()
+        );
+    }
+}
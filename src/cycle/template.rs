@@ -1,10 +1,15 @@
 //! Template expansion for cycle prompts.
 //!
 //! Supports `{{variable_name}}` syntax. Unknown variables are left as-is.
+//! `{{snippet:<name>}}` includes a `[snippet.<name>]` definition; snippets are
+//! expanded when the config loads, before runtime variables.
 
 use std::collections::HashMap;
 use std::path::Path;
 
+/// Prefix of snippet includes: `{{snippet:<name>}}`.
+pub const SNIPPET_PREFIX: &str = "snippet:";
+
 /// Expand `{{variable_name}}` patterns in a template string.
 ///
 /// Resolution: looks up each `{{name}}` in `vars`. If found, replaces with
@@ -13,6 +18,44 @@ use std::path::Path;
 #[must_use]
 #[allow(clippy::implicit_hasher)]
 pub fn expand_template(template: &str, vars: &HashMap<String, String>) -> String {
+    expand_with(template, |name| vars.get(name).map(String::as_str))
+}
+
+/// Replace `{{snippet:<name>}}` includes with the snippet text. Unknown
+/// snippets and all other `{{...}}` patterns are left as-is.
+#[must_use]
+#[allow(clippy::implicit_hasher)]
+pub fn expand_snippets(template: &str, snippets: &HashMap<String, String>) -> String {
+    expand_with(template, |name| {
+        name.strip_prefix(SNIPPET_PREFIX)
+            .and_then(|snippet| snippets.get(snippet))
+            .map(String::as_str)
+    })
+}
+
+/// Names of the snippets a template includes, in order of appearance.
+#[must_use]
+pub fn snippet_names(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(close) = after.find("}}") else {
+            break;
+        };
+        let name = &after[..close];
+        if let Some(snippet) = name.strip_prefix(SNIPPET_PREFIX) {
+            if !snippet.is_empty() && !name.contains(char::is_whitespace) {
+                names.push(snippet);
+            }
+        }
+        rest = &after[close + 2..];
+    }
+    names
+}
+
+/// Expand `{{name}}` patterns using `lookup`; names it doesn't resolve stay literal.
+fn expand_with<'a>(template: &str, lookup: impl Fn(&str) -> Option<&'a str>) -> String {
     let mut result = String::with_capacity(template.len());
     let bytes = template.as_bytes();
     let len = bytes.len();
@@ -25,7 +68,7 @@ pub fn expand_template(template: &str, vars: &HashMap<String, String>) -> String
                 let var_name = &template[i + 2..i + 2 + close];
                 // Only match if the variable name contains no whitespace
                 if !var_name.is_empty() && !var_name.contains(char::is_whitespace) {
-                    if let Some(value) = vars.get(var_name) {
+                    if let Some(value) = lookup(var_name) {
                         result.push_str(value);
                     } else {
                         // Unknown variable — leave as-is
@@ -216,4 +259,34 @@ mod tests {
         let result = expand_template(template, &v);
         assert_eq!(result, "You are flow's coding cycle. Iteration 3/20.");
     }
+
+    // --- snippet tests ---
+
+    #[test]
+    fn test_expand_snippets_leaves_variables_for_runtime() {
+        let snippets = vars(&[("tdd-rules", "Write the test first in {{cycle_name}}.")]);
+        let result = expand_snippets("Rules: {{snippet:tdd-rules}} Go {{iteration}}", &snippets);
+        assert_eq!(
+            result,
+            "Rules: Write the test first in {{cycle_name}}. Go {{iteration}}"
+        );
+    }
+
+    #[test]
+    fn test_expand_snippets_unknown_left_as_is() {
+        let result = expand_snippets(
+            "{{snippet:nope}} {{tdd-rules}}",
+            &vars(&[("tdd-rules", "x")]),
+        );
+        assert_eq!(result, "{{snippet:nope}} {{tdd-rules}}");
+    }
+
+    #[test]
+    fn test_snippet_names() {
+        assert_eq!(
+            snippet_names("{{snippet:a}} {{x}} {{snippet:b-c}} {{snippet:}} {{snippet:a}}"),
+            ["a", "b-c", "a"]
+        );
+        assert!(snippet_names("{{snippet:unclosed").is_empty());
+    }
 }
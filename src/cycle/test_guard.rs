@@ -0,0 +1,137 @@
+//! Test-suite regression guard (`[test_guard]`)
+//!
+//! Runs the project's test command before and after a guarded cycle and
+//! parses both outputs with the `[test_parser]` patterns. The counts are
+//! logged with the cycle (`test_delta`), and with `fail_on_regression` a cycle
+//! that leaves fewer passing or more failing tests than it started with is
+//! marked failed, so `rollback_on_failure` can undo it.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::claude::test_parser::{TestCounts, TestParser};
+
+/// Test counts before and after a cycle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TestDelta {
+    /// Passing tests before the cycle
+    pub passed_before: u32,
+    /// Failing tests before the cycle
+    pub failed_before: u32,
+    /// Passing tests after the cycle
+    pub passed_after: u32,
+    /// Failing tests after the cycle
+    pub failed_after: u32,
+}
+
+impl TestDelta {
+    /// Compare the counts from before and after a cycle.
+    #[must_use]
+    pub const fn new(before: TestCounts, after: TestCounts) -> Self {
+        Self {
+            passed_before: before.passed,
+            failed_before: before.failed,
+            passed_after: after.passed,
+            failed_after: after.failed,
+        }
+    }
+
+    /// Whether the cycle lost passing tests or gained failing ones.
+    #[must_use]
+    pub const fn regressed(&self) -> bool {
+        self.passed_after < self.passed_before || self.failed_after > self.failed_before
+    }
+
+    /// Short description, e.g. `passed 40 → 38, failed 0 → 2`.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        format!(
+            "passed {} → {}, failed {} → {}",
+            self.passed_before, self.passed_after, self.failed_before, self.failed_after
+        )
+    }
+}
+
+/// Run the test `command` through `sh -c` in `dir` and parse the counts from
+/// its combined stdout and stderr.
+///
+/// Returns `None` (with a warning) if the command cannot be run or its output
+/// matches none of the parser's patterns. A non-zero exit is expected when
+/// tests fail, so only the parsed counts matter.
+pub async fn run_test_command(
+    command: &str,
+    parser: &TestParser,
+    dir: &Path,
+) -> Option<TestCounts> {
+    let output = match tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Warning: test command '{command}' could not be run: {e}");
+            return None;
+        }
+    };
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let counts = parser.parse(&text);
+    if counts.is_none() {
+        eprintln!("Warning: no test counts found in the output of '{command}'");
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(passed: u32, failed: u32) -> TestCounts {
+        TestCounts {
+            passed,
+            failed,
+            ignored: 0,
+        }
+    }
+
+    #[test]
+    fn test_regression_detected() {
+        assert!(TestDelta::new(counts(40, 0), counts(38, 2)).regressed());
+        assert!(TestDelta::new(counts(40, 0), counts(39, 0)).regressed());
+        assert!(TestDelta::new(counts(40, 1), counts(41, 2)).regressed());
+        assert!(!TestDelta::new(counts(40, 2), counts(43, 0)).regressed());
+        assert!(!TestDelta::new(counts(40, 0), counts(40, 0)).regressed());
+    }
+
+    #[test]
+    fn test_summary() {
+        let delta = TestDelta::new(counts(40, 0), counts(38, 2));
+        assert_eq!(delta.summary(), "passed 40 → 38, failed 0 → 2");
+    }
+
+    #[tokio::test]
+    async fn test_run_test_command_parses_output() {
+        let parser = TestParser::default();
+        let counts = run_test_command(
+            "echo 'test result: FAILED. 7 passed; 2 failed; 1 ignored' >&2; exit 101",
+            &parser,
+            Path::new("."),
+        )
+        .await
+        .unwrap();
+        assert_eq!((counts.passed, counts.failed, counts.ignored), (7, 2, 1));
+        assert!(
+            run_test_command("echo nothing here", &parser, Path::new("."))
+                .await
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! User-level configuration (`~/.config/flow/config.toml`)
+//!
+//! Personal preferences shared by every project: where logs go, which
+//! `claude` binary to run, and defaults for `cycles.toml` tables such as
+//! `[global]` (e.g. `model`), `[notifications]`, or `[hooks]`. Those tables are
+//! merged *under* the project config, so anything the project sets wins.
+//!
+//! ```toml
+//! log_dir = ".flow-local"
+//! claude_bin = "/opt/claude/bin/claude"
+//!
+//! [global]
+//! model = "sonnet"
+//!
+//! [notifications.channels.me]
+//! type = "slack"
+//! url = "https://hooks.slack.com/services/..."
+//! ```
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use toml::{Table, Value};
+
+/// Environment variable naming the user config file (empty disables it)
+pub const USER_CONFIG_ENV: &str = "FLOW_USER_CONFIG";
+
+/// Parsed user config
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserConfig {
+    /// File the settings were read from (`None` when there is none)
+    pub path: Option<PathBuf>,
+    /// Default `--log-dir`
+    pub log_dir: Option<PathBuf>,
+    /// Program (plus leading arguments) to run in place of `claude`;
+    /// `FLOW_CLAUDE_BIN` takes precedence
+    pub claude_bin: Option<String>,
+    /// Remaining tables, merged under the project's cycles.toml
+    pub defaults: Table,
+}
+
+impl UserConfig {
+    /// Load the user config from [`user_config_path`], or an empty config if
+    /// that file doesn't exist.
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but is invalid.
+    pub fn load_default() -> Result<Self> {
+        match user_config_path(
+            std::env::var_os(USER_CONFIG_ENV),
+            std::env::var_os("XDG_CONFIG_HOME"),
+            std::env::var_os("HOME"),
+        ) {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Load the user config at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or parsed, a setting has the
+    /// wrong type, or it defines cycles (those belong to the project).
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read user config '{}'", path.display()))?;
+        let mut config = Self::parse(&content)
+            .with_context(|| format!("Invalid user config '{}'", path.display()))?;
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Parse user config content.
+    ///
+    /// # Errors
+    /// Returns an error if the TOML is invalid, `log_dir` or `claude_bin`
+    /// isn't a string, or it contains `[[cycle]]` entries.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut defaults: Table = content.parse().context("Failed to parse TOML")?;
+        if defaults.contains_key("cycle") {
+            bail!("cycles can't be defined in the user config; add them to cycles.toml");
+        }
+        let log_dir = take_string(&mut defaults, "log_dir")?.map(PathBuf::from);
+        let claude_bin = take_string(&mut defaults, "claude_bin")?;
+        Ok(Self {
+            path: None,
+            log_dir,
+            claude_bin,
+            defaults,
+        })
+    }
+
+    /// Label for values that come from this file, e.g. in `flow config show`.
+    #[must_use]
+    pub fn label(&self) -> String {
+        self.path.as_ref().map_or_else(
+            || "user config".to_string(),
+            |p| format!("user config {}", p.display()),
+        )
+    }
+}
+
+/// Remove `key` from `table`, requiring a string if present.
+fn take_string(table: &mut Table, key: &str) -> Result<Option<String>> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => bail!("'{key}' must be a string"),
+    }
+}
+
+/// Where the user config lives: `$FLOW_USER_CONFIG` (empty disables it), else
+/// `$XDG_CONFIG_HOME/flow/config.toml`, else `$HOME/.config/flow/config.toml`.
+#[must_use]
+pub fn user_config_path(
+    flow_env: Option<OsString>,
+    xdg_config_home: Option<OsString>,
+    home: Option<OsString>,
+) -> Option<PathBuf> {
+    if let Some(path) = flow_env {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    let config_home = xdg_config_home
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("flow").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // --- UserConfig tests ---
+
+    #[test]
+    fn test_parse_splits_settings_from_defaults() {
+        let config = UserConfig::parse(
+            r#"
+log_dir = "logs"
+claude_bin = "my-claude --debug"
+
+[global]
+model = "sonnet"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.log_dir, Some(PathBuf::from("logs")));
+        assert_eq!(config.claude_bin.as_deref(), Some("my-claude --debug"));
+        assert!(!config.defaults.contains_key("log_dir"));
+        assert_eq!(config.defaults["global"]["model"].as_str(), Some("sonnet"));
+    }
+
+    #[test]
+    fn test_parse_rejects_cycles_and_bad_types() {
+        let err = UserConfig::parse("[[cycle]]\nname = \"x\"\n").unwrap_err();
+        assert!(err.to_string().contains("cycles.toml"), "{err}");
+        let err = UserConfig::parse("log_dir = 3\n").unwrap_err();
+        assert!(
+            err.to_string().contains("'log_dir' must be a string"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_load_records_path_in_label() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[global]\nmodel = \"opus\"\n").unwrap();
+        let config = UserConfig::load(&path).unwrap();
+        assert_eq!(config.path.as_deref(), Some(path.as_path()));
+        assert!(config.label().ends_with("config.toml"));
+        assert_eq!(UserConfig::default().label(), "user config");
+    }
+
+    // --- user_config_path tests ---
+
+    #[test]
+    fn test_user_config_path_precedence() {
+        let os = |s: &str| Some(OsString::from(s));
+        assert_eq!(
+            user_config_path(os("/etc/flow.toml"), os("/xdg"), os("/home/u")),
+            Some(PathBuf::from("/etc/flow.toml"))
+        );
+        assert_eq!(user_config_path(os(""), os("/xdg"), os("/home/u")), None);
+        assert_eq!(
+            user_config_path(None, os("/xdg"), os("/home/u")),
+            Some(PathBuf::from("/xdg/flow/config.toml"))
+        );
+        assert_eq!(
+            user_config_path(None, os(""), os("/home/u")),
+            Some(PathBuf::from("/home/u/.config/flow/config.toml"))
+        );
+        assert_eq!(user_config_path(None, None, None), None);
+    }
+}
@@ -6,11 +6,18 @@
 
 use std::collections::HashMap;
 
-use crate::cycle::config::FlowConfig;
+use serde::Serialize;
+
+use crate::claude::permissions::{is_broad_grant, permission_covers};
+use crate::cycle::config::{ContextMode, CycleConfig, FlowConfig, MinInterval, StepRouter};
+use crate::cycle::context::{build_context, estimate_tokens, windowed};
+use crate::log::index::LogIndex;
+use crate::log::jsonl::StepOutcome;
 use crate::log::CycleOutcome;
 
-/// Severity level for a diagnostic finding
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Severity level for a diagnostic finding, ordered from most to least severe
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     /// Must fix — something is broken
     Error,
@@ -21,7 +28,7 @@ pub enum Severity {
 }
 
 /// A single diagnostic finding
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Finding {
     /// Severity of the finding
     pub severity: Severity,
@@ -194,6 +201,13 @@ impl DiagnosticReport {
             .filter(|f| f.severity == Severity::Info)
             .count()
     }
+
+    /// Returns true if any finding is at least as severe as `threshold`
+    /// (e.g., a `Warning` threshold matches both errors and warnings)
+    #[must_use]
+    pub fn has_findings_at_or_above(&self, threshold: &Severity) -> bool {
+        self.findings.iter().any(|f| f.severity <= *threshold)
+    }
 }
 
 /// Run all diagnostic checks and return a report.
@@ -202,10 +216,20 @@ pub fn diagnose(config: &FlowConfig, log: &[CycleOutcome]) -> DiagnosticReport {
     let mut findings = Vec::new();
 
     check_permission_denials(log, &mut findings);
-    check_failure_rate(log, &mut findings);
+    check_failure_rate(&LogIndex::from_outcomes(log), &mut findings);
     check_high_cost(log, &mut findings);
     check_config_lint(config, &mut findings);
     check_frequency_tuning(config, log, &mut findings);
+    check_config_drift(config, log, &mut findings);
+    check_shadowed_permissions(config, &mut findings);
+    check_broad_permissions(config, &mut findings);
+    check_prompt_size(config, log, &mut findings);
+    check_routing_loops(config, log, &mut findings);
+
+    // Drop codes the user has suppressed via `[doctor] suppress = [...]`
+    if let Some(ref doctor) = config.doctor {
+        findings.retain(|f| !doctor.suppress.contains(&f.code));
+    }
 
     // Sort: errors first, then warnings, then info
     findings.sort_by_key(|f| match f.severity {
@@ -254,12 +278,10 @@ fn check_permission_denials(log: &[CycleOutcome], findings: &mut Vec<Finding>) {
 }
 
 /// D002: Check for cycles that consistently fail
-fn check_failure_rate(log: &[CycleOutcome], findings: &mut Vec<Finding>) {
-    let cycle_outcomes = group_by_cycle(log);
-
-    for (cycle_name, outcomes) in &cycle_outcomes {
-        let failure_count = outcomes.iter().filter(|o| !o.is_success()).count();
-        let total = outcomes.len();
+fn check_failure_rate(index: &LogIndex, findings: &mut Vec<Finding>) {
+    for (cycle_name, stats) in &index.cycles {
+        let failure_count = stats.runs - stats.successes;
+        let total = stats.runs;
 
         // If more than half of runs failed, flag it
         if total >= 2 && failure_count * 2 > total {
@@ -270,7 +292,7 @@ fn check_failure_rate(log: &[CycleOutcome], findings: &mut Vec<Finding>) {
                     "Cycle '{cycle_name}' failed {failure_count}/{total} times"
                 ),
                 suggestion: Some("Check cycle prompt and permissions. Run `flow --cycle <name>` manually to debug.".to_string()),
-                cycle_name: Some(cycle_name.to_string()),
+                cycle_name: Some(cycle_name.clone()),
             });
         }
     }
@@ -325,13 +347,14 @@ fn group_by_cycle(log: &[CycleOutcome]) -> HashMap<&str, Vec<&CycleOutcome>> {
 fn check_config_lint(config: &FlowConfig, findings: &mut Vec<Finding>) {
     for cycle in &config.cycles {
         // Warn about triggered cycles without min_interval
-        if !cycle.after.is_empty() && cycle.min_interval.is_none() {
+        let sources = cycle.trigger_sources();
+        if !sources.is_empty() && cycle.min_interval.is_none() {
             findings.push(Finding {
                 severity: Severity::Info,
                 code: "D004".to_string(),
                 message: format!(
                     "Cycle '{}' triggers after {:?} but has no min_interval",
-                    cycle.name, cycle.after
+                    cycle.name, sources
                 ),
                 suggestion: Some(format!(
                     "Add `min_interval = 3` to '{}' in cycles.toml to avoid redundant runs",
@@ -367,7 +390,7 @@ fn check_frequency_tuning(config: &FlowConfig, log: &[CycleOutcome], findings: &
 
     // For triggered cycles, check if they run too frequently
     for cycle in &config.cycles {
-        if cycle.after.is_empty() {
+        if cycle.trigger_sources().is_empty() {
             continue;
         }
 
@@ -385,7 +408,11 @@ fn check_frequency_tuning(config: &FlowConfig, log: &[CycleOutcome], findings: &
             }
         }
 
-        if close_runs > 0 && cycle.min_interval.is_none_or(|v| v <= 1) {
+        if close_runs > 0
+            && cycle
+                .min_interval
+                .is_none_or(|v| matches!(v, MinInterval::Iterations(n) if n <= 1))
+        {
             findings.push(Finding {
                 severity: Severity::Info,
                 code: "D006".to_string(),
@@ -403,6 +430,337 @@ fn check_frequency_tuning(config: &FlowConfig, log: &[CycleOutcome], findings: &
     }
 }
 
+/// Minimum number of logged iterations before a configured cycle with no
+/// log entries is reported as unused (D007).
+const UNUSED_CYCLE_MIN_ITERATIONS: u32 = 10;
+
+/// D007/D008: Detect drift between the configured cycles and the log
+fn check_config_drift(config: &FlowConfig, log: &[CycleOutcome], findings: &mut Vec<Finding>) {
+    if log.is_empty() {
+        return;
+    }
+
+    let logged = group_by_cycle(log);
+    let iterations = log.iter().map(|e| e.iteration).max().unwrap_or(0);
+
+    // D007: configured cycles that never ran
+    if iterations >= UNUSED_CYCLE_MIN_ITERATIONS {
+        for cycle in &config.cycles {
+            if logged.contains_key(cycle.name.as_str()) {
+                continue;
+            }
+            findings.push(Finding {
+                severity: Severity::Info,
+                code: "D007".to_string(),
+                message: format!(
+                    "Cycle '{}' has never run in {iterations} logged iteration(s)",
+                    cycle.name
+                ),
+                suggestion: Some(format!(
+                    "Remove '{}' from cycles.toml, or check its `after` triggers and selector description",
+                    cycle.name
+                )),
+                cycle_name: Some(cycle.name.clone()),
+            });
+        }
+    }
+
+    // D008: cycles in the log that are no longer configured
+    let mut orphaned: Vec<(&str, usize)> = logged
+        .iter()
+        .filter(|(name, _)| config.get_cycle(name).is_none())
+        .map(|(name, entries)| (*name, entries.len()))
+        .collect();
+    orphaned.sort_unstable();
+
+    for (cycle_name, count) in orphaned {
+        findings.push(Finding {
+            severity: Severity::Warning,
+            code: "D008".to_string(),
+            message: format!(
+                "Cycle '{cycle_name}' appears {count} time(s) in the log but is not in cycles.toml"
+            ),
+            suggestion: Some(
+                "If the cycle was renamed, its history no longer informs the selector or triggers"
+                    .to_string(),
+            ),
+            cycle_name: Some(cycle_name.to_string()),
+        });
+    }
+}
+
+/// D009: Flag permissions already granted by a broader entry in the same or
+/// an enclosing layer (global → cycle → step)
+fn check_shadowed_permissions(config: &FlowConfig, findings: &mut Vec<Finding>) {
+    let global = &config.global.permissions;
+    report_shadowed(&[], global, "global permissions", None, findings);
+    for cycle in &config.cycles {
+        let location = format!("cycle '{}'", cycle.name);
+        report_shadowed(
+            global,
+            &cycle.permissions,
+            &location,
+            Some(&cycle.name),
+            findings,
+        );
+        let enclosing: Vec<String> = global.iter().chain(&cycle.permissions).cloned().collect();
+        for step in &cycle.steps {
+            let location = format!("step '{}.{}'", cycle.name, step.name);
+            report_shadowed(
+                &enclosing,
+                &step.permissions,
+                &location,
+                Some(&cycle.name),
+                findings,
+            );
+        }
+    }
+}
+
+/// Report each entry of `layer` covered by `enclosing` or by a sibling. Of two
+/// identical siblings only the later one is reported.
+fn report_shadowed(
+    enclosing: &[String],
+    layer: &[String],
+    location: &str,
+    cycle_name: Option<&str>,
+    findings: &mut Vec<Finding>,
+) {
+    for (i, perm) in layer.iter().enumerate() {
+        let sibling = layer.iter().enumerate().find(|&(j, other)| {
+            j != i && permission_covers(other, perm) && (other != perm || j < i)
+        });
+        let Some(broader) = enclosing
+            .iter()
+            .find(|other| permission_covers(other, perm))
+            .or_else(|| sibling.map(|(_, other)| other))
+        else {
+            continue;
+        };
+        findings.push(Finding {
+            severity: Severity::Info,
+            code: "D009".to_string(),
+            message: format!("Permission '{perm}' in {location} is already covered by '{broader}'"),
+            suggestion: Some(format!("Remove '{perm}' from {location} in cycles.toml")),
+            cycle_name: cycle_name.map(str::to_string),
+        });
+    }
+}
+
+/// Whether a cycle runs without a sandbox and isn't listed in `[doctor] trusted_cycles`
+fn is_low_trust(config: &FlowConfig, cycle: &CycleConfig) -> bool {
+    cycle.sandbox.is_none()
+        && !config
+            .doctor
+            .as_ref()
+            .is_some_and(|d| d.trusted_cycles.contains(&cycle.name))
+}
+
+/// D010: Warn about unrestricted grants (`Bash(*)`, `Edit(./**)`) reaching
+/// cycles that are neither sandboxed nor marked trusted
+fn check_broad_permissions(config: &FlowConfig, findings: &mut Vec<Finding>) {
+    let low_trust: Vec<&CycleConfig> = config
+        .cycles
+        .iter()
+        .filter(|c| is_low_trust(config, c))
+        .collect();
+    if low_trust.is_empty() {
+        return;
+    }
+
+    let suggestion = |name: &str| {
+        format!(
+            "Narrow the grant, set `sandbox = \"docker\"`, or add '{name}' to `[doctor] trusted_cycles`"
+        )
+    };
+    for perm in config
+        .global
+        .permissions
+        .iter()
+        .filter(|p| is_broad_grant(p))
+    {
+        let names: Vec<&str> = low_trust.iter().map(|c| c.name.as_str()).collect();
+        findings.push(Finding {
+            severity: Severity::Warning,
+            code: "D010".to_string(),
+            message: format!(
+                "Global permission '{perm}' is a broad grant for unsandboxed cycle(s): {}",
+                names.join(", ")
+            ),
+            suggestion: Some(suggestion(names[0])),
+            cycle_name: None,
+        });
+    }
+    for cycle in low_trust {
+        let step_perms = cycle.steps.iter().flat_map(|s| &s.permissions);
+        let mut broad: Vec<&String> = cycle
+            .permissions
+            .iter()
+            .chain(step_perms)
+            .filter(|p| is_broad_grant(p))
+            .collect();
+        broad.sort_unstable();
+        broad.dedup();
+        for perm in broad {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                code: "D010".to_string(),
+                message: format!("Cycle '{}' grants '{perm}' without a sandbox", cycle.name),
+                suggestion: Some(suggestion(&cycle.name)),
+                cycle_name: Some(cycle.name.clone()),
+            });
+        }
+    }
+}
+
+/// Context window of current Claude models, in tokens
+const MODEL_CONTEXT_TOKENS: u64 = 200_000;
+
+/// Estimated prompt size that leaves too little of the context window for the
+/// session itself (D011)
+const PROMPT_WARN_TOKENS: u64 = MODEL_CONTEXT_TOKENS / 2;
+
+/// Estimated prompt size above which a prompt is reported as unusually large (D012)
+const LARGE_PROMPT_TOKENS: u64 = 20_000;
+
+/// D011/D012: Estimate each cycle or step prompt, plus system prompts and the
+/// history its `context` mode would inject today, and flag oversized ones
+fn check_prompt_size(config: &FlowConfig, log: &[CycleOutcome], findings: &mut Vec<Finding>) {
+    for cycle in &config.cycles {
+        let system_prompts: [Option<String>; 2] = cycle.system_prompts(&config.global).into();
+        let system_tokens = system_prompts
+            .iter()
+            .flatten()
+            .map(|text| estimate_tokens(text))
+            .sum::<u64>();
+        let mut check = |location: String, prompt: &str, mode: &ContextMode| {
+            let prompt_tokens = system_tokens + estimate_tokens(prompt);
+            let history = windowed(mode, cycle.context_window, log);
+            let context_tokens = build_context(mode, history).map_or(0, |c| estimate_tokens(&c));
+            if let Some(finding) =
+                prompt_size_finding(&cycle.name, &location, prompt_tokens, context_tokens)
+            {
+                findings.push(finding);
+            }
+        };
+        if cycle.is_multi_step() {
+            for step in &cycle.steps {
+                let location = format!("step '{}.{}'", cycle.name, step.name);
+                check(location, &step.prompt, cycle.step_context(step));
+            }
+        } else {
+            check(
+                format!("cycle '{}'", cycle.name),
+                &cycle.prompt,
+                &cycle.context,
+            );
+        }
+    }
+}
+
+/// The D011/D012 finding for a prompt of the given size, if it is oversized
+fn prompt_size_finding(
+    cycle_name: &str,
+    location: &str,
+    prompt_tokens: u64,
+    context_tokens: u64,
+) -> Option<Finding> {
+    let total = prompt_tokens + context_tokens;
+    let (severity, code) = if total >= PROMPT_WARN_TOKENS {
+        (Severity::Warning, "D011")
+    } else if total >= LARGE_PROMPT_TOKENS {
+        (Severity::Info, "D012")
+    } else {
+        return None;
+    };
+    let suggestion = if context_tokens > prompt_tokens {
+        format!("Set `context = \"summaries\"` or `\"none\"` for {location} to stop injecting the full history")
+    } else {
+        format!("Trim the prompt for {location}, or move reference material into files Claude can read on demand")
+    };
+    Some(Finding {
+        severity,
+        code: code.to_string(),
+        message: format!(
+            "Prompt for {location} is ~{total} tokens ({prompt_tokens} prompt + {context_tokens} history), {}% of a {MODEL_CONTEXT_TOKENS}-token context window",
+            total * 100 / MODEL_CONTEXT_TOKENS
+        ),
+        suggestion: Some(suggestion),
+        cycle_name: Some(cycle_name.to_string()),
+    })
+}
+
+/// Minimum number of runs that hit `max_visits` before a routing loop is reported (D013)
+const ROUTING_LOOP_MIN_RUNS: usize = 2;
+
+/// D013: Detect multi-step cycles whose router keeps bouncing between the same
+/// steps until `max_visits` stops the cycle
+fn check_routing_loops(config: &FlowConfig, log: &[CycleOutcome], findings: &mut Vec<Finding>) {
+    let logged = group_by_cycle(log);
+    for cycle in &config.cycles {
+        let routed = cycle
+            .steps
+            .iter()
+            .any(|s| s.router != StepRouter::Sequential);
+        let Some(entries) = logged.get(cycle.name.as_str()).filter(|_| routed) else {
+            continue;
+        };
+        let looped: Vec<&[StepOutcome]> = entries
+            .iter()
+            .filter_map(|e| e.steps.as_deref())
+            .filter(|steps| hit_max_visits(cycle, steps))
+            .collect();
+        if looped.len() < ROUTING_LOOP_MIN_RUNS {
+            continue;
+        }
+
+        let mut pairs: HashMap<(&str, &str), usize> = HashMap::new();
+        for steps in &looped {
+            for window in steps.windows(2) {
+                let (a, b) = (window[0].name.as_str(), window[1].name.as_str());
+                if a != b {
+                    *pairs.entry((a.min(b), a.max(b))).or_default() += 1;
+                }
+            }
+        }
+        let Some(((a, b), _)) = pairs.into_iter().max_by_key(|&(pair, n)| (n, pair)) else {
+            continue;
+        };
+        let spent: f64 = looped
+            .iter()
+            .flat_map(|steps| steps.iter())
+            .filter_map(|s| s.cost_usd)
+            .sum();
+        let router_step = cycle
+            .steps
+            .iter()
+            .find(|s| (s.name == a || s.name == b) && s.router != StepRouter::Sequential)
+            .map_or(b, |s| s.name.as_str());
+        findings.push(Finding {
+            severity: Severity::Warning,
+            code: "D013".to_string(),
+            message: format!(
+                "Cycle '{}' hit max_visits in {} of {} run(s), bouncing between '{a}' and '{b}' (${spent:.2} spent in those runs)",
+                cycle.name,
+                looped.len(),
+                entries.len()
+            ),
+            suggestion: Some(format!(
+                "Tell '{router_step}' when the work is done (or route it with a script that has an explicit exit), and lower max_visits to cap the waste"
+            )),
+            cycle_name: Some(cycle.name.clone()),
+        });
+    }
+}
+
+/// Whether a run revisited some step up to its configured `max_visits`
+fn hit_max_visits(cycle: &CycleConfig, steps: &[StepOutcome]) -> bool {
+    cycle.steps.iter().any(|step| {
+        let visits = steps.iter().filter(|s| s.name == step.name).count();
+        visits >= 2 && visits >= step.max_visits as usize
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -475,6 +833,78 @@ min_interval = 3
         assert!(!report.is_clean());
     }
 
+    #[test]
+    fn test_has_findings_at_or_above() {
+        let report = DiagnosticReport {
+            findings: vec![Finding {
+                severity: Severity::Warning,
+                code: "W1".to_string(),
+                message: "warning".to_string(),
+                suggestion: None,
+                cycle_name: None,
+            }],
+        };
+
+        assert!(!report.has_findings_at_or_above(&Severity::Error));
+        assert!(report.has_findings_at_or_above(&Severity::Warning));
+        assert!(report.has_findings_at_or_above(&Severity::Info));
+    }
+
+    #[test]
+    fn test_clean_report_has_no_findings_at_any_severity() {
+        let report = DiagnosticReport { findings: vec![] };
+        assert!(!report.has_findings_at_or_above(&Severity::Info));
+    }
+
+    // --- Suppression ---
+
+    #[test]
+    fn test_suppressed_codes_are_omitted() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = []
+
+[doctor]
+suppress = ["D005"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#,
+        )
+        .unwrap();
+
+        let report = diagnose(&config, &[]);
+        assert!(
+            !report.findings.iter().any(|f| f.code == "D005"),
+            "D005 should be suppressed by config"
+        );
+    }
+
+    #[test]
+    fn test_unsuppressed_codes_still_reported() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = []
+
+[doctor]
+suppress = ["D004"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#,
+        )
+        .unwrap();
+
+        let report = diagnose(&config, &[]);
+        assert!(report.findings.iter().any(|f| f.code == "D005"));
+    }
+
     // --- D001: Permission denial detection ---
 
     #[test]
@@ -786,7 +1216,386 @@ min_interval = 3
         );
     }
 
-    // --- Ordering ---
+    // --- D007/D008: Config drift ---
+
+    #[test]
+    fn test_d007_flags_cycle_that_never_ran() {
+        let config = basic_config();
+        let log: Vec<_> = (1..=10)
+            .map(|i| make_outcome(i, "coding", "done"))
+            .collect();
+
+        let report = diagnose(&config, &log);
+        let d007 = report.findings.iter().find(|f| f.code == "D007");
+        assert!(d007.is_some(), "Should flag gardening as never run");
+        assert_eq!(d007.unwrap().cycle_name.as_deref(), Some("gardening"));
+        assert!(d007.unwrap().message.contains("10 logged iteration"));
+    }
+
+    #[test]
+    fn test_d007_no_finding_before_threshold() {
+        let config = basic_config();
+        let log: Vec<_> = (1..=9).map(|i| make_outcome(i, "coding", "done")).collect();
+
+        let report = diagnose(&config, &log);
+        assert!(
+            !report.findings.iter().any(|f| f.code == "D007"),
+            "Should not flag unused cycles before enough iterations have run"
+        );
+    }
+
+    #[test]
+    fn test_d007_no_finding_when_all_cycles_ran() {
+        let config = basic_config();
+        let mut log: Vec<_> = (1..=10)
+            .map(|i| make_outcome(i, "coding", "done"))
+            .collect();
+        log.push(make_outcome(10, "gardening", "done"));
+
+        let report = diagnose(&config, &log);
+        assert!(!report.findings.iter().any(|f| f.code == "D007"));
+    }
+
+    #[test]
+    fn test_d008_flags_logged_cycle_missing_from_config() {
+        let config = basic_config();
+        let log = vec![
+            make_outcome(1, "coding", "done"),
+            make_outcome(2, "refactor", "done"),
+            make_outcome(3, "refactor", "done"),
+        ];
+
+        let report = diagnose(&config, &log);
+        let d008: Vec<_> = report
+            .findings
+            .iter()
+            .filter(|f| f.code == "D008")
+            .collect();
+        assert_eq!(
+            d008.len(),
+            1,
+            "Should produce one finding per orphaned cycle"
+        );
+        assert_eq!(d008[0].severity, Severity::Warning);
+        assert_eq!(d008[0].cycle_name.as_deref(), Some("refactor"));
+        assert!(d008[0].message.contains("2 time(s)"));
+    }
+
+    #[test]
+    fn test_d008_no_finding_for_configured_cycles() {
+        let config = basic_config();
+        let log = vec![
+            make_outcome(1, "coding", "done"),
+            make_outcome(2, "gardening", "done"),
+        ];
+
+        let report = diagnose(&config, &log);
+        assert!(!report.findings.iter().any(|f| f.code == "D008"));
+    }
+
+    // --- D009: Shadowed permissions ---
+
+    #[test]
+    fn test_d009_flags_permissions_covered_by_broader_ones() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = ["Read", "Edit(./**)", "Edit(./src/**)"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+permissions = ["Read", "Bash(cargo *)", "Bash(cargo test *)"]
+"#,
+        )
+        .unwrap();
+
+        let report = diagnose(&config, &[]);
+        let messages: Vec<&str> = report
+            .findings
+            .iter()
+            .filter(|f| f.code == "D009")
+            .map(|f| f.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Permission 'Edit(./src/**)' in global permissions is already covered by 'Edit(./**)'",
+                "Permission 'Read' in cycle 'coding' is already covered by 'Read'",
+                "Permission 'Bash(cargo test *)' in cycle 'coding' is already covered by 'Bash(cargo *)'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_d009_reports_only_later_duplicate_in_step() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = ["Read"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+
+[[cycle.step]]
+name = "fix"
+prompt = "Fix"
+permissions = ["Bash(git *)", "Bash(git *)"]
+"#,
+        )
+        .unwrap();
+
+        let d009: Vec<_> = diagnose(&config, &[])
+            .findings
+            .into_iter()
+            .filter(|f| f.code == "D009")
+            .collect();
+        assert_eq!(d009.len(), 1);
+        assert!(d009[0].message.contains("step 'coding.fix'"));
+        assert_eq!(d009[0].cycle_name.as_deref(), Some("coding"));
+    }
+
+    #[test]
+    fn test_d009_silent_for_basic_config() {
+        let report = diagnose(&basic_config(), &[]);
+        assert!(!report.findings.iter().any(|f| f.code == "D009"));
+    }
+
+    // --- D010: Broad permissions ---
+
+    #[test]
+    fn test_d010_warns_broad_grant_on_unsandboxed_cycle() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = ["Read"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+permissions = ["Bash(*)"]
+
+[[cycle]]
+name = "boxed"
+description = "Boxed"
+prompt = "Code"
+permissions = ["Bash(*)"]
+sandbox = "docker"
+sandbox_image = "flow-sandbox"
+"#,
+        )
+        .unwrap();
+
+        let d010: Vec<_> = diagnose(&config, &[])
+            .findings
+            .into_iter()
+            .filter(|f| f.code == "D010")
+            .collect();
+        assert_eq!(d010.len(), 1);
+        assert_eq!(d010[0].severity, Severity::Warning);
+        assert_eq!(d010[0].cycle_name.as_deref(), Some("coding"));
+    }
+
+    #[test]
+    fn test_d010_global_grant_reported_once_and_trusted_cycles_exempt() {
+        let toml = r#"
+[global]
+permissions = ["Read", "Edit(./**)"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review"
+"#;
+        let config = FlowConfig::parse(toml).unwrap();
+        let d010: Vec<_> = diagnose(&config, &[])
+            .findings
+            .into_iter()
+            .filter(|f| f.code == "D010")
+            .collect();
+        assert_eq!(d010.len(), 1);
+        assert!(d010[0].message.contains("coding, review"));
+
+        let trusted = format!("{toml}\n[doctor]\ntrusted_cycles = [\"coding\", \"review\"]\n");
+        let config = FlowConfig::parse(&trusted).unwrap();
+        let report = diagnose(&config, &[]);
+        assert!(!report.findings.iter().any(|f| f.code == "D010"));
+    }
+
+    #[test]
+    fn test_d010_reports_grant_repeated_on_steps_once() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = ["Read"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+permissions = ["Bash(*)", "Edit(./**)"]
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement"
+permissions = ["Bash(*)"]
+"#,
+        )
+        .unwrap();
+
+        let mut d010: Vec<String> = diagnose(&config, &[])
+            .findings
+            .into_iter()
+            .filter(|f| f.code == "D010")
+            .map(|f| f.message)
+            .collect();
+        d010.sort_unstable();
+        assert_eq!(
+            d010,
+            [
+                "Cycle 'coding' grants 'Bash(*)' without a sandbox",
+                "Cycle 'coding' grants 'Edit(./**)' without a sandbox",
+            ]
+        );
+    }
+
+    // --- D011/D012: Prompt size ---
+
+    #[test]
+    fn test_d012_flags_large_prompt() {
+        let mut config = basic_config();
+        config.cycles[0].prompt = "x".repeat(100_000);
+
+        let report = diagnose(&config, &[]);
+        let d012 = report.findings.iter().find(|f| f.code == "D012").unwrap();
+        assert_eq!(d012.severity, Severity::Info);
+        assert_eq!(d012.cycle_name.as_deref(), Some("coding"));
+        assert!(d012.message.contains("~25"), "{}", d012.message);
+        assert!(!report.findings.iter().any(|f| f.code == "D011"));
+    }
+
+    #[test]
+    fn test_d011_warns_when_full_history_nears_context_window() {
+        let mut config = basic_config();
+        config.cycles[0].context = ContextMode::Full;
+        let mut outcome = make_outcome(1, "coding", "Did things");
+        outcome.files_changed = vec!["src/some/long/path/to/a/file.rs".to_string(); 200];
+        let log = vec![outcome; 100];
+
+        let report = diagnose(&config, &log);
+        let d011 = report.findings.iter().find(|f| f.code == "D011").unwrap();
+        assert_eq!(d011.severity, Severity::Warning);
+        assert!(d011.suggestion.as_ref().unwrap().contains("summaries"));
+    }
+
+    #[test]
+    fn test_prompt_size_silent_for_small_prompts() {
+        let report = diagnose(&basic_config(), &[make_outcome(1, "coding", "Done")]);
+        assert!(!report
+            .findings
+            .iter()
+            .any(|f| f.code == "D011" || f.code == "D012"));
+    }
+
+    // --- D013: Routing loops ---
+
+    fn routed_config() -> FlowConfig {
+        FlowConfig::parse(
+            r#"
+[global]
+permissions = ["Read"]
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement"
+
+[[cycle.step]]
+name = "review"
+prompt = "Review"
+router = "llm"
+"#,
+        )
+        .unwrap()
+    }
+
+    fn stepped_outcome(iteration: u32, names: &[&str]) -> CycleOutcome {
+        let mut outcome = make_outcome(iteration, "coding", "Done");
+        outcome.steps = Some(
+            names
+                .iter()
+                .map(|name| StepOutcome {
+                    name: (*name).to_string(),
+                    session: None,
+                    duration_secs: 10,
+                    num_turns: None,
+                    cost_usd: Some(0.5),
+                    exit_code: None,
+                })
+                .collect(),
+        );
+        outcome
+    }
+
+    #[test]
+    fn test_d013_detects_repeated_bouncing() {
+        let bounce = [
+            "implement",
+            "review",
+            "implement",
+            "review",
+            "implement",
+            "review",
+        ];
+        let log = vec![
+            stepped_outcome(1, &bounce),
+            stepped_outcome(2, &["implement", "review"]),
+            stepped_outcome(3, &bounce),
+        ];
+
+        let report = diagnose(&routed_config(), &log);
+        let d013 = report.findings.iter().find(|f| f.code == "D013").unwrap();
+        assert_eq!(d013.severity, Severity::Warning);
+        assert_eq!(
+            d013.message,
+            "Cycle 'coding' hit max_visits in 2 of 3 run(s), bouncing between 'implement' and 'review' ($6.00 spent in those runs)"
+        );
+        assert!(d013
+            .suggestion
+            .as_ref()
+            .unwrap()
+            .starts_with("Tell 'review'"));
+    }
+
+    #[test]
+    fn test_d013_ignores_single_loop_and_sequential_cycles() {
+        let bounce = [
+            "implement",
+            "review",
+            "implement",
+            "review",
+            "implement",
+            "review",
+        ];
+        let report = diagnose(&routed_config(), &[stepped_outcome(1, &bounce)]);
+        assert!(!report.findings.iter().any(|f| f.code == "D013"));
+
+        let mut config = routed_config();
+        config.cycles[0].steps[1].router = StepRouter::Sequential;
+        let log = vec![stepped_outcome(1, &bounce), stepped_outcome(2, &bounce)];
+        let report = diagnose(&config, &log);
+        assert!(!report.findings.iter().any(|f| f.code == "D013"));
+    }
 
     // --- cycle_name field ---
 
@@ -907,7 +1716,7 @@ after = ["coding"]
         // Re-read and verify min_interval was set
         let updated = FlowConfig::from_path(&config_path).unwrap();
         let gardening = updated.get_cycle("gardening").unwrap();
-        assert_eq!(gardening.min_interval, Some(3));
+        assert_eq!(gardening.min_interval, Some(MinInterval::Iterations(3)));
     }
 
     #[test]
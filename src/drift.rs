@@ -0,0 +1,335 @@
+//! Permission drift report (`flow permissions diff`)
+//!
+//! Compares the permissions `cycles.toml` grants with what the log shows
+//! cycles actually doing: grants no run exercised (candidates for removal)
+//! and tools denied again and again (candidates for a grant, or for a prompt
+//! that stops reaching for them).
+//!
+//! A grant counts as exercised when its tool appears in an entry's
+//! `tools_used`, and, for a pattern, when a logged shell command (`Bash`) or
+//! changed file (file tools) falls under it. Entries logged before Flow
+//! recorded `tools_used` carry no usage and are left out of the unused check.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::claude::permissions::{
+    permission_covers, resolve_permissions, resolve_step_permissions,
+};
+use crate::claude::stream::suggest_permission_fix;
+use crate::cycle::config::FlowConfig;
+use crate::log::CycleOutcome;
+
+/// Tools whose permission pattern is a file path
+const FILE_TOOLS: [&str; 4] = ["Edit", "Write", "MultiEdit", "NotebookEdit"];
+
+/// A tool a cycle was denied repeatedly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeniedTool {
+    /// Tool name as reported by Claude Code (e.g. `Edit`)
+    pub tool: String,
+    /// Denials across the cycle's logged runs
+    pub count: u32,
+    /// Permission string that would allow it
+    pub suggestion: String,
+}
+
+/// Drift between one cycle's grants and its history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleDrift {
+    /// Cycle name
+    pub cycle: String,
+    /// Logged runs of the cycle
+    pub runs: usize,
+    /// Logged runs that recorded tool usage
+    pub runs_with_usage: usize,
+    /// Cycle and step grants no logged run exercised
+    pub unused: Vec<String>,
+    /// Tools denied at least the threshold number of times, most denied first
+    pub denied: Vec<DeniedTool>,
+}
+
+/// Permission drift across every configured cycle
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DriftReport {
+    /// One entry per configured cycle, in config order
+    pub cycles: Vec<CycleDrift>,
+    /// `[global]` grants no cycle exercised
+    pub unused_global: Vec<String>,
+}
+
+impl DriftReport {
+    /// Whether there is nothing to recommend.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.unused_global.is_empty()
+            && self
+                .cycles
+                .iter()
+                .all(|c| c.unused.is_empty() && c.denied.is_empty())
+    }
+}
+
+/// Compare `config`'s grants with the log `entries`, reporting tools denied
+/// at least `min_denials` times. Changed-file paths are made relative to
+/// `project_dir` before matching file-tool patterns.
+#[must_use]
+pub fn permission_drift(
+    config: &FlowConfig,
+    entries: &[CycleOutcome],
+    project_dir: &Path,
+    min_denials: u32,
+) -> DriftReport {
+    let cycles = config
+        .cycles
+        .iter()
+        .map(|cycle| {
+            let runs: Vec<&CycleOutcome> =
+                entries.iter().filter(|e| e.cycle == cycle.name).collect();
+            let with_usage: Vec<&CycleOutcome> = runs
+                .iter()
+                .copied()
+                .filter(|e| e.tools_used.is_some())
+                .collect();
+            let mut grants = resolve_permissions(&config.global, cycle);
+            for step in &cycle.steps {
+                for perm in resolve_step_permissions(&config.global, cycle, step) {
+                    if !grants.contains(&perm) {
+                        grants.push(perm);
+                    }
+                }
+            }
+            let unused = if with_usage.is_empty() {
+                Vec::new()
+            } else {
+                grants
+                    .into_iter()
+                    .filter(|perm| !config.global.permissions.contains(perm))
+                    .filter(|perm| !with_usage.iter().any(|e| exercises(e, perm, project_dir)))
+                    .collect()
+            };
+            CycleDrift {
+                cycle: cycle.name.clone(),
+                runs: runs.len(),
+                runs_with_usage: with_usage.len(),
+                unused,
+                denied: denied_tools(&runs, min_denials),
+            }
+        })
+        .collect();
+
+    let with_usage: Vec<&CycleOutcome> =
+        entries.iter().filter(|e| e.tools_used.is_some()).collect();
+    let unused_global = if with_usage.is_empty() {
+        Vec::new()
+    } else {
+        config
+            .global
+            .permissions
+            .iter()
+            .filter(|perm| !with_usage.iter().any(|e| exercises(e, perm, project_dir)))
+            .cloned()
+            .collect()
+    };
+    DriftReport {
+        cycles,
+        unused_global,
+    }
+}
+
+/// Tools denied at least `min_denials` times across `runs`, most denied first.
+fn denied_tools(runs: &[&CycleOutcome], min_denials: u32) -> Vec<DeniedTool> {
+    let mut counts: BTreeMap<&str, u32> = BTreeMap::new();
+    for tool in runs
+        .iter()
+        .filter_map(|e| e.permission_denials.as_ref())
+        .flatten()
+    {
+        *counts.entry(tool.as_str()).or_default() += 1;
+    }
+    let mut denied: Vec<DeniedTool> = counts
+        .into_iter()
+        .filter(|&(_, count)| count >= min_denials.max(1))
+        .map(|(tool, count)| DeniedTool {
+            tool: tool.to_string(),
+            count,
+            suggestion: suggest_permission_fix(tool),
+        })
+        .collect();
+    denied.sort_by_key(|d| std::cmp::Reverse(d.count));
+    denied
+}
+
+/// Whether a logged run exercised `perm`.
+fn exercises(entry: &CycleOutcome, perm: &str, project_dir: &Path) -> bool {
+    let tool = perm.split_once('(').map_or(perm, |(tool, _)| tool);
+    let Some(tools_used) = &entry.tools_used else {
+        return false;
+    };
+    let pattern = perm.contains('(');
+    if tool == "Bash" && pattern {
+        return entry
+            .commands_run
+            .iter()
+            .flatten()
+            .any(|cmd| permission_covers(perm, &format!("Bash({cmd})")));
+    }
+    if FILE_TOOLS.contains(&tool) && pattern {
+        return tools_used.contains_key(tool)
+            && entry.files_changed.iter().any(|file| {
+                permission_covers(perm, &format!("{tool}({})", relative(file, project_dir)))
+            });
+    }
+    tools_used.contains_key(tool)
+}
+
+/// `file` as a `./`-prefixed path relative to `project_dir`, as patterns are written.
+fn relative(file: &str, project_dir: &Path) -> String {
+    let path = Path::new(file);
+    let relative = path.strip_prefix(project_dir).unwrap_or(path);
+    let relative = relative.to_string_lossy();
+    if relative.starts_with("./") || relative.starts_with('/') {
+        relative.to_string()
+    } else {
+        format!("./{relative}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::make_test_outcome;
+
+    const CONFIG: &str = r#"
+[global]
+permissions = ["Read", "WebFetch"]
+
+[[cycle]]
+name = "coding"
+description = "Implement"
+prompt = "Code"
+permissions = ["Edit(./src/**)", "Edit(./docs/**)", "Bash(cargo test *)", "Bash(npm *)"]
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review"
+"#;
+
+    fn config() -> FlowConfig {
+        FlowConfig::parse(CONFIG).unwrap()
+    }
+
+    fn run(
+        cycle: &str,
+        tools: &[(&str, u32)],
+        files: &[&str],
+        commands: &[&str],
+        denials: &[&str],
+    ) -> CycleOutcome {
+        let mut entry = make_test_outcome(1, cycle, "Done");
+        entry.tools_used = Some(tools.iter().map(|(t, n)| ((*t).to_string(), *n)).collect());
+        entry.files_changed = files.iter().map(|f| (*f).to_string()).collect();
+        entry.commands_run = Some(commands.iter().map(|c| (*c).to_string()).collect());
+        entry.permission_denials = Some(denials.iter().map(|d| (*d).to_string()).collect());
+        entry
+    }
+
+    fn coding(report: &DriftReport) -> &CycleDrift {
+        report.cycles.iter().find(|c| c.cycle == "coding").unwrap()
+    }
+
+    #[test]
+    fn test_unused_cycle_grants_are_reported() {
+        let entries = vec![run(
+            "coding",
+            &[("Edit", 2), ("Bash", 1), ("Read", 3)],
+            &["/repo/src/main.rs"],
+            &["cargo test --lib"],
+            &[],
+        )];
+        let report = permission_drift(&config(), &entries, Path::new("/repo"), 2);
+        assert_eq!(
+            coding(&report).unused,
+            vec!["Edit(./docs/**)", "Bash(npm *)"]
+        );
+        assert_eq!(report.unused_global, vec!["WebFetch"]);
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_relative_file_paths_match_patterns() {
+        let entries = vec![run(
+            "coding",
+            &[("Edit", 1)],
+            &["docs/guide.md", "src/lib.rs"],
+            &[],
+            &[],
+        )];
+        let report = permission_drift(&config(), &entries, Path::new("/repo"), 2);
+        let unused = &coding(&report).unused;
+        assert!(
+            !unused.contains(&"Edit(./docs/**)".to_string()),
+            "{unused:?}"
+        );
+        assert!(
+            !unused.contains(&"Edit(./src/**)".to_string()),
+            "{unused:?}"
+        );
+    }
+
+    #[test]
+    fn test_repeated_denials_are_reported_with_suggestion() {
+        let entries = vec![
+            run("coding", &[("Read", 1)], &[], &[], &["Write", "Glob"]),
+            run("coding", &[("Read", 1)], &[], &[], &["Write"]),
+        ];
+        let report = permission_drift(&config(), &entries, Path::new("/repo"), 2);
+        let denied = &coding(&report).denied;
+        assert_eq!(denied.len(), 1, "Glob was denied only once: {denied:?}");
+        assert_eq!(denied[0].tool, "Write");
+        assert_eq!(denied[0].count, 2);
+        assert_eq!(denied[0].suggestion, "Write(./**) or Write(./src/**)");
+    }
+
+    #[test]
+    fn test_runs_without_usage_skip_unused_check() {
+        let entries = vec![make_test_outcome(1, "coding", "Done")];
+        let report = permission_drift(&config(), &entries, Path::new("/repo"), 2);
+        let coding = coding(&report);
+        assert_eq!((coding.runs, coding.runs_with_usage), (1, 0));
+        assert!(coding.unused.is_empty());
+        assert!(report.unused_global.is_empty());
+    }
+
+    #[test]
+    fn test_cycle_without_history_has_nothing_to_report() {
+        let report = permission_drift(&config(), &[], Path::new("/repo"), 2);
+        let review = report.cycles.iter().find(|c| c.cycle == "review").unwrap();
+        assert_eq!(review.runs, 0);
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_step_grants_are_checked() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "planned"
+description = "Plan then code"
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan"
+permissions = ["Write(./PLAN.md)"]
+"#,
+        )
+        .unwrap();
+        let entries = vec![run("planned", &[("Read", 1)], &[], &[], &[])];
+        let report = permission_drift(&config, &entries, Path::new("/repo"), 2);
+        assert_eq!(report.cycles[0].unused, vec!["Write(./PLAN.md)"]);
+    }
+}
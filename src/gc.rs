@@ -0,0 +1,310 @@
+//! Housekeeping for the log directory (`flow gc`)
+//!
+//! Prunes what accumulates in `.flow/` across runs, per the `[gc]` retention
+//! settings: old `--debug-stream` transcripts and `--record` recordings,
+//! rotated or backed-up logs (`log.jsonl.*`), persisted sessions nobody has
+//! resumed lately, leftovers of interrupted writes (`*.tmp`) and crashed runs
+//! (a dead process's lock), and the progress file of a finished run. The live
+//! `log.jsonl` is never touched.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+
+use crate::claude::session::{load_sessions, prune_sessions, save_sessions};
+use crate::cycle::config::GcConfig;
+use crate::log::lock::live_holder;
+
+/// Seconds in a retention day
+const SECS_PER_DAY: u64 = 86_400;
+
+/// A file `flow gc` removed (or would remove, in a dry run)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Removal {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Size in bytes
+    pub bytes: u64,
+    /// Why the file is garbage (e.g. "debug transcript older than 14 day(s)")
+    pub reason: String,
+}
+
+/// What a `flow gc` pass removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Files removed, in the order they were found
+    pub removals: Vec<Removal>,
+    /// Tags of persisted sessions pruned from `sessions.json`
+    pub pruned_sessions: Vec<String>,
+}
+
+impl GcReport {
+    /// Total size of the removed files
+    #[must_use]
+    pub fn bytes_freed(&self) -> u64 {
+        self.removals.iter().map(|r| r.bytes).sum()
+    }
+
+    /// Whether nothing was (or would be) removed
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.removals.is_empty() && self.pruned_sessions.is_empty()
+    }
+}
+
+/// Remove garbage from `log_dir`, judging ages against `now`. With `dry_run`,
+/// nothing is deleted and the report lists what would be.
+///
+/// # Errors
+/// Returns an error if a live run holds the lock, or a file can't be
+/// inspected or removed.
+pub fn collect(
+    log_dir: &Path,
+    settings: &GcConfig,
+    now: SystemTime,
+    dry_run: bool,
+) -> Result<GcReport> {
+    if let Some(holder) = live_holder(log_dir) {
+        bail!(
+            "A flow run (PID {}) is in progress in {}; run `flow gc` after it finishes",
+            holder.pid,
+            log_dir.display()
+        );
+    }
+
+    let mut removals = expired_files(
+        &log_dir.join("debug"),
+        settings.debug_days,
+        now,
+        "debug transcript",
+    )?;
+    removals.extend(expired_files(
+        &log_dir.join("recordings"),
+        settings.recording_days,
+        now,
+        "recording",
+    )?);
+    removals.extend(top_level_garbage(log_dir, settings, now)?);
+    if !dry_run {
+        for removal in &removals {
+            std::fs::remove_file(&removal.path)
+                .with_context(|| format!("Failed to remove {}", removal.path.display()))?;
+        }
+    }
+
+    let sessions_path = log_dir.join("sessions.json");
+    let mut sessions = load_sessions(&sessions_path)?;
+    let cutoff = chrono::DateTime::<chrono::Utc>::from(now)
+        - chrono::Duration::days(i64::from(settings.session_days));
+    let pruned_sessions = prune_sessions(&mut sessions, cutoff);
+    if !dry_run && !pruned_sessions.is_empty() {
+        save_sessions(&sessions_path, &sessions)?;
+    }
+
+    Ok(GcReport {
+        removals,
+        pruned_sessions,
+    })
+}
+
+/// Files directly in `dir` last modified more than `days` before `now`.
+fn expired_files(dir: &Path, days: u32, now: SystemTime, kind: &str) -> Result<Vec<Removal>> {
+    let mut removals = Vec::new();
+    for (path, metadata) in files_in(dir)? {
+        if is_older_than(&metadata, days, now) {
+            removals.push(Removal {
+                path,
+                bytes: metadata.len(),
+                reason: format!("{kind} older than {days} day(s)"),
+            });
+        }
+    }
+    Ok(removals)
+}
+
+/// Garbage directly in the log directory: expired rotated logs, `*.tmp`
+/// leftovers, a dead run's lock, and a finished run's progress file.
+fn top_level_garbage(log_dir: &Path, settings: &GcConfig, now: SystemTime) -> Result<Vec<Removal>> {
+    let mut removals = Vec::new();
+    for (path, metadata) in files_in(log_dir)? {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let reason = if name.ends_with(".tmp") {
+            "leftover from an interrupted write".to_string()
+        } else if name.starts_with("log.jsonl.") {
+            if !is_older_than(&metadata, settings.log_backup_days, now) {
+                continue;
+            }
+            format!("rotated log older than {} day(s)", settings.log_backup_days)
+        } else if name == "lock" {
+            "lock left by a run that is no longer alive".to_string()
+        } else if name == "progress.json" {
+            "progress of a finished run".to_string()
+        } else {
+            continue;
+        };
+        removals.push(Removal {
+            path,
+            bytes: metadata.len(),
+            reason,
+        });
+    }
+    Ok(removals)
+}
+
+/// Regular files directly in `dir`, sorted by path (empty if `dir` is missing).
+fn files_in(dir: &Path) -> Result<Vec<(PathBuf, std::fs::Metadata)>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+        let metadata = entry
+            .metadata()
+            .with_context(|| format!("Failed to inspect {}", entry.path().display()))?;
+        if metadata.is_file() {
+            files.push((entry.path(), metadata));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Whether a file was last modified more than `days` before `now`.
+fn is_older_than(metadata: &std::fs::Metadata, days: u32, now: SystemTime) -> bool {
+    let retention = Duration::from_secs(u64::from(days) * SECS_PER_DAY);
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| now.duration_since(modified).ok())
+        .is_some_and(|age| age > retention)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    use crate::claude::session::PersistedSession;
+
+    fn days(n: u64) -> Duration {
+        Duration::from_secs(n * SECS_PER_DAY)
+    }
+
+    /// Write `path` (creating parents) with a modification time `age` ago.
+    fn write_aged(path: &Path, age: Duration) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "data").unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    fn removed_names(report: &GcReport) -> Vec<String> {
+        report
+            .removals
+            .iter()
+            .map(|r| r.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    // --- collect ---
+
+    #[test]
+    fn test_collect_removes_expired_transcripts_and_recordings() {
+        let dir = TempDir::new().unwrap();
+        write_aged(&dir.path().join("debug/old.log"), days(20));
+        write_aged(&dir.path().join("debug/new.log"), days(1));
+        write_aged(&dir.path().join("recordings/old.jsonl"), days(40));
+        write_aged(&dir.path().join("recordings/new.jsonl"), days(10));
+        write_aged(&dir.path().join("log.jsonl"), days(100));
+
+        let report = collect(dir.path(), &GcConfig::default(), SystemTime::now(), false).unwrap();
+
+        assert_eq!(removed_names(&report), ["old.log", "old.jsonl"]);
+        assert_eq!(report.bytes_freed(), 8);
+        assert!(!dir.path().join("debug/old.log").exists());
+        assert!(dir.path().join("debug/new.log").exists());
+        assert!(dir.path().join("recordings/new.jsonl").exists());
+        assert!(dir.path().join("log.jsonl").exists(), "live log is kept");
+    }
+
+    #[test]
+    fn test_collect_removes_leftovers_and_expired_backups() {
+        let dir = TempDir::new().unwrap();
+        write_aged(&dir.path().join("log.jsonl.bak"), days(45));
+        write_aged(&dir.path().join("log.jsonl.1"), days(2));
+        write_aged(&dir.path().join("progress.json.tmp"), days(0));
+        write_aged(&dir.path().join("progress.json"), days(0));
+        write_aged(&dir.path().join("last-run-summary.md"), days(90));
+
+        let report = collect(dir.path(), &GcConfig::default(), SystemTime::now(), false).unwrap();
+
+        assert_eq!(
+            removed_names(&report),
+            ["log.jsonl.bak", "progress.json", "progress.json.tmp"]
+        );
+        assert!(dir.path().join("log.jsonl.1").exists());
+        assert!(dir.path().join("last-run-summary.md").exists());
+    }
+
+    #[test]
+    fn test_collect_prunes_stale_sessions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sessions.json");
+        let saved = |age: u64| PersistedSession {
+            session_id: "sess".to_string(),
+            cycle: "coding".to_string(),
+            last_used: chrono::Utc::now() - chrono::Duration::days(i64::try_from(age).unwrap()),
+        };
+        let sessions = BTreeMap::from([
+            ("architect".to_string(), saved(60)),
+            ("reviewer".to_string(), saved(1)),
+        ]);
+        save_sessions(&path, &sessions).unwrap();
+
+        let report = collect(dir.path(), &GcConfig::default(), SystemTime::now(), false).unwrap();
+
+        assert_eq!(report.pruned_sessions, ["architect"]);
+        let remaining = load_sessions(&path).unwrap();
+        assert_eq!(remaining.keys().collect::<Vec<_>>(), ["reviewer"]);
+    }
+
+    #[test]
+    fn test_collect_dry_run_deletes_nothing() {
+        let dir = TempDir::new().unwrap();
+        write_aged(&dir.path().join("debug/old.log"), days(20));
+        write_aged(&dir.path().join("progress.json"), days(0));
+
+        let report = collect(dir.path(), &GcConfig::default(), SystemTime::now(), true).unwrap();
+
+        assert_eq!(report.removals.len(), 2);
+        assert!(dir.path().join("debug/old.log").exists());
+        assert!(dir.path().join("progress.json").exists());
+    }
+
+    #[test]
+    fn test_collect_on_missing_dir_is_empty() {
+        let dir = TempDir::new().unwrap();
+        let report = collect(
+            &dir.path().join(".flow"),
+            &GcConfig::default(),
+            SystemTime::now(),
+            false,
+        )
+        .unwrap();
+        assert!(report.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_refuses_while_run_in_progress() {
+        let dir = TempDir::new().unwrap();
+        let _lock = crate::log::lock::RunLock::acquire(dir.path(), false).unwrap();
+        let err = collect(dir.path(), &GcConfig::default(), SystemTime::now(), false).unwrap_err();
+        assert!(err.to_string().contains("in progress"), "{err}");
+    }
+}
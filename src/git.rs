@@ -0,0 +1,422 @@
+//! Git integration
+//!
+//! Thin wrappers around the `git` CLI used for working-tree snapshots and the
+//! branch-per-run strategy (`global.git_branch_template`): each run works on
+//! its own branch, commits leftover changes after every cycle, and can
+//! fast-forward the original branch or open a pull request when it finishes.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::cycle::config::GitFinish;
+
+/// Run a git command in `root` and return its trimmed stdout.
+///
+/// # Errors
+/// Returns an error if git cannot be spawned or exits non-zero (the message
+/// includes git's stderr).
+pub fn run(root: &Path, args: &[&str]) -> Result<String> {
+    output(
+        Command::new("git").current_dir(root).args(args),
+        "git",
+        args,
+    )
+}
+
+/// Like [`run`], but against an alternate index file (`GIT_INDEX_FILE`).
+///
+/// # Errors
+/// Returns an error if git cannot be spawned or exits non-zero.
+pub fn run_with_index(root: &Path, args: &[&str], index: &Path) -> Result<String> {
+    output(
+        Command::new("git")
+            .current_dir(root)
+            .args(args)
+            .env("GIT_INDEX_FILE", index),
+        "git",
+        args,
+    )
+}
+
+/// Run a prepared command and return its trimmed stdout.
+fn output(cmd: &mut Command, program: &str, args: &[&str]) -> Result<String> {
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run {program} {}", args.join(" ")))?;
+    if !output.status.success() {
+        bail!(
+            "{program} {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Current `HEAD` commit, or `None` outside a git repository (or before the
+/// first commit).
+#[must_use]
+pub fn head_sha(root: &Path) -> Option<String> {
+    run(root, &["rev-parse", "--verify", "-q", "HEAD"]).ok()
+}
+
+/// Lines added and removed between two commits or trees (`git diff --numstat`).
+/// Binary files count as no lines.
+///
+/// # Errors
+/// Returns an error if either object is unknown or git fails.
+pub fn diff_line_counts(root: &Path, from: &str, to: &str) -> Result<(u32, u32)> {
+    let numstat = run(root, &["diff", "--numstat", from, to])?;
+    Ok(parse_numstat(&numstat))
+}
+
+/// `git diff` between two trees or commits, cut to at most `max_chars`
+/// characters with a `... (diff truncated)` marker.
+///
+/// # Errors
+/// Returns an error if `git diff` fails.
+pub fn truncated_diff(root: &Path, from: &str, to: &str, max_chars: usize) -> Result<String> {
+    let diff = run(root, &["diff", from, to])?;
+    if diff.chars().count() <= max_chars {
+        return Ok(diff);
+    }
+    let mut cut: String = diff.chars().take(max_chars).collect();
+    cut.push_str("\n... (diff truncated)");
+    Ok(cut)
+}
+
+/// Sum the added/removed columns of `git diff --numstat` output.
+fn parse_numstat(output: &str) -> (u32, u32) {
+    output.lines().fold((0, 0), |(added, removed), line| {
+        let mut cols = line.split('\t');
+        let count = |col: Option<&str>| col.and_then(|c| c.parse::<u32>().ok()).unwrap_or(0);
+        let a = count(cols.next());
+        let r = count(cols.next());
+        (added.saturating_add(a), removed.saturating_add(r))
+    })
+}
+
+/// Stage and commit every change in the working tree except `exclude`.
+/// Returns whether a commit was made (false when there was nothing to commit).
+///
+/// # Errors
+/// Returns an error if staging or committing fails.
+pub fn commit_all(root: &Path, message: &str, exclude: &[String]) -> Result<bool> {
+    let excludes = exclude_pathspecs(root, exclude);
+    let mut add = vec!["add", "-A", "--", "."];
+    add.extend(excludes.iter().map(String::as_str));
+    run(root, &add)?;
+    if run(root, &["diff", "--cached", "--name-only"])?.is_empty() {
+        return Ok(false);
+    }
+    run(root, &["commit", "-q", "-m", message])?;
+    Ok(true)
+}
+
+/// `path` relative to `root`, the directory git runs in, for use in a
+/// pathspec. `None` if `path` isn't inside `root` (or is `root` itself).
+#[must_use]
+pub fn pathspec_within(root: &Path, path: &Path) -> Option<String> {
+    let resolve = |p: &Path| p.canonicalize().or_else(|_| std::path::absolute(p)).ok();
+    let relative = resolve(path)?
+        .strip_prefix(resolve(root)?)
+        .ok()?
+        .to_path_buf();
+    let relative = relative.display().to_string();
+    (!relative.is_empty()).then_some(relative)
+}
+
+/// `:(exclude)` pathspecs for the given paths. Gitignored paths are left
+/// out: git skips them anyway, and `git add` fails when a pathspec names one.
+#[must_use]
+pub fn exclude_pathspecs(root: &Path, paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .filter(|p| run(root, &["check-ignore", "-q", p.as_str()]).is_err())
+        .map(|p| format!(":(exclude){p}"))
+        .collect()
+}
+
+/// Expand a `git_branch_template` such as `flow/{run_id}`.
+#[must_use]
+#[allow(clippy::literal_string_with_formatting_args)] // `{run_id}` is our placeholder
+pub fn branch_name(template: &str, run_id: &str) -> String {
+    template.replace("{run_id}", run_id)
+}
+
+/// The branch a run works on, and the branch it started from
+#[derive(Debug, Clone)]
+pub struct RunBranch {
+    /// Repository root
+    root: PathBuf,
+    /// Branch created for this run
+    name: String,
+    /// Branch that was checked out when the run started
+    base: String,
+}
+
+impl RunBranch {
+    /// Create branch `name` at `HEAD` and switch to it.
+    ///
+    /// The working tree must be clean apart from `exclude` (the log
+    /// directory), so the user's own uncommitted work never ends up in the
+    /// run's commits.
+    ///
+    /// # Errors
+    /// Returns an error if `root` is not a git repository on a branch, has
+    /// uncommitted changes, or the branch already exists.
+    pub fn start(root: &Path, name: &str, exclude: &[String]) -> Result<Self> {
+        let base = run(root, &["rev-parse", "--abbrev-ref", "HEAD"])
+            .context("Branch-per-run needs a git repository with at least one commit")?;
+        if base == "HEAD" {
+            bail!("Branch-per-run needs a checked-out branch, but HEAD is detached");
+        }
+        let excludes = exclude_pathspecs(root, exclude);
+        let mut status = vec!["status", "--porcelain", "--", "."];
+        status.extend(excludes.iter().map(String::as_str));
+        let dirty = run(root, &status)?;
+        if !dirty.is_empty() {
+            bail!(
+                "Branch-per-run needs a clean working tree; commit or stash these changes first:\n{dirty}"
+            );
+        }
+        run(root, &["switch", "-c", name])
+            .with_context(|| format!("Failed to create run branch '{name}'"))?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            name: name.to_string(),
+            base,
+        })
+    }
+
+    /// Branch created for this run
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Branch the run started from
+    #[must_use]
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Commit any changes a cycle left uncommitted. Paths in `exclude` (the
+    /// log directory) are never committed. Returns whether a commit was made.
+    ///
+    /// # Errors
+    /// Returns an error if staging or committing fails.
+    pub fn commit_cycle(&self, message: &str, exclude: &[String]) -> Result<bool> {
+        commit_all(&self.root, message, exclude)
+    }
+
+    /// Hand the run's commits back: fast-forward the base branch onto the run
+    /// branch, or push the run branch and open a pull request with `gh`.
+    ///
+    /// # Errors
+    /// Returns an error if the base branch has diverged (fast-forward), or
+    /// pushing or creating the pull request fails.
+    pub fn finish(&self, how: GitFinish, title: &str) -> Result<()> {
+        match how {
+            GitFinish::FastForward => {
+                run(&self.root, &["switch", &self.base])?;
+                run(&self.root, &["merge", "--ff-only", &self.name]).with_context(|| {
+                    format!("Cannot fast-forward '{}' to '{}'", self.base, self.name)
+                })?;
+            }
+            GitFinish::PullRequest => {
+                run(&self.root, &["push", "-u", "origin", &self.name])?;
+                let args = [
+                    "pr",
+                    "create",
+                    "--base",
+                    &self.base,
+                    "--head",
+                    &self.name,
+                    "--title",
+                    title,
+                    "--body",
+                    "Opened automatically by flow at the end of a run.",
+                ];
+                output(
+                    Command::new("gh").current_dir(&self.root).args(args),
+                    "gh",
+                    &args,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A repository with one commit on branch `main`.
+    fn init_repo() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for args in [
+            vec!["init", "-q", "-b", "main"],
+            vec!["config", "user.email", "flow@example.com"],
+            vec!["config", "user.name", "Flow"],
+        ] {
+            run(root, &args).unwrap();
+        }
+        std::fs::write(root.join("tracked.txt"), "original\n").unwrap();
+        run(root, &["add", "tracked.txt"]).unwrap();
+        run(root, &["commit", "-q", "-m", "init"]).unwrap();
+        dir
+    }
+
+    // --- run tests ---
+
+    #[test]
+    fn test_run_reports_git_errors() {
+        let dir = TempDir::new().unwrap();
+        let err = run(dir.path(), &["rev-parse", "HEAD"]).unwrap_err();
+        assert!(
+            err.to_string().contains("git rev-parse HEAD failed"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_branch_name_expands_run_id() {
+        assert_eq!(
+            branch_name("flow/{run_id}", "20260301T120000Z-42"),
+            "flow/20260301T120000Z-42"
+        );
+        assert_eq!(branch_name("flow-run", "x"), "flow-run");
+    }
+
+    #[test]
+    fn test_head_sha() {
+        let dir = init_repo();
+        let sha = head_sha(dir.path()).unwrap();
+        assert_eq!(sha.len(), 40);
+        assert!(head_sha(TempDir::new().unwrap().path()).is_none());
+    }
+
+    #[test]
+    fn test_parse_numstat_sums_and_skips_binary() {
+        let output = "10\t2\tsrc/main.rs\n-\t-\tlogo.png\n3\t0\tREADME.md";
+        assert_eq!(parse_numstat(output), (13, 2));
+        assert_eq!(parse_numstat(""), (0, 0));
+    }
+
+    #[test]
+    fn test_diff_line_counts_between_commits() {
+        let dir = init_repo();
+        let root = dir.path();
+        let before = head_sha(root).unwrap();
+        std::fs::write(root.join("tracked.txt"), "changed\nadded\n").unwrap();
+        run(root, &["commit", "-q", "-am", "edit"]).unwrap();
+        let after = head_sha(root).unwrap();
+        assert_eq!(diff_line_counts(root, &before, &after).unwrap(), (2, 1));
+    }
+
+    // --- RunBranch tests ---
+
+    #[test]
+    fn test_pathspec_within_is_relative_to_root() {
+        let dir = init_repo();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("sub/.flow")).unwrap();
+        assert_eq!(
+            pathspec_within(&root.join("sub"), &root.join("sub/.flow")).as_deref(),
+            Some(".flow")
+        );
+        assert_eq!(
+            pathspec_within(root, &root.join("sub/.flow")).as_deref(),
+            Some("sub/.flow")
+        );
+        assert!(pathspec_within(&root.join("sub"), root).is_none());
+        assert!(pathspec_within(root, root).is_none());
+    }
+
+    #[test]
+    fn test_start_refuses_dirty_tree_outside_excluded_paths() {
+        let dir = init_repo();
+        let root = dir.path();
+        std::fs::create_dir(root.join(".flow")).unwrap();
+        std::fs::write(root.join(".flow/lock"), "{}").unwrap();
+        std::fs::write(root.join("tracked.txt"), "user edit\n").unwrap();
+
+        let err = RunBranch::start(root, "flow/run-1", &[".flow".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("tracked.txt"), "{err}");
+        assert_eq!(
+            run(root, &["rev-parse", "--abbrev-ref", "HEAD"]).unwrap(),
+            "main"
+        );
+
+        run(root, &["checkout", "tracked.txt"]).unwrap();
+        RunBranch::start(root, "flow/run-1", &[".flow".to_string()]).unwrap();
+    }
+
+    #[test]
+    fn test_start_switches_to_new_branch() {
+        let dir = init_repo();
+        let branch = RunBranch::start(dir.path(), "flow/run-1", &[]).unwrap();
+        assert_eq!(branch.base(), "main");
+        assert_eq!(
+            run(dir.path(), &["rev-parse", "--abbrev-ref", "HEAD"]).unwrap(),
+            "flow/run-1"
+        );
+    }
+
+    #[test]
+    fn test_commit_cycle_commits_changes_but_not_excluded_paths() {
+        let dir = init_repo();
+        let root = dir.path();
+        let branch = RunBranch::start(root, "flow/run-1", &[]).unwrap();
+        std::fs::write(root.join("tracked.txt"), "changed\n").unwrap();
+        std::fs::create_dir(root.join(".flow")).unwrap();
+        std::fs::write(root.join(".flow/log.jsonl"), "{}\n").unwrap();
+
+        assert!(branch
+            .commit_cycle("flow: coding", &[".flow".to_string()])
+            .unwrap());
+        let files = run(root, &["show", "--name-only", "--format=", "HEAD"]).unwrap();
+        assert_eq!(files, "tracked.txt");
+        assert!(!branch
+            .commit_cycle("flow: coding", &[".flow".to_string()])
+            .unwrap());
+    }
+
+    #[test]
+    fn test_commit_all_with_gitignored_exclude() {
+        let dir = init_repo();
+        let root = dir.path();
+        std::fs::write(root.join(".gitignore"), ".flow/\n").unwrap();
+        std::fs::create_dir(root.join(".flow")).unwrap();
+        std::fs::write(root.join(".flow/log.jsonl"), "{}\n").unwrap();
+
+        assert!(commit_all(root, "ignore logs", &[".flow".to_string()]).unwrap());
+        let files = run(root, &["show", "--name-only", "--format=", "HEAD"]).unwrap();
+        assert_eq!(files, ".gitignore");
+    }
+
+    #[test]
+    fn test_finish_fast_forwards_base() {
+        let dir = init_repo();
+        let root = dir.path();
+        let branch = RunBranch::start(root, "flow/run-1", &[]).unwrap();
+        std::fs::write(root.join("tracked.txt"), "changed\n").unwrap();
+        branch.commit_cycle("flow: coding", &[]).unwrap();
+
+        branch.finish(GitFinish::FastForward, "run").unwrap();
+        assert_eq!(
+            run(root, &["rev-parse", "--abbrev-ref", "HEAD"]).unwrap(),
+            "main"
+        );
+        assert_eq!(
+            run(root, &["rev-parse", "main"]).unwrap(),
+            run(root, &["rev-parse", "flow/run-1"]).unwrap()
+        );
+    }
+}
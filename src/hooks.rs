@@ -0,0 +1,188 @@
+//! Shell-command hooks for lifecycle events (`[hooks]`)
+//!
+//! A lightweight extension point: each configured command runs through
+//! `sh -c` with the triggering [`FlowEvent`] as a single JSON line on stdin and
+//! the hook name in `FLOW_HOOK`. Hooks run synchronously, one after another,
+//! and are killed after `timeout_secs`. Their stdout is discarded (it would
+//! corrupt `--emit-events`); stderr passes through. A failing hook is reported
+//! but never stops the run.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::cycle::config::HooksConfig;
+use crate::log::events::{event_line, FlowEvent};
+
+/// Hook names triggered by `event`, in the order they run.
+#[must_use]
+pub fn hook_names(event: &FlowEvent<'_>) -> Vec<&'static str> {
+    match event {
+        FlowEvent::RunStarted { .. } => vec!["run_start"],
+        FlowEvent::CycleCompleted { success: true, .. } => vec!["cycle_end"],
+        FlowEvent::CycleCompleted { success: false, .. } => vec!["cycle_end", "failure"],
+        FlowEvent::GateTripped { .. } => vec!["failure"],
+        FlowEvent::RunFinished { .. } => vec!["run_end"],
+        FlowEvent::IterationStarted { .. } | FlowEvent::CycleSelected { .. } => Vec::new(),
+    }
+}
+
+/// Runs the `[hooks]` commands for lifecycle events
+#[derive(Debug, Default)]
+pub struct HookRunner {
+    /// `None` when no hooks are configured
+    config: Option<HooksConfig>,
+}
+
+impl HookRunner {
+    /// Create a runner from the `[hooks]` section (if any).
+    #[must_use]
+    pub const fn new(config: Option<HooksConfig>) -> Self {
+        Self { config }
+    }
+
+    /// `(hook name, command)` pairs to run for `event`.
+    #[must_use]
+    pub fn commands(&self, event: &FlowEvent<'_>) -> Vec<(&'static str, &str)> {
+        let Some(ref config) = self.config else {
+            return Vec::new();
+        };
+        hook_names(event)
+            .into_iter()
+            .flat_map(|name| {
+                let commands = match name {
+                    "run_start" => &config.run_start,
+                    "cycle_end" => &config.cycle_end,
+                    "run_end" => &config.run_end,
+                    _ => &config.failure,
+                };
+                commands.iter().map(move |c| (name, c.as_str()))
+            })
+            .collect()
+    }
+
+    /// Run every hook command for `event`, warning about failures.
+    pub fn run(&self, event: &FlowEvent<'_>) {
+        let commands = self.commands(event);
+        if commands.is_empty() {
+            return;
+        }
+        let timeout = Duration::from_secs(self.config.as_ref().map_or(30, |c| c.timeout_secs));
+        let payload = event_line(event, Utc::now());
+        for (name, command) in commands {
+            if let Err(e) = run_hook(name, command, &payload, timeout) {
+                eprintln!("Warning: {name} hook '{command}' failed: {e}");
+            }
+        }
+    }
+}
+
+/// Run one hook command, feeding `payload` on stdin.
+fn run_hook(name: &str, command: &str, payload: &str, timeout: Duration) -> Result<(), String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("FLOW_HOOK", name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores stdin may exit before reading it; that's fine
+        let _ = writeln!(stdin, "{payload}");
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) if status.success() => return Ok(()),
+            Some(status) => return Err(format!("exited with {status}")),
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            None => std::thread::sleep(Duration::from_millis(20)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn completed(success: bool) -> FlowEvent<'static> {
+        FlowEvent::CycleCompleted {
+            iteration: 1,
+            cycle: "coding",
+            success,
+            exit_code: Some(i32::from(!success)),
+            duration_secs: 10,
+            cost_usd: None,
+        }
+    }
+
+    // --- hook_names tests ---
+
+    #[test]
+    fn test_failed_cycle_triggers_cycle_end_and_failure() {
+        assert_eq!(hook_names(&completed(true)), ["cycle_end"]);
+        assert_eq!(hook_names(&completed(false)), ["cycle_end", "failure"]);
+        let gate = FlowEvent::GateTripped {
+            gate: "consecutive_failures",
+            reason: "3 failures",
+            cycle: None,
+        };
+        assert_eq!(hook_names(&gate), ["failure"]);
+    }
+
+    // --- HookRunner tests ---
+
+    #[test]
+    fn test_commands_follow_config() {
+        let runner = HookRunner::new(Some(HooksConfig {
+            cycle_end: vec!["a".to_string()],
+            failure: vec!["b".to_string(), "c".to_string()],
+            ..HooksConfig::default()
+        }));
+        assert_eq!(
+            runner.commands(&completed(false)),
+            [("cycle_end", "a"), ("failure", "b"), ("failure", "c")]
+        );
+        assert!(HookRunner::default().commands(&completed(false)).is_empty());
+    }
+
+    #[test]
+    fn test_hook_receives_event_json_on_stdin() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("event.json");
+        let runner = HookRunner::new(Some(HooksConfig {
+            cycle_end: vec![format!(
+                "cat > '{}' && echo \"$FLOW_HOOK\" >> '{}'",
+                out.display(),
+                out.display()
+            )],
+            ..HooksConfig::default()
+        }));
+        runner.run(&completed(true));
+
+        let written = std::fs::read_to_string(&out).unwrap();
+        let mut lines = written.lines();
+        let json: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!(json["event"], "cycle_completed");
+        assert_eq!(json["cycle"], "coding");
+        assert_eq!(lines.next(), Some("cycle_end"));
+    }
+
+    #[test]
+    fn test_run_hook_reports_failure_and_timeout() {
+        let err = run_hook("failure", "exit 3", "{}", Duration::from_secs(5)).unwrap_err();
+        assert!(err.contains("exit"), "{err}");
+        let err = run_hook("failure", "sleep 5", "{}", Duration::from_millis(100)).unwrap_err();
+        assert!(err.contains("timed out"), "{err}");
+    }
+}
@@ -6,12 +6,22 @@
 // Allow multiple crate versions from dependencies (can't easily control)
 #![allow(clippy::multiple_crate_versions)]
 
+pub mod api;
 pub mod claude;
 pub mod cli;
 pub mod cycle;
 pub mod doctor;
+pub mod drift;
+pub mod gc;
+pub mod git;
+pub mod hooks;
 pub mod init;
 pub mod log;
+pub mod notifications;
+pub mod queue;
+pub mod runner;
+pub mod stats;
+pub mod tasks;
 #[cfg(test)]
 pub mod testutil;
 
@@ -22,10 +32,12 @@ pub use claude::cli::{
 };
 pub use claude::permissions::{resolve_permissions, resolve_step_permissions};
 pub use claude::stream::{parse_event, StreamAccumulator, StreamEvent};
-pub use cli::{render_diagnostic_report, CycleDisplay, StatusLine};
+pub use cli::{render_diagnostic_report, CycleDisplay, StatusLine, StepProgress};
 pub use cycle::config::{CycleConfig, FlowConfig, GlobalConfig, StepConfig, StepRouter};
 pub use cycle::executor::{CycleExecutor, CycleResult};
+pub use cycle::observer::ExecutionObserver;
 pub use cycle::rules::find_triggered_cycles;
 pub use cycle::selector::select_cycle;
 pub use cycle::template::{build_template_vars, expand_template};
 pub use log::{CycleOutcome, JsonlLogger, ProgressWriter, RunProgress, RunStatus};
+pub use runner::{FlowRunner, RunReport};
@@ -0,0 +1,189 @@
+//! Lifecycle event stream (`--emit-events`)
+//!
+//! Supervisor scripts get a stable, machine-readable view of the orchestrator:
+//! one JSON object per line on stdout, each with a `timestamp` and an `event`
+//! tag. Human-oriented output stays on stderr, so the two never interleave.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::log::progress::RunStatus;
+
+/// A Flow lifecycle event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum FlowEvent<'a> {
+    /// The run loop started
+    RunStarted {
+        /// Identifier stamped on this run's log entries
+        run_id: &'a str,
+        /// Iteration budget for the run
+        max_iterations: u32,
+    },
+    /// A new iteration is starting
+    IterationStarted {
+        /// 1-based iteration number
+        iteration: u32,
+        /// Iteration budget for the run
+        max_iterations: u32,
+    },
+    /// A cycle was chosen to run next
+    CycleSelected {
+        /// Iteration the cycle runs in
+        iteration: u32,
+        /// Cycle name
+        cycle: &'a str,
+        /// Why it was chosen: `fixed`, `playlist`, `selector`, `fallback`,
+        /// `replay`, `triggered`, `periodic`, or `recovery`
+        source: &'a str,
+    },
+    /// A cycle finished (successfully or not)
+    CycleCompleted {
+        /// Iteration the cycle ran in
+        iteration: u32,
+        /// Cycle name
+        cycle: &'a str,
+        /// Whether the cycle succeeded
+        success: bool,
+        /// Process exit code (`None` if killed by a signal)
+        exit_code: Option<i32>,
+        /// Wall-clock duration in seconds
+        duration_secs: u64,
+        /// Cost in USD, when reported
+        cost_usd: Option<f64>,
+    },
+    /// A stop gate ended the run
+    GateTripped {
+        /// Which gate: `permission_denials`, `consecutive_failures`, or `stop_condition`
+        gate: &'a str,
+        /// Human-readable explanation
+        reason: &'a str,
+        /// Cycle that tripped the gate, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cycle: Option<&'a str>,
+    },
+    /// The run loop ended
+    RunFinished {
+        /// Final status
+        status: RunStatus,
+        /// Total cost in USD
+        total_cost_usd: f64,
+        /// Why the run stopped early, if it did
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stop_reason: Option<&'a str>,
+    },
+}
+
+/// A [`FlowEvent`] with its timestamp, as written to stdout
+#[derive(Serialize)]
+struct Envelope<'a> {
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a FlowEvent<'a>,
+}
+
+/// Render `event` as one NDJSON line (without the trailing newline).
+#[must_use]
+pub fn event_line(event: &FlowEvent<'_>, timestamp: DateTime<Utc>) -> String {
+    serde_json::to_string(&Envelope { timestamp, event }).unwrap_or_default()
+}
+
+/// Writes lifecycle events to stdout when `--emit-events` is set
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EventEmitter {
+    enabled: bool,
+}
+
+impl EventEmitter {
+    /// Create an emitter; a disabled emitter writes nothing.
+    #[must_use]
+    pub const fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Write `event` to stdout as a single JSON line.
+    pub fn emit(&self, event: &FlowEvent<'_>) {
+        if self.enabled {
+            println!("{}", event_line(event, Utc::now()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn parse(event: &FlowEvent<'_>) -> serde_json::Value {
+        let ts = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        serde_json::from_str(&event_line(event, ts)).unwrap()
+    }
+
+    // --- event_line tests ---
+
+    #[test]
+    fn test_event_line_is_tagged_and_timestamped() {
+        let json = parse(&FlowEvent::IterationStarted {
+            iteration: 2,
+            max_iterations: 10,
+        });
+        assert_eq!(json["event"], "iteration_started");
+        assert_eq!(json["timestamp"], "2026-03-01T12:00:00Z");
+        assert_eq!(json["iteration"], 2);
+        assert_eq!(json["max_iterations"], 10);
+    }
+
+    #[test]
+    fn test_cycle_completed_fields() {
+        let json = parse(&FlowEvent::CycleCompleted {
+            iteration: 1,
+            cycle: "coding",
+            success: false,
+            exit_code: Some(1),
+            duration_secs: 42,
+            cost_usd: Some(0.5),
+        });
+        assert_eq!(json["event"], "cycle_completed");
+        assert_eq!(json["cycle"], "coding");
+        assert_eq!(json["success"], false);
+        assert_eq!(json["exit_code"], 1);
+        assert_eq!(json["cost_usd"], 0.5);
+    }
+
+    #[test]
+    fn test_gate_tripped_omits_missing_cycle() {
+        let json = parse(&FlowEvent::GateTripped {
+            gate: "consecutive_failures",
+            reason: "3 consecutive failures",
+            cycle: None,
+        });
+        assert_eq!(json["event"], "gate_tripped");
+        assert_eq!(json["gate"], "consecutive_failures");
+        assert!(json.get("cycle").is_none());
+    }
+
+    #[test]
+    fn test_run_finished_status() {
+        let json = parse(&FlowEvent::RunFinished {
+            status: RunStatus::Completed,
+            total_cost_usd: 1.25,
+            stop_reason: None,
+        });
+        assert_eq!(json["event"], "run_finished");
+        assert_eq!(json["status"], "completed");
+        assert!(json.get("stop_reason").is_none());
+    }
+
+    #[test]
+    fn test_event_line_is_single_line() {
+        let line = event_line(
+            &FlowEvent::GateTripped {
+                gate: "stop_condition",
+                reason: "line one\nline two",
+                cycle: Some("coding"),
+            },
+            Utc::now(),
+        );
+        assert!(!line.contains('\n'));
+    }
+}
@@ -0,0 +1,333 @@
+//! Log export for spreadsheet analysis
+//!
+//! Flattens `.flow/log.jsonl` entries into CSV: one `cycle` row per outcome,
+//! followed by one `step` row per step of a multi-step cycle. Filter on
+//! `row_type` before summing cost or duration to avoid double counting.
+//!
+//! [`anonymize`] scrubs entries for sharing in bug reports against Flow:
+//! project-specific text (outcomes, file paths, commands, denials, queued task
+//! names, commit SHAs) is removed while the numeric metrics stay.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use crate::log::jsonl::CycleOutcome;
+
+/// Stand-in for a redacted outcome text
+const REDACTED: &str = "(redacted)";
+
+/// CSV header row for [`export_csv`]
+const CSV_HEADER: &[&str] = &[
+    "row_type",
+    "iteration",
+    "cycle",
+    "step",
+    "timestamp",
+    "success",
+    "duration_secs",
+    "num_turns",
+    "cost_usd",
+    "tests_passed",
+    "tests_failed",
+    "files_changed",
+    "permission_denials",
+    "outcome",
+];
+
+/// Render log entries as CSV (RFC 4180, `\n` line endings).
+#[must_use]
+pub fn export_csv(entries: &[CycleOutcome]) -> String {
+    let mut out = String::new();
+    push_row(&mut out, CSV_HEADER.iter().map(|h| (*h).to_string()));
+
+    for entry in entries {
+        let timestamp = entry.timestamp.to_rfc3339();
+        push_row(
+            &mut out,
+            [
+                "cycle".to_string(),
+                entry.iteration.to_string(),
+                entry.cycle.clone(),
+                String::new(),
+                timestamp.clone(),
+                entry.is_success().to_string(),
+                entry.duration_secs.to_string(),
+                opt(entry.num_turns),
+                opt(entry.total_cost_usd),
+                entry.tests_passed.to_string(),
+                entry.tests_failed.unwrap_or(0).to_string(),
+                entry.files_changed.len().to_string(),
+                entry.permission_denial_count.unwrap_or(0).to_string(),
+                entry.outcome.clone(),
+            ],
+        );
+
+        for step in entry.steps.iter().flatten() {
+            push_row(
+                &mut out,
+                [
+                    "step".to_string(),
+                    entry.iteration.to_string(),
+                    entry.cycle.clone(),
+                    step.name.clone(),
+                    timestamp.clone(),
+                    String::new(),
+                    step.duration_secs.to_string(),
+                    opt(step.num_turns),
+                    opt(step.cost_usd),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                ],
+            );
+        }
+    }
+
+    out
+}
+
+/// Render log entries as JSON Lines, the format of `.flow/log.jsonl`.
+///
+/// # Errors
+/// Returns an error if an entry fails to serialize.
+pub fn export_jsonl(entries: &[CycleOutcome]) -> serde_json::Result<String> {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&serde_json::to_string(entry)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Copies of `entries` with project-specific details removed.
+///
+/// Outcome texts become `(redacted)` (`Failed (redacted)` for failures, so
+/// success still reads correctly), each changed file becomes a stable
+/// `file-N` placeholder (the same path maps to the same placeholder across
+/// entries), and commands, denied tool names, queued task names, and commit
+/// SHAs are dropped. Counts, durations, turns, costs, token usage, tool usage
+/// counts, cycle and step names, and version fields are kept.
+#[must_use]
+pub fn anonymize(entries: &[CycleOutcome]) -> Vec<CycleOutcome> {
+    let mut files: HashMap<String, String> = HashMap::new();
+    entries
+        .iter()
+        .map(|entry| {
+            let mut entry = entry.clone();
+            entry.outcome = if entry.is_success() {
+                REDACTED.to_string()
+            } else {
+                format!("Failed {REDACTED}")
+            };
+            entry.files_changed = entry
+                .files_changed
+                .iter()
+                .map(|path| {
+                    let next = files.len() + 1;
+                    files
+                        .entry(path.clone())
+                        .or_insert_with(|| format!("file-{next}"))
+                        .clone()
+                })
+                .collect();
+            entry.permission_denials = None;
+            entry.commands_run = None;
+            entry.task = None;
+            entry.git_sha_before = None;
+            entry.git_sha_after = None;
+            if let Some(ref mut verdict) = entry.verdict {
+                verdict.reasons.clear();
+            }
+            if let Some(ref mut lint) = entry.lint {
+                lint.new.clear();
+            }
+            entry
+        })
+        .collect()
+}
+
+/// Format an optional value, leaving the cell empty when absent.
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Append one CSV row, quoting fields that need it.
+fn push_row(out: &mut String, fields: impl IntoIterator<Item = String>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            let _ = write!(out, "\"{}\"", field.replace('"', "\"\""));
+        } else {
+            out.push_str(&field);
+        }
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::jsonl::StepOutcome;
+    use crate::testutil::make_test_outcome;
+
+    // --- export_csv tests ---
+
+    #[test]
+    fn test_export_csv_empty_log_has_header_only() {
+        let csv = export_csv(&[]);
+        assert_eq!(csv.lines().count(), 1);
+        assert!(csv.starts_with("row_type,iteration,cycle,step,"));
+    }
+
+    #[test]
+    fn test_export_csv_cycle_row() {
+        let mut entry = make_test_outcome(3, "coding", "Implemented feature");
+        entry.total_cost_usd = Some(1.25);
+        entry.num_turns = Some(12);
+        entry.tests_passed = 40;
+        entry.files_changed = vec!["a.rs".to_string(), "b.rs".to_string()];
+        let csv = export_csv(&[entry]);
+        let row = csv.lines().nth(1).unwrap();
+        let fields: Vec<&str> = row.split(',').collect();
+        assert_eq!(fields[0], "cycle");
+        assert_eq!(fields[1], "3");
+        assert_eq!(fields[2], "coding");
+        assert_eq!(fields[3], "");
+        assert_eq!(fields[5], "true");
+        assert_eq!(fields[6], "60");
+        assert_eq!(fields[7], "12");
+        assert_eq!(fields[8], "1.25");
+        assert_eq!(fields[9], "40");
+        assert_eq!(fields[11], "2");
+        assert_eq!(fields[13], "Implemented feature");
+    }
+
+    #[test]
+    fn test_export_csv_step_rows_follow_cycle() {
+        let mut entry = make_test_outcome(1, "coding", "Done");
+        entry.steps = Some(vec![
+            StepOutcome {
+                name: "plan".to_string(),
+                session: Some("architect".to_string()),
+                duration_secs: 20,
+                num_turns: Some(3),
+                cost_usd: Some(0.5),
+                exit_code: None,
+            },
+            StepOutcome {
+                name: "implement".to_string(),
+                session: None,
+                duration_secs: 40,
+                num_turns: None,
+                cost_usd: None,
+                exit_code: None,
+            },
+        ]);
+        let csv = export_csv(&[entry]);
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert!(rows[2].starts_with("step,1,coding,plan,"));
+        assert!(rows[2].contains(",20,3,0.5,"));
+        assert!(rows[3].starts_with("step,1,coding,implement,"));
+        assert!(rows[3].contains(",40,,,"));
+    }
+
+    #[test]
+    fn test_export_csv_quotes_special_characters() {
+        let entry = make_test_outcome(1, "coding", "Fixed \"quoted\", then\nmore");
+        let csv = export_csv(&[entry]);
+        assert!(csv.contains("\"Fixed \"\"quoted\"\", then\nmore\""));
+    }
+
+    #[test]
+    fn test_export_csv_marks_failures() {
+        let entry = make_test_outcome(1, "coding", "Failed with exit code 1");
+        let csv = export_csv(&[entry]);
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(row.split(',').nth(5), Some("false"));
+    }
+
+    // --- anonymize tests ---
+
+    #[test]
+    fn test_anonymize_strips_text_and_keeps_metrics() {
+        let mut entry = make_test_outcome(2, "coding", "Refactored src/billing/acme.rs");
+        entry.files_changed = vec!["src/billing/acme.rs".to_string()];
+        entry.total_cost_usd = Some(0.75);
+        entry.num_turns = Some(9);
+        entry.tests_passed = 12;
+        entry.permission_denial_count = Some(1);
+        entry.permission_denials = Some(vec!["Bash".to_string()]);
+        entry.commands_run = Some(vec!["cargo test -p acme-billing".to_string()]);
+        entry.tools_used = Some(std::iter::once(("Edit".to_string(), 3)).collect());
+        entry.task = Some("acme-invoices.md".to_string());
+        entry.git_sha_after = Some("abc123".to_string());
+        entry.verdict = Some(crate::cycle::arbiter::Verdict {
+            decision: crate::cycle::arbiter::Decision::Reject,
+            reasons: vec!["acme totals are wrong".to_string()],
+        });
+        entry.lint = Some(crate::cycle::lint::LintReport::new(
+            &[],
+            &["src/billing/acme.rs:4:1: unused import".to_string()],
+        ));
+
+        let scrubbed = anonymize(&[entry.clone()]).remove(0);
+        assert_eq!(scrubbed.outcome, "(redacted)");
+        assert_eq!(scrubbed.files_changed, vec!["file-1"]);
+        assert_eq!(scrubbed.permission_denials, None);
+        assert_eq!(scrubbed.commands_run, None);
+        assert_eq!(scrubbed.task, None);
+        assert_eq!(scrubbed.git_sha_after, None);
+        let verdict = scrubbed.verdict.as_ref().unwrap();
+        assert_eq!(verdict.decision, crate::cycle::arbiter::Decision::Reject);
+        assert!(verdict.reasons.is_empty());
+        let report = scrubbed.lint.as_ref().unwrap();
+        assert_eq!((report.warnings, report.new_count), (1, 1));
+        assert!(report.new.is_empty());
+        assert_eq!(scrubbed.cycle, "coding");
+        assert_eq!(scrubbed.total_cost_usd, Some(0.75));
+        assert_eq!(scrubbed.num_turns, Some(9));
+        assert_eq!(scrubbed.tests_passed, 12);
+        assert_eq!(scrubbed.permission_denial_count, Some(1));
+        assert_eq!(scrubbed.tools_used, entry.tools_used);
+        let line = export_jsonl(&[scrubbed]).unwrap();
+        assert!(!line.contains("acme"), "{line}");
+    }
+
+    #[test]
+    fn test_anonymize_keeps_failures_failed() {
+        let entry = make_test_outcome(1, "coding", "Failed: could not build acme");
+        let scrubbed = anonymize(&[entry]).remove(0);
+        assert_eq!(scrubbed.outcome, "Failed (redacted)");
+        assert!(!scrubbed.is_success());
+    }
+
+    #[test]
+    fn test_anonymize_maps_paths_consistently() {
+        let mut first = make_test_outcome(1, "coding", "Done");
+        first.files_changed = vec!["src/a.rs".to_string(), "src/b.rs".to_string()];
+        let mut second = make_test_outcome(2, "coding", "Done");
+        second.files_changed = vec!["src/b.rs".to_string(), "src/c.rs".to_string()];
+        let scrubbed = anonymize(&[first, second]);
+        assert_eq!(scrubbed[0].files_changed, vec!["file-1", "file-2"]);
+        assert_eq!(scrubbed[1].files_changed, vec!["file-2", "file-3"]);
+    }
+
+    #[test]
+    fn test_export_jsonl_round_trips() {
+        let entries = vec![
+            make_test_outcome(1, "coding", "Done"),
+            make_test_outcome(2, "gardening", "Tidied"),
+        ];
+        let jsonl = export_jsonl(&entries).unwrap();
+        let parsed: Vec<CycleOutcome> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed, entries);
+    }
+}
@@ -0,0 +1,324 @@
+//! Follow a run from another terminal (`flow watch-progress`)
+//!
+//! Polls `.flow/progress.json` and tails `.flow/log.jsonl` for entries
+//! appended since the last poll. Strictly read-only: the watcher never takes
+//! the run lock or writes to the log directory.
+
+use anyhow::{Context, Result};
+use std::io::{Read as _, Seek as _, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::log::jsonl::CycleOutcome;
+use crate::log::lock::live_holder;
+use crate::log::progress::{read_progress, RunProgress, RunStatus};
+
+/// Tails a JSONL log, returning the entries appended since the last poll
+pub struct LogFollower {
+    path: PathBuf,
+    /// Byte offset up to which the log has been read
+    offset: u64,
+    /// Start of a line whose newline hasn't been written yet
+    partial: Vec<u8>,
+}
+
+impl LogFollower {
+    /// Follow `path` from its current end, so only entries appended from
+    /// now on are returned. A log that doesn't exist yet is read from its start.
+    #[must_use]
+    pub fn from_end(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            offset: std::fs::metadata(path).map_or(0, |m| m.len()),
+            partial: Vec::new(),
+        }
+    }
+
+    /// Entries appended since the last poll.
+    ///
+    /// A line still being written is held back until it is complete, and
+    /// malformed lines are skipped. If the log shrank (rewritten by
+    /// `flow logs repair`, `merge`, or `compact`), following resumes at its
+    /// new end rather than replaying history.
+    pub fn poll(&mut self) -> Result<Vec<CycleOutcome>> {
+        let len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.path.display()))
+            }
+        };
+        if len < self.offset {
+            self.offset = len;
+            self.partial.clear();
+        }
+        if len == self.offset {
+            return Ok(Vec::new());
+        }
+
+        let mut file = std::fs::File::open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.take(len - self.offset)
+            .read_to_end(&mut self.partial)?;
+        self.offset = len;
+
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        Ok(String::from_utf8_lossy(&complete)
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+/// Something `flow watch-progress` reports
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchUpdate {
+    /// No run has written progress yet
+    Waiting,
+    /// The run's progress changed
+    Progress(Box<RunProgress>),
+    /// A cycle finished and was logged
+    Outcome(Box<CycleOutcome>),
+    /// The run ended: its progress file was removed or shows a final status
+    Finished,
+    /// `progress.json` says the run is going, but no live process holds the
+    /// run lock (the run crashed or was killed)
+    Abandoned,
+}
+
+/// Follows one log directory's progress file and log
+pub struct RunWatcher {
+    log_dir: PathBuf,
+    log: LogFollower,
+    /// Progress reported last
+    last: Option<RunProgress>,
+    /// Whether `Waiting` has been reported
+    waiting: bool,
+}
+
+impl RunWatcher {
+    /// Watch `log_dir`, reporting log entries appended from now on.
+    #[must_use]
+    pub fn new(log_dir: &Path) -> Self {
+        Self {
+            log_dir: log_dir.to_path_buf(),
+            log: LogFollower::from_end(&log_dir.join("log.jsonl")),
+            last: None,
+            waiting: false,
+        }
+    }
+
+    /// What changed since the last poll: new log entries first, then the
+    /// progress change they led to. Once `Finished` or `Abandoned` is
+    /// reported the run is over and there is nothing more to follow.
+    pub fn poll(&mut self) -> Result<Vec<WatchUpdate>> {
+        let mut updates: Vec<WatchUpdate> = self
+            .log
+            .poll()?
+            .into_iter()
+            .map(|outcome| WatchUpdate::Outcome(Box::new(outcome)))
+            .collect();
+        match read_progress(&self.log_dir)? {
+            None if self.last.is_some() => updates.push(WatchUpdate::Finished),
+            None if !self.waiting => {
+                self.waiting = true;
+                updates.push(WatchUpdate::Waiting);
+            }
+            None => {}
+            Some(progress) => {
+                let running = progress.current_status == RunStatus::Running;
+                if running && live_holder(&self.log_dir).is_none() {
+                    updates.push(WatchUpdate::Abandoned);
+                    return Ok(updates);
+                }
+                if self.last.as_ref() != Some(&progress) {
+                    self.last = Some(progress.clone());
+                    updates.push(WatchUpdate::Progress(Box::new(progress)));
+                }
+                if !running {
+                    updates.push(WatchUpdate::Finished);
+                }
+            }
+        }
+        Ok(updates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::jsonl::JsonlLogger;
+    use crate::log::lock::RunLock;
+    use crate::log::progress::ProgressWriter;
+    use crate::testutil::make_test_outcome;
+    use std::io::Write as _;
+    use tempfile::TempDir;
+
+    fn append_raw(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    // --- LogFollower tests ---
+
+    #[test]
+    fn test_follower_skips_existing_entries() {
+        let dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(dir.path()).unwrap();
+        logger
+            .append(&make_test_outcome(1, "coding", "Done"))
+            .unwrap();
+
+        let mut follower = LogFollower::from_end(logger.log_path());
+        assert!(follower.poll().unwrap().is_empty());
+
+        logger
+            .append(&make_test_outcome(2, "review", "Done"))
+            .unwrap();
+        let appended = follower.poll().unwrap();
+        assert_eq!(appended.len(), 1);
+        assert_eq!(appended[0].cycle, "review");
+        assert!(follower.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_follower_reads_log_created_later_from_start() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log.jsonl");
+        let mut follower = LogFollower::from_end(&path);
+        assert!(follower.poll().unwrap().is_empty());
+
+        let logger = JsonlLogger::new(dir.path()).unwrap();
+        logger
+            .append(&make_test_outcome(1, "coding", "Done"))
+            .unwrap();
+        assert_eq!(follower.poll().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_follower_holds_partial_line_until_complete() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log.jsonl");
+        let line = serde_json::to_string(&make_test_outcome(1, "coding", "Done")).unwrap();
+        let (head, tail) = line.split_at(line.len() / 2);
+        let mut follower = LogFollower::from_end(&path);
+
+        append_raw(&path, head);
+        assert!(follower.poll().unwrap().is_empty());
+        append_raw(&path, &format!("{tail}\n"));
+        assert_eq!(follower.poll().unwrap()[0].cycle, "coding");
+    }
+
+    #[test]
+    fn test_follower_skips_malformed_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("log.jsonl");
+        let mut follower = LogFollower::from_end(&path);
+        let line = serde_json::to_string(&make_test_outcome(1, "coding", "Done")).unwrap();
+        append_raw(&path, &format!("{{not json\n{line}\n"));
+        assert_eq!(follower.poll().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_follower_resumes_at_end_after_rewrite() {
+        let dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(dir.path()).unwrap();
+        logger
+            .append(&make_test_outcome(1, "coding", "Done"))
+            .unwrap();
+        logger
+            .append(&make_test_outcome(2, "coding", "Done"))
+            .unwrap();
+        let mut follower = LogFollower::from_end(logger.log_path());
+
+        logger
+            .write_all(&[make_test_outcome(1, "coding", "Done")])
+            .unwrap();
+        assert!(follower.poll().unwrap().is_empty());
+        logger
+            .append(&make_test_outcome(2, "review", "Done"))
+            .unwrap();
+        assert_eq!(follower.poll().unwrap()[0].cycle, "review");
+    }
+
+    // --- RunWatcher tests ---
+
+    #[test]
+    fn test_watcher_reports_waiting_once() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = RunWatcher::new(dir.path());
+        assert_eq!(watcher.poll().unwrap(), vec![WatchUpdate::Waiting]);
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_watcher_follows_a_live_run_to_its_end() {
+        let dir = TempDir::new().unwrap();
+        let lock = RunLock::acquire(dir.path(), false).unwrap();
+        let writer = ProgressWriter::new(dir.path()).unwrap();
+        let logger = JsonlLogger::new(dir.path()).unwrap();
+        let mut progress = RunProgress::new(2);
+        progress.current_cycle = "coding".to_string();
+        writer.write(&progress).unwrap();
+
+        let mut watcher = RunWatcher::new(dir.path());
+        assert_eq!(
+            watcher.poll().unwrap(),
+            vec![WatchUpdate::Progress(Box::new(progress.clone()))]
+        );
+        assert!(watcher.poll().unwrap().is_empty(), "unchanged progress");
+
+        logger
+            .append(&make_test_outcome(1, "coding", "Done"))
+            .unwrap();
+        progress.current_iteration = 2;
+        writer.write(&progress).unwrap();
+        let updates = watcher.poll().unwrap();
+        assert!(matches!(updates[0], WatchUpdate::Outcome(ref o) if o.cycle == "coding"));
+        assert!(matches!(updates[1], WatchUpdate::Progress(ref p) if p.current_iteration == 2));
+
+        writer.delete().unwrap();
+        drop(lock);
+        assert_eq!(watcher.poll().unwrap(), vec![WatchUpdate::Finished]);
+    }
+
+    #[test]
+    fn test_watcher_finishes_on_final_status() {
+        let dir = TempDir::new().unwrap();
+        let mut progress = RunProgress::new(1);
+        progress.current_status = RunStatus::Completed;
+        ProgressWriter::new(dir.path())
+            .unwrap()
+            .write(&progress)
+            .unwrap();
+
+        let updates = RunWatcher::new(dir.path()).poll().unwrap();
+        assert_eq!(
+            updates,
+            vec![
+                WatchUpdate::Progress(Box::new(progress)),
+                WatchUpdate::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watcher_flags_running_progress_without_lock_holder() {
+        let dir = TempDir::new().unwrap();
+        ProgressWriter::new(dir.path())
+            .unwrap()
+            .write(&RunProgress::new(3))
+            .unwrap();
+        assert_eq!(
+            RunWatcher::new(dir.path()).poll().unwrap(),
+            vec![WatchUpdate::Abandoned]
+        );
+    }
+}
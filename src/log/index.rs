@@ -0,0 +1,144 @@
+//! Running totals over the JSONL log (`.flow/log-index.json`)
+//!
+//! The index keeps per-cycle run counts, successes, and costs up to date as
+//! outcomes are appended, so the selector and doctor can read aggregates
+//! without scanning the whole history. It records the log's size at the last
+//! update; if the log changes behind its back (repair, merge, hand edits) the
+//! sizes disagree and the index is rebuilt from a single pass over the log.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::log::jsonl::CycleOutcome;
+
+/// Name of the index file inside the log directory
+pub const INDEX_FILE: &str = "log-index.json";
+
+/// Running totals for one cycle
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CycleStats {
+    /// Number of logged runs
+    pub runs: u32,
+    /// Number of those runs that succeeded
+    pub successes: u32,
+    /// Sum of reported costs
+    pub total_cost_usd: f64,
+}
+
+impl CycleStats {
+    /// Fraction of runs that succeeded (0.0 with no runs)
+    #[must_use]
+    pub fn success_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            f64::from(self.successes) / f64::from(self.runs)
+        }
+    }
+}
+
+/// Aggregates over every outcome in the log
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogIndex {
+    /// Size in bytes of `log.jsonl` when the index was last updated
+    pub log_bytes: u64,
+    /// Number of outcomes indexed
+    pub entries: u64,
+    /// Highest iteration number seen
+    pub total_iterations: u32,
+    /// Sum of reported costs across all cycles
+    pub total_cost_usd: f64,
+    /// Per-cycle totals, keyed by cycle name
+    pub cycles: BTreeMap<String, CycleStats>,
+}
+
+impl LogIndex {
+    /// Index a sequence of outcomes from scratch.
+    pub fn from_outcomes<'a>(outcomes: impl IntoIterator<Item = &'a CycleOutcome>) -> Self {
+        let mut index = Self::default();
+        for outcome in outcomes {
+            index.record(outcome);
+        }
+        index
+    }
+
+    /// Add one outcome to the totals.
+    pub fn record(&mut self, outcome: &CycleOutcome) {
+        self.entries += 1;
+        self.total_iterations = self.total_iterations.max(outcome.iteration);
+        let stats = self.cycles.entry(outcome.cycle.clone()).or_default();
+        stats.runs += 1;
+        if outcome.is_success() {
+            stats.successes += 1;
+        }
+        if let Some(cost) = outcome.total_cost_usd {
+            stats.total_cost_usd += cost;
+            self.total_cost_usd += cost;
+        }
+    }
+}
+
+/// Load the index at `path`, or `None` if it is missing or unreadable (it
+/// is derived data, so a damaged index is simply rebuilt).
+#[must_use]
+pub fn load(path: &Path) -> Option<LogIndex> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Atomically write the index to `path` (write to temp, then rename).
+///
+/// # Errors
+/// Returns an error if serialization or any file operation fails.
+pub fn save(path: &Path, index: &LogIndex) -> Result<()> {
+    let json = serde_json::to_string_pretty(index).context("Failed to serialize log index")?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, json)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::make_test_outcome;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_accumulates_per_cycle_totals() {
+        let mut failed = make_test_outcome(3, "coding", "Broke");
+        failed.failure_kind = Some(crate::log::jsonl::FailureKind::Cycle);
+        failed.total_cost_usd = Some(0.5);
+        let mut ok = make_test_outcome(2, "coding", "Done");
+        ok.total_cost_usd = Some(1.0);
+        let review = make_test_outcome(1, "review", "Looked");
+
+        let index = LogIndex::from_outcomes([&ok, &failed, &review]);
+
+        assert_eq!(index.entries, 3);
+        assert_eq!(index.total_iterations, 3);
+        assert!((index.total_cost_usd - 1.5).abs() < f64::EPSILON);
+        let coding = &index.cycles["coding"];
+        assert_eq!((coding.runs, coding.successes), (2, 1));
+        assert!((coding.success_rate() - 0.5).abs() < f64::EPSILON);
+        assert!((CycleStats::default().success_rate()).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(INDEX_FILE);
+        assert!(load(&path).is_none());
+
+        let index = LogIndex::from_outcomes([&make_test_outcome(1, "coding", "Done")]);
+        save(&path, &index).unwrap();
+        assert_eq!(load(&path), Some(index));
+
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(load(&path).is_none(), "damaged index is treated as missing");
+    }
+}
@@ -1,13 +1,23 @@
 //! JSONL (JSON Lines) logging for cycle execution history
 //!
-//! Provides append-only logging of cycle outcomes to `.flow/log.jsonl`
+//! Provides append-only logging of cycle outcomes to `.flow/log.jsonl`.
+//! Reading the log skips malformed lines (e.g. one truncated by a crash) with a
+//! warning instead of failing; `flow logs repair` drops them for good.
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::fs::{self, OpenOptions};
-use std::io::Write as _;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead as _, BufReader, Lines, Read as _, Seek as _, SeekFrom, Write as _};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::claude::stream::TokenUsage;
+use crate::cycle::arbiter::Verdict;
+use crate::cycle::lint::LintReport;
+use crate::cycle::test_guard::TestDelta;
+use crate::log::index::{self, LogIndex, INDEX_FILE};
 
 /// Per-step outcome data for multi-step cycles
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -25,6 +35,20 @@ pub struct StepOutcome {
     /// Cost of this step in USD
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cost_usd: Option<f64>,
+    /// Process exit code (`None` if the step was killed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+/// Classification of a failed cycle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// An upstream API hiccup (overloaded, rate limited, 5xx); retried with
+    /// backoff and not counted toward `max_consecutive_failures`
+    Transient,
+    /// The cycle itself failed (non-zero exit, unmet `success_when`, ...)
+    Cycle,
 }
 
 /// Represents the outcome of a single cycle execution
@@ -38,10 +62,17 @@ pub struct CycleOutcome {
     pub timestamp: DateTime<Utc>,
     /// Human-readable summary of what was accomplished
     pub outcome: String,
+    /// Why a failed cycle failed: a transient API error or the cycle itself
+    /// (`None` on success)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_kind: Option<FailureKind>,
     /// List of files that were modified
     pub files_changed: Vec<String>,
     /// Number of tests that passed
     pub tests_passed: u32,
+    /// Number of tests that failed (omitted when none failed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests_failed: Option<u32>,
     /// Duration of the cycle in seconds
     pub duration_secs: u64,
     /// Number of conversation turns
@@ -50,31 +81,234 @@ pub struct CycleOutcome {
     /// Total cost in USD
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub total_cost_usd: Option<f64>,
+    /// Cost computed from token usage with the `[pricing]` table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub computed_cost_usd: Option<f64>,
+    /// Token usage reported by Claude Code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Model reported by Claude Code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
     /// Number of permission denials during the cycle
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub permission_denial_count: Option<u32>,
     /// List of denied tool names (e.g., `["Edit", "Bash"]`)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub permission_denials: Option<Vec<String>>,
+    /// Shell commands executed via the Bash tool (capped and truncated for log size)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands_run: Option<Vec<String>>,
+    /// Number of uses of each tool, by tool name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools_used: Option<BTreeMap<String, u32>>,
     /// Per-step outcome data for multi-step cycles (omitted for single-step cycles)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub steps: Option<Vec<StepOutcome>>,
+    /// Steps of a multi-step cycle skipped because their `skip_if` held
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped_steps: Option<Vec<String>>,
+    /// Identifier of the run that produced this entry (stamped by the logger;
+    /// absent in logs written before run IDs existed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// Set when the working tree was restored after this cycle failed
+    /// (`rollback_on_failure`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rolled_back: Option<bool>,
+    /// Sample kept by a multi-sample cycle (`samples`), 1-based
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chosen_sample: Option<u32>,
+    /// Attempts made, when a transient API error was retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    /// Verdict of a cycle that `arbitrates` another
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<Verdict>,
+    /// Rubric scores (1–5) from the cycle's `eval` block, by criterion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_scores: Option<BTreeMap<String, u8>>,
+    /// Test counts before and after the cycle, from `[test_guard]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub test_delta: Option<TestDelta>,
+    /// Lint warnings after the cycle and the ones it introduced, from `[lint]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lint: Option<LintReport>,
+    /// Subagents launched via the `Task` tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subagent_count: Option<u32>,
+    /// Cost in USD reported by subagents, separate from `total_cost_usd`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subagent_cost_usd: Option<f64>,
+    /// `HEAD` commit when the cycle started (absent outside a git repository)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_sha_before: Option<String>,
+    /// `HEAD` commit when the cycle finished (after any rollback)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_sha_after: Option<String>,
+    /// Lines added by the cycle (`git diff --numstat` against the pre-cycle state)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines_added: Option<u32>,
+    /// Lines removed by the cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines_removed: Option<u32>,
+    /// Hash of the fully-resolved prompt sent to Claude Code (after context
+    /// injection and templating; combined across steps)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_hash: Option<String>,
+    /// Hash of the resolved permission set (order-independent; combined across steps)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions_hash: Option<String>,
+    /// Version of flow that wrote this entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow_version: Option<String>,
+    /// Claude Code CLI version reported by the session (of the last step)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claude_version: Option<String>,
+    /// Operating system and architecture of the machine, e.g. `linux-x86_64`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    /// Queue file (`.flow/queue/`) whose task this cycle worked on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
 }
 
 impl CycleOutcome {
-    /// Whether this cycle execution succeeded (outcome does not start with "Failed").
+    /// Whether this cycle execution succeeded (no failure kind recorded and
+    /// outcome does not start with "Failed").
     #[must_use]
     pub fn is_success(&self) -> bool {
-        !self.outcome.starts_with("Failed")
+        self.failure_kind.is_none() && !self.outcome.starts_with("Failed")
     }
 }
 
+/// Version of this flow build, recorded in log entries and progress
+pub const FLOW_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Operating system and architecture of this machine, e.g. `linux-x86_64`.
+#[must_use]
+pub fn os_label() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Generate a run identifier: start time plus process ID, e.g. `20260301T120000Z-4242`.
+#[must_use]
+pub fn new_run_id(now: DateTime<Utc>) -> String {
+    format!("{}-{}", now.format("%Y%m%dT%H%M%SZ"), std::process::id())
+}
+
+/// A log line that couldn't be parsed as a cycle outcome
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptLine {
+    /// 1-based line number in the log file
+    pub line_number: usize,
+    /// Why the line didn't parse
+    pub error: String,
+}
+
+/// Set once the corrupt-log warning has been printed, so repeated reads
+/// during a run don't repeat it
+static CORRUPTION_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Bytes read per step when scanning the log backwards in [`JsonlLogger::read_last`]
+const TAIL_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// Warn (once per process) that `path` has malformed lines.
+fn report_corruption(path: &Path, corrupt: &[CorruptLine]) {
+    let Some(first) = corrupt.first() else {
+        return;
+    };
+    if !CORRUPTION_REPORTED.swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "Warning: skipped {} malformed line(s) in {} (line {}: {}); run `flow logs repair` to remove them",
+            corrupt.len(),
+            path.display(),
+            first.line_number,
+            first.error
+        );
+    }
+}
+
+/// Iterator over a log's outcomes, parsed one line at a time so the whole
+/// file is never held in memory. Malformed lines are skipped and reported
+/// once the stream is exhausted.
+pub struct LogStream {
+    /// `None` when the log doesn't exist yet
+    lines: Option<Lines<BufReader<File>>>,
+    /// Path of the log, for the corruption warning
+    path: PathBuf,
+    /// Number of the line read last (1-based)
+    line_number: usize,
+    /// Lines skipped so far
+    corrupt: Vec<CorruptLine>,
+}
+
+impl LogStream {
+    /// Lines skipped so far because they didn't parse
+    #[must_use]
+    pub fn corrupt_lines(&self) -> &[CorruptLine] {
+        &self.corrupt
+    }
+}
+
+impl Iterator for LogStream {
+    type Item = CycleOutcome;
+
+    fn next(&mut self) -> Option<CycleOutcome> {
+        let lines = self.lines.as_mut()?;
+        loop {
+            let Some(line) = lines.next() else {
+                report_corruption(&self.path, &self.corrupt);
+                return None;
+            };
+            self.line_number += 1;
+            let parsed = line
+                .map_err(|e| e.to_string())
+                .and_then(|l| match l.trim() {
+                    "" => Ok(None),
+                    _ => serde_json::from_str(&l)
+                        .map(Some)
+                        .map_err(|e| e.to_string()),
+                });
+            match parsed {
+                Ok(Some(outcome)) => return Some(outcome),
+                Ok(None) => {}
+                Err(error) => self.corrupt.push(CorruptLine {
+                    line_number: self.line_number,
+                    error,
+                }),
+            }
+        }
+    }
+}
+
+/// Parse JSONL content into outcomes, collecting lines that don't parse.
+fn parse_lines(content: &str) -> (Vec<CycleOutcome>, Vec<CorruptLine>) {
+    let mut outcomes = Vec::new();
+    let mut corrupt = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(e) => corrupt.push(CorruptLine {
+                line_number: index + 1,
+                error: e.to_string(),
+            }),
+        }
+    }
+    (outcomes, corrupt)
+}
+
 /// JSONL logger for cycle execution history
 ///
 /// Provides append-only logging to `.flow/log.jsonl`.
 /// Each line is a JSON object representing a single cycle outcome.
 pub struct JsonlLogger {
     log_path: PathBuf,
+    index_path: PathBuf,
+    run_id: Option<String>,
 }
 
 impl JsonlLogger {
@@ -93,8 +327,20 @@ impl JsonlLogger {
             .with_context(|| format!("Failed to create log directory: {}", log_dir.display()))?;
 
         let log_path = log_dir.join("log.jsonl");
+        let index_path = log_dir.join(INDEX_FILE);
+
+        Ok(Self {
+            log_path,
+            index_path,
+            run_id: None,
+        })
+    }
 
-        Ok(Self { log_path })
+    /// Stamp every appended entry that has no `run_id` with this one.
+    #[must_use]
+    pub fn with_run_id(mut self, run_id: String) -> Self {
+        self.run_id = Some(run_id);
+        self
     }
 
     /// Append a cycle outcome to the log
@@ -111,53 +357,254 @@ impl JsonlLogger {
         // Open file in append mode, create if it doesn't exist
         let mut file = OpenOptions::new()
             .create(true)
+            .read(true)
             .append(true)
             .open(&self.log_path)
             .with_context(|| format!("Failed to open log file: {}", self.log_path.display()))?;
 
-        // Serialize to JSON
-        let json =
-            serde_json::to_string(outcome).context("Failed to serialize cycle outcome to JSON")?;
+        // A crash mid-write can leave a line without its newline; start a
+        // fresh line so this entry isn't glued onto the broken one
+        let size_before = file.seek(SeekFrom::End(0))?;
+        if size_before > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                writeln!(file).context("Failed to write to log file")?;
+            }
+        }
+
+        // Serialize to JSON, stamping the run ID if the entry doesn't carry one
+        let json = match (&self.run_id, &outcome.run_id) {
+            (Some(run_id), None) => serde_json::to_string(&CycleOutcome {
+                run_id: Some(run_id.clone()),
+                ..outcome.clone()
+            }),
+            _ => serde_json::to_string(outcome),
+        }
+        .context("Failed to serialize cycle outcome to JSON")?;
 
         // Write JSON line
         writeln!(file, "{json}").context("Failed to write to log file")?;
 
+        // Keep the index current; it is derived data, so a failure here only
+        // means the next `index()` rebuilds it
+        let _ = self.update_index(outcome, size_before, file.seek(SeekFrom::End(0))?);
+
         Ok(())
     }
 
+    /// Add a just-appended outcome to the index if the index was current
+    /// before the append (the log was `size_before` bytes), else rebuild it.
+    fn update_index(
+        &self,
+        outcome: &CycleOutcome,
+        size_before: u64,
+        size_after: u64,
+    ) -> Result<()> {
+        match index::load(&self.index_path) {
+            Some(mut current) if current.log_bytes == size_before => {
+                current.record(outcome);
+                current.log_bytes = size_after;
+                index::save(&self.index_path, &current)
+            }
+            _ => self.rebuild_index().map(|_| ()),
+        }
+    }
+
+    /// Running totals over the whole log, from `log-index.json` when it is
+    /// current and otherwise rebuilt with one streaming pass (and saved)
+    ///
+    /// # Errors
+    /// Returns an error if the log can't be read or the index can't be saved
+    pub fn index(&self) -> Result<LogIndex> {
+        let log_bytes = fs::metadata(&self.log_path).map_or(0, |m| m.len());
+        match index::load(&self.index_path) {
+            Some(current) if current.log_bytes == log_bytes => Ok(current),
+            _ => self.rebuild_index(),
+        }
+    }
+
+    /// Recompute the index from the log and save it.
+    fn rebuild_index(&self) -> Result<LogIndex> {
+        let log_bytes = fs::metadata(&self.log_path).map_or(0, |m| m.len());
+        let mut rebuilt = LogIndex {
+            log_bytes,
+            ..LogIndex::default()
+        };
+        for outcome in self.stream()? {
+            rebuilt.record(&outcome);
+        }
+        index::save(&self.index_path, &rebuilt)?;
+        Ok(rebuilt)
+    }
+
     /// Read all cycle outcomes from the log
     ///
+    /// Malformed lines are skipped; the first read that finds any prints a
+    /// warning pointing at `flow logs repair`.
+    ///
     /// # Returns
-    /// A vector of all cycle outcomes, in chronological order
+    /// A vector of all parseable cycle outcomes, in chronological order
     ///
     /// # Errors
-    /// Returns an error if:
-    /// - The log file cannot be read
-    /// - Any line cannot be parsed as valid JSON
+    /// Returns an error if the log file exists but cannot be read
     pub fn read_all(&self) -> Result<Vec<CycleOutcome>> {
-        // If log file doesn't exist yet, return empty vector
-        if !self.log_path.exists() {
+        let (outcomes, corrupt) = self.read_lenient()?;
+        report_corruption(&self.log_path, &corrupt);
+        Ok(outcomes)
+    }
+
+    /// Stream the log's outcomes in order without loading the whole file
+    ///
+    /// Malformed lines are skipped, as in [`Self::read_all`].
+    ///
+    /// # Errors
+    /// Returns an error if the log file exists but cannot be opened
+    pub fn stream(&self) -> Result<LogStream> {
+        let lines = if self.log_path.exists() {
+            let file = File::open(&self.log_path)
+                .with_context(|| format!("Failed to open log file: {}", self.log_path.display()))?;
+            Some(BufReader::new(file).lines())
+        } else {
+            None
+        };
+        Ok(LogStream {
+            lines,
+            path: self.log_path.clone(),
+            line_number: 0,
+            corrupt: Vec::new(),
+        })
+    }
+
+    /// Read the last `n` outcomes (oldest first), scanning backwards from
+    /// the end of the file so only the tail is read
+    ///
+    /// # Errors
+    /// Returns an error if the log file exists but cannot be read
+    pub fn read_last(&self, n: usize) -> Result<Vec<CycleOutcome>> {
+        if n == 0 || !self.log_path.exists() {
             return Ok(Vec::new());
         }
+        let mut file = File::open(&self.log_path)
+            .with_context(|| format!("Failed to open log file: {}", self.log_path.display()))?;
+        let mut pos = file.seek(SeekFrom::End(0))?;
+        let mut tail: Vec<u8> = Vec::new();
+        loop {
+            let chunk_len = TAIL_CHUNK_BYTES.min(pos);
+            pos -= chunk_len;
+            let mut chunk = vec![0; usize::try_from(chunk_len)?];
+            file.seek(SeekFrom::Start(pos))?;
+            file.read_exact(&mut chunk)
+                .with_context(|| format!("Failed to read log file: {}", self.log_path.display()))?;
+            chunk.append(&mut tail);
+            tail = chunk;
+
+            // Until the start of the file is reached, the first line may be partial
+            let start = if pos == 0 {
+                0
+            } else {
+                match tail.iter().position(|&b| b == b'\n') {
+                    Some(newline) => newline + 1,
+                    None => continue,
+                }
+            };
+            let (outcomes, corrupt) = parse_lines(&String::from_utf8_lossy(&tail[start..]));
+            if outcomes.len() >= n || pos == 0 {
+                report_corruption(&self.log_path, &corrupt);
+                let skip = outcomes.len().saturating_sub(n);
+                return Ok(outcomes.into_iter().skip(skip).collect());
+            }
+        }
+    }
 
+    /// Read the log, returning the parseable outcomes and the lines that
+    /// failed to parse
+    ///
+    /// # Errors
+    /// Returns an error if the log file exists but cannot be read
+    pub fn read_lenient(&self) -> Result<(Vec<CycleOutcome>, Vec<CorruptLine>)> {
+        // If log file doesn't exist yet, there is nothing to read
+        if !self.log_path.exists() {
+            return Ok((Vec::new(), Vec::new()));
+        }
         let content = fs::read_to_string(&self.log_path)
             .with_context(|| format!("Failed to read log file: {}", self.log_path.display()))?;
+        Ok(parse_lines(&content))
+    }
 
-        let mut outcomes = Vec::new();
+    /// Rewrite the log without its malformed lines, keeping the original as
+    /// `log.jsonl.corrupt`. Returns the lines removed (none leaves the log as is).
+    ///
+    /// # Errors
+    /// Returns an error if the log can't be read, backed up, or rewritten
+    pub fn repair(&self) -> Result<Vec<CorruptLine>> {
+        let (outcomes, corrupt) = self.read_lenient()?;
+        if corrupt.is_empty() {
+            return Ok(corrupt);
+        }
+        let backup = self.log_path.with_extension("jsonl.corrupt");
+        fs::copy(&self.log_path, &backup)
+            .with_context(|| format!("Failed to back up log to {}", backup.display()))?;
+        self.write_all(&outcomes)?;
+        Ok(corrupt)
+    }
 
-        for (line_num, line) in content.lines().enumerate() {
-            // Skip empty lines
-            if line.trim().is_empty() {
-                continue;
-            }
+    /// Read all cycle outcomes from an arbitrary JSONL log file
+    ///
+    /// Unlike [`Self::read_all`] this is strict, for files handed to flow
+    /// explicitly (e.g. `flow logs merge`).
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or any line is not a valid outcome
+    pub fn read_path(path: &Path) -> Result<Vec<CycleOutcome>> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read log file: {}", path.display()))?;
+
+        let (outcomes, corrupt) = parse_lines(&content);
+        if let Some(first) = corrupt.first() {
+            anyhow::bail!(
+                "Failed to parse line {} of {} as JSON: {}",
+                first.line_number,
+                path.display(),
+                first.error
+            );
+        }
+        Ok(outcomes)
+    }
 
-            let outcome: CycleOutcome = serde_json::from_str(line)
-                .with_context(|| format!("Failed to parse line {} as JSON", line_num + 1))?;
+    /// Replace the whole log with `outcomes`
+    ///
+    /// Writes to a temporary file and renames it over the log, so a crash
+    /// never leaves a half-written log behind.
+    ///
+    /// # Errors
+    /// Returns an error if serialization or any file operation fails
+    pub fn write_all(&self, outcomes: &[CycleOutcome]) -> Result<()> {
+        Self::write_path(&self.log_path, outcomes)
+    }
 
-            outcomes.push(outcome);
+    /// Write cycle outcomes as JSONL to an arbitrary file, replacing it atomically
+    ///
+    /// # Errors
+    /// Returns an error if serialization or any file operation fails
+    pub fn write_path(path: &Path, outcomes: &[CycleOutcome]) -> Result<()> {
+        let mut content = String::new();
+        for outcome in outcomes {
+            content.push_str(
+                &serde_json::to_string(outcome)
+                    .context("Failed to serialize cycle outcome to JSON")?,
+            );
+            content.push('\n');
         }
-
-        Ok(outcomes)
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        fs::write(&tmp_path, content)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
     }
 
     /// Get the path to the log file
@@ -238,6 +685,66 @@ mod tests {
         assert_eq!(content.lines().count(), 2);
     }
 
+    #[test]
+    fn test_append_stamps_run_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path())
+            .unwrap()
+            .with_run_id("run-a".to_string());
+
+        logger
+            .append(&make_test_outcome(1, "coding", "Done"))
+            .unwrap();
+        let mut tagged = make_test_outcome(2, "coding", "Done");
+        tagged.run_id = Some("run-b".to_string());
+        logger.append(&tagged).unwrap();
+
+        let outcomes = logger.read_all().unwrap();
+        assert_eq!(outcomes[0].run_id.as_deref(), Some("run-a"));
+        assert_eq!(outcomes[1].run_id.as_deref(), Some("run-b"));
+    }
+
+    #[test]
+    fn test_append_without_run_id_omits_field() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        logger
+            .append(&make_test_outcome(1, "coding", "Done"))
+            .unwrap();
+        let content = fs::read_to_string(logger.log_path()).unwrap();
+        assert!(!content.contains("run_id"));
+    }
+
+    #[test]
+    fn test_write_all_replaces_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        logger
+            .append(&make_test_outcome(1, "coding", "Old"))
+            .unwrap();
+
+        logger
+            .write_all(&[
+                make_test_outcome(1, "gardening", "New 1"),
+                make_test_outcome(2, "review", "New 2"),
+            ])
+            .unwrap();
+
+        let outcomes = logger.read_all().unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].cycle, "gardening");
+        assert!(!temp_dir.path().join("log.jsonl.tmp").exists());
+    }
+
+    #[test]
+    fn test_new_run_id_format() {
+        let now = chrono::DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let id = new_run_id(now);
+        assert!(id.starts_with("20260301T120000Z-"), "{id}");
+    }
+
     #[test]
     fn test_read_all_empty_log() {
         let temp_dir = TempDir::new().unwrap();
@@ -273,6 +780,158 @@ mod tests {
         assert_eq!(results[1].cycle, "gardening");
     }
 
+    #[test]
+    fn test_read_all_skips_malformed_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        logger
+            .append(&make_test_outcome(1, "coding", "First"))
+            .unwrap();
+        fs::write(
+            logger.log_path(),
+            format!(
+                "{}not json\n",
+                fs::read_to_string(logger.log_path()).unwrap()
+            ),
+        )
+        .unwrap();
+        logger
+            .append(&make_test_outcome(2, "coding", "Second"))
+            .unwrap();
+
+        let outcomes = logger.read_all().unwrap();
+        assert_eq!(outcomes.len(), 2);
+        let (_, corrupt) = logger.read_lenient().unwrap();
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!(corrupt[0].line_number, 2);
+        assert!(JsonlLogger::read_path(logger.log_path()).is_err());
+    }
+
+    #[test]
+    fn test_append_after_truncated_line_starts_new_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        logger
+            .append(&make_test_outcome(1, "coding", "First"))
+            .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(logger.log_path())
+            .unwrap();
+        write!(file, "{{\"iteration\":2,\"cyc").unwrap();
+        logger
+            .append(&make_test_outcome(3, "coding", "Third"))
+            .unwrap();
+
+        let (outcomes, corrupt) = logger.read_lenient().unwrap();
+        assert_eq!(outcomes.len(), 2, "the entry after the crash survives");
+        assert_eq!(corrupt.len(), 1);
+    }
+
+    #[test]
+    fn test_repair_drops_corrupt_lines_and_keeps_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        assert!(logger.repair().unwrap().is_empty());
+
+        logger
+            .append(&make_test_outcome(1, "coding", "First"))
+            .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(logger.log_path())
+            .unwrap();
+        writeln!(file, "{{truncated").unwrap();
+
+        let removed = logger.repair().unwrap();
+        assert_eq!(removed.len(), 1);
+        let (outcomes, corrupt) = logger.read_lenient().unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(corrupt.is_empty());
+        let backup = fs::read_to_string(temp_dir.path().join("log.jsonl.corrupt")).unwrap();
+        assert!(backup.contains("{truncated"));
+    }
+
+    #[test]
+    fn test_stream_yields_outcomes_in_order_skipping_malformed() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        assert_eq!(logger.stream().unwrap().count(), 0);
+
+        logger
+            .append(&make_test_outcome(1, "coding", "First"))
+            .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(logger.log_path())
+            .unwrap();
+        writeln!(file, "not json\n").unwrap();
+        logger
+            .append(&make_test_outcome(2, "review", "Second"))
+            .unwrap();
+
+        let mut stream = logger.stream().unwrap();
+        let cycles: Vec<String> = stream.by_ref().map(|o| o.cycle).collect();
+        assert_eq!(cycles, ["coding", "review"]);
+        assert_eq!(stream.corrupt_lines().len(), 1);
+        assert_eq!(stream.corrupt_lines()[0].line_number, 2);
+    }
+
+    #[test]
+    fn test_read_last_returns_tail_across_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        assert!(logger.read_last(3).unwrap().is_empty());
+
+        // Long outcomes so the tail spans several read chunks
+        let padding = "x".repeat(20_000);
+        for i in 1..=10 {
+            logger
+                .append(&make_test_outcome(i, "coding", &format!("{i} {padding}")))
+                .unwrap();
+        }
+
+        let last: Vec<u32> = logger
+            .read_last(4)
+            .unwrap()
+            .iter()
+            .map(|o| o.iteration)
+            .collect();
+        assert_eq!(last, [7, 8, 9, 10]);
+        assert_eq!(logger.read_last(50).unwrap().len(), 10);
+        assert!(logger.read_last(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_tracks_appends_and_rebuilds_after_rewrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = JsonlLogger::new(temp_dir.path()).unwrap();
+        assert_eq!(logger.index().unwrap().entries, 0);
+
+        logger
+            .append(&make_test_outcome(1, "coding", "First"))
+            .unwrap();
+        logger
+            .append(&make_test_outcome(2, "review", "Second"))
+            .unwrap();
+        let index = logger.index().unwrap();
+        assert_eq!(index.entries, 2);
+        assert_eq!(index.cycles["review"].runs, 1);
+        assert_eq!(
+            index::load(&temp_dir.path().join(INDEX_FILE)),
+            Some(index),
+            "appends keep the saved index current"
+        );
+
+        // Rewriting the log behind the index's back forces a rebuild
+        logger
+            .write_all(&[make_test_outcome(1, "coding", "Only")])
+            .unwrap();
+        let rebuilt = logger.index().unwrap();
+        assert_eq!(rebuilt.entries, 1);
+        assert!(!rebuilt.cycles.contains_key("review"));
+    }
+
     #[test]
     fn test_round_trip_serialization() {
         let temp_dir = TempDir::new().unwrap();
@@ -323,6 +982,34 @@ mod tests {
                 "Edit".to_string(),
             ]),
             steps: None,
+            commands_run: None,
+            tests_failed: None,
+            run_id: None,
+            rolled_back: None,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            failure_kind: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            flow_version: None,
+            claude_version: None,
+            os: None,
+            task: None,
+            skipped_steps: None,
+            tools_used: None,
         };
 
         logger.append(&outcome).unwrap();
@@ -352,6 +1039,20 @@ mod tests {
         assert_eq!(entries[0].permission_denials, None);
     }
 
+    #[test]
+    fn test_cycle_outcome_git_shas_round_trip() {
+        let mut outcome = make_test_outcome(1, "coding", "done");
+        outcome.git_sha_before = Some("a".repeat(40));
+        outcome.git_sha_after = Some("b".repeat(40));
+        let json = serde_json::to_string(&outcome).unwrap();
+        assert!(json.contains("\"git_sha_before\""));
+        let recovered: CycleOutcome = serde_json::from_str(&json).unwrap();
+        assert_eq!(recovered.git_sha_after, outcome.git_sha_after);
+
+        let bare = serde_json::to_string(&make_test_outcome(1, "coding", "done")).unwrap();
+        assert!(!bare.contains("git_sha"));
+    }
+
     #[test]
     fn test_cycle_outcome_with_permission_denials_list() {
         let temp_dir = TempDir::new().unwrap();
@@ -370,6 +1071,34 @@ mod tests {
             permission_denial_count: Some(2),
             permission_denials: Some(vec!["Edit".to_string(), "Bash".to_string()]),
             steps: None,
+            commands_run: None,
+            tests_failed: None,
+            run_id: None,
+            rolled_back: None,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            failure_kind: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            flow_version: None,
+            claude_version: None,
+            os: None,
+            task: None,
+            skipped_steps: None,
+            tools_used: None,
         };
 
         logger.append(&outcome).unwrap();
@@ -435,6 +1164,7 @@ mod tests {
                     duration_secs: 120,
                     num_turns: Some(10),
                     cost_usd: Some(0.5),
+                    exit_code: None,
                 },
                 StepOutcome {
                     name: "implement".to_string(),
@@ -442,8 +1172,37 @@ mod tests {
                     duration_secs: 180,
                     num_turns: Some(20),
                     cost_usd: Some(1.0),
+                    exit_code: None,
                 },
             ]),
+            commands_run: None,
+            tests_failed: None,
+            run_id: None,
+            rolled_back: None,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            failure_kind: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            flow_version: None,
+            claude_version: None,
+            os: None,
+            task: None,
+            skipped_steps: None,
+            tools_used: None,
         };
 
         logger.append(&outcome).unwrap();
@@ -0,0 +1,304 @@
+//! Run lock to prevent concurrent executions
+//!
+//! Manages `.flow/lock` — created when a run starts and removed when it ends.
+//! A second run in the same project refuses to start while the lock's owner is
+//! alive; locks left behind by a dead process are reclaimed automatically.
+//! The lock is written to a temporary file and linked into place, so it is
+//! never seen half-written.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Contents of `.flow/lock`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockInfo {
+    /// Process ID of the run holding the lock
+    pub pid: u32,
+    /// When the run started (ISO 8601)
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Held run lock; the lock file is removed on drop.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+    /// PID written to the lock, so a lock another run took over is left alone
+    pid: u32,
+}
+
+impl RunLock {
+    /// Acquire the lock in `log_dir` for the current process.
+    ///
+    /// If another live process holds it, or the lock can't be read, returns an
+    /// error unless `force` is set. A lock whose owner is no longer running is
+    /// reclaimed with a warning.
+    ///
+    /// # Errors
+    /// Returns an error if the lock is held by a live process or unreadable
+    /// (without `force`), or the lock file cannot be written.
+    pub fn acquire(log_dir: &Path, force: bool) -> Result<Self> {
+        Self::acquire_with(log_dir, force, std::process::id(), process_alive)
+    }
+
+    /// `acquire` with an injectable PID and liveness check (for tests).
+    fn acquire_with(
+        log_dir: &Path,
+        force: bool,
+        pid: u32,
+        is_alive: impl Fn(u32) -> bool,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(log_dir)
+            .with_context(|| format!("Failed to create {}", log_dir.display()))?;
+        let path = log_dir.join("lock");
+        let info = LockInfo {
+            pid,
+            started_at: chrono::Utc::now(),
+        };
+        let json = serde_json::to_string(&info).context("Failed to serialize run lock")?;
+
+        if create_lock(&path, &json)? {
+            return Ok(Self { path, pid });
+        }
+
+        // The lock exists: only a dead (or `--force`d) holder's lock may be removed
+        let content = std::fs::read_to_string(&path).unwrap_or_default();
+        match serde_json::from_str::<LockInfo>(&content) {
+            Ok(existing) if existing.pid != pid => {
+                if is_alive(existing.pid) && !force {
+                    bail!(
+                        "Another flow run (PID {}, started {}) holds {}. \
+                         Wait for it to finish, or pass --force if it is no longer running.",
+                        existing.pid,
+                        existing.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        path.display()
+                    );
+                }
+                eprintln!(
+                    "Warning: taking over run lock from PID {} (started {})",
+                    existing.pid,
+                    existing.started_at.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+            }
+            Ok(_) => {}
+            Err(_) if !force => bail!(
+                "Cannot read run lock {}. \
+                 Pass --force if no other flow run is in progress.",
+                path.display()
+            ),
+            Err(_) => eprintln!("Warning: replacing unreadable run lock {}", path.display()),
+        }
+        remove_if_unchanged(&path, &content);
+
+        // Retry once; if another run took over the stale lock in the
+        // meantime, it wins
+        if !create_lock(&path, &json)? {
+            bail!(
+                "Another flow run took over {} while this one was starting",
+                path.display()
+            );
+        }
+        Ok(Self { path, pid })
+    }
+
+    /// Path of the lock file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        if read_lock(&self.path).is_some_and(|info| info.pid == self.pid) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Create the lock file exclusively with `json` as its contents. Returns
+/// `false` if it already exists.
+///
+/// The contents are written to a temporary file first and hard-linked into
+/// place, so other runs see either no lock or a complete one.
+fn create_lock(path: &Path, json: &str) -> Result<bool> {
+    let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&temp, json)
+        .with_context(|| format!("Failed to write run lock {}", temp.display()))?;
+    let linked = std::fs::hard_link(&temp, path);
+    let _ = std::fs::remove_file(&temp);
+    match linked {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("Failed to create run lock {}", path.display())),
+    }
+}
+
+/// Remove the lock file if it still holds `expected`, so a lock another run
+/// has just taken is left alone. Returns whether it was removed.
+fn remove_if_unchanged(path: &Path, expected: &str) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|content| content == expected)
+        && std::fs::remove_file(path).is_ok()
+}
+
+/// The live run holding the lock in `log_dir`, if any.
+#[must_use]
+pub fn live_holder(log_dir: &Path) -> Option<LockInfo> {
+    read_lock(&log_dir.join("lock")).filter(|info| process_alive(info.pid))
+}
+
+/// Read a lock file, returning `None` if it is missing or unparseable.
+fn read_lock(path: &Path) -> Option<LockInfo> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Whether a process with this PID is still running.
+///
+/// A process owned by another user can't be signalled (`EPERM`) but is alive.
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .env("LC_ALL", "C")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            output.status.success()
+                || String::from_utf8_lossy(&output.stderr).contains("not permitted")
+        })
+}
+
+/// Whether a process with this PID is still running.
+///
+/// Without a portable check, assume it is — `--force` overrides.
+#[cfg(not(unix))]
+const fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // --- RunLock tests ---
+
+    #[test]
+    fn test_acquire_creates_lock_with_pid() {
+        let dir = TempDir::new().unwrap();
+        let lock = RunLock::acquire_with(dir.path(), false, 1234, |_| true).unwrap();
+        let info = read_lock(lock.path()).unwrap();
+        assert_eq!(info.pid, 1234);
+    }
+
+    #[test]
+    fn test_drop_removes_lock() {
+        let dir = TempDir::new().unwrap();
+        let path = {
+            let lock = RunLock::acquire_with(dir.path(), false, 1234, |_| true).unwrap();
+            lock.path().to_path_buf()
+        };
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_acquire_refuses_when_held_by_live_process() {
+        let dir = TempDir::new().unwrap();
+        let _held = RunLock::acquire_with(dir.path(), false, 1111, |_| true).unwrap();
+        let err = RunLock::acquire_with(dir.path(), false, 2222, |_| true).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("PID 1111"), "{msg}");
+        assert!(msg.contains("--force"), "{msg}");
+    }
+
+    #[test]
+    fn test_acquire_force_steals_live_lock() {
+        let dir = TempDir::new().unwrap();
+        let held = RunLock::acquire_with(dir.path(), false, 1111, |_| true).unwrap();
+        let path = held.path().to_path_buf();
+        std::mem::forget(held);
+        let lock = RunLock::acquire_with(dir.path(), true, 2222, |_| true).unwrap();
+        assert_eq!(read_lock(&path).unwrap().pid, 2222);
+        drop(lock);
+    }
+
+    #[test]
+    fn test_drop_keeps_lock_taken_over_by_another_run() {
+        let dir = TempDir::new().unwrap();
+        let held = RunLock::acquire_with(dir.path(), false, 1111, |_| true).unwrap();
+        let lock = RunLock::acquire_with(dir.path(), true, 2222, |_| true).unwrap();
+        drop(held);
+        assert_eq!(read_lock(lock.path()).unwrap().pid, 2222);
+        drop(lock);
+        assert!(!dir.path().join("lock").exists());
+    }
+
+    #[test]
+    fn test_acquire_reclaims_lock_from_dead_process() {
+        let dir = TempDir::new().unwrap();
+        let held = RunLock::acquire_with(dir.path(), false, 1111, |_| true).unwrap();
+        std::mem::forget(held);
+        let lock = RunLock::acquire_with(dir.path(), false, 2222, |_| false).unwrap();
+        assert_eq!(read_lock(lock.path()).unwrap().pid, 2222);
+    }
+
+    #[test]
+    fn test_acquire_treats_unreadable_lock_as_held_unless_forced() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lock"), "").unwrap();
+        let err = RunLock::acquire_with(dir.path(), false, 2222, |_| false).unwrap_err();
+        assert!(err.to_string().contains("--force"), "{err}");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("lock")).unwrap(),
+            ""
+        );
+
+        let lock = RunLock::acquire_with(dir.path(), true, 2222, |_| false).unwrap();
+        assert_eq!(read_lock(lock.path()).unwrap().pid, 2222);
+    }
+
+    #[test]
+    fn test_create_lock_leaves_only_the_complete_lock() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("lock");
+        assert!(create_lock(&path, "first").unwrap());
+        assert!(!create_lock(&path, "second").unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+        let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_if_unchanged_keeps_a_replaced_lock() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("lock");
+        std::fs::write(&path, "winner").unwrap();
+        assert!(!remove_if_unchanged(&path, "stale"));
+        assert!(path.exists());
+        assert!(remove_if_unchanged(&path, "winner"));
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_live_holder_ignores_missing_and_dead_locks() {
+        let dir = TempDir::new().unwrap();
+        assert!(live_holder(dir.path()).is_none());
+        let lock = RunLock::acquire_with(dir.path(), false, std::process::id(), |_| true).unwrap();
+        assert_eq!(live_holder(dir.path()).unwrap().pid, std::process::id());
+        drop(lock);
+        // Above Linux's PID_MAX_LIMIT, so no such process can exist
+        let dead = RunLock::acquire_with(dir.path(), false, 4_194_305, |_| true).unwrap();
+        assert!(live_holder(dir.path()).is_none());
+        drop(dead);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_alive_for_current_and_init_process() {
+        assert!(process_alive(std::process::id()));
+        // Signalling PID 1 is refused unless running as root; either way it's alive
+        assert!(process_alive(1));
+    }
+}
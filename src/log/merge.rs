@@ -0,0 +1,143 @@
+//! Merging logs from several machines
+//!
+//! Combines `.flow/log.jsonl` files into one history: entries are grouped into
+//! runs, interleaved by timestamp, and renumbered so iterations count up from 1
+//! within each run.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::log::jsonl::CycleOutcome;
+
+/// Assign a run ID to entries written before run IDs existed.
+///
+/// A new run starts whenever the iteration number fails to increase. Inferred
+/// IDs are `<label>#<n>`, where `label` identifies the source log.
+fn infer_run_ids(label: &str, entries: &mut [CycleOutcome]) {
+    let mut run = 0;
+    let mut last_iteration = None;
+    for entry in entries.iter_mut().filter(|e| e.run_id.is_none()) {
+        if last_iteration.is_none_or(|last| entry.iteration <= last) {
+            run += 1;
+        }
+        last_iteration = Some(entry.iteration);
+        entry.run_id = Some(format!("{label}#{run}"));
+    }
+}
+
+/// Merge logs into a single history.
+///
+/// Each source is a `(label, entries)` pair; the label names inferred runs for
+/// entries that carry no `run_id`. Entries that appear in more than one source
+/// (same run, cycle, and timestamp) are kept once, so re-merging is harmless.
+/// The result is sorted by timestamp, and iterations are renumbered 1..n
+/// within each run.
+#[must_use]
+pub fn merge_logs(sources: Vec<(String, Vec<CycleOutcome>)>) -> Vec<CycleOutcome> {
+    let mut merged = Vec::new();
+    let mut seen = HashSet::new();
+    for (label, mut entries) in sources {
+        infer_run_ids(&label, &mut entries);
+        for entry in entries {
+            let key = (entry.run_id.clone(), entry.cycle.clone(), entry.timestamp);
+            if seen.insert(key) {
+                merged.push(entry);
+            }
+        }
+    }
+
+    // Stable: entries with equal timestamps keep their source order
+    merged.sort_by_key(|e| e.timestamp);
+
+    let mut counters: HashMap<Option<String>, u32> = HashMap::new();
+    for entry in &mut merged {
+        let counter = counters.entry(entry.run_id.clone()).or_insert(0);
+        *counter += 1;
+        entry.iteration = *counter;
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::make_test_outcome;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn entry_at(iteration: u32, cycle: &str, minute: i64) -> CycleOutcome {
+        let mut e = make_test_outcome(iteration, cycle, "Done");
+        e.timestamp =
+            Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap() + Duration::minutes(minute);
+        e
+    }
+
+    // --- infer_run_ids tests ---
+
+    #[test]
+    fn test_infer_run_ids_splits_on_iteration_reset() {
+        let mut entries = vec![
+            entry_at(1, "coding", 0),
+            entry_at(2, "review", 1),
+            entry_at(1, "coding", 10),
+        ];
+        infer_run_ids("a", &mut entries);
+        assert_eq!(entries[0].run_id.as_deref(), Some("a#1"));
+        assert_eq!(entries[1].run_id.as_deref(), Some("a#1"));
+        assert_eq!(entries[2].run_id.as_deref(), Some("a#2"));
+    }
+
+    #[test]
+    fn test_infer_run_ids_keeps_existing_ids() {
+        let mut entries = vec![entry_at(1, "coding", 0)];
+        entries[0].run_id = Some("20260301T120000Z-1".to_string());
+        infer_run_ids("a", &mut entries);
+        assert_eq!(entries[0].run_id.as_deref(), Some("20260301T120000Z-1"));
+    }
+
+    // --- merge_logs tests ---
+
+    #[test]
+    fn test_merge_interleaves_by_timestamp() {
+        let local = vec![entry_at(1, "coding", 0), entry_at(2, "coding", 20)];
+        let other = vec![entry_at(1, "review", 10), entry_at(2, "review", 30)];
+        let merged = merge_logs(vec![
+            ("local".to_string(), local),
+            ("other".to_string(), other),
+        ]);
+        let cycles: Vec<&str> = merged.iter().map(|e| e.cycle.as_str()).collect();
+        assert_eq!(cycles, vec!["coding", "review", "coding", "review"]);
+    }
+
+    #[test]
+    fn test_merge_renumbers_iterations_per_run() {
+        let local = vec![entry_at(5, "coding", 0), entry_at(9, "coding", 20)];
+        let other = vec![entry_at(1, "review", 10)];
+        let merged = merge_logs(vec![
+            ("local".to_string(), local),
+            ("other".to_string(), other),
+        ]);
+        let numbered: Vec<(&str, u32)> = merged
+            .iter()
+            .map(|e| (e.run_id.as_deref().unwrap(), e.iteration))
+            .collect();
+        assert_eq!(
+            numbered,
+            vec![("local#1", 1), ("other#1", 1), ("local#1", 2)]
+        );
+    }
+
+    #[test]
+    fn test_merge_is_idempotent() {
+        let local = vec![entry_at(1, "coding", 0), entry_at(2, "coding", 20)];
+        let other = vec![entry_at(1, "review", 10)];
+        let merged = merge_logs(vec![
+            ("local".to_string(), local),
+            ("other".to_string(), other.clone()),
+        ]);
+        let again = merge_logs(vec![
+            ("local".to_string(), merged.clone()),
+            ("other".to_string(), other),
+        ]);
+        assert_eq!(again.len(), 3);
+        assert_eq!(again, merged);
+    }
+}
@@ -0,0 +1,196 @@
+//! Versioning and migration of the state directory
+//!
+//! `<log_dir>/version` records the on-disk format of the log, progress, and
+//! session files. A directory with state files but no marker predates
+//! versioning (version 0). Runs upgrade older directories through
+//! [`MIGRATIONS`] before touching them, and every command refuses a directory
+//! written by a newer flow instead of misparsing its files. `flow migrate`
+//! applies pending migrations on demand.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// On-disk format version this build reads and writes
+pub const STATE_VERSION: u32 = 1;
+
+/// Name of the version marker inside the log directory
+const VERSION_FILE: &str = "version";
+
+/// Files whose presence marks an unversioned directory as version 0
+const STATE_FILES: &[&str] = &["log.jsonl", "progress.json", "sessions.json"];
+
+/// An upgrade of the state directory from one version to the next
+#[derive(Debug)]
+pub struct Migration {
+    /// Version the migration upgrades from (to `from + 1`)
+    pub from: u32,
+    /// What the migration changes, shown when it is applied
+    pub description: &'static str,
+    /// Rewrites the files in the log directory
+    apply: fn(&Path) -> Result<()>,
+}
+
+/// Every migration, in version order. Add one (and bump [`STATE_VERSION`])
+/// whenever a change to the log, progress, or session files would make older
+/// files misparse.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    description: "record the state format version (earlier layouts already match version 1)",
+    apply: |_| Ok(()),
+}];
+
+/// Version of the state in `log_dir`, or `None` for a missing or empty
+/// directory that has nothing to migrate.
+///
+/// # Errors
+/// Returns an error if the version marker can't be read or parsed.
+pub fn read_version(log_dir: &Path) -> Result<Option<u32>> {
+    let path = log_dir.join(VERSION_FILE);
+    if path.exists() {
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let version = content.trim().parse().with_context(|| {
+            format!(
+                "Unrecognized state version '{}' in {}",
+                content.trim(),
+                path.display()
+            )
+        })?;
+        return Ok(Some(version));
+    }
+    let has_state = STATE_FILES.iter().any(|name| log_dir.join(name).exists());
+    Ok(has_state.then_some(0))
+}
+
+/// Migrations needed to bring state at `version` up to [`STATE_VERSION`].
+#[must_use]
+pub fn pending(version: u32) -> Vec<&'static Migration> {
+    MIGRATIONS.iter().filter(|m| m.from >= version).collect()
+}
+
+/// Fail if `log_dir` was written by a newer flow whose files this build
+/// would misparse.
+///
+/// # Errors
+/// Returns an error if the state is newer than [`STATE_VERSION`] or its
+/// version can't be read.
+pub fn ensure_compatible(log_dir: &Path) -> Result<()> {
+    match read_version(log_dir)? {
+        Some(version) if version > STATE_VERSION => bail!(
+            "{} uses state format version {version}, but this flow only understands up to \
+             version {STATE_VERSION}. Upgrade flow to use it.",
+            log_dir.display()
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Upgrade `log_dir` to [`STATE_VERSION`], stamping the version after each
+/// step so an interrupted upgrade resumes where it stopped. A fresh directory
+/// is stamped directly. Returns the migrations applied.
+///
+/// # Errors
+/// Returns an error if the state is newer than this build, a migration fails,
+/// or the version marker can't be written.
+pub fn migrate(log_dir: &Path) -> Result<Vec<&'static Migration>> {
+    ensure_compatible(log_dir)?;
+    let Some(version) = read_version(log_dir)? else {
+        write_version(log_dir, STATE_VERSION)?;
+        return Ok(Vec::new());
+    };
+    let migrations = pending(version);
+    for migration in &migrations {
+        (migration.apply)(log_dir).with_context(|| {
+            format!(
+                "Failed to migrate {} from version {}",
+                log_dir.display(),
+                migration.from
+            )
+        })?;
+        write_version(log_dir, migration.from + 1)?;
+    }
+    Ok(migrations)
+}
+
+/// Write the version marker, creating the directory if needed.
+fn write_version(log_dir: &Path, version: u32) -> Result<()> {
+    std::fs::create_dir_all(log_dir)
+        .with_context(|| format!("Failed to create {}", log_dir.display()))?;
+    let path = log_dir.join(VERSION_FILE);
+    std::fs::write(&path, format!("{version}\n"))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // --- read_version ---
+
+    #[test]
+    fn test_read_version_distinguishes_fresh_and_unversioned_dirs() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(read_version(&dir.path().join(".flow")).unwrap(), None);
+        assert_eq!(read_version(dir.path()).unwrap(), None);
+
+        std::fs::write(dir.path().join("log.jsonl"), "").unwrap();
+        assert_eq!(read_version(dir.path()).unwrap(), Some(0));
+
+        std::fs::write(dir.path().join("version"), "1\n").unwrap();
+        assert_eq!(read_version(dir.path()).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_read_version_rejects_garbage_marker() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("version"), "one").unwrap();
+        let err = read_version(dir.path()).unwrap_err();
+        assert!(
+            err.to_string().contains("Unrecognized state version"),
+            "{err}"
+        );
+    }
+
+    // --- migrate ---
+
+    #[test]
+    fn test_migrate_stamps_fresh_dir_without_migrations() {
+        let dir = TempDir::new().unwrap();
+        let log_dir = dir.path().join(".flow");
+        assert!(migrate(&log_dir).unwrap().is_empty());
+        assert_eq!(read_version(&log_dir).unwrap(), Some(STATE_VERSION));
+    }
+
+    #[test]
+    fn test_migrate_upgrades_unversioned_dir() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("sessions.json"), "{}").unwrap();
+
+        let applied = migrate(dir.path()).unwrap();
+        assert_eq!(applied.len(), pending(0).len());
+        assert_eq!(read_version(dir.path()).unwrap(), Some(STATE_VERSION));
+        assert!(
+            migrate(dir.path()).unwrap().is_empty(),
+            "second run is a no-op"
+        );
+    }
+
+    #[test]
+    fn test_newer_state_is_refused() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("version"), format!("{}", STATE_VERSION + 1)).unwrap();
+        let err = ensure_compatible(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("Upgrade flow"), "{err}");
+        assert!(migrate(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_migrations_form_a_chain_to_current_version() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.from, u32::try_from(i).unwrap());
+        }
+        assert_eq!(u32::try_from(MIGRATIONS.len()).unwrap(), STATE_VERSION);
+    }
+}
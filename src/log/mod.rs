@@ -1,10 +1,21 @@
 //! Logging and observability
 //!
 //! This module provides logging functionality for Flow, including
-//! JSONL logging for cycle execution history.
+//! JSONL logging for cycle execution history, its running-totals index, CSV
+//! export, log merging, the run lock, following a run from another terminal,
+//! state directory versioning, the `--emit-events` lifecycle stream, and
+//! `--record` / `--replay` recordings.
 
+pub mod events;
+pub mod export;
+pub mod follow;
+pub mod index;
 pub mod jsonl;
+pub mod lock;
+pub mod merge;
+pub mod migrate;
 pub mod progress;
+pub mod recording;
 
 pub use jsonl::{CycleOutcome, JsonlLogger};
 pub use progress::{ProgressWriter, RunProgress, RunStatus};
@@ -9,6 +9,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use crate::log::jsonl::{os_label, FLOW_VERSION};
+
 /// Current status of a Flow run
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -46,6 +48,34 @@ pub struct RunProgress {
     /// Outcome text from the most recent cycle (None if no cycle has completed yet)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_outcome: Option<String>,
+    /// Why the run stopped early (e.g. a duration limit), if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+    /// Steps the most recent multi-step cycle completed before finishing or failing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub completed_steps: Vec<String>,
+    /// Steps the most recent multi-step cycle skipped via `skip_if`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_steps: Vec<String>,
+    /// Step the most recent multi-step cycle failed on (resume with `--from-step`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_step: Option<String>,
+    /// Version of flow running this run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow_version: Option<String>,
+    /// Claude Code CLI version reported by the most recent cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claude_version: Option<String>,
+    /// Model reported by the most recent cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Operating system and architecture, e.g. `linux-x86_64`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    /// `HEAD` commit when the run started, updated after each cycle
+    /// (absent outside a git repository)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_sha: Option<String>,
 }
 
 impl RunProgress {
@@ -62,7 +92,32 @@ impl RunProgress {
             total_duration_secs: 0,
             total_cost_usd: 0.0,
             last_outcome: None,
+            stop_reason: None,
+            completed_steps: Vec::new(),
+            skipped_steps: Vec::new(),
+            failed_step: None,
+            flow_version: Some(FLOW_VERSION.to_string()),
+            claude_version: None,
+            model: None,
+            os: Some(os_label()),
+            git_sha: None,
+        }
+    }
+
+    /// Estimate the seconds remaining in the run.
+    ///
+    /// Uses the average wall-clock time per completed iteration (selector
+    /// overhead included) since `started_at`. Returns `None` before the first
+    /// iteration completes or once all iterations are done.
+    #[must_use]
+    pub fn estimated_remaining_secs(&self, now: chrono::DateTime<chrono::Utc>) -> Option<u64> {
+        let completed: u32 = self.cycles_executed.values().sum();
+        if completed == 0 || completed >= self.max_iterations {
+            return None;
         }
+        let elapsed = u64::try_from((now - self.started_at).num_seconds()).unwrap_or(0);
+        let remaining = u64::from(self.max_iterations - completed);
+        Some(elapsed / u64::from(completed) * remaining)
     }
 }
 
@@ -100,14 +155,7 @@ impl ProgressWriter {
 
     /// Read the current progress from the file, or `None` if it doesn't exist.
     pub fn read(&self) -> Result<Option<RunProgress>> {
-        if !self.path.exists() {
-            return Ok(None);
-        }
-        let content = std::fs::read_to_string(&self.path)
-            .with_context(|| format!("Failed to read {}", self.path.display()))?;
-        let progress: RunProgress =
-            serde_json::from_str(&content).context("Failed to parse progress.json")?;
-        Ok(Some(progress))
+        read_progress_file(&self.path)
     }
 
     /// Delete the progress file. No-op if it doesn't exist.
@@ -120,6 +168,22 @@ impl ProgressWriter {
     }
 }
 
+/// Read `<log_dir>/progress.json` without creating the log directory, or
+/// `None` if no run has written one.
+pub fn read_progress(log_dir: &Path) -> Result<Option<RunProgress>> {
+    read_progress_file(&log_dir.join("progress.json"))
+}
+
+fn read_progress_file(path: &Path) -> Result<Option<RunProgress>> {
+    let content = match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        read => read.with_context(|| format!("Failed to read {}", path.display()))?,
+    };
+    let progress: RunProgress =
+        serde_json::from_str(&content).context("Failed to parse progress.json")?;
+    Ok(Some(progress))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,9 +205,48 @@ mod tests {
             total_duration_secs: 445,
             total_cost_usd: 3.45,
             last_outcome: Some("Added ClaudeClient implementation".to_string()),
+            stop_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            flow_version: None,
+            claude_version: None,
+            model: None,
+            os: None,
+            git_sha: None,
+            skipped_steps: Vec::new(),
         }
     }
 
+    // --- estimated_remaining_secs tests ---
+
+    #[test]
+    fn test_eta_uses_average_iteration_time() {
+        let progress = sample_progress(); // 3 of 20 done
+        let now = progress.started_at + chrono::Duration::seconds(300);
+        // 100s per iteration × 17 remaining
+        assert_eq!(progress.estimated_remaining_secs(now), Some(1700));
+    }
+
+    #[test]
+    fn test_eta_none_before_first_iteration() {
+        let progress = RunProgress::new(10);
+        assert_eq!(progress.estimated_remaining_secs(Utc::now()), None);
+    }
+
+    #[test]
+    fn test_eta_none_when_run_complete() {
+        let mut progress = sample_progress();
+        progress.max_iterations = 3;
+        assert_eq!(progress.estimated_remaining_secs(Utc::now()), None);
+    }
+
+    #[test]
+    fn test_eta_clock_skew_no_panic() {
+        let progress = sample_progress();
+        let before_start = progress.started_at - chrono::Duration::seconds(60);
+        assert_eq!(progress.estimated_remaining_secs(before_start), Some(0));
+    }
+
     #[test]
     fn test_run_progress_serializes_to_expected_json() {
         let progress = sample_progress();
@@ -303,6 +406,17 @@ mod tests {
         assert!((progress.total_cost_usd - 0.0).abs() < f64::EPSILON);
     }
 
+    #[test]
+    fn test_stop_reason_serialized_only_when_set() {
+        let mut progress = sample_progress();
+        let json = serde_json::to_string(&progress).unwrap();
+        assert!(!json.contains("stop_reason"));
+
+        progress.stop_reason = Some("max run duration reached".to_string());
+        let json = serde_json::to_value(&progress).unwrap();
+        assert_eq!(json["stop_reason"], "max run duration reached");
+    }
+
     #[test]
     fn test_last_outcome_omitted_when_none() {
         let progress = RunProgress {
@@ -315,6 +429,15 @@ mod tests {
             total_duration_secs: 0,
             total_cost_usd: 0.0,
             last_outcome: None,
+            stop_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            flow_version: None,
+            claude_version: None,
+            model: None,
+            os: None,
+            git_sha: None,
+            skipped_steps: Vec::new(),
         };
 
         let json = serde_json::to_string(&progress).unwrap();
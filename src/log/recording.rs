@@ -0,0 +1,466 @@
+//! Record-and-replay of Claude Code output
+//!
+//! `flow --record` writes every nondeterministic input of a run to
+//! `.flow/recordings/<run_id>.jsonl`: the iteration limit, the stream-JSON output of each cycle and
+//! step invocation, each cycle selection, and each LLM routing decision.
+//! `flow --replay <recording>` re-runs the orchestrator against that file
+//! instead of spawning Claude Code, so aggregation, gating, and logging can be
+//! regression-tested on real transcripts.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// One Claude Code invocation for a cycle (or one step of it)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecordedInvocation {
+    /// Cycle name
+    pub cycle: String,
+    /// Step name (`None` for single-step cycles)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<String>,
+    /// Redacted stdout lines, without trailing newlines
+    pub stdout: Vec<String>,
+    /// Redacted stderr
+    #[serde(default)]
+    pub stderr: String,
+    /// Process exit code (`None` if killed)
+    pub exit_code: Option<i32>,
+    /// Wall-clock duration of the invocation
+    pub duration_secs: u64,
+}
+
+/// One line of a recording
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEntry {
+    /// Run header, written first
+    Run {
+        /// The recorded run's `--max-iterations`
+        max_iterations: u32,
+        /// The recorded run's `--cycles`
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        playlist: Vec<String>,
+    },
+    /// The cycle chosen for an iteration (fixed, playlist, or selector)
+    Selection {
+        /// Iteration number
+        iteration: u32,
+        /// Chosen cycle
+        cycle: String,
+        /// TODO.md task the selector chose for the cycle
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        task: Option<String>,
+    },
+    /// Output of a cycle or step invocation
+    Invocation(RecordedInvocation),
+    /// An LLM router's decision after `from` (`to` = `None` when done)
+    Route {
+        /// Cycle name
+        cycle: String,
+        /// Step that just completed
+        from: String,
+        /// Next step, or `None` when the cycle is done
+        to: Option<String>,
+        /// Router's reason
+        reason: String,
+    },
+    /// The sample a multi-sample cycle's judge kept
+    Judgment {
+        /// Cycle name
+        cycle: String,
+        /// Chosen sample (1-based)
+        sample: u32,
+        /// Judge's reason
+        reason: String,
+    },
+    /// The outcome of a command-backed condition (`success_when`,
+    /// `repeat_until`, or `skip_if`)
+    Condition {
+        /// Cycle name
+        cycle: String,
+        /// Step name, for step conditions
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        step: Option<String>,
+        /// Which condition was checked
+        check: String,
+        /// What the check reported: an unmet criterion, or why the step was
+        /// skipped (`None` when there was nothing to report)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        outcome: Option<String>,
+    },
+}
+
+/// Whether a [`Recording`] captures a live run or feeds a replay
+enum Mode {
+    Record(File),
+    Replay(VecDeque<RecordedEntry>),
+}
+
+/// A recording file being written (`--record`) or replayed (`--replay`)
+pub struct Recording {
+    path: PathBuf,
+    /// Iteration limit from the replayed run's header
+    max_iterations: Option<u32>,
+    /// `--cycles` from the replayed run's header
+    playlist: Vec<String>,
+    mode: Mutex<Mode>,
+}
+
+impl Recording {
+    /// Start recording to `path`, creating its directory.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be created.
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to create recording '{}'", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_iterations: None,
+            playlist: Vec::new(),
+            mode: Mutex::new(Mode::Record(file)),
+        })
+    }
+
+    /// Load a recording for replay.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or a line isn't a valid entry.
+    pub fn open(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording '{}'", path.display()))?;
+        let mut entries = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| {
+                    format!("Invalid recording entry at {}:{}", path.display(), i + 1)
+                })
+            })
+            .collect::<Result<VecDeque<RecordedEntry>>>()?;
+        let (max_iterations, playlist) = entries
+            .iter()
+            .find_map(|e| match e {
+                RecordedEntry::Run {
+                    max_iterations,
+                    playlist,
+                } => Some((Some(*max_iterations), playlist.clone())),
+                _ => None,
+            })
+            .unwrap_or_default();
+        entries.retain(|e| !matches!(e, RecordedEntry::Run { .. }));
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_iterations,
+            playlist,
+            mode: Mutex::new(Mode::Replay(entries)),
+        })
+    }
+
+    /// Path of the recording file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether this recording replaces Claude Code rather than capturing it.
+    #[must_use]
+    pub fn is_replay(&self) -> bool {
+        matches!(*self.lock(), Mode::Replay(_))
+    }
+
+    /// `--max-iterations` of the recorded run (`None` when recording, or for
+    /// a recording without a header).
+    #[must_use]
+    pub const fn max_iterations(&self) -> Option<u32> {
+        self.max_iterations
+    }
+
+    /// `--cycles` of the recorded run (empty when recording, or when the
+    /// recorded run didn't use a playlist).
+    #[must_use]
+    pub fn playlist(&self) -> &[String] {
+        &self.playlist
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Mode> {
+        self.mode.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Append `entry` when recording; a no-op when replaying.
+    ///
+    /// Write failures are reported but never abort the run.
+    pub fn record(&self, entry: &RecordedEntry) {
+        if let Mode::Record(file) = &mut *self.lock() {
+            let result = serde_json::to_string(entry)
+                .map_err(std::io::Error::other)
+                .and_then(|line| writeln!(file, "{line}"));
+            if let Err(e) = result {
+                eprintln!(
+                    "Warning: could not write recording {}: {e}",
+                    self.path.display()
+                );
+            }
+        }
+    }
+
+    /// Pop the next replayed entry, which must be of the kind `expected` describes.
+    fn take<T>(
+        &self,
+        expected: &str,
+        extract: impl FnOnce(RecordedEntry) -> Result<T, RecordedEntry>,
+    ) -> Result<T> {
+        let next = match &mut *self.lock() {
+            Mode::Replay(entries) => entries.pop_front(),
+            Mode::Record(_) => {
+                bail!("Recording '{}' is not open for replay", self.path.display())
+            }
+        };
+        let Some(entry) = next else {
+            bail!("Replay diverged: expected {expected}, but the recording has ended");
+        };
+        extract(entry).or_else(|entry| {
+            bail!("Replay diverged: expected {expected}, but the recording has {entry:?}")
+        })
+    }
+
+    /// The recorded cycle (and selected task) for `iteration`.
+    ///
+    /// # Errors
+    /// Returns an error if the next entry isn't that iteration's selection.
+    pub fn take_selection(&self, iteration: u32) -> Result<(String, Option<String>)> {
+        self.take(
+            &format!("the selection for iteration {iteration}"),
+            |e| match e {
+                RecordedEntry::Selection {
+                    iteration: i,
+                    cycle,
+                    task,
+                } if i == iteration => Ok((cycle, task)),
+                other => Err(other),
+            },
+        )
+    }
+
+    /// The recorded output of `cycle` (and `step`).
+    ///
+    /// # Errors
+    /// Returns an error if the next entry isn't an invocation of that cycle and step.
+    pub fn take_invocation(&self, cycle: &str, step: Option<&str>) -> Result<RecordedInvocation> {
+        let label = step.map_or_else(|| cycle.to_string(), |s| format!("{cycle}/{s}"));
+        self.take(&format!("output of '{label}'"), |e| match e {
+            RecordedEntry::Invocation(inv) if inv.cycle == cycle && inv.step.as_deref() == step => {
+                Ok(inv)
+            }
+            other => Err(other),
+        })
+    }
+
+    /// The recorded LLM routing decision after `cycle`/`from`: `(to, reason)`.
+    ///
+    /// # Errors
+    /// Returns an error if the next entry isn't a route from that step.
+    pub fn take_route(&self, cycle: &str, from: &str) -> Result<(Option<String>, String)> {
+        self.take(&format!("a route from '{cycle}/{from}'"), |e| match e {
+            RecordedEntry::Route {
+                cycle: c,
+                from: f,
+                to,
+                reason,
+            } if c == cycle && f == from => Ok((to, reason)),
+            other => Err(other),
+        })
+    }
+
+    /// The recorded outcome of `cycle`'s (or `step`'s) `check` condition.
+    ///
+    /// # Errors
+    /// Returns an error if the next entry isn't that condition.
+    pub fn take_condition(
+        &self,
+        cycle: &str,
+        step: Option<&str>,
+        check: &str,
+    ) -> Result<Option<String>> {
+        self.take(&format!("the {check} check of '{cycle}'"), |e| match e {
+            RecordedEntry::Condition {
+                cycle: c,
+                step: s,
+                check: k,
+                outcome,
+            } if c == cycle && s.as_deref() == step && k == check => Ok(outcome),
+            other => Err(other),
+        })
+    }
+
+    /// The recorded judgment of `cycle`'s samples: `(sample, reason)`.
+    ///
+    /// # Errors
+    /// Returns an error if the next entry isn't a judgment of that cycle.
+    pub fn take_judgment(&self, cycle: &str) -> Result<(u32, String)> {
+        self.take(&format!("the judgment of '{cycle}' samples"), |e| match e {
+            RecordedEntry::Judgment {
+                cycle: c,
+                sample,
+                reason,
+            } if c == cycle => Ok((sample, reason)),
+            other => Err(other),
+        })
+    }
+}
+
+/// Default path for a new recording: `<log_dir>/recordings/<run_id>.jsonl`.
+#[must_use]
+pub fn recording_path(log_dir: &Path, run_id: &str) -> PathBuf {
+    log_dir.join("recordings").join(format!("{run_id}.jsonl"))
+}
+
+/// Log directory of a replay, kept apart from the project's own log:
+/// `<log_dir>/replays/<run_id>`.
+#[must_use]
+pub fn replay_log_dir(log_dir: &Path, run_id: &str) -> PathBuf {
+    log_dir.join("replays").join(run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn invocation(cycle: &str, step: Option<&str>) -> RecordedInvocation {
+        RecordedInvocation {
+            cycle: cycle.to_string(),
+            step: step.map(str::to_string),
+            stdout: vec![r#"{"type":"result"}"#.to_string()],
+            stderr: String::new(),
+            exit_code: Some(0),
+            duration_secs: 12,
+        }
+    }
+
+    fn recorded(entries: &[RecordedEntry]) -> (TempDir, Recording) {
+        let dir = TempDir::new().unwrap();
+        let path = recording_path(dir.path(), "run-1");
+        let recording = Recording::create(&path).unwrap();
+        for entry in entries {
+            recording.record(entry);
+        }
+        drop(recording);
+        let replay = Recording::open(&path).unwrap();
+        (dir, replay)
+    }
+
+    // --- Recording tests ---
+
+    #[test]
+    fn test_round_trip_replays_entries_in_order() {
+        let (_dir, replay) = recorded(&[
+            RecordedEntry::Run {
+                max_iterations: 3,
+                playlist: vec!["coding".to_string()],
+            },
+            RecordedEntry::Selection {
+                iteration: 1,
+                cycle: "coding".to_string(),
+                task: Some("Fix the bug".to_string()),
+            },
+            RecordedEntry::Invocation(invocation("coding", Some("plan"))),
+            RecordedEntry::Route {
+                cycle: "coding".to_string(),
+                from: "plan".to_string(),
+                to: None,
+                reason: "done".to_string(),
+            },
+            RecordedEntry::Judgment {
+                cycle: "coding".to_string(),
+                sample: 2,
+                reason: "smaller diff".to_string(),
+            },
+            RecordedEntry::Condition {
+                cycle: "coding".to_string(),
+                step: None,
+                check: "success_when".to_string(),
+                outcome: Some("`cargo test` failed".to_string()),
+            },
+        ]);
+        assert!(replay.is_replay());
+        assert_eq!(replay.max_iterations(), Some(3));
+        assert_eq!(replay.playlist(), ["coding"]);
+        assert_eq!(
+            replay.take_selection(1).unwrap(),
+            ("coding".to_string(), Some("Fix the bug".to_string()))
+        );
+        assert_eq!(
+            replay.take_invocation("coding", Some("plan")).unwrap(),
+            invocation("coding", Some("plan"))
+        );
+        assert_eq!(
+            replay.take_route("coding", "plan").unwrap(),
+            (None, "done".to_string())
+        );
+        assert_eq!(
+            replay.take_judgment("coding").unwrap(),
+            (2, "smaller diff".to_string())
+        );
+        assert!(replay
+            .take_condition("coding", Some("plan"), "success_when")
+            .is_err());
+    }
+
+    #[test]
+    fn test_take_condition_returns_recorded_outcome() {
+        let (_dir, replay) = recorded(&[RecordedEntry::Condition {
+            cycle: "coding".to_string(),
+            step: Some("fix".to_string()),
+            check: "repeat_until".to_string(),
+            outcome: None,
+        }]);
+        assert_eq!(
+            replay
+                .take_condition("coding", Some("fix"), "repeat_until")
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_take_reports_divergence() {
+        let (_dir, replay) = recorded(&[RecordedEntry::Invocation(invocation("coding", None))]);
+        let err = replay.take_invocation("gardening", None).unwrap_err();
+        assert!(err.to_string().contains("Replay diverged"), "{err}");
+        let err = replay.take_selection(1).unwrap_err();
+        assert!(err.to_string().contains("has ended"), "{err}");
+    }
+
+    #[test]
+    fn test_open_rejects_invalid_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("bad.jsonl");
+        std::fs::write(&path, "{\"kind\":\"selection\"}\n").unwrap();
+        let err = Recording::open(&path).err().unwrap();
+        assert!(err.to_string().contains("bad.jsonl:1"), "{err}");
+    }
+
+    #[test]
+    fn test_recording_mode_is_not_replay() {
+        let dir = TempDir::new().unwrap();
+        let recording = Recording::create(&dir.path().join("r.jsonl")).unwrap();
+        assert!(!recording.is_replay());
+        assert_eq!(recording.max_iterations(), None);
+        assert!(recording.take_selection(1).is_err());
+    }
+}
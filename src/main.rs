@@ -5,26 +5,45 @@
 // Allow multiple crate versions from dependencies (can't easily control)
 #![allow(clippy::multiple_crate_versions)]
 
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use colored::Colorize;
-
-use flow::claude::stream::suggest_permission_fix;
-use flow::cli::render_diagnostic_report;
-use flow::cycle::config::FlowConfig;
+use clap::{Parser, Subcommand, ValueEnum};
+use tokio_util::sync::CancellationToken;
+
+use flow::api::{ApiServer, API_TOKEN_ENV};
+use flow::claude::cli::set_default_claude_bin;
+use flow::claude::mock::{prompt_arg, MockFixtures};
+use flow::claude::session::{load_sessions, prune_sessions, save_sessions};
+use flow::cli::{
+    pick_cycle, render_cycle_trends, render_diagnostic_json, render_diagnostic_report,
+    render_gc_report, render_permission_drift, render_sessions, render_watch_outcome,
+    render_watch_progress, terminal_mode,
+};
+use flow::cycle::compaction::{compact, DIGESTS_FILE};
+use flow::cycle::config::{CompactionConfig, FlowConfig, GcConfig};
 use flow::cycle::executor::CycleExecutor;
-use flow::cycle::rules::find_triggered_cycles;
-use flow::cycle::selector::select_cycle;
+use flow::cycle::explain::render_explanation;
+use flow::cycle::selector::{SELECTED_TASK_ID_VAR, SELECTED_TASK_VAR};
+use flow::cycle::show::{render_effective, value_origins};
 use flow::cycle::template::build_template_vars;
-use flow::doctor::diagnose;
+use flow::cycle::user_config::UserConfig;
+use flow::doctor::{diagnose, Severity};
+use flow::drift::permission_drift;
+use flow::gc::collect;
 use flow::init::init;
+use flow::log::export::{anonymize, export_csv, export_jsonl};
+use flow::log::follow::{RunWatcher, WatchUpdate};
 use flow::log::jsonl::JsonlLogger;
-use flow::log::progress::{ProgressWriter, RunProgress, RunStatus};
-use flow::log::CycleOutcome;
+use flow::log::lock::live_holder;
+use flow::log::merge::merge_logs;
+use flow::log::migrate::{ensure_compatible, migrate, pending, read_version, STATE_VERSION};
+use flow::log::recording::Recording;
+use flow::queue::{insert_task_vars, TaskQueue};
+use flow::stats::cycle_trends;
+use flow::tasks::TASKS_FILE;
+use flow::FlowRunner;
 
 /// Automated coding pipeline runner
 ///
@@ -32,18 +51,34 @@ use flow::log::CycleOutcome;
 /// review, planning) with controlled permissions and observability.
 #[derive(Parser, Debug)]
 #[command(name = "flow", version, about)]
+#[allow(clippy::struct_excessive_bools)] // independent CLI flags
 struct Cli {
     /// Name of the cycle to execute (shorthand for `flow run --cycle <name>`)
     #[arg(long)]
     cycle: Option<String>,
 
+    /// Start the `--cycle` multi-step cycle at this step, skipping earlier steps
+    /// (e.g. to resume after a failure; applies to the first iteration only)
+    #[arg(long, requires = "cycle", value_name = "STEP")]
+    from_step: Option<String>,
+
+    /// Run all these cycles in order each iteration
+    /// (e.g. `--cycles plan,coding,review`)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "NAMES",
+        conflicts_with = "cycle"
+    )]
+    cycles: Vec<String>,
+
     /// Path to the cycles.toml configuration file
     #[arg(long, default_value = "cycles.toml")]
     config: PathBuf,
 
-    /// Directory for log files (.flow by default)
-    #[arg(long, default_value = ".flow")]
-    log_dir: PathBuf,
+    /// Directory for log files (default: the user config's `log_dir`, else .flow)
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
 
     /// Maximum number of iterations to run (default: 1)
     #[arg(long, default_value = "1")]
@@ -53,9 +88,119 @@ struct Cli {
     #[arg(long, default_value = "TODO.md")]
     todo: PathBuf,
 
+    /// Structured task file used instead of TODO.md when it exists
+    #[arg(long, default_value = TASKS_FILE)]
+    tasks: PathBuf,
+
+    /// Show extra detail during execution (e.g., extended-thinking output)
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Tee raw stream-JSON output into <log-dir>/debug/ (also `global.debug_stream`)
+    #[arg(long)]
+    debug_stream: bool,
+
+    /// Override `max_turns` for every cycle and step in this run
+    #[arg(long)]
+    max_turns: Option<u32>,
+
+    /// Override `max_cost_usd` for every cycle and step in this run
+    #[arg(long, value_parser = parse_max_cost)]
+    max_cost_usd: Option<f64>,
+
+    /// Apply the `[profile.<name>]` section of cycles.toml (e.g. `ci`, `cheap`)
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Override a config value for this run, e.g. `global.summary_interval=10` or
+    /// `cycle.coding.max_turns=30` (repeatable)
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+
+    /// Exclude a cycle from AI selection for this run (repeatable)
+    #[arg(long = "skip-cycle", value_name = "NAME")]
+    skip_cycles: Vec<String>,
+
+    /// Start even if `.flow/lock` shows another run in progress (steals the lock)
+    #[arg(long)]
+    force: bool,
+
+    /// Stop starting new iterations after this long, e.g. `4h`, `90m`, `1h30m`
+    /// (overrides `global.max_run_duration_secs`)
+    #[arg(long, value_parser = parse_max_duration)]
+    max_duration: Option<u64>,
+
+    /// Write lifecycle events (iteration started, cycle selected, cycle completed,
+    /// gate tripped) to stdout as NDJSON; human output stays on stderr
+    #[arg(long)]
+    emit_events: bool,
+
+    /// Record Claude Code output, cycle selections, and LLM routing decisions
+    /// to `<log-dir>/recordings/<run_id>.jsonl` for `--replay`
+    #[arg(long)]
+    record: bool,
+
+    /// Re-run the orchestrator against a `--record` recording instead of
+    /// invoking Claude Code (cycles and iteration count come from the recording)
+    #[arg(
+        long,
+        value_name = "RECORDING",
+        conflicts_with_all = ["record", "cycle", "cycles"]
+    )]
+    replay: Option<PathBuf>,
+
     /// Subcommand to run
     #[command(subcommand)]
     command: Option<Command>,
+
+    /// Settings from `~/.config/flow/config.toml` (loaded after parsing)
+    #[arg(skip)]
+    user: UserConfig,
+}
+
+impl Cli {
+    /// `--log-dir`, else the user config's `log_dir`, else `.flow`.
+    fn log_dir(&self) -> &Path {
+        self.log_dir
+            .as_deref()
+            .or(self.user.log_dir.as_deref())
+            .unwrap_or_else(|| Path::new(".flow"))
+    }
+
+    /// Load `--config` with the user config merged under it, then apply the
+    /// profile and `--set` overrides. Its `[display]` section styles the
+    /// rest of the process's output.
+    fn load_config(&self) -> Result<FlowConfig> {
+        let config = FlowConfig::from_path_with_defaults(
+            &self.config,
+            &self.user.defaults,
+            self.profile.as_deref(),
+            &self.overrides,
+        )
+        .with_context(|| format!("Failed to load config from '{}'", self.config.display()))?;
+        if let Some(display) = &config.display {
+            flow::cli::set_theme(display);
+        }
+        Ok(config)
+    }
+}
+
+/// Parse `--max-duration` into seconds, rejecting zero.
+fn parse_max_duration(value: &str) -> Result<u64, String> {
+    match flow::cycle::config::parse_duration_secs(value) {
+        Ok(0) => Err("duration must be greater than 0".to_string()),
+        Ok(secs) => Ok(secs),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parse `--max-cost-usd`, rejecting non-positive or non-finite values.
+fn parse_max_cost(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(cost) if cost.is_finite() && cost > 0.0 => Ok(cost),
+        Ok(_) => Err("cost must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
 /// Available subcommands
@@ -66,239 +211,221 @@ enum Command {
         /// Auto-fix safe, repairable issues (D001 permissions, D004 `min_interval`)
         #[arg(long)]
         repair: bool,
+        /// Output format for the diagnostic report
+        #[arg(long, value_enum, default_value_t = DoctorFormat::Text)]
+        format: DoctorFormat,
+        /// Lowest severity that makes `flow doctor` exit non-zero
+        #[arg(long, value_enum, default_value_t = FailOn::Error)]
+        fail_on: FailOn,
+    },
+    /// Inspect the effective configuration
+    Config {
+        /// Config operation to run
+        #[command(subcommand)]
+        command: ConfigCommand,
     },
     /// Initialize a new Flow project (creates cycles.toml and .flow/)
     Init,
+    /// Prune old transcripts, recordings, backups, sessions, and leftovers
+    /// from the log directory per the `[gc]` retention settings
+    Gc {
+        /// List what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Upgrade the log directory's state files to this version's format
+    Migrate {
+        /// List pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Inspect and export the run log (.flow/log.jsonl)
+    Logs {
+        /// Log operation to run
+        #[command(subcommand)]
+        command: LogsCommand,
+    },
+    /// Show each cycle's runs, success rate, and cost, and trend its `eval`
+    /// rubric scores
+    Stats {
+        /// Compare the mean of the last this-many scored runs with the same
+        /// number before them
+        #[arg(long, default_value_t = 10)]
+        window: usize,
+    },
+    /// Compare granted permissions with what the log shows cycles using
+    Permissions {
+        /// Permissions operation to run
+        #[command(subcommand)]
+        command: PermissionsCommand,
+    },
+    /// Manage sessions persisted across runs (.flow/sessions.json)
+    Sessions {
+        /// Session operation to run
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
+    /// Serve an authenticated HTTP API to start, watch, and stop runs
+    /// (bearer token from `FLOW_API_TOKEN`)
+    Serve {
+        /// Serve the remote-control API
+        #[arg(long)]
+        api: bool,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8787")]
+        listen: String,
+    },
+    /// Print the prompt (with log context injected), permissions, sessions,
+    /// budgets, and exact Claude Code command line a cycle would run, without
+    /// running it
+    Explain {
+        /// Cycle to explain
+        cycle: String,
+    },
+    /// Follow a run started in another terminal: its progress and each
+    /// logged cycle, until it finishes (read-only)
+    WatchProgress {
+        /// How often to check for changes, e.g. `2s`, `1m`
+        #[arg(long, default_value = "2s", value_parser = parse_max_duration)]
+        interval: u64,
+        /// Print the current state and exit instead of following
+        #[arg(long)]
+        once: bool,
+    },
+    /// Stand in for the `claude` binary in offline tests: replay fixture
+    /// responses (use with `FLOW_CLAUDE_BIN="flow mock-claude --fixtures DIR"`)
+    MockClaude {
+        /// Directory containing responses.toml and transcripts
+        #[arg(long, default_value = ".")]
+        fixtures: PathBuf,
+        /// Claude Code arguments (the `-p` prompt selects the response)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
 }
 
-/// Format an exit code for display, returning "unknown" if the process was killed by signal.
-fn format_exit_code(exit_code: Option<i32>) -> String {
-    exit_code.map_or_else(|| "unknown".to_string(), |c| c.to_string())
-}
-
-/// Build a `CycleOutcome` from a `CycleResult` for JSONL logging.
-fn build_outcome(result: &flow::CycleResult, iteration: u32) -> CycleOutcome {
-    let outcome_text = result.result_text.clone().unwrap_or_else(|| {
-        if result.success {
-            "Completed successfully".to_string()
-        } else {
-            format!(
-                "Failed with exit code {}",
-                format_exit_code(result.exit_code)
-            )
-        }
-    });
-
-    CycleOutcome {
-        iteration,
-        cycle: result.cycle_name.clone(),
-        timestamp: chrono::Utc::now(),
-        outcome: outcome_text,
-        files_changed: result.files_changed.clone(),
-        tests_passed: result.tests_passed,
-        duration_secs: result.duration_secs,
-        num_turns: result.num_turns,
-        total_cost_usd: result.total_cost_usd,
-        permission_denial_count: result.permission_denial_count,
-        permission_denials: result.permission_denials.clone(),
-        steps: None,
-    }
+/// `flow config` operations
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum ConfigCommand {
+    /// Print every effective value (after profile, --set, --skip-cycle, prompt
+    /// files, and snippets) with the layer it came from
+    Show,
 }
 
-/// A compact record of one cycle execution within the current run, for health tracking.
-struct RunOutcome {
-    /// Whether the cycle completed successfully
-    success: bool,
+/// `flow permissions` operations
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum PermissionsCommand {
+    /// Report grants no logged run exercised and tools repeatedly denied, per cycle
+    Diff {
+        /// Report a tool once a cycle has been denied it this many times
+        #[arg(long, default_value = "2")]
+        min_denials: u32,
+    },
 }
 
-/// Check cumulative run health — returns Some(reason) if the run should stop.
-///
-/// Stops if the trailing window of outcomes contains `max_consecutive_failures`
-/// consecutive failures (cycles whose `success == false`). Successes reset the streak.
-fn check_run_health(history: &[RunOutcome], max_consecutive_failures: u32) -> Option<String> {
-    if max_consecutive_failures == 0 {
-        return None;
-    }
-    let mut consecutive = 0u32;
-    for outcome in history {
-        if outcome.success {
-            consecutive = 0;
-        } else {
-            consecutive += 1;
-            if consecutive >= max_consecutive_failures {
-                return Some(format!(
-                    "Stopping run: {consecutive} consecutive cycle failures (threshold: {max_consecutive_failures}). \
-                     Fix the underlying issue before continuing."
-                ));
-            }
-        }
-    }
-    None
+/// `flow sessions` operations
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum SessionsCommand {
+    /// List persisted sessions with their cycle and last use
+    List,
+    /// Remove sessions not used recently
+    Prune {
+        /// Remove sessions last used longer ago than this, e.g. `7d`, `12h`
+        #[arg(long, default_value = "7d", value_parser = parse_max_duration)]
+        older_than: u64,
+    },
+    /// Forget the persisted session for a tag (its next use starts fresh)
+    Delete {
+        /// Session tag to delete
+        tag: String,
+    },
 }
 
-/// Check if permission denials exceed the threshold and exit if so.
-fn check_denial_gate(denials: u32, max_denials: u32, cycle_name: &str) {
-    if denials > max_denials {
-        eprintln!(
-            "Stopping: {denials} permission denials in '{cycle_name}' exceeded threshold ({max_denials}). \
-             Fix permissions in cycles.toml before continuing."
-        );
-        std::process::exit(1);
-    }
+/// `flow logs` operations
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum LogsCommand {
+    /// Export the log for spreadsheet analysis (cycle rows plus per-step rows)
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Strip outcome texts, file paths, commands, and denial details,
+        /// keeping numeric metrics (for attaching to bug reports)
+        #[arg(long)]
+        anonymize: bool,
+    },
+    /// Merge other machines' logs into this one, interleaved by timestamp
+    Merge {
+        /// Log files to merge in (e.g. a teammate's .flow/log.jsonl)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Write the merged log here instead of replacing the local log
+        /// (the local log is backed up to log.jsonl.bak when replaced)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Rewrite the log without malformed lines (the original is kept as
+    /// log.jsonl.corrupt)
+    Repair,
+    /// Condense old log entries into digests for `context = "compact"` now
+    /// (a run also compacts before each cycle that uses it)
+    Compact,
 }
 
-/// Print a startup banner when running multiple iterations.
-fn print_run_banner(max_iterations: u32, fixed_cycle: Option<&str>, use_selector: bool) {
-    if max_iterations <= 1 {
-        return;
-    }
-    if use_selector {
-        eprintln!(
-            "Starting autonomous run: up to {max_iterations} iterations with AI cycle selection"
-        );
-    } else {
-        eprintln!(
-            "Starting multi-iteration run: up to {max_iterations} iterations of '{}'",
-            fixed_cycle.unwrap_or("?")
-        );
-    }
+/// Output format for `flow logs export`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    /// Comma-separated values with a header row
+    Csv,
+    /// JSON Lines, the format of .flow/log.jsonl
+    Jsonl,
 }
 
-/// Determine which cycle to run for this iteration.
-///
-/// Returns the fixed cycle name if `--cycle` was specified, or uses AI selection.
-async fn resolve_cycle_name(
-    config: &FlowConfig,
-    logger: &JsonlLogger,
-    fixed_cycle: Option<&str>,
-    todo_path: &std::path::PathBuf,
-) -> Result<String> {
-    if let Some(name) = fixed_cycle {
-        return Ok(name.to_string());
-    }
-    let log_entries = logger
-        .read_all()
-        .context("Failed to read log for selector")?;
-    let todo_content = std::fs::read_to_string(todo_path).unwrap_or_default();
-    eprintln!("{} Selecting next cycle...", ">>>".bold().yellow());
-    let selection = select_cycle(config, &log_entries, &todo_content)
-        .await
-        .context("Cycle selection failed")?;
-    eprintln!(
-        "{} Selected '{}': {}",
-        ">>>".bold().green(),
-        selection.cycle,
-        selection.reason
-    );
-    Ok(selection.cycle)
+/// Output format for `flow doctor`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DoctorFormat {
+    /// Human-readable report on stderr
+    Text,
+    /// Structured JSON report on stdout (for CI pipelines)
+    Json,
 }
 
-/// Update progress state after a cycle completes.
-fn update_progress_after_cycle(
-    progress: &mut RunProgress,
-    cycle_name: &str,
-    result: &flow::CycleResult,
-) {
-    *progress
-        .cycles_executed
-        .entry(cycle_name.to_string())
-        .or_insert(0) += 1;
-    progress.total_duration_secs += result.duration_secs;
-    progress.total_cost_usd += result.total_cost_usd.unwrap_or(0.0);
-    progress.last_outcome.clone_from(&result.result_text);
+/// Severity threshold for a non-zero `flow doctor` exit code
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FailOn {
+    /// Fail only on errors (default)
+    Error,
+    /// Fail on warnings or errors
+    Warning,
+    /// Fail on any finding
+    Info,
+    /// Never fail, regardless of findings
+    Never,
 }
 
-/// Execute a cycle with rich display and log the result. Returns the `CycleResult`.
-async fn execute_and_log(
-    executor: &CycleExecutor,
-    logger: &JsonlLogger,
-    cycle_name: &str,
-    iteration: &mut u32,
-    circuit_breaker_threshold: u32,
-    iteration_context: Option<(u32, u32)>,
-    template_vars: &std::collections::HashMap<String, String>,
-) -> Result<flow::CycleResult> {
-    // Read log entries for context injection
-    let log_entries = logger.read_all().unwrap_or_default();
-
-    let result = executor
-        .execute_with_display(
-            cycle_name,
-            circuit_breaker_threshold,
-            &log_entries,
-            iteration_context,
-            template_vars,
-        )
-        .await
-        .with_context(|| format!("Failed to execute cycle '{cycle_name}'"))?;
-
-    let outcome = build_outcome(&result, *iteration);
-    logger
-        .append(&outcome)
-        .context("Failed to write to JSONL log")?;
-
-    // Print actionable permission fix suggestions
-    if let Some(count) = result.permission_denial_count {
-        if count > 0 {
-            eprintln!("Tip: Add permission strings to cycles.toml to avoid denials.");
-            eprintln!("     e.g. {}", suggest_permission_fix("Edit"));
+impl FailOn {
+    /// The lowest severity that triggers a failure, or `None` to never fail.
+    const fn threshold(self) -> Option<Severity> {
+        match self {
+            Self::Error => Some(Severity::Error),
+            Self::Warning => Some(Severity::Warning),
+            Self::Info => Some(Severity::Info),
+            Self::Never => None,
         }
     }
-
-    *iteration += 1;
-
-    Ok(result)
-}
-
-/// Apply post-cycle checks: record outcome, check denial gate, health check.
-///
-/// Exits the process if any gate fires. Returns normally if the run should continue.
-///
-/// Individual cycle failures are recorded but do not immediately stop the run.
-/// Instead, the consecutive-failure health check (`max_consecutive_failures`)
-/// determines when to stop — e.g., default threshold 3 means 3 failures in a row.
-fn apply_cycle_gates(
-    result: &flow::CycleResult,
-    cycle_name: &str,
-    run_history: &mut Vec<RunOutcome>,
-    max_denials: u32,
-    max_consecutive_failures: u32,
-    iteration: u32,
-) {
-    run_history.push(RunOutcome {
-        success: result.success,
-    });
-
-    if !result.success {
-        eprintln!(
-            "Cycle '{cycle_name}' failed in iteration {iteration} (exit code {}).",
-            format_exit_code(result.exit_code)
-        );
-    }
-
-    check_denial_gate(
-        result.permission_denial_count.unwrap_or(0),
-        max_denials,
-        cycle_name,
-    );
-
-    if let Some(reason) = check_run_health(run_history, max_consecutive_failures) {
-        eprintln!("{reason}");
-        std::process::exit(1);
-    }
 }
 
 /// Validate CLI arguments and load configuration.
-///
-/// Returns `(config, fixed_cycle, use_selector)`.
-fn validate_cli(cli: &Cli) -> Result<(FlowConfig, Option<String>, bool)> {
-    let config = FlowConfig::from_path(&cli.config)
-        .with_context(|| format!("Failed to load config from '{}'", cli.config.display()))?;
+fn validate_cli(cli: &Cli) -> Result<FlowConfig> {
+    let mut config = cli.load_config()?;
 
     let fixed_cycle = cli.cycle.clone();
-    let use_selector = fixed_cycle.is_none();
+    let use_selector = fixed_cycle.is_none() && cli.cycles.is_empty() && cli.replay.is_none();
 
-    if let Some(ref name) = fixed_cycle {
+    for name in fixed_cycle.iter().chain(&cli.cycles) {
         config.get_cycle(name).with_context(|| {
             format!(
                 "Unknown cycle '{}'. Available cycles: {}",
@@ -308,283 +435,472 @@ fn validate_cli(cli: &Cli) -> Result<(FlowConfig, Option<String>, bool)> {
         })?;
     }
 
+    if let (Some(step), Some(name)) = (&cli.from_step, &fixed_cycle) {
+        let cycle = config.get_cycle(name).context("cycle validated above")?;
+        if !cycle.is_multi_step() {
+            anyhow::bail!("--from-step requires a multi-step cycle, but '{name}' has no steps");
+        }
+        if !cycle.steps.iter().any(|s| &s.name == step) {
+            let steps: Vec<&str> = cycle.steps.iter().map(|s| s.name.as_str()).collect();
+            anyhow::bail!(
+                "Unknown step '{step}' in cycle '{name}'. Available steps: {}",
+                steps.join(", ")
+            );
+        }
+    }
+
     if use_selector && cli.max_iterations <= 1 {
         anyhow::bail!(
-            "Missing --cycle argument. Usage: flow --cycle <name>, flow --max-iterations N (AI-selected), or flow doctor"
+            "Missing --cycle argument. Usage: flow --cycle <name>, flow --max-iterations N (AI-selected), or flow doctor (run in a terminal to pick a cycle from a list)"
         );
     }
 
-    Ok((config, fixed_cycle, use_selector))
+    apply_skip_cycles(&mut config, &cli.skip_cycles)?;
+    if use_selector && !config.cycles.iter().any(|c| c.selectable) {
+        anyhow::bail!("No selectable cycles left (check `selectable = false` and --skip-cycle)");
+    }
+
+    Ok(config)
+}
+
+/// Ask which cycle to run when none was given and a single iteration would
+/// otherwise be a usage error. Only in a terminal: scripts still get the error.
+fn pick_missing_cycle(cli: &mut Cli) -> Result<()> {
+    let needs_cycle = cli.cycle.is_none()
+        && cli.cycles.is_empty()
+        && cli.replay.is_none()
+        && cli.max_iterations <= 1;
+    if !needs_cycle || !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Ok(());
+    }
+    let config = cli.load_config()?;
+    match pick_cycle(&config, std::io::stdin().lock(), std::io::stderr())? {
+        Some(name) => cli.cycle = Some(name),
+        None => anyhow::bail!("No cycle selected"),
+    }
+    Ok(())
 }
 
-/// Install a Ctrl+C signal handler that sets a shared shutdown flag.
-fn install_signal_handler() -> Arc<AtomicBool> {
-    let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_for_signal = shutdown.clone();
+/// Mark each `--skip-cycle` name as non-selectable for this run.
+fn apply_skip_cycles(config: &mut FlowConfig, skip_cycles: &[String]) -> Result<()> {
+    for name in skip_cycles {
+        let Some(cycle) = config.cycles.iter_mut().find(|c| &c.name == name) else {
+            anyhow::bail!(
+                "Unknown cycle '{name}' in --skip-cycle. Available cycles: {}",
+                available_cycle_names(config)
+            );
+        };
+        cycle.selectable = false;
+    }
+    Ok(())
+}
+
+/// Install a Ctrl+C signal handler that cancels the returned token.
+fn install_signal_handler() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let cancel_on_signal = cancel.clone();
     tokio::spawn(async move {
         if tokio::signal::ctrl_c().await.is_ok() {
-            shutdown_for_signal.store(true, Ordering::Relaxed);
+            cancel_on_signal.cancel();
         }
     });
-    shutdown
+    cancel
 }
 
-/// Auto-trigger dependent cycles after a primary cycle completes.
-#[allow(clippy::too_many_arguments)]
-async fn run_dependent_cycles(
-    config: &FlowConfig,
-    executor: &CycleExecutor,
-    logger: &JsonlLogger,
-    progress_writer: &ProgressWriter,
-    progress: &mut RunProgress,
-    iteration: &mut u32,
-    run_history: &mut Vec<RunOutcome>,
-    completed_cycle: &str,
-    circuit_breaker: u32,
-    max_denials: u32,
-    max_consecutive_failures: u32,
-    shutdown: &AtomicBool,
-    base_template_vars: &std::collections::HashMap<String, String>,
-) -> Result<()> {
-    let log_entries = logger
-        .read_all()
-        .context("Failed to read log for frequency check")?;
-    let triggered = find_triggered_cycles(config, completed_cycle, &log_entries);
-    for dep_cycle in triggered {
-        if shutdown.load(Ordering::Relaxed) {
-            break;
-        }
-        eprintln!("Auto-triggering dependent cycle: {dep_cycle}");
-
-        progress.current_cycle = dep_cycle.to_string();
-        let _ = progress_writer.write(progress);
-
-        // Build template vars for this dependent cycle
-        let mut dep_vars = base_template_vars.clone();
-        dep_vars.insert("cycle_name".to_string(), dep_cycle.to_string());
-        dep_vars.insert("step_name".to_string(), String::new());
-        dep_vars.insert(
-            "iteration".to_string(),
-            progress.current_iteration.to_string(),
-        );
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+    // Enable ANSI on Windows (or fall back to plain output) before printing anything
+    terminal_mode();
+    cli.user = UserConfig::load_default()?;
+    if let Some(ref claude_bin) = cli.user.claude_bin {
+        set_default_claude_bin(claude_bin);
+    }
 
-        let iter_ctx = Some((progress.current_iteration, progress.max_iterations));
-        let dep_result = execute_and_log(
-            executor,
-            logger,
-            dep_cycle,
-            iteration,
-            circuit_breaker,
-            iter_ctx,
-            &dep_vars,
-        )
-        .await?;
-
-        update_progress_after_cycle(progress, dep_cycle, &dep_result);
-        let _ = progress_writer.write(progress);
-
-        apply_cycle_gates(
-            &dep_result,
-            dep_cycle,
-            run_history,
-            max_denials,
-            max_consecutive_failures,
-            *iteration - 1,
-        );
+    if let Some(ref command) = cli.command {
+        return run_subcommand(&cli, command).await;
+    }
+
+    pick_missing_cycle(&mut cli)?;
+    let config = validate_cli(&cli)?;
+    let runner = FlowRunner::new(config)
+        .with_log_dir(cli.log_dir())
+        .with_todo_path(&cli.todo)
+        .with_tasks_path(&cli.tasks)
+        .with_max_iterations(cli.max_iterations)
+        .with_playlist(cli.cycles.clone())
+        .with_start_step(cli.from_step.clone())
+        .with_max_duration(cli.max_duration)
+        .with_limit_overrides(cli.max_turns, cli.max_cost_usd)
+        .with_verbose(cli.verbose)
+        .with_debug_stream(cli.debug_stream)
+        .with_emit_events(cli.emit_events)
+        .with_force(cli.force)
+        .with_record(cli.record)
+        .with_cancellation(install_signal_handler());
+    let runner = match cli.cycle {
+        Some(ref cycle) => runner.with_cycle(cycle),
+        None => runner,
+    };
+    let runner = match cli.replay {
+        Some(ref path) => runner.with_replay(Recording::open(path)?),
+        None => runner,
+    };
+
+    let report = runner.run().await?;
+    if report.gate_tripped {
+        std::process::exit(1);
     }
     Ok(())
 }
 
-/// Print a periodic run summary if the completed iteration is at the configured interval.
-fn print_periodic_summary(
-    progress: &RunProgress,
-    run_history: &[RunOutcome],
-    max_iterations: u32,
-    summary_interval: u32,
-) {
-    if !should_print_summary(progress.current_iteration, summary_interval) {
-        return;
-    }
-    #[allow(clippy::cast_possible_truncation)] // bounded by max_iterations (u32)
-    let successes = run_history.iter().filter(|o| o.success).count() as u32;
-    #[allow(clippy::cast_possible_truncation)]
-    let failures = run_history.iter().filter(|o| !o.success).count() as u32;
-    let summary = flow::cli::render_run_summary(
-        progress.current_iteration,
-        max_iterations,
-        progress.total_cost_usd,
-        &progress.cycles_executed,
-        successes,
-        failures,
-        progress.total_duration_secs,
-    );
-    eprintln!("\n{summary}");
+/// Dispatch a subcommand (everything other than the default run loop).
+async fn run_subcommand(cli: &Cli, command: &Command) -> Result<()> {
+    // Commands that read the state directory must not misparse a newer format
+    if matches!(
+        command,
+        Command::Doctor { .. }
+            | Command::Logs { .. }
+            | Command::Sessions { .. }
+            | Command::Gc { .. }
+            | Command::WatchProgress { .. }
+            | Command::Serve { .. }
+            | Command::Explain { .. }
+            | Command::Permissions { .. }
+    ) {
+        ensure_compatible(cli.log_dir())?;
+    }
+    match command {
+        Command::Doctor {
+            repair,
+            format,
+            fail_on,
+        } => run_doctor(cli, *repair, *format, *fail_on),
+        Command::Config { command } => run_config(cli, command),
+        Command::Init => run_init(),
+        Command::Gc { dry_run } => run_gc(cli, *dry_run),
+        Command::Migrate { dry_run } => run_migrate(cli, *dry_run),
+        Command::Logs { command } => run_logs(cli, command).await,
+        Command::Sessions { command } => run_sessions(cli, command),
+        Command::Stats { window } => run_stats(cli, *window),
+        Command::Permissions { command } => run_permissions(cli, command),
+        Command::Serve { api, listen } => run_serve(cli, *api, listen).await,
+        Command::Explain { cycle } => run_explain(cli, cycle),
+        Command::WatchProgress { interval, once } => {
+            run_watch_progress(cli, *interval, *once).await
+        }
+        Command::MockClaude { fixtures, args } => run_mock_claude(fixtures, args),
+    }
 }
 
-/// Check if a periodic run summary should be printed at this iteration.
-///
-/// Returns `true` when `interval > 0` and `completed_iteration` is a multiple of `interval`.
-const fn should_print_summary(completed_iteration: u32, interval: u32) -> bool {
-    interval > 0 && completed_iteration > 0 && completed_iteration.is_multiple_of(interval)
+/// Run a `flow config` subcommand.
+fn run_config(cli: &Cli, command: &ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Show => {
+            let content = std::fs::read_to_string(&cli.config)
+                .with_context(|| format!("Failed to read config file: {}", cli.config.display()))?;
+            let mut config = cli.load_config()?;
+            apply_skip_cycles(&mut config, &cli.skip_cycles)?;
+            let origins =
+                value_origins(&content, &cli.user, cli.profile.as_deref(), &cli.overrides)?;
+            print!("{}", render_effective(&config, &origins, &cli.skip_cycles)?);
+        }
+    }
+    Ok(())
 }
 
-/// Write final progress state and print run summary.
-fn finalize_run(
-    shutdown: &AtomicBool,
-    progress_writer: &ProgressWriter,
-    progress: &mut RunProgress,
-    max_iterations: u32,
-    use_selector: bool,
-    fixed_cycle: Option<&str>,
-) {
-    if shutdown.load(Ordering::Relaxed) {
-        progress.current_status = RunStatus::Stopped;
-        let _ = progress_writer.write(progress);
-        let _ = progress_writer.delete();
-        eprintln!("\nRun interrupted by Ctrl+C");
+/// Print what `cycle` would hand to Claude Code, resolved the way a
+/// one-iteration run would resolve it now: the current log, the next queued
+/// task, persisted sessions, and the `--max-turns`/`--max-cost-usd` overrides.
+fn run_explain(cli: &Cli, cycle_name: &str) -> Result<()> {
+    let config = cli.load_config()?;
+    let cycle = config.get_cycle(cycle_name).with_context(|| {
+        format!(
+            "Unknown cycle '{cycle_name}'. Available cycles: {}",
+            available_cycle_names(&config)
+        )
+    })?;
+    let log_dir = cli.log_dir();
+    // Don't create the log directory just to read it
+    let log_entries = if cycle.uses_history() && log_dir.exists() {
+        let logger = JsonlLogger::new(log_dir)?;
+        match cycle.history_window() {
+            Some(window) => logger.read_last(window)?,
+            None => logger.read_all()?,
+        }
     } else {
-        progress.current_status = RunStatus::Completed;
-        let _ = progress_writer.write(progress);
-        let _ = progress_writer.delete();
+        Vec::new()
+    };
+    let mut vars = build_template_vars(
+        &config.global.vars,
+        &std::env::current_dir().unwrap_or_default(),
+        &cli.todo,
+        cycle_name,
+        "",
+        1,
+        cli.max_iterations,
+    );
+    insert_task_vars(&mut vars, TaskQueue::new(log_dir).peek()?.as_ref());
+    vars.insert(SELECTED_TASK_VAR.to_string(), String::new());
+    vars.insert(SELECTED_TASK_ID_VAR.to_string(), String::new());
+    let executor = CycleExecutor::new(config.clone(), CancellationToken::new())
+        .with_limit_overrides(cli.max_turns, cli.max_cost_usd)
+        .with_sessions_path(Some(log_dir.join("sessions.json")))
+        .with_digests_path(Some(log_dir.join(DIGESTS_FILE)));
+    let plan = executor.explain(cycle_name, &log_entries, &vars)?;
+    print!("{}", render_explanation(cycle_name, &plan));
+    Ok(())
+}
 
-        if max_iterations > 1 {
-            if use_selector {
-                eprintln!("\nCompleted {max_iterations} autonomous iteration(s)");
-            } else {
-                eprintln!(
-                    "\nCompleted {max_iterations} iteration(s) of '{}'",
-                    fixed_cycle.unwrap_or("?")
-                );
+/// Run `flow serve --api` until Ctrl+C. Runs it starts inherit `--config`,
+/// `--log-dir`, `--profile`, `--set`, and `--skip-cycle`.
+async fn run_serve(cli: &Cli, api: bool, listen: &str) -> Result<()> {
+    if !api {
+        anyhow::bail!("Nothing to serve: pass --api");
+    }
+    let token = std::env::var(API_TOKEN_ENV)
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+        .with_context(|| format!("Set {API_TOKEN_ENV} to the bearer token clients must send"))?;
+    // Fail now rather than on the first request
+    cli.load_config()?;
+
+    let mut run_args = Vec::new();
+    if let Some(ref profile) = cli.profile {
+        run_args.extend(["--profile".to_string(), profile.clone()]);
+    }
+    for value in &cli.overrides {
+        run_args.extend(["--set".to_string(), value.clone()]);
+    }
+    for name in &cli.skip_cycles {
+        run_args.extend(["--skip-cycle".to_string(), name.clone()]);
+    }
+    let server = ApiServer::new(&token, &cli.config, cli.log_dir())?.with_run_args(run_args);
+
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {listen}"))?;
+    eprintln!("Serving the Flow API on http://{}", listener.local_addr()?);
+    server.serve(listener, install_signal_handler()).await
+}
+
+/// Run `flow watch-progress`: print the run's progress whenever it changes
+/// and each cycle as it is logged, until the run ends (or after one check
+/// with `once`).
+async fn run_watch_progress(cli: &Cli, interval_secs: u64, once: bool) -> Result<()> {
+    if cli.config.exists() {
+        // Only for its [display] settings
+        cli.load_config()?;
+    }
+    let log_dir = cli.log_dir();
+    let mut watcher = RunWatcher::new(log_dir);
+    let cancel = install_signal_handler();
+    loop {
+        for update in watcher.poll()? {
+            match update {
+                WatchUpdate::Waiting => {
+                    eprintln!("Waiting for a run in {}...", log_dir.display());
+                }
+                WatchUpdate::Progress(progress) => {
+                    eprintln!("{}", render_watch_progress(&progress, chrono::Local::now()));
+                }
+                WatchUpdate::Outcome(outcome) => eprintln!("{}", render_watch_outcome(&outcome)),
+                WatchUpdate::Finished => {
+                    eprintln!("Run finished.");
+                    return Ok(());
+                }
+                WatchUpdate::Abandoned => anyhow::bail!(
+                    "{} shows a run in progress, but no live flow process holds the run lock; \
+                     the run likely crashed",
+                    log_dir.join("progress.json").display()
+                ),
             }
         }
+        if once {
+            return Ok(());
+        }
+        tokio::select! {
+            () = cancel.cancelled() => return Ok(()),
+            () = tokio::time::sleep(std::time::Duration::from_secs(interval_secs)) => {}
+        }
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
-
-    match cli.command {
-        Some(Command::Doctor { repair }) => return run_doctor(&cli, repair),
-        Some(Command::Init) => return run_init(),
-        None => {}
+/// Run `flow mock-claude`: print the fixture response for the `-p` prompt.
+fn run_mock_claude(fixtures: &std::path::Path, args: &[String]) -> Result<()> {
+    let fixtures = MockFixtures::load(fixtures)?;
+    let prompt = prompt_arg(args).context("mock-claude expects a -p <prompt> argument")?;
+    let (output, exit_code) = fixtures.respond(prompt)?;
+    print!("{output}");
+    if exit_code != 0 {
+        std::process::exit(exit_code);
     }
+    Ok(())
+}
 
-    let (config, fixed_cycle, use_selector) = validate_cli(&cli)?;
-
-    let shutdown = install_signal_handler();
-    let circuit_breaker = config.global.circuit_breaker_repeated;
-    let max_denials = config.global.max_permission_denials;
-    let max_consecutive_failures = config.global.max_consecutive_failures;
-    let executor = CycleExecutor::new(config.clone(), shutdown.clone());
-    let logger = JsonlLogger::new(&cli.log_dir).context("Failed to initialize JSONL logger")?;
-    let progress_writer =
-        ProgressWriter::new(&cli.log_dir).context("Failed to initialize progress writer")?;
-    let mut iteration: u32 = 1;
-    let max_iterations = cli.max_iterations;
-    let mut run_history: Vec<RunOutcome> = Vec::new();
-    let mut progress = RunProgress::new(max_iterations);
-
-    print_run_banner(max_iterations, fixed_cycle.as_deref(), use_selector);
+/// Run a `flow permissions` subcommand.
+fn run_permissions(cli: &Cli, command: &PermissionsCommand) -> Result<()> {
+    match command {
+        PermissionsCommand::Diff { min_denials } => {
+            let config = cli.load_config()?;
+            let log_dir = cli.log_dir();
+            let entries = if log_dir.exists() {
+                JsonlLogger::new(log_dir)?.read_all()?
+            } else {
+                Vec::new()
+            };
+            let project_dir = std::env::current_dir().unwrap_or_default();
+            let report = permission_drift(&config, &entries, &project_dir, *min_denials);
+            eprintln!("{}", render_permission_drift(&report));
+        }
+    }
+    Ok(())
+}
 
-    let project_dir = std::env::current_dir().unwrap_or_default();
+/// Run `flow stats`.
+fn run_stats(cli: &Cli, window: usize) -> Result<()> {
+    let log_dir = cli.log_dir();
+    let entries = if log_dir.exists() {
+        JsonlLogger::new(log_dir)?.read_all()?
+    } else {
+        Vec::new()
+    };
+    eprintln!(
+        "{}",
+        render_cycle_trends(&cycle_trends(&entries, window), window)
+    );
+    Ok(())
+}
 
-    // Main iteration loop
-    loop {
-        if iteration > max_iterations || shutdown.load(Ordering::Relaxed) {
-            break;
+/// Run a `flow sessions` subcommand.
+fn run_sessions(cli: &Cli, command: &SessionsCommand) -> Result<()> {
+    let path = cli.log_dir().join("sessions.json");
+    let mut sessions = load_sessions(&path)?;
+    match command {
+        SessionsCommand::List => eprintln!("{}", render_sessions(&sessions)),
+        SessionsCommand::Prune { older_than } => {
+            let cutoff = chrono::Utc::now()
+                - chrono::Duration::seconds(i64::try_from(*older_than).unwrap_or(i64::MAX));
+            let removed = prune_sessions(&mut sessions, cutoff);
+            if removed.is_empty() {
+                eprintln!("No stale sessions.");
+            } else {
+                save_sessions(&path, &sessions)?;
+                eprintln!(
+                    "Pruned {} session(s): {}",
+                    removed.len(),
+                    removed.join(", ")
+                );
+            }
         }
-
-        if max_iterations > 1 {
-            eprintln!(
-                "\n{} Iteration {iteration}/{max_iterations}",
-                ">>>".bold().cyan()
-            );
+        SessionsCommand::Delete { tag } => {
+            if sessions.remove(tag).is_none() {
+                anyhow::bail!("No persisted session tagged '{tag}'");
+            }
+            save_sessions(&path, &sessions)?;
+            eprintln!("Deleted session '{tag}'");
         }
+    }
+    Ok(())
+}
 
-        let cycle_name =
-            resolve_cycle_name(&config, &logger, fixed_cycle.as_deref(), &cli.todo).await?;
-
-        // Update progress before execution
-        progress.current_iteration = iteration;
-        progress.current_cycle = cycle_name.clone();
-        let _ = progress_writer.write(&progress);
-
-        // Build template variables for this cycle
-        let template_vars = build_template_vars(
-            &config.global.vars,
-            &project_dir,
-            &cli.todo,
-            &cycle_name,
-            "",
-            iteration,
-            max_iterations,
-        );
-
-        // Execute the selected cycle
-        let result = execute_and_log(
-            &executor,
-            &logger,
-            &cycle_name,
-            &mut iteration,
-            circuit_breaker,
-            Some((progress.current_iteration, max_iterations)),
-            &template_vars,
-        )
-        .await?;
-
-        // Update progress after execution
-        update_progress_after_cycle(&mut progress, &cycle_name, &result);
-        let _ = progress_writer.write(&progress);
-
-        apply_cycle_gates(
-            &result,
-            &cycle_name,
-            &mut run_history,
-            max_denials,
-            max_consecutive_failures,
-            iteration - 1,
-        );
-
-        // Check shutdown before auto-triggering dependent cycles
-        if shutdown.load(Ordering::Relaxed) {
-            break;
+/// Run a `flow logs` subcommand.
+async fn run_logs(cli: &Cli, command: &LogsCommand) -> Result<()> {
+    let logger = JsonlLogger::new(cli.log_dir()).context("Failed to initialize JSONL logger")?;
+    match command {
+        LogsCommand::Export {
+            format,
+            output,
+            anonymize: scrub,
+        } => {
+            let mut entries = logger.read_all().context("Failed to read log")?;
+            if *scrub {
+                entries = anonymize(&entries);
+            }
+            let rendered = match format {
+                ExportFormat::Csv => export_csv(&entries),
+                ExportFormat::Jsonl => {
+                    export_jsonl(&entries).context("Failed to serialize log entries")?
+                }
+            };
+            if let Some(path) = output {
+                std::fs::write(path, rendered)
+                    .with_context(|| format!("Failed to write '{}'", path.display()))?;
+                eprintln!(
+                    "Exported {} log entries to {}",
+                    entries.len(),
+                    path.display()
+                );
+            } else {
+                print!("{rendered}");
+            }
         }
+        LogsCommand::Merge { files, output } => merge_log_files(&logger, files, output.as_deref())?,
+        LogsCommand::Repair => {
+            let removed = logger.repair()?;
+            if removed.is_empty() {
+                eprintln!("No malformed lines in {}.", logger.log_path().display());
+            } else {
+                for line in &removed {
+                    eprintln!("  line {}: {}", line.line_number, line.error);
+                }
+                eprintln!(
+                    "Removed {} malformed line(s) from {} (original kept as {})",
+                    removed.len(),
+                    logger.log_path().display(),
+                    logger.log_path().with_extension("jsonl.corrupt").display()
+                );
+            }
+        }
+        LogsCommand::Compact => {
+            let settings = if cli.config.exists() {
+                cli.load_config()?.compaction.unwrap_or_default()
+            } else {
+                CompactionConfig::default()
+            };
+            let digests = compact(&logger, &settings).await?;
+            if digests.is_empty() {
+                eprintln!(
+                    "Nothing to compact: fewer than {} entries beyond the newest {}.",
+                    settings.block_size, settings.keep_recent
+                );
+            }
+            for digest in &digests {
+                eprintln!(
+                    "Compacted iterations {}–{} into a digest",
+                    digest.first_iteration, digest.last_iteration
+                );
+            }
+        }
+    }
+    Ok(())
+}
 
-        run_dependent_cycles(
-            &config,
-            &executor,
-            &logger,
-            &progress_writer,
-            &mut progress,
-            &mut iteration,
-            &mut run_history,
-            &result.cycle_name,
-            circuit_breaker,
-            max_denials,
-            max_consecutive_failures,
-            &shutdown,
-            &template_vars,
-        )
-        .await?;
-
-        print_periodic_summary(
-            &progress,
-            &run_history,
-            max_iterations,
-            config.global.summary_interval,
-        );
+/// Merge `files` into the local log, writing to `output` or replacing the local log.
+fn merge_log_files(
+    logger: &JsonlLogger,
+    files: &[PathBuf],
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let mut sources = vec![("local".to_string(), logger.read_all()?)];
+    for file in files {
+        sources.push((file.display().to_string(), JsonlLogger::read_path(file)?));
     }
+    let merged = merge_logs(sources);
 
-    finalize_run(
-        &shutdown,
-        &progress_writer,
-        &mut progress,
-        max_iterations,
-        use_selector,
-        fixed_cycle.as_deref(),
+    let target = output.unwrap_or_else(|| logger.log_path());
+    if output.is_none() && logger.log_path().exists() {
+        let backup = logger.log_path().with_extension("jsonl.bak");
+        std::fs::copy(logger.log_path(), &backup)
+            .with_context(|| format!("Failed to back up log to '{}'", backup.display()))?;
+    }
+    JsonlLogger::write_path(target, &merged)?;
+    eprintln!(
+        "Merged {} log file(s) into {} ({} entries)",
+        files.len(),
+        target.display(),
+        merged.len()
     );
-
     Ok(())
 }
 
@@ -602,20 +918,87 @@ fn run_init() -> Result<()> {
     Ok(())
 }
 
+/// Run `flow gc`. Retention comes from `[gc]`, or the defaults when there is
+/// no config file.
+fn run_gc(cli: &Cli, dry_run: bool) -> Result<()> {
+    let settings = if cli.config.exists() {
+        cli.load_config()?.gc.unwrap_or_default()
+    } else {
+        GcConfig::default()
+    };
+    let report = collect(
+        cli.log_dir(),
+        &settings,
+        std::time::SystemTime::now(),
+        dry_run,
+    )?;
+    eprintln!("{}", render_gc_report(&report, dry_run));
+    Ok(())
+}
+
+/// Run `flow migrate`: upgrade the state directory to [`STATE_VERSION`].
+fn run_migrate(cli: &Cli, dry_run: bool) -> Result<()> {
+    let log_dir = cli.log_dir();
+    ensure_compatible(log_dir)?;
+    let Some(version) = read_version(log_dir)? else {
+        eprintln!(
+            "{} has no state yet; nothing to migrate.",
+            log_dir.display()
+        );
+        return Ok(());
+    };
+    let migrations = pending(version);
+    if migrations.is_empty() {
+        eprintln!(
+            "{} is up to date (state version {STATE_VERSION}).",
+            log_dir.display()
+        );
+        return Ok(());
+    }
+    if dry_run {
+        for migration in migrations {
+            eprintln!(
+                "Would upgrade to version {}: {}",
+                migration.from + 1,
+                migration.description
+            );
+        }
+        return Ok(());
+    }
+    if let Some(holder) = live_holder(log_dir) {
+        anyhow::bail!(
+            "A flow run (PID {}) is in progress; run `flow migrate` after it finishes",
+            holder.pid
+        );
+    }
+    for migration in migrate(log_dir)? {
+        eprintln!(
+            "Upgraded to version {}: {}",
+            migration.from + 1,
+            migration.description
+        );
+    }
+    Ok(())
+}
+
 /// Run the `flow doctor` diagnostic command.
-fn run_doctor(cli: &Cli, repair: bool) -> Result<()> {
-    let config = FlowConfig::from_path(&cli.config)
-        .with_context(|| format!("Failed to load config from '{}'", cli.config.display()))?;
+fn run_doctor(cli: &Cli, repair: bool, format: DoctorFormat, fail_on: FailOn) -> Result<()> {
+    let config = cli.load_config()?;
 
-    let logger = JsonlLogger::new(&cli.log_dir).context("Failed to initialize JSONL logger")?;
+    let logger = JsonlLogger::new(cli.log_dir()).context("Failed to initialize JSONL logger")?;
     let log_entries = logger.read_all().unwrap_or_default();
 
     let report = diagnose(&config, &log_entries);
-    let output = render_diagnostic_report(&report);
-    eprintln!("{output}");
+    match format {
+        DoctorFormat::Text => eprintln!("{}", render_diagnostic_report(&report)),
+        DoctorFormat::Json => println!("{}", render_diagnostic_json(&report)),
+    }
 
     if repair {
-        let actions = flow::doctor::repair(&cli.config, &config, &log_entries)
+        // Repairs rewrite cycles.toml, so they work from that file alone
+        let file_config = FlowConfig::from_path(&cli.config)
+            .with_context(|| format!("Failed to load config from '{}'", cli.config.display()))?;
+        let actions = flow::doctor::repair(&cli.config, &file_config, &log_entries)
             .context("Failed to apply repairs")?;
         if actions.is_empty() {
             eprintln!("No auto-fixable issues found.");
@@ -627,7 +1010,10 @@ fn run_doctor(cli: &Cli, repair: bool) -> Result<()> {
         }
     }
 
-    if report.error_count() > 0 && !repair {
+    let failed = fail_on
+        .threshold()
+        .is_some_and(|severity| report.has_findings_at_or_above(&severity));
+    if failed && !repair {
         std::process::exit(1);
     }
 
@@ -647,176 +1033,174 @@ fn available_cycle_names(config: &FlowConfig) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use flow::CycleResult;
 
     #[test]
-    fn test_build_outcome_success() {
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: true,
-            exit_code: Some(0),
-            stderr: String::new(),
-            duration_secs: 120,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 0,
-        };
+    fn test_available_cycle_names() {
+        let config = FlowConfig::parse(
+            r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "Garden"
+"#,
+        )
+        .unwrap();
 
-        let outcome = build_outcome(&result, 1);
-        assert_eq!(outcome.cycle, "coding");
-        assert_eq!(outcome.iteration, 1);
-        assert_eq!(outcome.outcome, "Completed successfully");
-        assert_eq!(outcome.duration_secs, 120);
-        assert!(outcome.files_changed.is_empty());
+        let names = available_cycle_names(&config);
+        assert_eq!(names, "coding, gardening");
     }
 
     #[test]
-    fn test_build_outcome_failure() {
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: false,
-            exit_code: Some(1),
-            stderr: "error".to_string(),
-            duration_secs: 30,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 0,
-        };
+    fn test_cli_parses_max_iterations() {
+        let cli =
+            Cli::try_parse_from(["flow", "--cycle", "coding", "--max-iterations", "5"]).unwrap();
+        assert_eq!(cli.max_iterations, 5);
+        assert_eq!(cli.cycle.as_deref(), Some("coding"));
+    }
 
-        let outcome = build_outcome(&result, 3);
-        assert_eq!(outcome.outcome, "Failed with exit code 1");
-        assert_eq!(outcome.iteration, 3);
+    #[test]
+    fn test_cli_parses_verbose_flag() {
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding", "-v"]).unwrap();
+        assert!(cli.verbose);
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
+        assert!(!cli.verbose);
     }
 
     #[test]
-    fn test_build_outcome_killed_by_signal() {
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: false,
-            exit_code: None,
-            stderr: String::new(),
-            duration_secs: 5,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 0,
-        };
+    fn test_cli_parses_emit_events_flag() {
+        let cli = Cli::try_parse_from(["flow", "--emit-events"]).unwrap();
+        assert!(cli.emit_events);
+        let cli = Cli::try_parse_from(["flow"]).unwrap();
+        assert!(!cli.emit_events);
+    }
 
-        let outcome = build_outcome(&result, 1);
-        assert_eq!(outcome.outcome, "Failed with exit code unknown");
+    #[test]
+    fn test_cli_parses_debug_stream_flag() {
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding", "--debug-stream"]).unwrap();
+        assert!(cli.debug_stream);
     }
 
     #[test]
-    fn test_build_outcome_uses_result_text_when_present() {
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: true,
-            exit_code: Some(0),
-            stderr: String::new(),
-            duration_secs: 120,
-            result_text: Some("Implemented feature X with 5 tests".to_string()),
-            num_turns: Some(53),
-            total_cost_usd: Some(2.15),
-            permission_denial_count: Some(3),
-            permission_denials: Some(vec![
-                "Edit".to_string(),
-                "Bash".to_string(),
-                "Edit".to_string(),
-            ]),
-            files_changed: vec!["src/main.rs".to_string()],
-            tests_passed: 0,
-        };
+    fn test_cli_parses_max_duration() {
+        let cli = Cli::try_parse_from(["flow", "--max-iterations", "50", "--max-duration", "4h"])
+            .unwrap();
+        assert_eq!(cli.max_duration, Some(14400));
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
+        assert!(cli.max_duration.is_none());
+    }
 
-        let outcome = build_outcome(&result, 1);
-        assert_eq!(outcome.outcome, "Implemented feature X with 5 tests");
-        assert_eq!(outcome.num_turns, Some(53));
-        assert_eq!(outcome.total_cost_usd, Some(2.15));
-        assert_eq!(outcome.permission_denial_count, Some(3));
-        assert_eq!(outcome.permission_denials.as_ref().unwrap().len(), 3);
-        assert_eq!(outcome.files_changed, vec!["src/main.rs"]);
+    #[test]
+    fn test_cli_rejects_invalid_max_duration() {
+        assert!(Cli::try_parse_from(["flow", "--max-duration", "soon"]).is_err());
+        assert!(Cli::try_parse_from(["flow", "--max-duration", "0"]).is_err());
     }
 
     #[test]
-    fn test_build_outcome_propagates_files_changed() {
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: true,
-            exit_code: Some(0),
-            stderr: String::new(),
-            duration_secs: 60,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![
-                "src/main.rs".to_string(),
-                "src/lib.rs".to_string(),
-                "tests/foo.rs".to_string(),
-            ],
-            tests_passed: 0,
-        };
-        let outcome = build_outcome(&result, 1);
+    fn test_cli_parses_limit_overrides() {
+        let cli = Cli::try_parse_from([
+            "flow",
+            "--cycle",
+            "coding",
+            "--max-turns",
+            "20",
+            "--max-cost-usd",
+            "1.5",
+        ])
+        .unwrap();
+        assert_eq!(cli.max_turns, Some(20));
+        assert_eq!(cli.max_cost_usd, Some(1.5));
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
+        assert!(cli.max_turns.is_none());
+        assert!(cli.max_cost_usd.is_none());
+    }
+
+    #[test]
+    fn test_cli_rejects_non_positive_max_cost() {
+        assert!(Cli::try_parse_from(["flow", "--cycle", "coding", "--max-cost-usd", "0"]).is_err());
+        assert!(
+            Cli::try_parse_from(["flow", "--cycle", "coding", "--max-cost-usd", "-1"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_parses_repeated_set_overrides() {
+        let cli = Cli::try_parse_from([
+            "flow",
+            "--cycle",
+            "coding",
+            "--set",
+            "global.summary_interval=10",
+            "--set",
+            "cycle.coding.max_turns=30",
+        ])
+        .unwrap();
         assert_eq!(
-            outcome.files_changed,
-            vec!["src/main.rs", "src/lib.rs", "tests/foo.rs"]
+            cli.overrides,
+            vec!["global.summary_interval=10", "cycle.coding.max_turns=30"]
         );
     }
 
     #[test]
-    fn test_build_outcome_propagates_tests_passed() {
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: true,
-            exit_code: Some(0),
-            stderr: String::new(),
-            duration_secs: 60,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 99,
-        };
-        let outcome = build_outcome(&result, 1);
-        assert_eq!(outcome.tests_passed, 99);
+    fn test_cli_parses_profile() {
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding", "--profile", "ci"]).unwrap();
+        assert_eq!(cli.profile.as_deref(), Some("ci"));
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
+        assert!(cli.profile.is_none());
     }
 
     #[test]
-    fn test_format_exit_code_some() {
-        assert_eq!(format_exit_code(Some(0)), "0");
-        assert_eq!(format_exit_code(Some(1)), "1");
-        assert_eq!(format_exit_code(Some(127)), "127");
+    fn test_cli_parses_cycles_list() {
+        let cli = Cli::try_parse_from([
+            "flow",
+            "--cycles",
+            "plan,coding,review",
+            "--max-iterations",
+            "6",
+        ])
+        .unwrap();
+        assert_eq!(cli.cycles, vec!["plan", "coding", "review"]);
+        assert!(cli.cycle.is_none());
     }
 
     #[test]
-    fn test_format_exit_code_none() {
-        assert_eq!(format_exit_code(None), "unknown");
+    fn test_cli_cycles_conflicts_with_cycle() {
+        assert!(
+            Cli::try_parse_from(["flow", "--cycle", "coding", "--cycles", "plan,coding"]).is_err()
+        );
     }
 
     #[test]
-    fn test_check_denial_gate_below_threshold_does_not_exit() {
-        // Should return normally when denials <= max_denials
-        check_denial_gate(0, 10, "coding");
-        check_denial_gate(5, 10, "coding");
-        check_denial_gate(10, 10, "coding"); // equal is not exceeded
+    fn test_cli_replay_conflicts_with_record_and_cycle() {
+        let cli = Cli::try_parse_from(["flow", "--replay", "run.jsonl"]).unwrap();
+        assert_eq!(cli.replay, Some(PathBuf::from("run.jsonl")));
+        assert!(Cli::try_parse_from(["flow", "--replay", "run.jsonl", "--record"]).is_err());
+        assert!(
+            Cli::try_parse_from(["flow", "--replay", "run.jsonl", "--cycle", "coding"]).is_err()
+        );
     }
 
     #[test]
-    fn test_available_cycle_names() {
-        let config = FlowConfig::parse(
+    fn test_cli_from_step_requires_cycle() {
+        assert!(Cli::try_parse_from(["flow", "--from-step", "implement"]).is_err());
+        let cli =
+            Cli::try_parse_from(["flow", "--cycle", "coding", "--from-step", "implement"]).unwrap();
+        assert_eq!(cli.from_step.as_deref(), Some("implement"));
+    }
+
+    #[test]
+    fn test_validate_cli_rejects_unknown_from_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("cycles.toml");
+        std::fs::write(
+            &config,
             r#"
 [global]
 permissions = []
@@ -824,26 +1208,78 @@ permissions = []
 [[cycle]]
 name = "coding"
 description = "Coding"
-prompt = "Code"
+
+[[cycle.step]]
+name = "plan"
+prompt = "Plan."
+
+[[cycle.step]]
+name = "implement"
+prompt = "Implement."
 
 [[cycle]]
-name = "gardening"
-description = "Gardening"
-prompt = "Garden"
+name = "review"
+description = "Review"
+prompt = "Review."
 "#,
         )
         .unwrap();
+        let config = config.to_str().unwrap();
+        let parse = |cycle: &str, step: &str| {
+            Cli::try_parse_from([
+                "flow",
+                "--config",
+                config,
+                "--cycle",
+                cycle,
+                "--from-step",
+                step,
+            ])
+            .unwrap()
+        };
 
-        let names = available_cycle_names(&config);
-        assert_eq!(names, "coding, gardening");
+        assert!(validate_cli(&parse("coding", "implement")).is_ok());
+        let err = validate_cli(&parse("coding", "deploy")).unwrap_err();
+        assert!(err.to_string().contains("plan, implement"), "{err}");
+        let err = validate_cli(&parse("review", "plan")).unwrap_err();
+        assert!(err.to_string().contains("multi-step"), "{err}");
     }
 
     #[test]
-    fn test_cli_parses_max_iterations() {
-        let cli =
-            Cli::try_parse_from(["flow", "--cycle", "coding", "--max-iterations", "5"]).unwrap();
-        assert_eq!(cli.max_iterations, 5);
-        assert_eq!(cli.cycle.as_deref(), Some("coding"));
+    fn test_cli_force_defaults_off() {
+        let cli = Cli::try_parse_from(["flow"]).unwrap();
+        assert!(!cli.force);
+        let cli = Cli::try_parse_from(["flow", "--force"]).unwrap();
+        assert!(cli.force);
+    }
+
+    #[test]
+    fn test_cli_parses_repeated_skip_cycle() {
+        let cli = Cli::try_parse_from([
+            "flow",
+            "--max-iterations",
+            "10",
+            "--skip-cycle",
+            "review",
+            "--skip-cycle",
+            "planning",
+        ])
+        .unwrap();
+        assert_eq!(cli.skip_cycles, vec!["review", "planning"]);
+    }
+
+    #[test]
+    fn test_apply_skip_cycles_marks_non_selectable() {
+        let mut config = FlowConfig::parse(
+            "[global]\npermissions = []\n\n[[cycle]]\nname = \"coding\"\ndescription = \"c\"\nprompt = \"c\"\n\n[[cycle]]\nname = \"review\"\ndescription = \"r\"\nprompt = \"r\"\n",
+        )
+        .unwrap();
+        apply_skip_cycles(&mut config, &["review".to_string()]).unwrap();
+        assert!(config.get_cycle("coding").unwrap().selectable);
+        assert!(!config.get_cycle("review").unwrap().selectable);
+
+        let err = apply_skip_cycles(&mut config, &["nope".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("Unknown cycle 'nope'"), "{err}");
     }
 
     #[test]
@@ -852,12 +1288,179 @@ prompt = "Garden"
         assert_eq!(cli.max_iterations, 1);
     }
 
+    #[test]
+    fn test_cli_parses_logs_export() {
+        let cli = Cli::try_parse_from(["flow", "logs", "export", "--format", "csv"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Logs {
+                command: LogsCommand::Export {
+                    format: ExportFormat::Csv,
+                    output: None,
+                    anonymize: false,
+                }
+            })
+        );
+        let cli = Cli::try_parse_from(["flow", "logs", "export", "-o", "runs.csv"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Logs {
+                command: LogsCommand::Export {
+                    format: ExportFormat::Csv,
+                    output: Some(PathBuf::from("runs.csv")),
+                    anonymize: false,
+                }
+            })
+        );
+        let cli =
+            Cli::try_parse_from(["flow", "logs", "export", "--format", "jsonl", "--anonymize"])
+                .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Logs {
+                command: LogsCommand::Export {
+                    format: ExportFormat::Jsonl,
+                    output: None,
+                    anonymize: true,
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_cli_parses_logs_repair() {
+        let cli = Cli::try_parse_from(["flow", "logs", "repair"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Logs {
+                command: LogsCommand::Repair
+            })
+        );
+    }
+
+    #[test]
+    fn test_cli_parses_logs_compact() {
+        let cli = Cli::try_parse_from(["flow", "logs", "compact"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Logs {
+                command: LogsCommand::Compact
+            })
+        );
+    }
+
+    #[test]
+    fn test_cli_parses_logs_merge() {
+        let cli = Cli::try_parse_from(["flow", "logs", "merge", "a.jsonl", "b.jsonl"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Logs {
+                command: LogsCommand::Merge {
+                    files: vec![PathBuf::from("a.jsonl"), PathBuf::from("b.jsonl")],
+                    output: None,
+                }
+            })
+        );
+        assert!(Cli::try_parse_from(["flow", "logs", "merge"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_parses_config_show_with_layers() {
+        let cli = Cli::try_parse_from([
+            "flow",
+            "--profile",
+            "ci",
+            "--set",
+            "cycle.coding.max_turns=5",
+            "config",
+            "show",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Config {
+                command: ConfigCommand::Show
+            })
+        );
+        assert_eq!(cli.profile.as_deref(), Some("ci"));
+        assert_eq!(cli.overrides, ["cycle.coding.max_turns=5"]);
+    }
+
+    #[test]
+    fn test_cli_parses_sessions_commands() {
+        let cli = Cli::try_parse_from(["flow", "sessions", "prune"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Sessions {
+                command: SessionsCommand::Prune {
+                    older_than: 7 * 86400
+                }
+            })
+        );
+        let cli = Cli::try_parse_from(["flow", "sessions", "delete", "architect"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Sessions {
+                command: SessionsCommand::Delete {
+                    tag: "architect".to_string()
+                }
+            })
+        );
+        assert!(Cli::try_parse_from(["flow", "sessions", "delete"]).is_err());
+    }
+
+    #[test]
+    fn test_run_sessions_delete_unknown_tag_is_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_dir = dir.path().join(".flow");
+        let cli = Cli::try_parse_from(["flow", "--log-dir", log_dir.to_str().unwrap()]).unwrap();
+        let err = run_sessions(
+            &cli,
+            &SessionsCommand::Delete {
+                tag: "architect".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("architect"), "{err}");
+    }
+
+    #[test]
+    fn test_merge_log_files_backs_up_and_replaces_local_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = JsonlLogger::new(dir.path().join(".flow")).unwrap();
+        std::fs::write(
+            logger.log_path(),
+            r#"{"iteration":1,"cycle":"coding","timestamp":"2026-03-01T12:00:00Z","outcome":"Local","files_changed":[],"tests_passed":0,"duration_secs":60}
+"#,
+        )
+        .unwrap();
+        let other = dir.path().join("other.jsonl");
+        std::fs::write(
+            &other,
+            r#"{"iteration":1,"cycle":"review","timestamp":"2026-03-01T12:05:00Z","outcome":"Remote","files_changed":[],"tests_passed":0,"duration_secs":60}
+"#,
+        )
+        .unwrap();
+
+        merge_log_files(&logger, &[other], None).unwrap();
+
+        let merged = logger.read_all().unwrap();
+        let cycles: Vec<&str> = merged.iter().map(|e| e.cycle.as_str()).collect();
+        assert_eq!(cycles, vec!["coding", "review"]);
+        assert!(logger.log_path().with_extension("jsonl.bak").exists());
+    }
+
+    #[test]
+    fn test_cli_rejects_unknown_export_format() {
+        assert!(Cli::try_parse_from(["flow", "logs", "export", "--format", "xlsx"]).is_err());
+    }
+
     #[test]
     fn test_cli_parses_doctor_subcommand() {
         let cli = Cli::try_parse_from(["flow", "doctor"]).unwrap();
         assert!(matches!(
             cli.command,
-            Some(Command::Doctor { repair: false })
+            Some(Command::Doctor { repair: false, .. })
         ));
         assert!(cli.cycle.is_none());
     }
@@ -867,7 +1470,7 @@ prompt = "Garden"
         let cli = Cli::try_parse_from(["flow", "doctor", "--repair"]).unwrap();
         assert!(matches!(
             cli.command,
-            Some(Command::Doctor { repair: true })
+            Some(Command::Doctor { repair: true, .. })
         ));
     }
 
@@ -876,192 +1479,203 @@ prompt = "Garden"
         let cli = Cli::try_parse_from(["flow", "doctor"]).unwrap();
         assert!(matches!(
             cli.command,
-            Some(Command::Doctor { repair: false })
+            Some(Command::Doctor { repair: false, .. })
         ));
     }
 
     #[test]
-    fn test_cli_parses_init_subcommand() {
-        let cli = Cli::try_parse_from(["flow", "init"]).unwrap();
-        assert!(matches!(cli.command, Some(Command::Init)));
-        assert!(cli.cycle.is_none());
+    fn test_cli_doctor_format_defaults_to_text() {
+        let cli = Cli::try_parse_from(["flow", "doctor"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Doctor {
+                format: DoctorFormat::Text,
+                ..
+            })
+        ));
     }
 
     #[test]
-    fn test_cli_parses_cycle_flag() {
-        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
-        assert_eq!(cli.cycle.as_deref(), Some("coding"));
-        assert!(cli.command.is_none());
+    fn test_cli_parses_doctor_format_json() {
+        let cli = Cli::try_parse_from(["flow", "doctor", "--format", "json"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Doctor {
+                format: DoctorFormat::Json,
+                ..
+            })
+        ));
     }
 
     #[test]
-    fn test_cli_parses_todo_flag() {
-        let cli =
-            Cli::try_parse_from(["flow", "--cycle", "coding", "--todo", "my-todo.md"]).unwrap();
-        assert_eq!(cli.todo, PathBuf::from("my-todo.md"));
+    fn test_cli_rejects_unknown_doctor_format() {
+        assert!(Cli::try_parse_from(["flow", "doctor", "--format", "yaml"]).is_err());
     }
 
     #[test]
-    fn test_cli_todo_defaults_to_todo_md() {
-        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
-        assert_eq!(cli.todo, PathBuf::from("TODO.md"));
+    fn test_cli_doctor_fail_on_defaults_to_error() {
+        let cli = Cli::try_parse_from(["flow", "doctor"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Doctor {
+                fail_on: FailOn::Error,
+                ..
+            })
+        ));
     }
 
     #[test]
-    fn test_cli_max_iterations_without_cycle_is_valid() {
-        // When --max-iterations > 1, --cycle is optional (uses selector)
-        let cli = Cli::try_parse_from(["flow", "--max-iterations", "10"]).unwrap();
-        assert!(cli.cycle.is_none());
-        assert_eq!(cli.max_iterations, 10);
+    fn test_cli_parses_doctor_fail_on_warning() {
+        let cli = Cli::try_parse_from(["flow", "doctor", "--fail-on", "warning"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Doctor {
+                fail_on: FailOn::Warning,
+                ..
+            })
+        ));
     }
 
-    // --- should_print_summary tests ---
-
     #[test]
-    fn test_should_print_summary_at_interval() {
-        assert!(should_print_summary(5, 5));
-        assert!(should_print_summary(10, 5));
+    fn test_fail_on_thresholds() {
+        assert_eq!(FailOn::Error.threshold(), Some(Severity::Error));
+        assert_eq!(FailOn::Warning.threshold(), Some(Severity::Warning));
+        assert_eq!(FailOn::Info.threshold(), Some(Severity::Info));
+        assert_eq!(FailOn::Never.threshold(), None);
     }
 
     #[test]
-    fn test_should_print_summary_not_at_interval() {
-        assert!(!should_print_summary(3, 5));
-        assert!(!should_print_summary(1, 5));
+    fn test_cli_parses_mock_claude_with_claude_args() {
+        let cli = Cli::try_parse_from([
+            "flow",
+            "mock-claude",
+            "--fixtures",
+            "tests/mock",
+            "-p",
+            "Fix it",
+            "--verbose",
+            "--output-format",
+            "stream-json",
+        ])
+        .unwrap();
+        let Some(Command::MockClaude { fixtures, args }) = cli.command else {
+            panic!("expected mock-claude");
+        };
+        assert_eq!(fixtures, PathBuf::from("tests/mock"));
+        assert_eq!(
+            args,
+            [
+                "-p",
+                "Fix it",
+                "--verbose",
+                "--output-format",
+                "stream-json"
+            ]
+        );
     }
 
     #[test]
-    fn test_should_print_summary_disabled_when_zero() {
-        assert!(!should_print_summary(5, 0));
-        assert!(!should_print_summary(10, 0));
+    fn test_cli_parses_gc_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "gc", "--dry-run"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Gc { dry_run: true }));
     }
 
-    // --- check_run_health tests ---
-
     #[test]
-    fn test_run_health_ok_when_all_succeed() {
-        let history = vec![
-            RunOutcome { success: true },
-            RunOutcome { success: true },
-            RunOutcome { success: true },
-        ];
-        assert!(check_run_health(&history, 3).is_none());
+    fn test_cli_parses_serve_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "serve", "--api"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Serve {
+                api: true,
+                listen: "127.0.0.1:8787".to_string()
+            })
+        );
     }
 
     #[test]
-    fn test_run_health_stops_on_consecutive_failures() {
-        let history = vec![
-            RunOutcome { success: true },
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-        ];
-        // 3 consecutive failures at the end — should stop
-        assert!(check_run_health(&history, 3).is_some());
+    fn test_cli_parses_permissions_diff_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "permissions", "diff"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Permissions {
+                command: PermissionsCommand::Diff { min_denials: 2 }
+            })
+        );
     }
 
     #[test]
-    fn test_run_health_does_not_stop_below_threshold() {
-        let history = vec![RunOutcome { success: false }, RunOutcome { success: false }];
-        // Only 2 consecutive failures, threshold is 3
-        assert!(check_run_health(&history, 3).is_none());
+    fn test_cli_parses_stats_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "stats"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Stats { window: 10 }));
+        let cli = Cli::try_parse_from(["flow", "stats", "--window", "5"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Stats { window: 5 }));
     }
 
     #[test]
-    fn test_run_health_resets_on_success() {
-        let history = vec![
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-            RunOutcome { success: true }, // resets the streak
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-        ];
-        // Streak is only 2 (after the success) — should not stop
-        assert!(check_run_health(&history, 3).is_none());
+    fn test_cli_parses_explain_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "--max-turns", "5", "explain", "coding"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Explain {
+                cycle: "coding".to_string()
+            })
+        );
+        assert_eq!(cli.max_turns, Some(5));
+        assert!(Cli::try_parse_from(["flow", "explain"]).is_err());
     }
 
     #[test]
-    fn test_run_health_empty_history_is_ok() {
-        assert!(check_run_health(&[], 3).is_none());
+    fn test_cli_parses_watch_progress_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "watch-progress", "--interval", "5s"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::WatchProgress {
+                interval: 5,
+                once: false
+            })
+        );
+        assert!(Cli::try_parse_from(["flow", "watch-progress", "--interval", "0s"]).is_err());
     }
 
     #[test]
-    fn test_run_health_returns_message_with_count() {
-        let history = vec![
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-        ];
-        let msg = check_run_health(&history, 3).unwrap();
-        assert!(
-            msg.contains('3'),
-            "Message should mention failure count: {msg}"
-        );
+    fn test_cli_parses_migrate_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "migrate"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Migrate { dry_run: false }));
     }
 
     #[test]
-    fn test_run_health_disabled_when_zero() {
-        let history = vec![
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-            RunOutcome { success: false },
-        ];
-        // max_consecutive_failures = 0 disables the check
-        assert!(check_run_health(&history, 0).is_none());
+    fn test_cli_parses_init_subcommand() {
+        let cli = Cli::try_parse_from(["flow", "init"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Init)));
+        assert!(cli.cycle.is_none());
     }
 
     #[test]
-    fn test_apply_cycle_gates_records_failure_in_history() {
-        // After the fix, failures are recorded but do not call process::exit
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: false,
-            exit_code: Some(1),
-            stderr: String::new(),
-            duration_secs: 10,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 0,
-        };
-
-        let mut run_history = Vec::new();
-        // With max_consecutive_failures high enough, a single failure should not exit
-        apply_cycle_gates(&result, "coding", &mut run_history, 10, 3, 1);
-
-        assert_eq!(run_history.len(), 1);
-        assert!(
-            !run_history[0].success,
-            "Failure should be recorded in history"
-        );
+    fn test_cli_parses_cycle_flag() {
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
+        assert_eq!(cli.cycle.as_deref(), Some("coding"));
+        assert!(cli.command.is_none());
     }
 
     #[test]
-    fn test_apply_cycle_gates_records_success_in_history() {
-        let result = CycleResult {
-            cycle_name: "coding".to_string(),
-            success: true,
-            exit_code: Some(0),
-            stderr: String::new(),
-            duration_secs: 60,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 0,
-        };
+    fn test_cli_parses_todo_flag() {
+        let cli =
+            Cli::try_parse_from(["flow", "--cycle", "coding", "--todo", "my-todo.md"]).unwrap();
+        assert_eq!(cli.todo, PathBuf::from("my-todo.md"));
+    }
 
-        let mut run_history = Vec::new();
-        apply_cycle_gates(&result, "coding", &mut run_history, 10, 3, 1);
+    #[test]
+    fn test_cli_todo_defaults_to_todo_md() {
+        let cli = Cli::try_parse_from(["flow", "--cycle", "coding"]).unwrap();
+        assert_eq!(cli.todo, PathBuf::from("TODO.md"));
+        assert_eq!(cli.tasks, PathBuf::from("tasks.toml"));
+    }
 
-        assert_eq!(run_history.len(), 1);
-        assert!(
-            run_history[0].success,
-            "Success should be recorded in history"
-        );
+    #[test]
+    fn test_cli_max_iterations_without_cycle_is_valid() {
+        // When --max-iterations > 1, --cycle is optional (uses selector)
+        let cli = Cli::try_parse_from(["flow", "--max-iterations", "10"]).unwrap();
+        assert!(cli.cycle.is_none());
+        assert_eq!(cli.max_iterations, 10);
     }
 }
@@ -0,0 +1,430 @@
+//! Notifications for run events
+//!
+//! Flow often runs in a background terminal. With `[notifications]` configured,
+//! run completion, stop-gate trips, budget warnings, and cycle results are
+//! routed to channels: native desktop notifications (`notify-send` on Linux,
+//! `osascript` on macOS), generic JSON webhooks, or Slack incoming webhooks
+//! (both via `curl`). Without rules, the enabled `events` pop desktop
+//! notifications. Delivery is best-effort: a failing channel never affects the
+//! run.
+
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cycle::config::{
+    ChannelKind, NotificationChannel, NotificationEvent, NotificationRule, NotificationsConfig,
+};
+
+/// A single event to deliver
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// What happened
+    pub event: NotificationEvent,
+    /// Cycle the event concerns (`None` for run-level events)
+    pub cycle: Option<String>,
+    /// Cost of the cycle, or of the run so far
+    pub cost_usd: Option<f64>,
+    /// Short headline
+    pub title: String,
+    /// Details
+    pub body: String,
+}
+
+impl Notification {
+    /// A notification with no cycle or cost attached.
+    #[must_use]
+    pub fn new(event: NotificationEvent, title: &str, body: &str) -> Self {
+        Self {
+            event,
+            cycle: None,
+            cost_usd: None,
+            title: title.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    /// Attach the cycle this notification concerns.
+    #[must_use]
+    pub fn with_cycle(mut self, cycle: &str) -> Self {
+        self.cycle = Some(cycle.to_string());
+        self
+    }
+
+    /// Attach a cost for `min_cost_usd` matching.
+    #[must_use]
+    pub const fn with_cost(mut self, cost_usd: Option<f64>) -> Self {
+        self.cost_usd = cost_usd;
+        self
+    }
+}
+
+/// Whether `rule` matches `notification`.
+#[must_use]
+pub fn rule_matches(rule: &NotificationRule, notification: &Notification) -> bool {
+    let event_ok = rule.events.is_empty() || rule.events.contains(&notification.event);
+    let cycle_ok = rule.cycles.is_empty()
+        || notification
+            .cycle
+            .as_ref()
+            .is_some_and(|c| rule.cycles.contains(c));
+    let severity_ok = rule
+        .min_severity
+        .is_none_or(|min| notification.event.severity() >= min);
+    let cost_ok = rule
+        .min_cost_usd
+        .is_none_or(|min| notification.cost_usd.is_some_and(|c| c >= min));
+    event_ok && cycle_ok && severity_ok && cost_ok
+}
+
+/// Sends notifications according to `[notifications]`
+#[derive(Debug, Default)]
+pub struct Notifier {
+    /// `None` when notifications are not configured
+    config: Option<NotificationsConfig>,
+    /// Set once the budget warning has fired, so it fires only once per run
+    budget_warned: AtomicBool,
+}
+
+impl Notifier {
+    /// Create a notifier from the `[notifications]` section (if any).
+    #[must_use]
+    pub const fn new(config: Option<NotificationsConfig>) -> Self {
+        Self {
+            config,
+            budget_warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Channels `notification` should be delivered to, deduplicated, in rule order.
+    ///
+    /// Without rules, enabled `events` go to the desktop (when `desktop = true`).
+    #[must_use]
+    pub fn route(&self, notification: &Notification) -> Vec<String> {
+        let Some(ref config) = self.config else {
+            return Vec::new();
+        };
+        if config.rules.is_empty() {
+            return if config.desktop && config.events.contains(&notification.event) {
+                vec!["desktop".to_string()]
+            } else {
+                Vec::new()
+            };
+        }
+        let mut channels: Vec<String> = Vec::new();
+        for rule in config
+            .rules
+            .iter()
+            .filter(|r| rule_matches(r, notification))
+        {
+            for channel in &rule.channels {
+                if !channels.contains(channel) {
+                    channels.push(channel.clone());
+                }
+            }
+        }
+        channels
+    }
+
+    /// Deliver `notification` to every channel it routes to.
+    pub fn notify(&self, notification: &Notification) {
+        let Some(ref config) = self.config else {
+            return;
+        };
+        for name in self.route(notification) {
+            let channel = config
+                .channels
+                .get(&name)
+                .cloned()
+                .unwrap_or(NotificationChannel {
+                    kind: ChannelKind::Desktop,
+                    url: None,
+                });
+            send(&channel, notification);
+        }
+    }
+
+    /// Warn once when the run's total cost reaches `budget_warning_usd`.
+    pub fn check_budget(&self, total_cost_usd: f64) {
+        if let Some(budget) = self.budget_crossed(total_cost_usd) {
+            self.notify(
+                &Notification::new(
+                    NotificationEvent::BudgetWarning,
+                    "Flow budget warning",
+                    &format!(
+                        "Run cost ${total_cost_usd:.2} reached the ${budget:.2} warning threshold"
+                    ),
+                )
+                .with_cost(Some(total_cost_usd)),
+            );
+        }
+    }
+
+    /// The threshold, the first time `total_cost_usd` reaches it.
+    fn budget_crossed(&self, total_cost_usd: f64) -> Option<f64> {
+        let budget = self.config.as_ref()?.budget_warning_usd?;
+        (total_cost_usd >= budget && !self.budget_warned.swap(true, Ordering::Relaxed))
+            .then_some(budget)
+    }
+}
+
+/// JSON body posted to `webhook` channels.
+#[must_use]
+pub fn webhook_payload(notification: &Notification) -> serde_json::Value {
+    serde_json::json!({
+        "event": notification.event,
+        "severity": notification.event.severity(),
+        "cycle": notification.cycle,
+        "cost_usd": notification.cost_usd,
+        "title": notification.title,
+        "body": notification.body,
+    })
+}
+
+/// JSON body posted to `slack` channels.
+#[must_use]
+pub fn slack_payload(notification: &Notification) -> serde_json::Value {
+    serde_json::json!({
+        "text": format!("*{}*\n{}", notification.title, notification.body),
+    })
+}
+
+/// `curl` invocation that posts `payload` as JSON to `url`.
+#[must_use]
+pub fn post_command(url: &str, payload: &serde_json::Value) -> Command {
+    let mut cmd = Command::new("curl");
+    cmd.args([
+        "-sS",
+        "--max-time",
+        "10",
+        "-X",
+        "POST",
+        "-H",
+        "Content-Type: application/json",
+        "--data-raw",
+        &payload.to_string(),
+        url,
+    ]);
+    cmd
+}
+
+/// Deliver to one channel without waiting, ignoring failures.
+fn send(channel: &NotificationChannel, notification: &Notification) {
+    let cmd = match (channel.kind, channel.url.as_deref()) {
+        (ChannelKind::Desktop, _) => desktop_command(
+            std::env::consts::OS,
+            &notification.title,
+            &notification.body,
+        ),
+        (ChannelKind::Webhook, Some(url)) => {
+            Some(post_command(url, &webhook_payload(notification)))
+        }
+        (ChannelKind::Slack, Some(url)) => Some(post_command(url, &slack_payload(notification))),
+        _ => None,
+    };
+    if let Some(mut cmd) = cmd {
+        let _ = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+    }
+}
+
+/// Build the native notification command for `os` (as in `std::env::consts::OS`).
+///
+/// Returns `None` on platforms without a supported notifier.
+#[must_use]
+pub fn desktop_command(os: &str, title: &str, body: &str) -> Option<Command> {
+    match os {
+        "linux" | "freebsd" | "openbsd" => {
+            let mut cmd = Command::new("notify-send");
+            cmd.args(["--app-name", "flow", title, body]);
+            Some(cmd)
+        }
+        "macos" => {
+            let script = format!(
+                "display notification \"{}\" with title \"{}\"",
+                applescript_escape(body),
+                applescript_escape(title)
+            );
+            let mut cmd = Command::new("osascript");
+            cmd.args(["-e", &script]);
+            Some(cmd)
+        }
+        _ => None,
+    }
+}
+
+/// Escape `s` for use inside an `AppleScript` string literal.
+fn applescript_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycle::config::NotificationSeverity;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    // --- desktop_command tests ---
+
+    #[test]
+    fn test_desktop_command_linux_uses_notify_send() {
+        let cmd = desktop_command("linux", "Flow run completed", "5 iterations").unwrap();
+        assert_eq!(cmd.get_program(), "notify-send");
+        assert_eq!(
+            args(&cmd),
+            ["--app-name", "flow", "Flow run completed", "5 iterations"]
+        );
+    }
+
+    #[test]
+    fn test_desktop_command_macos_escapes_quotes() {
+        let cmd = desktop_command("macos", "Flow", r#"cycle "coding" failed"#).unwrap();
+        assert_eq!(cmd.get_program(), "osascript");
+        assert_eq!(
+            args(&cmd)[1],
+            r#"display notification "cycle \"coding\" failed" with title "Flow""#
+        );
+    }
+
+    #[test]
+    fn test_desktop_command_unsupported_os() {
+        assert!(desktop_command("windows", "Flow", "done").is_none());
+    }
+
+    // --- rule matching tests ---
+
+    fn rule(channels: &[&str]) -> NotificationRule {
+        NotificationRule {
+            events: Vec::new(),
+            cycles: Vec::new(),
+            min_severity: None,
+            min_cost_usd: None,
+            channels: channels.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn cycle_failed(cycle: &str, cost: f64) -> Notification {
+        Notification::new(NotificationEvent::CycleFailed, "Cycle failed", "exit 1")
+            .with_cycle(cycle)
+            .with_cost(Some(cost))
+    }
+
+    #[test]
+    fn test_empty_rule_matches_everything() {
+        let r = rule(&["desktop"]);
+        assert!(rule_matches(&r, &cycle_failed("coding", 0.5)));
+        assert!(rule_matches(
+            &r,
+            &Notification::new(NotificationEvent::RunCompleted, "t", "b")
+        ));
+    }
+
+    #[test]
+    fn test_rule_matches_event_and_cycle() {
+        let r = NotificationRule {
+            events: vec![NotificationEvent::CycleFailed],
+            cycles: vec!["coding".to_string()],
+            ..rule(&["desktop"])
+        };
+        assert!(rule_matches(&r, &cycle_failed("coding", 0.5)));
+        assert!(!rule_matches(&r, &cycle_failed("gardening", 0.5)));
+        assert!(!rule_matches(
+            &r,
+            &Notification::new(NotificationEvent::RunStopped, "t", "b")
+        ));
+    }
+
+    #[test]
+    fn test_rule_matches_severity_and_cost() {
+        let r = NotificationRule {
+            min_severity: Some(NotificationSeverity::Warning),
+            min_cost_usd: Some(2.0),
+            ..rule(&["desktop"])
+        };
+        assert!(rule_matches(&r, &cycle_failed("coding", 3.0)));
+        assert!(!rule_matches(&r, &cycle_failed("coding", 1.0)));
+        let completed =
+            Notification::new(NotificationEvent::CycleCompleted, "t", "b").with_cost(Some(3.0));
+        assert!(!rule_matches(&r, &completed));
+    }
+
+    // --- Notifier tests ---
+
+    #[test]
+    fn test_route_without_rules_uses_desktop_events() {
+        let notifier = Notifier::new(Some(NotificationsConfig::default()));
+        let stopped = Notification::new(NotificationEvent::RunStopped, "t", "b");
+        assert_eq!(notifier.route(&stopped), ["desktop"]);
+        assert!(notifier.route(&cycle_failed("coding", 1.0)).is_empty());
+    }
+
+    #[test]
+    fn test_route_dedupes_channels_across_rules() {
+        let notifier = Notifier::new(Some(NotificationsConfig {
+            rules: vec![
+                rule(&["slack", "desktop"]),
+                NotificationRule {
+                    events: vec![NotificationEvent::CycleFailed],
+                    ..rule(&["ops", "slack"])
+                },
+            ],
+            ..NotificationsConfig::default()
+        }));
+        assert_eq!(
+            notifier.route(&cycle_failed("coding", 1.0)),
+            ["slack", "desktop", "ops"]
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_notifier_routes_nowhere() {
+        let notifier = Notifier::default();
+        let completed = Notification::new(NotificationEvent::RunCompleted, "t", "b");
+        assert!(notifier.route(&completed).is_empty());
+        assert!(notifier.budget_crossed(100.0).is_none());
+    }
+
+    #[test]
+    fn test_budget_warning_fires_once() {
+        let notifier = Notifier::new(Some(NotificationsConfig {
+            budget_warning_usd: Some(5.0),
+            ..NotificationsConfig::default()
+        }));
+        assert!(notifier.budget_crossed(4.99).is_none());
+        assert_eq!(notifier.budget_crossed(5.5), Some(5.0));
+        assert!(notifier.budget_crossed(7.0).is_none());
+    }
+
+    // --- payload tests ---
+
+    #[test]
+    fn test_webhook_payload_fields() {
+        let payload = webhook_payload(&cycle_failed("coding", 1.5));
+        assert_eq!(payload["event"], "cycle_failed");
+        assert_eq!(payload["severity"], "warning");
+        assert_eq!(payload["cycle"], "coding");
+        assert_eq!(payload["cost_usd"], 1.5);
+    }
+
+    #[test]
+    fn test_slack_payload_text() {
+        let payload = slack_payload(&cycle_failed("coding", 1.5));
+        assert_eq!(payload["text"], "*Cycle failed*\nexit 1");
+    }
+
+    #[test]
+    fn test_post_command_targets_url() {
+        let cmd = post_command("https://example.com/hook", &serde_json::json!({"a": 1}));
+        assert_eq!(cmd.get_program(), "curl");
+        let args = args(&cmd);
+        assert_eq!(args.last().unwrap(), "https://example.com/hook");
+        assert!(args.contains(&r#"{"a":1}"#.to_string()));
+    }
+}
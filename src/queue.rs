@@ -0,0 +1,367 @@
+//! Task queue directory (`.flow/queue/`)
+//!
+//! Other automation drops one file per task into `<log_dir>/queue/`. Each
+//! iteration pops the first pending task (by file name), hands its body to
+//! the selected cycle (with `--cycles`, every cycle of the pass) as
+//! `{{queued_task}}`, and archives the file under `done/` or `failed/` once
+//! the iteration's cycles finish. A popped task waits in
+//! `active/` meanwhile, so a run that crashes leaves it to be requeued by the
+//! next run rather than lost. Dotfiles are ignored, so writers can create
+//! `.name.tmp` and rename it into place.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Queue directory under the log directory
+pub const QUEUE_DIR: &str = "queue";
+
+/// Template variable holding the task body
+pub const TASK_VAR: &str = "queued_task";
+
+/// Template variable holding the task's file name
+pub const TASK_NAME_VAR: &str = "queued_task_name";
+
+/// Where a popped task waits while its cycle runs
+const ACTIVE_DIR: &str = "active";
+
+/// A task popped from the queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedTask {
+    /// File name, e.g. `0042-fix-login.md`
+    pub name: String,
+    /// File contents
+    pub body: String,
+    /// Current location, under `active/`
+    path: PathBuf,
+}
+
+/// Set `queued_task` and `queued_task_name` in a cycle's template variables,
+/// empty when no task was popped so prompts placing them expand cleanly.
+#[allow(clippy::implicit_hasher)]
+pub fn insert_task_vars(vars: &mut HashMap<String, String>, task: Option<&QueuedTask>) {
+    let (body, name) = task.map_or((String::new(), String::new()), |task| {
+        (task.body.clone(), task.name.clone())
+    });
+    vars.insert(TASK_VAR.to_string(), body);
+    vars.insert(TASK_NAME_VAR.to_string(), name);
+}
+
+/// How a queued task's cycle ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskDisposition {
+    /// The cycle succeeded: archive under `done/`
+    Done,
+    /// The cycle failed: archive under `failed/`
+    Failed,
+    /// The run was cancelled mid-cycle: return the task to the queue
+    Requeue,
+}
+
+/// Per-task files consumed one per iteration
+#[derive(Debug, Clone)]
+pub struct TaskQueue {
+    dir: PathBuf,
+}
+
+impl TaskQueue {
+    /// The queue of a log directory (`<log_dir>/queue`). Nothing is created
+    /// until a task is popped.
+    #[must_use]
+    pub fn new(log_dir: &Path) -> Self {
+        Self {
+            dir: log_dir.join(QUEUE_DIR),
+        }
+    }
+
+    /// Queue directory
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Pending task files, in the order they will be popped.
+    ///
+    /// # Errors
+    /// Returns an error if the queue directory exists but can't be read.
+    pub fn pending(&self) -> Result<Vec<PathBuf>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.dir.display()))
+            }
+        };
+        let mut tasks: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path())
+            .collect();
+        tasks.sort();
+        Ok(tasks)
+    }
+
+    /// Return tasks a crashed run left in `active/` to the queue. Returns
+    /// their names.
+    ///
+    /// # Errors
+    /// Returns an error if a task can't be moved back.
+    pub fn requeue_abandoned(&self) -> Result<Vec<String>> {
+        let active = self.dir.join(ACTIVE_DIR);
+        let Ok(entries) = std::fs::read_dir(&active) else {
+            return Ok(Vec::new());
+        };
+        let mut names = Vec::new();
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let target = unique_path(&self.dir, &name);
+            std::fs::rename(entry.path(), &target)
+                .with_context(|| format!("Failed to requeue {}", entry.path().display()))?;
+            names.push(name);
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// The task the next [`pop`](Self::pop) would take, left in the queue.
+    ///
+    /// # Errors
+    /// Returns an error if the queue or the task can't be read.
+    pub fn peek(&self) -> Result<Option<QueuedTask>> {
+        let Some(path) = self.pending()?.into_iter().next() else {
+            return Ok(None);
+        };
+        let bytes = std::fs::read(&path)
+            .with_context(|| format!("Failed to read task {}", path.display()))?;
+        Ok(Some(QueuedTask {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            body: String::from_utf8_lossy(&bytes).trim().to_string(),
+            path,
+        }))
+    }
+
+    /// Take the first pending task, moving it to `active/`.
+    ///
+    /// A task another consumer moved first is skipped.
+    ///
+    /// # Errors
+    /// Returns an error if the queue can't be read or a popped task can't be read.
+    pub fn pop(&self) -> Result<Option<QueuedTask>> {
+        let active = self.dir.join(ACTIVE_DIR);
+        for path in self.pending()? {
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+                continue;
+            };
+            std::fs::create_dir_all(&active)
+                .with_context(|| format!("Failed to create {}", active.display()))?;
+            let target = unique_path(&active, &name);
+            if std::fs::rename(&path, &target).is_err() {
+                continue;
+            }
+            let bytes = std::fs::read(&target)
+                .with_context(|| format!("Failed to read task {}", target.display()))?;
+            return Ok(Some(QueuedTask {
+                name,
+                body: String::from_utf8_lossy(&bytes).trim().to_string(),
+                path: target,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Archive a popped task under `done/` or `failed/`, or return it to the
+    /// queue. Returns where it ended up.
+    ///
+    /// # Errors
+    /// Returns an error if the task can't be moved.
+    pub fn finish(&self, task: &QueuedTask, disposition: TaskDisposition) -> Result<PathBuf> {
+        let dir = match disposition {
+            TaskDisposition::Done => self.dir.join("done"),
+            TaskDisposition::Failed => self.dir.join("failed"),
+            TaskDisposition::Requeue => self.dir.clone(),
+        };
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let target = unique_path(&dir, &task.name);
+        std::fs::rename(&task.path, &target)
+            .with_context(|| format!("Failed to move task {}", task.path.display()))?;
+        Ok(target)
+    }
+}
+
+/// `dir/name`, or `dir/name.1`, `dir/name.2`, ... if that is taken, so an
+/// archived task never overwrites an earlier one with the same name.
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    (1..=u32::MAX)
+        .map(|n| dir.join(format!("{name}.{n}")))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(path)
+}
+
+/// Append the queued task to a prompt that doesn't place `{{queued_task}}`
+/// itself. Prompts are unchanged when no task was popped.
+#[must_use]
+#[allow(clippy::implicit_hasher)]
+pub fn with_queued_task(prompt: &str, vars: &HashMap<String, String>) -> String {
+    let placeholder = format!("{{{{{TASK_VAR}}}}}");
+    if vars.get(TASK_VAR).is_none_or(String::is_empty) || prompt.contains(&placeholder) {
+        return prompt.to_string();
+    }
+    format!("{prompt}\n\n## Queued task\n\n{placeholder}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycle::template::expand_template;
+    use tempfile::TempDir;
+
+    fn enqueue(queue: &TaskQueue, name: &str, body: &str) {
+        std::fs::create_dir_all(queue.dir()).unwrap();
+        std::fs::write(queue.dir().join(name), body).unwrap();
+    }
+
+    // --- pop tests ---
+
+    #[test]
+    fn test_pop_empty_or_missing_queue() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        assert_eq!(queue.pop().unwrap(), None);
+        assert!(!queue.dir().exists(), "pop must not create the queue");
+    }
+
+    #[test]
+    fn test_pop_takes_tasks_in_name_order() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        enqueue(&queue, "002-second.md", "Second");
+        enqueue(&queue, "001-first.md", "  First task\n");
+
+        let task = queue.pop().unwrap().unwrap();
+        assert_eq!(task.name, "001-first.md");
+        assert_eq!(task.body, "First task");
+        assert!(queue.dir().join("active/001-first.md").exists());
+        assert_eq!(queue.pending().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_peek_leaves_task_queued() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        assert_eq!(queue.peek().unwrap(), None);
+        enqueue(&queue, "001.md", "Next up");
+        let peeked = queue.peek().unwrap().unwrap();
+        assert_eq!(
+            (peeked.name.as_str(), peeked.body.as_str()),
+            ("001.md", "Next up")
+        );
+        assert_eq!(queue.pop().unwrap().unwrap().body, "Next up");
+    }
+
+    #[test]
+    fn test_pop_ignores_dotfiles_and_directories() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        enqueue(&queue, ".incoming.tmp", "half-written");
+        std::fs::create_dir_all(queue.dir().join("done")).unwrap();
+        assert_eq!(queue.pop().unwrap(), None);
+    }
+
+    // --- finish tests ---
+
+    #[test]
+    fn test_finish_archives_by_disposition() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        enqueue(&queue, "a.md", "A");
+        enqueue(&queue, "b.md", "B");
+        enqueue(&queue, "c.md", "C");
+
+        let a = queue.pop().unwrap().unwrap();
+        assert_eq!(
+            queue.finish(&a, TaskDisposition::Done).unwrap(),
+            queue.dir().join("done/a.md")
+        );
+        let b = queue.pop().unwrap().unwrap();
+        queue.finish(&b, TaskDisposition::Failed).unwrap();
+        assert!(queue.dir().join("failed/b.md").exists());
+        let c = queue.pop().unwrap().unwrap();
+        queue.finish(&c, TaskDisposition::Requeue).unwrap();
+        assert_eq!(queue.pending().unwrap(), vec![queue.dir().join("c.md")]);
+    }
+
+    #[test]
+    fn test_finish_never_overwrites_archived_task() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        for body in ["first", "second"] {
+            enqueue(&queue, "task.md", body);
+            let task = queue.pop().unwrap().unwrap();
+            queue.finish(&task, TaskDisposition::Done).unwrap();
+        }
+        let done = queue.dir().join("done");
+        assert_eq!(
+            std::fs::read_to_string(done.join("task.md")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            std::fs::read_to_string(done.join("task.md.1")).unwrap(),
+            "second"
+        );
+    }
+
+    #[test]
+    fn test_requeue_abandoned_returns_active_tasks() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        assert!(queue.requeue_abandoned().unwrap().is_empty());
+        enqueue(&queue, "crashed.md", "C");
+        queue.pop().unwrap().unwrap();
+
+        assert_eq!(queue.requeue_abandoned().unwrap(), vec!["crashed.md"]);
+        assert_eq!(queue.pop().unwrap().unwrap().name, "crashed.md");
+    }
+
+    // --- with_queued_task tests ---
+
+    #[test]
+    fn test_with_queued_task_appends_section() {
+        let dir = TempDir::new().unwrap();
+        let queue = TaskQueue::new(dir.path());
+        enqueue(&queue, "t.md", "Fix {{the}} login");
+        let mut vars = HashMap::new();
+        insert_task_vars(&mut vars, queue.pop().unwrap().as_ref());
+
+        let prompt = expand_template(&with_queued_task("Do the work.", &vars), &vars);
+        assert_eq!(
+            prompt,
+            "Do the work.\n\n## Queued task\n\nFix {{the}} login"
+        );
+    }
+
+    #[test]
+    fn test_with_queued_task_respects_explicit_placement() {
+        let vars = HashMap::from([(TASK_VAR.to_string(), "Fix it".to_string())]);
+        let prompt = "Task: {{queued_task}}. Go.";
+        assert_eq!(with_queued_task(prompt, &vars), prompt);
+    }
+
+    #[test]
+    fn test_with_queued_task_without_task_is_unchanged() {
+        assert_eq!(with_queued_task("Do it.", &HashMap::new()), "Do it.");
+        let mut vars = HashMap::new();
+        insert_task_vars(&mut vars, None);
+        assert_eq!(with_queued_task("Do it.", &vars), "Do it.");
+        assert_eq!(expand_template("[{{queued_task}}]", &vars), "[]");
+    }
+}
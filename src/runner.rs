@@ -0,0 +1,3283 @@
+//! Embeddable run loop
+//!
+//! [`FlowRunner`] drives a run the way the `flow` binary does: each iteration
+//! picks a cycle (fixed, playlist, or AI selector), executes and logs it,
+//! enforces the stop gates, auto-triggers dependent and periodic cycles, and
+//! keeps `progress.json` current. Human-oriented output goes to stderr, as in
+//! the binary.
+//!
+//! ```no_run
+//! # async fn demo() -> anyhow::Result<()> {
+//! use flow::{FlowConfig, FlowRunner};
+//!
+//! let config = FlowConfig::from_path("cycles.toml")?;
+//! let report = FlowRunner::new(config)
+//!     .with_cycle("coding")
+//!     .with_max_iterations(3)
+//!     .run()
+//!     .await?;
+//! println!("spent ${:.2}", report.progress.total_cost_usd);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use tokio_util::sync::CancellationToken;
+
+use crate::claude::stream::suggest_permission_fix;
+use crate::cli::{render_activity_summary, render_tool_summary, theme, Role};
+use crate::cycle::arbiter::Decision;
+use crate::cycle::claim::{claim_task, finish_claims};
+use crate::cycle::compaction::{compact, DIGESTS_FILE};
+use crate::cycle::config::{CycleConfig, FlowConfig, GitFinish, NotificationEvent};
+use crate::cycle::executor::{CycleExecutor, CycleResult};
+use crate::cycle::observer::ExecutionObserver;
+use crate::cycle::rules::{find_periodic_cycles, find_triggered_cycles};
+use crate::cycle::selector::{
+    select_cycle, select_with_fallback, CycleSelection, LogSummary, SELECTED_TASK_ID_VAR,
+    SELECTED_TASK_VAR, SELECTOR_RECENT_OUTCOMES,
+};
+use crate::cycle::snapshot::Snapshot;
+use crate::cycle::stop::check_stop_when;
+use crate::cycle::template::build_template_vars;
+use crate::git::{branch_name, commit_all, head_sha, pathspec_within, RunBranch};
+use crate::hooks::HookRunner;
+use crate::log::events::{EventEmitter, FlowEvent};
+use crate::log::jsonl::{new_run_id, os_label, FailureKind, JsonlLogger, FLOW_VERSION};
+use crate::log::lock::RunLock;
+use crate::log::migrate::migrate;
+use crate::log::progress::{ProgressWriter, RunProgress, RunStatus};
+use crate::log::recording::{recording_path, replay_log_dir, RecordedEntry, Recording};
+use crate::log::CycleOutcome;
+use crate::notifications::{Notification, Notifier};
+use crate::queue::{insert_task_vars, QueuedTask, TaskDisposition, TaskQueue, TASK_NAME_VAR};
+use crate::tasks::{
+    release_in_progress, set_status, StructuredTask, TaskFile, TaskStatus, TASKS_FILE,
+};
+
+/// A task marked in progress for the duration of a cycle
+enum TaskClaim {
+    /// A TODO.md checkbox marked `- [~]`
+    Todo,
+    /// A `tasks.toml` task (by id) set to `in_progress`
+    Structured(String),
+}
+
+/// Builder for an orchestrated Flow run
+#[allow(clippy::struct_excessive_bools)] // independent run options
+pub struct FlowRunner {
+    config: FlowConfig,
+    log_dir: PathBuf,
+    todo_path: PathBuf,
+    tasks_path: PathBuf,
+    project_dir: PathBuf,
+    max_iterations: u32,
+    /// Cycle to run every iteration (`None` with an empty playlist = AI selection)
+    cycle: Option<String>,
+    /// Cycles to run in order, one per iteration, wrapping around
+    playlist: Vec<String>,
+    /// Step to start the fixed cycle at (first iteration only)
+    start_step: Option<String>,
+    max_duration_secs: Option<u64>,
+    max_turns: Option<u32>,
+    max_cost_usd: Option<f64>,
+    verbose: bool,
+    debug_stream: bool,
+    emit_events: bool,
+    force: bool,
+    /// Render cycle output and status lines to stderr
+    display: bool,
+    cancel: CancellationToken,
+    observer: Option<Arc<dyn ExecutionObserver>>,
+    /// Write a recording of this run under `<log_dir>/recordings/`
+    record: bool,
+    /// Recording to replay instead of running Claude Code
+    replay: Option<Arc<Recording>>,
+}
+
+/// What a finished run reports back to the caller
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    /// Identifier stamped on this run's log entries
+    pub run_id: String,
+    /// Final progress snapshot (status, cost, cycles executed, stop reason)
+    pub progress: RunProgress,
+    /// Whether a stop gate (permission denials, consecutive failures) ended the run
+    pub gate_tripped: bool,
+}
+
+impl FlowRunner {
+    /// Create a runner for `config` with the binary's defaults: one iteration,
+    /// logs in `.flow`, `TODO.md` (or `tasks.toml` if it exists) for selector
+    /// context, and the current directory as the project root.
+    #[must_use]
+    pub fn new(config: FlowConfig) -> Self {
+        Self {
+            config,
+            log_dir: PathBuf::from(".flow"),
+            todo_path: PathBuf::from("TODO.md"),
+            tasks_path: PathBuf::from(TASKS_FILE),
+            project_dir: std::env::current_dir().unwrap_or_default(),
+            max_iterations: 1,
+            cycle: None,
+            playlist: Vec::new(),
+            start_step: None,
+            max_duration_secs: None,
+            max_turns: None,
+            max_cost_usd: None,
+            verbose: false,
+            debug_stream: false,
+            emit_events: false,
+            force: false,
+            display: true,
+            cancel: CancellationToken::new(),
+            observer: None,
+            record: false,
+            replay: None,
+        }
+    }
+
+    /// Directory for the JSONL log, progress, lock, and sessions.
+    #[must_use]
+    pub fn with_log_dir(mut self, log_dir: impl Into<PathBuf>) -> Self {
+        self.log_dir = log_dir.into();
+        self
+    }
+
+    /// TODO file given to the cycle selector and `stop_when` checks.
+    #[must_use]
+    pub fn with_todo_path(mut self, todo_path: impl Into<PathBuf>) -> Self {
+        self.todo_path = todo_path.into();
+        self
+    }
+
+    /// Structured task file used instead of the TODO file when it exists.
+    #[must_use]
+    pub fn with_tasks_path(mut self, tasks_path: impl Into<PathBuf>) -> Self {
+        self.tasks_path = tasks_path.into();
+        self
+    }
+
+    /// Project root: Claude Code, check commands (tests, lint, `success_when`,
+    /// `stop_when`), snapshots, and the run branch all work in it.
+    #[must_use]
+    pub fn with_project_dir(mut self, project_dir: impl Into<PathBuf>) -> Self {
+        self.project_dir = project_dir.into();
+        self
+    }
+
+    /// Maximum number of iterations to run.
+    #[must_use]
+    pub const fn with_max_iterations(mut self, max_iterations: u32) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Run this cycle every iteration instead of using AI selection.
+    #[must_use]
+    pub fn with_cycle(mut self, cycle: impl Into<String>) -> Self {
+        self.cycle = Some(cycle.into());
+        self
+    }
+
+    /// Run these cycles in order, one per iteration, wrapping around.
+    #[must_use]
+    pub fn with_playlist(mut self, playlist: Vec<String>) -> Self {
+        self.playlist = playlist;
+        self
+    }
+
+    /// Start the fixed cycle at this step on the first iteration.
+    #[must_use]
+    pub fn with_start_step(mut self, step: Option<String>) -> Self {
+        self.start_step = step;
+        self
+    }
+
+    /// Stop starting new iterations after this many seconds
+    /// (overrides `global.max_run_duration_secs`).
+    #[must_use]
+    pub const fn with_max_duration(mut self, secs: Option<u64>) -> Self {
+        self.max_duration_secs = secs;
+        self
+    }
+
+    /// Override `max_turns` / `max_cost_usd` for every cycle and step.
+    #[must_use]
+    pub const fn with_limit_overrides(
+        mut self,
+        max_turns: Option<u32>,
+        max_cost_usd: Option<f64>,
+    ) -> Self {
+        self.max_turns = max_turns;
+        self.max_cost_usd = max_cost_usd;
+        self
+    }
+
+    /// Show extra detail during execution (e.g., extended-thinking output).
+    #[must_use]
+    pub const fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Tee raw stream-JSON output into `<log_dir>/debug/`.
+    #[must_use]
+    pub const fn with_debug_stream(mut self, debug_stream: bool) -> Self {
+        self.debug_stream = debug_stream;
+        self
+    }
+
+    /// Write lifecycle events to stdout as NDJSON.
+    #[must_use]
+    pub const fn with_emit_events(mut self, emit_events: bool) -> Self {
+        self.emit_events = emit_events;
+        self
+    }
+
+    /// Steal the run lock if another run appears to hold it.
+    #[must_use]
+    pub const fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Render cycle output and status lines to stderr (default: true).
+    #[must_use]
+    pub const fn with_display(mut self, display: bool) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// Report stream events, step transitions, and gate decisions to `observer`.
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn ExecutionObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Token that stops the run once cancelled (e.g. from a Ctrl+C handler):
+    /// the running step is killed and no further cycles start.
+    #[must_use]
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Record Claude Code output, cycle selections, and LLM routing decisions
+    /// to `<log_dir>/recordings/<run_id>.jsonl` for a later replay.
+    #[must_use]
+    pub const fn with_record(mut self, record: bool) -> Self {
+        self.record = record;
+        self
+    }
+
+    /// Replay `recording` instead of running Claude Code. Cycle selection,
+    /// the playlist, and the iteration limit come from the recording.
+    #[must_use]
+    pub fn with_replay(mut self, recording: Recording) -> Self {
+        if let Some(max_iterations) = recording.max_iterations() {
+            self.max_iterations = max_iterations;
+        }
+        self.playlist = recording.playlist().to_vec();
+        self.replay = Some(Arc::new(recording));
+        self
+    }
+
+    /// The configuration this runner executes.
+    #[must_use]
+    pub const fn config(&self) -> &FlowConfig {
+        &self.config
+    }
+
+    /// Whether cycles are chosen by the AI selector.
+    #[must_use]
+    pub const fn uses_selector(&self) -> bool {
+        self.cycle.is_none() && self.playlist.is_empty()
+    }
+
+    /// Label used in run banners: the fixed cycle, or the playlist.
+    fn run_label(&self) -> Option<String> {
+        if self.playlist.is_empty() {
+            self.cycle.clone()
+        } else {
+            Some(self.playlist.join(" → "))
+        }
+    }
+
+    /// The log directory as a pathspec relative to the project, for keeping
+    /// it out of snapshots and commits (empty if it lies outside the project).
+    fn log_dir_excludes(&self) -> Vec<String> {
+        pathspec_within(&self.project_dir, &self.log_dir)
+            .into_iter()
+            .collect()
+    }
+
+    /// The replayed recording, or a new one for `run_id` when recording.
+    fn recording(&self, run_id: &str) -> Result<Option<Arc<Recording>>> {
+        if self.replay.is_some() || !self.record {
+            return Ok(self.replay.clone());
+        }
+        let recording = Recording::create(&recording_path(&self.log_dir, run_id))?;
+        eprintln!("Recording to {}", recording.path().display());
+        recording.record(&RecordedEntry::Run {
+            max_iterations: self.max_iterations,
+            playlist: self.playlist.clone(),
+        });
+        Ok(Some(Arc::new(recording)))
+    }
+
+    /// Create the cycle executor with display and debugging options, keeping
+    /// its state files in `log_dir`.
+    fn executor(&self, recording: Option<Arc<Recording>>, log_dir: &Path) -> CycleExecutor {
+        let debug_stream_dir =
+            (self.debug_stream || self.config.global.debug_stream).then(|| log_dir.join("debug"));
+        let executor = CycleExecutor::new(self.config.clone(), self.cancel.clone())
+            .with_verbose(self.verbose)
+            .with_display(self.display)
+            .with_observer(self.observer.clone())
+            .with_recording(recording)
+            .with_debug_stream(debug_stream_dir)
+            .with_limit_overrides(self.max_turns, self.max_cost_usd)
+            .with_sessions_path(Some(log_dir.join("sessions.json")))
+            .with_digests_path(Some(log_dir.join(DIGESTS_FILE)))
+            .with_snapshot_exclude(self.log_dir_excludes())
+            .with_project_dir(&self.project_dir);
+        match (&self.cycle, &self.start_step) {
+            (Some(cycle), Some(step)) => executor.with_start_step(cycle, step),
+            _ => executor,
+        }
+    }
+
+    /// Run the main loop to completion, a stop condition, a tripped gate, or cancellation.
+    ///
+    /// # Errors
+    /// Returns an error if the run lock is held, the log or progress files
+    /// can't be written, cycle selection fails, or a cycle can't be executed.
+    pub async fn run(&self) -> Result<RunReport> {
+        let run_id = new_run_id(chrono::Utc::now());
+        // A replay leaves the project alone: it logs to a directory of its
+        // own and skips task claims, the run branch, hooks, and notifications
+        let replaying = self.replay.is_some();
+        let log_dir = if replaying {
+            replay_log_dir(&self.log_dir, &run_id)
+        } else {
+            self.log_dir.clone()
+        };
+        // Held for the whole run; released on drop
+        let _lock = RunLock::acquire(&log_dir, self.force)?;
+        if replaying {
+            eprintln!("Replay log: {}", log_dir.display());
+        } else {
+            self.release_abandoned_work()?;
+        }
+        let config = &self.config;
+        let recording = self.recording(&run_id)?;
+        let executor = self.executor(recording.clone(), &log_dir);
+        let run_branch = if replaying {
+            None
+        } else {
+            start_run_branch(config, &self.project_dir, &run_id, &self.log_dir_excludes())?
+        };
+        let logger = JsonlLogger::new(&log_dir)
+            .context("Failed to initialize JSONL logger")?
+            .with_run_id(run_id.clone());
+        let progress_writer =
+            ProgressWriter::new(&log_dir).context("Failed to initialize progress writer")?;
+        let hooks = RunHooks {
+            events: EventEmitter::new(self.emit_events),
+            observer: self.observer.clone(),
+            ..if replaying {
+                RunHooks::default()
+            } else {
+                RunHooks {
+                    notifier: Notifier::new(config.notifications.clone()),
+                    commands: HookRunner::new(config.hooks.clone()),
+                    ..RunHooks::default()
+                }
+            }
+        };
+        let ctx = RunContext {
+            runner: self,
+            config,
+            executor: &executor,
+            logger: &logger,
+            progress_writer: &progress_writer,
+            cancel: &self.cancel,
+            run_branch: run_branch.as_ref(),
+            log_dir: &log_dir,
+            hooks: &hooks,
+            recording: recording.as_deref(),
+        };
+        let mut state = RunState {
+            iteration: 1,
+            progress: RunProgress {
+                git_sha: head_sha(&self.project_dir),
+                ..RunProgress::new(self.max_iterations)
+            },
+            run_history: Vec::new(),
+            gate_tripped: false,
+            arbitration: None,
+            claim: None,
+            task: None,
+        };
+        let run_label = self.run_label();
+
+        print_run_banner(
+            self.max_iterations,
+            run_label.as_deref(),
+            self.uses_selector(),
+        );
+        hooks.emit(&FlowEvent::RunStarted {
+            run_id: &run_id,
+            max_iterations: self.max_iterations,
+        });
+
+        self.run_iterations(&ctx, &mut state).await?;
+        if let Some(pending) = state.arbitration.take() {
+            keep_changes(&ctx, pending);
+        }
+
+        finalize_run(
+            &self.cancel,
+            &progress_writer,
+            &mut state.progress,
+            &logger,
+            &log_dir,
+            self.uses_selector(),
+            run_label.as_deref(),
+        );
+        notify_run_end(&hooks, &state.progress, state.gate_tripped);
+        if let Some(ref branch) = run_branch {
+            let interrupted = state.gate_tripped || self.cancel.is_cancelled();
+            finish_run_branch(config, branch, interrupted, run_label.as_deref());
+        }
+
+        Ok(RunReport {
+            run_id,
+            progress: state.progress,
+            gate_tripped: state.gate_tripped,
+        })
+    }
+
+    /// Return work an earlier, interrupted run left behind to the queue and
+    /// task files, and upgrade the log directory's state files.
+    fn release_abandoned_work(&self) -> Result<()> {
+        for name in TaskQueue::new(&self.log_dir).requeue_abandoned()? {
+            eprintln!("Requeued task '{name}' left active by an earlier run");
+        }
+        match finish_claims(&self.todo_path, false) {
+            Ok(0) => {}
+            Ok(n) => eprintln!(
+                "Released {n} task claim(s) in {} left by an earlier run",
+                self.todo_path.display()
+            ),
+            Err(e) => eprintln!("Warning: failed to release stale task claims: {e:#}"),
+        }
+        match release_in_progress(&self.tasks_path) {
+            Ok(ids) => {
+                for id in ids {
+                    eprintln!("Returned task '{id}' left in progress by an earlier run to pending");
+                }
+            }
+            Err(e) => eprintln!("Warning: failed to release stale task claims: {e:#}"),
+        }
+        for migration in migrate(&self.log_dir)? {
+            eprintln!(
+                "Upgraded {} to state version {}: {}",
+                self.log_dir.display(),
+                migration.from + 1,
+                migration.description
+            );
+        }
+        Ok(())
+    }
+
+    /// The `tasks.toml` task the selector named, if the file has one with that id.
+    fn structured_task(&self, id: &str) -> Option<StructuredTask> {
+        TaskFile::load(&self.tasks_path)
+            .ok()
+            .flatten()
+            .and_then(|file| file.get(id).cloned())
+    }
+
+    /// Mark the selector's task in progress: its `tasks.toml` status, or its
+    /// TODO.md checkbox. Returns the claim if one was taken (never when replaying).
+    fn claim_selected_task(
+        &self,
+        vars: &std::collections::HashMap<String, String>,
+    ) -> Option<TaskClaim> {
+        if self.replay.is_some() {
+            return None;
+        }
+        let nonempty = |name: &str| vars.get(name).filter(|v| !v.is_empty());
+        let (claim, result) = if let Some(id) = nonempty(SELECTED_TASK_ID_VAR) {
+            let claim = TaskClaim::Structured(id.clone());
+            (
+                claim,
+                set_status(&self.tasks_path, id, TaskStatus::InProgress),
+            )
+        } else {
+            let task = nonempty(SELECTED_TASK_VAR)?;
+            (TaskClaim::Todo, claim_task(&self.todo_path, task))
+        };
+        match result {
+            Ok(claimed) => claimed.then_some(claim),
+            Err(e) => {
+                eprintln!("Warning: failed to claim task: {e:#}");
+                None
+            }
+        }
+    }
+
+    /// Resolve a claim when its cycle ends: done when `completed`, otherwise
+    /// released for a later iteration.
+    fn finish_claim(&self, claim: &TaskClaim, completed: bool) {
+        let result = match claim {
+            TaskClaim::Todo => finish_claims(&self.todo_path, completed).map(|_| ()),
+            TaskClaim::Structured(id) => {
+                let status = if completed {
+                    TaskStatus::Done
+                } else {
+                    TaskStatus::Pending
+                };
+                set_status(&self.tasks_path, id, status).map(|_| ())
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Warning: failed to update task claim: {e:#}");
+        }
+    }
+
+    /// The main iteration loop. With `--cycles`, each iteration runs the
+    /// whole playlist in order, sharing the iteration's queued task.
+    async fn run_iterations(&self, ctx: &RunContext<'_>, state: &mut RunState) -> Result<()> {
+        let max_iterations = self.max_iterations;
+        for pass in 1.. {
+            // Dependent and recovery cycles count against the limit, except
+            // in playlist mode, where an iteration is one pass of the list
+            let iteration = if self.playlist.is_empty() {
+                state.iteration
+            } else {
+                pass
+            };
+            if iteration > max_iterations || self.cancel.is_cancelled() {
+                break;
+            }
+            if stop_condition_met(ctx, &mut state.progress).await {
+                break;
+            }
+
+            print_iteration_banner(iteration, max_iterations, &state.progress);
+            ctx.hooks.emit(&FlowEvent::IterationStarted {
+                iteration,
+                max_iterations,
+            });
+
+            state.task = pop_queued_task(ctx.log_dir)?.map(|task| IterationTask {
+                task,
+                succeeded: true,
+            });
+            for pick in 0..self.playlist.len().max(1) {
+                let succeeded = self.run_iteration_cycle(ctx, state, pick).await?;
+                if let Some(task) = state.task.as_mut() {
+                    task.succeeded &= succeeded;
+                }
+                if state.gate_tripped || self.cancel.is_cancelled() {
+                    break;
+                }
+            }
+            finish_iteration_task(ctx, state);
+            if state.gate_tripped || self.cancel.is_cancelled() {
+                return Ok(());
+            }
+
+            print_periodic_summary(
+                &state.progress,
+                &state.run_history,
+                max_iterations,
+                self.config.global.summary_interval,
+            );
+        }
+        Ok(())
+    }
+
+    /// Run one selected cycle of an iteration (the `pick`th playlist entry)
+    /// with the iteration's queued task, then the cycles it triggers unless
+    /// a gate tripped or the run was cancelled. Returns whether the selected
+    /// cycle succeeded.
+    async fn run_iteration_cycle(
+        &self,
+        ctx: &RunContext<'_>,
+        state: &mut RunState,
+        pick: usize,
+    ) -> Result<bool> {
+        let (cycle_name, selected_task) =
+            select_iteration_cycle(ctx, pick, state.iteration).await?;
+        state.progress.current_iteration = state.iteration;
+
+        // Build template variables for this cycle
+        let mut template_vars = build_template_vars(
+            &self.config.global.vars,
+            &self.project_dir,
+            &self.todo_path,
+            &cycle_name,
+            "",
+            state.iteration,
+            self.max_iterations,
+        );
+
+        let mut cycle_vars = template_vars.clone();
+        insert_task_vars(&mut cycle_vars, state.task.as_ref().map(|t| &t.task));
+        let structured = selected_task
+            .as_deref()
+            .and_then(|id| self.structured_task(id));
+        let (selected_text, selected_id) = structured.map_or_else(
+            || (selected_task.unwrap_or_default(), String::new()),
+            |t| (t.render(), t.id),
+        );
+        cycle_vars.insert(SELECTED_TASK_VAR.to_string(), selected_text);
+        cycle_vars.insert(SELECTED_TASK_ID_VAR.to_string(), selected_id);
+        insert_task_vars(&mut template_vars, None);
+        template_vars.insert(SELECTED_TASK_VAR.to_string(), String::new());
+        template_vars.insert(SELECTED_TASK_ID_VAR.to_string(), String::new());
+        state.claim = self.claim_selected_task(&cycle_vars);
+        let result = run_cycle(ctx, state, &cycle_name, &cycle_vars).await?;
+
+        // Check shutdown and gates before auto-triggering dependent cycles
+        if !state.gate_tripped && !self.cancel.is_cancelled() {
+            run_dependent_cycles(ctx, state, &result.cycle_name, &template_vars).await?;
+        }
+        Ok(result.success)
+    }
+}
+
+/// Format an exit code for display, returning "unknown" if the process was killed by signal.
+fn format_exit_code(exit_code: Option<i32>) -> String {
+    exit_code.map_or_else(|| "unknown".to_string(), |c| c.to_string())
+}
+
+/// Build a `CycleOutcome` from a `CycleResult` for JSONL logging.
+fn build_outcome(result: &CycleResult, iteration: u32) -> CycleOutcome {
+    let outcome_text = result.failure_reason.as_ref().map_or_else(
+        || result.result_text.clone(),
+        |reason| Some(format!("Failed: {reason}")),
+    );
+    let outcome_text = outcome_text.unwrap_or_else(|| {
+        if result.success {
+            "Completed successfully".to_string()
+        } else {
+            format!(
+                "Failed with exit code {}",
+                format_exit_code(result.exit_code)
+            )
+        }
+    });
+
+    CycleOutcome {
+        iteration,
+        cycle: result.cycle_name.clone(),
+        timestamp: chrono::Utc::now(),
+        outcome: outcome_text,
+        files_changed: result.files_changed.clone(),
+        tests_passed: result.tests_passed,
+        tests_failed: (result.tests_failed > 0).then_some(result.tests_failed),
+        duration_secs: result.duration_secs,
+        num_turns: result.num_turns,
+        total_cost_usd: result.total_cost_usd,
+        computed_cost_usd: result.computed_cost_usd,
+        usage: result.usage,
+        model: result.model.clone(),
+        claude_version: result.claude_version.clone(),
+        permission_denial_count: result.permission_denial_count,
+        permission_denials: result.permission_denials.clone(),
+        steps: (!result.steps.is_empty()).then(|| result.steps.clone()),
+        skipped_steps: (!result.skipped_steps.is_empty()).then(|| result.skipped_steps.clone()),
+        commands_run: truncate_commands(&result.commands_run),
+        tools_used: (!result.tools_used.is_empty()).then(|| result.tools_used.clone()),
+        run_id: None,
+        rolled_back: result.rolled_back.then_some(true),
+        chosen_sample: result.chosen_sample,
+        attempts: result.attempts,
+        verdict: result.verdict.clone(),
+        eval_scores: result.eval_scores.clone(),
+        test_delta: result.test_delta,
+        lint: result.lint.clone(),
+        subagent_count: result.subagent_count,
+        subagent_cost_usd: result.subagent_cost_usd,
+        git_sha_before: result.git_sha_before.clone(),
+        git_sha_after: result.git_sha_after.clone(),
+        lines_added: result.lines_added,
+        lines_removed: result.lines_removed,
+        prompt_hash: result.prompt_hash.clone(),
+        permissions_hash: result.permissions_hash.clone(),
+        failure_kind: (!result.success).then_some(if result.transient_error.is_some() {
+            FailureKind::Transient
+        } else {
+            FailureKind::Cycle
+        }),
+        flow_version: Some(FLOW_VERSION.to_string()),
+        os: Some(os_label()),
+        task: None,
+    }
+}
+
+/// Maximum number of shell commands recorded per log entry.
+const MAX_LOGGED_COMMANDS: usize = 50;
+
+/// Maximum length (in characters) of each shell command recorded in the log.
+const MAX_LOGGED_COMMAND_CHARS: usize = 200;
+
+/// Cap and truncate executed shell commands so a chatty cycle can't bloat the log.
+///
+/// Returns `None` when no commands ran. When commands are dropped, a final
+/// `"... (N more)"` marker records how many were omitted.
+fn truncate_commands(commands: &[String]) -> Option<Vec<String>> {
+    if commands.is_empty() {
+        return None;
+    }
+    let mut logged: Vec<String> = commands
+        .iter()
+        .take(MAX_LOGGED_COMMANDS)
+        .map(|cmd| {
+            if cmd.chars().count() > MAX_LOGGED_COMMAND_CHARS {
+                let head: String = cmd.chars().take(MAX_LOGGED_COMMAND_CHARS).collect();
+                format!("{head}...")
+            } else {
+                cmd.clone()
+            }
+        })
+        .collect();
+    if commands.len() > MAX_LOGGED_COMMANDS {
+        logged.push(format!(
+            "... ({} more)",
+            commands.len() - MAX_LOGGED_COMMANDS
+        ));
+    }
+    Some(logged)
+}
+
+/// A compact record of one cycle execution within the current run, for health tracking.
+struct RunOutcome {
+    /// Whether the cycle completed successfully
+    success: bool,
+    /// Whether a failure was a transient API error (neither a failure nor a
+    /// success for the streak)
+    transient: bool,
+    /// Whether a failure was handed to an `on_failure` recovery cycle, whose
+    /// outcome counts for the streak in its place
+    recovered: bool,
+}
+
+/// Check cumulative run health — returns Some(reason) if the run should stop.
+///
+/// Stops if the trailing window of outcomes contains `max_consecutive_failures`
+/// consecutive failures (cycles whose `success == false`). Successes reset the streak;
+/// transient API failures are skipped.
+fn check_run_health(history: &[RunOutcome], max_consecutive_failures: u32) -> Option<String> {
+    if max_consecutive_failures == 0 {
+        return None;
+    }
+    let mut consecutive = 0u32;
+    for outcome in history.iter().filter(|o| !o.transient && !o.recovered) {
+        if outcome.success {
+            consecutive = 0;
+        } else {
+            consecutive += 1;
+            if consecutive >= max_consecutive_failures {
+                return Some(format!(
+                    "Stopping run: {consecutive} consecutive cycle failures (threshold: {max_consecutive_failures}). \
+                     Fix the underlying issue before continuing."
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// Check if permission denials exceed the threshold. Returns the stop reason if so.
+fn check_denial_gate(
+    denials: u32,
+    max_denials: u32,
+    cycle_name: &str,
+    hooks: &RunHooks,
+) -> Option<String> {
+    if denials <= max_denials {
+        return None;
+    }
+    eprintln!(
+        "Stopping: {denials} permission denials in '{cycle_name}' exceeded threshold ({max_denials}). \
+         Fix permissions in cycles.toml before continuing."
+    );
+    let reason = format!("{denials} permission denials in '{cycle_name}'");
+    hooks.gate_tripped("permission_denials", &reason, Some(cycle_name));
+    Some(reason)
+}
+
+/// Print a startup banner when running multiple iterations.
+fn print_run_banner(max_iterations: u32, fixed_cycle: Option<&str>, use_selector: bool) {
+    if max_iterations <= 1 {
+        return;
+    }
+    if use_selector {
+        eprintln!(
+            "Starting autonomous run: up to {max_iterations} iterations with AI cycle selection"
+        );
+    } else {
+        eprintln!(
+            "Starting multi-iteration run: up to {max_iterations} iterations of '{}'",
+            fixed_cycle.unwrap_or("?")
+        );
+    }
+}
+
+/// Determine which cycle to run for this iteration.
+///
+/// Returns the fixed cycle name if `--cycle` (or a `--cycles` entry) was
+/// specified, or uses AI selection (falling back to `selector.fallback_cycle`
+/// when it fails), including the task the selector chose.
+async fn resolve_cycle_name(
+    config: &FlowConfig,
+    logger: &JsonlLogger,
+    fixed_cycle: Option<&str>,
+    todo_path: &Path,
+    tasks_path: &Path,
+) -> Result<CycleSelection> {
+    if let Some(name) = fixed_cycle {
+        return Ok(CycleSelection {
+            cycle: name.to_string(),
+            task: None,
+            reason: String::new(),
+            fallback: false,
+        });
+    }
+    let index = logger
+        .index()
+        .context("Failed to read log index for selector")?;
+    let recent = logger
+        .read_last(SELECTOR_RECENT_OUTCOMES)
+        .context("Failed to read log for selector")?;
+    let summary = LogSummary::from_index(&index, &recent);
+    let todo_content = std::fs::read_to_string(todo_path).unwrap_or_default();
+    let structured = TaskFile::load(tasks_path)?;
+    eprintln!(
+        "{} Selecting next cycle...",
+        theme().paint(Role::Warning, ">>>").bold()
+    );
+    let selection = select_with_fallback(
+        config,
+        select_cycle(config, &summary, &todo_content, structured.as_ref()),
+    )
+    .await
+    .context("Cycle selection failed")?;
+    if selection.fallback {
+        eprintln!(
+            "{} Falling back to '{}': {}",
+            theme().paint(Role::Warning, ">>>").bold(),
+            selection.cycle,
+            selection.reason
+        );
+    } else {
+        eprintln!(
+            "{} Selected '{}': {}",
+            theme().paint(Role::Success, ">>>").bold(),
+            selection.cycle,
+            selection.reason
+        );
+    }
+    if let Some(ref task) = selection.task {
+        eprintln!("    Task: {task}");
+    }
+    Ok(selection)
+}
+
+/// Update progress state after a cycle completes.
+fn update_progress_after_cycle(progress: &mut RunProgress, cycle_name: &str, result: &CycleResult) {
+    *progress
+        .cycles_executed
+        .entry(cycle_name.to_string())
+        .or_insert(0) += 1;
+    progress.total_duration_secs += result.duration_secs;
+    progress.total_cost_usd += result.total_cost_usd.unwrap_or(0.0);
+    progress.last_outcome.clone_from(&result.result_text);
+    progress.completed_steps.clone_from(&result.completed_steps);
+    progress.skipped_steps.clone_from(&result.skipped_steps);
+    progress.failed_step.clone_from(&result.failed_step);
+    if result.claude_version.is_some() {
+        progress.claude_version.clone_from(&result.claude_version);
+    }
+    if result.model.is_some() {
+        progress.model.clone_from(&result.model);
+    }
+    if result.git_sha_after.is_some() {
+        progress.git_sha.clone_from(&result.git_sha_after);
+    }
+}
+
+/// Digest old log entries for `context = "compact"` before a cycle that uses
+/// it. A failure only costs context size, so it is reported and the cycle runs.
+async fn compact_history(config: &FlowConfig, logger: &JsonlLogger) {
+    let settings = config.compaction.clone().unwrap_or_default();
+    match compact(logger, &settings).await {
+        Ok(digests) => {
+            for digest in digests {
+                eprintln!(
+                    "{} Compacted iterations {}–{} into a digest",
+                    theme().paint(Role::Header, ">>>").bold(),
+                    digest.first_iteration,
+                    digest.last_iteration
+                );
+            }
+        }
+        Err(e) => eprintln!(
+            "{} History compaction failed, continuing with uncompacted context: {e:#}",
+            theme().paint(Role::Warning, ">>>").bold()
+        ),
+    }
+}
+
+/// Execute a cycle with rich display and log the result. Returns the `CycleResult`.
+async fn execute_and_log(
+    executor: &CycleExecutor,
+    logger: &JsonlLogger,
+    cycle_name: &str,
+    iteration: &mut u32,
+    circuit_breaker_threshold: u32,
+    iteration_context: Option<(u32, u32)>,
+    template_vars: &std::collections::HashMap<String, String>,
+) -> Result<CycleResult> {
+    // Read log entries for context injection, only if some prompt uses them or
+    // the cycle gets lint feedback
+    let cycle = executor.config().get_cycle(cycle_name);
+    if cycle.is_some_and(CycleConfig::uses_compaction) {
+        compact_history(executor.config(), logger).await;
+    }
+    let linted = executor
+        .config()
+        .lint
+        .as_ref()
+        .is_some_and(|lint| lint.lints(cycle_name));
+    let uses_history = linted || cycle.is_some_and(CycleConfig::uses_history);
+    let log_entries: Vec<CycleOutcome> = if uses_history {
+        // A windowed cycle only needs the log's tail
+        cycle
+            .and_then(CycleConfig::history_window)
+            .map_or_else(
+                || logger.stream().map(Iterator::collect),
+                |window| logger.read_last(window),
+            )
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    let result = executor
+        .execute_with_display(
+            cycle_name,
+            circuit_breaker_threshold,
+            &log_entries,
+            iteration_context,
+            template_vars,
+        )
+        .await
+        .with_context(|| format!("Failed to execute cycle '{cycle_name}'"))?;
+
+    if let Some(activity) = render_activity_summary(&result) {
+        eprintln!("{activity}");
+    }
+    if let Some(tools) = render_tool_summary(&result) {
+        eprintln!("{tools}");
+    }
+
+    let mut outcome = build_outcome(&result, *iteration);
+    outcome.task = template_vars
+        .get(TASK_NAME_VAR)
+        .filter(|name| !name.is_empty())
+        .cloned();
+    logger
+        .append(&outcome)
+        .context("Failed to write to JSONL log")?;
+
+    // Print actionable permission fix suggestions
+    if let Some(count) = result.permission_denial_count {
+        if count > 0 {
+            eprintln!("Tip: Add permission strings to cycles.toml to avoid denials.");
+            eprintln!("     e.g. {}", suggest_permission_fix("Edit"));
+        }
+    }
+
+    *iteration += 1;
+
+    Ok(result)
+}
+
+/// Apply post-cycle checks: record outcome, check denial gate, health check.
+///
+/// Returns the stop reason if a gate fires, or `None` if the run should continue.
+///
+/// Individual cycle failures are recorded but do not immediately stop the run.
+/// Instead, the consecutive-failure health check (`max_consecutive_failures`)
+/// determines when to stop — e.g., default threshold 3 means 3 failures in a row.
+fn apply_cycle_gates(
+    result: &CycleResult,
+    cycle_name: &str,
+    run_history: &mut Vec<RunOutcome>,
+    max_denials: u32,
+    max_consecutive_failures: u32,
+    iteration: u32,
+    hooks: &RunHooks,
+) -> Option<String> {
+    if let Some(reason) = record_cycle_outcome(
+        result,
+        cycle_name,
+        run_history,
+        max_denials,
+        iteration,
+        hooks,
+    ) {
+        return Some(reason);
+    }
+
+    let reason = check_run_health(run_history, max_consecutive_failures)?;
+    eprintln!("{reason}");
+    hooks.gate_tripped("consecutive_failures", &reason, None);
+    Some(reason)
+}
+
+/// Record a cycle outcome in the run history and enforce the denial gate,
+/// without running the consecutive-failure health check. Returns the denial
+/// gate's stop reason, if it fired.
+fn record_cycle_outcome(
+    result: &CycleResult,
+    cycle_name: &str,
+    run_history: &mut Vec<RunOutcome>,
+    max_denials: u32,
+    iteration: u32,
+    hooks: &RunHooks,
+) -> Option<String> {
+    run_history.push(RunOutcome {
+        success: result.success,
+        transient: result.transient_error.is_some(),
+        recovered: false,
+    });
+
+    if !result.success {
+        eprintln!(
+            "Cycle '{cycle_name}' failed in iteration {iteration} (exit code {}).",
+            format_exit_code(result.exit_code)
+        );
+        if let Some(ref step) = result.failed_step {
+            eprintln!(
+                "Resume from the failed step with: flow --cycle {cycle_name} --from-step {step}"
+            );
+        }
+    }
+
+    check_denial_gate(
+        result.permission_denial_count.unwrap_or(0),
+        max_denials,
+        cycle_name,
+        hooks,
+    )
+}
+
+/// Outward-facing reporting for a run: notifications, `--emit-events`, and
+/// `[hooks]` commands, plus the embedder's observer.
+#[derive(Default)]
+struct RunHooks {
+    notifier: Notifier,
+    events: EventEmitter,
+    commands: HookRunner,
+    observer: Option<Arc<dyn ExecutionObserver>>,
+}
+
+impl RunHooks {
+    /// Emit a lifecycle event and run the hook commands it triggers.
+    fn emit(&self, event: &FlowEvent<'_>) {
+        self.events.emit(event);
+        self.commands.run(event);
+        if let (
+            Some(observer),
+            FlowEvent::GateTripped {
+                gate,
+                reason,
+                cycle,
+            },
+        ) = (&self.observer, event)
+        {
+            observer.on_gate(gate, reason, *cycle);
+        }
+    }
+
+    /// Report a stop gate that ends the run.
+    fn gate_tripped(&self, gate: &str, reason: &str, cycle: Option<&str>) {
+        self.emit(&FlowEvent::GateTripped {
+            gate,
+            reason,
+            cycle,
+        });
+        let notification =
+            Notification::new(NotificationEvent::RunStopped, "Flow run stopped", reason);
+        self.notifier.notify(&match cycle {
+            Some(cycle) => notification.with_cycle(cycle),
+            None => notification,
+        });
+    }
+
+    /// Report the cycle chosen for an iteration and why.
+    fn cycle_selected(&self, iteration: u32, cycle: &str, source: &str) {
+        self.emit(&FlowEvent::CycleSelected {
+            iteration,
+            cycle,
+            source,
+        });
+    }
+}
+
+/// Shared handles for one run.
+struct RunContext<'a> {
+    runner: &'a FlowRunner,
+    config: &'a FlowConfig,
+    executor: &'a CycleExecutor,
+    logger: &'a JsonlLogger,
+    progress_writer: &'a ProgressWriter,
+    cancel: &'a CancellationToken,
+    /// Branch this run commits onto (`global.git_branch_template`)
+    run_branch: Option<&'a RunBranch>,
+    log_dir: &'a std::path::Path,
+    hooks: &'a RunHooks,
+    /// `--record` capture or `--replay` source
+    recording: Option<&'a Recording>,
+}
+
+/// Mutable bookkeeping for one `flow` run.
+struct RunState {
+    /// Next iteration number; advanced by every executed cycle (including triggered ones)
+    iteration: u32,
+    /// Snapshot mirrored to `.flow/progress.json`
+    progress: RunProgress,
+    /// Outcomes of every executed cycle, for the consecutive-failure health check
+    run_history: Vec<RunOutcome>,
+    /// Set once a stop gate (permission denials, consecutive failures) ends the run
+    gate_tripped: bool,
+    /// Changes of the last cycle, if a reviewer `arbitrates` it
+    arbitration: Option<PendingArbitration>,
+    /// Task the selected cycle claimed, resolved as soon as that cycle ends
+    /// so the resolution is part of its commit
+    claim: Option<TaskClaim>,
+    /// Queued task of the current iteration
+    task: Option<IterationTask>,
+}
+
+/// A queued task and whether its iteration's selected cycles have all succeeded
+struct IterationTask {
+    task: QueuedTask,
+    succeeded: bool,
+}
+
+/// A cycle's changes awaiting the verdict of the cycle that `arbitrates` it
+struct PendingArbitration {
+    /// Cycle whose changes are pending
+    cycle: String,
+    /// Working tree before that cycle ran (`None` when replaying, which
+    /// neither commits nor rolls back)
+    snapshot: Option<Snapshot>,
+    /// Run-branch commit message held back until the verdict
+    message: Option<String>,
+    /// Queued task of the iteration that made the changes, archived once
+    /// the verdict is in
+    task: Option<IterationTask>,
+}
+
+impl RunState {
+    /// Record a gate's stop reason, if it fired. Returns whether the run must stop.
+    fn trip(&mut self, reason: Option<String>) -> bool {
+        if reason.is_some() {
+            self.progress.stop_reason = reason;
+            self.gate_tripped = true;
+        }
+        self.gate_tripped
+    }
+}
+
+/// Template variables for a cycle triggered from within an iteration.
+fn cycle_template_vars(
+    base: &std::collections::HashMap<String, String>,
+    cycle_name: &str,
+    iteration: u32,
+) -> std::collections::HashMap<String, String> {
+    let mut vars = base.clone();
+    vars.insert("cycle_name".to_string(), cycle_name.to_string());
+    vars.insert("step_name".to_string(), String::new());
+    vars.insert("iteration".to_string(), iteration.to_string());
+    vars
+}
+
+/// Execute and log a cycle, keeping `progress.json` current before and after.
+async fn execute_tracked(
+    ctx: &RunContext<'_>,
+    state: &mut RunState,
+    cycle_name: &str,
+    template_vars: &std::collections::HashMap<String, String>,
+) -> Result<CycleResult> {
+    state.progress.current_cycle = cycle_name.to_string();
+    let _ = ctx.progress_writer.write(&state.progress);
+
+    let iter_ctx = Some((
+        state.progress.current_iteration,
+        state.progress.max_iterations,
+    ));
+    let result = execute_and_log(
+        ctx.executor,
+        ctx.logger,
+        cycle_name,
+        &mut state.iteration,
+        ctx.config.global.circuit_breaker_repeated,
+        iter_ctx,
+        template_vars,
+    )
+    .await?;
+
+    if let Some(claim) = state.claim.take() {
+        let completed = result.success && !ctx.cancel.is_cancelled();
+        ctx.runner.finish_claim(&claim, completed);
+    }
+    commit_cycle(ctx, state, cycle_name);
+
+    update_progress_after_cycle(&mut state.progress, cycle_name, &result);
+    let _ = ctx.progress_writer.write(&state.progress);
+    ctx.hooks.emit(&FlowEvent::CycleCompleted {
+        iteration: state.iteration - 1,
+        cycle: cycle_name,
+        success: result.success,
+        exit_code: result.exit_code,
+        duration_secs: result.duration_secs,
+        cost_usd: result.total_cost_usd,
+    });
+    ctx.hooks
+        .notifier
+        .notify(&cycle_notification(cycle_name, &result));
+    ctx.hooks
+        .notifier
+        .check_budget(state.progress.total_cost_usd);
+    Ok(result)
+}
+
+/// Commit a finished cycle onto the run branch. While changes await an
+/// arbiter, the commit is held back for the verdict to make or drop.
+fn commit_cycle(ctx: &RunContext<'_>, state: &mut RunState, cycle_name: &str) {
+    let Some(branch) = ctx.run_branch else {
+        return;
+    };
+    let message = format!("flow: {cycle_name} (iteration {})", state.iteration - 1);
+    if let Some(pending) = state.arbitration.as_mut() {
+        pending.message.get_or_insert(message);
+    } else if let Err(e) = branch.commit_cycle(&message, &ctx.runner.log_dir_excludes()) {
+        eprintln!("Warning: failed to commit onto '{}': {e:#}", branch.name());
+    }
+}
+
+/// Execute a cycle and apply post-cycle gates; a tripped gate is recorded in `state`.
+///
+/// If the cycle fails and names an `on_failure` recovery cycle, the recovery
+/// runs before the consecutive-failure health check and the pair counts once:
+/// a successful recovery resets the failure streak, a failed one adds a single
+/// failure. Recovery cycles don't chain their own `on_failure`,
+/// and don't run after a transient API error that outlasted its retries.
+async fn run_cycle(
+    ctx: &RunContext<'_>,
+    state: &mut RunState,
+    cycle_name: &str,
+    template_vars: &std::collections::HashMap<String, String>,
+) -> Result<CycleResult> {
+    let global = &ctx.config.global;
+    let reviewing = begin_arbitration(ctx, state, cycle_name);
+    let result = execute_tracked(ctx, state, cycle_name, template_vars).await?;
+    if reviewing {
+        if let Some(pending) = state.arbitration.take() {
+            apply_verdict(ctx, state, pending, cycle_name, &result);
+        }
+    }
+
+    let recovery = ctx
+        .config
+        .get_cycle(cycle_name)
+        .and_then(|c| c.on_failure.as_deref());
+    match recovery {
+        Some(recovery)
+            if !result.success
+                && result.transient_error.is_none()
+                && !ctx.cancel.is_cancelled() =>
+        {
+            let denied = record_cycle_outcome(
+                &result,
+                cycle_name,
+                &mut state.run_history,
+                global.max_permission_denials,
+                state.iteration - 1,
+                ctx.hooks,
+            );
+            if state.trip(denied) {
+                return Ok(result);
+            }
+            // The recovery's outcome stands in for this failure in the streak
+            if let Some(failed) = state.run_history.last_mut() {
+                failed.recovered = true;
+            }
+            eprintln!("Running recovery cycle '{recovery}' after '{cycle_name}' failed");
+            ctx.hooks
+                .cycle_selected(state.iteration, recovery, "recovery");
+            let vars =
+                cycle_template_vars(template_vars, recovery, state.progress.current_iteration);
+            let recovery_result = execute_tracked(ctx, state, recovery, &vars).await?;
+            let stop = apply_cycle_gates(
+                &recovery_result,
+                recovery,
+                &mut state.run_history,
+                global.max_permission_denials,
+                global.max_consecutive_failures,
+                state.iteration - 1,
+                ctx.hooks,
+            );
+            state.trip(stop);
+        }
+        _ => {
+            let stop = apply_cycle_gates(
+                &result,
+                cycle_name,
+                &mut state.run_history,
+                global.max_permission_denials,
+                global.max_consecutive_failures,
+                state.iteration - 1,
+                ctx.hooks,
+            );
+            state.trip(stop);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Settle arbitration before `cycle_name` runs.
+///
+/// Returns whether `cycle_name` is the arbiter of the pending changes, which
+/// then stay pending until its verdict. Any other cycle ends the chance to
+/// arbitrate them, so they are kept, and if `cycle_name` is itself
+/// arbitrated, the working tree is snapshotted for its reviewer (except when
+/// replaying).
+fn begin_arbitration(ctx: &RunContext<'_>, state: &mut RunState, cycle_name: &str) -> bool {
+    let arbiter_of = ctx
+        .config
+        .get_cycle(cycle_name)
+        .and_then(|c| c.arbitrates.as_deref());
+    let reviewing = state
+        .arbitration
+        .as_ref()
+        .is_some_and(|pending| arbiter_of == Some(pending.cycle.as_str()));
+    if reviewing {
+        return true;
+    }
+    if let Some(pending) = state.arbitration.take() {
+        keep_changes(ctx, pending);
+    }
+    let arbitrated = ctx
+        .config
+        .cycles
+        .iter()
+        .any(|c| c.arbitrates.as_deref() == Some(cycle_name));
+    if arbitrated {
+        let snapshot = if ctx.recording.is_some_and(Recording::is_replay) {
+            Ok(None)
+        } else {
+            Snapshot::capture(&ctx.runner.project_dir, &ctx.runner.log_dir_excludes()).map(Some)
+        };
+        match snapshot {
+            Ok(snapshot) => {
+                state.arbitration = Some(PendingArbitration {
+                    cycle: cycle_name.to_string(),
+                    snapshot,
+                    message: None,
+                    task: None,
+                });
+            }
+            Err(e) => eprintln!("Warning: cannot snapshot '{cycle_name}' for arbitration: {e:#}"),
+        }
+    }
+    false
+}
+
+/// Keep changes no verdict was given on: make their held-back run-branch
+/// commit and archive their iteration's task by how its cycles went.
+fn keep_changes(ctx: &RunContext<'_>, pending: PendingArbitration) {
+    if let (Some(branch), Some(message)) = (ctx.run_branch, &pending.message) {
+        if let Err(e) = branch.commit_cycle(message, &ctx.runner.log_dir_excludes()) {
+            eprintln!("Warning: failed to commit onto '{}': {e:#}", branch.name());
+        }
+    }
+    if let Some(task) = pending.task {
+        finish_queued_task(ctx, &task.task, task.succeeded);
+    }
+}
+
+/// Commit or roll back the pending changes as the arbiter's verdict says,
+/// then archive the queued task of the iteration that made them.
+///
+/// The changes are kept if the arbiter failed, the run was cancelled, or no
+/// verdict was found in its result; a replay only reports the verdict.
+/// Rolling back also releases the task claim the snapshot brought back and
+/// fails the queued task.
+fn apply_verdict(
+    ctx: &RunContext<'_>,
+    state: &mut RunState,
+    pending: PendingArbitration,
+    arbiter: &str,
+    result: &CycleResult,
+) {
+    let target = &pending.cycle;
+    let verdict = match &result.verdict {
+        Some(verdict) if result.success && !ctx.cancel.is_cancelled() => verdict,
+        _ => {
+            eprintln!(
+                "{} '{arbiter}' gave no verdict; changes from '{target}' are left as they are",
+                theme().paint(Role::Warning, "Arbitration:").bold()
+            );
+            keep_changes(ctx, pending);
+            return;
+        }
+    };
+    let reasons = if verdict.reasons.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", verdict.reasons.join("; "))
+    };
+    let outcome = match (verdict.decision, &pending.snapshot) {
+        (_, None) => Ok("not applied when replaying"),
+        (Decision::Approve, Some(_)) => {
+            let message = format!("flow: {target} (approved by {arbiter})");
+            commit_all(
+                &ctx.runner.project_dir,
+                &message,
+                &ctx.runner.log_dir_excludes(),
+            )
+            .map(|committed| {
+                if committed {
+                    "changes committed"
+                } else {
+                    "nothing left to commit"
+                }
+            })
+        }
+        (Decision::Reject, Some(snapshot)) => snapshot.restore().and_then(|()| {
+            finish_claims(&ctx.runner.todo_path, false)?;
+            release_in_progress(&ctx.runner.tasks_path)?;
+            Ok("changes rolled back")
+        }),
+    };
+    match outcome {
+        Ok(what) => eprintln!(
+            "{} '{arbiter}' says {} for '{target}': {what}{reasons}",
+            theme().paint(Role::Header, "Arbitration:").bold(),
+            verdict.decision.as_str()
+        ),
+        Err(e) => eprintln!(
+            "Warning: failed to {} '{target}' changes: {e:#}",
+            verdict.decision.as_str()
+        ),
+    }
+
+    let rejected = verdict.decision == Decision::Reject && pending.snapshot.is_some();
+    match pending.task {
+        Some(task) => finish_queued_task(ctx, &task.task, task.succeeded && !rejected),
+        // Reviewed within the iteration that made the changes
+        None if rejected => {
+            if let Some(task) = state.task.as_mut() {
+                task.succeeded = false;
+            }
+        }
+        None => {}
+    }
+}
+
+/// Archive the iteration's queued task, unless changes of the iteration
+/// still await their arbiter: the verdict archives it then.
+fn finish_iteration_task(ctx: &RunContext<'_>, state: &mut RunState) {
+    let Some(task) = state.task.take() else {
+        return;
+    };
+    match state.arbitration.as_mut() {
+        Some(pending) => pending.task = Some(task),
+        None => finish_queued_task(ctx, &task.task, task.succeeded),
+    }
+}
+
+/// Take the next task from the log directory's queue, if any.
+fn pop_queued_task(log_dir: &Path) -> Result<Option<QueuedTask>> {
+    let task = TaskQueue::new(log_dir).pop()?;
+    if let Some(ref task) = task {
+        eprintln!(
+            "{} Queued task: {}",
+            theme().paint(Role::Header, ">>>").bold(),
+            task.name
+        );
+    }
+    Ok(task)
+}
+
+/// Archive a queued task by whether its iteration's selected cycles all
+/// succeeded; a cancelled run returns it to the queue for the next run.
+fn finish_queued_task(ctx: &RunContext<'_>, task: &QueuedTask, succeeded: bool) {
+    let disposition = if ctx.cancel.is_cancelled() {
+        TaskDisposition::Requeue
+    } else if succeeded {
+        TaskDisposition::Done
+    } else {
+        TaskDisposition::Failed
+    };
+    if let Err(e) = TaskQueue::new(ctx.log_dir).finish(task, disposition) {
+        eprintln!("Warning: failed to archive task '{}': {e:#}", task.name);
+    }
+}
+
+/// Pick the cycle to run: the `pick`th playlist entry, the fixed cycle, or
+/// the AI selector's choice — or, when replaying, the recorded cycle. Also
+/// returns the TODO.md task the selector chose, if any.
+async fn select_iteration_cycle(
+    ctx: &RunContext<'_>,
+    pick: usize,
+    iteration: u32,
+) -> Result<(String, Option<String>)> {
+    let runner = ctx.runner;
+    if let Some(replay) = ctx.recording.filter(|r| r.is_replay()) {
+        let (cycle_name, task) = replay.take_selection(iteration)?;
+        ctx.hooks.cycle_selected(iteration, &cycle_name, "replay");
+        return Ok((cycle_name, task));
+    }
+    let playlist = runner.playlist.get(pick).map(String::as_str);
+    let fixed = playlist.or(runner.cycle.as_deref());
+    let selection = resolve_cycle_name(
+        ctx.config,
+        ctx.logger,
+        fixed,
+        &runner.todo_path,
+        &runner.tasks_path,
+    )
+    .await?;
+    let source = match (playlist, fixed) {
+        (Some(_), _) => "playlist",
+        (None, Some(_)) => "fixed",
+        (None, None) if selection.fallback => "fallback",
+        (None, None) => "selector",
+    };
+    if let Some(recording) = ctx.recording {
+        recording.record(&RecordedEntry::Selection {
+            iteration,
+            cycle: selection.cycle.clone(),
+            task: selection.task.clone(),
+        });
+    }
+    ctx.hooks
+        .cycle_selected(iteration, &selection.cycle, source);
+    Ok((selection.cycle, selection.task))
+}
+
+/// Auto-trigger dependent cycles after a primary cycle completes, then any
+/// cycles whose `every_n_iterations` cadence is due.
+async fn run_dependent_cycles(
+    ctx: &RunContext<'_>,
+    state: &mut RunState,
+    completed_cycle: &str,
+    base_template_vars: &std::collections::HashMap<String, String>,
+) -> Result<()> {
+    let log_entries = ctx
+        .logger
+        .read_all()
+        .context("Failed to read log for frequency check")?;
+    let triggered = find_triggered_cycles(ctx.config, completed_cycle, &log_entries);
+    for dep_cycle in triggered {
+        if ctx.cancel.is_cancelled() {
+            break;
+        }
+        eprintln!("Auto-triggering dependent cycle: {dep_cycle}");
+        ctx.hooks
+            .cycle_selected(state.iteration, dep_cycle, "triggered");
+        let dep_vars = cycle_template_vars(
+            base_template_vars,
+            dep_cycle,
+            state.progress.current_iteration,
+        );
+        run_cycle(ctx, state, dep_cycle, &dep_vars).await?;
+        if state.gate_tripped {
+            return Ok(());
+        }
+    }
+
+    // Periodic cycles, checked against the log as it stands after any dependents ran
+    let log_entries = ctx
+        .logger
+        .read_all()
+        .context("Failed to read log for periodic check")?;
+    for periodic_cycle in find_periodic_cycles(ctx.config, &log_entries) {
+        if ctx.cancel.is_cancelled() {
+            break;
+        }
+        eprintln!("Auto-triggering periodic cycle: {periodic_cycle}");
+        ctx.hooks
+            .cycle_selected(state.iteration, periodic_cycle, "periodic");
+        let vars = cycle_template_vars(
+            base_template_vars,
+            periodic_cycle,
+            state.progress.current_iteration,
+        );
+        run_cycle(ctx, state, periodic_cycle, &vars).await?;
+        if state.gate_tripped {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Print a periodic run summary if the completed iteration is at the configured interval.
+fn print_periodic_summary(
+    progress: &RunProgress,
+    run_history: &[RunOutcome],
+    max_iterations: u32,
+    summary_interval: u32,
+) {
+    if !should_print_summary(progress.current_iteration, summary_interval) {
+        return;
+    }
+    #[allow(clippy::cast_possible_truncation)] // bounded by max_iterations (u32)
+    let successes = run_history.iter().filter(|o| o.success).count() as u32;
+    #[allow(clippy::cast_possible_truncation)]
+    let failures = run_history.iter().filter(|o| !o.success).count() as u32;
+    let summary = crate::cli::render_run_summary(
+        progress.current_iteration,
+        max_iterations,
+        progress.total_cost_usd,
+        &progress.cycles_executed,
+        successes,
+        failures,
+        progress.total_duration_secs,
+        progress_eta(progress).as_deref(),
+    );
+    eprintln!("\n{summary}");
+}
+
+/// Print the `>>> Iteration N/M` banner (with ETA once one can be estimated).
+fn print_iteration_banner(iteration: u32, max_iterations: u32, progress: &RunProgress) {
+    if max_iterations <= 1 {
+        return;
+    }
+    let eta = progress_eta(progress)
+        .map(|eta| format!(" · ETA {eta}"))
+        .unwrap_or_default();
+    eprintln!(
+        "\n{} Iteration {iteration}/{max_iterations}{}",
+        theme().paint(Role::Header, ">>>").bold(),
+        eta.dimmed()
+    );
+}
+
+/// Human-readable ETA for the run, if enough iterations have completed to estimate one.
+fn progress_eta(progress: &RunProgress) -> Option<String> {
+    progress
+        .estimated_remaining_secs(chrono::Utc::now())
+        .map(|secs| crate::cli::format_eta(secs, chrono::Local::now()))
+}
+
+/// Check if a periodic run summary should be printed at this iteration.
+///
+/// Returns `true` when `interval > 0` and `completed_iteration` is a multiple of `interval`.
+const fn should_print_summary(completed_iteration: u32, interval: u32) -> bool {
+    interval > 0 && completed_iteration > 0 && completed_iteration.is_multiple_of(interval)
+}
+
+/// Write final progress state, print the completion message, and persist
+/// the run summary to `<log_dir>/last-run-summary.md`.
+fn finalize_run(
+    cancel: &CancellationToken,
+    progress_writer: &ProgressWriter,
+    progress: &mut RunProgress,
+    logger: &JsonlLogger,
+    log_dir: &std::path::Path,
+    use_selector: bool,
+    fixed_cycle: Option<&str>,
+) {
+    let max_iterations = progress.max_iterations;
+    if cancel.is_cancelled() {
+        progress.current_status = RunStatus::Stopped;
+        let _ = progress_writer.write(progress);
+        let _ = progress_writer.delete();
+        eprintln!("\nRun interrupted by Ctrl+C");
+    } else if let Some(ref reason) = progress.stop_reason {
+        progress.current_status = RunStatus::Stopped;
+        let _ = progress_writer.write(progress);
+        let _ = progress_writer.delete();
+        eprintln!("\nRun stopped: {reason}");
+    } else {
+        progress.current_status = RunStatus::Completed;
+        let _ = progress_writer.write(progress);
+        let _ = progress_writer.delete();
+
+        if max_iterations > 1 {
+            if use_selector {
+                eprintln!("\nCompleted {max_iterations} autonomous iteration(s)");
+            } else {
+                eprintln!(
+                    "\nCompleted {max_iterations} iteration(s) of '{}'",
+                    fixed_cycle.unwrap_or("?")
+                );
+            }
+        }
+    }
+
+    write_last_run_summary(log_dir, logger, progress);
+}
+
+/// Check run-level stop conditions before starting the next iteration.
+///
+/// The wall-clock limit (`--max-duration` / `global.max_run_duration_secs`)
+/// applies always; `global.stop_when` is only evaluated between iterations,
+/// i.e. once at least one cycle has completed.
+async fn check_stop_conditions(
+    config: &FlowConfig,
+    runner: &FlowRunner,
+    progress: &RunProgress,
+) -> Option<String> {
+    let max_run_duration = runner
+        .max_duration_secs
+        .or(config.global.max_run_duration_secs);
+    if let Some(reason) = run_duration_exceeded(progress, max_run_duration, chrono::Utc::now()) {
+        return Some(reason);
+    }
+    match config.global.stop_when {
+        Some(ref stop_when) if !progress.cycles_executed.is_empty() => {
+            let source = if runner.tasks_path.exists() {
+                &runner.tasks_path
+            } else {
+                &runner.todo_path
+            };
+            check_stop_when(stop_when, source, &runner.project_dir).await
+        }
+        _ => None,
+    }
+}
+
+/// Record (and emit) a run-level stop reason if one applies. Returns whether to stop.
+async fn stop_condition_met(ctx: &RunContext<'_>, progress: &mut RunProgress) -> bool {
+    progress.stop_reason = check_stop_conditions(ctx.config, ctx.runner, progress).await;
+    let Some(ref reason) = progress.stop_reason else {
+        return false;
+    };
+    ctx.hooks.emit(&FlowEvent::GateTripped {
+        gate: "stop_condition",
+        reason,
+        cycle: None,
+    });
+    true
+}
+
+/// Return a stop reason once the run has been going for at least `limit_secs`.
+fn run_duration_exceeded(
+    progress: &RunProgress,
+    limit_secs: Option<u64>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<String> {
+    let limit = limit_secs?;
+    let elapsed = u64::try_from((now - progress.started_at).num_seconds()).unwrap_or(0);
+    (elapsed >= limit).then(|| format!("max run duration of {limit}s reached after {elapsed}s"))
+}
+
+/// Render the run summary and per-cycle table to `<log_dir>/last-run-summary.md`.
+///
+/// Only log entries written since the run started are included. Failures are
+/// reported as warnings — the summary is a convenience, not part of the run.
+fn write_last_run_summary(log_dir: &std::path::Path, logger: &JsonlLogger, progress: &RunProgress) {
+    let outcomes: Vec<CycleOutcome> = logger
+        .read_all()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|o| o.timestamp >= progress.started_at)
+        .collect();
+    let path = log_dir.join("last-run-summary.md");
+    let markdown = crate::cli::render_run_summary_markdown(progress, &outcomes);
+    match std::fs::write(&path, markdown) {
+        Ok(()) => eprintln!("Run summary written to {}", path.display()),
+        Err(e) => eprintln!("Warning: could not write {}: {e}", path.display()),
+    }
+}
+
+/// `cycle_completed` / `cycle_failed` notification for a finished cycle.
+fn cycle_notification(cycle_name: &str, result: &CycleResult) -> Notification {
+    let (event, title, body) = if result.success {
+        (
+            NotificationEvent::CycleCompleted,
+            format!("Cycle '{cycle_name}' completed"),
+            result
+                .result_text
+                .clone()
+                .unwrap_or_else(|| "Completed successfully".to_string()),
+        )
+    } else {
+        (
+            NotificationEvent::CycleFailed,
+            format!("Cycle '{cycle_name}' failed"),
+            result
+                .failure_reason
+                .clone()
+                .unwrap_or_else(|| format!("Exit code {}", format_exit_code(result.exit_code))),
+        )
+    };
+    Notification::new(event, &title, &body)
+        .with_cycle(cycle_name)
+        .with_cost(result.total_cost_usd)
+}
+
+/// Emit `run_finished`, and notify that the run completed or was stopped by a
+/// stop condition. Ctrl+C interruptions don't notify: the user is already at
+/// the terminal. Tripped gates have already notified.
+fn notify_run_end(hooks: &RunHooks, progress: &RunProgress, gate_tripped: bool) {
+    hooks.emit(&FlowEvent::RunFinished {
+        status: progress.current_status.clone(),
+        total_cost_usd: progress.total_cost_usd,
+        stop_reason: progress.stop_reason.as_deref(),
+    });
+    let notifier = &hooks.notifier;
+    match (&progress.current_status, &progress.stop_reason) {
+        (RunStatus::Completed, _) => notifier.notify(
+            &Notification::new(
+                NotificationEvent::RunCompleted,
+                "Flow run completed",
+                &format!(
+                    "{} cycle(s), ${:.2}",
+                    progress.cycles_executed.values().sum::<u32>(),
+                    progress.total_cost_usd
+                ),
+            )
+            .with_cost(Some(progress.total_cost_usd)),
+        ),
+        (RunStatus::Stopped, Some(reason)) if !gate_tripped => notifier.notify(
+            &Notification::new(NotificationEvent::RunStopped, "Flow run stopped", reason)
+                .with_cost(Some(progress.total_cost_usd)),
+        ),
+        _ => {}
+    }
+}
+
+/// Create the run branch when `global.git_branch_template` is set.
+fn start_run_branch(
+    config: &FlowConfig,
+    project_dir: &std::path::Path,
+    run_id: &str,
+    exclude: &[String],
+) -> Result<Option<RunBranch>> {
+    let Some(ref template) = config.global.git_branch_template else {
+        return Ok(None);
+    };
+    let branch = RunBranch::start(project_dir, &branch_name(template, run_id), exclude)?;
+    eprintln!(
+        "Working on branch '{}' (from '{}')",
+        branch.name(),
+        branch.base()
+    );
+    Ok(Some(branch))
+}
+
+/// Apply `global.git_on_finish` to the run branch. Interrupted runs and runs
+/// ended by a gate are left on the run branch for inspection.
+fn finish_run_branch(
+    config: &FlowConfig,
+    branch: &RunBranch,
+    interrupted: bool,
+    run_label: Option<&str>,
+) {
+    let Some(how) = config.global.git_on_finish else {
+        eprintln!("Run commits are on branch '{}'", branch.name());
+        return;
+    };
+    if interrupted {
+        eprintln!(
+            "Run did not finish; leaving commits on branch '{}'",
+            branch.name()
+        );
+        return;
+    }
+    let title = format!("flow: {}", run_label.unwrap_or("autonomous run"));
+    match branch.finish(how, &title) {
+        Ok(()) => match how {
+            GitFinish::FastForward => {
+                eprintln!("Fast-forwarded '{}' to '{}'", branch.base(), branch.name());
+            }
+            GitFinish::PullRequest => {
+                eprintln!("Opened a pull request for '{}'", branch.name());
+            }
+        },
+        Err(e) => eprintln!("Warning: {e:#} (commits remain on '{}')", branch.name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycle::arbiter::Verdict;
+    use crate::queue::QUEUE_DIR;
+    use std::collections::BTreeMap;
+
+    // --- build_outcome tests ---
+
+    #[test]
+    fn test_build_outcome_success() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stderr: String::new(),
+            duration_secs: 120,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(outcome.cycle, "coding");
+        assert_eq!(outcome.iteration, 1);
+        assert_eq!(outcome.outcome, "Completed successfully");
+        assert_eq!(outcome.duration_secs, 120);
+        assert!(outcome.files_changed.is_empty());
+    }
+
+    #[test]
+    fn test_build_outcome_failure() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: false,
+            exit_code: Some(1),
+            stderr: "error".to_string(),
+            duration_secs: 30,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+
+        let outcome = build_outcome(&result, 3);
+        assert_eq!(outcome.outcome, "Failed with exit code 1");
+        assert_eq!(outcome.iteration, 3);
+        assert_eq!(outcome.failure_kind, Some(FailureKind::Cycle));
+    }
+
+    #[test]
+    fn test_build_outcome_classifies_transient_failure() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: false,
+            exit_code: Some(1),
+            result_text: Some("API Error: 529 Overloaded".to_string()),
+            transient_error: Some("overloaded".to_string()),
+            ..Default::default()
+        };
+
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(outcome.failure_kind, Some(FailureKind::Transient));
+        assert!(!outcome.is_success());
+        assert_eq!(
+            build_outcome(&CycleResult::default(), 1).failure_kind,
+            Some(FailureKind::Cycle)
+        );
+    }
+
+    #[test]
+    fn test_environment_metadata_reaches_outcome_and_progress() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            model: Some("claude-opus-4-6".to_string()),
+            claude_version: Some("2.0.14".to_string()),
+            git_sha_after: Some("abc123".to_string()),
+            ..Default::default()
+        };
+
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(outcome.flow_version.as_deref(), Some(FLOW_VERSION));
+        assert_eq!(outcome.claude_version.as_deref(), Some("2.0.14"));
+        assert_eq!(outcome.os, Some(os_label()));
+
+        let mut progress = RunProgress::new(5);
+        update_progress_after_cycle(&mut progress, "coding", &result);
+        assert_eq!(progress.model.as_deref(), Some("claude-opus-4-6"));
+        assert_eq!(progress.claude_version.as_deref(), Some("2.0.14"));
+        assert_eq!(progress.git_sha.as_deref(), Some("abc123"));
+
+        // A cycle that reports nothing keeps the last known values
+        update_progress_after_cycle(&mut progress, "coding", &CycleResult::default());
+        assert_eq!(progress.model.as_deref(), Some("claude-opus-4-6"));
+    }
+
+    #[test]
+    fn test_build_outcome_failure_reason_overrides_result_text() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: false,
+            exit_code: Some(0),
+            result_text: Some("All done!".to_string()),
+            failure_reason: Some("0 tests passed, expected at least 1".to_string()),
+            ..Default::default()
+        };
+
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(
+            outcome.outcome,
+            "Failed: 0 tests passed, expected at least 1"
+        );
+        assert!(!outcome.is_success());
+    }
+
+    #[test]
+    fn test_build_outcome_killed_by_signal() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: false,
+            exit_code: None,
+            stderr: String::new(),
+            duration_secs: 5,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(outcome.outcome, "Failed with exit code unknown");
+    }
+
+    #[test]
+    fn test_build_outcome_uses_result_text_when_present() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stderr: String::new(),
+            duration_secs: 120,
+            result_text: Some("Implemented feature X with 5 tests".to_string()),
+            num_turns: Some(53),
+            total_cost_usd: Some(2.15),
+            permission_denial_count: Some(3),
+            permission_denials: Some(vec![
+                "Edit".to_string(),
+                "Bash".to_string(),
+                "Edit".to_string(),
+            ]),
+            files_changed: vec!["src/main.rs".to_string()],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(outcome.outcome, "Implemented feature X with 5 tests");
+        assert_eq!(outcome.num_turns, Some(53));
+        assert_eq!(outcome.total_cost_usd, Some(2.15));
+        assert_eq!(outcome.permission_denial_count, Some(3));
+        assert_eq!(outcome.permission_denials.as_ref().unwrap().len(), 3);
+        assert_eq!(outcome.files_changed, vec!["src/main.rs"]);
+    }
+
+    #[test]
+    fn test_build_outcome_propagates_files_changed() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stderr: String::new(),
+            duration_secs: 60,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![
+                "src/main.rs".to_string(),
+                "src/lib.rs".to_string(),
+                "tests/foo.rs".to_string(),
+            ],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(
+            outcome.files_changed,
+            vec!["src/main.rs", "src/lib.rs", "tests/foo.rs"]
+        );
+    }
+
+    #[test]
+    fn test_build_outcome_propagates_tests_passed() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stderr: String::new(),
+            duration_secs: 60,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 99,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(outcome.tests_passed, 99);
+    }
+
+    #[test]
+    fn test_build_outcome_propagates_commands_run() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stderr: String::new(),
+            duration_secs: 60,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 0,
+            commands_run: vec!["cargo test".to_string(), "git status".to_string()],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: Some(1),
+            subagent_cost_usd: Some(0.4),
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::from([("Bash".to_string(), 2)]),
+        };
+        let outcome = build_outcome(&result, 1);
+        assert_eq!(
+            outcome.commands_run,
+            Some(vec!["cargo test".to_string(), "git status".to_string()])
+        );
+        assert_eq!(outcome.tools_used, Some(result.tools_used));
+        assert_eq!(outcome.subagent_count, Some(1));
+        assert_eq!(outcome.subagent_cost_usd, Some(0.4));
+    }
+
+    // --- truncate_commands tests ---
+
+    #[test]
+    fn test_truncate_commands_empty_is_none() {
+        assert_eq!(truncate_commands(&[]), None);
+    }
+
+    #[test]
+    fn test_truncate_commands_caps_count() {
+        let commands: Vec<String> = (0..60).map(|i| format!("echo {i}")).collect();
+        let logged = truncate_commands(&commands).unwrap();
+        assert_eq!(logged.len(), MAX_LOGGED_COMMANDS + 1);
+        assert_eq!(logged[0], "echo 0");
+        assert_eq!(logged.last().unwrap(), "... (10 more)");
+    }
+
+    #[test]
+    fn test_truncate_commands_shortens_long_commands() {
+        let long = "x".repeat(500);
+        let logged = truncate_commands(&[long]).unwrap();
+        assert_eq!(logged[0].chars().count(), MAX_LOGGED_COMMAND_CHARS + 3);
+        assert!(logged[0].ends_with("..."));
+    }
+
+    // --- format_exit_code tests ---
+
+    #[test]
+    fn test_format_exit_code_some() {
+        assert_eq!(format_exit_code(Some(0)), "0");
+        assert_eq!(format_exit_code(Some(1)), "1");
+        assert_eq!(format_exit_code(Some(127)), "127");
+    }
+
+    #[test]
+    fn test_format_exit_code_none() {
+        assert_eq!(format_exit_code(None), "unknown");
+    }
+
+    // --- check_denial_gate tests ---
+
+    #[test]
+    fn test_check_denial_gate_below_threshold_does_not_exit() {
+        // Should return normally when denials <= max_denials
+        assert!(check_denial_gate(0, 10, "coding", &RunHooks::default()).is_none());
+        assert!(check_denial_gate(5, 10, "coding", &RunHooks::default()).is_none());
+        // equal is not exceeded
+        assert!(check_denial_gate(10, 10, "coding", &RunHooks::default()).is_none());
+    }
+
+    #[test]
+    fn test_check_denial_gate_above_threshold_returns_reason() {
+        let reason = check_denial_gate(11, 10, "coding", &RunHooks::default()).unwrap();
+        assert!(reason.contains("11 permission denials"), "{reason}");
+    }
+
+    #[test]
+    fn test_check_denial_gate_reports_to_observer() {
+        use crate::cycle::observer::tests::RecordingObserver;
+
+        let observer = Arc::new(RecordingObserver::default());
+        let hooks = RunHooks {
+            observer: Some(observer.clone()),
+            ..RunHooks::default()
+        };
+        assert!(check_denial_gate(11, 10, "coding", &hooks).is_some());
+        assert_eq!(
+            *observer.calls.lock().unwrap(),
+            ["gate permission_denials coding"]
+        );
+    }
+
+    // --- run_duration_exceeded tests ---
+
+    #[test]
+    fn test_run_duration_not_exceeded() {
+        let progress = RunProgress::new(10);
+        let now = progress.started_at + chrono::Duration::seconds(59);
+        assert!(run_duration_exceeded(&progress, Some(60), now).is_none());
+    }
+
+    #[test]
+    fn test_run_duration_exceeded_returns_reason() {
+        let progress = RunProgress::new(10);
+        let now = progress.started_at + chrono::Duration::seconds(61);
+        let reason = run_duration_exceeded(&progress, Some(60), now).unwrap();
+        assert!(reason.contains("max run duration"), "{reason}");
+    }
+
+    #[test]
+    fn test_run_duration_no_limit() {
+        let progress = RunProgress::new(10);
+        let now = progress.started_at + chrono::Duration::days(7);
+        assert!(run_duration_exceeded(&progress, None, now).is_none());
+    }
+
+    // --- should_print_summary tests ---
+
+    #[test]
+    fn test_should_print_summary_at_interval() {
+        assert!(should_print_summary(5, 5));
+        assert!(should_print_summary(10, 5));
+    }
+
+    #[test]
+    fn test_should_print_summary_not_at_interval() {
+        assert!(!should_print_summary(3, 5));
+        assert!(!should_print_summary(1, 5));
+    }
+
+    #[test]
+    fn test_should_print_summary_disabled_when_zero() {
+        assert!(!should_print_summary(5, 0));
+        assert!(!should_print_summary(10, 0));
+    }
+
+    // --- check_run_health tests ---
+
+    #[test]
+    fn test_run_health_ok_when_all_succeed() {
+        let history = vec![
+            RunOutcome {
+                success: true,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: true,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: true,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        assert!(check_run_health(&history, 3).is_none());
+    }
+
+    #[test]
+    fn test_run_health_stops_on_consecutive_failures() {
+        let history = vec![
+            RunOutcome {
+                success: true,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        // 3 consecutive failures at the end — should stop
+        assert!(check_run_health(&history, 3).is_some());
+    }
+
+    #[test]
+    fn test_run_health_does_not_stop_below_threshold() {
+        let history = vec![
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        // Only 2 consecutive failures, threshold is 3
+        assert!(check_run_health(&history, 3).is_none());
+    }
+
+    #[test]
+    fn test_run_health_resets_on_success() {
+        let history = vec![
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: true,
+                transient: false,
+                recovered: false,
+            }, // resets the streak
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        // Streak is only 2 (after the success) — should not stop
+        assert!(check_run_health(&history, 3).is_none());
+    }
+
+    #[test]
+    fn test_run_health_skips_transient_failures() {
+        let failure = || RunOutcome {
+            success: false,
+            transient: false,
+            recovered: false,
+        };
+        let transient = || RunOutcome {
+            success: false,
+            transient: true,
+            recovered: false,
+        };
+        let history = vec![failure(), transient(), transient(), failure()];
+        // Transient failures neither count nor reset the streak
+        assert!(check_run_health(&history, 3).is_none());
+        let history = vec![failure(), transient(), failure(), transient(), failure()];
+        assert!(check_run_health(&history, 3).is_some());
+    }
+
+    #[test]
+    fn test_run_health_counts_recovered_failure_once() {
+        let failure = |recovered| RunOutcome {
+            success: false,
+            transient: false,
+            recovered,
+        };
+        // A failed cycle plus its failed recovery is one failure
+        assert!(check_run_health(&[failure(true), failure(false)], 2).is_none());
+        let history = vec![failure(true), failure(false), failure(true), failure(false)];
+        assert!(check_run_health(&history, 2).is_some());
+    }
+
+    #[test]
+    fn test_run_health_empty_history_is_ok() {
+        assert!(check_run_health(&[], 3).is_none());
+    }
+
+    #[test]
+    fn test_run_health_returns_message_with_count() {
+        let history = vec![
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        let msg = check_run_health(&history, 3).unwrap();
+        assert!(
+            msg.contains('3'),
+            "Message should mention failure count: {msg}"
+        );
+    }
+
+    #[test]
+    fn test_run_health_disabled_when_zero() {
+        let history = vec![
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        // max_consecutive_failures = 0 disables the check
+        assert!(check_run_health(&history, 0).is_none());
+    }
+
+    #[test]
+    fn test_apply_cycle_gates_records_failure_in_history() {
+        // After the fix, failures are recorded but do not call process::exit
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: false,
+            exit_code: Some(1),
+            stderr: String::new(),
+            duration_secs: 10,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+
+        let mut run_history = Vec::new();
+        // With max_consecutive_failures high enough, a single failure should not stop
+        let stop = apply_cycle_gates(
+            &result,
+            "coding",
+            &mut run_history,
+            10,
+            3,
+            1,
+            &RunHooks::default(),
+        );
+
+        assert!(stop.is_none());
+        assert_eq!(run_history.len(), 1);
+        assert!(
+            !run_history[0].success,
+            "Failure should be recorded in history"
+        );
+    }
+
+    #[test]
+    fn test_record_cycle_outcome_skips_health_check() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: false,
+            exit_code: Some(1),
+            ..Default::default()
+        };
+        // Two prior failures: with max_consecutive_failures = 3, a health check
+        // here would stop. Recording alone must leave the decision to the caller.
+        let mut run_history = vec![
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        let stop = record_cycle_outcome(
+            &result,
+            "coding",
+            &mut run_history,
+            10,
+            3,
+            &RunHooks::default(),
+        );
+
+        assert!(stop.is_none());
+        assert_eq!(run_history.len(), 3);
+        assert!(check_run_health(&run_history, 3).is_some());
+    }
+
+    #[test]
+    fn test_cycle_template_vars_override_cycle_and_iteration() {
+        let mut base = std::collections::HashMap::new();
+        base.insert("cycle_name".to_string(), "coding".to_string());
+        base.insert("step_name".to_string(), "plan".to_string());
+        base.insert("project".to_string(), "flow".to_string());
+
+        let vars = cycle_template_vars(&base, "fixup", 4);
+        assert_eq!(vars["cycle_name"], "fixup");
+        assert_eq!(vars["step_name"], "");
+        assert_eq!(vars["iteration"], "4");
+        assert_eq!(vars["project"], "flow");
+    }
+
+    #[test]
+    fn test_apply_cycle_gates_records_success_in_history() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: true,
+            exit_code: Some(0),
+            stderr: String::new(),
+            duration_secs: 60,
+            result_text: None,
+            num_turns: None,
+            total_cost_usd: None,
+            permission_denial_count: None,
+            permission_denials: None,
+            files_changed: vec![],
+            tests_passed: 0,
+            commands_run: vec![],
+            tests_failed: 0,
+            failure_reason: None,
+            completed_steps: Vec::new(),
+            failed_step: None,
+            rolled_back: false,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            transient_error: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            claude_version: None,
+            skipped_steps: Vec::new(),
+            steps: Vec::new(),
+            tools_used: BTreeMap::new(),
+        };
+
+        let mut run_history = Vec::new();
+        let stop = apply_cycle_gates(
+            &result,
+            "coding",
+            &mut run_history,
+            10,
+            3,
+            1,
+            &RunHooks::default(),
+        );
+
+        assert!(stop.is_none());
+        assert_eq!(run_history.len(), 1);
+        assert!(
+            run_history[0].success,
+            "Success should be recorded in history"
+        );
+    }
+
+    #[test]
+    fn test_apply_cycle_gates_returns_reason_on_consecutive_failures() {
+        let result = CycleResult {
+            cycle_name: "coding".to_string(),
+            success: false,
+            exit_code: Some(1),
+            ..Default::default()
+        };
+        let mut run_history = vec![
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+            RunOutcome {
+                success: false,
+                transient: false,
+                recovered: false,
+            },
+        ];
+        let stop = apply_cycle_gates(
+            &result,
+            "coding",
+            &mut run_history,
+            10,
+            3,
+            3,
+            &RunHooks::default(),
+        );
+        assert!(stop.unwrap().contains("3 consecutive cycle failures"));
+    }
+
+    // --- FlowRunner tests ---
+
+    fn cancelled() -> CancellationToken {
+        let token = CancellationToken::new();
+        token.cancel();
+        token
+    }
+
+    const RUNNER_CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+"#;
+
+    #[test]
+    fn test_runner_defaults_and_selector_mode() {
+        let config = FlowConfig::parse(RUNNER_CONFIG).unwrap();
+        let runner = FlowRunner::new(config.clone());
+        assert!(runner.uses_selector());
+        assert_eq!(runner.max_iterations, 1);
+        assert_eq!(runner.log_dir, PathBuf::from(".flow"));
+        assert!(runner.run_label().is_none());
+
+        let runner = FlowRunner::new(config.clone()).with_cycle("coding");
+        assert!(!runner.uses_selector());
+        assert_eq!(runner.run_label().as_deref(), Some("coding"));
+
+        let runner =
+            FlowRunner::new(config).with_playlist(vec!["plan".to_string(), "coding".to_string()]);
+        assert!(!runner.uses_selector());
+        assert_eq!(runner.run_label().as_deref(), Some("plan → coding"));
+    }
+
+    #[tokio::test]
+    async fn test_runner_stops_immediately_when_cancelled() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = FlowConfig::parse(RUNNER_CONFIG).unwrap();
+        let report = FlowRunner::new(config)
+            .with_cycle("coding")
+            .with_log_dir(dir.path().join(".flow"))
+            .with_project_dir(dir.path())
+            .with_cancellation(cancelled())
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(report.progress.current_status, RunStatus::Stopped);
+        assert!(report.progress.cycles_executed.is_empty());
+        assert!(!report.gate_tripped);
+        assert!(dir.path().join(".flow/last-run-summary.md").exists());
+    }
+
+    // --- arbitration tests ---
+
+    const ARBITRATION_CONFIG: &str = r#"
+[global]
+permissions = []
+
+[[cycle]]
+name = "coding"
+description = "Coding"
+prompt = "Code"
+
+[[cycle]]
+name = "gardening"
+description = "Gardening"
+prompt = "Tidy"
+
+[[cycle]]
+name = "review"
+description = "Review"
+prompt = "Review"
+arbitrates = "coding"
+"#;
+
+    /// A git repository with a committed TODO.md, and the handles a run
+    /// over it needs.
+    struct Arbitration {
+        dir: tempfile::TempDir,
+        runner: FlowRunner,
+        executor: CycleExecutor,
+        logger: JsonlLogger,
+        progress_writer: ProgressWriter,
+        hooks: RunHooks,
+        recording: Option<Recording>,
+        run_branch: Option<RunBranch>,
+    }
+
+    impl Arbitration {
+        fn new(replay: bool) -> Self {
+            let dir = tempfile::TempDir::new().unwrap();
+            let root = dir.path();
+            std::fs::write(root.join("TODO.md"), "- [ ] Fix the bug\n").unwrap();
+            std::fs::write(root.join("code.txt"), "original\n").unwrap();
+            for args in [
+                &["init", "-q", "-b", "main"][..],
+                &["config", "user.email", "flow@example.com"],
+                &["config", "user.name", "Flow"],
+                &["add", "."],
+                &["commit", "-q", "-m", "init"],
+            ] {
+                git(root, args);
+            }
+            let recording = replay.then(|| {
+                let path = root.join("recording.jsonl");
+                std::fs::write(&path, "").unwrap();
+                Recording::open(&path).unwrap()
+            });
+            let log_dir = root.join(".flow");
+            let runner = FlowRunner::new(FlowConfig::parse(ARBITRATION_CONFIG).unwrap())
+                .with_log_dir(&log_dir)
+                .with_project_dir(root)
+                .with_todo_path(root.join("TODO.md"))
+                .with_tasks_path(root.join(TASKS_FILE));
+            Self {
+                executor: runner.executor(None, &log_dir),
+                logger: JsonlLogger::new(&log_dir).unwrap(),
+                progress_writer: ProgressWriter::new(&log_dir).unwrap(),
+                hooks: RunHooks::default(),
+                runner,
+                recording,
+                run_branch: None,
+                dir,
+            }
+        }
+
+        /// Commit each cycle onto a run branch, as `git_branch_template` does.
+        fn on_branch(mut self) -> Self {
+            let excludes = self.runner.log_dir_excludes();
+            self.run_branch =
+                Some(RunBranch::start(self.dir.path(), "flow/run", &excludes).unwrap());
+            self
+        }
+
+        /// Pop a queued task for the current iteration.
+        fn queue_task(&self, state: &mut RunState) {
+            let queue = self.runner.log_dir.join(QUEUE_DIR);
+            std::fs::create_dir_all(&queue).unwrap();
+            write(&queue, "task.md", "Fix the bug\n");
+            state.task = pop_queued_task(&self.runner.log_dir)
+                .unwrap()
+                .map(|task| IterationTask {
+                    task,
+                    succeeded: true,
+                });
+        }
+
+        /// Where the queued task ended up: `done`, `failed` or `active`.
+        fn task_archive(&self) -> &'static str {
+            let queue = self.runner.log_dir.join(QUEUE_DIR);
+            ["done", "failed", "active"]
+                .into_iter()
+                .find(|dir| queue.join(dir).join("task.md").exists())
+                .unwrap()
+        }
+
+        fn ctx(&self) -> RunContext<'_> {
+            RunContext {
+                runner: &self.runner,
+                config: &self.runner.config,
+                executor: &self.executor,
+                logger: &self.logger,
+                progress_writer: &self.progress_writer,
+                cancel: &self.runner.cancel,
+                run_branch: self.run_branch.as_ref(),
+                log_dir: &self.runner.log_dir,
+                hooks: &self.hooks,
+                recording: self.recording.as_ref(),
+            }
+        }
+
+        /// Run `cycle` as the run loop does, with `edit` standing in for
+        /// Claude Code and `verdict` in its result.
+        fn cycle(
+            &self,
+            state: &mut RunState,
+            cycle: &str,
+            edit: impl FnOnce(&Path),
+            verdict: Option<Decision>,
+        ) {
+            let ctx = self.ctx();
+            let reviewing = begin_arbitration(&ctx, state, cycle);
+            edit(self.dir.path());
+            let result = CycleResult {
+                cycle_name: cycle.to_string(),
+                success: true,
+                verdict: verdict.map(|decision| Verdict {
+                    decision,
+                    reasons: Vec::new(),
+                }),
+                ..CycleResult::default()
+            };
+            state.iteration += 1;
+            commit_cycle(&ctx, state, cycle);
+            if reviewing {
+                if let Some(pending) = state.arbitration.take() {
+                    apply_verdict(&ctx, state, pending, cycle, &result);
+                }
+            }
+        }
+
+        /// Run the claimed coding cycle, which edits code.txt and finishes its task.
+        fn coding(&self, state: &mut RunState) {
+            let todo = self.dir.path().join("TODO.md");
+            assert!(claim_task(&todo, "Fix the bug").unwrap());
+            self.cycle(
+                state,
+                "coding",
+                |root| write(root, "code.txt", "changed\n"),
+                None,
+            );
+            finish_claims(&todo, true).unwrap();
+        }
+
+        fn read(&self, file: &str) -> String {
+            std::fs::read_to_string(self.dir.path().join(file)).unwrap()
+        }
+
+        fn log(&self) -> String {
+            git(self.dir.path(), &["log", "--format=%s"])
+        }
+    }
+
+    fn git(root: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    fn write(root: &Path, file: &str, content: &str) {
+        std::fs::write(root.join(file), content).unwrap();
+    }
+
+    fn run_state() -> RunState {
+        RunState {
+            iteration: 1,
+            progress: RunProgress::new(1),
+            run_history: Vec::new(),
+            gate_tripped: false,
+            arbitration: None,
+            claim: None,
+            task: None,
+        }
+    }
+
+    #[test]
+    fn test_approve_commits_arbitrated_changes() {
+        let arbitration = Arbitration::new(false);
+        let mut state = run_state();
+        arbitration.coding(&mut state);
+        arbitration.cycle(&mut state, "review", |_| {}, Some(Decision::Approve));
+
+        assert_eq!(
+            arbitration.log(),
+            "flow: coding (approved by review)\ninit\n"
+        );
+        assert_eq!(arbitration.read("code.txt"), "changed\n");
+        assert_eq!(arbitration.read("TODO.md"), "- [x] Fix the bug\n");
+        assert!(state.arbitration.is_none());
+    }
+
+    #[test]
+    fn test_reject_rolls_back_changes_and_releases_the_claim() {
+        let arbitration = Arbitration::new(false);
+        let mut state = run_state();
+        arbitration.coding(&mut state);
+        arbitration.cycle(&mut state, "review", |_| {}, Some(Decision::Reject));
+
+        assert_eq!(arbitration.log(), "init\n");
+        assert_eq!(arbitration.read("code.txt"), "original\n");
+        assert_eq!(arbitration.read("TODO.md"), "- [ ] Fix the bug\n");
+    }
+
+    #[test]
+    fn test_missing_verdict_leaves_changes_in_place() {
+        let arbitration = Arbitration::new(false);
+        let mut state = run_state();
+        arbitration.coding(&mut state);
+        arbitration.cycle(&mut state, "review", |_| {}, None);
+
+        assert_eq!(arbitration.log(), "init\n");
+        assert_eq!(arbitration.read("code.txt"), "changed\n");
+        assert_eq!(arbitration.read("TODO.md"), "- [x] Fix the bug\n");
+    }
+
+    #[test]
+    fn test_other_cycle_in_between_ends_arbitration() {
+        let arbitration = Arbitration::new(false);
+        let mut state = run_state();
+        arbitration.coding(&mut state);
+        arbitration.cycle(
+            &mut state,
+            "gardening",
+            |root| write(root, "notes.txt", "tidied\n"),
+            None,
+        );
+        assert!(state.arbitration.is_none());
+        arbitration.cycle(&mut state, "review", |_| {}, Some(Decision::Reject));
+
+        assert_eq!(arbitration.log(), "init\n");
+        assert_eq!(arbitration.read("code.txt"), "changed\n");
+        assert_eq!(arbitration.read("notes.txt"), "tidied\n");
+    }
+
+    #[test]
+    fn test_approve_makes_the_held_back_branch_commit() {
+        let arbitration = Arbitration::new(false).on_branch();
+        let mut state = run_state();
+        arbitration.queue_task(&mut state);
+        arbitration.coding(&mut state);
+        finish_iteration_task(&arbitration.ctx(), &mut state);
+        assert_eq!(arbitration.log(), "init\n");
+        assert_eq!(arbitration.task_archive(), "active");
+
+        arbitration.cycle(&mut state, "review", |_| {}, Some(Decision::Approve));
+
+        assert_eq!(
+            arbitration.log(),
+            "flow: coding (approved by review)\ninit\n"
+        );
+        assert_eq!(arbitration.task_archive(), "done");
+    }
+
+    #[test]
+    fn test_reject_fails_the_queued_task() {
+        let arbitration = Arbitration::new(false).on_branch();
+        let mut state = run_state();
+        arbitration.queue_task(&mut state);
+        arbitration.coding(&mut state);
+        finish_iteration_task(&arbitration.ctx(), &mut state);
+        arbitration.cycle(&mut state, "review", |_| {}, Some(Decision::Reject));
+
+        assert_eq!(arbitration.log(), "init\n");
+        assert_eq!(arbitration.task_archive(), "failed");
+    }
+
+    #[test]
+    fn test_reject_within_the_iteration_fails_its_task() {
+        let arbitration = Arbitration::new(false);
+        let mut state = run_state();
+        arbitration.queue_task(&mut state);
+        arbitration.coding(&mut state);
+        arbitration.cycle(&mut state, "review", |_| {}, Some(Decision::Reject));
+        finish_iteration_task(&arbitration.ctx(), &mut state);
+
+        assert_eq!(arbitration.task_archive(), "failed");
+    }
+
+    #[test]
+    fn test_unreviewed_changes_get_their_held_back_commit() {
+        let arbitration = Arbitration::new(false).on_branch();
+        let mut state = run_state();
+        arbitration.queue_task(&mut state);
+        arbitration.coding(&mut state);
+        finish_iteration_task(&arbitration.ctx(), &mut state);
+        arbitration.cycle(
+            &mut state,
+            "gardening",
+            |root| write(root, "notes.txt", "tidied\n"),
+            None,
+        );
+
+        assert_eq!(
+            arbitration.log(),
+            "flow: gardening (iteration 2)\nflow: coding (iteration 1)\ninit\n"
+        );
+        assert_eq!(arbitration.task_archive(), "done");
+    }
+
+    #[test]
+    fn test_missing_verdict_makes_the_held_back_commit() {
+        let arbitration = Arbitration::new(false).on_branch();
+        let mut state = run_state();
+        arbitration.coding(&mut state);
+        arbitration.cycle(&mut state, "review", |_| {}, None);
+
+        assert_eq!(arbitration.log(), "flow: coding (iteration 1)\ninit\n");
+        assert_eq!(arbitration.read("TODO.md"), "- [x] Fix the bug\n");
+    }
+
+    #[test]
+    fn test_replayed_verdict_is_not_applied() {
+        let arbitration = Arbitration::new(true);
+        let mut state = run_state();
+        arbitration.coding(&mut state);
+        assert!(state
+            .arbitration
+            .as_ref()
+            .is_some_and(|pending| pending.snapshot.is_none()));
+        arbitration.cycle(&mut state, "review", |_| {}, Some(Decision::Reject));
+
+        assert_eq!(arbitration.log(), "init\n");
+        assert_eq!(arbitration.read("code.txt"), "changed\n");
+    }
+}
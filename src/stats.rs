@@ -0,0 +1,158 @@
+//! Per-cycle statistics and rubric trends (`flow stats`)
+//!
+//! Sums each cycle's logged runs, successes, and cost, and for cycles with an
+//! `eval` rubric compares the mean score of each criterion over the most
+//! recent scored runs with the window before it, so gradual quality decay
+//! shows up before it turns into failures.
+
+use std::collections::BTreeMap;
+
+use crate::log::CycleOutcome;
+
+/// Mean score of one rubric criterion, recent versus earlier runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriterionTrend {
+    /// Criterion as written in the rubric
+    pub criterion: String,
+    /// Mean over the most recent scored runs (up to the window size)
+    pub recent: f64,
+    /// Mean over the window before that (`None` without enough history)
+    pub previous: Option<f64>,
+    /// Scored runs in total
+    pub scored_runs: usize,
+}
+
+impl CriterionTrend {
+    /// Change from the previous window to the recent one.
+    #[must_use]
+    pub fn delta(&self) -> Option<f64> {
+        self.previous.map(|previous| self.recent - previous)
+    }
+}
+
+/// Totals and rubric trends for one cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleTrend {
+    /// Cycle name
+    pub cycle: String,
+    /// Logged runs
+    pub runs: usize,
+    /// Runs that succeeded
+    pub successes: usize,
+    /// Sum of reported costs
+    pub total_cost_usd: f64,
+    /// One trend per criterion ever scored, by criterion name
+    pub criteria: Vec<CriterionTrend>,
+}
+
+/// Summarize `entries` per cycle (by name), comparing each rubric criterion's
+/// last `window` scores with the `window` before them.
+#[must_use]
+pub fn cycle_trends(entries: &[CycleOutcome], window: usize) -> Vec<CycleTrend> {
+    let window = window.max(1);
+    let mut by_cycle: BTreeMap<&str, Vec<&CycleOutcome>> = BTreeMap::new();
+    for entry in entries {
+        by_cycle
+            .entry(entry.cycle.as_str())
+            .or_default()
+            .push(entry);
+    }
+    by_cycle
+        .into_iter()
+        .map(|(cycle, runs)| {
+            let mut scores: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+            for (criterion, score) in runs.iter().filter_map(|e| e.eval_scores.as_ref()).flatten() {
+                scores.entry(criterion.as_str()).or_default().push(*score);
+            }
+            CycleTrend {
+                cycle: cycle.to_string(),
+                runs: runs.len(),
+                successes: runs.iter().filter(|e| e.is_success()).count(),
+                total_cost_usd: runs.iter().filter_map(|e| e.total_cost_usd).sum(),
+                criteria: scores
+                    .into_iter()
+                    .map(|(criterion, scores)| trend(criterion, &scores, window))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Trend of one criterion's scores, oldest first.
+fn trend(criterion: &str, scores: &[u8], window: usize) -> CriterionTrend {
+    let split = scores.len().saturating_sub(window);
+    let (earlier, recent) = scores.split_at(split);
+    let previous = &earlier[earlier.len().saturating_sub(window)..];
+    CriterionTrend {
+        criterion: criterion.to_string(),
+        recent: mean(recent),
+        previous: (!previous.is_empty()).then(|| mean(previous)),
+        scored_runs: scores.len(),
+    }
+}
+
+/// Arithmetic mean (0.0 for no scores).
+fn mean(scores: &[u8]) -> f64 {
+    if scores.is_empty() {
+        return 0.0;
+    }
+    let total: u32 = scores.iter().map(|&s| u32::from(s)).sum();
+    f64::from(total) / f64::from(u32::try_from(scores.len()).unwrap_or(u32::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::make_test_outcome;
+
+    fn scored(iteration: u32, cycle: &str, tests: u8, size: u8) -> CycleOutcome {
+        let mut entry = make_test_outcome(iteration, cycle, "Done");
+        entry.eval_scores = Some(BTreeMap::from([
+            ("tests".to_string(), tests),
+            ("size".to_string(), size),
+        ]));
+        entry
+    }
+
+    #[test]
+    fn test_trend_compares_recent_window_with_previous() {
+        let entries = vec![
+            scored(1, "coding", 5, 3),
+            scored(2, "coding", 5, 3),
+            scored(3, "coding", 3, 3),
+            scored(4, "coding", 2, 4),
+        ];
+        let trends = cycle_trends(&entries, 2);
+        assert_eq!(trends.len(), 1);
+        let tests = &trends[0].criteria[1];
+        assert_eq!(tests.criterion, "tests");
+        assert!((tests.recent - 2.5).abs() < f64::EPSILON);
+        assert!((tests.delta().unwrap() + 2.5).abs() < f64::EPSILON);
+        assert_eq!(tests.scored_runs, 4);
+        let size = &trends[0].criteria[0];
+        assert!((size.delta().unwrap() - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_short_history_has_no_previous_window() {
+        let entries = vec![scored(1, "coding", 4, 4)];
+        let trends = cycle_trends(&entries, 5);
+        assert!(trends[0].criteria[0].previous.is_none());
+        assert!(trends[0].criteria[0].delta().is_none());
+    }
+
+    #[test]
+    fn test_totals_per_cycle() {
+        let mut failed = make_test_outcome(2, "coding", "Failed with exit code 1");
+        failed.total_cost_usd = Some(0.5);
+        let mut ok = make_test_outcome(1, "coding", "Done");
+        ok.total_cost_usd = Some(1.0);
+        let entries = vec![ok, failed, make_test_outcome(3, "review", "Done")];
+        let trends = cycle_trends(&entries, 5);
+        let names: Vec<&str> = trends.iter().map(|t| t.cycle.as_str()).collect();
+        assert_eq!(names, vec!["coding", "review"]);
+        assert_eq!((trends[0].runs, trends[0].successes), (2, 1));
+        assert!((trends[0].total_cost_usd - 1.5).abs() < f64::EPSILON);
+        assert!(trends[1].criteria.is_empty());
+    }
+}
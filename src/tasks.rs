@@ -0,0 +1,429 @@
+//! Structured task source (`tasks.toml`)
+//!
+//! An alternative to TODO.md checkboxes for backlogs that need structure:
+//! each `[[task]]` has an `id`, `title`, `priority`, acceptance criteria, and
+//! the ids it `depends_on`. When the file exists, the selector sees which
+//! tasks are ready (every dependency done) and which are blocked, names a task
+//! by id, and the cycle receives it as `{{selected_task}}`. Flow keeps each
+//! task's `status` current: `in_progress` while a cycle works on it, `done`
+//! when the cycle succeeds, back to `pending` when it fails. Status updates go
+//! through `toml_edit`, so comments and formatting survive.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::cycle::selector::{TodoTask, DEFAULT_TODO_PRIORITY};
+
+/// Default path of the structured task file
+pub const TASKS_FILE: &str = "tasks.toml";
+
+/// Where a task stands
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    /// Not started (the default)
+    #[default]
+    Pending,
+    /// A cycle is working on it
+    InProgress,
+    /// Finished
+    Done,
+}
+
+impl TaskStatus {
+    /// The status as written in `tasks.toml`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::InProgress => "in_progress",
+            Self::Done => "done",
+        }
+    }
+}
+
+/// One `[[task]]` entry
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct StructuredTask {
+    /// Unique identifier, used in `depends_on` and by the selector
+    pub id: String,
+    /// One-line summary
+    pub title: String,
+    /// Priority level (P0-P3)
+    #[serde(default = "default_priority")]
+    pub priority: String,
+    /// Where the task stands; maintained by Flow
+    #[serde(default)]
+    pub status: TaskStatus,
+    /// Longer explanation
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Conditions the finished work must meet
+    #[serde(default)]
+    pub acceptance: Vec<String>,
+    /// Ids of tasks that must be done first
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+}
+
+fn default_priority() -> String {
+    DEFAULT_TODO_PRIORITY.to_string()
+}
+
+impl StructuredTask {
+    /// The task as handed to a cycle in `{{selected_task}}`.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = format!("{}: {} ({})", self.id, self.title, self.priority);
+        if let Some(ref description) = self.description {
+            let _ = write!(out, "\n\n{}", description.trim());
+        }
+        if !self.acceptance.is_empty() {
+            out.push_str("\n\nAcceptance criteria:");
+            for criterion in &self.acceptance {
+                let _ = write!(out, "\n- {criterion}");
+            }
+        }
+        out
+    }
+}
+
+/// The parsed contents of `tasks.toml`
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+pub struct TaskFile {
+    /// Tasks in file order
+    #[serde(default, rename = "task")]
+    pub tasks: Vec<StructuredTask>,
+}
+
+impl TaskFile {
+    /// Parse and validate `tasks.toml` content.
+    ///
+    /// # Errors
+    /// Returns an error if the TOML is invalid, an id is empty or repeated, or
+    /// a dependency names an unknown task or forms a cycle.
+    pub fn parse(content: &str) -> Result<Self> {
+        let file: Self = toml::from_str(content)?;
+        let mut ids = HashSet::new();
+        for task in &file.tasks {
+            if task.id.trim().is_empty() {
+                bail!("Task '{}' has an empty id", task.title);
+            }
+            if !ids.insert(task.id.as_str()) {
+                bail!("Duplicate task id '{}'", task.id);
+            }
+        }
+        for task in &file.tasks {
+            if let Some(unknown) = task.depends_on.iter().find(|d| !ids.contains(d.as_str())) {
+                bail!("Task '{}' depends on unknown task '{unknown}'", task.id);
+            }
+            if file.reaches(&task.id, &task.id, &mut HashSet::new()) {
+                bail!("Task '{}' depends on itself", task.id);
+            }
+        }
+        Ok(file)
+    }
+
+    /// Load `path`, or `None` if it doesn't exist.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or is invalid.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content)
+                .map(Some)
+                .with_context(|| format!("Invalid task file {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// The task with `id`.
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&StructuredTask> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+
+    /// Pending tasks whose dependencies are all done.
+    #[must_use]
+    pub fn ready(&self) -> Vec<&StructuredTask> {
+        self.tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::Pending && self.unmet(t).is_empty())
+            .collect()
+    }
+
+    /// Ids of `task`'s dependencies that aren't done.
+    #[must_use]
+    pub fn unmet<'a>(&self, task: &'a StructuredTask) -> Vec<&'a str> {
+        task.depends_on
+            .iter()
+            .filter(|d| self.get(d).is_none_or(|dep| dep.status != TaskStatus::Done))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Tasks not yet done, as [`TodoTask`]s for stop conditions.
+    #[must_use]
+    pub fn todo_tasks(&self) -> Vec<TodoTask> {
+        self.tasks
+            .iter()
+            .filter(|t| t.status != TaskStatus::Done)
+            .map(|t| TodoTask {
+                priority: t.priority.clone(),
+                description: t.title.clone(),
+                subtasks: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// The task section of the selector prompt: ready tasks with their
+    /// acceptance criteria, then in-progress and blocked ones.
+    #[must_use]
+    pub fn selector_summary(&self) -> String {
+        let ready = self.ready();
+        let mut lines = Vec::new();
+        if ready.is_empty() {
+            lines.push("No ready tasks in tasks.toml".to_string());
+        } else {
+            lines.push(format!("Ready: {} task(s)", ready.len()));
+            for task in ready {
+                lines.push(format!(
+                    "  - [{}] {}: {}",
+                    task.priority, task.id, task.title
+                ));
+                for criterion in &task.acceptance {
+                    lines.push(format!("      accept: {criterion}"));
+                }
+            }
+        }
+        for task in self
+            .tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::InProgress)
+        {
+            lines.push(format!("In progress: {}: {}", task.id, task.title));
+        }
+        for task in self
+            .tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::Pending)
+        {
+            let unmet = self.unmet(task);
+            if !unmet.is_empty() {
+                lines.push(format!(
+                    "Blocked: {}: {} (waiting on {})",
+                    task.id,
+                    task.title,
+                    unmet.join(", ")
+                ));
+            }
+        }
+        let done = self
+            .tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::Done)
+            .count();
+        lines.push(format!("Done: {done} task(s)"));
+        lines.join("\n")
+    }
+
+    /// Whether following dependencies from `from` leads to `target`.
+    fn reaches<'a>(&'a self, from: &'a str, target: &str, seen: &mut HashSet<&'a str>) -> bool {
+        let Some(task) = self.get(from) else {
+            return false;
+        };
+        task.depends_on.iter().any(|dep| {
+            dep == target || (seen.insert(dep.as_str()) && self.reaches(dep, target, seen))
+        })
+    }
+}
+
+/// `content` with task `id`'s status set, preserving comments and layout.
+/// Returns `None` if no task has that id.
+///
+/// # Errors
+/// Returns an error if `content` isn't valid TOML.
+pub fn with_status(content: &str, id: &str, status: TaskStatus) -> Result<Option<String>> {
+    let mut doc: toml_edit::DocumentMut = content.parse()?;
+    let Some(tasks) = doc
+        .get_mut("task")
+        .and_then(toml_edit::Item::as_array_of_tables_mut)
+    else {
+        return Ok(None);
+    };
+    let Some(table) = tasks
+        .iter_mut()
+        .find(|t| t.get("id").and_then(toml_edit::Item::as_str) == Some(id))
+    else {
+        return Ok(None);
+    };
+    table.insert("status", toml_edit::value(status.as_str()));
+    Ok(Some(doc.to_string()))
+}
+
+/// Set task `id`'s status in the file at `path`. Returns whether the task was found.
+///
+/// # Errors
+/// Returns an error if the file can't be read, parsed, or written.
+pub fn set_status(path: &Path, id: &str, status: TaskStatus) -> Result<bool> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let Some(updated) = with_status(&content, id, status)? else {
+        return Ok(false);
+    };
+    std::fs::write(path, updated).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+/// Return tasks an interrupted run left `in_progress` to `pending`, returning their ids.
+///
+/// # Errors
+/// Returns an error if the file exists but can't be read, parsed, or written.
+pub fn release_in_progress(path: &Path) -> Result<Vec<String>> {
+    let Some(file) = TaskFile::load(path)? else {
+        return Ok(Vec::new());
+    };
+    let stale: Vec<String> = file
+        .tasks
+        .into_iter()
+        .filter(|t| t.status == TaskStatus::InProgress)
+        .map(|t| t.id)
+        .collect();
+    for id in &stale {
+        set_status(path, id, TaskStatus::Pending)?;
+    }
+    Ok(stale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TASKS: &str = r#"
+# Backlog for the billing service
+[[task]]
+id = "schema"
+title = "Add invoices table"
+priority = "P0"
+status = "done"
+
+[[task]]
+id = "api"
+title = "Invoice API"
+priority = "P1"
+acceptance = ["GET /invoices lists invoices", "Covered by tests"]
+depends_on = ["schema"]
+
+[[task]]
+id = "ui"
+title = "Invoice page"  # shown to customers
+depends_on = ["api"]
+"#;
+
+    fn tasks() -> TaskFile {
+        TaskFile::parse(TASKS).unwrap()
+    }
+
+    // --- parsing ---
+
+    #[test]
+    fn test_parse_defaults() {
+        let file = tasks();
+        let ui = file.get("ui").unwrap();
+        assert_eq!(ui.priority, DEFAULT_TODO_PRIORITY);
+        assert_eq!(ui.status, TaskStatus::Pending);
+        assert!(ui.acceptance.is_empty());
+        assert_eq!(TaskFile::parse("").unwrap(), TaskFile::default());
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_ids_and_dependencies() {
+        let dup = "[[task]]\nid = \"a\"\ntitle = \"A\"\n[[task]]\nid = \"a\"\ntitle = \"B\"\n";
+        assert!(TaskFile::parse(dup)
+            .unwrap_err()
+            .to_string()
+            .contains("Duplicate"));
+        let unknown = "[[task]]\nid = \"a\"\ntitle = \"A\"\ndepends_on = [\"b\"]\n";
+        assert!(TaskFile::parse(unknown)
+            .unwrap_err()
+            .to_string()
+            .contains("unknown task 'b'"));
+        let cyclic = "[[task]]\nid = \"a\"\ntitle = \"A\"\ndepends_on = [\"b\"]\n\
+                      [[task]]\nid = \"b\"\ntitle = \"B\"\ndepends_on = [\"a\"]\n";
+        assert!(TaskFile::parse(cyclic)
+            .unwrap_err()
+            .to_string()
+            .contains("depends on itself"));
+    }
+
+    // --- readiness ---
+
+    #[test]
+    fn test_ready_requires_done_dependencies() {
+        let file = tasks();
+        let ready: Vec<&str> = file.ready().iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ready, vec!["api"]);
+        assert_eq!(file.unmet(file.get("ui").unwrap()), vec!["api"]);
+    }
+
+    #[test]
+    fn test_selector_summary() {
+        let summary = tasks().selector_summary();
+        assert!(summary.contains("Ready: 1 task(s)"), "{summary}");
+        assert!(summary.contains("  - [P1] api: Invoice API"), "{summary}");
+        assert!(summary.contains("accept: Covered by tests"), "{summary}");
+        assert!(
+            summary.contains("Blocked: ui: Invoice page (waiting on api)"),
+            "{summary}"
+        );
+        assert!(summary.contains("Done: 1 task(s)"), "{summary}");
+    }
+
+    #[test]
+    fn test_render_includes_acceptance() {
+        let file = tasks();
+        assert_eq!(
+            file.get("api").unwrap().render(),
+            "api: Invoice API (P1)\n\nAcceptance criteria:\n- GET /invoices lists invoices\n- Covered by tests"
+        );
+    }
+
+    #[test]
+    fn test_todo_tasks_exclude_done() {
+        let todo = tasks().todo_tasks();
+        let titles: Vec<&str> = todo.iter().map(|t| t.description.as_str()).collect();
+        assert_eq!(titles, vec!["Invoice API", "Invoice page"]);
+    }
+
+    // --- status updates ---
+
+    #[test]
+    fn test_with_status_preserves_comments() {
+        let updated = with_status(TASKS, "ui", TaskStatus::InProgress)
+            .unwrap()
+            .unwrap();
+        assert!(updated.contains("# Backlog for the billing service"));
+        assert!(updated.contains("# shown to customers"));
+        let file = TaskFile::parse(&updated).unwrap();
+        assert_eq!(file.get("ui").unwrap().status, TaskStatus::InProgress);
+        assert_eq!(with_status(TASKS, "nope", TaskStatus::Done).unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_status_and_release_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TASKS_FILE);
+        assert!(release_in_progress(&path).unwrap().is_empty());
+        std::fs::write(&path, TASKS).unwrap();
+        assert!(set_status(&path, "api", TaskStatus::InProgress).unwrap());
+        assert!(!set_status(&path, "nope", TaskStatus::Done).unwrap());
+        assert_eq!(release_in_progress(&path).unwrap(), vec!["api"]);
+        let file = TaskFile::load(&path).unwrap().unwrap();
+        assert_eq!(file.get("api").unwrap().status, TaskStatus::Pending);
+    }
+}
@@ -23,5 +23,33 @@ pub fn make_test_outcome(iteration: u32, cycle: &str, outcome: &str) -> CycleOut
         permission_denial_count: None,
         permission_denials: None,
         steps: None,
+        commands_run: None,
+        tests_failed: None,
+        run_id: None,
+        rolled_back: None,
+        chosen_sample: None,
+        attempts: None,
+        verdict: None,
+        eval_scores: None,
+        test_delta: None,
+        lint: None,
+        subagent_count: None,
+        subagent_cost_usd: None,
+        git_sha_before: None,
+        git_sha_after: None,
+        lines_added: None,
+        lines_removed: None,
+        prompt_hash: None,
+        permissions_hash: None,
+        failure_kind: None,
+        computed_cost_usd: None,
+        usage: None,
+        model: None,
+        flow_version: None,
+        claude_version: None,
+        os: None,
+        task: None,
+        skipped_steps: None,
+        tools_used: None,
     }
 }
@@ -1,18 +1,17 @@
 #![allow(missing_docs)]
 
 use std::process::Command;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
 use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
 
 use flow::cycle::config::FlowConfig;
 use flow::cycle::executor::CycleExecutor;
 use flow::cycle::rules::find_triggered_cycles;
-use flow::log::jsonl::JsonlLogger;
+use flow::log::jsonl::{FailureKind, JsonlLogger};
 use flow::log::CycleOutcome;
 
-fn no_shutdown() -> Arc<AtomicBool> {
-    Arc::new(AtomicBool::new(false))
+fn no_shutdown() -> CancellationToken {
+    CancellationToken::new()
 }
 
 const TEST_CONFIG: &str = r#"
@@ -88,13 +87,7 @@ async fn test_coding_cycle_end_to_end() {
         exit_code,
         stderr,
         duration_secs,
-        result_text: None,
-        num_turns: None,
-        total_cost_usd: None,
-        permission_denial_count: None,
-        permission_denials: None,
-        files_changed: vec![],
-        tests_passed: 0,
+        ..Default::default()
     };
 
     let outcome = CycleOutcome {
@@ -114,6 +107,34 @@ async fn test_coding_cycle_end_to_end() {
         permission_denial_count: None,
         permission_denials: None,
         steps: None,
+        commands_run: None,
+        tests_failed: None,
+        run_id: None,
+        rolled_back: None,
+        chosen_sample: None,
+        attempts: None,
+        verdict: None,
+        eval_scores: None,
+        test_delta: None,
+        lint: None,
+        subagent_count: None,
+        subagent_cost_usd: None,
+        git_sha_before: None,
+        git_sha_after: None,
+        lines_added: None,
+        lines_removed: None,
+        prompt_hash: None,
+        permissions_hash: None,
+        failure_kind: None,
+        computed_cost_usd: None,
+        usage: None,
+        model: None,
+        flow_version: None,
+        claude_version: None,
+        os: None,
+        task: None,
+        skipped_steps: None,
+        tools_used: None,
     };
 
     logger.append(&outcome).unwrap();
@@ -157,13 +178,7 @@ async fn test_failed_cycle_logged_correctly() {
         exit_code,
         stderr,
         duration_secs,
-        result_text: None,
-        num_turns: None,
-        total_cost_usd: None,
-        permission_denial_count: None,
-        permission_denials: None,
-        files_changed: vec![],
-        tests_passed: 0,
+        ..Default::default()
     };
 
     let outcome = CycleOutcome {
@@ -184,6 +199,34 @@ async fn test_failed_cycle_logged_correctly() {
         permission_denial_count: None,
         permission_denials: None,
         steps: None,
+        commands_run: None,
+        tests_failed: None,
+        run_id: None,
+        rolled_back: None,
+        chosen_sample: None,
+        attempts: None,
+        verdict: None,
+        eval_scores: None,
+        test_delta: None,
+        lint: None,
+        subagent_count: None,
+        subagent_cost_usd: None,
+        git_sha_before: None,
+        git_sha_after: None,
+        lines_added: None,
+        lines_removed: None,
+        prompt_hash: None,
+        permissions_hash: None,
+        failure_kind: None,
+        computed_cost_usd: None,
+        usage: None,
+        model: None,
+        flow_version: None,
+        claude_version: None,
+        os: None,
+        task: None,
+        skipped_steps: None,
+        tools_used: None,
     };
 
     logger.append(&outcome).unwrap();
@@ -195,6 +238,52 @@ async fn test_failed_cycle_logged_correctly() {
     assert!(!result.success);
 }
 
+/// A successful log entry for `result` at `iteration`.
+fn completed_outcome(iteration: u32, result: &flow::CycleResult) -> CycleOutcome {
+    CycleOutcome {
+        iteration,
+        cycle: result.cycle_name.clone(),
+        timestamp: chrono::Utc::now(),
+        outcome: "Completed successfully".to_string(),
+        files_changed: vec![],
+        tests_passed: 0,
+        duration_secs: result.duration_secs,
+        num_turns: None,
+        total_cost_usd: None,
+        permission_denial_count: None,
+        permission_denials: None,
+        steps: None,
+        commands_run: None,
+        tests_failed: None,
+        run_id: None,
+        rolled_back: None,
+        chosen_sample: None,
+        attempts: None,
+        verdict: None,
+        eval_scores: None,
+        test_delta: None,
+        lint: None,
+        subagent_count: None,
+        subagent_cost_usd: None,
+        git_sha_before: None,
+        git_sha_after: None,
+        lines_added: None,
+        lines_removed: None,
+        prompt_hash: None,
+        permissions_hash: None,
+        failure_kind: None,
+        computed_cost_usd: None,
+        usage: None,
+        model: None,
+        flow_version: None,
+        claude_version: None,
+        os: None,
+        task: None,
+        skipped_steps: None,
+        tools_used: None,
+    }
+}
+
 /// Integration test: Gardening auto-triggers after coding.
 ///
 /// Tests the full flow: coding cycle succeeds → rules engine finds
@@ -221,30 +310,11 @@ async fn test_gardening_auto_triggers_after_coding() {
         exit_code,
         stderr,
         duration_secs,
-        result_text: None,
-        num_turns: None,
-        total_cost_usd: None,
-        permission_denial_count: None,
-        permission_denials: None,
-        files_changed: vec![],
-        tests_passed: 0,
+        ..Default::default()
     };
 
     // Log coding result
-    let coding_outcome = CycleOutcome {
-        iteration,
-        cycle: coding_result.cycle_name.clone(),
-        timestamp: chrono::Utc::now(),
-        outcome: "Completed successfully".to_string(),
-        files_changed: vec![],
-        tests_passed: 0,
-        duration_secs: coding_result.duration_secs,
-        num_turns: None,
-        total_cost_usd: None,
-        permission_denial_count: None,
-        permission_denials: None,
-        steps: None,
-    };
+    let coding_outcome = completed_outcome(iteration, &coding_result);
     logger.append(&coding_outcome).unwrap();
     iteration += 1;
 
@@ -272,29 +342,10 @@ async fn test_gardening_auto_triggers_after_coding() {
             exit_code,
             stderr,
             duration_secs,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 0,
+            ..Default::default()
         };
 
-        let dep_outcome = CycleOutcome {
-            iteration,
-            cycle: dep_result.cycle_name.clone(),
-            timestamp: chrono::Utc::now(),
-            outcome: "Completed successfully".to_string(),
-            files_changed: vec![],
-            tests_passed: 0,
-            duration_secs: dep_result.duration_secs,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            steps: None,
-        };
+        let dep_outcome = completed_outcome(iteration, &dep_result);
         logger.append(&dep_outcome).unwrap();
         iteration += 1;
 
@@ -373,13 +424,7 @@ async fn test_multiple_iterations_logged() {
             exit_code,
             stderr,
             duration_secs,
-            result_text: None,
-            num_turns: None,
-            total_cost_usd: None,
-            permission_denial_count: None,
-            permission_denials: None,
-            files_changed: vec![],
-            tests_passed: 0,
+            ..Default::default()
         };
 
         let outcome = CycleOutcome {
@@ -395,6 +440,34 @@ async fn test_multiple_iterations_logged() {
             permission_denial_count: None,
             permission_denials: None,
             steps: None,
+            commands_run: None,
+            tests_failed: None,
+            run_id: None,
+            rolled_back: None,
+            chosen_sample: None,
+            attempts: None,
+            verdict: None,
+            eval_scores: None,
+            test_delta: None,
+            lint: None,
+            subagent_count: None,
+            subagent_cost_usd: None,
+            git_sha_before: None,
+            git_sha_after: None,
+            lines_added: None,
+            lines_removed: None,
+            prompt_hash: None,
+            permissions_hash: None,
+            failure_kind: None,
+            computed_cost_usd: None,
+            usage: None,
+            model: None,
+            flow_version: None,
+            claude_version: None,
+            os: None,
+            task: None,
+            skipped_steps: None,
+            tools_used: None,
         };
         logger.append(&outcome).unwrap();
     }
@@ -421,3 +494,970 @@ fn test_unknown_cycle_rejected() {
     assert!(result.is_err());
     assert!(result.unwrap_err().to_string().contains("Unknown cycle"),);
 }
+
+/// Integration test: the `flow` binary runs a cycle against `flow mock-claude`
+/// fixtures, with no real Claude Code process.
+/// A project with `TEST_CONFIG` and `flow mock-claude` fixtures answering the
+/// coding cycle. Returns the project dir and the fixtures dir.
+fn mock_project() -> (TempDir, std::path::PathBuf) {
+    let project = TempDir::new().unwrap();
+    let fixtures = project.path().join("mock");
+    std::fs::create_dir(&fixtures).unwrap();
+    std::fs::write(project.path().join("cycles.toml"), TEST_CONFIG).unwrap();
+    std::fs::write(
+        fixtures.join("responses.toml"),
+        "[[response]]\nmatch = \"integration test\"\ntranscript = \"coding.jsonl\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        fixtures.join("coding.jsonl"),
+        concat!(
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Working"}]}}"#,
+            "\n",
+            r#"{"type":"result","subtype":"success","is_error":false,"num_turns":4,"result":"Implemented the task","total_cost_usd":0.25,"duration_ms":1000,"permission_denials":[]}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    (project, fixtures)
+}
+
+#[test]
+fn test_flow_binary_runs_against_mock_claude() {
+    let (project, fixtures) = mock_project();
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args(["--cycle", "coding"])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "flow failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let coding = entries.iter().find(|e| e.cycle == "coding").unwrap();
+    assert_eq!(coding.outcome, "Implemented the task");
+    assert_eq!(coding.num_turns, Some(4));
+    assert_eq!(coding.total_cost_usd, Some(0.25));
+    assert_eq!(coding.prompt_hash.as_ref().map(String::len), Some(16));
+    assert!(coding.permissions_hash.is_some());
+}
+
+/// The single file or directory inside `dir`.
+fn only_entry(dir: &std::path::Path) -> std::path::PathBuf {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path()
+}
+
+#[test]
+fn test_replay_reproduces_recorded_run_without_claude() {
+    let (project, fixtures) = mock_project();
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let log_dir = project.path().join(".flow");
+    let run = |args: &[&str], claude_bin: String| {
+        let output = Command::new(flow)
+            .args(args)
+            .current_dir(project.path())
+            .env("FLOW_CLAUDE_BIN", claude_bin)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "flow failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+
+    run(
+        &["--cycle", "coding", "--record"],
+        format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+    );
+    let recording = only_entry(&log_dir.join("recordings"));
+
+    // Replay must not spawn Claude Code at all
+    run(
+        &["--replay", recording.to_str().unwrap()],
+        "/nonexistent/claude".to_string(),
+    );
+
+    // The replay logs to a directory of its own, leaving the project's log alone
+    let recorded = JsonlLogger::new(&log_dir).unwrap().read_all().unwrap();
+    let replayed = JsonlLogger::new(only_entry(&log_dir.join("replays")))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    // coding, then gardening (auto-triggered; no fixture matches, so it fails)
+    assert_eq!(recorded.len(), 2);
+    assert_eq!(replayed.len(), 2);
+    for (recorded, replayed) in recorded.iter().zip(&replayed) {
+        assert_ne!(recorded.run_id, replayed.run_id);
+        assert_eq!(replayed.cycle, recorded.cycle);
+        assert_eq!(replayed.iteration, recorded.iteration);
+        assert_eq!(replayed.outcome, recorded.outcome);
+        assert_eq!(replayed.num_turns, recorded.num_turns);
+        assert_eq!(replayed.total_cost_usd, recorded.total_cost_usd);
+        assert_eq!(replayed.duration_secs, recorded.duration_secs);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_replay_runs_no_commands_and_leaves_the_project_alone() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (project, fixtures) = mock_project();
+    let root = project.path();
+    let config = format!(
+        "{TEST_CONFIG}{}",
+        r#"
+[[cycle]]
+name = "checked"
+description = "Done only once the ready marker exists"
+prompt = "echo integration test checked"
+rollback_on_failure = true
+success_when = { command = "test -f ready" }
+
+[hooks]
+cycle_end = ["touch hook-ran"]
+
+[notifications]
+desktop = true
+"#
+    );
+    std::fs::write(root.join("cycles.toml"), config).unwrap();
+
+    // A stand-in `notify-send` that leaves a marker
+    let bin = root.join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let notify = bin.join("notify-send");
+    std::fs::write(&notify, "#!/bin/sh\ntouch \"$MARKERS/notified\"\n").unwrap();
+    std::fs::set_permissions(&notify, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    git(&["init", "-q", "-b", "main"]);
+    git(&["config", "user.email", "flow@example.com"]);
+    git(&["config", "user.name", "Flow"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let run = |args: &[&str], claude_bin: String| {
+        let output = Command::new(flow)
+            .args(["--set", "global.git_branch_template=\"flow/{run_id}\""])
+            .args(args)
+            .current_dir(root)
+            .env("PATH", &path)
+            .env("MARKERS", root)
+            .env("FLOW_CLAUDE_BIN", claude_bin)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(output.status.success(), "flow failed: {stderr}");
+        stderr
+    };
+
+    // Recording: success_when fails, so the cycle is rolled back, and the
+    // hook, notification, and run branch all happen
+    let stderr = run(
+        &["--cycle", "checked", "--record"],
+        format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+    );
+    assert!(stderr.contains("Rolled back"), "{stderr}");
+    for marker in ["hook-ran", "notified"] {
+        assert!(
+            root.join(marker).exists(),
+            "{marker} missing after the recorded run"
+        );
+        std::fs::remove_file(root.join(marker)).unwrap();
+    }
+    let branches = git(&["branch", "--list"]);
+    assert!(branches.contains("flow/"), "{branches}");
+    let head = git(&["rev-parse", "HEAD"]);
+    let recording = only_entry(&root.join(".flow/recordings"));
+
+    // success_when would pass now; the replay must use the recorded outcome
+    std::fs::write(root.join("ready"), "").unwrap();
+    let stderr = run(
+        &["--replay", recording.to_str().unwrap()],
+        "/nonexistent/claude".to_string(),
+    );
+    assert!(!stderr.contains("Rolled back"), "{stderr}");
+    for marker in ["hook-ran", "notified"] {
+        assert!(!root.join(marker).exists(), "replay left {marker}");
+    }
+    assert_eq!(git(&["branch", "--list"]), branches);
+    assert_eq!(git(&["rev-parse", "HEAD"]), head);
+
+    let replayed = JsonlLogger::new(only_entry(&root.join(".flow/replays")))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let checked = replayed.iter().find(|e| e.cycle == "checked").unwrap();
+    assert!(
+        checked.outcome.contains("`test -f ready` failed"),
+        "{}",
+        checked.outcome
+    );
+    assert_ne!(checked.rolled_back, Some(true));
+}
+
+#[test]
+fn test_claimed_task_is_checked_off_on_the_run_branch() {
+    let (project, fixtures) = mock_project();
+    let root = project.path();
+    std::fs::write(
+        root.join("cycles.toml"),
+        r#"
+[global]
+permissions = []
+git_branch_template = "flow/{run_id}"
+git_on_finish = "fast_forward"
+
+[[cycle]]
+name = "coding"
+description = "Implement a task"
+prompt = "echo integration test"
+"#,
+    )
+    .unwrap();
+    std::fs::write(root.join("TODO.md"), "- [ ] Fix the bug\n").unwrap();
+    std::fs::write(root.join(".gitignore"), ".flow/\n").unwrap();
+    std::fs::write(
+        fixtures.join("responses.toml"),
+        concat!(
+            "[[response]]\nmatch = \"TODO.md State\"\n",
+            "result = '{\"cycle\": \"coding\", \"task\": \"Fix the bug\", \"reason\": \"open task\"}'\n",
+            "[[response]]\nmatch = \"integration test\"\ntranscript = \"coding.jsonl\"\n",
+        ),
+    )
+    .unwrap();
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    git(&["init", "-q", "-b", "main"]);
+    git(&["config", "user.email", "flow@example.com"]);
+    git(&["config", "user.name", "Flow"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args(["--max-iterations", "2"])
+        .current_dir(root)
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "flow failed: {stderr}");
+
+    // The run fast-forwarded main, which has the task checked off and never
+    // saw the claim marker
+    assert_eq!(git(&["rev-parse", "--abbrev-ref", "HEAD"]).trim(), "main");
+    assert_eq!(git(&["status", "--porcelain"]), "", "{stderr}");
+    assert_eq!(git(&["show", "HEAD:TODO.md"]), "- [x] Fix the bug\n");
+    assert!(!git(&["log", "-p"]).contains("flow:claimed"));
+}
+
+#[test]
+fn test_samples_keep_the_judged_pick_and_add_up_with_the_judge() {
+    let (project, fixtures) = mock_project();
+    let root = project.path();
+    std::fs::write(root.join(".gitignore"), ".flow/\nmock/\n").unwrap();
+    std::fs::write(
+        fixtures.join("responses.toml"),
+        concat!(
+            "[[response]]\nmatch = \"judging competing attempts\"\ntranscript = \"judge.jsonl\"\n",
+            "[[response]]\nmatch = \"integration test\"\ntranscript = \"coding.jsonl\"\n",
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        fixtures.join("judge.jsonl"),
+        concat!(
+            r#"{"type":"result","subtype":"success","is_error":false,"num_turns":1,"result":"{\"sample\": 2, \"reason\": \"tidier\"}","total_cost_usd":0.05,"duration_ms":10,"permission_denials":[]}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    for args in [
+        &["init", "-q", "-b", "main"][..],
+        &["config", "user.email", "flow@example.com"],
+        &["config", "user.name", "Flow"],
+        &["add", "."],
+        &["commit", "-q", "-m", "init"],
+    ] {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(root)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args(["--set", "cycle.coding.samples=2", "--cycle", "coding"])
+        .current_dir(root)
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "flow failed: {stderr}");
+    assert!(stderr.contains("Judge picked sample 2: tidier"), "{stderr}");
+
+    let entries = JsonlLogger::new(root.join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let coding = entries.iter().find(|e| e.cycle == "coding").unwrap();
+    assert_eq!(coding.chosen_sample, Some(2));
+    // Two samples at $0.25 each plus the judge's $0.05
+    let cost = coding.total_cost_usd.unwrap();
+    assert!((cost - 0.55).abs() < 1e-9, "{cost}");
+}
+
+#[test]
+fn test_doctor_diagnoses_config_with_overrides() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("cycles.toml"), TEST_CONFIG).unwrap();
+    let doctor = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_flow"))
+            .args(args)
+            .args(["doctor", "--format", "json"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    assert!(!doctor(&[]).contains("D010"));
+    let report = doctor(&["--set", r#"cycle.review.permissions=["Bash(*)"]"#]);
+    assert!(
+        report.contains("Cycle 'review' grants 'Bash(*)' without a sandbox"),
+        "{report}"
+    );
+}
+
+#[test]
+fn test_transient_api_errors_are_retried() {
+    let (project, fixtures) = mock_project();
+    std::fs::write(
+        fixtures.join("responses.toml"),
+        "[[response]]\nmatch = \"integration test\"\nresult = \"API Error: 529 Overloaded\"\nexit_code = 1\n",
+    )
+    .unwrap();
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args([
+            "--set",
+            "global.transient_retries=2",
+            "--set",
+            "global.transient_backoff_secs=0",
+            "--cycle",
+            "coding",
+        ])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("retry 2/2"), "{stderr}");
+
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    // The first attempt plus two retries are one entry in one iteration, so
+    // the auto-triggered gardening cycle runs as iteration 2
+    let iterations: Vec<_> = entries
+        .iter()
+        .map(|e| (e.cycle.as_str(), e.iteration))
+        .collect();
+    assert_eq!(iterations, [("coding", 1), ("gardening", 2)]);
+    assert_eq!(entries[0].failure_kind, Some(FailureKind::Transient));
+    assert_eq!(entries[0].attempts, Some(3));
+}
+
+#[test]
+fn test_failed_recovery_counts_as_one_consecutive_failure() {
+    let (project, fixtures) = mock_project();
+    let config = format!(
+        "{TEST_CONFIG}{}",
+        r#"
+[[cycle]]
+name = "broken"
+description = "Always fails"
+prompt = "echo broken"
+on_failure = "fixup"
+
+[[cycle]]
+name = "fixup"
+description = "Recovery that also fails"
+prompt = "echo fixup"
+"#
+    );
+    std::fs::write(project.path().join("cycles.toml"), config).unwrap();
+
+    // No fixture matches either prompt, so both cycles fail
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args([
+            "--set",
+            "global.max_consecutive_failures=2",
+            "--cycle",
+            "broken",
+            "--max-iterations",
+            "4",
+        ])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("2 consecutive cycle failures"), "{stderr}");
+
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let cycles: Vec<&str> = entries.iter().map(|e| e.cycle.as_str()).collect();
+    // The first failure + failed recovery count once, so the gate only trips
+    // after the second pair
+    assert_eq!(cycles, vec!["broken", "fixup", "broken", "fixup"]);
+}
+
+#[test]
+fn test_playlist_runs_every_cycle_each_iteration() {
+    let (project, fixtures) = mock_project();
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args([
+            "--set",
+            "global.max_consecutive_failures=10",
+            "--cycles",
+            "review,coding",
+            "--max-iterations",
+            "2",
+        ])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "flow failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let cycles: Vec<&str> = entries.iter().map(|e| e.cycle.as_str()).collect();
+    // Each iteration runs the whole list in order, plus what coding triggers
+    assert_eq!(
+        cycles,
+        vec![
+            "review",
+            "coding",
+            "gardening",
+            "review",
+            "coding",
+            "gardening"
+        ]
+    );
+}
+
+#[test]
+fn test_playlist_iteration_shares_one_queued_task() {
+    let (project, fixtures) = mock_project();
+    std::fs::write(
+        fixtures.join("responses.toml"),
+        "[[response]]\nmatch = \"integration test|echo review\"\ntranscript = \"coding.jsonl\"\n",
+    )
+    .unwrap();
+    let queue = project.path().join(".flow/queue");
+    std::fs::create_dir_all(&queue).unwrap();
+    for name in ["001-a.md", "002-b.md", "003-c.md"] {
+        std::fs::write(queue.join(name), format!("Task {name}\n")).unwrap();
+    }
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args([
+            "--set",
+            "global.max_consecutive_failures=10",
+            "--cycles",
+            "review,coding",
+            "--max-iterations",
+            "2",
+        ])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "flow failed: {stderr}");
+
+    // One task per pass of the list, handed to every cycle in it
+    assert!(queue.join("done/001-a.md").exists(), "{stderr}");
+    assert!(queue.join("done/002-b.md").exists(), "{stderr}");
+    assert!(queue.join("003-c.md").exists(), "{stderr}");
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let tasks: Vec<(&str, Option<&str>)> = entries
+        .iter()
+        .filter(|e| e.cycle != "gardening")
+        .map(|e| (e.cycle.as_str(), e.task.as_deref()))
+        .collect();
+    assert_eq!(
+        tasks,
+        [
+            ("review", Some("001-a.md")),
+            ("coding", Some("001-a.md")),
+            ("review", Some("002-b.md")),
+            ("coding", Some("002-b.md")),
+        ]
+    );
+}
+
+#[test]
+fn test_step_with_satisfied_skip_if_is_skipped() {
+    let (project, fixtures) = mock_project();
+    let config = format!(
+        "{TEST_CONFIG}{}",
+        r#"
+[[cycle]]
+name = "planned"
+description = "Plan, then implement"
+
+[[cycle.step]]
+name = "plan"
+prompt = "echo integration test plan"
+skip_if = { exists = "PLAN.md" }
+
+[[cycle.step]]
+name = "implement"
+prompt = "echo integration test implement"
+"#
+    );
+    std::fs::write(project.path().join("cycles.toml"), config).unwrap();
+    std::fs::write(project.path().join("PLAN.md"), "- [ ] Do it\n").unwrap();
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args(["--cycle", "planned"])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "flow failed: {stderr}");
+    assert!(stderr.contains("Skipping step 'plan'"), "{stderr}");
+
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let planned = entries.iter().find(|e| e.cycle == "planned").unwrap();
+    assert_eq!(planned.skipped_steps, Some(vec!["plan".to_string()]));
+    assert_eq!(planned.num_turns, Some(4), "only implement ran");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sandboxed_multi_step_cycle_runs_each_step_in_docker() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (project, fixtures) = mock_project();
+    let config = format!(
+        "{TEST_CONFIG}{}",
+        r#"
+[[cycle]]
+name = "boxed"
+description = "Plan, then implement, in a container"
+sandbox = "docker"
+sandbox_image = "flow-sandbox:test"
+
+[[cycle.step]]
+name = "plan"
+prompt = "echo integration test plan"
+
+[[cycle.step]]
+name = "implement"
+prompt = "echo integration test implement"
+"#
+    );
+    std::fs::write(project.path().join("cycles.toml"), config).unwrap();
+
+    // A stand-in `docker` that logs its arguments and runs the command after
+    // the image on the host
+    let bin = project.path().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let docker = bin.join("docker");
+    std::fs::write(
+        &docker,
+        "#!/bin/sh\necho \"$*\" >> \"$DOCKER_LOG\"\n\
+         while [ \"$1\" != flow-sandbox:test ]; do shift; done\nshift\nexec \"$@\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&docker, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let docker_log = project.path().join("docker.log");
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let output = Command::new(flow)
+        .args(["--cycle", "boxed"])
+        .current_dir(project.path())
+        .env("PATH", path)
+        .env("DOCKER_LOG", &docker_log)
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "flow failed: {stderr}");
+
+    let calls = std::fs::read_to_string(&docker_log).unwrap();
+    let calls: Vec<&str> = calls.lines().collect();
+    assert_eq!(calls.len(), 2, "one container per step: {calls:?}");
+    let mount = format!("-v {}:/workspace", project.path().display());
+    for call in &calls {
+        assert!(call.contains("--user "), "{call}");
+        assert!(call.contains(&mount), "{call}");
+    }
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let boxed = entries.iter().find(|e| e.cycle == "boxed").unwrap();
+    assert_eq!(boxed.num_turns, Some(8), "both steps ran");
+}
+
+#[test]
+fn test_step_with_repeat_until_reruns_until_condition_holds() {
+    let (project, fixtures) = mock_project();
+    let config = format!(
+        "{TEST_CONFIG}{}",
+        r#"
+[[cycle]]
+name = "fixing"
+description = "Fix until the check passes"
+
+[[cycle.step]]
+name = "fix"
+prompt = "echo integration test fix"
+max_visits = 5
+repeat_until = { command = "n=$(( $(cat attempts 2>/dev/null || echo 0) + 1 )); echo $n > attempts; [ $n -ge 3 ]" }
+"#
+    );
+    std::fs::write(project.path().join("cycles.toml"), config).unwrap();
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args(["--cycle", "fixing"])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "flow failed: {stderr}");
+    assert_eq!(
+        stderr.matches("Repeating step 'fix'").count(),
+        2,
+        "{stderr}"
+    );
+    let attempts = std::fs::read_to_string(project.path().join("attempts")).unwrap();
+    assert_eq!(attempts.trim(), "3", "fix ran until the third check passed");
+
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let fixing = entries.iter().find(|e| e.cycle == "fixing").unwrap();
+    let steps = fixing
+        .steps
+        .as_deref()
+        .expect("per-step outcomes are logged");
+    assert_eq!(steps.len(), 3, "one entry per run of the step");
+    for step in steps {
+        assert_eq!(step.name, "fix");
+        assert_eq!(step.exit_code, Some(0));
+        assert!(
+            step.num_turns.is_some() && step.cost_usd.is_some(),
+            "{step:?}"
+        );
+    }
+}
+
+#[test]
+fn test_queued_tasks_are_injected_and_archived() {
+    let (project, fixtures) = mock_project();
+    std::fs::write(
+        fixtures.join("responses.toml"),
+        concat!(
+            "[[response]]\nmatch = \"(?s)integration test.*Unfixable\"\nresult = \"Gave up\"\nexit_code = 1\n",
+            "[[response]]\nmatch = \"(?s)integration test.*Fix the login form\"\ntranscript = \"coding.jsonl\"\n",
+        ),
+    )
+    .unwrap();
+    let queue = project.path().join(".flow/queue");
+    std::fs::create_dir_all(&queue).unwrap();
+    std::fs::write(queue.join("001-login.md"), "Fix the login form\n").unwrap();
+    std::fs::write(queue.join("002-hard.md"), "Unfixable\n").unwrap();
+    std::fs::write(queue.join(".003-incoming.tmp"), "Still being written").unwrap();
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let output = Command::new(flow)
+        .args(["--cycle", "coding", "--max-iterations", "4"])
+        .current_dir(project.path())
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Queued task: 001-login.md"), "{stderr}");
+
+    assert!(queue.join("done/001-login.md").exists(), "{stderr}");
+    assert!(queue.join("failed/002-hard.md").exists(), "{stderr}");
+    assert!(queue.join(".003-incoming.tmp").exists());
+
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    let coding: Vec<_> = entries.iter().filter(|e| e.cycle == "coding").collect();
+    assert_eq!(coding.len(), 2);
+    assert_eq!(coding[0].task.as_deref(), Some("001-login.md"));
+    assert_eq!(coding[0].outcome, "Implemented the task");
+    assert_eq!(coding[1].task.as_deref(), Some("002-hard.md"));
+    let gardening = entries.iter().find(|e| e.cycle == "gardening").unwrap();
+    assert_eq!(gardening.task, None, "triggered cycles don't take the task");
+}
+
+#[test]
+fn test_explain_previews_invocation_without_running_it() {
+    let (project, _fixtures) = mock_project();
+    JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .append(&CycleOutcome {
+            cycle: "review".to_string(),
+            outcome: "Refactored the parser".to_string(),
+            ..completed_outcome(1, &flow::CycleResult::default())
+        })
+        .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_flow"))
+        .args(["--max-turns", "12", "explain", "coding"])
+        .current_dir(project.path())
+        .env("FLOW_CLAUDE_BIN", "/nonexistent/claude")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Refactored the parser"),
+        "log context: {stdout}"
+    );
+    assert!(
+        stdout.contains("Permissions: Read, Edit(./src/**), Edit(./tests/**), Bash(cargo test *)"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Budgets:     max_turns 12"), "{stdout}");
+    assert!(
+        stdout.contains("/nonexistent/claude -p '"),
+        "command line: {stdout}"
+    );
+    assert!(stdout.contains("--max-turns 12"), "{stdout}");
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    assert_eq!(entries.len(), 1, "nothing ran");
+}
+
+#[test]
+fn test_watch_progress_reports_live_run_and_detects_crash() {
+    let (project, _fixtures) = mock_project();
+    let log_dir = project.path().join(".flow");
+    let mut progress = flow::log::RunProgress::new(3);
+    progress.current_cycle = "coding".to_string();
+    flow::log::ProgressWriter::new(&log_dir)
+        .unwrap()
+        .write(&progress)
+        .unwrap();
+    // This test process stands in for the run holding the lock
+    let lock = flow::log::lock::RunLock::acquire(&log_dir, false).unwrap();
+
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let watch = || {
+        Command::new(flow)
+            .args(["watch-progress", "--once"])
+            .current_dir(project.path())
+            .output()
+            .unwrap()
+    };
+    let output = watch();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains("[1/3] coding"), "{stderr}");
+    assert!(stderr.contains("running"), "{stderr}");
+
+    drop(lock);
+    let output = watch();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("likely crashed"), "{stderr}");
+}
+
+/// Send one HTTP request to the API server and return the raw reply.
+fn api_request(addr: &str, method: &str, path: &str, body: &str) -> String {
+    use std::io::{Read as _, Write as _};
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nAuthorization: Bearer t0ken\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    reply
+}
+
+#[test]
+fn test_api_starts_a_run_and_streams_its_events() {
+    use std::io::{BufRead as _, BufReader};
+
+    let (project, fixtures) = mock_project();
+    let flow = env!("CARGO_BIN_EXE_flow");
+    let mut server = Command::new(flow)
+        .args(["serve", "--api", "--listen", "127.0.0.1:0"])
+        .current_dir(project.path())
+        .env("FLOW_API_TOKEN", "t0ken")
+        .env(
+            "FLOW_CLAUDE_BIN",
+            format!("{flow} mock-claude --fixtures {}", fixtures.display()),
+        )
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(server.stderr.take().unwrap());
+    let mut banner = String::new();
+    stderr.read_line(&mut banner).unwrap();
+    let addr = banner.trim().rsplit("http://").next().unwrap().to_string();
+
+    let events = {
+        let addr = addr.clone();
+        std::thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(&addr).unwrap();
+            std::io::Write::write_all(
+                &mut stream,
+                b"GET /events HTTP/1.1\r\nAuthorization: Bearer t0ken\r\n\r\n",
+            )
+            .unwrap();
+            BufReader::new(stream)
+                .lines()
+                .map_while(Result::ok)
+                .take_while(|line| !line.contains("\"run_finished\""))
+                .collect::<Vec<_>>()
+        })
+    };
+    // Let the subscriber connect before the run emits anything
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let unauthorized = {
+        use std::io::{Read as _, Write as _};
+        let mut stream = std::net::TcpStream::connect(&addr).unwrap();
+        stream.write_all(b"GET /status HTTP/1.1\r\n\r\n").unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        reply
+    };
+    assert!(unauthorized.starts_with("HTTP/1.1 401"), "{unauthorized}");
+
+    let started = api_request(&addr, "POST", "/run", r#"{"cycle":"coding"}"#);
+    assert!(started.starts_with("HTTP/1.1 202"), "{started}");
+
+    let lines = events.join().unwrap();
+    assert!(
+        lines.iter().any(|l| l.contains("\"cycle_completed\"")),
+        "{lines:?}"
+    );
+    let status = api_request(&addr, "GET", "/status", "");
+    assert!(status.starts_with("HTTP/1.1 200"), "{status}");
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+    let entries = JsonlLogger::new(project.path().join(".flow"))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    assert!(entries.iter().any(|e| e.cycle == "coding"));
+}